@@ -0,0 +1,153 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// first line of a cache manifest, a manifest written by another version is ignored.
+const CACHE_HEADER: &str = "oxide-build-cache 2";
+
+/// extension added to an output for its cache manifest.
+const CACHE_EXTENSION: &str = "cache";
+
+/// 64 bit fnv-1a, it is stable across builds of the compiler unlike the std hasher.
+fn hash(bytes: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// the hashes of everything an output is built from. An output is up to date when the cache
+/// manifest next to it records the same hashes, so touching a file does not rebuild it.
+#[derive(Debug, PartialEq)]
+pub struct BuildCache {
+    options: u64,
+    /// the source of each module by the name it was resolved to, so importing a module by
+    /// another spelling or moving its file does not rebuild the output.
+    modules: Vec<(u64, String)>,
+}
+
+impl BuildCache {
+    /// hashes the modules of an output, by name and source, along with the options that change
+    /// what is built.
+    pub fn new(options: &str, modules: &[(&str, &str)]) -> Self {
+        let mut modules = modules
+            .iter()
+            .map(|(name, source)| (hash(source.as_bytes()), name.to_string()))
+            .collect::<Vec<_>>();
+        modules.sort_by(|a, b| a.1.cmp(&b.1));
+        Self {
+            options: hash(options.as_bytes()),
+            modules,
+        }
+    }
+
+    /// the cache manifest of output.
+    pub fn path(output: &Path) -> PathBuf {
+        let mut name = output.file_name().unwrap_or_default().to_os_string();
+        name.push(".");
+        name.push(CACHE_EXTENSION);
+        output.with_file_name(name)
+    }
+
+    /// whether output exists and was built from the same sources and options. A cache
+    /// manifest that can not be read is treated as stale.
+    pub fn is_fresh(&self, output: &Path) -> bool {
+        output.is_file()
+            && std::fs::read_to_string(Self::path(output))
+                .ok()
+                .and_then(|text| Self::parse(text.as_str()))
+                .is_some_and(|cache| cache == *self)
+    }
+
+    /// records the cache manifest next to output once it is built.
+    pub fn store(&self, output: &Path) -> Result<(), io::Error> {
+        let mut text = format!("{}\noptions {:016x}\n", CACHE_HEADER, self.options);
+        for (hash, name) in &self.modules {
+            text.push_str(format!("module {:016x} {}\n", hash, name).as_str());
+        }
+        std::fs::write(Self::path(output), text)
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        let mut lines = text.lines();
+        if lines.next()? != CACHE_HEADER {
+            return None;
+        }
+        let options = lines.next()?.strip_prefix("options ")?;
+        let options = u64::from_str_radix(options, 16).ok()?;
+
+        let mut modules = vec![];
+        for line in lines {
+            let (hash, name) = line.strip_prefix("module ")?.split_once(' ')?;
+            modules.push((u64::from_str_radix(hash, 16).ok()?, name.to_owned()));
+        }
+        Some(Self { options, modules })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildCache;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_rebuild_only_when_something_changed() {
+        let root = std::env::temp_dir().join(format!("aubc-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let output = root.join("main.oxb");
+        let main = ("main", "use util.text\nfn main() i64 = twice(1)\n");
+        let text = ("util.text", "pub fn twice(x i64) i64 = x + x\n");
+        std::fs::write(&output, "module").unwrap();
+
+        let cache = BuildCache::new("mode=default", &[main, text]);
+        assert!(!cache.is_fresh(&output));
+        cache.store(&output).unwrap();
+        assert!(cache.is_fresh(&output));
+
+        // the same sources in another order are not a change.
+        assert!(BuildCache::new("mode=default", &[text, main]).is_fresh(&output));
+        assert!(!BuildCache::new("mode=script", &[main, text]).is_fresh(&output));
+
+        let changed = ("util.text", "pub fn twice(x i64) i64 = x * 2\n");
+        assert!(!BuildCache::new("mode=default", &[main, changed]).is_fresh(&output));
+        // a module is recorded by the name it resolved to, not by where it was found.
+        let renamed = ("text", text.1);
+        assert!(!BuildCache::new("mode=default", &[main, renamed]).is_fresh(&output));
+        let other = ("other", "");
+        assert!(!BuildCache::new("mode=default", &[main, text, other]).is_fresh(&output));
+
+        std::fs::remove_file(&output).unwrap();
+        assert!(!cache.is_fresh(&output));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_unreadable_cache_is_stale() {
+        let root = std::env::temp_dir().join(format!("aubc-corrupt-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let output = root.join("main.oxb");
+        std::fs::write(&output, "module").unwrap();
+        let cache = BuildCache::new("", &[]);
+        assert_eq!(BuildCache::path(&output), root.join("main.oxb.cache"));
+
+        let manifests = [
+            "oxide-build-cache 1\noptions cbf29ce484222325\n",
+            "oxide-build-cache 2\noptions nothex\n",
+            "oxide-build-cache 2\noptions cbf29ce484222325\nmodule 12\n",
+            "oxide-build-cache 2\noptions cbf29ce484222325\nfile 12 main.au\n",
+            "oxide-build-cache 2\n",
+            "\u{0}\u{1}garbage",
+        ];
+        for manifest in manifests.iter() {
+            std::fs::write(BuildCache::path(&output), manifest).unwrap();
+            assert!(!cache.is_fresh(&output), "{:?}", manifest);
+        }
+        std::fs::write(BuildCache::path(&output), [0xff, 0xfe, 0x00]).unwrap();
+        assert!(!cache.is_fresh(&output));
+
+        cache.store(&output).unwrap();
+        assert!(cache.is_fresh(&output));
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(!cache.is_fresh(&PathBuf::from("missing.oxb")));
+    }
+}
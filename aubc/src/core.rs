@@ -1,21 +1,40 @@
 extern crate clap;
 
-use std::path::Path;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use auburn::{
-    analysis::Analysis,
-    code_gen::{BuildError, CodeGen},
-    error::Error,
+    analysis::{Analysis, BuildInfo, Check, Checks, ParsedModule, LANGUAGE_VERSION},
+    code_gen::{emit_c, module_api, BuildError, CodeGen},
+    error::{Error, Renderer},
     ir::hir::HirFile,
-    oxide::{gc::Gc, OxModule, Vm},
-    syntax::{ParsedFile, Parser, Position},
-    system::{File, FileMap},
-    utils::MirPrinter,
+    oxide::{
+        compiled::{LoadWarning, ModuleApi, ModuleMetadata, MODULE_FILE_VERSION},
+        disassemble,
+        gc::Gc,
+        source_map::SourceMap,
+        vm::{
+            BenchConfig, Capabilities, Capability, CapabilityKind, ErrorCaptureLevel,
+            IoErrorPolicy,
+        },
+        CompiledModule, CoverageReport, OxModule, Value, Vm,
+    },
+    passes::{ConstantFolding, DeadCode, Limits, Lint, Metrics, MirPass, PassContext},
+    syntax::{ParsedFile, Parser, BENCH_PREFIX, DEFAULT_MAX_EXPR_DEPTH},
+    system::{discover_sources, module_name, resolve_imports, File, FileMap, FileSystemResolver},
+    utils::{source_text, timing, Annotations, Diagnostics, EntityDump, MirPrinter, Skipped},
     Executor, LanguageMode,
 };
 use clap::Clap;
 
+use crate::cache::BuildCache;
+use crate::manifest::{
+    parse_opt_level, Manifest, ManifestError, Provenance, WarningPolicy, MANIFEST_FILE_NAME,
+};
+
 #[derive(Clap, Debug)]
 enum Command {
     #[clap()]
@@ -25,12 +44,89 @@ enum Command {
     Parse { input: String },
 
     #[clap()]
-    Run { input: String },
+    Run {
+        /// a source file, or a module file written by compile. The entry of the manifest
+        /// when it is not given
+        input: Option<String>,
+        /// external source map used for stack traces
+        #[clap(long)]
+        sourcemap: Option<String>,
+        /// report the executed lines and write an lcov file next to the input
+        #[clap(long)]
+        coverage: bool,
+        /// measure the `bench` items of the program instead of running main
+        #[clap(long)]
+        bench: bool,
+        /// only measure the benchmarks whose name contains the filter
+        #[clap(long)]
+        bench_filter: Option<String>,
+        /// write the measures of the benchmarks as json
+        #[clap(long)]
+        bench_json: Option<String>,
+    },
+
+    #[clap()]
+    Build {
+        /// a source file, the entry of the manifest when it is not given
+        input: Option<String>,
+        /// additional output to write next to the input: sourcemap, c for the program
+        /// translated to a C file instead of a module, or entities and entities-json for every
+        /// entity the typer built, written even when some items do not check. bytecode prints
+        /// the instructions of every function instead, the input can be a module file then
+        #[clap(long)]
+        emit: Option<String>,
+    },
+
+    /// compiles a file, or every file of a directory as one program, to a module file
+    #[clap()]
+    Compile {
+        /// a source file or directory, - reads the source from stdin
+        input: String,
+        /// the module file to write, the input with the extension oxb by default, stdin.oxb
+        /// when reading stdin
+        #[clap(short, long)]
+        output: Option<String>,
+        /// compile even when the module file is up to date
+        #[clap(long)]
+        force: bool,
+    },
 
+    /// lists the changes to the public items between two module files, it fails when one of
+    /// them breaks code using the old module
     #[clap()]
-    Build { input: String },
+    CheckApi { old: String, new: String },
+
+    /// writes the types the checker inferred into a source file, for the `let` bindings and
+    /// the functions returning an expression that leave them out
+    #[clap()]
+    Annotate {
+        /// a source file, - reads the source from stdin and prints it annotated
+        input: String,
+        /// print the annotations as suggestions instead of writing them
+        #[clap(long)]
+        check: bool,
+    },
+
+    /// rewrites a module file of a previous version of the format as the current version
+    #[clap()]
+    RecompileBytecode {
+        input: String,
+        /// the module file to write
+        #[clap(short, long)]
+        output: String,
+    },
 }
 
+/// a built module along with its debug info and declarations.
+type Built = (Gc<OxModule>, SourceMap, ModuleApi);
+
+/// extension of a module file written by compile.
+const MODULE_FILE_EXTENSION: &str = "oxb";
+
+/// the input naming stdin, and the name its source is reported by.
+const STDIN_INPUT: &str = "-";
+const STDIN_NAME: &str = "stdin";
+
 #[derive(Clap, Debug)]
 #[clap(version = "0.1.0", author = "Andrew Bregger")]
 struct Arguments {
@@ -38,16 +134,280 @@ struct Arguments {
     command: Option<Command>,
     #[clap(short, long)]
     mode: Option<LanguageMode>,
+    /// maximum nesting depth of expressions
+    #[clap(long)]
+    max_expr_depth: Option<usize>,
+    /// debug output to print: desugar
+    #[clap(long)]
+    trace: Option<String>,
+    /// conditional compilation flags, they replace the flags of the manifest
+    #[clap(long)]
+    cfg: Vec<String>,
+    /// what is done with the warnings of a program: allow, warn or deny
+    #[clap(long)]
+    warnings: Option<WarningPolicy>,
+    /// optimization level, 0 to 3
+    #[clap(long, parse(try_from_str = parse_opt_level))]
+    opt_level: Option<u8>,
+    /// fold constants and remove the code that is never run: true or false, on from
+    /// optimization level 1
+    #[clap(long)]
+    fold: Option<bool>,
+    /// directories searched for imported modules, they replace the paths of the manifest
+    #[clap(long = "search-path")]
+    search_paths: Vec<String>,
+    /// make every check, deny warnings, trap on integer overflow and check unreachable() at
+    /// every optimization level
+    #[clap(long)]
+    strict: bool,
+    /// a check to make: annotated-signatures, no-shadowing or used-results
+    #[clap(long = "check")]
+    checks: Vec<Check>,
+    /// a limit on every function, its measure is a warning when it is over: max-statements,
+    /// max-depth, max-params or max-complexity, like max-depth=4. They replace the lints of
+    /// the manifest
+    #[clap(long = "lint")]
+    lints: Vec<Lint>,
+    /// print the measures of every function
+    #[clap(long)]
+    metrics_report: bool,
+    /// print the settings of the build and where each came from
+    #[clap(short, long)]
+    verbose: bool,
+    /// print the time spent in each phase of the compiler
+    #[clap(long)]
+    time_passes: bool,
+    /// write the time spent in each phase of the compiler as json
+    #[clap(long)]
+    time_passes_json: Option<String>,
+    /// a capability taken from scripts: fs.read, fs.write, env, clock, rng, ffi or spawn. They
+    /// are all granted otherwise, net and eval are never granted
+    #[clap(long = "deny", number_of_values = 1)]
+    denied: Vec<CapabilityKind>,
+    /// print the name of a module file and the compiler and options that built it
+    #[clap(long)]
+    module_info: Option<String>,
+}
+
+/// what the scripts run from the command line may do, everything but the network and `eval`.
+/// An embedding host grants its own, a vm is granted nothing.
+fn default_capabilities(denied: &[CapabilityKind]) -> Capabilities {
+    let mut capabilities = Capabilities::none();
+    for capability in [
+        Capability::FsRead(PathBuf::from("/")),
+        Capability::FsWrite(PathBuf::from("/")),
+        Capability::Env,
+        Capability::Clock,
+        Capability::Rng,
+        Capability::Ffi,
+        Capability::Spawn,
+    ] {
+        capabilities.grant(capability);
+    }
+    for kind in denied {
+        capabilities.deny(*kind);
+    }
+    capabilities
 }
 
 pub struct Options {
     mode: LanguageMode,
+    max_expr_depth: usize,
+    /// the file build and run start at when they are not given one, set by the manifest.
+    entry: Option<PathBuf>,
+    search_paths: Vec<PathBuf>,
+    warnings: WarningPolicy,
+    checks: Checks,
+    limits: Limits,
+    metrics_report: bool,
+    /// whether integer overflow is a runtime error.
+    overflow_traps: bool,
+    /// whether `unreachable()` is a runtime error, above level 0 the program is built trusting
+    /// it is never reached unless the build is strict.
+    checked_unreachable: bool,
+    /// the contracts of functions are only checked at runtime at level 0.
+    opt_level: u8,
+    /// whether constants are folded and code never run removed before the program is built.
+    fold: bool,
+    provenance: Provenance,
+}
+
+impl Options {
+    /// the options that change what is built, a module file is rebuilt when they change.
+    fn fingerprint(&self) -> String {
+        format!(
+            "version={} format={} mode={} max_expr_depth={} search_paths={:?} checks={:?} \
+             opt_level={} fold={} checked_unreachable={}",
+            env!("CARGO_PKG_VERSION"),
+            MODULE_FILE_VERSION,
+            self.mode,
+            self.max_expr_depth,
+            self.search_paths,
+            self.checks,
+            self.opt_level,
+            self.fold,
+            self.checked_unreachable
+        )
+    }
+
+    /// the compiler and the options a module is built with, recorded in its module file.
+    fn metadata(&self) -> ModuleMetadata {
+        let overflow = if self.overflow_traps { "trap" } else { "wrap" };
+        let unreachable = if self.checked_unreachable {
+            "checked"
+        } else {
+            "trusted"
+        };
+        ModuleMetadata {
+            compiler_version: LANGUAGE_VERSION.to_string(),
+            options: vec![
+                ("mode".to_string(), self.mode.to_string()),
+                ("opt_level".to_string(), self.opt_level.to_string()),
+                ("fold".to_string(), self.fold.to_string()),
+                ("overflow".to_string(), overflow.to_string()),
+                ("unreachable".to_string(), unreachable.to_string()),
+                ("checks".to_string(), describe_list(&self.checks.enabled())),
+            ],
+        }
+    }
+}
+
+fn non_empty<T: Clone>(values: &[T]) -> Option<Vec<T>> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.to_vec())
+    }
+}
+
+fn describe_list<T: Display>(values: &[T]) -> String {
+    let values = values.iter().map(ToString::to_string).collect::<Vec<_>>();
+    format!("[{}]", values.join(", "))
 }
 
 impl Arguments {
-    pub fn build_options(&self) -> Options {
+    /// the settings of a build, a command line flag overrides the manifest.
+    pub fn build_options(&self, manifest: Option<&Manifest>) -> Options {
+        let mut provenance = Provenance::new(manifest);
+        let entry = provenance.resolve(
+            "entry",
+            None,
+            manifest.and_then(|m| m.setting("entry", |m| m.entry.clone().map(Some))),
+            None,
+            |entry| match entry {
+                Some(entry) => entry.display().to_string(),
+                None => "none".to_string(),
+            },
+        );
+        let search_paths = provenance.resolve(
+            "search_paths",
+            non_empty(&self.search_paths).map(|paths| paths.iter().map(PathBuf::from).collect()),
+            manifest.and_then(|m| m.setting("search_paths", |m| Some(m.search_paths.clone()))),
+            vec![],
+            |paths| describe_list(&paths.iter().map(|p| p.display()).collect::<Vec<_>>()),
+        );
+        // no part of a program is conditionally compiled, constant folding is the only pass the
+        // level turns on. The flags and the level are checked and reported.
+        provenance.resolve(
+            "cfg",
+            non_empty(&self.cfg),
+            manifest.and_then(|m| m.setting("cfg", |m| Some(m.cfg.clone()))),
+            vec![],
+            |flags| describe_list(flags),
+        );
+        // strict mode is a preset, a flag given along with it still wins.
+        let strict_warnings = if self.strict {
+            Some(WarningPolicy::Deny)
+        } else {
+            None
+        };
+        let warnings = provenance.resolve(
+            "warnings",
+            self.warnings.or(strict_warnings),
+            manifest.and_then(|m| m.setting("warnings", |m| m.warnings)),
+            WarningPolicy::default(),
+            ToString::to_string,
+        );
+        let opt_level = provenance.resolve(
+            "opt_level",
+            self.opt_level,
+            manifest.and_then(|m| m.setting("opt_level", |m| m.opt_level)),
+            0,
+            ToString::to_string,
+        );
+        let mode = provenance.resolve(
+            "mode",
+            self.mode,
+            manifest.and_then(|m| m.setting("mode", |m| m.mode)),
+            LanguageMode::default(),
+            ToString::to_string,
+        );
+        let max_expr_depth = provenance.resolve(
+            "max_expr_depth",
+            self.max_expr_depth,
+            None,
+            DEFAULT_MAX_EXPR_DEPTH,
+            ToString::to_string,
+        );
+        let preset = if self.strict {
+            Checks::strict()
+        } else {
+            Checks::default()
+        };
+        let checks = provenance.resolve(
+            "checks",
+            if self.strict || !self.checks.is_empty() {
+                Some(
+                    self.checks
+                        .iter()
+                        .fold(preset, |checks, check| checks.with(*check)),
+                )
+            } else {
+                None
+            },
+            None,
+            Checks::default(),
+            |checks| describe_list(&checks.enabled()),
+        );
+        let lints = provenance.resolve(
+            "lints",
+            non_empty(&self.lints),
+            manifest.and_then(|m| m.setting("lints", |m| Some(m.lints.clone()))),
+            vec![],
+            |lints| describe_list(lints),
+        );
+        let fold = provenance.resolve("fold", self.fold, None, opt_level > 0, ToString::to_string);
+        let overflow_traps = provenance.resolve(
+            "overflow",
+            if self.strict { Some(true) } else { None },
+            None,
+            false,
+            |traps| if *traps { "trap" } else { "wrap" }.to_string(),
+        );
+        let checked_unreachable = provenance.resolve(
+            "unreachable",
+            if self.strict { Some(true) } else { None },
+            None,
+            opt_level == 0,
+            |checked| if *checked { "checked" } else { "trusted" }.to_string(),
+        );
+
         Options {
-            mode: self.mode.unwrap_or_default(),
+            mode,
+            max_expr_depth,
+            entry,
+            search_paths,
+            warnings,
+            checks,
+            limits: lints
+                .into_iter()
+                .fold(Limits::default(), |limits, lint| limits.with(lint)),
+            metrics_report: self.metrics_report,
+            overflow_traps,
+            checked_unreachable,
+            opt_level,
+            fold,
+            provenance,
         }
     }
 }
@@ -56,8 +416,15 @@ impl Arguments {
 enum CoreError {
     IoError(std::io::Error, String),
     // CommandError(CommandError),
+    InvalidArgument(String),
     CompilerError(Error),
+    /// every construct the C backend could not translate.
+    CompilerErrors(Vec<Error>),
     BuildError(BuildError),
+    RuntimeError(auburn::oxide::Error),
+    ManifestError(ManifestError),
+    /// the number of breaking changes found by check-api.
+    BreakingChanges(usize),
 }
 
 impl From<Error> for CoreError {
@@ -76,6 +443,7 @@ pub struct Core {
     file_map: FileMap,
     vm: Vm,
     analysis: Analysis,
+    max_expr_depth: usize,
 }
 
 impl Core {
@@ -84,15 +452,18 @@ impl Core {
             file_map: FileMap::new(),
             vm: Vm::new(),
             analysis: Analysis::new(),
+            max_expr_depth: DEFAULT_MAX_EXPR_DEPTH,
         }
     }
 
-    pub fn run(&mut self) {
+    /// runs the command line, the exit code is 1 when it fails.
+    pub fn run(&mut self) -> i32 {
         let cmd = Arguments::parse();
         match self.execute(cmd) {
-            Ok(()) => {}
+            Ok(()) => 0,
             Err(e) => {
                 self.print_error(&e);
+                1
             }
         }
     }
@@ -100,67 +471,44 @@ impl Core {
     fn print_error(&self, err: &CoreError) {
         match err {
             CoreError::IoError(err, file_name) => self.print_io_error(err, file_name),
+            CoreError::InvalidArgument(msg) => println!("{}", msg),
             CoreError::CompilerError(err) => self.print_compiler_error(err),
+            CoreError::CompilerErrors(errs) => {
+                for err in errs {
+                    self.print_compiler_error(err);
+                }
+            }
             CoreError::BuildError(err) => self.print_build_error(err),
+            CoreError::RuntimeError(err) => println!("{}", err),
+            CoreError::ManifestError(err) => println!("{}", err),
+            CoreError::BreakingChanges(1) => println!("1 breaking change"),
+            CoreError::BreakingChanges(count) => println!("{} breaking changes", count),
         }
     }
 
-    fn print_io_error(&self, err: &std::io::Error, file_name: &str) {
-        println!("{}: {}", file_name, err)
+    fn print_warning(&self, warning: &Error) {
+        match self.file_map.find(&warning.pos().file_id()) {
+            Some(file) => println!("warning: {}: {}", file.path().display(), warning),
+            None => println!("warning: {}", warning),
+        }
     }
 
-    fn print_compiler_error(&self, err: &Error) {
-        let pos = err.pos();
-        println!("Error Pos: {}", pos);
-        if let Some(file) = self.file_map.file_by_id(&pos.file_id()) {
-            let start_coord = pos.start();
-
-            println!(
-                "{}:{}:{}| {}",
-                file.path().display(),
-                start_coord.line(),
-                start_coord.column(),
-                err
-            );
-
-            if start_coord.line() == 0 && start_coord.column() == 0
-                || start_coord.line() != pos.end().line()
-            {
-                return;
-            }
-
-            let line = file.get_line(start_coord.line());
-            self.print_file_lines(line, pos);
-        // } else {
-        //     // let line = *file.get_lines(start_coord.0, end_coord.0).first().unwrap();
-        //     // println!("Mutli-line error are not supported");
-        //
-        // }
-        } else {
-            println!("Unable to find file of id: '{}'", pos.file_id().0);
+    fn print_skipped(&self, skipped: &Skipped) {
+        let start = skipped.position.start();
+        if let Some(file) = self.file_map.find(&skipped.position.file_id()) {
+            print!("{}:{}:{}: ", file.path().display(), start.line(), start.column());
         }
+        println!("note: '{}' is not annotated, {}", skipped.name, skipped.reason);
     }
 
-    fn print_file_lines(&self, line: &str, pos: &Position) {
-        println!(">\t{}", line);
-        Self::print_line_cursor(line, pos.start().column(), pos.end().column());
+    fn print_io_error(&self, err: &std::io::Error, file_name: &str) {
+        println!("{}: {}", file_name, err)
     }
 
-    fn print_line_cursor(line: &str, start_column: usize, end_column: usize) {
-        let offset = (0..(start_column - 1))
-            .map(|idx| match line.chars().nth(idx) {
-                Some('\t') => '\t',
-                Some(_) => ' ',
-                None => panic!("{}|{},{}", line, idx, start_column),
-            })
-            .collect::<String>();
-
-        if !start_column <= end_column {
-            return;
-        }
-        let cursor = String::from_utf8(vec![b'^'; end_column - start_column])
-            .expect("cursor string is not valid utf8??");
-        println!(" \t{}{}", offset, cursor);
+    /// the error with its source, colored when it is shown in a terminal.
+    fn print_compiler_error(&self, err: &Error) {
+        let renderer = Renderer::new().with_color(std::io::stdout().is_terminal());
+        print!("{}", renderer.render(err, |id| source_text(&self.file_map, id)));
     }
 
     fn print_build_error(&self, err: &BuildError) {
@@ -168,11 +516,62 @@ impl Core {
     }
 
     fn execute(&mut self, arg: Arguments) -> Result<(), CoreError> {
-        let options = arg.build_options();
-        match arg.command {
+        if let Some(path) = arg.module_info.as_deref() {
+            print!("{}", Self::module_info(path)?);
+            return Ok(());
+        }
+        let manifest = match &arg.command {
+            Some(Command::Build { input: None, .. }) | Some(Command::Run { input: None, .. }) => {
+                Some(Self::load_manifest()?)
+            }
+            _ => None,
+        };
+        let options = arg.build_options(manifest.as_ref());
+        if arg.verbose {
+            print!("{}", options.provenance);
+        }
+        self.max_expr_depth = options.max_expr_depth;
+        self.analysis.set_max_expr_depth(options.max_expr_depth);
+        self.analysis.set_checks(options.checks);
+        self.analysis.set_contracts(options.opt_level == 0);
+        // every build writes the source map of the program.
+        self.analysis.set_build_info(BuildInfo {
+            opt_level: options.opt_level,
+            debug_info: true,
+            checked_unreachable: options.checked_unreachable,
+        });
+        self.vm.set_overflow_traps(options.overflow_traps);
+        // output piped to a reader that stops early ends the script quietly.
+        self.vm.set_io_error_policy(IoErrorPolicy::Exit);
+        self.vm.set_capabilities(default_capabilities(&arg.denied));
+        match arg.trace.as_deref() {
+            Some("desugar") => self.analysis.set_trace_desugar(true),
+            Some(other) => {
+                return Err(CoreError::InvalidArgument(format!(
+                    "unknown trace kind '{}'",
+                    other
+                )))
+            }
+            None => {}
+        }
+        if arg.time_passes || arg.time_passes_json.is_some() {
+            timing::start_timing();
+        }
+        let result = match arg.command {
             Some(cmd) => self.execute_command(cmd, options),
             None => self.execute_repl(options),
+        };
+
+        if let Some(report) = timing::finish_timing() {
+            if arg.time_passes {
+                print!("{}", report.table());
+            }
+            if let Some(path) = arg.time_passes_json {
+                std::fs::write(&path, report.to_json())
+                    .map_err(|err| CoreError::IoError(err, path.clone()))?;
+            }
         }
+        result
     }
 
     fn execute_command(&mut self, cmd: Command, options: Options) -> Result<(), CoreError> {
@@ -193,63 +592,659 @@ impl Core {
                 let parsed_file = self
                     .parse_file(file.as_ref())
                     .map_err(|err| CoreError::from(err))?;
-                let resolved_file = self
-                    .resolve_root(parsed_file, options.mode)
-                    .map_err(|err| CoreError::from(err))?;
+                let mut resolved_file = self
+                    .analysis
+                    .check_all(parsed_file, options.mode)
+                    .map_err(CoreError::CompilerErrors)?;
+                let warnings = self.run_passes(&mut resolved_file, &options)?;
+                self.report_warnings(warnings, &options)?;
 
                 MirPrinter::print_file(&resolved_file);
             }
-            Command::Run { input } => {
-                let file = self
-                    .open_file(input.as_str())
-                    .map_err(|err| CoreError::IoError(err, input))?;
-
-                let ox_module = self.build(file, options)?;
+            Command::Run {
+                input,
+                sourcemap,
+                coverage,
+                bench,
+                bench_filter,
+                bench_json,
+            } => {
+                let target = Self::target(input.as_deref(), &options);
+                let (ox_module, built_map, _) = match input.as_deref() {
+                    Some(input) if Self::is_module_file(input) => (
+                        self.load_module_file(input)?,
+                        SourceMap::new(),
+                        ModuleApi::default(),
+                    ),
+                    Some(input) => {
+                        let file = self.open(input)?;
+                        self.build(file, &options)?
+                    }
+                    None => self.build_entry(&options)?,
+                };
+                if bench {
+                    return self.run_benches(ox_module, bench_filter.as_deref(), bench_json);
+                }
                 // ox_module.disassemble();
                 println!("Running module");
-                match self.vm.run_module(ox_module) {
+                self.vm
+                    .set_error_capture(ErrorCaptureLevel::FramesAndLocals);
+                self.vm.set_coverage(coverage);
+                let result = match sourcemap {
+                    Some(path) => {
+                        let source_map = Self::read_source_map(path.as_str())?;
+                        self.vm.load_with_sourcemap(ox_module, source_map)
+                    }
+                    None => self.vm.run_module(ox_module),
+                };
+
+                match result {
                     Ok(_) => {
                         self.vm.print_stack();
                         self.vm.dump_mem_stats();
                     }
-                    Err(err) => println!("{}", err),
+                    Err(auburn::oxide::Error::ExitedDueToIo(_)) => return Ok(()),
+                    Err(err) => {
+                        println!("{}", err);
+                        let snapshot = self.vm.on_error_snapshot();
+                        for (idx, frame) in self.vm.stack_trace().iter().enumerate() {
+                            println!("\t{}", frame);
+                            if let Some(frame) = snapshot.and_then(|s| s.frames.get(idx)) {
+                                for (name, value) in &frame.locals {
+                                    println!("\t\t{} = {}", name, value);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                if let Some(coverage) = self.vm.take_coverage() {
+                    let report = CoverageReport::new(&coverage, &built_map);
+                    print!("{}", report.summary());
+                    let path = target.with_extension("lcov");
+                    std::fs::write(&path, report.to_lcov())
+                        .map_err(|err| CoreError::IoError(err, path.display().to_string()))?;
+                }
+            }
+
+            Command::Build { input, emit } if emit.as_deref() == Some("c") => {
+                let target = Self::target(input.as_deref(), &options);
+                let hir_file = match input.as_deref() {
+                    Some(input) => {
+                        let file = self.open(input)?;
+                        self.check(file, &options)?
+                    }
+                    None => self.check_entry(&options)?,
+                };
+                let source = emit_c(&hir_file).map_err(CoreError::CompilerErrors)?;
+                let path = target.with_extension("c");
+                std::fs::write(&path, source)
+                    .map_err(|err| CoreError::IoError(err, path.display().to_string()))?;
+            }
+
+            Command::Build { input, emit }
+                if matches!(emit.as_deref(), Some("entities" | "entities-json")) =>
+            {
+                let target = Self::target(input.as_deref(), &options);
+                let modules = match input.as_deref() {
+                    Some(input) => {
+                        let file = self.open(input)?;
+                        let parsed_file = self.parse_file(file.as_ref())?;
+                        let path = PathBuf::from(file.name());
+                        vec![ParsedModule {
+                            name: module_name(&path),
+                            path,
+                            file: parsed_file,
+                            imports: HashMap::new(),
+                        }]
+                    }
+                    None => self.parse_modules(self.entry_sources(&options)?)?,
+                };
+                let name = target
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let partial =
+                    self.analysis
+                        .check_program_partial(name.as_str(), modules, options.mode)?;
+
+                let dump = EntityDump::of_partial(&partial, &self.file_map);
+                let (path, dump) = match emit.as_deref() {
+                    Some("entities") => (target.with_extension("entities"), dump.to_text()),
+                    _ => (target.with_extension("entities.json"), dump.to_json()),
+                };
+                std::fs::write(&path, dump)
+                    .map_err(|err| CoreError::IoError(err, path.display().to_string()))?;
+                if !partial.is_complete() {
+                    return Err(CoreError::CompilerErrors(partial.diagnostics));
                 }
             }
 
-            Command::Build { input } => {
+            Command::Build { input, emit } => {
+                let target = Self::target(input.as_deref(), &options);
+                let (module, source_map, _) = match input.as_deref() {
+                    Some(input) if Self::is_module_file(input) => (
+                        self.load_module_file(input)?,
+                        SourceMap::new(),
+                        ModuleApi::default(),
+                    ),
+                    Some(input) => {
+                        let file = self.open(input)?;
+                        self.build(file, &options)?
+                    }
+                    None => self.build_entry(&options)?,
+                };
+
+                match emit.as_deref() {
+                    Some("bytecode") => print!("{}", disassemble(module.as_ref())),
+                    Some("sourcemap") => {
+                        let path = target.with_extension("map.json");
+                        std::fs::write(&path, source_map.to_json())
+                            .map_err(|err| CoreError::IoError(err, path.display().to_string()))?;
+                    }
+                    Some(other) => {
+                        return Err(CoreError::InvalidArgument(format!(
+                            "unknown emit kind '{}'",
+                            other
+                        )))
+                    }
+                    None => {}
+                }
+            }
+
+            Command::Compile {
+                input,
+                output,
+                force,
+            } => {
+                let output = match output {
+                    Some(output) => PathBuf::from(output),
+                    None if input == STDIN_INPUT => {
+                        PathBuf::from(STDIN_NAME).with_extension(MODULE_FILE_EXTENSION)
+                    }
+                    None => {
+                        // `.` names the directory it is, not a file to add an extension to.
+                        let input = Path::new(input.as_str());
+                        let input = match input.file_name() {
+                            Some(_) => input.to_path_buf(),
+                            None => input.canonicalize().map_err(|err| {
+                                CoreError::IoError(err, input.display().to_string())
+                            })?,
+                        };
+                        input.with_extension(MODULE_FILE_EXTENSION)
+                    }
+                };
+                let name = output
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                let sources = if Path::new(input.as_str()).is_dir() {
+                    let mut roots = vec![PathBuf::from(input.as_str())];
+                    roots.extend(options.search_paths.iter().cloned());
+                    Some(Self::discover(&roots)?)
+                } else {
+                    None
+                };
+                let modules = match sources {
+                    Some(sources) => self.parse_modules(sources)?,
+                    None => {
+                        let file = self.open(input.as_str())?;
+                        self.load(file, &options)?
+                    }
+                };
+                // source read from stdin is always compiled.
+                let cache = if input == STDIN_INPUT {
+                    None
+                } else {
+                    let sources = modules
+                        .iter()
+                        .map(|module| {
+                            let file = self.file_map.find(&module.file.file_id).unwrap();
+                            (module.name.as_str(), file.content())
+                        })
+                        .collect::<Vec<_>>();
+                    let cache = BuildCache::new(options.fingerprint().as_str(), &sources);
+                    if !force && cache.is_fresh(&output) {
+                        println!("{} is up to date", output.display());
+                        return Ok(());
+                    }
+                    Some(cache)
+                };
+
+                let (module, _, api) = if Path::new(input.as_str()).is_dir() {
+                    let hir_file = self.check_modules(modules, name.as_str(), &options)?;
+                    self.generate(&hir_file)?
+                } else {
+                    let hir_file = self.check_loaded(modules, &options)?;
+                    self.generate(&hir_file)?
+                };
+                let compiled = CompiledModule::from_module(module)
+                    .map_err(CoreError::RuntimeError)?
+                    .with_api(api)
+                    .with_metadata(options.metadata());
+                compiled
+                    .save(&output)
+                    .map_err(|err| CoreError::IoError(err, output.display().to_string()))?;
+                if let Some(cache) = cache {
+                    let cache_path = BuildCache::path(&output);
+                    cache
+                        .store(&output)
+                        .map_err(|err| CoreError::IoError(err, cache_path.display().to_string()))?;
+                }
+                println!("wrote {}", output.display());
+            }
+
+            Command::CheckApi { old, new } => {
+                let diff = Self::read_api(old.as_str())?.diff(&Self::read_api(new.as_str())?);
+                print!("{}", diff);
+                let breaking = diff.breaking().count();
+                if breaking > 0 {
+                    return Err(CoreError::BreakingChanges(breaking));
+                }
+            }
+            Command::Annotate { input, check } => {
                 let file = self.open(input.as_str())?;
-                let module = self.build(file, options)?;
-                module.disassemble(0);
+                let modules = self.load(file.clone(), &options)?;
+                let parsed_file = modules[0].file.clone();
+                let hir_file = self.check_loaded(modules, &options)?;
+                let annotations = Annotations::of(&parsed_file, &hir_file);
+                for skipped in annotations.skipped() {
+                    self.print_skipped(skipped);
+                }
+                if check {
+                    print!("{}", annotations.to_suggestions(input.as_str()));
+                } else if input == STDIN_INPUT {
+                    print!("{}", annotations.apply(file.content()));
+                } else if !annotations.insertions().is_empty() {
+                    std::fs::write(&input, annotations.apply(file.content()))
+                        .map_err(|err| CoreError::IoError(err, input.clone()))?;
+                    let count = annotations.insertions().len();
+                    println!("wrote {} annotations to {}", count, input);
+                }
+            }
+            Command::RecompileBytecode { input, output } => {
+                let bytes = std::fs::read(&input)
+                    .map_err(|err| CoreError::IoError(err, input.clone()))?;
+                let compiled = CompiledModule::from_bytes_with(&bytes, &mut |warning| {
+                    Self::print_load_warning(&input, &warning)
+                })
+                .map_err(CoreError::RuntimeError)?;
+                compiled
+                    .save(Path::new(output.as_str()))
+                    .map_err(|err| CoreError::IoError(err, output.clone()))?;
             }
         }
         Ok(())
     }
 
+    fn print_load_warning(path: &str, warning: &LoadWarning) {
+        println!("warning: {}: {}", path, warning);
+    }
+
+    /// the declarations recorded in a module file by compile.
+    fn read_api(path: &str) -> Result<ModuleApi, CoreError> {
+        let bytes = std::fs::read(path).map_err(|err| CoreError::IoError(err, path.to_owned()))?;
+        let compiled = CompiledModule::from_bytes_with(&bytes, &mut |warning| {
+            Self::print_load_warning(path, &warning)
+        })
+        .map_err(CoreError::RuntimeError)?;
+        compiled.api().cloned().ok_or_else(|| {
+            CoreError::InvalidArgument(format!(
+                "{}: the module file does not record its api, compile it again",
+                path
+            ))
+        })
+    }
+
+    /// the name of a module file and how it was built, a file written before module files
+    /// recorded it only has its name.
+    fn module_info(path: &str) -> Result<String, CoreError> {
+        let bytes = std::fs::read(path).map_err(|err| CoreError::IoError(err, path.to_owned()))?;
+        let compiled = CompiledModule::from_bytes_with(&bytes, &mut |warning| {
+            Self::print_load_warning(path, &warning)
+        })
+        .map_err(CoreError::RuntimeError)?;
+        let metadata = match compiled.metadata() {
+            Some(metadata) => metadata.to_string(),
+            None => "the module file does not record how it was built".to_string(),
+        };
+        Ok(format!("module {}\n{}\n", compiled.name(), metadata))
+    }
+
+    /// the manifest of the project the driver is run in, the nearest one in the current
+    /// directory or a parent of it.
+    fn load_manifest() -> Result<Manifest, CoreError> {
+        let dir = std::env::current_dir().map_err(|err| CoreError::IoError(err, ".".to_owned()))?;
+        let path = Manifest::find(&dir).ok_or_else(|| {
+            CoreError::InvalidArgument(format!(
+                "no input file was given and there is no {} in '{}' or a parent of it",
+                MANIFEST_FILE_NAME,
+                dir.display()
+            ))
+        })?;
+        let source = std::fs::read_to_string(&path)
+            .map_err(|err| CoreError::IoError(err, path.display().to_string()))?;
+        let manifest = Manifest::parse(&path, source.as_str()).map_err(CoreError::ManifestError)?;
+        if manifest.entry.is_none() {
+            return Err(CoreError::InvalidArgument(format!(
+                "{}: no input file was given and the manifest does not name an entry file",
+                path.display()
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// the file outputs of a build are written next to.
+    fn target(input: Option<&str>, options: &Options) -> PathBuf {
+        input
+            .map(PathBuf::from)
+            .or_else(|| options.entry.clone())
+            .unwrap_or_default()
+    }
+
+    fn is_module_file(path: &str) -> bool {
+        Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext == MODULE_FILE_EXTENSION)
+    }
+
+    fn load_module_file(&mut self, path: &str) -> Result<Gc<OxModule>, CoreError> {
+        let file =
+            std::fs::File::open(path).map_err(|err| CoreError::IoError(err, path.to_owned()))?;
+        let compiled =
+            CompiledModule::from_reader_with(std::io::BufReader::new(file), &mut |warning| {
+                Self::print_load_warning(path, &warning)
+            })
+            .map_err(CoreError::RuntimeError)?;
+        Ok(compiled.load(&mut self.vm))
+    }
+
+    /// builds the program of the manifest, it is made of the files under the directory of
+    /// the entry file and the search paths.
+    fn build_entry(&mut self, options: &Options) -> Result<Built, CoreError> {
+        let hir_file = self.check_entry(options)?;
+        self.generate(&hir_file)
+    }
+
+    /// checks the program of the manifest.
+    fn check_entry(&mut self, options: &Options) -> Result<HirFile, CoreError> {
+        let sources = self.entry_sources(options)?;
+        let name = options
+            .entry
+            .as_ref()
+            .and_then(|entry| entry.file_stem())
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.check_program(sources, name.as_str(), options)
+    }
+
+    /// the source files of the program of the manifest.
+    fn entry_sources(&self, options: &Options) -> Result<Vec<(PathBuf, PathBuf)>, CoreError> {
+        let entry = options
+            .entry
+            .as_ref()
+            .expect("the manifest names an entry file");
+        let root = entry.parent().unwrap_or_else(|| Path::new("."));
+        let mut roots = vec![root.to_path_buf()];
+        roots.extend(options.search_paths.iter().cloned());
+        Self::discover(&roots)
+    }
+
+    /// the source files under each root, relative to the root along with their full path.
+    fn discover(roots: &[PathBuf]) -> Result<Vec<(PathBuf, PathBuf)>, CoreError> {
+        let mut sources = vec![];
+        for root in roots {
+            let relative = discover_sources(root)
+                .map_err(|err| CoreError::IoError(err, root.display().to_string()))?;
+            sources.extend(relative.into_iter().map(|path| {
+                let full = root.join(&path);
+                (path, full)
+            }));
+        }
+        Ok(sources)
+    }
+
+    /// checks every source file as a module of the program `name`.
+    fn check_program(
+        &mut self,
+        sources: Vec<(PathBuf, PathBuf)>,
+        name: &str,
+        options: &Options,
+    ) -> Result<HirFile, CoreError> {
+        let modules = self.parse_modules(sources)?;
+        self.check_modules(modules, name, options)
+    }
+
+    fn check_modules(
+        &mut self,
+        modules: Vec<ParsedModule>,
+        name: &str,
+        options: &Options,
+    ) -> Result<HirFile, CoreError> {
+        let mut partial = self
+            .analysis
+            .check_program_partial(name, modules, options.mode)?;
+        if !partial.is_complete() {
+            return Err(CoreError::CompilerErrors(partial.diagnostics));
+        }
+        let warnings = self.run_passes(&mut partial.file, options)?;
+        partial.warnings.extend(warnings);
+        self.report_warnings(partial.warnings, options)?;
+        Ok(partial.file)
+    }
+
+    /// parses every source file as a module named by its path relative to its root.
+    fn parse_modules(
+        &mut self,
+        sources: Vec<(PathBuf, PathBuf)>,
+    ) -> Result<Vec<ParsedModule>, CoreError> {
+        let mut modules = vec![];
+        for (path, full) in sources {
+            let file = self
+                .open_file(&full)
+                .map_err(|err| CoreError::IoError(err, full.display().to_string()))?;
+            let parsed_file = self.parse_file(file.as_ref())?;
+            modules.push(ParsedModule {
+                name: module_name(&path),
+                path,
+                file: parsed_file,
+                imports: HashMap::new(),
+            });
+        }
+        Ok(modules)
+    }
+
+    /// folds the constants of a checked program and removes its dead code when it is optimized
+    /// and measures its functions, the warnings are those of the lints and of the dead code. An
+    /// error of folding stops the build.
+    fn run_passes(
+        &mut self,
+        hir_file: &mut HirFile,
+        options: &Options,
+    ) -> Result<Vec<Error>, CoreError> {
+        let mut metrics = Metrics::new(options.limits);
+        let mut diagnostics = Diagnostics::new();
+        let mut cx = PassContext::new(self.analysis.type_map_mut(), &mut diagnostics);
+        if options.fold {
+            ConstantFolding::default().run(hir_file, &mut cx);
+            DeadCode::default().run(hir_file, &mut cx);
+            let errors = cx.take_errors();
+            if !errors.is_empty() {
+                return Err(CoreError::CompilerErrors(errors));
+            }
+        }
+        metrics.run(hir_file, &mut cx);
+        if options.metrics_report {
+            print!("{}", metrics.table());
+        }
+        Ok(diagnostics.finish())
+    }
+
+    fn report_warnings(&self, warnings: Vec<Error>, options: &Options) -> Result<(), CoreError> {
+        match options.warnings {
+            WarningPolicy::Allow => {}
+            WarningPolicy::Warn => {
+                for warning in &warnings {
+                    self.print_warning(warning);
+                }
+            }
+            WarningPolicy::Deny => {
+                if let Some(warning) = warnings.into_iter().next() {
+                    return Err(CoreError::CompilerError(warning));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// opens a source file, `-` is the source read from stdin.
     fn open(&mut self, path: &str) -> Result<Rc<File>, CoreError> {
+        if path == STDIN_INPUT {
+            let source = File::read_source(std::io::stdin())
+                .map_err(|err| CoreError::IoError(err, STDIN_NAME.to_owned()))?;
+            return Ok(self.file_map.add_virtual_file(STDIN_NAME, source));
+        }
         self.open_file(path)
             .map_err(|err| CoreError::IoError(err, path.to_owned()))
     }
 
-    fn build(&mut self, file: Rc<File>, options: Options) -> Result<Gc<OxModule>, CoreError> {
+    /// measures the benchmarks of the module whose name contains the filter, in the order they
+    /// are declared. The instructions of an iteration are the same on every machine, the time
+    /// is what is tracked.
+    fn run_benches(
+        &mut self,
+        module: Gc<OxModule>,
+        filter: Option<&str>,
+        json: Option<String>,
+    ) -> Result<(), CoreError> {
+        let benches = module
+            .objects()
+            .iter()
+            .filter_map(|object| match object {
+                Value::Function(function) => function
+                    .name()
+                    .as_str()
+                    .strip_prefix(BENCH_PREFIX)
+                    .map(|name| (name.to_owned(), *object)),
+                _ => None,
+            })
+            .filter(|(name, _)| filter.is_none_or(|filter| name.contains(filter)))
+            .collect::<Vec<_>>();
+
+        let config = BenchConfig::default();
+        let mut reports = vec![];
+        println!(
+            "{:<24} {:>10} {:>14} {:>12} {:>12} {:>14}",
+            "bench", "iterations", "iterations/s", "mean (us)", "std dev", "instructions"
+        );
+        for (name, function) in benches {
+            let report = self
+                .vm
+                .bench(module, function, &config)
+                .map_err(CoreError::RuntimeError)?;
+            println!(
+                "{:<24} {:>10} {:>14.1} {:>12.3} {:>12.3} {:>14.1}",
+                name,
+                report.iterations(),
+                report.iterations_per_sec(),
+                report.nanos.mean() / 1e3,
+                report.nanos.std_dev() / 1e3,
+                report.instructions.mean()
+            );
+            reports.push(report.to_json(&name));
+        }
+
+        if let Some(path) = json {
+            std::fs::write(&path, format!("{{\"benches\":[{}]}}", reports.join(",")))
+                .map_err(|err| CoreError::IoError(err, path.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn read_source_map(path: &str) -> Result<SourceMap, CoreError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| CoreError::IoError(err, path.to_owned()))?;
+        SourceMap::from_json(content.as_str())
+            .map_err(|err| CoreError::InvalidArgument(format!("{}: {}", path, err)))
+    }
+
+    fn build(&mut self, file: Rc<File>, options: &Options) -> Result<Built, CoreError> {
+        let hir_file = self.check(file, options)?;
+        self.generate(&hir_file)
+    }
+
+    fn check(&mut self, file: Rc<File>, options: &Options) -> Result<HirFile, CoreError> {
+        let modules = self.load(file, options)?;
+        self.check_loaded(modules, options)
+    }
+
+    /// a file along with the modules it imports, they are found by their path under the
+    /// directory of the file and the search paths.
+    fn load(&mut self, file: Rc<File>, options: &Options) -> Result<Vec<ParsedModule>, CoreError> {
         let parsed_file = self
             .parse_file(file.as_ref())
             .map_err(Into::<CoreError>::into)?;
-        let hir_file = self
-            .resolve_root(parsed_file, options.mode)
-            .map_err(Into::<CoreError>::into)?;
+        let path = PathBuf::from(file.name());
+        let mut modules = vec![ParsedModule {
+            name: module_name(&path),
+            path,
+            file: parsed_file,
+            imports: HashMap::new(),
+        }];
+
+        let mut roots = vec![file.path().parent().unwrap_or_else(|| Path::new("")).to_path_buf()];
+        roots.extend(options.search_paths.iter().cloned());
+        let resolver = FileSystemResolver::new(roots);
+        let max_depth = self.max_expr_depth;
+        resolve_imports(&mut modules, &mut self.file_map, &resolver, |file| {
+            Self::parse_with_depth(file, max_depth)
+        })?;
+        Ok(modules)
+    }
 
+    /// checks a loaded file, a file that imports nothing is checked on its own.
+    fn check_loaded(
+        &mut self,
+        mut modules: Vec<ParsedModule>,
+        options: &Options,
+    ) -> Result<HirFile, CoreError> {
+        if modules.len() > 1 {
+            let name = modules[0].name.clone();
+            return self.check_modules(modules, name.as_str(), options);
+        }
+        let parsed_file = modules.pop().unwrap().file;
+        let mut hir_file = self
+            .analysis
+            .check_all(parsed_file, options.mode)
+            .map_err(CoreError::CompilerErrors)?;
+        let warnings = self.run_passes(&mut hir_file, options)?;
+        self.report_warnings(warnings, options)?;
+        Ok(hir_file)
+    }
+
+    /// generates the module of a checked program.
+    fn generate(&mut self, hir_file: &HirFile) -> Result<Built, CoreError> {
         self.vm.set_code_gen(true);
-        let module = CodeGen::build(&self.file_map, &hir_file, &mut self.vm)
-            .map_err(|e| CoreError::from(e))?;
+        let (module, source_map) =
+            CodeGen::build_with_source_map(&self.file_map, hir_file, &mut self.vm)
+                .map_err(|e| CoreError::from(e))?;
         self.vm.set_code_gen(false);
 
-        Ok(module)
+        Ok((module, source_map, module_api(hir_file)))
     }
 
     fn execute_repl(&mut self, _options: Options) -> Result<(), CoreError> {
         println!("REPL not implemented");
         Ok(())
     }
+
+    fn parse_with_depth(file: &File, max_depth: usize) -> Result<ParsedFile, Error> {
+        let mut parser = Parser::new(file);
+        parser.set_max_depth(max_depth);
+        parser.init()?;
+        parser.parse_file()
+    }
 }
 
 impl Executor for Core {
@@ -258,9 +1253,7 @@ impl Executor for Core {
     }
 
     fn parse_file(&self, file: &File) -> Result<ParsedFile, Error> {
-        let mut parser = Parser::new(file);
-        parser.init()?;
-        parser.parse_file()
+        Self::parse_with_depth(file, self.max_expr_depth)
     }
 
     fn resolve_root(&mut self, file: ParsedFile, mode: LanguageMode) -> Result<HirFile, Error> {
@@ -268,3 +1261,41 @@ impl Executor for Core {
         self.analysis.check(file, mode)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Arguments, Core};
+    use auburn::{analysis::LANGUAGE_VERSION, oxide::CompiledModule, oxide::Value, oxide::Vm};
+    use clap::Clap;
+
+    #[test]
+    fn test_module_info_of_a_compiled_module() {
+        let root = std::env::temp_dir().join(format!("aubc-module-info-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let input = root.join("main.au");
+        std::fs::write(&input, "fn main() i64 = sys.opt_level()\n").unwrap();
+        let input = input.display().to_string();
+        let args = ["aubc", "--opt-level", "2", "compile", input.as_str()];
+        Core::new().execute(Arguments::parse_from(args)).unwrap();
+
+        let output = root.join("main.oxb").display().to_string();
+        let info = Core::module_info(output.as_str()).unwrap();
+        assert_eq!(
+            info,
+            format!(
+                "module main\ncompiler {}\nmode: default\nopt_level: 2\nfold: true\n\
+                 overflow: wrap\nunreachable: trusted\nchecks: []\n",
+                LANGUAGE_VERSION
+            )
+        );
+
+        // the level the module was built at is what the program sees.
+        let compiled = CompiledModule::from_bytes(&std::fs::read(&output).unwrap()).unwrap();
+        assert_eq!(compiled.metadata().unwrap().option("opt_level"), Some("2"));
+        let mut vm = Vm::new();
+        let module = compiled.load(&mut vm);
+        vm.run_module(module).unwrap();
+        assert!(matches!(vm.top(), Value::I64(2)));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -6,9 +6,19 @@ extern crate auburn;
 use crate::core::Core;
 use std::error::Error;
 
+mod cache;
 mod core;
+mod manifest;
+
+// the compiler passes are recursive over the tree, give them plenty of room to work with so
+// the expression depth limit is what stops a deeply nested file.
+const COMPILER_STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() -> Result<(), Box<dyn Error>> {
-    Core::new().run();
-    Ok(())
+    let code = std::thread::Builder::new()
+        .stack_size(COMPILER_STACK_SIZE)
+        .spawn(|| Core::new().run())?
+        .join()
+        .expect("compiler thread panicked");
+    std::process::exit(code)
 }
@@ -0,0 +1,564 @@
+use std::fmt::{self, Display, Formatter};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::{Chars, FromStr};
+
+use auburn::passes::Lint;
+use auburn::LanguageMode;
+
+/// name of the manifest at the root of a project.
+pub const MANIFEST_FILE_NAME: &str = "oxide.toml";
+
+/// what is done with the warnings of a program.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WarningPolicy {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
+}
+
+impl Display for WarningPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Warn => write!(f, "warn"),
+            Self::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+impl FromStr for WarningPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Self::Allow),
+            "warn" => Ok(Self::Warn),
+            "deny" => Ok(Self::Deny),
+            other => Err(format!(
+                "'{}' is not a warning policy, expected allow, warn or deny",
+                other
+            )),
+        }
+    }
+}
+
+/// highest optimization level.
+pub const MAX_OPT_LEVEL: u8 = 3;
+
+/// checks an optimization level given on the command line.
+pub fn parse_opt_level(s: &str) -> Result<u8, String> {
+    match s.parse::<u8>() {
+        Ok(level) if level <= MAX_OPT_LEVEL => Ok(level),
+        _ => Err(format!(
+            "'{}' is not an optimization level, expected 0 to {}",
+            s, MAX_OPT_LEVEL
+        )),
+    }
+}
+
+/// a manifest error along with the line it was found on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+    }
+}
+
+/// the settings of a project. Paths are relative to the directory of the manifest, they are
+/// resolved against it when it is read.
+///
+/// ```toml
+/// entry = "src/main.au"
+/// search_paths = ["lib"]
+/// cfg = ["debug"]
+/// warnings = "deny"
+/// opt_level = 1
+/// mode = "default"
+/// lints = ["max-depth=4", "max-params=5"]
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Manifest {
+    pub path: PathBuf,
+    /// the file declaring `main`, the directory it is in is the root of the program.
+    pub entry: Option<PathBuf>,
+    /// directories searched for the modules a program imports.
+    pub search_paths: Vec<PathBuf>,
+    pub cfg: Vec<String>,
+    pub warnings: Option<WarningPolicy>,
+    pub opt_level: Option<u8>,
+    pub mode: Option<LanguageMode>,
+    /// limits on the size and complexity of every function, see `auburn::passes::Metrics`.
+    pub lints: Vec<Lint>,
+    /// the line each key is set on.
+    lines: Vec<(&'static str, usize)>,
+}
+
+/// a value of the subset of toml a manifest is written in.
+enum Value {
+    String(String),
+    Integer(i64),
+    Array(Vec<String>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::String(_) => "a string",
+            Self::Integer(_) => "an integer",
+            Self::Array(_) => "an array",
+        }
+    }
+}
+
+impl Manifest {
+    /// the manifest of the project dir is in, the nearest one in it or a parent of it.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|dir| dir.join(MANIFEST_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    /// parses the manifest at path, the entry file must exist.
+    pub fn parse(path: &Path, source: &str) -> Result<Self, ManifestError> {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut manifest = Self {
+            path: path.to_path_buf(),
+            ..Self::default()
+        };
+        let mut reader = Reader {
+            chars: source.chars().peekable(),
+            line: 1,
+        };
+        let error = |line: usize, message: String| ManifestError {
+            path: path.to_path_buf(),
+            line,
+            message,
+        };
+
+        while let Some((line, key)) = reader.key().map_err(|msg| error(reader.line, msg))? {
+            let value = reader.value().map_err(|msg| error(reader.line, msg))?;
+            reader.end_of_line().map_err(|msg| error(reader.line, msg))?;
+
+            let key = match manifest.set(dir, key.as_str(), value) {
+                Ok(key) => key,
+                Err(msg) => return Err(error(line, msg)),
+            };
+            if manifest.line_of(key).is_some() {
+                return Err(error(line, format!("'{}' is set more than once", key)));
+            }
+            manifest.lines.push((key, line));
+        }
+
+        if let Some(entry) = &manifest.entry {
+            if !entry.is_file() {
+                let line = manifest.line_of("entry").unwrap();
+                let msg = format!("entry file '{}' does not exist", entry.display());
+                return Err(error(line, msg));
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// the line key is set on.
+    pub fn line_of(&self, key: &str) -> Option<usize> {
+        self.lines
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, line)| *line)
+    }
+
+    /// a setting of the manifest along with its line, none when it is not set.
+    pub fn setting<T>(&self, key: &str, get: impl Fn(&Self) -> Option<T>) -> Option<(T, usize)> {
+        get(self).zip(self.line_of(key))
+    }
+
+    fn set(&mut self, dir: &Path, key: &str, value: Value) -> Result<&'static str, String> {
+        let expected = |expected: &str, value: &Value| {
+            format!("'{}' is {}, found {}", key, expected, value.kind())
+        };
+        match (key, value) {
+            ("entry", Value::String(entry)) => {
+                self.entry = Some(dir.join(entry));
+                Ok("entry")
+            }
+            ("search_paths", Value::Array(paths)) => {
+                self.search_paths = paths.iter().map(|path| dir.join(path)).collect();
+                Ok("search_paths")
+            }
+            ("cfg", Value::Array(flags)) => {
+                self.cfg = flags;
+                Ok("cfg")
+            }
+            ("warnings", Value::String(policy)) => {
+                self.warnings = Some(policy.parse()?);
+                Ok("warnings")
+            }
+            ("opt_level", Value::Integer(level)) => {
+                self.opt_level = Some(parse_opt_level(level.to_string().as_str())?);
+                Ok("opt_level")
+            }
+            ("mode", Value::String(mode)) => {
+                self.mode = Some(mode.parse().map_err(|err| format!("{}", err))?);
+                Ok("mode")
+            }
+            ("lints", Value::Array(lints)) => {
+                self.lints = lints
+                    .iter()
+                    .map(|lint| lint.parse())
+                    .collect::<Result<_, _>>()?;
+                Ok("lints")
+            }
+            ("entry", value) | ("warnings", value) | ("mode", value) => {
+                Err(expected("a string", &value))
+            }
+            ("search_paths", value) | ("cfg", value) | ("lints", value) => {
+                Err(expected("an array of strings", &value))
+            }
+            ("opt_level", value) => Err(expected("an integer", &value)),
+            (key, _) => Err(format!("unknown key '{}'", key)),
+        }
+    }
+}
+
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.chars.next();
+        if ch == Some('\n') {
+            self.line += 1;
+        }
+        ch
+    }
+
+    fn skip_spaces(&mut self) {
+        while let Some(' ') | Some('\t') | Some('\r') = self.chars.peek() {
+            self.bump();
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.chars.peek() == Some(&'#') {
+            while !matches!(self.chars.peek(), Some('\n') | None) {
+                self.bump();
+            }
+        }
+    }
+
+    /// skips blank lines and comments, including newlines.
+    fn skip_trivia(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.chars.peek() {
+                Some('\n') => {
+                    self.bump();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// the key of the next setting along with its line, none at the end of the manifest.
+    fn key(&mut self) -> Result<Option<(usize, String)>, String> {
+        self.skip_trivia();
+        let line = self.line;
+        let mut key = String::new();
+        while let Some(ch) = self.chars.peek().copied() {
+            if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' {
+                key.push(ch);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        match self.chars.peek() {
+            None if key.is_empty() => return Ok(None),
+            Some('[') if key.is_empty() => return Err("tables are not supported".to_string()),
+            _ if key.is_empty() => return Err(self.unexpected("a key")),
+            _ => {}
+        }
+
+        self.skip_spaces();
+        match self.bump() {
+            Some('=') => Ok(Some((line, key))),
+            _ => Err(format!("expected '=' after '{}'", key)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_spaces();
+        match self.chars.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('[') => {
+                self.bump();
+                let mut elements = vec![];
+                loop {
+                    self.skip_trivia();
+                    match self.chars.peek() {
+                        Some(']') => {
+                            self.bump();
+                            return Ok(Value::Array(elements));
+                        }
+                        Some('"') => elements.push(self.string()?),
+                        _ => return Err(self.unexpected("a string or ']'")),
+                    }
+                    self.skip_trivia();
+                    match self.chars.peek() {
+                        Some(',') => {
+                            self.bump();
+                        }
+                        Some(']') => {}
+                        _ => return Err(self.unexpected("',' or ']'")),
+                    }
+                }
+            }
+            Some(ch) if ch.is_ascii_digit() || *ch == '-' => {
+                let mut digits = String::new();
+                while let Some(ch) = self.chars.peek().copied() {
+                    if ch.is_ascii_digit() || ch == '-' || ch == '_' {
+                        digits.push(ch);
+                        self.bump();
+                    } else {
+                        break;
+                    }
+                }
+                digits
+                    .replace('_', "")
+                    .parse()
+                    .map(Value::Integer)
+                    .map_err(|_| format!("'{}' is not an integer", digits))
+            }
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.bump() {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(ch) => return Err(format!("unknown escape '\\{}'", ch)),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some('\n') | None => return Err("unterminated string".to_string()),
+                Some(ch) => value.push(ch),
+            }
+        }
+    }
+
+    /// a setting is followed by a comment or the end of its line.
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.chars.peek() {
+            Some('\n') | None => Ok(()),
+            _ => Err(self.unexpected("the end of the line")),
+        }
+    }
+
+    fn unexpected(&mut self, expected: &str) -> String {
+        match self.chars.peek() {
+            Some('\n') | None => format!("expected {} found the end of the line", expected),
+            Some(ch) => format!("expected {} found '{}'", expected, ch),
+        }
+    }
+}
+
+/// where the value of a setting came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Origin {
+    Default,
+    /// the line of the manifest it is set on.
+    Manifest(usize),
+    CommandLine,
+}
+
+/// the value of each setting of a build and where it came from. A command line flag overrides
+/// the manifest, which overrides the default.
+#[derive(Debug, Default)]
+pub struct Provenance {
+    manifest: Option<PathBuf>,
+    settings: Vec<(&'static str, String, Origin)>,
+}
+
+impl Provenance {
+    pub fn new(manifest: Option<&Manifest>) -> Self {
+        Self {
+            manifest: manifest.map(|manifest| manifest.path.clone()),
+            settings: vec![],
+        }
+    }
+
+    /// the value of a setting, describe is how it is shown in the verbose output.
+    pub fn resolve<T>(
+        &mut self,
+        name: &'static str,
+        flag: Option<T>,
+        manifest: Option<(T, usize)>,
+        default: T,
+        describe: impl Fn(&T) -> String,
+    ) -> T {
+        let (value, origin) = match (flag, manifest) {
+            (Some(value), _) => (value, Origin::CommandLine),
+            (None, Some((value, line))) => (value, Origin::Manifest(line)),
+            (None, None) => (default, Origin::Default),
+        };
+        self.settings.push((name, describe(&value), origin));
+        value
+    }
+}
+
+impl Display for Provenance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (name, value, origin) in &self.settings {
+            write!(f, "{} = {} ", name, value)?;
+            match (origin, &self.manifest) {
+                (Origin::Default, _) => writeln!(f, "(default)")?,
+                (Origin::CommandLine, _) => writeln!(f, "(command line)")?,
+                (Origin::Manifest(line), Some(path)) => {
+                    writeln!(f, "({}:{})", path.display(), line)?
+                }
+                (Origin::Manifest(line), None) => writeln!(f, "(manifest:{})", line)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Manifest, Provenance, WarningPolicy};
+    use auburn::LanguageMode;
+    use std::path::PathBuf;
+
+    fn project(name: &str, files: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("aubc-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "fn main() i64 = 0\n").unwrap();
+        }
+        root
+    }
+
+    fn parse_error(source: &str) -> String {
+        let path = PathBuf::from("project").join(super::MANIFEST_FILE_NAME);
+        Manifest::parse(&path, source).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_relative_paths_resolve_against_the_manifest() {
+        let root = project("paths", &["src/main.au"]);
+        let path = root.join(super::MANIFEST_FILE_NAME);
+        let source = "# the demo project\n\
+            entry = \"src/main.au\"\n\
+            search_paths = [\n    \"lib\", # vendored\n    \"../shared\",\n]\n\
+            cfg = [\"debug\"]\n\
+            warnings = \"deny\"\n\
+            opt_level = 2\n\
+            mode = \"script\"\n\
+            lints = [\"max-depth=4\"]\n";
+        let manifest = Manifest::parse(&path, source).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(manifest.entry, Some(root.join("src/main.au")));
+        assert_eq!(
+            manifest.search_paths,
+            [root.join("lib"), root.join("../shared")]
+        );
+        assert_eq!(manifest.cfg, ["debug"]);
+        assert_eq!(manifest.warnings, Some(WarningPolicy::Deny));
+        assert_eq!(manifest.opt_level, Some(2));
+        assert_eq!(manifest.mode, Some(LanguageMode::Script));
+        assert_eq!(manifest.lints, ["max-depth=4".parse().unwrap()]);
+        assert_eq!(manifest.line_of("search_paths"), Some(3));
+        assert_eq!(manifest.line_of("warnings"), Some(8));
+    }
+
+    #[test]
+    fn test_missing_entry_file() {
+        let root = project("missing", &[]);
+        let path = root.join(super::MANIFEST_FILE_NAME);
+        let err = Manifest::parse(&path, "opt_level = 1\nentry = \"main.au\"\n").unwrap_err();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(err.line, 2);
+        assert_eq!(
+            err.message,
+            format!("entry file '{}' does not exist", root.join("main.au").display())
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_report_their_line() {
+        assert_eq!(
+            parse_error("mode = \"script\"\n\nname = \"demo\"\n"),
+            "project/oxide.toml:3: unknown key 'name'"
+        );
+        assert_eq!(
+            parse_error("opt_level = 1\nopt_level = 2\n"),
+            "project/oxide.toml:2: 'opt_level' is set more than once"
+        );
+        assert_eq!(
+            parse_error("cfg = [\"a\",\n  \"b\"\n  \"c\"]\n"),
+            "project/oxide.toml:3: expected ',' or ']' found '\"'"
+        );
+        assert_eq!(
+            parse_error("opt_level = \"fast\"\n"),
+            "project/oxide.toml:1: 'opt_level' is an integer, found a string"
+        );
+        assert_eq!(
+            parse_error("opt_level = 4\n"),
+            "project/oxide.toml:1: '4' is not an optimization level, expected 0 to 3"
+        );
+        assert_eq!(
+            parse_error("warnings = \"loud\" # try it\n"),
+            "project/oxide.toml:1: 'loud' is not a warning policy, expected allow, warn or deny"
+        );
+        assert_eq!(
+            parse_error("entry = \"main.au\" extra\n"),
+            "project/oxide.toml:1: expected the end of the line found 'e'"
+        );
+        assert_eq!(
+            parse_error("[package]\n"),
+            "project/oxide.toml:1: tables are not supported"
+        );
+        assert_eq!(
+            parse_error("lints = [\"max-lines=3\"]\n"),
+            "project/oxide.toml:1: unknown lint 'max-lines', expected one of max-statements, \
+            max-depth, max-params, max-complexity"
+        );
+    }
+
+    #[test]
+    fn test_command_line_overrides_manifest() {
+        let mut provenance = Provenance::new(None);
+        let describe = |level: &u8| level.to_string();
+        assert_eq!(provenance.resolve("opt_level", Some(3), Some((1, 4)), 0, describe), 3);
+        assert_eq!(provenance.resolve("level", None, Some((1, 4)), 0, describe), 1);
+        assert_eq!(provenance.resolve("other", None, None, 0, describe), 0);
+        assert_eq!(
+            provenance.to_string(),
+            "opt_level = 3 (command line)\nlevel = 1 (manifest:4)\nother = 0 (default)\n"
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! copies, appends and fills vectors of ELEMENTS values from a script, once with the bulk methods
+//! of `Vec[T]` and once with the loop over the elements a script would write without them.
+//! Run with `cargo bench -p auburn --bench list_bulk --profile dev > /dev/null`, release builds
+//! of the vm do not compile. The vm prints each time a run completes, the times are written to
+//! stderr.
+use std::time::{Duration, Instant};
+
+use auburn::code_gen::CodeGen;
+use auburn::Compiler;
+use oxide::Vm;
+
+const ELEMENTS: usize = 10_000;
+const ROUNDS: usize = 20;
+
+/// the name of an operation, its body with the bulk method and its body as a loop. `src` holds
+/// 0 to ELEMENTS and `dst` ELEMENTS zeros when a body runs, `j` is free to use.
+const OPERATIONS: &[(&str, &str, &str)] = &[
+    (
+        "extend",
+        "        dst = Vec[i64].new()
+        dst.extend(src)
+",
+        "        dst = Vec[i64].new()
+        j = 0
+        while j < src.len() {
+            dst.push(src[j])
+            j += 1
+        }
+",
+    ),
+    (
+        "copy_from",
+        "        dst.copy_from(src, 0, 0, src.len())
+",
+        "        j = 0
+        while j < src.len() {
+            dst[j] = src[j]
+            j += 1
+        }
+",
+    ),
+    (
+        "fill",
+        "        dst.fill(7)
+",
+        "        j = 0
+        while j < dst.len() {
+            dst[j] = 7
+            j += 1
+        }
+",
+    ),
+];
+
+fn source(body: &str) -> String {
+    format!(
+        "fn main() i64 {{
+    mut src Vec[i64] = Vec[i64].with_capacity({elements})
+    mut i i64 = 0
+    while i < {elements} {{
+        src.push(i)
+        i += 1
+    }}
+    mut dst Vec[i64] = Vec[i64].with_capacity({elements})
+    dst.extend(src)
+    dst.fill(0)
+    mut j i64 = 0
+    mut round i64 = 0
+    while round < {rounds} {{
+{body}        round += 1
+    }}
+    dst.len()
+}}
+",
+        elements = ELEMENTS,
+        rounds = ROUNDS,
+        body = body
+    )
+}
+
+/// the time of running the body ROUNDS times, building the script is not counted.
+fn run(body: &str) -> Duration {
+    let mut compiler = Compiler::new();
+    let program = compiler.compile_str("list_bulk.au", &source(body)).unwrap();
+    let mut vm = Vm::new();
+    vm.set_code_gen(true);
+    let module = CodeGen::build(compiler.file_map(), &program.file, &mut vm).unwrap();
+    vm.set_code_gen(false);
+
+    let started = Instant::now();
+    vm.run_module(module).unwrap();
+    started.elapsed()
+}
+
+fn main() {
+    // the setup of the vectors is the time of a script without a body.
+    let setup = run("");
+    for (name, bulk, looped) in OPERATIONS {
+        for (kind, body) in [("bulk", bulk), ("loop", looped)] {
+            let elapsed = run(body).saturating_sub(setup);
+            eprintln!(
+                "{:>10} {}: {:>10.3?} per {} elements",
+                name,
+                kind,
+                elapsed / ROUNDS as u32,
+                ELEMENTS
+            );
+        }
+    }
+}
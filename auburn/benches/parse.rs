@@ -0,0 +1,96 @@
+//! parses a generated file of LINES lines and reports the time of the parser, the time of
+//! tokenizing the file alone and the allocations the parser makes for its tree.
+//! Run with `cargo bench -p auburn --bench parse --profile dev`, release builds of the vm do not
+//! compile. The times and the allocations are written to stderr.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use auburn::syntax::{Parser, TokenCursor};
+use auburn::system::File;
+
+const LINES: usize = 50_000;
+const ROUNDS: usize = 5;
+
+/// the system allocator counting the allocations and the bytes it hands out.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// a function of thirteen lines, `index` keeps the names apart.
+fn function(index: usize) -> String {
+    format!(
+        "fn work{index}(a i64, b i64) i64 {{
+    mut total i64 = a * {index} + b
+    let pair = (a, b)
+    if total > 100 {{
+        total = total - pair.0 * 2
+    }} else {{
+        total += pair.1
+    }}
+    while total < 1000 {{
+        total = total + a * b + 1
+    }}
+    total
+}}
+",
+        index = index
+    )
+}
+
+fn source() -> String {
+    (0..LINES / 13).map(function).collect()
+}
+
+fn main() {
+    let file = File::from_source("parse.au", source());
+    let lines = file.content().lines().count();
+
+    let mut tokenizing = Duration::default();
+    let mut parsing = Duration::default();
+    let mut allocations = 0;
+    let mut bytes = 0;
+    for _ in 0..ROUNDS {
+        let started = Instant::now();
+        for token in TokenCursor::new(&file) {
+            token.unwrap();
+        }
+        tokenizing += started.elapsed();
+
+        let allocated = ALLOCATIONS.load(Ordering::Relaxed);
+        let allocated_bytes = BYTES.load(Ordering::Relaxed);
+        let started = Instant::now();
+        let mut parser = Parser::new(&file);
+        parser.init().unwrap();
+        let parsed = parser.parse_file().unwrap();
+        parsing += started.elapsed();
+        allocations += ALLOCATIONS.load(Ordering::Relaxed) - allocated;
+        bytes += BYTES.load(Ordering::Relaxed) - allocated_bytes;
+        drop(parsed);
+    }
+
+    eprintln!(
+        "{} lines: parsed in {:.3?}, tokenized in {:.3?}, {} allocations of {} bytes",
+        lines,
+        parsing / ROUNDS as u32,
+        tokenizing / ROUNDS as u32,
+        allocations / ROUNDS,
+        bytes / ROUNDS
+    );
+}
@@ -0,0 +1,70 @@
+//! concatenates STRINGS strings of each of LENGTHS from a script. A string of up to
+//! INLINE_CAPACITY bytes is kept inline in the string object, a longer one allocates a buffer of
+//! the heap as well.
+//! Run with `cargo bench -p auburn --bench strings --profile dev > /dev/null`, release builds of
+//! the vm do not compile. The vm prints each time a run completes, the times and the bytes
+//! allocated are written to stderr.
+use std::time::{Duration, Instant};
+
+use auburn::code_gen::CodeGen;
+use auburn::Compiler;
+use oxide::Vm;
+
+const STRINGS: usize = 100_000;
+/// the longest string the string object of the vm keeps inline.
+const INLINE_CAPACITY: usize = 16;
+
+/// each string is a part concatenated with itself.
+const LENGTHS: &[usize] = &[INLINE_CAPACITY / 2, INLINE_CAPACITY, INLINE_CAPACITY + 2, 64];
+
+fn source(length: usize, strings: usize) -> String {
+    format!(
+        "fn main() i64 {{
+    let part string = \"{part}\"
+    mut s string = part
+    mut i i64 = 0
+    while i < {strings} {{
+        s = part + part
+        i += 1
+    }}
+    i
+}}
+",
+        part = "x".repeat(length / 2),
+        strings = strings
+    )
+}
+
+/// the time of running the script and the bytes it allocated, building it is not counted.
+fn run(length: usize, strings: usize) -> (Duration, u64) {
+    let mut compiler = Compiler::new();
+    let program = compiler
+        .compile_str("strings.au", &source(length, strings))
+        .unwrap();
+    let mut vm = Vm::new();
+    vm.set_code_gen(true);
+    let module = CodeGen::build(compiler.file_map(), &program.file, &mut vm).unwrap();
+    vm.set_code_gen(false);
+
+    let allocated = vm.heap_stats().bytes_allocated;
+    let started = Instant::now();
+    vm.run_module(module).unwrap();
+    let elapsed = started.elapsed();
+    (elapsed, vm.heap_stats().bytes_allocated - allocated)
+}
+
+fn main() {
+    for length in LENGTHS {
+        // the loop without a string is the time and the allocations of a script that makes none.
+        let (setup, setup_allocated) = run(*length, 0);
+        let (elapsed, allocated) = run(*length, STRINGS);
+        let inline = if *length <= INLINE_CAPACITY { "inline" } else { "heap" };
+        eprintln!(
+            "{:>4} bytes {:>6}: {:>10.3?} and {:>4} bytes allocated per string",
+            length,
+            inline,
+            elapsed.saturating_sub(setup) / STRINGS as u32,
+            (allocated - setup_allocated) / STRINGS as u64
+        );
+    }
+}
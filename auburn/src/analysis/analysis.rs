@@ -1,24 +1,39 @@
 use crate::analysis::entity::Path;
 use crate::analysis::scope::{Scope, ScopeKind};
-use crate::analysis::typer::Typer;
-use crate::analysis::Entity;
+use crate::analysis::typer::{BuildInfo, Recovered, Typer};
+use crate::analysis::{Checks, Entity, Generation};
 use crate::error::Error;
 use crate::ir::ast::Visibility;
 use crate::ir::hir::HirFile;
-use crate::syntax::ParsedFile;
+use crate::syntax::{ParsedFile, DEFAULT_MAX_EXPR_DEPTH};
 use crate::types::TypeMap;
+use crate::utils::Diagnostics;
 use crate::{analysis::entity::EntityInfo, LanguageMode};
 
 pub struct Analysis {
-    type_map: TypeMap,
-    scope_stack: Vec<Scope>,
+    pub(super) type_map: TypeMap,
+    pub(super) scope_stack: Vec<Scope>,
+    pub(super) trace_desugar: bool,
+    pub(super) checks: Checks,
+    pub(super) contracts: bool,
+    pub(super) build_info: BuildInfo,
+    pub(super) max_expr_depth: usize,
+    /// the generation of the prelude, every check is a generation of its own using it.
+    pub(super) prelude: Generation,
 }
 
 impl Analysis {
     pub fn new() -> Self {
+        let prelude = Generation::begin_prelude();
         let mut analysis = Analysis {
             type_map: TypeMap::new(),
             scope_stack: vec![],
+            trace_desugar: false,
+            checks: Checks::default(),
+            contracts: true,
+            build_info: BuildInfo::default(),
+            max_expr_depth: DEFAULT_MAX_EXPR_DEPTH,
+            prelude,
         };
 
         analysis.type_map.init_primitives();
@@ -62,11 +77,83 @@ impl Analysis {
 
         define_primitive!(prelude_scope, "char", self.type_map.get_char());
         define_primitive!(prelude_scope, "bool", self.type_map.get_bool());
+        define_primitive!(prelude_scope, "string", self.type_map.get_string());
+        define_primitive!(prelude_scope, "any", self.type_map.get_any());
+        define_primitive!(prelude_scope, "unit", self.type_map.get_unit());
 
         // load prelude
     }
 
+    /// print desugared constructs before and after they are lowered while checking.
+    pub fn set_trace_desugar(&mut self, enabled: bool) {
+        self.trace_desugar = enabled;
+    }
+
+    /// the opt-in checks made while checking.
+    pub fn set_checks(&mut self, checks: Checks) {
+        self.checks = checks;
+    }
+
+    /// whether the `@requires` and `@ensures` of functions are checked at runtime, they are by
+    /// default. Without they are only type checked and compile to nothing.
+    pub fn set_contracts(&mut self, enabled: bool) {
+        self.contracts = enabled;
+    }
+
+    /// how the program is built, `sys.opt_level()` and `sys.debug_info()` are folded to it.
+    pub fn set_build_info(&mut self, build_info: BuildInfo) {
+        self.build_info = build_info;
+    }
+
+    /// how deeply the expressions being checked may nest, the limit the files were parsed with.
+    pub fn set_max_expr_depth(&mut self, max_depth: usize) {
+        self.max_expr_depth = max_depth;
+    }
+
+    /// the types made while checking, for the passes run over a checked program.
+    pub fn type_map_mut(&mut self) -> &mut TypeMap {
+        &mut self.type_map
+    }
+
     pub fn check(&mut self, file: ParsedFile, mode: LanguageMode) -> Result<HirFile, Error> {
-        Typer::new(&mut self.type_map, &mut self.scope_stack, mode).resolve_root(file)
+        Generation::begin(self.prelude);
+        Typer::new(&mut self.type_map, &mut self.scope_stack, mode)
+            .with_trace_desugar(self.trace_desugar)
+            .with_checks(self.checks)
+            .with_contracts(self.contracts)
+            .with_build_info(self.build_info)
+            .with_max_depth(self.max_expr_depth)
+            .resolve_root(file)
+    }
+
+    /// checks a file like `check`, but a statement or item that fails to resolve is recorded
+    /// and the rest of the file is still checked. Every error found is returned, ordered and
+    /// without those that follow from another, see `Diagnostics::finish`.
+    pub fn check_all(
+        &mut self,
+        file: ParsedFile,
+        mode: LanguageMode,
+    ) -> Result<HirFile, Vec<Error>> {
+        Generation::begin(self.prelude);
+        let mut recovered = Recovered::default();
+        let result = Typer::new(&mut self.type_map, &mut self.scope_stack, mode)
+            .with_trace_desugar(self.trace_desugar)
+            .with_checks(self.checks)
+            .with_contracts(self.contracts)
+            .with_build_info(self.build_info)
+            .with_max_depth(self.max_expr_depth)
+            .with_recovery(&mut recovered)
+            .resolve_root(file);
+
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.extend(recovered.failures);
+        match result {
+            Ok(file) if diagnostics.is_empty() => Ok(file),
+            Ok(_) => Err(diagnostics.finish()),
+            Err(err) => {
+                diagnostics.push(err);
+                Err(diagnostics.finish())
+            }
+        }
     }
 }
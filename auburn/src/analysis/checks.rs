@@ -0,0 +1,168 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// checks that are off unless asked for, each can be enabled on its own or all at once with
+/// `--strict`. The language has no match expression yet, so there is no check of matches.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Checks {
+    /// the parameters and return type of a public function are written out, a block body
+    /// without a return type returns unit so it needs none.
+    pub annotated_signatures: bool,
+    /// a local or parameter can not reuse the name of a declaration of an enclosing scope.
+    pub no_shadowing: bool,
+    /// a call statement whose result is not unit must be discarded with `_ = f()`.
+    pub used_results: bool,
+}
+
+/// a check that can be enabled by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    AnnotatedSignatures,
+    NoShadowing,
+    UsedResults,
+}
+
+impl Check {
+    pub const ALL: [Check; 3] = [
+        Check::AnnotatedSignatures,
+        Check::NoShadowing,
+        Check::UsedResults,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::AnnotatedSignatures => "annotated-signatures",
+            Check::NoShadowing => "no-shadowing",
+            Check::UsedResults => "used-results",
+        }
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Check::ALL
+            .iter()
+            .copied()
+            .find(|check| check.name() == s)
+            .ok_or_else(|| {
+                let names = Check::ALL.iter().map(|check| check.name()).collect::<Vec<_>>();
+                format!("unknown check '{}', expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Checks {
+    /// every check, the checks made by `--strict`.
+    pub fn strict() -> Self {
+        Check::ALL
+            .iter()
+            .fold(Self::default(), |checks, check| checks.with(*check))
+    }
+
+    pub fn with(mut self, check: Check) -> Self {
+        match check {
+            Check::AnnotatedSignatures => self.annotated_signatures = true,
+            Check::NoShadowing => self.no_shadowing = true,
+            Check::UsedResults => self.used_results = true,
+        }
+        self
+    }
+
+    pub fn enabled(&self) -> Vec<Check> {
+        Check::ALL
+            .iter()
+            .copied()
+            .filter(|check| match check {
+                Check::AnnotatedSignatures => self.annotated_signatures,
+                Check::NoShadowing => self.no_shadowing,
+                Check::UsedResults => self.used_results,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Check, Checks};
+    use crate::analysis::Analysis;
+    use crate::error::{Error, ErrorKind};
+    use crate::syntax::Parser;
+    use crate::system::File;
+    use crate::LanguageMode;
+
+    fn check(source: &str, checks: Checks) -> Result<(), Error> {
+        let file = File::raw_test(source.to_string());
+        let mut parser = Parser::new(&file);
+        let parsed = parser.init().and_then(|_| parser.parse_file())?;
+        let mut analysis = Analysis::new();
+        analysis.set_checks(checks);
+        analysis.check(parsed, LanguageMode::Default).map(|_| ())
+    }
+
+    #[test]
+    fn test_strict_reports_each_check() {
+        let corpus = [
+            (
+                "pub fn double(x i64) = x + x\nfn main() i64 = double(1)\n",
+                "public function 'double' must declare its return type",
+            ),
+            (
+                "pub fn scale(x i32, factor = 2) i32 = x * factor\nfn main() i32 = scale(1, 3)\n",
+                "parameter 'factor' of public function 'scale' must declare its type",
+            ),
+            (
+                "fn main() i32 {\n    let x = 1\n    {\n        let x = 2\n        x\n    }\n}\n",
+                "'x' shadows a declaration of an enclosing scope",
+            ),
+            (
+                "let limit i64 = 10\nfn clamp(limit i64) i64 = limit\nfn main() i64 = clamp(1)\n",
+                "'limit' shadows a declaration of an enclosing scope",
+            ),
+            (
+                "fn one() i64 = 1\nfn main() i64 {\n    one()\n    2\n}\n",
+                "the result of this call is unused",
+            ),
+        ];
+        for (source, message) in corpus.iter() {
+            assert!(check(source, Checks::default()).is_ok(), "{}", source);
+            let err = check(source, Checks::strict()).err().unwrap();
+            assert_eq!(err.to_string(), *message);
+        }
+
+        // each check is independent of the others.
+        let unused = corpus[4].0;
+        assert!(check(unused, Checks::default().with(Check::NoShadowing)).is_ok());
+        assert!(matches!(
+            check(unused, Checks::default().with(Check::UsedResults))
+                .err()
+                .unwrap()
+                .kind(),
+            ErrorKind::UnusedResult { .. }
+        ));
+    }
+
+    #[test]
+    fn test_strict_accepts_careful_code() {
+        let source = "struct Countdown {\n    pub left i64\n    fn next(mut self) Option[i64] {\n\
+            \x20       mut item Option[i64] = none\n        if self.left != 0 {\n\
+            \x20           self.left -= 1\n            item = self.left\n        } else {\n            item = none\n        }\n        item\n    }\n}\n\
+            fn countdown(left i64) Countdown = Countdown { left: left }\n\
+            pub fn double(x i64) i64 = x + x\n\
+            pub fn report(x i64) {\n    let doubled = double(x)\n}\n\
+            fn main() i64 {\n    _ = double(1)\n    report(2)\n\
+            \x20   for i in countdown(2) {\n        for j in countdown(i) {\n\
+            \x20           _ = double(i + j)\n        }\n    }\n    double(3)\n}\n";
+        check(source, Checks::strict()).unwrap();
+        assert_eq!("used-results".parse::<Check>(), Ok(Check::UsedResults));
+        assert!("all".parse::<Check>().is_err());
+        assert_eq!(Checks::strict().enabled(), Check::ALL.to_vec());
+    }
+}
@@ -1,3 +1,4 @@
+use std::cell::Cell;
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -5,8 +6,11 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use itertools::Itertools;
 
 use crate::analysis::scope::ScopeRef;
+use crate::error::Error;
 use crate::ir::ast::{Item, Visibility};
-use crate::ir::hir::{HirExprPtr, HirSpecPtr};
+use crate::ir::hir::{BuiltinFunction, HirExprPtr, HirSpecPtr};
+use crate::passes::FunctionMetrics;
+use crate::syntax::Position;
 use crate::types::Type;
 use crate::utils::{new_ptr, Ptr};
 
@@ -16,6 +20,49 @@ pub type EntityRef = Ptr<Entity>;
 pub struct StructureInfo {
     pub fields: ScopeRef,
     pub methods: ScopeRef,
+    /// declared with `@finalize`, its `finalize` method runs once an instance is collected.
+    pub finalized: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariantInfo {
+    pub name: String,
+    pub discriminant: i64,
+    pub fields: Vec<Rc<Type>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumInfo {
+    pub variants: Vec<VariantInfo>,
+    /// the variants as entities, declared where they are named.
+    pub scope: ScopeRef,
+    /// declared with `@flags`, every variant is a single bit.
+    pub flags: bool,
+}
+
+impl EnumInfo {
+    pub fn variant(&self, name: &str) -> Option<&VariantInfo> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+
+    /// where the variant named is declared.
+    pub fn declared(&self, name: &str) -> Option<Position> {
+        self.scope
+            .get(name)
+            .and_then(|variant| variant.borrow().position())
+    }
+
+    /// only unit only enums have an integer representation.
+    pub fn is_unit_only(&self) -> bool {
+        self.variants.iter().all(|variant| variant.fields.is_empty())
+    }
+
+    /// every bit used by the variants of a flags enum.
+    pub fn all_flags(&self) -> i64 {
+        self.variants
+            .iter()
+            .fold(0, |bits, variant| bits | variant.discriminant)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -23,6 +70,22 @@ pub struct FunctionInfo {
     pub params: ScopeRef,
     pub body_scope: Option<ScopeRef>,
     pub body: HirExprPtr,
+    /// measured once the program is checked, see `passes::Metrics`.
+    pub metrics: Option<FunctionMetrics>,
+    /// declared in the body of a function, it is a local of that function.
+    pub local: bool,
+    /// the type parameters of a generic function, it is only code generated through its
+    /// instances.
+    pub type_params: Vec<EntityRef>,
+}
+
+/// a function literal, it is created with the values of the locals of enclosing functions its
+/// body uses. They are captured by value, so only immutable locals can be captured.
+#[derive(Debug, Clone)]
+pub struct ClosureInfo {
+    pub function: FunctionInfo,
+    /// the captured locals, they are passed to the function after its parameters.
+    pub captures: Vec<EntityRef>,
 }
 
 #[derive(Debug, Clone)]
@@ -33,6 +96,19 @@ pub struct AssociatedFunctionInfo {
     pub body: HirExprPtr,
     pub takes_self: bool,
     pub index: usize,
+    pub metrics: Option<FunctionMetrics>,
+    /// the function of a built in type it is, its body is empty and it compiles to an
+    /// instruction rather than a call.
+    pub builtin: Option<BuiltinFunction>,
+}
+
+/// a function whose signature is resolved before its body, see `Typer::declare_items`. A body
+/// calls it by its type, its own body among them.
+#[derive(Debug, Clone)]
+pub struct DeclaredInfo {
+    /// the structure of an associated function, none for a function.
+    pub entity: Option<EntityRef>,
+    pub takes_self: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -54,14 +130,23 @@ pub struct LocalInfo {
 pub enum EntityInfo {
     Unresolved(Box<Item>),
     Resolving,
+    Declared(DeclaredInfo),
     Primitive,
     Structure(StructureInfo),
+    Enumeration(EnumInfo),
+    /// a variant of an enum, its type is the enum.
+    Variant(VariantInfo),
     Function(FunctionInfo),
+    Closure(ClosureInfo),
     AssociatedFunction(AssociatedFunctionInfo),
     Variable(VariableInfo),
     Param(LocalInfo),
     SelfParam { mutable: bool },
     Field(LocalInfo),
+    /// a type parameter of a generic function.
+    TypeParam,
+    /// an item that failed to resolve when checking recovers from errors, along with why.
+    Failed(Box<Error>),
 }
 
 #[derive(Debug, Clone)]
@@ -121,14 +206,76 @@ impl EntityId {
     }
 }
 
+thread_local! {
+    /// the generation being checked and the prelude it is checked with.
+    static GENERATION: Cell<(Generation, Generation)> =
+        const { Cell::new((Generation(0), Generation(0))) };
+}
+
+/// the check an entity or scope is made by. Each check of an `Analysis` is a generation of its
+/// own, only the entities of the generation being checked and of the prelude the analysis was
+/// made with can take part in it. An entity kept from an earlier check refers to definitions
+/// that may have since changed.
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
+pub struct Generation(pub usize);
+
+impl Generation {
+    fn next() -> Self {
+        static TOKEN: AtomicUsize = AtomicUsize::new(1);
+        Self(TOKEN.fetch_add(1, Ordering::SeqCst))
+    }
+
+    /// the generation the entities made now belong to.
+    pub fn current() -> Self {
+        GENERATION.with(|generation| generation.get().0)
+    }
+
+    /// starts a generation for the prelude of an analysis, the prelude is a part of every
+    /// check of the analysis.
+    pub fn begin_prelude() -> Self {
+        let prelude = Self::next();
+        GENERATION.with(|generation| generation.set((prelude, prelude)));
+        prelude
+    }
+
+    /// starts the generation of a check of the analysis with prelude.
+    pub fn begin(prelude: Generation) -> Self {
+        let current = Self::next();
+        GENERATION.with(|generation| generation.set((current, prelude)));
+        current
+    }
+
+    /// whether what generation made can take part in the check running now.
+    pub fn is_live(self) -> bool {
+        GENERATION.with(|generation| {
+            let (current, prelude) = generation.get();
+            self == current || self == prelude
+        })
+    }
+
+    /// an internal compiler error when name, made by generation, can not take part in the
+    /// check running now, see `Entity::assert_live`.
+    pub fn check(self, name: &str) -> Result<(), Error> {
+        if self.is_live() {
+            Ok(())
+        } else {
+            Err(Error::stale_entity(name, self.0, Self::current().0))
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Entity {
     id: EntityId,
+    /// the check that made the entity.
+    generation: Generation,
     visibility: Visibility,
     name: String,
     ty: Rc<Type>,
     kind: EntityInfo,
     path: Path,
+    /// where the entity is declared, none for those the compiler declares.
+    position: Option<Position>,
 }
 
 impl Entity {
@@ -136,6 +283,20 @@ impl Entity {
         self.id
     }
 
+    pub fn generation(&self) -> Generation {
+        self.generation
+    }
+
+    /// panics with an internal compiler error when the entity was made by an earlier check
+    /// than the one running. Only checked with debug assertions.
+    pub fn assert_live(&self) {
+        if cfg!(debug_assertions) {
+            if let Err(err) = self.generation.check(self.name()) {
+                panic!("{}", err);
+            }
+        }
+    }
+
     pub fn unresolved(
         visibility: Visibility,
         name: String,
@@ -144,22 +305,26 @@ impl Entity {
     ) -> Self {
         Self {
             id: EntityId::next(),
+            generation: Generation::current(),
             visibility,
             name,
             ty: invalid_type,
             kind: EntityInfo::Unresolved(item),
             path: Path::empty(),
+            position: None,
         }
     }
 
     pub fn resolving(visibility: Visibility, name: String, invalid_type: Rc<Type>) -> Self {
         Self {
             id: EntityId::next(),
+            generation: Generation::current(),
             visibility,
             name,
             ty: invalid_type,
             kind: EntityInfo::Resolving,
             path: Path::empty(),
+            position: None,
         }
     }
 
@@ -173,10 +338,21 @@ impl Entity {
         self.visibility = visibility;
     }
 
+    /// renames an item once it is resolved, see `mangle`.
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
     pub fn to_resolving(&mut self) {
         self.kind = EntityInfo::Resolving;
     }
 
+    /// leaves a tombstone of an item that failed to resolve, see `Typer::recover_file`.
+    pub fn to_failed(&mut self, err: Error, position: Position) {
+        self.kind = EntityInfo::Failed(Box::new(err));
+        self.position = Some(position);
+    }
+
     pub fn new(
         visibility: Visibility,
         name: String,
@@ -186,11 +362,13 @@ impl Entity {
     ) -> Self {
         Self {
             id: EntityId::next(),
+            generation: Generation::current(),
             visibility,
             name,
             ty,
             kind,
             path,
+            position: None,
         }
     }
 
@@ -218,6 +396,15 @@ impl Entity {
         &self.path
     }
 
+    pub fn declared_at(mut self, position: Position) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub fn position(&self) -> Option<Position> {
+        self.position
+    }
+
     pub fn visibility(&self) -> Visibility {
         self.visibility
     }
@@ -248,6 +435,20 @@ impl Entity {
         }
     }
 
+    pub fn as_enum(&self) -> &EnumInfo {
+        match &self.kind {
+            EntityInfo::Enumeration(enum_info) => enum_info,
+            _ => panic!("Attempting to get an enumeration of an entity that is not an enumeration"),
+        }
+    }
+
+    pub fn as_enum_mut(&mut self) -> &mut EnumInfo {
+        match &mut self.kind {
+            EntityInfo::Enumeration(enum_info) => enum_info,
+            _ => panic!("Attempting to get an enumeration of an entity that is not an enumeration"),
+        }
+    }
+
     pub fn as_local(&self) -> &LocalInfo {
         match &self.kind {
             EntityInfo::Param(info) | EntityInfo::Field(info) => info,
@@ -269,7 +470,10 @@ impl Entity {
 
     pub fn is_type(&self) -> bool {
         match self.kind {
-            EntityInfo::Primitive | EntityInfo::Structure { .. } => true,
+            EntityInfo::Primitive
+            | EntityInfo::Structure { .. }
+            | EntityInfo::Enumeration(..)
+            | EntityInfo::TypeParam => true,
             _ => false,
         }
     }
@@ -281,6 +485,13 @@ impl Entity {
         }
     }
 
+    pub fn is_enum(&self) -> bool {
+        match self.kind {
+            EntityInfo::Enumeration(..) => true,
+            _ => false,
+        }
+    }
+
     pub fn is_function(&self) -> bool {
         match self.kind {
             EntityInfo::Function { .. } | EntityInfo::AssociatedFunction { .. } => true,
@@ -297,7 +508,10 @@ impl Entity {
 
     pub fn is_instance(&self) -> bool {
         match self.kind {
-            EntityInfo::Variable(..) | EntityInfo::Field(..) | EntityInfo::SelfParam { .. } => true,
+            EntityInfo::Variable(..)
+            | EntityInfo::Param(..)
+            | EntityInfo::Field(..)
+            | EntityInfo::SelfParam { .. } => true,
             _ => false,
         }
     }
@@ -311,11 +525,18 @@ impl Entity {
 
     pub fn is_resolved(&self) -> bool {
         match self.kind {
-            EntityInfo::Unresolved(_) | EntityInfo::Resolving => false,
+            EntityInfo::Unresolved(_)
+            | EntityInfo::Resolving
+            | EntityInfo::Declared(_)
+            | EntityInfo::Failed(_) => false,
             _ => true,
         }
     }
 
+    pub fn is_declared(&self) -> bool {
+        matches!(self.kind, EntityInfo::Declared(_))
+    }
+
     pub fn is_resolving(&self) -> bool {
         match self.kind {
             EntityInfo::Resolving => true,
@@ -330,18 +551,33 @@ impl Entity {
         }
     }
 
+    /// why the item failed to resolve, none for an item that did not fail.
+    pub fn failure(&self) -> Option<&Error> {
+        match &self.kind {
+            EntityInfo::Failed(err) => Some(err),
+            _ => None,
+        }
+    }
+
     pub fn type_name(&self) -> &'static str {
         match self.kind {
             EntityInfo::Unresolved(_) => "unresolved",
             EntityInfo::Resolving => "resolving",
+            EntityInfo::Declared(_) => "declared",
             EntityInfo::Primitive => "primitive",
             EntityInfo::Structure { .. } => "structure",
+            EntityInfo::Enumeration(..) => "enumeration",
+            EntityInfo::Variant(..) => "variant",
             EntityInfo::Function { .. } => "function",
+            EntityInfo::Closure(..) => "closure",
             EntityInfo::AssociatedFunction(..) => "associated function",
             EntityInfo::Variable { .. } => "variable",
             EntityInfo::Param { .. } => "param",
             EntityInfo::SelfParam { .. } => "self",
             EntityInfo::Field { .. } => "field",
+            EntityInfo::TypeParam => "type parameter",
+            EntityInfo::Failed(_) => "failed",
+
         }
     }
 }
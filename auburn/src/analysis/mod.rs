@@ -1,8 +1,14 @@
 mod analysis;
+mod checks;
 mod entity;
+mod program;
 mod scope;
 mod typer;
 
 pub use analysis::Analysis;
+pub use checks::{Check, Checks};
 pub use entity::*;
-pub use scope::Scope;
+pub use program::{mangle, ParsedModule, PartialModule, Program};
+
+pub use scope::{Scope, ScopeKind};
+pub use typer::{BuildInfo, LANGUAGE_VERSION};
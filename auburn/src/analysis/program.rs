@@ -0,0 +1,890 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use crate::analysis::scope::{Scope, ScopeKind, ScopeRef};
+use crate::analysis::typer::{Recovered, Typer};
+use crate::analysis::{Analysis, EntityRef, Generation};
+use crate::error::Error;
+use crate::ir::ast::{Expr, ExprKind, Identifier, ItemKind, Node, StmtKind, Visibility};
+use crate::ir::hir::{HirFile, HirStmtKind};
+use crate::syntax::{FilePos, ParsedFile, Position, Span};
+use crate::system::FileId;
+use crate::utils::Diagnostics;
+use crate::LanguageMode;
+
+/// name of the function a program starts at.
+const ENTRY_NAME: &str = "main";
+
+/// joins the module of an item to its name. It is not `.` so a mangled item is never mistaken
+/// for a method of a structure, `Point.len`, in a source map.
+const MANGLE_SEPARATOR: &str = "::";
+
+/// a parsed file of a program along with the module it defines.
+pub struct ParsedModule {
+    /// `shapes.circle` for the file `shapes/circle.au`.
+    pub name: String,
+    /// path of the file relative to the root of the program.
+    pub path: PathBuf,
+    pub file: ParsedFile,
+    /// the module an import names when a resolver found it by another spelling, by spelling.
+    pub imports: HashMap<String, String>,
+}
+
+/// the modules of a program resolved and linked into a single file.
+pub struct Program {
+    pub file: HirFile,
+    /// diagnostics that do not stop the program from being built.
+    pub warnings: Vec<Error>,
+}
+
+/// what could be resolved of a program whose items do not all resolve, for tools that show
+/// what they can of a program being edited. See `Analysis::check_program_partial`.
+pub struct PartialModule {
+    /// the items that resolved, linked like the file of a program. It has no entry when main
+    /// failed.
+    pub file: HirFile,
+    pub warnings: Vec<Error>,
+    /// the errors of the items that failed, by file and position with an error that follows
+    /// from another left out, see `Diagnostics::finish`.
+    pub diagnostics: Vec<Error>,
+    /// a tombstone for every item of a linked module that failed, see `EntityInfo::Failed`.
+    pub failed: Vec<EntityRef>,
+}
+
+impl PartialModule {
+    /// every item resolved, the file is that of the program.
+    pub fn is_complete(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    /// the item named name, a tombstone when it failed.
+    pub fn find_entity(&self, name: &str) -> Option<EntityRef> {
+        self.file.find_entity_by_name(name).or_else(|| {
+            self.failed
+                .iter()
+                .find(|entity| entity.borrow().name() == name)
+                .cloned()
+        })
+    }
+}
+
+/// the name of an item of a module once it is linked into a program.
+pub fn mangle(module: &str, name: &str) -> String {
+    format!(
+        "{}{}{}",
+        module.replace('.', MANGLE_SEPARATOR),
+        MANGLE_SEPARATOR,
+        name
+    )
+}
+
+fn file_position(file_id: FileId) -> Position {
+    Position::new(Span::default(), FilePos::default(), file_id)
+}
+
+impl Analysis {
+    /// resolves every module of a program and links them into one file named `name`. A module
+    /// is resolved after the modules it imports, so everything it uses from them is resolved.
+    /// Modules that can not be reached from the module with `main` are checked but left out.
+    pub fn check_program(
+        &mut self,
+        name: &str,
+        modules: Vec<ParsedModule>,
+        mode: LanguageMode,
+    ) -> Result<Program, Error> {
+        self.link_program(name, modules, mode, None)
+    }
+
+    /// checks a program like `check_program`, but an item that fails to resolve is recorded
+    /// and the rest of the program is still checked. Only errors of the program as a whole,
+    /// its imports and entry, are returned as an error.
+    pub fn check_program_partial(
+        &mut self,
+        name: &str,
+        modules: Vec<ParsedModule>,
+        mode: LanguageMode,
+    ) -> Result<PartialModule, Error> {
+        let mut recovered = modules
+            .iter()
+            .map(|_| Recovered::default())
+            .collect::<Vec<_>>();
+
+        let program = self.link_program(name, modules, mode, Some(&mut recovered))?;
+        let mut diagnostics = Diagnostics::new();
+        let mut failed = vec![];
+        for recovered in recovered {
+            diagnostics.extend(recovered.failures);
+            failed.extend(recovered.failed);
+        }
+        Ok(PartialModule {
+            file: program.file,
+            warnings: program.warnings,
+            diagnostics: diagnostics.finish(),
+            failed,
+        })
+    }
+
+    /// the failed items of a module that is not linked are left out of recovered.
+    fn link_program(
+        &mut self,
+        name: &str,
+        modules: Vec<ParsedModule>,
+        mode: LanguageMode,
+        mut recovered: Option<&mut Vec<Recovered>>,
+    ) -> Result<Program, Error> {
+        Generation::begin(self.prelude);
+        let imports = Self::module_imports(&modules)?;
+        let order = Self::import_order(&modules, &imports)?;
+        let entry = Self::find_entry(&modules)?;
+
+        let names = modules
+            .iter()
+            .map(|module| module.name.clone())
+            .collect::<Vec<_>>();
+        let mut parsed = modules
+            .into_iter()
+            .map(|module| Some(module.file))
+            .collect::<Vec<_>>();
+        let mut files: Vec<Option<HirFile>> = names.iter().map(|_| None).collect();
+        for idx in order.iter().copied() {
+            let parsed = parsed[idx].take().unwrap();
+            let failed = recovered.as_deref().map(|recovered| recovered.as_slice());
+            let (scope, hidden) =
+                Self::imports_scope(&names, &imports[idx], &files, failed, &parsed);
+            let mut typer = Typer::new(&mut self.type_map, &mut self.scope_stack, mode)
+                .with_trace_desugar(self.trace_desugar)
+                .with_checks(self.checks)
+                .with_contracts(self.contracts)
+                .with_build_info(self.build_info)
+                .with_max_depth(self.max_expr_depth)
+                .in_module(names[idx].as_str(), scope, hidden);
+            if let Some(recovered) = recovered.as_mut() {
+                typer = typer.with_recovery(&mut recovered[idx]);
+            }
+            let file = if idx == entry {
+                typer.resolve_root(parsed)?
+            } else {
+                typer.resolve_file(parsed)?
+            };
+            files[idx] = Some(file);
+        }
+
+        let mut linked = vec![false; names.len()];
+        let mut reachable = vec![entry];
+        while let Some(idx) = reachable.pop() {
+            if !linked[idx] {
+                linked[idx] = true;
+                reachable.extend(imports[idx].iter().map(|(imported, _)| *imported));
+            }
+        }
+
+        let mut warnings = vec![];
+        let mut stmts = vec![];
+        for idx in order {
+            let file = files[idx].as_ref().unwrap();
+            if !linked[idx] {
+                warnings.push(
+                    Error::unused_module(names[idx].as_str())
+                        .with_position(file_position(file.id())),
+                );
+                if let Some(recovered) = recovered.as_mut() {
+                    recovered[idx].failed.clear();
+                }
+                continue;
+            }
+
+            if let Some(recovered) = recovered.as_ref() {
+                for entity in &recovered[idx].failed {
+                    let mut entity = entity.borrow_mut();
+                    let mangled = mangle(names[idx].as_str(), entity.name());
+                    entity.set_name(mangled);
+                }
+            }
+
+            for stmt in file.stmts() {
+                if let HirStmtKind::Item(entity) = stmt.inner() {
+                    let mut entity = entity.borrow_mut();
+                    let mangled = mangle(names[idx].as_str(), entity.name());
+                    entity.set_name(mangled);
+                }
+                stmts.push(stmt.clone());
+            }
+        }
+
+        let entry = files[entry].as_ref().unwrap();
+        let mut file = HirFile::new(entry.id(), name.to_owned(), stmts);
+        if let Some(entry) = entry.get_entry() {
+            file.set_entry(entry);
+        }
+        Ok(Program { file, warnings })
+    }
+
+    /// the modules imported by each module along with the position of the import.
+    fn module_imports(modules: &[ParsedModule]) -> Result<Vec<Vec<(usize, Position)>>, Error> {
+        let mut by_name = HashMap::new();
+        for (idx, module) in modules.iter().enumerate() {
+            if let Some(first) = by_name.insert(module.name.as_str(), idx) {
+                let first = modules[first].path.display().to_string();
+                return Err(Error::duplicate_module(module.name.as_str(), first.as_str())
+                    .with_position(file_position(module.file.file_id)));
+            }
+        }
+
+        let mut imports = vec![];
+        for module in modules {
+            let mut imported: Vec<(usize, Position)> = vec![];
+            for import in &module.file.imports {
+                let name = import.module_name();
+                let name = module.imports.get(name.as_str()).unwrap_or(&name);
+                match by_name.get(name.as_str()) {
+                    Some(idx) if imported.iter().any(|(other, _)| other == idx) => {}
+                    Some(idx) => imported.push((*idx, import.position)),
+                    None => {
+                        return Err(Error::unknown_module(name.as_str()).with_position(import.position))
+                    }
+                }
+            }
+            imports.push(imported);
+        }
+        Ok(imports)
+    }
+
+    /// every module after the modules it imports.
+    fn import_order(
+        modules: &[ParsedModule],
+        imports: &[Vec<(usize, Position)>],
+    ) -> Result<Vec<usize>, Error> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            Visiting,
+            Done,
+        }
+
+        fn visit(
+            idx: usize,
+            modules: &[ParsedModule],
+            imports: &[Vec<(usize, Position)>],
+            states: &mut Vec<State>,
+            path: &mut Vec<usize>,
+            order: &mut Vec<usize>,
+        ) -> Result<(), Error> {
+            states[idx] = State::Visiting;
+            path.push(idx);
+            for (imported, position) in imports[idx].iter() {
+                match states[*imported] {
+                    State::Done => {}
+                    State::Unvisited => visit(*imported, modules, imports, states, path, order)?,
+                    State::Visiting => {
+                        let start = path.iter().position(|idx| idx == imported).unwrap();
+                        if let Some(err) = Analysis::constant_cycle(modules, &path[start..]) {
+                            return Err(err);
+                        }
+                        let cycle = path[start..]
+                            .iter()
+                            .chain(std::iter::once(imported))
+                            .map(|idx| modules[*idx].name.clone())
+                            .collect();
+                        return Err(Error::import_cycle(cycle).with_position(*position));
+                    }
+                }
+            }
+            path.pop();
+            states[idx] = State::Done;
+            order.push(idx);
+            Ok(())
+        }
+
+        let mut states = vec![State::Unvisited; modules.len()];
+        let mut order = vec![];
+        for idx in 0..modules.len() {
+            if states[idx] == State::Unvisited {
+                visit(idx, modules, imports, &mut states, &mut vec![], &mut order)?;
+            }
+        }
+        Ok(order)
+    }
+
+    /// the error of the immutable globals of modules importing each other that are initialized
+    /// by each other, by name and file. The modules are an import cycle either way, the chain
+    /// of constants is the reason when there is one.
+    fn constant_cycle(modules: &[ParsedModule], cycle: &[usize]) -> Option<Error> {
+        let constants = cycle
+            .iter()
+            .flat_map(|idx| {
+                modules[*idx].file.stmts.iter().filter_map(move |stmt| match stmt.kind() {
+                    StmtKind::Item(item) => match item.kind() {
+                        ItemKind::Variable {
+                            vis,
+                            mutable: false,
+                            name,
+                            init: Some(init),
+                            ..
+                        } => Some((*idx, *vis, name, init.as_ref())),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // the constant a name of an initializer in module idx refers to, its own before an
+        // imported one.
+        let find = |idx: usize, module: Option<&str>, name: &str| {
+            let declared = |(other, vis, ident, _): &(usize, Visibility, &Identifier, &Expr)| {
+                ident.kind().value == name
+                    && match module {
+                        Some(module) => modules[*other].name == module,
+                        None => *other == idx || *vis == Visibility::Public,
+                    }
+            };
+            constants
+                .iter()
+                .position(|constant| constant.0 == idx && declared(constant))
+                .or_else(|| constants.iter().position(declared))
+        };
+        let uses = constants
+            .iter()
+            .map(|(idx, _, _, init)| {
+                let mut names = vec![];
+                Self::constant_names(init, &mut names);
+                names
+                    .into_iter()
+                    .filter_map(|(module, name)| find(*idx, module.as_deref(), name))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        fn visit(constant: usize, uses: &[Vec<usize>], path: &mut Vec<usize>) -> Option<usize> {
+            if path.contains(&constant) {
+                return Some(constant);
+            }
+            path.push(constant);
+            for used in &uses[constant] {
+                if let Some(start) = visit(*used, uses, path) {
+                    return Some(start);
+                }
+            }
+            path.pop();
+            None
+        }
+
+        (0..constants.len()).find_map(|constant| {
+            let mut path = vec![];
+            let start = visit(constant, &uses, &mut path)?;
+            let start = path.iter().position(|constant| *constant == start).unwrap();
+            let chain = path[start..].iter().chain(std::iter::once(&path[start]));
+            // the chain only runs through one module when it is not why they import each other.
+            let crosses = path[start..]
+                .iter()
+                .any(|constant| constants[*constant].0 != constants[path[start]].0);
+            if !crosses {
+                return None;
+            }
+            let chain = chain
+                .map(|constant| {
+                    let (idx, _, name, _) = constants[*constant];
+                    format!("{} ({})", name.kind().value, modules[idx].path.display())
+                })
+                .collect();
+            let position = constants[path[start]].2.position();
+            Some(Error::constant_cycle(chain).with_position(position))
+        })
+    }
+
+    /// the names an initializer reads, with the module they are qualified by.
+    fn constant_names<'e>(expr: &'e Expr, names: &mut Vec<(Option<String>, &'e str)>) {
+        match expr.kind() {
+            ExprKind::Name(name) => names.push((None, name.kind().value.as_str())),
+            ExprKind::Field(operand, name) => {
+                if let Some(module) = Self::module_path(operand) {
+                    names.push((Some(module), name.kind().value.as_str()));
+                }
+            }
+            // the left operands of a chain are read in a loop, from the first operand.
+            ExprKind::Binary(_, left, right) => {
+                let mut rights = vec![right];
+                let mut first = left;
+                while let ExprKind::Binary(_, left, right) = first.kind() {
+                    rights.push(right);
+                    first = left;
+                }
+                Self::constant_names(first, names);
+                for right in rights.into_iter().rev() {
+                    Self::constant_names(right, names);
+                }
+            }
+            ExprKind::Unary(_, operand) => Self::constant_names(operand, names),
+            _ => {}
+        }
+    }
+
+    /// `shapes.square` of `shapes.square.SIDES`.
+    fn module_path(expr: &Expr) -> Option<String> {
+        match expr.kind() {
+            ExprKind::Name(name) => Some(name.kind().value.clone()),
+            ExprKind::Field(operand, name) => {
+                Some(format!("{}.{}", Self::module_path(operand)?, name.kind().value))
+            }
+            _ => None,
+        }
+    }
+
+    /// the module declaring `main`, there must be exactly one.
+    fn find_entry(modules: &[ParsedModule]) -> Result<usize, Error> {
+        let mut entry: Option<usize> = None;
+        for (idx, module) in modules.iter().enumerate() {
+            for stmt in &module.file.stmts {
+                let name = match stmt.kind() {
+                    StmtKind::Item(item) => match item.get_name() {
+                        Some(name) if name.kind().value == ENTRY_NAME => name,
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                match entry {
+                    Some(first) => {
+                        let err = Error::duplicate_entry(
+                            ENTRY_NAME,
+                            modules[first].name.as_str(),
+                            module.name.as_str(),
+                        );
+                        return Err(err.with_position(name.position()));
+                    }
+                    None => entry = Some(idx),
+                }
+            }
+        }
+
+        entry.ok_or_else(|| {
+            Error::entry_not_found(ENTRY_NAME.to_string()).with_position(Position::default())
+        })
+    }
+
+    /// the public items of the imported modules, two modules may not both provide a name. The
+    /// items of a module that failed are imported as well so using them is reported as such.
+    fn imports_scope(
+        names: &[String],
+        imports: &[(usize, Position)],
+        files: &[Option<HirFile>],
+        failed: Option<&[Recovered]>,
+        parsed: &ParsedFile,
+    ) -> (Scope, HashMap<String, Error>) {
+        let mut scope = Scope::new(ScopeKind::Imports, None);
+        let mut provided_by: HashMap<String, usize> = HashMap::new();
+        let mut provided = vec![];
+        let mut private = HashMap::new();
+        let mut hidden = HashMap::new();
+        for (idx, _) in imports {
+            let file = files[*idx].as_ref().unwrap();
+            let resolved = file.stmts().iter().filter_map(|stmt| match stmt.inner() {
+                HirStmtKind::Item(entity) => Some(entity),
+                _ => None,
+            });
+            let failed = failed.map(|failed| failed[*idx].failed.as_slice());
+            for entity in resolved.chain(failed.unwrap_or_default()) {
+
+                let borrow = entity.borrow();
+                let name = borrow.name().to_owned();
+                if borrow.visibility() != Visibility::Public {
+                    private.insert(name, names[*idx].as_str());
+                    continue;
+                }
+
+                // a name the file declares itself hides the imported one.
+                if Self::declares(parsed, name.as_str()) {
+                    continue;
+                }
+                // a name provided by two modules is an error once it is used without its module.
+                if let Some(first) = provided_by.insert(name.clone(), *idx) {
+                    let err = Error::ambiguous_import(
+                        name.as_str(),
+                        names[first].as_str(),
+                        names[*idx].as_str(),
+                    );
+                    hidden.insert(name, err);
+                    continue;
+                }
+                provided.push((name, entity.clone()));
+            }
+        }
+
+        for (name, entity) in provided {
+            if !hidden.contains_key(&name) {
+                scope.add_element(name.as_str(), entity);
+            }
+        }
+        for (name, module) in private {
+            if !provided_by.contains_key(&name) {
+                let err = Error::private_import(name.as_str(), module);
+                hidden.insert(name, err);
+            }
+        }
+        let mut roots = vec![];
+        for (idx, _) in imports {
+            let root = names[*idx].split('.').next().unwrap();
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+        for root in roots {
+            scope.add_child(Self::module_scope(root, names, imports, files, failed));
+        }
+        (scope, hidden)
+    }
+
+    /// the scope of the module named path, `shapes.square.area()` is looked up through it. It
+    /// holds every item of the module when the file imports it, public or not, and a scope for
+    /// each module the file imports below it.
+    fn module_scope(
+        path: &str,
+        names: &[String],
+        imports: &[(usize, Position)],
+        files: &[Option<HirFile>],
+        failed: Option<&[Recovered]>,
+    ) -> ScopeRef {
+        let mut scope = Scope::new(ScopeKind::Module(path.to_owned()), None);
+        let mut below = vec![];
+        for (idx, _) in imports {
+            let name = names[*idx].as_str();
+            if name == path {
+                let file = files[*idx].as_ref().unwrap();
+                let resolved = file.stmts().iter().filter_map(|stmt| match stmt.inner() {
+                    HirStmtKind::Item(entity) => Some(entity),
+                    _ => None,
+                });
+                let failed = failed.map(|failed| failed[*idx].failed.as_slice());
+                for entity in resolved.chain(failed.unwrap_or_default()) {
+                    let item = entity.borrow().name().to_owned();
+                    scope.add_element(item.as_str(), entity.clone());
+                }
+            } else if let Some(rest) = name.strip_prefix(format!("{}.", path).as_str()) {
+                let child = rest.split('.').next().unwrap();
+                if !below.contains(&child) {
+                    below.push(child);
+                }
+            }
+        }
+        for child in below {
+            let path = format!("{}.{}", path, child);
+            scope.add_child(Self::module_scope(path.as_str(), names, imports, files, failed));
+        }
+        Rc::new(scope)
+    }
+
+    fn declares(parsed: &ParsedFile, name: &str) -> bool {
+        parsed.stmts.iter().any(|stmt| match stmt.kind() {
+            StmtKind::Item(item) => {
+                item.get_name().map(|ident| ident.kind().value.as_str()) == Some(name)
+            }
+            _ => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    use crate::analysis::{Analysis, Generation, ParsedModule, Scope, ScopeKind};
+    use crate::code_gen::CodeGen;
+    use crate::syntax::Parser;
+    use crate::system::{discover_sources, module_name, File, FileMap};
+    use crate::LanguageMode;
+    use oxide::{compiled::CompiledObject, CompiledModule, Value, Vm};
+
+    const PROJECT: &[(&str, &str)] = &[
+        (
+            "app.au",
+            "use geometry\nuse util.text\n\n\
+            fn helper() i64 = 2\n\
+            fn main() i64 {\n    let greeting = \"hello\"\n    let p = point(3, 4)\n    \
+            p.length2() + util.text.twice(helper())\n}\n",
+        ),
+        (
+            "geometry.au",
+            "pub struct Point {\n    pub x i64\n    pub y i64\n    \
+            pub fn length2(self) i64 = (self.x * self.x) + (self.y * self.y)\n}\n\
+            fn helper() i64 = 100\n\
+            pub fn point(x i64, y i64) Point = Point { x: x, y: y }\n",
+        ),
+        (
+            "util/text.au",
+            "pub fn twice(x i64) i64 {\n    let greeting = \"hello\"\n    x + x\n}\n",
+        ),
+        ("scratch.au", "fn unused() i64 = 1\n"),
+    ];
+
+    fn parse(file: &File) -> crate::syntax::ParsedFile {
+        let mut parser = Parser::new(file);
+        parser.init().and_then(|_| parser.parse_file()).unwrap()
+    }
+
+    fn check(modules: &[(&str, &str)]) -> Result<Vec<String>, String> {
+        let modules = modules
+            .iter()
+            .map(|(path, source)| {
+                let path = PathBuf::from(path);
+                ParsedModule {
+                    name: module_name(&path),
+                    path,
+                    file: parse(&File::raw_test(source.to_string())),
+                    imports: HashMap::new(),
+                }
+            })
+            .collect();
+        Analysis::new()
+            .check_program("app", modules, LanguageMode::Default)
+            .map(|program| program.warnings.iter().map(|w| w.to_string()).collect())
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_compile_project_directory() {
+        let root = std::env::temp_dir().join(format!("auburn-project-{}", std::process::id()));
+        for (path, source) in PROJECT {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+
+        let sources = discover_sources(&root).unwrap();
+        let mut file_map = FileMap::new();
+        let modules = sources
+            .iter()
+            .map(|path| ParsedModule {
+                name: module_name(path),
+                path: path.clone(),
+                file: parse(&file_map.open_file(root.join(path)).unwrap()),
+                imports: HashMap::new(),
+            })
+            .collect::<Vec<_>>();
+        std::fs::remove_dir_all(&root).unwrap();
+        let names = modules.iter().map(|m| m.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["app", "geometry", "scratch", "util.text"]);
+
+        let program = Analysis::new()
+            .check_program("app", modules, LanguageMode::Default)
+            .unwrap();
+        // a module nothing imports is checked so its errors are reported, but it is not linked.
+        let warnings = program.warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            ["module 'scratch' is never imported, it is checked but not linked"]
+        );
+
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        let (module, source_map) =
+            CodeGen::build_with_source_map(&file_map, &program.file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        let bytes = CompiledModule::from_module(module).unwrap().to_bytes();
+
+        // the constant pool is shared by the files of the program.
+        assert_eq!(bytes.windows(5).filter(|bytes| bytes == b"hello").count(), 1);
+        let files = source_map.files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+        assert_eq!(files.len(), 3);
+        assert!(files[1].ends_with("geometry.au"));
+
+        let compiled = CompiledModule::from_bytes(&bytes).unwrap();
+        let mut names = compiled
+            .objects()
+            .iter()
+            .filter_map(|object| match object {
+                CompiledObject::Function(function) => Some(function.name.as_str()),
+                CompiledObject::Struct { name, .. } => Some(name.as_str()),
+                CompiledObject::Constant(_) => None,
+            })
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "app::helper",
+                "app::main",
+                "geometry::Point",
+                "geometry::helper",
+                "geometry::point",
+                "util::text::twice"
+            ]
+        );
+
+        let mut vm = Vm::new();
+        let module = compiled.load(&mut vm);
+        vm.run_module(module).unwrap();
+        assert!(matches!(vm.top(), Value::I64(29)));
+    }
+
+    #[test]
+    fn test_program_diagnostics() {
+        let main = "fn main() i64 = 0\n";
+        assert_eq!(
+            check(&[("a.b.au", main), ("a/b.au", "fn f() i64 = 0\n")]),
+            Err("module 'a.b' is defined by more than one file".to_string())
+        );
+        assert_eq!(
+            check(&[("app.au", main), ("tool.au", main)]),
+            Err("entry function 'main' is defined by both 'app' and 'tool'".to_string())
+        );
+        assert_eq!(
+            check(&[("app.au", "use shapes.square\nfn main() i64 = 0\n")]),
+            Err("module 'shapes.square' is not part of the program".to_string())
+        );
+        assert_eq!(
+            check(&[("app.au", "use lib\nfn main() i64 = 0\n"), ("lib.au", "use app\n")]),
+            Err("modules import each other: app -> lib -> app".to_string())
+        );
+
+        let lib = "pub fn area() i64 = 1\nfn secret() i64 = 2\n";
+        assert_eq!(
+            check(&[("app.au", "use lib\nfn main() i64 = area() + secret()\n"), ("lib.au", lib)]),
+            Err("'secret' is private to module 'lib'".to_string())
+        );
+        assert_eq!(
+            check(&[("app.au", "use lib\nfn main() i64 = volume()\n"), ("lib.au", lib)]),
+            Err("use of undeclared identifier 'volume'".to_string())
+        );
+        assert_eq!(
+            check(&[
+                ("app.au", "use lib\nuse other\nfn main() i64 = area()\n"),
+                ("lib.au", lib),
+                ("other.au", lib),
+            ]),
+            Err("'area' is imported from both 'lib' and 'other'".to_string())
+        );
+        // a local declaration hides an imported one.
+        assert_eq!(
+            check(&[("app.au", "use lib\nfn area() i64 = 3\nfn main() i64 = area()\n"), ("lib.au", lib)]),
+            Ok(vec![])
+        );
+    }
+
+    #[test]
+    fn test_visibility_across_modules() {
+        // the associated functions of a structure use its private members on any instance.
+        let lib = "pub struct Point {\n    pub x i64\n    y i64\n    \
+            pub fn new(x i64) Point = Point { x: x, y: x }\n    \
+            fn secret(self) i64 = self.y\n    \
+            pub fn same(self, other Point) bool = self.secret() == other.secret()\n}\n\
+            pub fn origin() Point = Point.new(0)\nfn hidden() i64 = 1\n\
+            pub struct Countdown {\n    pub left i64\n    fn next(mut self) Option[i64] = none\n}\n\
+            pub struct Count {\n    pub to i64\n    \
+            fn iter(self) Countdown = countdown(self.to)\n}\n\
+            pub fn countdown(left i64) Countdown = Countdown { left: left }\n\
+            pub fn count(to i64) Count = Count { to: to }\n";
+        let program = |main: &str| {
+            let main = format!("use lib\nfn main() i64 {{\n    let p = origin()\n{}\n}}\n", main);
+            check(&[("app.au", main.as_str()), ("lib.au", lib)])
+        };
+        assert_eq!(program("    let same bool = p.same(origin())\n    p.x"), Ok(vec![]));
+        assert_eq!(
+            program("    p.y"),
+            Err("unable to access private field 'y' of structure 'lib.Point'".to_string())
+        );
+        assert_eq!(
+            program("    p.secret()"),
+            Err("unable to access private associated function 'secret' of structure 'lib.Point'"
+                .to_string())
+        );
+        assert_eq!(
+            program("    lib.hidden()"),
+            Err("'hidden' is private to module 'lib'".to_string())
+        );
+
+        // a for loop calls `next` whatever its visibility, but no other call it generates does.
+        assert_eq!(program("    for i in countdown(3) {\n    }\n    p.x"), Ok(vec![]));
+        let next = "unable to access private associated function 'next' of structure \
+            'lib.Countdown'";
+        assert_eq!(
+            program("    mut c = countdown(3)\n    c.next()\n    p.x"),
+            Err(next.to_string())
+        );
+        assert_eq!(
+            program("    mut c = countdown(3)\n    while let some(i) = c.next() {\n    }\n    p.x"),
+            Err(next.to_string())
+        );
+        assert_eq!(
+            program("    for i in count(3) {\n    }\n    p.x"),
+            Err("unable to access private associated function 'iter' of structure 'lib.Count'"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn test_qualified_names() {
+        let square = "pub struct Side {\n    pub len i64\n    \
+            pub fn new(len i64) Side = Side { len: len }\n}\n\
+            pub fn area(side Side) i64 = side.len * side.len\nfn secret() i64 = 2\n";
+        let program = |main: &str| {
+            let main = format!(
+                "use shapes.square\nuse shapes.circle\nfn main() i64 {{\n{}\n}}\n",
+                main
+            );
+            check(&[
+                ("app.au", main.as_str()),
+                ("shapes/square.au", square),
+                ("shapes/circle.au", "pub fn area(r i64) i64 = r * r * 3\n"),
+            ])
+        };
+        // the items of both modules are named through their path, `area` alone is ambiguous.
+        let main = "    let side shapes.square.Side = shapes.square.Side.new(2)\n    \
+            let f = shapes.circle.area\n    shapes.square.area(side) + f(1)";
+        assert_eq!(program(main), Ok(vec![]));
+        assert_eq!(
+            program("    shapes.square.secret()"),
+            Err("'secret' is private to module 'shapes.square'".to_string())
+        );
+        assert_eq!(
+            program("    shapes.square.volume()"),
+            Err("module 'shapes.square' has no item named 'volume'".to_string())
+        );
+        assert_eq!(
+            program("    shapes.area(1)"),
+            Err("module 'shapes' has no item named 'area'".to_string())
+        );
+        assert_eq!(
+            program("    let side shapes.cube.Side = 1\n    0"),
+            Err("module 'shapes.cube' is not part of the program".to_string())
+        );
+        // a local hides the module its name starts.
+        assert_eq!(
+            program("    let shapes = 1\n    shapes.circle.area(1)"),
+            Err("type must be a struct, found 'i32'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_entities_of_an_earlier_check_are_stale() {
+        let source = "struct Point {\n    pub x i64\n}\nfn main() {}\n";
+        let mut analysis = Analysis::new();
+        let first = analysis
+            .check(parse(&File::raw_test(source.to_string())), LanguageMode::Default)
+            .unwrap();
+        let point = first.find_entity_by_name("Point").unwrap();
+        let generation = point.borrow().generation();
+        assert!(generation.is_live());
+
+        // checking again redefines 'Point', the entity of the first check is not a part of it.
+        analysis
+            .check(parse(&File::raw_test(source.to_string())), LanguageMode::Default)
+            .unwrap();
+        let current = Generation::current();
+        assert_ne!(generation, current);
+        assert_eq!(
+            generation.check("Point").unwrap_err().to_string(),
+            format!(
+                "internal compiler error: 'Point' is from generation {} but generation {} is \
+                being checked",
+                generation.0, current.0
+            )
+        );
+        // using it is an internal compiler error.
+        let mut scope = Scope::new(ScopeKind::Block, None);
+        let added = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            scope.add_element("Point", point.clone())
+        }));
+        assert_eq!(added.is_err(), cfg!(debug_assertions));
+    }
+}
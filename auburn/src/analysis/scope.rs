@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 use std::rc::Rc;
 
-use crate::analysis::EntityRef;
+use crate::analysis::{EntityRef, Generation};
+use crate::error::Error;
 use crate::system::FileId;
 
 pub type ScopeRef = Rc<Scope>;
@@ -10,11 +11,18 @@ pub type ScopeRef = Rc<Scope>;
 pub enum ScopeKind {
     Invalid,
     Prelude,
+    /// public items of the modules imported by a file.
+    Imports,
     File { file_id: FileId, file_name: String },
+    /// the items of an imported module and the imported modules below it, `shapes` holds
+    /// `shapes.square`. Named by the full name of the module.
+    Module(String),
     Param(String),
     Block,
     Struct(String),
     StructMethods(String),
+    /// the variants of an enum.
+    Enum(String),
 }
 
 #[derive(Debug, Clone)]
@@ -29,6 +37,8 @@ pub struct Scope {
     elements: Vec<EntityRef>,
     parent: Option<ScopeRef>,
     children: Vec<ScopeRef>,
+    /// the check that made the scope, see `Generation`.
+    generation: Generation,
 }
 
 impl Scope {
@@ -39,6 +49,7 @@ impl Scope {
             elements: vec![],
             parent,
             children: vec![],
+            generation: Generation::current(),
         }
     }
 
@@ -50,6 +61,25 @@ impl Scope {
         self.elements.len()
     }
 
+    /// removes the elements keep refuses, the others stay in order. A shadowed element has no
+    /// name.
+    pub fn retain(&mut self, keep: impl Fn(&EntityRef) -> bool) {
+        let mut names = self
+            .names
+            .drain()
+            .map(|(name, idx)| (idx, name))
+            .collect::<HashMap<_, _>>();
+        let elements = std::mem::take(&mut self.elements);
+        for (idx, element) in elements.into_iter().enumerate() {
+            if keep(&element) {
+                if let Some(name) = names.remove(&idx) {
+                    self.names.insert(name, self.elements.len());
+                }
+                self.elements.push(element);
+            }
+        }
+    }
+
     pub fn get(&self, name: &str) -> Option<&EntityRef> {
         let idx = self.names.get(name)?;
         Some(&self.elements[*idx])
@@ -80,6 +110,13 @@ impl Scope {
     }
 
     pub fn add_element(&mut self, name: &str, entity: EntityRef) {
+        // the scopes of a check only hold the entities of that check.
+        entity.borrow().assert_live();
+        if cfg!(debug_assertions) && !self.generation.is_live() {
+            let scope = format!("the scope of '{}'", name);
+            let current = Generation::current().0;
+            panic!("{}", Error::stale_entity(&scope, self.generation.0, current));
+        }
         let idx = self.len();
         self.names.insert(name.to_string(), idx);
         self.elements.push(entity);
@@ -0,0 +1,313 @@
+use crate::analysis::entity::{ClosureInfo, Path};
+use crate::analysis::scope::ScopeKind;
+use crate::analysis::typer::{Typer, FUNCTION};
+use crate::analysis::{Entity, EntityInfo, EntityRef};
+use crate::error::Error;
+use crate::ir::ast::{
+    Expr, ExprKind, FunctionBody, Ident, Identifier, Item, ItemKind, Node, Spec, SpecKind,
+    Visibility,
+};
+use crate::ir::hir::{AddressMode, HirExpr, HirExprInner, HirExprKind, HirExprPtr, ResultMeta};
+use crate::syntax::Position;
+use crate::types::{Type, TypeKind};
+use crate::utils::new_ptr;
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// the parameters and return of a function literal.
+type LambdaSignature = (Vec<Box<Item>>, Box<Spec>);
+
+/// a function literal whose body is being resolved.
+pub(super) struct ResolvingClosure {
+    /// index of the parameter scope of the literal in the scope stack, the locals of the scopes
+    /// below it are captured.
+    depth: usize,
+    captures: Vec<EntityRef>,
+}
+
+impl<'a> Typer<'a> {
+    /// `fn(<params>) <ret> = body` is resolved like a local function named after the function
+    /// declaring it, the locals of the enclosing functions its body uses are its captures. Where
+    /// a function is expected, the parameters and the return left without a type have the ones
+    /// of the expected function.
+    pub(crate) fn resolve_lambda(
+        &mut self,
+        params: &[Box<Item>],
+        ret: &Spec,
+        body: &FunctionBody,
+        expected: Option<Rc<Type>>,
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let inferred = self.infer_signature(params, ret, expected.clone());
+        let (params, ret) = match inferred.as_ref() {
+            Some((params, ret)) => (params.as_slice(), ret.as_ref()),
+            None => {
+                Self::check_annotated(params, expected, position)?;
+                (params, ret)
+            }
+        };
+
+        let enclosing = self
+            .scope_stack
+            .iter()
+            .rev()
+            .find_map(|scope| match scope.kind() {
+                ScopeKind::Param(name) => Some(format!("{}.lambda", name)),
+                _ => None,
+            })
+            .unwrap_or_else(|| "lambda".to_owned());
+        let name = Identifier::new_with_position(Ident::from(enclosing.as_str()), position);
+        let entity = new_ptr(Entity::resolving(
+            Visibility::Private,
+            enclosing,
+            self.type_map.get_invalid(),
+        ));
+
+        self.closures.push(ResolvingClosure {
+            depth: self.scope_stack.len(),
+            captures: vec![],
+        });
+        // the body is not within the loops or the methods of the function declaring the literal.
+        let state = std::mem::replace(&mut self.state, FUNCTION);
+        let res = self.resolve_function(
+            entity.clone(),
+            Visibility::Private,
+            &name,
+            &[],
+            &[],
+            params,
+            ret,
+            body,
+            position,
+            true,
+            None,
+        );
+        self.state = state;
+        if let Some((params, ret)) = inferred.as_ref() {
+            self.forget_resolved_specs(params, ret);
+        }
+        let closure = self.closures.pop().expect("the closure is resolving");
+        res?;
+
+        let ty = entity.deref().borrow().ty();
+        let kind = std::mem::replace(entity.borrow_mut().kind_mut(), EntityInfo::Resolving);
+        let mut function = match kind {
+            EntityInfo::Function(function) => function,
+            _ => unreachable!("a function literal resolves to a function"),
+        };
+        function.local = true;
+        entity.borrow_mut().resolve(
+            ty.clone(),
+            EntityInfo::Closure(ClosureInfo {
+                function,
+                captures: closure.captures,
+            }),
+            Path::empty(),
+        );
+
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Lambda(entity),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
+    }
+
+    /// the parameters and return of a literal with the types it leaves out replaced by
+    /// `SpecKind::Resolved` specs of the ones of expected, none when expected is not a function
+    /// of as many parameters.
+    fn infer_signature(
+        &mut self,
+        params: &[Box<Item>],
+        ret: &Spec,
+        expected: Option<Rc<Type>>,
+    ) -> Option<LambdaSignature> {
+        let expected = Type::inner(expected?);
+        let (expected_params, return_type) = match expected.kind() {
+            TypeKind::Function {
+                params,
+                return_type,
+            } => (params, return_type),
+            _ => return None,
+        };
+        if Self::param_count(params) != expected_params.len() {
+            return None;
+        }
+
+        let mut types = expected_params.iter();
+        let mut inferred = vec![];
+        for param in params {
+            match param.kind() {
+                ItemKind::Param { names, spec, init } if Self::unannotated(spec) => {
+                    // each name is a parameter of its own, they may have different types.
+                    for name in names {
+                        let spec = Spec::new_with_position(SpecKind::Resolved, name.position());
+                        self.resolved_specs.insert(spec.id(), types.next()?.clone());
+                        let kind = ItemKind::Param {
+                            names: vec![name.clone()],
+                            spec: Some(Box::new(spec)),
+                            init: init.clone(),
+                        };
+                        inferred.push(Box::new(Item::new_with_position(kind, param.position())));
+                    }
+                }
+                ItemKind::Param { names, .. } => {
+                    types.nth(names.len() - 1);
+                    inferred.push(param.clone());
+                }
+                _ => {
+                    types.next();
+                    inferred.push(param.clone());
+                }
+            }
+        }
+        let ret = if ret.is_infer() {
+            let spec = Spec::new_with_position(SpecKind::Resolved, ret.position());
+            self.resolved_specs.insert(spec.id(), return_type.clone());
+            Box::new(spec)
+        } else {
+            Box::new(ret.clone())
+        };
+        Some((inferred, ret))
+    }
+
+    /// drops the types of the `Resolved` specs of an inferred signature once it is resolved.
+    fn forget_resolved_specs(&mut self, params: &[Box<Item>], ret: &Spec) {
+        let specs = params.iter().filter_map(|param| match param.kind() {
+            ItemKind::Param {
+                spec: Some(spec), ..
+            } => Some(spec.as_ref()),
+            _ => None,
+        });
+        for spec in specs.chain(std::iter::once(ret)) {
+            self.resolved_specs.remove(&spec.id());
+        }
+    }
+
+    /// the parameters of a literal whose signature is not inferred must declare their types,
+    /// the expected function tells why it is not inferred when there is one.
+    fn check_annotated(
+        params: &[Box<Item>],
+        expected: Option<Rc<Type>>,
+        position: Position,
+    ) -> Result<(), Error> {
+        let unannotated = params.iter().find_map(|param| match param.kind() {
+            ItemKind::Param { names, spec, .. } if Self::unannotated(spec) => names.first(),
+            _ => None,
+        });
+        let name = match unannotated {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let expected = expected.map(Type::inner);
+        match expected.as_ref().map(|ty| ty.kind()) {
+            Some(TypeKind::Function { params: expected, .. }) => {
+                let err = Error::lambda_params(expected.len(), Self::param_count(params));
+                Err(err.with_position(position))
+            }
+            _ => {
+                let err = Error::uninferred_param(name.kind().value.as_str());
+                Err(err.with_position(name.position()))
+            }
+        }
+    }
+
+    /// the number of parameters of a literal, a parameter may declare several names.
+    fn param_count(params: &[Box<Item>]) -> usize {
+        params
+            .iter()
+            .map(|param| match param.kind() {
+                ItemKind::Param { names, .. } => names.len(),
+                _ => 1,
+            })
+            .sum()
+    }
+
+    fn unannotated(spec: &Option<Box<Spec>>) -> bool {
+        spec.as_ref().is_none_or(|spec| spec.is_infer())
+    }
+
+    /// a trailing block is the last actual of a call, the callee must take a function as its
+    /// last parameter. params and actuals are the ones following a receiver.
+    pub(super) fn check_trailing_block(
+        name: &str,
+        params: &[Rc<Type>],
+        actuals: &[Box<Expr>],
+    ) -> Result<(), Error> {
+        let (block, block_params) = match actuals.last().map(|block| (block, block.kind())) {
+            Some((block, ExprKind::Lambda { params, trailing: true, .. })) => (block, params),
+            _ => return Ok(()),
+        };
+        let function_params = |ty: &Rc<Type>| match Type::inner(ty.clone()).kind() {
+            TypeKind::Function { params, .. } => Some(params.len()),
+            _ => None,
+        };
+        let is_function = |ty: &Rc<Type>| function_params(ty).is_some();
+        if params.len() == actuals.len() {
+            if let Some(expected) = params.last().and_then(function_params) {
+                if expected == block_params.len() {
+                    return Ok(());
+                }
+                let err = Error::trailing_block_params(name, expected, block_params.len());
+                return Err(err.with_position(block.position()));
+            }
+        }
+        let err = match params.iter().rposition(is_function) {
+            Some(index) => Error::misplaced_trailing_block(name, index + 1, params.len()),
+            None => Error::trailing_block_without_function(name),
+        };
+        Err(err.with_position(block.position()))
+    }
+
+    /// the name a call is reported by, the one of its callee when it has one.
+    pub(super) fn callee_name(operand: &Expr) -> String {
+        match operand.kind() {
+            ExprKind::Name(name) => name.kind().value.clone(),
+            ExprKind::Field(_, name) => name.kind().value.clone(),
+            _ => "function".to_owned(),
+        }
+    }
+
+    /// whether the parameter scope at index of the scope stack is the one of a function literal.
+    pub(super) fn is_closure_scope(&self, index: usize) -> bool {
+        self.closures.iter().any(|closure| closure.depth == index)
+    }
+
+    /// records the entity named by name as a capture of the function literals being resolved
+    /// when it is a local declared outside of them. Captured values are copies, so a mutable
+    /// local is not captured, assigning it would not change the copy.
+    pub(super) fn capture(&mut self, name: &Identifier) -> Result<(), Error> {
+        let value = name.kind().value.as_str();
+        let found = self
+            .scope_stack
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, scope)| scope.shallow_lookup(value).map(|entity| (index, entity)));
+        let (index, entity) = match found {
+            Some((index, entity)) if self.closures.iter().any(|closure| closure.depth > index) => {
+                (index, entity)
+            }
+            _ => return Ok(()),
+        };
+        if !matches!(self.scope_stack[index].kind(), ScopeKind::Param(_) | ScopeKind::Block) {
+            return Ok(());
+        }
+        let mutable = match entity.deref().borrow().kind() {
+            EntityInfo::Variable(variable) if variable.global => return Ok(()),
+            EntityInfo::Variable(variable) => variable.mutable,
+            EntityInfo::Param(_) => false,
+            _ => return Ok(()),
+        };
+        if mutable || entity.deref().borrow().ty().is_mutable() {
+            return Err(Error::captured_mutable(value).with_position(name.position()));
+        }
+
+        for closure in self.closures.iter_mut().filter(|closure| closure.depth > index) {
+            if !closure.captures.iter().any(|capture| Rc::ptr_eq(capture, &entity)) {
+                closure.captures.push(entity.clone());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+use crate::analysis::typer::Typer;
+use crate::analysis::{
+    AssociatedFunctionInfo, Entity, EntityInfo, EntityRef, Path, Scope, ScopeKind, StructureInfo,
+};
+use crate::error::Error;
+use crate::ir::ast::{Expr, ExprKind, Node, Visibility};
+use crate::ir::hir::{
+    BlockExpr, BuiltinFunction, HirExpr, HirExprInner, HirExprKind, HirExprPtr, MapFunction,
+    MirNode, ResultMeta, VecFunction, WeakFunction,
+};
+use crate::syntax::Position;
+use crate::types::{Type, TypeKind};
+use std::rc::Rc;
+
+/// name of the built in growable vector.
+pub(crate) const VEC_TYPE: &str = "Vec";
+/// name of the built in hash map.
+pub(crate) const MAP_TYPE: &str = "Map";
+/// name of the built in weak reference.
+pub(crate) const WEAK_TYPE: &str = "Weak";
+/// name of the entity holding the functions of slices, it cannot be named in a program.
+const SLICE_TYPE: &str = "slice";
+
+/// a function of a built in type, the types of its parameters and its return type.
+type Signature = (BuiltinFunction, Vec<Rc<Type>>, Rc<Type>);
+
+impl<'a> Typer<'a> {
+    /// `Vec[T]` and `Map[K, V]` name the built in types unless their names are shadowed.
+    pub(crate) fn is_collection_type(&self, expr: &Expr) -> bool {
+        match expr.kind() {
+            ExprKind::Index { operand, .. } => match operand.kind() {
+                ExprKind::Name(ident) => {
+                    let name = ident.kind().value.as_str();
+                    (name == VEC_TYPE || name == MAP_TYPE) && self.deep_lookup(name).is_none()
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// the receiver of `Vec[T].new()`, it names the entity of the type like the name of a
+    /// structure names the structure.
+    pub(crate) fn resolve_collection_type(&mut self, expr: &Expr) -> Result<HirExprPtr, Error> {
+        let ty = self.resolve_named_spec_type(expr)?;
+        let entity = self.collection_entity(ty.clone());
+        let inner = HirExprInner::new(
+            ty.address_mode(),
+            ResultMeta::new(false, false, false, false, true),
+            HirExprKind::Name(entity),
+        );
+        Ok(Rc::new(HirExpr::new(inner, expr.position(), ty)))
+    }
+
+    /// `Map[K, V]` from the type arguments, a tuple of the key and the value type. Keys are
+    /// compared by their contents, so they are limited to the types compared that way.
+    pub(crate) fn resolve_map_type(&mut self, arguments: &Expr) -> Result<Rc<Type>, Error> {
+        let (key, value) = match arguments.kind() {
+            ExprKind::Tuple(elements) if elements.len() == 2 => (&elements[0], &elements[1]),
+            ExprKind::Tuple(elements) => {
+                let err = Error::type_argument_count(MAP_TYPE, 2, elements.len());
+                return Err(err.with_position(arguments.position()));
+            }
+            _ => {
+                let err = Error::type_argument_count(MAP_TYPE, 2, 1);
+                return Err(err.with_position(arguments.position()));
+            }
+        };
+
+        let key_type = self.resolve_named_spec_type(key.as_ref())?;
+        // a function is a key by its identity, see `Value::hash_constant`.
+        let valid_key = match key_type.kind() {
+            TypeKind::Bool | TypeKind::Char | TypeKind::String | TypeKind::Function { .. } => true,
+            kind => kind.is_integer(),
+        };
+        if !valid_key {
+            let err = Error::invalid_map_key_type(key_type.as_ref());
+            return Err(err.with_position(key.position()));
+        }
+        let value_type = self.resolve_named_spec_type(value.as_ref())?;
+        Ok(self.insert_type(TypeKind::Map {
+            key_type,
+            value_type,
+        }))
+    }
+
+    /// the entity holding the methods of `Vec[T]`, `Map[K, V]` or `Weak[T]`, they are associated
+    /// functions of it like the methods of a structure so calls to them are checked the same way.
+    /// Each is created once for every type.
+    pub(crate) fn collection_entity(&mut self, collection_type: Rc<Type>) -> EntityRef {
+        if let Some(entity) = self.type_map.builtin_entity(collection_type.as_ref()) {
+            return entity;
+        }
+        let (name, functions) = match collection_type.kind() {
+            TypeKind::Vector { element_type } => (
+                VEC_TYPE,
+                self.vector_functions(collection_type.clone(), element_type.clone()),
+            ),
+            TypeKind::Map {
+                key_type,
+                value_type,
+            } => (
+                MAP_TYPE,
+                self.map_functions(collection_type.clone(), key_type.clone(), value_type.clone()),
+            ),
+            TypeKind::Weak { inner } => {
+                (WEAK_TYPE, self.weak_functions(collection_type.clone(), inner.clone()))
+            }
+            // slices are lists while running, their length is the one of a vector.
+            TypeKind::Slice { .. } => {
+                let i64_type = self.type_map.get_i64();
+                let len = (
+                    BuiltinFunction::Vec(VecFunction::Len),
+                    vec![collection_type.clone()],
+                    i64_type,
+                );
+                (SLICE_TYPE, vec![len])
+            }
+            _ => unreachable!("the entity of a built in type for {}", collection_type),
+        };
+
+        let structure_info = StructureInfo {
+            fields: Scope::new_ref(ScopeKind::Struct(name.to_owned()), None),
+            methods: Scope::new_ref(ScopeKind::Invalid, None),
+            finalized: false,
+        };
+        let entity = Entity::new_ref(
+            Visibility::Public,
+            name.to_owned(),
+            collection_type,
+            EntityInfo::Structure(structure_info),
+            Path::empty(),
+        );
+
+        let mut methods = Scope::new(ScopeKind::StructMethods(name.to_owned()), None);
+        for (index, (function, params, return_type)) in functions.into_iter().enumerate() {
+            let function_type = self.insert_type(TypeKind::Function {
+                params,
+                return_type,
+            });
+            let body = HirExprInner::new(
+                self.type_map.get_unit().address_mode(),
+                ResultMeta::new(false, false, false, false, false),
+                HirExprKind::Block(BlockExpr {
+                    stmts: vec![],
+                    function_block: true,
+                }),
+            );
+            let info = AssociatedFunctionInfo {
+                entity: entity.clone(),
+                params: Scope::new_ref(ScopeKind::Param(function.name().to_owned()), None),
+                body_scope: None,
+                body: Rc::new(HirExpr::new(body, Position::default(), self.type_map.get_unit())),
+                takes_self: !matches!(
+                    function,
+                    BuiltinFunction::Vec(VecFunction::New)
+                        | BuiltinFunction::Vec(VecFunction::WithCapacity)
+                        | BuiltinFunction::Map(MapFunction::New)
+                        | BuiltinFunction::Weak(WeakFunction::New)
+                ),
+                index,
+                metrics: None,
+                builtin: Some(function),
+            };
+            let method = Entity::new_ref(
+                Visibility::Public,
+                function.name().to_owned(),
+                function_type,
+                EntityInfo::AssociatedFunction(info),
+                Path::empty(),
+            );
+            methods.add_element(function.name(), method);
+        }
+        entity.borrow_mut().as_struct_mut().methods = Rc::new(methods);
+
+        self.type_map.add_builtin_entity(entity.clone());
+        entity
+    }
+
+    /// a count of `reserve` known while compiling is checked here, any other one when it runs.
+    pub(super) fn check_reserve(method: &Entity, actuals: &[HirExprPtr]) -> Result<(), Error> {
+        let reserve = match method.kind() {
+            EntityInfo::AssociatedFunction(info) => matches!(
+                info.builtin,
+                Some(BuiltinFunction::Vec(VecFunction::Reserve))
+                    | Some(BuiltinFunction::Map(MapFunction::Reserve))
+            ),
+            _ => false,
+        };
+        match actuals {
+            [_, count] if reserve => match Self::constant_integer(count.as_ref()) {
+                Some(value) if value < 0 => {
+                    Err(Error::negative_reserve(value).with_position(count.position()))
+                }
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// the source of `extend` and `copy_from` is a vector of the same type, the elements of the
+    /// two are checked like the ones of an assignment.
+    fn vector_functions(&mut self, vector: Rc<Type>, element_type: Rc<Type>) -> Vec<Signature> {
+        use VecFunction::*;
+        let mutable = self.insert_type(TypeKind::Mutable {
+            inner: vector.clone(),
+        });
+        let unit = self.type_map.get_unit();
+        let i64_type = self.type_map.get_i64();
+        vec![
+            (New, vec![], vector.clone()),
+            (WithCapacity, vec![i64_type.clone()], vector.clone()),
+            (Push, vec![mutable.clone(), element_type.clone()], unit.clone()),
+            (Pop, vec![mutable.clone()], element_type.clone()),
+            (Len, vec![vector.clone()], i64_type.clone()),
+            (Extend, vec![mutable.clone(), vector.clone()], unit.clone()),
+            (
+                CopyFrom,
+                vec![
+                    mutable.clone(),
+                    vector.clone(),
+                    i64_type.clone(),
+                    i64_type.clone(),
+                    i64_type.clone(),
+                ],
+                unit.clone(),
+            ),
+            (Fill, vec![mutable.clone(), element_type.clone()], unit.clone()),
+            (
+                FillRange,
+                vec![mutable.clone(), i64_type.clone(), i64_type.clone(), element_type],
+                unit.clone(),
+            ),
+            (Reserve, vec![mutable.clone(), i64_type.clone()], unit.clone()),
+            (Capacity, vec![vector], i64_type),
+            (ShrinkToFit, vec![mutable], unit),
+        ]
+        .into_iter()
+        .map(|(function, params, ty)| (BuiltinFunction::Vec(function), params, ty))
+        .collect()
+    }
+
+    /// a missing key is none rather than an error, `get` and `remove` return an option.
+    fn map_functions(&mut self, map: Rc<Type>, key: Rc<Type>, value: Rc<Type>) -> Vec<Signature> {
+        use MapFunction::*;
+        let mutable = self.insert_type(TypeKind::Mutable { inner: map.clone() });
+        let optional = self.optional_of(value.clone());
+        let unit = self.type_map.get_unit();
+        let bool_type = self.type_map.get_bool();
+        let i64_type = self.type_map.get_i64();
+        vec![
+            (New, vec![], map.clone()),
+            (Insert, vec![mutable.clone(), key.clone(), value], unit.clone()),
+            (Get, vec![map.clone(), key.clone()], optional.clone()),
+            (Remove, vec![mutable.clone(), key.clone()], optional),
+            (Contains, vec![map.clone(), key], bool_type),
+            (Len, vec![map], i64_type.clone()),
+            (Reserve, vec![mutable, i64_type], unit),
+        ]
+        .into_iter()
+        .map(|(function, params, ty)| (BuiltinFunction::Map(function), params, ty))
+        .collect()
+    }
+
+    /// `get` is none once the referent has been collected.
+    fn weak_functions(&mut self, weak: Rc<Type>, referent: Rc<Type>) -> Vec<Signature> {
+        let optional = self.optional_of(referent.clone());
+        vec![
+            (WeakFunction::New, vec![referent], weak.clone()),
+            (WeakFunction::Get, vec![weak], optional),
+        ]
+        .into_iter()
+        .map(|(function, params, ty)| (BuiltinFunction::Weak(function), params, ty))
+        .collect()
+    }
+}
@@ -0,0 +1,293 @@
+use crate::analysis::entity::{Path, VariableInfo};
+use crate::analysis::scope::ScopeKind;
+use crate::analysis::typer::desugar::{Lowered, Synthesizer};
+use crate::analysis::typer::Typer;
+use crate::analysis::{Entity, EntityInfo};
+use crate::error::Error;
+use crate::ir::ast::{
+    Attribute, Expr, ExprKind, FunctionBody, Item, ItemKind, Node, Spec, Stmt, StmtKind, Visibility,
+};
+use crate::ir::hir::{Desugaring, HirExprPtr};
+use crate::syntax::Position;
+use crate::types::Type;
+use std::rc::Rc;
+
+/// a condition holding when the function is called.
+pub(super) const REQUIRES_ATTRIBUTE: &str = "requires";
+/// a condition holding when the function returns.
+pub(super) const ENSURES_ATTRIBUTE: &str = "ensures";
+/// the local holding the value the function returns, named by its postconditions.
+const RESULT_LOCAL: &str = "result";
+
+/// a condition written as `@requires(condition)` or `@ensures(condition)`.
+struct Contract {
+    attribute: &'static str,
+    cond: Box<Expr>,
+    source: String,
+}
+
+/// the contracts of a function.
+pub(super) struct Contracts {
+    function: String,
+    /// the parameters, rendered along with a violated contract. `self` is the receiver.
+    params: Vec<String>,
+    /// the annotated return type, the type of `result`.
+    return_spec: Option<Box<Spec>>,
+    requires: Vec<Contract>,
+    ensures: Vec<Contract>,
+    position: Position,
+}
+
+impl Contracts {
+    pub fn is_empty(&self) -> bool {
+        self.requires.is_empty() && self.ensures.is_empty()
+    }
+
+    pub fn has_postconditions(&self) -> bool {
+        !self.ensures.is_empty()
+    }
+}
+
+impl<'a> Typer<'a> {
+    /// the contracts among the attributes of a function, the other attributes are errors.
+    pub(super) fn contracts_of(
+        name: &str,
+        attributes: &[Attribute],
+        params: &[Box<Item>],
+        return_spec: &Spec,
+        position: Position,
+    ) -> Result<Contracts, Error> {
+        let mut contracts = Contracts {
+            function: name.to_owned(),
+            params: params
+                .iter()
+                .flat_map(|param| match param.kind() {
+                    ItemKind::Param { names, .. } => names
+                        .iter()
+                        .map(|name| name.kind().value.clone())
+                        .collect::<Vec<_>>(),
+                    _ => vec!["self".to_owned()],
+                })
+                .collect(),
+            return_spec: Some(Box::new(return_spec.clone())).filter(|spec| !spec.is_infer()),
+            requires: vec![],
+            ensures: vec![],
+            position,
+        };
+        for attribute in attributes {
+            let name = attribute.name.kind().value.as_str();
+            let attribute_name = match name {
+                REQUIRES_ATTRIBUTE => REQUIRES_ATTRIBUTE,
+                ENSURES_ATTRIBUTE => ENSURES_ATTRIBUTE,
+                super::items::FLAGS_ATTRIBUTE => {
+                    let err = Error::misplaced_attribute(name, "an enum");
+                    return Err(err.with_position(attribute.name.position()));
+                }
+                super::items::FINALIZE_ATTRIBUTE => {
+                    let err = Error::misplaced_attribute(name, "a struct");
+                    return Err(err.with_position(attribute.name.position()));
+                }
+                other => {
+                    let err = Error::unknown_attribute(other);
+                    return Err(err.with_position(attribute.name.position()));
+                }
+            };
+            let cond = match attribute.argument.as_ref() {
+                Some(cond) => cond.clone(),
+                None => {
+                    let err = Error::missing_attribute_argument(name);
+                    return Err(err.with_position(attribute.name.position()));
+                }
+            };
+            let contract = Contract {
+                attribute: attribute_name,
+                cond,
+                source: attribute.source.clone(),
+            };
+            if attribute_name == REQUIRES_ATTRIBUTE {
+                contracts.requires.push(contract);
+            } else {
+                contracts.ensures.push(contract);
+            }
+        }
+        Ok(contracts)
+    }
+
+    /// the body of a function with contracts checked, in the scope of its parameters:
+    /// ```text
+    /// {
+    ///     if precondition {} else { trap }        // for every precondition
+    ///     let result Ret = body
+    ///     if postcondition {} else { trap }       // for every postcondition
+    ///     result
+    /// }
+    /// ```
+    /// a `return` of the body checks the postconditions as well, see `resolve_return_value`.
+    /// The contracts name the parameters as they are when they are checked, a postcondition
+    /// sees the changes the body made to a mutable parameter.
+    pub(super) fn resolve_contract_body(
+        &mut self,
+        contracts: Rc<Contracts>,
+        body: &FunctionBody,
+        expected: Option<Rc<Type>>,
+    ) -> Result<HirExprPtr, Error> {
+        let mut synthesizer = Synthesizer::new(Desugaring::Contract, contracts.position);
+        let (body, block) = match body {
+            FunctionBody::Block(expr) => (expr, true),
+            FunctionBody::Expression(expr) => (expr, false),
+        };
+        let mut stmts = contracts
+            .requires
+            .iter()
+            .map(|contract| Self::contract_check(&mut synthesizer, &contracts, contract, false))
+            .collect::<Vec<_>>();
+        if contracts.has_postconditions() {
+            stmts.extend(Self::checked_result(&mut synthesizer, &contracts, body.clone()));
+        } else {
+            stmts.push(synthesizer.stmt(StmtKind::Expr(body.clone())));
+        }
+
+        let lowered = synthesizer.expr(ExprKind::Block(stmts));
+        self.trace_desugaring(&synthesizer, body.as_ref(), Lowered::Ast(&lowered));
+        self.mark_desugared(&synthesizer);
+        let outer = std::mem::replace(
+            &mut self.postconditions,
+            Some(contracts).filter(|contracts| contracts.has_postconditions()),
+        );
+        let resolved = match lowered.kind() {
+            ExprKind::Block(stmts) if block => {
+                self.resolve_block_expression(stmts, true, expected, lowered.position())
+            }
+            _ => self.resolve_expr(lowered.as_ref(), expected),
+        };
+        self.postconditions = outer;
+        resolved
+    }
+
+    /// the value of `return value`, resolved with the declared return type. In a function with
+    /// postconditions they are checked before it returns.
+    pub(super) fn resolve_return_value(&mut self, value: &Expr) -> Result<HirExprPtr, Error> {
+        let contracts = match self.postconditions.clone() {
+            Some(contracts) => contracts,
+            None => return self.resolve_expr(value, self.return_type.clone()),
+        };
+        let mut synthesizer = Synthesizer::new(Desugaring::Contract, contracts.position);
+        let stmts = Self::checked_result(&mut synthesizer, &contracts, Box::new(value.clone()));
+        let lowered = synthesizer.expr(ExprKind::Block(stmts));
+        self.trace_desugaring(&synthesizer, value, Lowered::Ast(&lowered));
+        self.mark_desugared(&synthesizer);
+        self.resolve_expr(lowered.as_ref(), self.return_type.clone())
+    }
+
+    /// type checks the contracts of a function, the return type is the type of `result`. The
+    /// checks generated for them report their errors as those of an `if`, they are checked first.
+    /// When the return type is inferred the postconditions are left to the generated checks.
+    pub(super) fn check_contracts(
+        &mut self,
+        contracts: &Contracts,
+        return_type: Option<Rc<Type>>,
+    ) -> Result<(), Error> {
+        for contract in &contracts.requires {
+            self.resolve_condition(contract.cond.as_ref(), contract.attribute)?;
+        }
+        let return_type = match return_type {
+            Some(return_type) if contracts.has_postconditions() => return_type,
+            _ => return Ok(()),
+        };
+
+        // the scope of `result` is not part of the function, nothing is generated for it.
+        self.push_scope(ScopeKind::Block);
+        let info = VariableInfo {
+            spec: None,
+            mutable: false,
+            global: false,
+            default: None,
+        };
+        let result = Entity::new_ref(
+            Visibility::Private,
+            RESULT_LOCAL.to_owned(),
+            return_type,
+            EntityInfo::Variable(info),
+            Path::empty(),
+        );
+        self.insert_entity(RESULT_LOCAL, result);
+        let checked = contracts
+            .ensures
+            .iter()
+            .try_for_each(|contract| {
+                self.resolve_condition(contract.cond.as_ref(), contract.attribute)
+                    .map(|_| ())
+            });
+        self.scope_stack.pop();
+        checked
+    }
+
+    /// `let result Ret = value`, the postconditions checked and `result`.
+    fn checked_result(
+        synthesizer: &mut Synthesizer,
+        contracts: &Contracts,
+        value: Box<Expr>,
+    ) -> Vec<Box<Stmt>> {
+        let result = synthesizer.item(ItemKind::Variable {
+            vis: Visibility::Private,
+            mutable: false,
+            name: synthesizer.ident(RESULT_LOCAL),
+            init: Some(value),
+            spec: contracts.return_spec.clone(),
+        });
+        let mut stmts = vec![synthesizer.stmt(StmtKind::Item(result))];
+        stmts.extend(
+            contracts
+                .ensures
+                .iter()
+                .map(|contract| Self::contract_check(synthesizer, contracts, contract, true)),
+        );
+        let result = synthesizer.expr(ExprKind::Name(synthesizer.ident(RESULT_LOCAL)));
+        stmts.push(synthesizer.stmt(StmtKind::Expr(result)));
+        stmts
+    }
+
+    /// `if condition {} else { trap }`, the trap renders the parameters and with_result `result`.
+    fn contract_check(
+        synthesizer: &mut Synthesizer,
+        contracts: &Contracts,
+        contract: &Contract,
+        with_result: bool,
+    ) -> Box<Stmt> {
+        let mut names = contracts.params.clone();
+        if with_result {
+            names.push(RESULT_LOCAL.to_owned());
+        }
+        let values = names
+            .iter()
+            .map(|name| match name.as_str() {
+                "self" => synthesizer.expr(ExprKind::SelfLit),
+                name => synthesizer.expr(ExprKind::Name(synthesizer.ident(name))),
+            })
+            .collect();
+        let rendered = names
+            .iter()
+            .map(|name| format!("{} = {{}}", name))
+            .collect::<Vec<_>>();
+        let mut message = format!(
+            "{} '{}' of '{}' violated",
+            contract.attribute,
+            contract.source.replace('{', "{{").replace('}', "}}"),
+            contracts.function
+        );
+        if !rendered.is_empty() {
+            message = format!("{}: {}", message, rendered.join(", "));
+        }
+
+        let trap = synthesizer.expr(ExprKind::Trap { message, values });
+        let trap = synthesizer.stmt(StmtKind::Expr(trap));
+        let held = synthesizer.expr(ExprKind::Block(vec![]));
+        let violated = synthesizer.expr(ExprKind::Block(vec![trap]));
+        let check = synthesizer.expr(ExprKind::If {
+            cond: contract.cond.clone(),
+            body: held,
+            else_if: Some(violated),
+        });
+        synthesizer.stmt(StmtKind::Expr(check))
+    }
+}
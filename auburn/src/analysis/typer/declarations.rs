@@ -0,0 +1,215 @@
+use crate::analysis::entity::Path;
+use crate::analysis::typer::{Typer, ASSOCIATIVE_FUNCTION};
+use crate::analysis::{DeclaredInfo, EntityInfo, EntityRef};
+use crate::error::Error;
+use crate::ir::ast::{FunctionBody, Item, ItemKind, Stmt, StmtKind};
+use crate::ir::hir::MirNode;
+use crate::syntax::Position;
+use crate::types::{Type, TypeKind};
+use std::ops::Deref;
+use std::rc::Rc;
+
+impl<'a> Typer<'a> {
+    /// declares the items of the module before any body is resolved, the fields of every
+    /// structure first and then the signatures of the functions and methods. A body uses the
+    /// items it calls by their signatures, so it can call an item declared after it and two
+    /// functions can call each other. An item that fails to be declared is resolved where it is
+    /// reached, its error is reported there. Returns how many items were declared.
+    pub(super) fn declare_items(&mut self, stmts: &[Box<Stmt>]) -> usize {
+        let items = stmts
+            .iter()
+            .filter_map(|stmt| match stmt.kind() {
+                StmtKind::Item(item) => Some(item.as_ref()),
+                _ => None,
+            })
+            .filter_map(|item| {
+                let name = item.get_name()?;
+                Some((item, self.shallow_lookup(name.kind().value.as_str())?))
+            })
+            .collect::<Vec<_>>();
+
+        let mut declared = 0;
+        for (item, entity) in items.iter() {
+            if let ItemKind::Struct { .. } = item.kind() {
+                declared += self.try_declare(entity, None) as usize;
+            }
+        }
+        for (item, entity) in items {
+            match item.kind() {
+                ItemKind::Function { .. } => declared += self.try_declare(&entity, None) as usize,
+                ItemKind::Struct { .. } if entity.deref().borrow().is_struct() => {
+                    let methods = entity.deref().borrow().as_struct().methods.clone();
+                    for method in methods.elements() {
+                        declared += self.try_declare(method, Some(&entity)) as usize;
+                    }
+                }
+                _ => {}
+            }
+        }
+        declared
+    }
+
+    /// the method of structure a call uses. A method that is not declared yet, used by the
+    /// default of a field or with a type only known from its body, is declared or resolved
+    /// then.
+    pub(super) fn declared_method(
+        &mut self,
+        structure: &EntityRef,
+        method: EntityRef,
+        position: Position,
+    ) -> Result<EntityRef, Error> {
+        let borrow = method.deref().borrow();
+        let item = match borrow.kind() {
+            EntityInfo::Unresolved(item) => item.clone(),
+            EntityInfo::Resolving => {
+                let err = Error::cyclic_reference(borrow.name());
+                return Err(err.with_position(position));
+            }
+            _ => {
+                std::mem::drop(borrow);
+                return Ok(method);
+            }
+        };
+        std::mem::drop(borrow);
+        if self.declare_function(&method, item.as_ref(), Some(structure))? {
+            return Ok(method);
+        }
+
+        let index = structure
+            .deref()
+            .borrow()
+            .as_struct()
+            .methods
+            .elements()
+            .iter()
+            .position(|element| Rc::ptr_eq(element, &method))
+            .expect("a method is a member of its structure");
+        let state = std::mem::replace(&mut self.state, ASSOCIATIVE_FUNCTION);
+        let self_entity = self.self_entity.replace(structure.clone());
+        let res = self.resolve_method(method, item.as_ref(), index);
+        self.state = state;
+        self.self_entity = self_entity;
+        res
+    }
+
+    /// declares the unresolved item entity names, a method when structure is given. When it
+    /// fails the typer is put back as it was and the item is left unresolved.
+    fn try_declare(&mut self, entity: &EntityRef, structure: Option<&EntityRef>) -> bool {
+        let item = match entity.deref().borrow().kind() {
+            EntityInfo::Unresolved(item) => item.clone(),
+            // declared while an item before it was.
+            _ => return false,
+        };
+        let depth = self.scope_stack.len();
+        let state = self.state;
+        let declared = match item.kind() {
+            ItemKind::Struct { name, fields, .. } => {
+                // the fields of a structure can not use a function that returns it.
+                entity.deref().borrow_mut().to_resolving();
+                self.declare_struct(entity.clone(), name, fields).map(|_| true)
+            }
+            _ => self.declare_function(entity, item.as_ref(), structure),
+        };
+        match declared {
+            Ok(declared) => declared,
+            Err(_) => {
+                self.scope_stack.truncate(depth);
+                self.state = state;
+                self.self_entity = None;
+                self.closures.clear();
+                self.chain_receiver = None;
+                self.resolved.clear();
+                let invalid = self.type_map.get_invalid();
+                let kind = EntityInfo::Unresolved(item);
+                entity.deref().borrow_mut().resolve(invalid, kind, Path::empty());
+                false
+            }
+        }
+    }
+
+    /// gives the function its type when its signature is enough for it, it is then declared.
+    /// A function whose type is only known from its body is left unresolved.
+    fn declare_function(
+        &mut self,
+        entity: &EntityRef,
+        item: &Item,
+        structure: Option<&EntityRef>,
+    ) -> Result<bool, Error> {
+        let self_entity = std::mem::replace(&mut self.self_entity, structure.cloned());
+        let signature = self.signature_type(item);
+        self.self_entity = self_entity;
+        let (ty, takes_self) = match signature? {
+            Some(signature) => signature,
+            None => return Ok(false),
+        };
+        let info = DeclaredInfo {
+            entity: structure.cloned(),
+            takes_self,
+        };
+        let path = self.current_path_from_root();
+        entity
+            .deref()
+            .borrow_mut()
+            .resolve(ty, EntityInfo::Declared(info), path);
+        Ok(true)
+    }
+
+    /// the type of a function and whether it takes self when every parameter is annotated and
+    /// so is the return type, unless the body is a block. None for a generic function and for
+    /// one whose return type is inferred from its body.
+    fn signature_type(&mut self, item: &Item) -> Result<Option<(Rc<Type>, bool)>, Error> {
+        let (type_params, params, ret, body) = match item.kind() {
+            ItemKind::Function {
+                type_params,
+                params,
+                ret,
+                body,
+                ..
+            } => (type_params, params, ret, body),
+            _ => return Ok(None),
+        };
+        let inferred = ret.is_infer() && matches!(body, FunctionBody::Expression(_));
+        if !type_params.is_empty() || inferred {
+            return Ok(None);
+        }
+
+        let mut param_types = vec![];
+        let mut takes_self = false;
+        for param in params {
+            match param.kind() {
+                ItemKind::Param {
+                    names,
+                    spec: Some(spec),
+                    ..
+                } => {
+                    let ty = self.resolve_spec(spec)?.ty();
+                    param_types.extend(names.iter().map(|_| ty.clone()));
+                }
+                ItemKind::SelfParam { mutable } if param_types.is_empty() => {
+                    let ty = match self.self_entity.as_ref() {
+                        Some(entity) => entity.deref().borrow().ty(),
+                        None => return Ok(None),
+                    };
+                    let ty = if *mutable {
+                        self.insert_type(TypeKind::Mutable { inner: ty })
+                    } else {
+                        ty
+                    };
+                    takes_self = true;
+                    param_types.push(ty);
+                }
+                _ => return Ok(None),
+            }
+        }
+        let return_type = if ret.is_infer() {
+            self.type_map.get_unit()
+        } else {
+            self.resolve_spec(ret)?.ty()
+        };
+        let ty = self.insert_type(TypeKind::Function {
+            params: param_types,
+            return_type,
+        });
+        Ok(Some((ty, takes_self)))
+    }
+}
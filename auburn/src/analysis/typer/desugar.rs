@@ -0,0 +1,197 @@
+use crate::analysis::typer::Typer;
+use crate::error::Error;
+use crate::ir::ast::{
+    Expr, ExprKind, Ident, Identifier, Item, ItemKind, Node, NodeId, Stmt, StmtKind,
+};
+use crate::ir::hir::{
+    AddressMode, Desugaring, HirExpr, HirExprInner, HirExprKind, HirExprPtr, ResultMeta,
+};
+use crate::syntax::Position;
+use crate::types::Type;
+use crate::utils::timing::{self, PhaseTimer};
+use crate::utils::MirPrinter;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// builds the nodes of a desugaring. Every node is placed at the user written construct it
+/// replaces and is recorded as generated once it is resolved.
+pub(crate) struct Synthesizer {
+    desugaring: Desugaring,
+    origin: Position,
+    generated: Vec<NodeId>,
+    /// the values of the `Resolved` operands, by the id of the operand.
+    operands: HashMap<NodeId, HirExprPtr>,
+}
+
+impl Synthesizer {
+    pub fn new(desugaring: Desugaring, origin: Position) -> Self {
+        Self {
+            desugaring,
+            origin,
+            generated: vec![],
+            operands: HashMap::new(),
+        }
+    }
+
+    pub fn expr(&mut self, kind: ExprKind) -> Box<Expr> {
+        let expr = Expr::new_with_position(kind, self.origin);
+        self.generated.push(expr.id());
+        Box::new(expr)
+    }
+
+    /// an operand whose value is already resolved.
+    pub fn resolved(&mut self, value: HirExprPtr) -> Box<Expr> {
+        let expr = self.expr(ExprKind::Resolved);
+        self.operands.insert(expr.id(), value);
+        expr
+    }
+
+    /// the values of the operands created by `resolved`.
+    pub fn take_operands(&mut self) -> HashMap<NodeId, HirExprPtr> {
+        std::mem::take(&mut self.operands)
+    }
+
+    pub fn stmt(&mut self, kind: StmtKind) -> Box<Stmt> {
+        let stmt = Stmt::new_with_position(kind, self.origin);
+        self.generated.push(stmt.id());
+        Box::new(stmt)
+    }
+
+    pub fn item(&mut self, kind: ItemKind) -> Box<Item> {
+        let item = Item::new_with_position(kind, self.origin);
+        self.generated.push(item.id());
+        Box::new(item)
+    }
+
+    /// a node placed at a user written part of the construct, its errors are reported there.
+    pub fn expr_at(&mut self, kind: ExprKind, position: Position) -> Box<Expr> {
+        let expr = Expr::new_with_position(kind, position);
+        self.generated.push(expr.id());
+        Box::new(expr)
+    }
+
+    pub fn origin(&self) -> Position {
+        self.origin
+    }
+
+    pub fn ident(&self, name: &str) -> Identifier {
+        Identifier::new_with_position(Ident::from(name), self.origin)
+    }
+
+    /// a value computed by generated code, for desugarings of expressions that are already typed.
+    pub fn hir_expr(&self, kind: HirExprKind, ty: Rc<Type>) -> Rc<HirExpr> {
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            kind,
+        );
+        let expr = HirExpr::new(inner, self.origin, ty);
+        expr.mark_generated(self.desugaring);
+        Rc::new(expr)
+    }
+}
+
+/// what a construct was lowered to, printed by `--trace desugar`.
+pub(crate) enum Lowered<'a> {
+    Ast(&'a dyn Debug),
+    Hir(&'a HirExpr),
+}
+
+impl<'a> Typer<'a> {
+    /// resolves the nodes built by the synthesizer. An error raised by a generated node is
+    /// moved to the user written construct along with a note naming the desugaring.
+    pub(crate) fn resolve_desugared<T>(
+        &mut self,
+        synthesizer: &Synthesizer,
+        resolve: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let _timer = PhaseTimer::start(timing::DESUGAR);
+        self.mark_desugared(synthesizer);
+
+        resolve(self)
+            .map_err(|err| Self::repoint_error(err, synthesizer.desugaring, synthesizer.origin))
+    }
+
+    /// records the nodes built by the synthesizer as generated. The errors they raise are left
+    /// where they are, for desugarings mixing them with user written code outside of the origin.
+    pub(crate) fn mark_desugared(&mut self, synthesizer: &Synthesizer) {
+        for id in synthesizer.generated.iter() {
+            self.generated.insert(*id, synthesizer.desugaring);
+        }
+    }
+
+    fn repoint_error(err: Error, desugaring: Desugaring, origin: Position) -> Error {
+        // user written parts of the construct are at their own position inside of the origin.
+        if *err.pos() != origin && origin.contains(err.pos()) {
+            return err;
+        }
+
+        err.with_position(origin)
+            .with_note(format!("in code generated for this {}", desugaring))
+    }
+
+    pub(crate) fn generated_by(&self, node: &impl Node) -> Option<Desugaring> {
+        self.generated.get(&node.id()).copied()
+    }
+
+    pub(crate) fn trace_desugaring(
+        &self,
+        synthesizer: &Synthesizer,
+        before: &dyn Debug,
+        after: Lowered,
+    ) {
+        if !self.trace_desugar {
+            return;
+        }
+
+        let start = synthesizer.origin.start();
+        println!(
+            "desugar {} at {}:{}",
+            synthesizer.desugaring,
+            start.line(),
+            start.column()
+        );
+        println!("before:\n{:#?}", before);
+        match after {
+            Lowered::Ast(after) => println!("after:\n{:#?}", after),
+            Lowered::Hir(after) => {
+                println!("after:");
+                MirPrinter::print_expr(after);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::typer::Typer;
+    use crate::error::Error;
+    use crate::ir::hir::Desugaring;
+    use crate::syntax::{Coord, FilePos, Position, Span};
+    use crate::system::FileId;
+
+    fn position(start: usize, end: usize) -> Position {
+        let file_pos = FilePos::new(Coord(1, start + 1), Coord(1, end + 1));
+        Position::new(Span(start, end), file_pos, FileId(0))
+    }
+
+    #[test]
+    fn test_errors_are_moved_to_the_origin() {
+        let origin = position(4, 14);
+        let repoint = |err: Error| {
+            let err = Typer::repoint_error(err, Desugaring::CompoundAssignment, origin);
+            (*err.pos(), err.notes().to_vec())
+        };
+
+        // an error in a user written part of the construct is left alone.
+        let err = Error::immutable_entity("x").with_position(position(9, 14));
+        assert_eq!(repoint(err), (position(9, 14), vec![]));
+
+        let note = vec!["in code generated for this compound assignment".to_string()];
+        for generated in [origin, Position::default()].iter() {
+            let err = Error::immutable_entity("x").with_position(*generated);
+            assert_eq!(repoint(err), (origin, note.clone()));
+        }
+    }
+}
@@ -1,26 +1,44 @@
+use crate::analysis::typer::desugar::{Lowered, Synthesizer};
 use crate::analysis::typer::{
-    Typer, ALLOW_CONTROL_FLOW_EXPRESSIONS, ASSOCIATIVE_FUNCTION, BLOCK, EXPR_RESULT_USED, FUNCTION,
-    FUNCTION_BODY, SELF_PARAM_IDENT,
+    Typer, ALLOW_CONTROL_FLOW_EXPRESSIONS, ASSOCIATIVE_FUNCTION, BLOCK, BLOCK_RESULT,
+    EXPR_RESULT_USED, FUNCTION, FUNCTION_BODY, SELF_PARAM_IDENT,
 };
-use crate::analysis::{EntityInfo, EntityRef};
-use crate::error::Error;
+use crate::analysis::{DeclaredInfo, EntityInfo, EntityRef};
+use crate::error::{Error, ErrorKind};
 use crate::ir::ast::{
-    BinaryOp, Expr, ExprKind, Identifier, Node, StructExprField, UnaryOp, Visibility,
+    BinaryOp, Expr, ExprKind, Identifier, Node, Spec, StructExprField, UnaryOp, Visibility,
 };
 use crate::ir::hir::{
-    AddressMode, AssociatedFunctionExpr, BinaryExpr, BlockExpr, CallExpr, FieldExpr, HirExpr,
-    HirExprInner, HirExprKind, HirExprPtr, HirStmtKind, IfExpr, IfExprBranch, IndexExpr, LoopExpr,
-    MethodExpr, MirNode, ResultMeta, StructExpr, TupleExpr, UnaryExpr, WhileExpr,
+    AddressMode, ArrayExpr, AssociatedFunctionExpr, BinaryExpr, BlockExpr, CallExpr, CastExpr, CoalesceExpr,
+    Desugaring, EnumFunction, EnumFunctionExpr, FieldExpr, HirExpr, HirExprInner, HirExprKind,
+    HirExprPtr, HirStmtKind, IfExpr, IfExprBranch, IndexExpr, LogExpr, LoopExpr, MethodExpr,
+    MirNode, OptionalChainExpr, ResultMeta, StructExpr, TrapExpr, TupleExpr, UnaryExpr,
+    VariantExpr, WhileExpr,
 };
 use crate::syntax::Position;
 use crate::types::{Type, TypeKind};
 use crate::{analysis::entity::StructureInfo, ir::hir::TupleIndex};
-use crate::{analysis::scope::ScopeKind, ir::ast::Stmt};
+use crate::{
+    analysis::scope::ScopeKind,
+    ir::ast::{Stmt, StmtKind},
+};
 
 use itertools::Itertools;
+use oxide::vm::LogLevel;
+use std::convert::TryFrom;
 use std::ops::Deref;
 use std::rc::Rc;
 
+/// name of the built in logging module.
+const LOG_MODULE: &str = "log";
+
+/// name of the built in function returning its actual that the optimizer cannot see through.
+const BLACK_BOX: &str = "black_box";
+const EVAL: &str = "eval";
+
+/// associated function of flags, the value without any flag set.
+const EMPTY_FUNCTION: &str = "empty";
+
 macro_rules! with_state {
     ($typer:expr, $state:expr, $body:tt) => {{
         let old_state = $typer.state;
@@ -31,96 +49,63 @@ macro_rules! with_state {
     }};
 }
 
+/// the type the branches of an if or the arms of a match agree on.
+pub(super) struct BranchTypes {
+    /// the expected type, or the type of the first branch that does not diverge.
+    ty: Option<Rc<Type>>,
+    /// the type is that of a literal branch, `0` in `if c { 0 } else { n }`.
+    literal: bool,
+}
+
+impl BranchTypes {
+    pub(super) fn new(expected_type: Option<Rc<Type>>) -> Self {
+        Self {
+            ty: expected_type,
+            literal: false,
+        }
+    }
+
+    pub(super) fn into_type(self) -> Option<Rc<Type>> {
+        self.ty
+    }
+}
+
 impl<'src> Typer<'src> {
     pub(crate) fn resolve_expr(
         &mut self,
         expr: &Expr,
         expected_type: Option<Rc<Type>>,
+    ) -> Result<Rc<HirExpr>, Error> {
+        if self.depth == self.max_depth {
+            let err = Error::expr_too_deeply_nested(self.max_depth);
+            return Err(err.with_position(expr.position()));
+        }
+        self.depth += 1;
+        let result = self.resolve_expr_kind(expr, expected_type);
+        self.depth -= 1;
+        result
+    }
+
+    fn resolve_expr_kind(
+        &mut self,
+        expr: &Expr,
+        expected_type: Option<Rc<Type>>,
     ) -> Result<Rc<HirExpr>, Error> {
         // println!("Resolving Expr: {}", expr.kind().name());
+        let generated = self.generated_by(expr);
+        // the kinds are resolved by functions of their own, this frame is on the stack once for
+        // every level of nesting.
         let expr = match expr.kind() {
-            ExprKind::Integer(val) => {
-                let ty = self.type_map.get_i32();
-                Rc::new(HirExpr::new(
-                    HirExprInner::new(
-                        AddressMode::Value,
-                        ResultMeta::literal(),
-                        HirExprKind::Integer(*val as i64),
-                    ),
-                    expr.position(),
-                    ty,
-                ))
-            }
-            ExprKind::Float(val) => {
-                let ty = self.type_map.get_f32();
-                Rc::new(HirExpr::new(
-                    HirExprInner::new(
-                        AddressMode::Value,
-                        ResultMeta::literal(),
-                        HirExprKind::Float(*val),
-                    ),
-                    expr.position(),
-                    ty,
-                ))
-            }
-            ExprKind::String(val) => {
-                let ty = self.type_map.get_string();
-                Rc::new(HirExpr::new(
-                    HirExprInner::new(
-                        AddressMode::Address,
-                        ResultMeta::literal(),
-                        HirExprKind::String(val.clone()),
-                    ),
-                    expr.position(),
-                    ty,
-                ))
-            }
-            ExprKind::Char(val) => {
-                let ty = self.type_map.get_char();
-                Rc::new(HirExpr::new(
-                    HirExprInner::new(
-                        AddressMode::Value,
-                        ResultMeta::literal(),
-                        HirExprKind::Char(*val),
-                    ),
-                    expr.position(),
-                    ty,
-                ))
-            }
-            ExprKind::Name(ident) => {
-                let name = self.resolve_ident(ident)?;
-                let ty = name.deref().borrow().ty();
-                let mutable = match name.deref().borrow().kind() {
-                    EntityInfo::Variable(variable) => {
-                        ResultMeta::new(variable.mutable, false, ty.is_mutable(), false, false)
-                    }
-                    EntityInfo::Structure(_structure) => {
-                        ResultMeta::new(false, false, false, false, true)
-                    }
-                    EntityInfo::Param(_local_info) => {
-                        ResultMeta::new(false, false, ty.is_mutable(), false, false)
-                    }
-                    _ => ResultMeta::new(false, false, false, false, false),
-                };
-
-                Rc::new(HirExpr::new(
-                    HirExprInner::new(AddressMode::Address, mutable, HirExprKind::Name(name)),
-                    expr.position(),
-                    ty,
-                ))
-            }
-            ExprKind::Bool(val) => {
-                let ty = self.type_map.get_bool();
-                Rc::new(HirExpr::new(
-                    HirExprInner::new(
-                        ty.address_mode(),
-                        ResultMeta::literal(),
-                        HirExprKind::Bool(*val),
-                    ),
-                    expr.position(),
-                    ty,
-                ))
-            }
+            ExprKind::Integer(_)
+            | ExprKind::Float(_)
+            | ExprKind::String(_)
+            | ExprKind::Char(_)
+            | ExprKind::Bool(_)
+            | ExprKind::NoneLit => self.resolve_literal(expr, expected_type.as_ref()),
+            ExprKind::Name(ident) => self.resolve_ident(ident).and_then(|name| {
+                Self::check_not_generic(&name, expr.position())?;
+                Ok(Self::name_expr(name, expr.position()))
+            }),
             ExprKind::Binary(op, left, right) => self.resolve_binary(
                 *op,
                 left.as_ref(),
@@ -128,190 +113,146 @@ impl<'src> Typer<'src> {
                 // expected_type.clone(),
                 None,
                 expr.position(),
-            )?,
+            ),
             ExprKind::Unary(op, expr) => {
-                self.resolve_unary(*op, expr.as_ref(), expected_type.clone(), expr.position())?
-            }
-            ExprKind::Block(stmts) => {
-                self.resolve_block_expression(stmts, false, expr.position())?
+                self.resolve_unary(*op, expr.as_ref(), expected_type.clone(), expr.position())
             }
+            ExprKind::Block(stmts) => self.resolve_block_expression(
+                stmts,
+                false,
+                Self::branch_type(expected_type.as_ref()),
+                expr.position(),
+            ),
             ExprKind::StructExpr { name, fields } => {
-                let ty = self.resolve_type_expr(name.as_ref())?;
-                let entity_borrow = ty.deref().borrow();
-                let struct_type = entity_borrow.ty();
-                // println!("StructExpr: {}", struct_type);
-                match entity_borrow.kind() {
-                    EntityInfo::Structure(structure) => {
-                        self.resolve_struct_expr(struct_type, fields, structure, expr.position())?
-                    }
-                    _ => {
-                        let err = Error::expected_struct_type(struct_type.as_ref())
-                            .with_position(name.position());
-                        return Err(err);
-                    }
-                }
-            }
-            ExprKind::SelfLit => {
-                if self.check_state(ASSOCIATIVE_FUNCTION) {
-                    let self_entity = match self.deep_lookup(SELF_PARAM_IDENT) {
-                        Some(entity) => entity,
-                        None => {
-                            let err =
-                                Error::invalid_self_in_function().with_position(expr.position());
-                            return Err(err);
-                        }
-                    };
-
-                    let self_borrow = self_entity.deref().borrow();
-                    if let EntityInfo::SelfParam { mutable } = self_borrow.kind() {
-                        let mir_inner = HirExprInner::new(
-                            AddressMode::Address,
-                            ResultMeta::new(*mutable, false, false, false, false),
-                            HirExprKind::SelfLit(self_entity.clone()),
-                        );
-                        Rc::new(HirExpr::new(
-                            mir_inner,
-                            expr.position(),
-                            self_entity.deref().borrow().ty(),
-                        ))
-                    } else {
-                        let err = Error::other(
-                            "Compiler Error: 'self' entity is not a Self Entity type".to_owned(),
-                        );
-                        return Err(err.with_position(expr.position()));
-                    }
-                } else {
-                    let err = Error::invalid_self_expression().with_position(expr.position());
-                    return Err(err);
-                }
+                self.resolve_struct_literal(name.as_ref(), fields, expr.position())
             }
+            ExprKind::SelfLit => self.resolve_self_lit(expr.position()),
             ExprKind::Field(operand, field) => {
-                self.resolve_field_access(operand.as_ref(), field.as_ref())?
+                self.resolve_field_expr(operand.as_ref(), field.as_ref(), expr.position())
+            }
+            ExprKind::Call { operand, actual } if self.is_builtin(operand.as_ref(), BLACK_BOX) => {
+                self.resolve_black_box(actual.as_slice(), expected_type.clone(), expr.position())
+            }
+            ExprKind::Call { operand, actual } if self.is_builtin(operand.as_ref(), EVAL) => {
+                self.resolve_eval(actual.as_slice(), expr.position())
+            }
+            ExprKind::Call { operand, actual } if self.is_marker(operand.as_ref()) => {
+                self.resolve_marker(operand.as_ref(), actual.as_slice(), expr.position())
+            }
+            ExprKind::Call { operand, actual } if self.is_gc_builtin(operand.as_ref()) => {
+                self.resolve_gc_builtin(operand.as_ref(), actual.as_slice(), expr.position())
+            }
+            ExprKind::Call { operand, actual } if self.is_reflect_type_call(operand.as_ref()) => {
+                let actuals = actual.as_slice();
+                self.resolve_reflect_type_call(operand.as_ref(), actuals, expr.position())
             }
             ExprKind::Call { operand, actual } => {
-                self.resolve_call(operand.as_ref(), actual.as_slice())?
+                self.resolve_call(operand.as_ref(), actual.as_slice())
+            }
+            ExprKind::Method { name, actual } if self.is_log_receiver(actual[0].as_ref()) => {
+                self.resolve_log_call(name.as_ref(), &actual[1..], expr.position())
+            }
+            ExprKind::Method { name, actual } if self.is_task_receiver(actual[0].as_ref()) => {
+                self.resolve_task_call(name.as_ref(), &actual[1..], expr.position())
+            }
+            ExprKind::Method { name, actual } if self.is_reflect_receiver(actual[0].as_ref()) => {
+                self.resolve_reflect_call(name.as_ref(), &actual[1..], expr.position())
+            }
+            ExprKind::Method { name, actual } if self.is_sys_receiver(actual[0].as_ref()) => {
+                self.resolve_sys_call(name.as_ref(), &actual[1..], expr.position())
+            }
+            // `shapes.square.area(2)` calls an item of a module rather than a method.
+            ExprKind::Method { name, actual } if self.module_path(actual[0].as_ref()).is_some() => {
+                self.resolve_module_call(name.as_ref(), actual)
             }
             ExprKind::Method { name, actual } => {
-                self.resolve_method_call(name.as_ref(), actual, expr.position())?
+                let for_loop_next = self.is_for_loop_next(expr, name.as_ref());
+                self.resolve_method_call(name.as_ref(), actual, expr.position(), for_loop_next)
             }
             ExprKind::Tuple(elements) => {
-                let mut mir_elements = vec![];
-                for element in elements {
-                    let mir_expr = self.resolve_expr(element.as_ref(), None)?;
-                    mir_elements.push(mir_expr);
-                }
-
-                let elements = mir_elements
-                    .iter()
-                    .map(|element| element.ty())
-                    .collect_vec();
-
-                let tuple_expr = TupleExpr {
-                    elements: mir_elements,
-                };
-
-                let tuple_type = self.insert_type(TypeKind::Tuple { elements });
-                let mir_expr_inner = HirExprInner::new(
-                    AddressMode::Value,
-                    ResultMeta::new(false, false, false, true, false),
-                    HirExprKind::Tuple(tuple_expr),
-                );
-                Rc::new(HirExpr::new(mir_expr_inner, expr.position(), tuple_type))
+                self.resolve_tuple(elements, expected_type.as_ref(), expr.position())
+            }
+            ExprKind::Array(elements) => {
+                self.resolve_array(elements, expected_type.clone(), expr.position())
             }
             ExprKind::TupleIndex { operand, element } => {
-                let operand = self.resolve_expr(operand.as_ref(), expected_type.clone())?;
-
-                let result_meta = operand.inner().meta();
-                let mir_expr_inner = HirExprInner::new(
-                    AddressMode::Value,
-                    result_meta.clone(),
-                    HirExprKind::TupleIndex(TupleIndex {
-                        tuple: operand.clone(),
-                        field: *element,
-                    }),
-                );
-                let ty = operand.ty().clone();
-                Rc::new(HirExpr::new(mir_expr_inner, expr.position(), ty))
+                self.resolve_tuple_index(operand.as_ref(), *element, expr.position())
+            }
+            ExprKind::If { .. } => {
+                self.resolve_if(expr, Self::branch_type(expected_type.as_ref()), expr.position())
+            }
+            ExprKind::Match { value, arms } => {
+                let expected_type = Self::branch_type(expected_type.as_ref());
+                self.resolve_match(value.as_ref(), arms, expected_type, expr.position())
             }
-            ExprKind::If { .. } => self.resolve_if(expr, None, expr.position())?,
-            ExprKind::Loop(body) => self.resolve_loop(body.as_ref(), expr.position())?,
+            ExprKind::Loop(body) => self.resolve_loop(body.as_ref(), expr.position()),
             ExprKind::While(cond, body) => {
-                self.resolve_while(cond.as_ref(), body.as_ref(), expr.position())?
+                self.resolve_while(cond.as_ref(), body.as_ref(), expr.position())
             }
-            ExprKind::Break => {
-                if self.check_state(ALLOW_CONTROL_FLOW_EXPRESSIONS) {
-                    let inner = HirExprInner::new(
-                        AddressMode::Value,
-                        ResultMeta::default(),
-                        HirExprKind::Break,
-                    );
-                    Rc::new(HirExpr::new(
-                        inner,
-                        expr.position(),
-                        self.type_map.get_unit(),
-                    ))
-                } else {
-                    let err =
-                        Error::invalid_control_in_loop("break").with_position(expr.position());
-                    return Err(err);
-                }
+            ExprKind::Break | ExprKind::Continue => self.resolve_loop_control(expr),
+            ExprKind::Return(value) => self.resolve_return(value.as_ref(), expr.position()),
+            ExprKind::Index { operand, index } => {
+                self.resolve_index(operand, index, expr.position())
             }
-            ExprKind::Continue => {
-                if self.check_state(ALLOW_CONTROL_FLOW_EXPRESSIONS) {
-                    let inner = HirExprInner::new(
-                        AddressMode::Value,
-                        ResultMeta::default(),
-                        HirExprKind::Continue,
-                    );
-                    Rc::new(HirExpr::new(
-                        inner,
-                        expr.position(),
-                        self.type_map.get_unit(),
-                    ))
-                } else {
-                    let err =
-                        Error::invalid_control_in_loop("continue").with_position(expr.position());
-                    return Err(err);
-                }
+            ExprKind::Cast { operand, spec } => {
+                self.resolve_cast(operand.as_ref(), spec.as_ref(), expr.position())
             }
-            ExprKind::Return(expr) => {
-                if self.check_state(FUNCTION | FUNCTION_BODY)
-                    || self.check_state(ASSOCIATIVE_FUNCTION | FUNCTION_BODY)
-                {
-                    let mir_expr = self.resolve_expr(expr, None)?;
-                    let ty = mir_expr.ty();
-                    let mutable = ResultMeta::new(false, false, ty.is_mutable(), false, false);
-                    let inner = HirExprInner::new(
-                        mir_expr.inner().address_mode(),
-                        mutable,
-                        HirExprKind::Return(mir_expr),
-                    );
-                    Rc::new(HirExpr::new(inner, expr.position(), ty))
-                } else {
-                    let err = Error::invalid_return().with_position(expr.position());
-                    return Err(err);
-                }
+            ExprKind::OptionalField(..) | ExprKind::OptionalMethod { .. } => {
+                self.resolve_optional_chain(expr)
             }
-            ExprKind::Index { operand, index } => {
-                self.resolve_index(operand, index, expr.position())?
+            ExprKind::ChainReceiver => Ok(self
+                .chain_receiver
+                .take()
+                .expect("Compiler Error: chain receiver outside of an optional chain")),
+            ExprKind::Coalesce(value, fallback) => {
+                self.resolve_coalesce(value.as_ref(), fallback.as_ref(), expr.position())
             }
-            /*
-            ExprKind::For {
-                element,
-                expr,
-                body,
-            } => {}
-            */
-            ExprKind::For {
-                element: _,
-                expr: _,
-                body: _,
-            } => {
-                todo!()
+            ExprKind::Range { .. } => {
+                Err(Error::range_outside_for().with_position(expr.position()))
             }
+            ExprKind::Resolved => Ok(self
+                .resolved
+                .get(&expr.id())
+                .cloned()
+                .expect("Compiler Error: resolved operand outside of a desugaring")),
+            ExprKind::For { .. } => self.resolve_for(expr),
+            ExprKind::WhileLet { .. } => self.resolve_while_let(expr),
+            ExprKind::Trap { message, values } => {
+                self.resolve_trap(message, values, expr.position())
+            }
+            ExprKind::Lambda {
+                params, ret, body, ..
+            } => self.resolve_lambda(
+                params,
+                ret.as_ref(),
+                body,
+                expected_type.clone(),
+                expr.position(),
+            ),
             ExprKind::SelfType => {
                 todo!()
             }
+            ExprKind::Spec(_) => Err(Error::type_as_value().with_position(expr.position())),
+        }?;
+
+        if let Some(desugaring) = generated {
+            expr.mark_generated(desugaring);
+        }
+
+        // a value is implicitly optional where an optional of its type is expected.
+        let expr = match expected_type.as_ref().map(|expected| expected.kind()) {
+            Some(TypeKind::Option { inner }) if *inner == expr.ty() => {
+                let optional = Rc::new(HirExpr::new(
+                    expr.inner().clone(),
+                    expr.position(),
+                    expected_type.clone().unwrap(),
+                ));
+                if let Some(desugaring) = generated {
+                    optional.mark_generated(desugaring);
+                }
+                optional
+            }
+            _ => expr,
         };
 
         if let Some(expected_type) = &expected_type {
@@ -320,7 +261,13 @@ impl<'src> Typer<'src> {
             //     expected_type,
             //     expr.ty()
             // );
-            if *expected_type != expr.ty() {
+            // an expression that diverges has no value, it fits where any type is expected.
+            // any value is given where `any` is expected, it is only known while running.
+            if *expected_type != expr.ty()
+                && !Self::diverges(expr.as_ref())
+                && !expr.ty().is_never()
+                && !matches!(expected_type.kind(), TypeKind::Any)
+            {
                 let position = expr
                     .returned_expression()
                     .map(|expr| expr.position())
@@ -336,140 +283,555 @@ impl<'src> Typer<'src> {
         Ok(expr)
     }
 
-    pub(crate) fn resolve_block_expression(
+    fn resolve_literal(
         &mut self,
-        stmts: &[Box<Stmt>],
-        function_body: bool,
-        position: Position,
-    ) -> Result<HirExprPtr, Error> {
-        self.push_scope(ScopeKind::Block);
-        let stmts = with_state!(self, BLOCK, {
-            let mut mir_stmts = vec![];
-            for stmt in stmts {
-                let mir_stmt = self.resolve_stmt(stmt.as_ref())?;
-                mir_stmts.push(mir_stmt);
+        expr: &Expr,
+        expected_type: Option<&Rc<Type>>,
+    ) -> Result<Rc<HirExpr>, Error> {
+        let (address_mode, kind, ty) = match expr.kind() {
+            ExprKind::Integer(val) => {
+                // the literal takes the expected integer type when it fits.
+                let ty = match expected_type {
+                    Some(expected)
+                        if expected.is_integer()
+                            && i64::try_from(*val).is_ok_and(|val| expected.fits_integer(val)) =>
+                    {
+                        expected.clone()
+                    }
+                    _ => self.type_map.get_i32(),
+                };
+                (AddressMode::Value, HirExprKind::Integer(*val as i64), ty)
             }
-
-            Ok(mir_stmts)
-        })?;
-
-        self.pop_scope();
-
-        // the result of the block is the result of the last expression in the block, this includes the addressing, type, and mutability
-        let (address_mode, return_type, mutable) = stmts.last().map_or(
-            (
-                AddressMode::Value,
-                self.type_map.get_unit(),
-                ResultMeta::literal(),
-            ),
-            |stmt| match stmt.inner() {
-                HirStmtKind::Expr(expr) => {
-                    let inner = expr.inner();
-                    (inner.address_mode(), expr.ty(), inner.meta())
+            ExprKind::Float(val) => {
+                // the literal takes the expected float type, an f64 keeps every digit written.
+                let ty = match expected_type {
+                    Some(expected) if expected.is_float() => expected.clone(),
+                    _ => self.type_map.get_f32(),
+                };
+                (AddressMode::Value, HirExprKind::Float(*val), ty)
+            }
+            ExprKind::String(val) => {
+                let ty = self.type_map.get_string();
+                (AddressMode::Address, HirExprKind::String(val.clone()), ty)
+            }
+            ExprKind::Char(val) => {
+                let ty = self.type_map.get_char();
+                (AddressMode::Value, HirExprKind::Char(*val), ty)
+            }
+            ExprKind::Bool(val) => {
+                let ty = self.type_map.get_bool();
+                (ty.address_mode(), HirExprKind::Bool(*val), ty)
+            }
+            ExprKind::NoneLit => match expected_type {
+                Some(expected) if Type::inner(expected.clone()).is_option() => {
+                    (AddressMode::Value, HirExprKind::NoneLit, expected.clone())
                 }
-                _ => (
-                    AddressMode::Value,
-                    stmt.ty(),
-                    ResultMeta::new(false, false, false, false, false),
-                ),
+                _ => return Err(Error::uninferred_none().with_position(expr.position())),
             },
-        );
-
-        let block_expr = BlockExpr {
-            stmts,
-            function_block: function_body,
+            _ => unreachable!("Compiler Error: resolving a literal that is not one"),
         };
-
         Ok(Rc::new(HirExpr::new(
-            HirExprInner::new(address_mode, mutable, HirExprKind::Block(block_expr)),
-            position,
-            return_type,
+            HirExprInner::new(address_mode, ResultMeta::literal(), kind),
+            expr.position(),
+            ty,
         )))
     }
 
-    pub(crate) fn resolve_index(
+    fn resolve_struct_literal(
         &mut self,
-        operand: &Expr,
-        index: &Expr,
+        name: &Expr,
+        fields: &[StructExprField],
         position: Position,
-    ) -> Result<HirExprPtr, Error> {
-        let mir_operand = self.resolve_expr(operand, None)?;
-        let operand_meta = mir_operand.inner().meta();
-        if operand_meta.is_type {
-            let err = Error::expecting_instance_type(mir_operand.ty().as_ref());
-            return Err(err.with_position(operand.position()));
+    ) -> Result<Rc<HirExpr>, Error> {
+        let ty = self.resolve_type_expr(name)?;
+        let entity_borrow = ty.deref().borrow();
+        let struct_type = entity_borrow.ty();
+        // println!("StructExpr: {}", struct_type);
+        match entity_borrow.kind() {
+            EntityInfo::Structure(structure) => {
+                self.resolve_struct_expr(struct_type, fields, structure, position)
+            }
+            _ => {
+                let err = Error::expected_struct_type(struct_type.as_ref())
+                    .with_position(name.position());
+                Err(err)
+            }
         }
+    }
 
-        let operand_type = mir_operand.ty();
-        match Type::inner(operand_type.clone()).kind() {
-            TypeKind::Array { element_type, .. } | TypeKind::Slice { element_type } => {
-                let mir_index = self.resolve_expr(index, None)?;
-                let index_meta = mir_index.inner().meta();
-                if index_meta.is_type {
-                    let err = Error::expecting_instance_type(mir_index.ty().as_ref());
-                    return Err(err.with_position(operand.position()));
-                }
-
-                let mir_index_type = mir_index.ty();
-                if mir_index_type.is_integer() {
-                    let parent_mutable = mir_index.inner().meta();
-                    let meta = ResultMeta::new(
-                        parent_mutable.mutable,
-                        true,
-                        element_type.is_mutable(),
-                        false,
-                        false,
-                    );
-                    Ok(Rc::new(HirExpr::new(
-                        HirExprInner::new(
-                            element_type.address_mode(),
-                            meta,
-                            HirExprKind::Index(IndexExpr {
-                                operand: mir_operand,
-                                index: mir_index,
-                            }),
-                        ),
-                        operand.position(),
-                        element_type.clone(),
-                    )))
-                } else {
-                    let err = Error::incompatible_types(&Type::integer(), mir_index_type.as_ref());
-                    Err(err.with_position(index.position()))
+    fn resolve_self_lit(&mut self, position: Position) -> Result<Rc<HirExpr>, Error> {
+        if self.check_state(ASSOCIATIVE_FUNCTION) {
+            let self_entity = match self.deep_lookup(SELF_PARAM_IDENT) {
+                Some(entity) => entity,
+                None => {
+                    let err = Error::invalid_self_in_function().with_position(position);
+                    return Err(err);
                 }
-            }
-            _ => {
-                let err = Error::invalid_index_type(operand_type.as_ref());
+            };
+
+            let self_borrow = self_entity.deref().borrow();
+            if let EntityInfo::SelfParam { mutable } = self_borrow.kind() {
+                let mir_inner = HirExprInner::new(
+                    AddressMode::Address,
+                    ResultMeta::new(*mutable, false, false, false, false),
+                    HirExprKind::SelfLit(self_entity.clone()),
+                );
+                Ok(Rc::new(HirExpr::new(
+                    mir_inner,
+                    position,
+                    self_entity.deref().borrow().ty(),
+                )))
+            } else {
+                let err = Error::other(
+                    "Compiler Error: 'self' entity is not a Self Entity type".to_owned(),
+                );
                 Err(err.with_position(position))
             }
+        } else if !self.closures.is_empty() && self.deep_lookup(SELF_PARAM_IDENT).is_some() {
+            Err(Error::captured_self().with_position(position))
+        } else {
+            Err(Error::invalid_self_expression().with_position(position))
         }
     }
 
-    pub(crate) fn resolve_expr_to_entity(
+    /// `operand.field`, the item field of a module when the operand names one.
+    fn resolve_field_expr(
         &mut self,
-        expr: &Expr,
-    ) -> Result<(EntityRef, Rc<HirExpr>), Error> {
-        let mir_expr = self.resolve_expr(expr, None)?;
-        let entity = match mir_expr.inner().kind() {
-            HirExprKind::Field(field_expr) => field_expr.field.clone(),
-            HirExprKind::Name(entity) => entity.clone(),
-            _ => {
-                let err = Error::invalid_lvalue();
-                return Err(err.with_position(expr.position()));
+        operand: &Expr,
+        field: &Identifier,
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        match self.module_path(operand) {
+            Some(module) => {
+                let item = self.module_item(&module, field)?;
+                Self::check_not_generic(&item, position)?;
+                Ok(Self::name_expr(item, position))
+            }
+            None => self.resolve_field_access(operand, field),
+        }
+    }
+
+    fn resolve_module_call(
+        &mut self,
+        name: &Identifier,
+        actual: &[Box<Expr>],
+    ) -> Result<Rc<HirExpr>, Error> {
+        let operand = Expr::new_with_position(
+            ExprKind::Field(actual[0].clone(), Box::new(name.clone())),
+            actual[0].position().extended_to(name),
+        );
+        self.resolve_call(&operand, &actual[1..])
+    }
+
+    fn resolve_tuple(
+        &mut self,
+        elements: &[Box<Expr>],
+        expected_type: Option<&Rc<Type>>,
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        // the elements take the types of an expected tuple of the same length.
+        let expected_elements = expected_type.and_then(|ty| match ty.kind() {
+            TypeKind::Tuple { elements: expected } if expected.len() == elements.len() => {
+                Some(expected.clone())
+            }
+            _ => None,
+        });
+        let mut mir_elements = vec![];
+        for (index, element) in elements.iter().enumerate() {
+            let expected = expected_elements
+                .as_ref()
+                .map(|expected| expected[index].clone());
+            let mir_expr = self.resolve_expr(element.as_ref(), expected)?;
+            mir_elements.push(mir_expr);
+        }
+
+        let elements = mir_elements
+            .iter()
+            .map(|element| element.ty())
+            .collect_vec();
+
+        let tuple_expr = TupleExpr {
+            elements: mir_elements,
+        };
+
+        let tuple_type = self.insert_type(TypeKind::Tuple { elements });
+        let mir_expr_inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Tuple(tuple_expr),
+        );
+        Ok(Rc::new(HirExpr::new(mir_expr_inner, position, tuple_type)))
+    }
+
+    fn resolve_tuple_index(
+        &mut self,
+        operand: &Expr,
+        element: u64,
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        let operand = self.resolve_expr(operand, None)?;
+        let tuple_type = Type::inner(operand.ty());
+        let ty = match tuple_type.kind() {
+            TypeKind::Tuple { elements } => match elements.get(element as usize) {
+                Some(ty) => ty.clone(),
+                None => {
+                    let err = Error::tuple_element_out_of_range(&tuple_type, element);
+                    return Err(err.with_position(position));
+                }
+            },
+            _ => {
+                let err = Error::invalid_index_type(tuple_type.as_ref());
+                return Err(err.with_position(position));
+            }
+        };
+
+        let result_meta = operand.inner().meta();
+        let mir_expr_inner = HirExprInner::new(
+            AddressMode::Value,
+            result_meta,
+            HirExprKind::TupleIndex(TupleIndex {
+                tuple: operand.clone(),
+                field: element,
+            }),
+        );
+        Ok(Rc::new(HirExpr::new(mir_expr_inner, position, ty)))
+    }
+
+    /// `break` or `continue`.
+    fn resolve_loop_control(&mut self, expr: &Expr) -> Result<Rc<HirExpr>, Error> {
+        let (name, kind) = match expr.kind() {
+            ExprKind::Break => ("break", HirExprKind::Break),
+            _ => ("continue", HirExprKind::Continue),
+        };
+        if self.check_state(ALLOW_CONTROL_FLOW_EXPRESSIONS) {
+            let inner = HirExprInner::new(AddressMode::Value, ResultMeta::default(), kind);
+            Ok(Rc::new(HirExpr::new(
+                inner,
+                expr.position(),
+                self.type_map.get_unit(),
+            )))
+        } else {
+            Err(Error::invalid_control_in_loop(name).with_position(expr.position()))
+        }
+    }
+
+    fn resolve_return(&mut self, value: &Expr, position: Position) -> Result<Rc<HirExpr>, Error> {
+        if self.check_state(FUNCTION | FUNCTION_BODY)
+            || self.check_state(ASSOCIATIVE_FUNCTION | FUNCTION_BODY)
+        {
+            let mir_expr = with_state!(self, EXPR_RESULT_USED, {
+                self.resolve_return_value(value)?
+            });
+            let ty = mir_expr.ty();
+            let mutable = ResultMeta::new(false, false, ty.is_mutable(), false, false);
+            let inner = HirExprInner::new(
+                mir_expr.inner().address_mode(),
+                mutable,
+                HirExprKind::Return(mir_expr),
+            );
+            Ok(Rc::new(HirExpr::new(inner, position, ty)))
+        } else {
+            Err(Error::invalid_return().with_position(position))
+        }
+    }
+
+    fn resolve_trap(
+        &mut self,
+        message: &str,
+        values: &[Box<Expr>],
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        let values = values
+            .iter()
+            .map(|value| self.resolve_expr(value.as_ref(), None))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let trap = TrapExpr {
+            message: message.to_owned(),
+            values,
+        };
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::default(),
+            HirExprKind::Trap(trap),
+        );
+        Ok(Rc::new(HirExpr::new(
+            inner,
+            position,
+            self.type_map.get_unit(),
+        )))
+    }
+
+    /// the value of the last statement is resolved with the expected type.
+    pub(crate) fn resolve_block_expression(
+        &mut self,
+        stmts: &[Box<Stmt>],
+        function_body: bool,
+        expected_type: Option<Rc<Type>>,
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        self.push_scope(ScopeKind::Block);
+        // the last statement failed when checking recovers from errors.
+        let mut failed_result = false;
+        let stmts = with_state!(self, BLOCK, {
+            let mut mir_stmts = vec![];
+            for (idx, stmt) in stmts.iter().enumerate() {
+                // the last statement is the result of the block.
+                let last = idx + 1 == stmts.len();
+                let old_state = self.state;
+                if last {
+                    self.state |= BLOCK_RESULT;
+                    self.block_result_type = expected_type.clone();
+                } else {
+                    self.state &= !BLOCK_RESULT;
+                }
+                let mir_stmt = self.resolve_body_stmt(stmt.as_ref());
+                self.state = old_state;
+                self.block_result_type = None;
+                let mir_stmt = match mir_stmt? {
+                    Some(mir_stmt) => mir_stmt,
+                    None => {
+                        failed_result = last;
+                        continue;
+                    }
+                };
+                if !last {
+                    if let Err(err) = self.check_result_used(stmt.as_ref(), mir_stmt.inner()) {
+                        self.record_failure(err)?;
+                    }
+                }
+                mir_stmts.push(mir_stmt);
+            }
+
+            Ok(mir_stmts)
+        })?;
+
+        self.pop_scope();
+
+        // the result of the block is the result of the last expression in the block, this includes the addressing, type, and mutability
+        let (address_mode, return_type, mutable) = match stmts.last() {
+            // the value of the block is poisoned, the errors using it follow from the failure.
+            _ if failed_result => (
+                AddressMode::Value,
+                self.type_map.get_invalid(),
+                ResultMeta::literal(),
+            ),
+            last => last.map_or(
+                (
+                    AddressMode::Value,
+                    self.type_map.get_unit(),
+                    ResultMeta::literal(),
+                ),
+                |stmt| match stmt.inner() {
+                    HirStmtKind::Expr(expr) => {
+                        let inner = expr.inner();
+                        (inner.address_mode(), expr.ty(), inner.meta())
+                    }
+                    _ => (
+                        AddressMode::Value,
+                        stmt.ty(),
+                        ResultMeta::new(false, false, false, false, false),
+                    ),
+                },
+            ),
+        };
+
+        let block_expr = BlockExpr {
+            stmts,
+            function_block: function_body,
+        };
+
+        Ok(Rc::new(HirExpr::new(
+            HirExprInner::new(address_mode, mutable, HirExprKind::Block(block_expr)),
+            position,
+            return_type,
+        )))
+    }
+
+    /// with used results, a call made as a statement returns unit or is discarded by `_ = f()`.
+    fn check_result_used(&self, stmt: &Stmt, resolved: &HirStmtKind) -> Result<(), Error> {
+        if !self.checks.used_results || self.generated_by(stmt).is_some() {
+            return Ok(());
+        }
+        if let (StmtKind::Expr(_), HirStmtKind::Expr(expr)) = (stmt.kind(), resolved) {
+            let is_call = matches!(
+                expr.inner().kind(),
+                HirExprKind::Call(_)
+                    | HirExprKind::Method(_)
+                    | HirExprKind::AssociatedFunction(_)
+                    | HirExprKind::Task(_)
+                    | HirExprKind::Eval(_)
+            );
+            if is_call && !matches!(expr.ty().kind(), TypeKind::Unit) {
+                let err = Error::unused_result(expr.ty().as_ref());
+                return Err(err.with_position(stmt.position()));
+            }
+        }
+        Ok(())
+    }
+
+    /// an array literal, the elements take the element type of an expected array and must be
+    /// as many as it holds. Without one the first element gives the type of the others.
+    fn resolve_array(
+        &mut self,
+        elements: &[Box<Expr>],
+        expected_type: Option<Rc<Type>>,
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let expected = expected_type.and_then(|ty| match Type::inner(ty).kind() {
+            TypeKind::Array { element_type, size } => Some((element_type.clone(), *size)),
+            _ => None,
+        });
+        if let Some((_, size)) = expected {
+            if size != elements.len() {
+                let err = Error::array_length_mismatch(size, elements.len());
+                return Err(err.with_position(position));
+            }
+        }
+
+        let mut element_type = expected.map(|(element_type, _)| element_type);
+        let mut mir_elements = vec![];
+        for element in elements {
+            let mir_element = self.resolve_expr(element.as_ref(), element_type.clone())?;
+            match element_type.as_ref() {
+                Some(ty) if !Self::same_type(ty, &mir_element.ty()) => {
+                    let err = Error::incompatible_types(ty.as_ref(), mir_element.ty().as_ref());
+                    return Err(err.with_position(element.position()));
+                }
+                Some(_) => {}
+                None => element_type = Some(mir_element.ty()),
+            }
+            mir_elements.push(mir_element);
+        }
+        let element_type = match element_type {
+            Some(element_type) => element_type,
+            None => return Err(Error::untyped_empty_array().with_position(position)),
+        };
+
+        let ty = self.insert_type(TypeKind::Array {
+            element_type,
+            size: mir_elements.len(),
+        });
+        let inner = HirExprInner::new(
+            AddressMode::Address,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Array(ArrayExpr {
+                elements: mir_elements,
+            }),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
+    }
+
+    pub(crate) fn resolve_index(
+        &mut self,
+        operand: &Expr,
+        index: &Expr,
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let mir_operand = self.resolve_expr(operand, None)?;
+        let operand_meta = mir_operand.inner().meta();
+        if operand_meta.is_type {
+            let err = Error::expecting_instance_type(mir_operand.ty().as_ref());
+            return Err(err.with_position(operand.position()));
+        }
+
+        let operand_type = mir_operand.ty();
+        match Type::inner(operand_type.clone()).kind() {
+            TypeKind::Array { element_type, .. }
+            | TypeKind::Slice { element_type }
+            | TypeKind::Vector { element_type } => {
+                let mir_index = self.resolve_expr(index, None)?;
+                let index_meta = mir_index.inner().meta();
+                if index_meta.is_type {
+                    let err = Error::expecting_instance_type(mir_index.ty().as_ref());
+                    return Err(err.with_position(operand.position()));
+                }
+
+                let mir_index_type = mir_index.ty();
+                if mir_index_type.is_integer() {
+                    // an element can be assigned when the array or vector holding it can.
+                    let parent_mutable = mir_operand.inner().meta();
+                    let meta = ResultMeta::new(
+                        parent_mutable.mutable,
+                        true,
+                        element_type.is_mutable(),
+                        false,
+                        false,
+                    );
+                    Ok(Rc::new(HirExpr::new(
+                        HirExprInner::new(
+                            element_type.address_mode(),
+                            meta,
+                            HirExprKind::Index(IndexExpr {
+                                operand: mir_operand,
+                                index: mir_index,
+                            }),
+                        ),
+                        operand.position(),
+                        element_type.clone(),
+                    )))
+                } else {
+                    let err = Error::incompatible_types(&Type::integer(), mir_index_type.as_ref());
+                    Err(err.with_position(index.position()))
+                }
+            }
+            _ => {
+                let err = Error::invalid_index_type(operand_type.as_ref());
+                Err(err.with_position(position))
+            }
+        }
+    }
+
+    pub(crate) fn resolve_expr_to_entity(
+        &mut self,
+        expr: &Expr,
+    ) -> Result<(EntityRef, Rc<HirExpr>), Error> {
+        let mir_expr = self.resolve_expr(expr, None)?;
+        let entity = match mir_expr.inner().kind() {
+            HirExprKind::Field(field_expr) if Self::is_place(field_expr.operand.as_ref()) => {
+                field_expr.field.clone()
+            }
+            HirExprKind::Name(entity) => entity.clone(),
+            HirExprKind::Index(index_expr) if Self::is_place(index_expr.operand.as_ref()) => {
+                match index_expr.operand.inner().kind() {
+                    HirExprKind::Field(field_expr) => field_expr.field.clone(),
+                    HirExprKind::Name(entity) | HirExprKind::SelfLit(entity) => entity.clone(),
+                    _ => unreachable!("a place is a name, self or a field"),
+                }
+            }
+            _ => {
+                let err = Error::not_assignable();
+                return Err(err.with_position(expr.position()));
             }
         };
 
         Ok((entity, mir_expr))
     }
 
+    /// a variable, `self` or a field of one, the result of a call is a temporary.
+    fn is_place(expr: &HirExpr) -> bool {
+        match expr.inner().kind() {
+            HirExprKind::Name(_) | HirExprKind::SelfLit(_) => true,
+            HirExprKind::Field(field_expr) => Self::is_place(field_expr.operand.as_ref()),
+            _ => false,
+        }
+    }
+
+    /// every branch is resolved with the expected type, or the type of the first branch that
+    /// does not diverge. When the value of the if is used those branches have to produce the
+    /// same type and there has to be an else unless it is unit. When it is discarded they can
+    /// disagree, the if is then unit.
     pub(crate) fn resolve_if(
         &mut self,
         expr: &Expr,
-        mut expected_type: Option<Rc<Type>>,
-        _position: Position,
+        expected_type: Option<Rc<Type>>,
+        position: Position,
     ) -> Result<HirExprPtr, Error> {
+        let result_used = self.check_state(EXPR_RESULT_USED);
         let mut branches = vec![];
         let mut curr_expr = expr;
         let mut first = true;
+        let mut has_else = false;
+        let mut types = BranchTypes::new(expected_type);
 
         loop {
             match curr_expr.kind() {
@@ -478,12 +840,9 @@ impl<'src> Typer<'src> {
                     body,
                     else_if,
                 } => {
-                    let mir_expr = self.resolve_expr(cond, Some(self.type_map.get_bool()))?;
-                    let body = self.resolve_expr(body, expected_type.clone())?;
-
-                    if expected_type.is_none() {
-                        expected_type = Some(body.ty());
-                    }
+                    let construct = if first { "if" } else { "elif" };
+                    let mir_expr = self.resolve_condition(cond, construct)?;
+                    let body = self.resolve_branch(body, &mut types, result_used)?;
 
                     let if_expr_branch = IfExprBranch::Conditional {
                         cond: mir_expr,
@@ -506,25 +865,183 @@ impl<'src> Typer<'src> {
                         panic!("Compiler Error: if: first is {}", first);
                     }
 
-                    let mir_expr = self.resolve_expr(curr_expr, expected_type.clone())?;
+                    let mir_expr = self.resolve_branch(curr_expr, &mut types, result_used)?;
 
                     let if_expr_branch = IfExprBranch::Unconditional { body: mir_expr };
 
                     branches.push(if_expr_branch);
+                    has_else = true;
                     break;
                 }
             }
         }
 
+        let unit = self.type_map.get_unit();
+        // every branch diverges, the if has no value.
+        let ty = types.into_type().unwrap_or_else(|| unit.clone());
+        let mut agree = true;
+        for branch in branches.iter_mut() {
+            let body = match branch {
+                IfExprBranch::Conditional { body, .. } | IfExprBranch::Unconditional { body } => {
+                    body
+                }
+            };
+            agree &= Self::unify_branch(body, &ty, result_used)?;
+        }
+        if result_used && !has_else && !Type::inner(ty.clone()).is_unit() {
+            return Err(Error::if_without_else(ty.as_ref()).with_position(position));
+        }
+        let ty = if agree && (has_else || result_used) {
+            ty
+        } else {
+            unit
+        };
+
         let if_expr = IfExpr { branches };
+        let mutable = ResultMeta::new(false, ty.is_mutable(), false, false, false);
+        let inner = HirExprInner::new(ty.address_mode(), mutable, HirExprKind::If(if_expr));
+        Ok(Rc::new(HirExpr::new(inner, expr.position(), ty)))
+    }
 
-        if let Some(ty) = expected_type.as_ref() {
-            let mutable = ResultMeta::new(false, ty.is_mutable(), false, false, false);
-            let inner = HirExprInner::new(ty.address_mode(), mutable, HirExprKind::If(if_expr));
-            Ok(Rc::new(HirExpr::new(inner, expr.position(), ty.clone())))
-        } else {
-            panic!("Compiler Error: Failed to determine the result type of if expression");
+    /// a branch of an if, the first that does not diverge gives the others their type unless
+    /// it is a literal, a branch that is not gives the literals its type. The branches of an if
+    /// that is discarded are resolved on their own.
+    pub(super) fn resolve_branch(
+        &mut self,
+        body: &Expr,
+        types: &mut BranchTypes,
+        result_used: bool,
+    ) -> Result<HirExprPtr, Error> {
+        let expected = types.ty.clone().filter(|_| result_used && !types.literal);
+        let body = self.resolve_expr(body, expected)?;
+        if !Self::diverges(body.as_ref()) {
+            let literal = Self::retyped_literal(&body, &body.ty()).is_some();
+            let replaces = match types.ty.as_ref() {
+                None => true,
+                Some(ty) if types.literal && !literal => {
+                    let (ty, found) = (Type::inner(ty.clone()), Type::inner(body.ty()));
+                    (ty.is_integer() && found.is_integer()) || (ty.is_float() && found.is_float())
+                }
+                Some(_) => false,
+            };
+            if replaces {
+                types.ty = Some(body.ty());
+                types.literal = literal;
+            }
+        }
+        Ok(body)
+    }
+
+    /// whether the branch has the type of the if or match it is part of, a literal is given
+    /// the type. When the value is used a branch of another type is an error.
+    pub(super) fn unify_branch(
+        body: &mut HirExprPtr,
+        ty: &Rc<Type>,
+        result_used: bool,
+    ) -> Result<bool, Error> {
+        if Self::diverges(body) || Self::same_type(ty, &body.ty()) {
+            return Ok(true);
+        }
+        if let Some(retyped) = Self::retyped_literal(body, ty).filter(|_| result_used) {
+            *body = retyped;
+            return Ok(true);
+        }
+        if result_used {
+            let position = body
+                .returned_expression()
+                .map(|expr| expr.position())
+                .unwrap_or_else(|| body.position());
+            let err = Error::incompatible_types(ty.as_ref(), body.ty().as_ref());
+            return Err(err.with_position(position));
+        }
+        Ok(false)
+    }
+
+    /// expr with the type ty when its value is a number literal that fits ty, the last
+    /// statement of a block included.
+    fn retyped_literal(expr: &HirExpr, ty: &Rc<Type>) -> Option<HirExprPtr> {
+        let ty = Type::inner(ty.clone());
+        match expr.inner().kind() {
+            HirExprKind::Integer(val) if ty.is_integer() && ty.fits_integer(*val) => {}
+            HirExprKind::Float(_) if ty.is_float() => {}
+            HirExprKind::Block(block) => {
+                let (last, stmts) = block.stmts.split_last()?;
+                let value = match last.inner() {
+                    HirStmtKind::Expr(value) => Self::retyped_literal(value, &ty)?,
+                    _ => return None,
+                };
+                let last = last.with_inner(HirStmtKind::Expr(value)).with_type(ty.clone());
+                let mut stmts = stmts.to_vec();
+                stmts.push(Rc::new(last));
+                let block = BlockExpr {
+                    stmts,
+                    function_block: block.function_block,
+                };
+                let inner = expr.inner();
+                let kind = HirExprKind::Block(block);
+                let inner = HirExprInner::new(inner.address_mode(), inner.meta(), kind);
+                return Some(Rc::new(expr.with_inner(inner).with_type(ty)));
+            }
+            _ => return None,
+        }
+        Some(Rc::new(expr.with_type(ty)))
+    }
+
+    /// the type expected of the branches of an if or of the value of a block, none where any
+    /// value is expected.
+    fn branch_type(expected_type: Option<&Rc<Type>>) -> Option<Rc<Type>> {
+        expected_type
+            .filter(|expected| !matches!(expected.kind(), TypeKind::Any))
+            .cloned()
+    }
+
+    pub(super) fn same_type(expected: &Rc<Type>, found: &Rc<Type>) -> bool {
+        *Type::inner(expected.clone()) == *Type::inner(found.clone())
+    }
+
+    /// whether the end of expr is never reached, its value can have any type.
+    pub(super) fn diverges(expr: &HirExpr) -> bool {
+        match expr.inner().kind() {
+            HirExprKind::Return(_)
+            | HirExprKind::Break
+            | HirExprKind::Continue
+            | HirExprKind::Trap(_)
+            | HirExprKind::Unreachable(_) => true,
+            HirExprKind::Block(block) => block.stmts.iter().any(|stmt| {
+                matches!(stmt.inner(), HirStmtKind::Expr(expr) if Self::diverges(expr.as_ref()))
+            }),
+            HirExprKind::If(if_expr) => {
+                if_expr
+                    .branches
+                    .iter()
+                    .any(|branch| matches!(branch, IfExprBranch::Unconditional { .. }))
+                    && if_expr
+                        .branches
+                        .iter()
+                        .all(|branch| Self::diverges(branch.body()))
+            }
+            HirExprKind::Match(match_expr) => match_expr
+                .arms
+                .iter()
+                .all(|arm| Self::diverges(arm.body.as_ref())),
+            _ => false,
+        }
+    }
+
+    /// every construct that branches on a value requires exactly a bool, an integer is never
+    /// implicitly compared against zero.
+    pub(crate) fn resolve_condition(
+        &mut self,
+        cond: &Expr,
+        construct: &str,
+    ) -> Result<HirExprPtr, Error> {
+        let mir_cond = self.resolve_expr(cond, None)?;
+        let ty = Type::inner(mir_cond.ty());
+        if !ty.is_bool() {
+            let err = Error::non_bool_condition(construct, ty.as_ref());
+            return Err(err.with_position(cond.position()));
         }
+        Ok(mir_cond)
     }
 
     pub(crate) fn resolve_while(
@@ -533,11 +1050,9 @@ impl<'src> Typer<'src> {
         body: &Expr,
         position: Position,
     ) -> Result<HirExprPtr, Error> {
-        let mir_cond = self.resolve_expr(cond, Some(self.type_map.get_bool()))?;
+        let mir_cond = self.resolve_condition(cond, "while")?;
 
-        let mir_body = with_state!(self, ALLOW_CONTROL_FLOW_EXPRESSIONS, {
-            self.resolve_expr(body, None)?
-        });
+        let mir_body = self.resolve_loop_body(body)?;
 
         let while_expr = WhileExpr {
             cond: mir_cond,
@@ -557,14 +1072,23 @@ impl<'src> Typer<'src> {
         )))
     }
 
-    pub(crate) fn resolve_loop(
+    /// the value of the body of a loop is discarded on every iteration.
+    fn resolve_loop_body(&mut self, body: &Expr) -> Result<HirExprPtr, Error> {
+        let old_state = self.state;
+        self.state &= !EXPR_RESULT_USED;
+        let mir_body = with_state!(self, ALLOW_CONTROL_FLOW_EXPRESSIONS, {
+            self.resolve_expr(body, None)
+        });
+        self.state = old_state;
+        mir_body
+    }
+
+    pub(crate) fn resolve_loop(
         &mut self,
         body: &Expr,
         position: Position,
     ) -> Result<HirExprPtr, Error> {
-        let mir_body = with_state!(self, ALLOW_CONTROL_FLOW_EXPRESSIONS, {
-            self.resolve_expr(body, None)?
-        });
+        let mir_body = self.resolve_loop_body(body)?;
 
         let loop_expr = LoopExpr { body: mir_body };
 
@@ -590,25 +1114,33 @@ impl<'src> Typer<'src> {
         // get any inner type when appropriate (ie. mut T -> T)
         let operand_type = Type::inner(operand.ty());
         match operand_type.kind() {
+            TypeKind::Enum { entity } if operand.inner().meta().is_type => {
+                self.resolve_enum_function(entity.clone(), field, None, field.position())
+            }
             TypeKind::Struct { entity } => {
                 // we know it is a structure at this point.
                 let entity_borrow = entity.deref().borrow();
                 let structure_info = entity_borrow.as_struct();
                 if let Some(field_entity) = structure_info.fields.get(field.kind().value.as_str()) {
                     let field_borrow = field_entity.deref().borrow();
-                    if !operand.inner().kind().is_self() {
-                        // otherwise, we need to check if this field can be access in this context.
-                        // @TODO: if the struct is declared in the same file then we do not need to
-                        // check the visibility.
+                    // private fields are used by the associated functions of the structure, on
+                    // any instance of it.
+                    if !operand.inner().kind().is_self() && !self.within_structure(entity) {
                         match field_borrow.visibility() {
                             Visibility::Private => {
+                                let name = field.kind().value.as_str();
                                 let err = Error::inaccessible_subentity(
+                                    "field",
                                     entity.borrow().type_name(),
                                     operand_type.as_ref(),
-                                    field.kind().value.clone(),
+                                    name.to_owned(),
                                 )
                                 .with_position(field.position());
-                                return Err(err);
+                                return Err(Self::with_declaration(
+                                    err,
+                                    field_borrow.position(),
+                                    name,
+                                ));
                             }
                             Visibility::Public => { /* Continue */ }
                         }
@@ -647,6 +1179,13 @@ impl<'src> Typer<'src> {
                         }
                         _ => todo!(),
                     }
+                } else if let Some(method) = structure_info.methods.get(field.kind().value.as_str())
+                {
+                    let name = field.kind().value.as_str();
+                    let err = Error::method_read_as_field(name, operand_type.as_ref())
+                        .with_position(field.position());
+                    let declared = method.deref().borrow().position();
+                    Err(Self::with_declaration(err, declared, name))
                 } else {
                     let err = Error::unknown_subfield(
                         entity.borrow().type_name(),
@@ -657,118 +1196,687 @@ impl<'src> Typer<'src> {
                     return Err(err);
                 }
             }
-            _ => todo!("Operand Type: {}", operand_type),
+            _ => {
+                let err = Error::expected_struct_type(operand_type.as_ref());
+                Err(err.with_position(field.position()))
+            }
+        }
+    }
+
+    pub(crate) fn resolve_call(
+        &mut self,
+        operand: &Expr,
+        actuals: &[Box<Expr>],
+    ) -> Result<Rc<HirExpr>, Error> {
+        if let Some(generic) = self.generic_callee(operand)? {
+            return self.resolve_generic_call(generic, operand, actuals);
+        }
+        let mir_operand = self.resolve_expr(operand, None)?;
+        // println!("Function Type: {}", mir_operand.ty());
+        let function_type = mir_operand.ty();
+        match function_type.kind() {
+            TypeKind::Function {
+                params,
+                return_type,
+            } => {
+                Self::check_trailing_block(&Self::callee_name(operand), params, actuals)?;
+                if params.len() != actuals.len() {
+                    let err = Error::invalid_actuals(params.len(), actuals.len())
+                        .with_position(operand.position());
+                    return Err(err);
+                }
+                let mut mir_actuals = vec![];
+                for (act, param) in actuals.iter().zip(params) {
+                    let mir_actual = self.resolve_expr(act, Some(param.clone()))?;
+                    mir_actuals.push(mir_actual);
+                }
+                let call_expr = CallExpr {
+                    operand: mir_operand,
+                    function_type: function_type.clone(),
+                    actuals: mir_actuals,
+                };
+                let result_used = self.check_state(EXPR_RESULT_USED);
+                let mutable = ResultMeta::funct(
+                    false,
+                    false,
+                    return_type.is_mutable(),
+                    false,
+                    false,
+                    true,
+                    result_used,
+                );
+                let inner =
+                    HirExprInner::new(AddressMode::Value, mutable, HirExprKind::Call(call_expr));
+                Ok(Rc::new(HirExpr::new(
+                    inner,
+                    operand.position(),
+                    return_type.clone(),
+                )))
+            }
+            _ => {
+                let err = Error::invalid_call_on_type(mir_operand.ty().as_ref());
+                return Err(err.with_position(operand.position()));
+            }
+        }
+    }
+
+    /// a for loop calls `next` whatever its visibility, any other call checks it.
+    pub(crate) fn resolve_method_call(
+        &mut self,
+        name: &Identifier,
+        actuals: &[Box<Expr>],
+        position: Position,
+        for_loop_next: bool,
+    ) -> Result<Rc<HirExpr>, Error> {
+        assert!(actuals.len() >= 1);
+        let receiver_expr = actuals.first().unwrap();
+        let mir_expr = if self.is_collection_type(receiver_expr.as_ref()) {
+            self.resolve_collection_type(receiver_expr.as_ref())?
+        } else {
+            self.resolve_expr(receiver_expr.as_ref(), None)?
+        };
+        let struct_type = mir_expr.ty();
+
+        if let TypeKind::Task { .. } | TypeKind::Result { .. } =
+            Type::inner(struct_type.clone()).kind()
+        {
+            return self.resolve_task_method(mir_expr, name, &actuals[1..], position);
+        }
+
+        if Type::inner(struct_type.clone()).is_integer() {
+            return self.resolve_integer_method(mir_expr, name, &actuals[1..], position);
+        }
+
+        if let TypeKind::Enum { entity } = Type::inner(struct_type.clone()).kind() {
+            if mir_expr.inner().meta().is_type {
+                return self.resolve_enum_function(
+                    entity.clone(),
+                    name,
+                    Some(&actuals[1..]),
+                    position,
+                );
+            }
+            return self.resolve_enum_method(
+                entity.clone(),
+                mir_expr,
+                name,
+                &actuals[1..],
+                position,
+            );
+        }
+
+        // a receiver that is not named, the result of a call for example, is an instance.
+        let mir_entity = Self::receiver_entity(mir_expr.as_ref());
+
+        let name_str = name.kind().value.as_str();
+        let inner_type = Type::inner(struct_type.clone());
+        if let TypeKind::Vector { .. }
+        | TypeKind::Map { .. }
+        | TypeKind::Slice { .. }
+        | TypeKind::Weak { .. } = inner_type.kind()
+        {
+            let entity = self.collection_entity(inner_type);
+            let method = entity.deref().borrow().as_struct().methods.get(name_str).cloned();
+            return match method {
+                Some(method) => self.resolve_method_from_entity(
+                    mir_entity, method, mir_expr, actuals, name, position,
+                ),
+                None => {
+                    let err = Error::unknown_subentity("method", name_str, struct_type.as_ref());
+                    Err(err.with_position(name.position()))
+                }
+            };
+        }
+
+        match struct_type.kind() {
+            TypeKind::Struct { entity } => {
+                if let EntityInfo::Structure(structure_info) = entity.deref().borrow().kind() {
+                    if let Some(method) = structure_info.methods.get(name_str) {
+                        let method = self.declared_method(entity, method.clone(), position)?;
+                        let private = method.deref().borrow().visibility() == Visibility::Private;
+                        if private && !for_loop_next && !self.within_structure(entity) {
+                            let err = Error::inaccessible_subentity(
+                                "associated function",
+                                entity.deref().borrow().type_name(),
+                                struct_type.as_ref(),
+                                name_str.to_owned(),
+                            )
+                            .with_position(name.position());
+                            let declared = method.deref().borrow().position();
+                            return Err(Self::with_declaration(err, declared, name_str));
+                        }
+                        self.resolve_method_from_entity(
+                            mir_entity.clone(),
+                            method,
+                            mir_expr,
+                            actuals,
+                            name,
+                            position,
+                        )
+                    } else if let Some(field) = structure_info.fields.get(name_str) {
+                        let field = field.deref().borrow();
+                        // a field holding a function is called like any other function value.
+                        if let TypeKind::Function { .. } = field.ty().kind() {
+                            let operand = Expr::new_with_position(
+                                ExprKind::Field(receiver_expr.clone(), Box::new(name.clone())),
+                                position,
+                            );
+                            return self.resolve_call(&operand, &actuals[1..]);
+                        }
+                        let err = Error::field_called_as_method(name_str, struct_type.as_ref())
+                            .with_position(name.position());
+                        Err(Self::with_declaration(err, field.position(), name_str))
+                    } else {
+                        return Err(Error::unknown_subentity(
+                            "associated function",
+                            name_str,
+                            struct_type.as_ref(),
+                        )
+                        .with_position(name.position()));
+                    }
+                } else {
+                    panic!("Compiler Error: structure type entity is not a struct entity")
+                }
+            }
+            _ => {
+                let err = Error::expected_struct_type(struct_type.as_ref());
+                Err(err.with_position(name.position()))
+            }
+        }
+    }
+
+    /// `a.div_floor(b)` is the binary expression of the division.
+    fn resolve_integer_method(
+        &mut self,
+        receiver: HirExprPtr,
+        name: &Identifier,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let name_str = name.kind().value.as_str();
+        let op = match BinaryOp::integer_method(name_str) {
+            Some(op) => op,
+            None => {
+                let err = Error::unknown_subentity("method", name_str, receiver.ty().as_ref());
+                return Err(err.with_position(name.position()));
+            }
+        };
+        if actuals.len() != 1 {
+            let err = Error::invalid_actuals(1, actuals.len());
+            return Err(err.with_position(position));
+        }
+        self.resolve_binary_operands(op, receiver, actuals[0].as_ref(), position)
+    }
+
+    /// whether the private members of structure can be used, they are within its associated
+    /// functions.
+    fn within_structure(&self, structure: &EntityRef) -> bool {
+        self.self_entity
+            .as_ref()
+            .is_some_and(|entity| entity.deref().borrow().id() == structure.deref().borrow().id())
+    }
+
+    /// points err at where the member it names is declared.
+    pub(super) fn with_declaration(err: Error, declared: Option<Position>, name: &str) -> Error {
+        match declared {
+            Some(declared) => err.with_label(declared, format!("'{}' is declared here", name)),
+            None => err,
+        }
+    }
+
+    /// `Enum.Variant`, `Enum.Variant(<fields>)` and `Enum.from_int(<value>)`, actuals is none
+    /// when the name is not called.
+    fn resolve_enum_function(
+        &mut self,
+        entity: EntityRef,
+        name: &Identifier,
+        actuals: Option<&[Box<Expr>]>,
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        let name_str = name.kind().value.as_str();
+        let entity_borrow = entity.deref().borrow();
+        let enum_type = entity_borrow.ty();
+        let enum_info = entity_borrow.as_enum();
+
+        if let Some(variant) = enum_info.variant(name_str) {
+            let discriminant = variant.discriminant;
+            let field_types = variant.fields.clone();
+            let declared = enum_info.declared(name_str);
+            std::mem::drop(entity_borrow);
+
+            let actuals = actuals.unwrap_or(&[]);
+            if actuals.len() != field_types.len() {
+                let err = Error::invalid_actuals(field_types.len(), actuals.len())
+                    .with_position(position);
+                return Err(Self::with_declaration(err, declared, name_str));
+            }
+
+            let mut fields = vec![];
+            for (actual, field_type) in actuals.iter().zip(field_types) {
+                fields.push(self.resolve_expr(actual.as_ref(), Some(field_type))?);
+            }
+
+            let inner = HirExprInner::new(
+                AddressMode::Value,
+                ResultMeta::new(false, false, false, true, false),
+                HirExprKind::Variant(VariantExpr {
+                    enum_entity: entity.clone(),
+                    discriminant,
+                    fields,
+                }),
+            );
+            return Ok(Rc::new(HirExpr::new(inner, position, enum_type)));
+        }
+
+        match actuals {
+            Some(actuals) if name_str == EnumFunction::FromInt.name() => {
+                if !enum_info.is_unit_only() {
+                    let err = Error::data_enum_integer_conversion(enum_type.as_ref());
+                    return Err(err.with_position(name.position()));
+                }
+                std::mem::drop(entity_borrow);
+
+                if actuals.len() != 1 {
+                    let err = Error::invalid_actuals(1, actuals.len());
+                    return Err(err.with_position(position));
+                }
+
+                let value =
+                    self.resolve_expr(actuals[0].as_ref(), Some(self.type_map.get_i64()))?;
+                let ty = self.insert_type(TypeKind::Option { inner: enum_type });
+                let inner = HirExprInner::new(
+                    AddressMode::Value,
+                    ResultMeta::new(false, false, false, true, false),
+                    HirExprKind::EnumFunction(EnumFunctionExpr {
+                        enum_entity: entity.clone(),
+                        function: EnumFunction::FromInt,
+                        actuals: vec![value],
+                    }),
+                );
+                Ok(Rc::new(HirExpr::new(inner, position, ty)))
+            }
+            Some(actuals) if name_str == EMPTY_FUNCTION && enum_info.flags => {
+                std::mem::drop(entity_borrow);
+                if !actuals.is_empty() {
+                    let err = Error::invalid_actuals(0, actuals.len());
+                    return Err(err.with_position(position));
+                }
+
+                let inner = HirExprInner::new(
+                    AddressMode::Value,
+                    ResultMeta::new(false, false, false, true, false),
+                    HirExprKind::Variant(VariantExpr {
+                        enum_entity: entity.clone(),
+                        discriminant: 0,
+                        fields: vec![],
+                    }),
+                );
+                Ok(Rc::new(HirExpr::new(inner, position, enum_type)))
+            }
+            _ => Err(Error::unknown_subentity("variant", name_str, enum_type.as_ref())
+                .with_position(name.position())),
+        }
+    }
+
+    /// `<flags>.contains(<flag>)` and `<flags>.display()`, the only methods of an enum value.
+    fn resolve_enum_method(
+        &mut self,
+        entity: EntityRef,
+        receiver: Rc<HirExpr>,
+        name: &Identifier,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        let name_str = name.kind().value.as_str();
+        let (enum_type, flags) = {
+            let entity_borrow = entity.deref().borrow();
+            (entity_borrow.ty(), entity_borrow.as_enum().flags)
+        };
+
+        let function = [EnumFunction::Contains, EnumFunction::Display]
+            .iter()
+            .copied()
+            .find(|function| flags && function.name() == name_str);
+        let (function, params, result_type) = match function {
+            Some(EnumFunction::Contains) => (EnumFunction::Contains, 1, self.type_map.get_bool()),
+            Some(EnumFunction::Display) => (EnumFunction::Display, 0, self.type_map.get_string()),
+            _ => {
+                let err = Error::unknown_subentity("method", name_str, enum_type.as_ref());
+                return Err(err.with_position(name.position()));
+            }
+        };
+
+        if actuals.len() != params {
+            let err = Error::invalid_actuals(params, actuals.len());
+            return Err(err.with_position(position));
+        }
+
+        let mut resolved = vec![receiver];
+        for actual in actuals {
+            let actual = self.resolve_expr(actual.as_ref(), Some(enum_type.clone()))?;
+            if *Type::inner(actual.ty()) != *enum_type {
+                let err = Error::incompatible_types(enum_type.as_ref(), actual.ty().as_ref());
+                return Err(err.with_position(actual.position()));
+            }
+            resolved.push(actual);
+        }
+
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::EnumFunction(EnumFunctionExpr {
+                enum_entity: entity,
+                function,
+                actuals: resolved,
+            }),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, result_type)))
+    }
+
+    /// the entity a method is called on, the receiver of an optional chain is the entity the
+    /// chain was applied to.
+    fn receiver_entity(expr: &HirExpr) -> Option<EntityRef> {
+        match expr.inner().kind() {
+            HirExprKind::Field(field_expr) => Some(field_expr.field.clone()),
+            HirExprKind::Name(entity) => Some(entity.clone()),
+            HirExprKind::SelfLit(entity) => Some(entity.clone()),
+            HirExprKind::ChainReceiver(receiver) => Self::receiver_entity(receiver.as_ref()),
+            HirExprKind::OptionalChain(chain_expr) => {
+                Self::receiver_entity(chain_expr.link.as_ref())
+            }
+            _ => None,
+        }
+    }
+
+    /// `a?.b` and `a?.b(<actuals>)` are lowered to a field or method of the unwrapped receiver,
+    /// the chain is none as soon as the receiver is none. The chain is `Option[U]` for a link of
+    /// type `U`, a link that is already optional is not wrapped again.
+    fn resolve_optional_chain(&mut self, expr: &Expr) -> Result<Rc<HirExpr>, Error> {
+        let mut synthesizer = Synthesizer::new(Desugaring::OptionalChain, expr.position());
+        let placeholder = synthesizer.expr(ExprKind::ChainReceiver);
+        let (receiver, link) = match expr.kind() {
+            ExprKind::OptionalField(operand, field) => (
+                operand,
+                synthesizer.expr(ExprKind::Field(placeholder, field.clone())),
+            ),
+            ExprKind::OptionalMethod { name, actual } => {
+                let mut link_actual = actual.clone();
+                link_actual[0] = placeholder;
+                let link = synthesizer.expr(ExprKind::Method {
+                    name: name.clone(),
+                    actual: link_actual,
+                });
+                (&actual[0], link)
+            }
+            _ => unreachable!(),
+        };
+
+        // the receiver is evaluated once, the link refers to it through the placeholder.
+        let receiver = self.resolve_expr(receiver.as_ref(), None)?;
+        let receiver_type = Type::inner(receiver.ty());
+        let unwrapped = match receiver_type.kind() {
+            TypeKind::Option { inner } => inner.clone(),
+            _ => {
+                let err = Error::non_optional_chain(receiver_type.as_ref());
+                return Err(err.with_position(expr.position()));
+            }
+        };
+        let chain_receiver = HirExpr::new(
+            HirExprInner::new(
+                receiver.inner().address_mode(),
+                receiver.inner().meta(),
+                HirExprKind::ChainReceiver(receiver.clone()),
+            ),
+            receiver.position(),
+            unwrapped,
+        );
+        chain_receiver.mark_generated(Desugaring::OptionalChain);
+
+        self.trace_desugaring(&synthesizer, expr, Lowered::Ast(&link));
+        let outer_receiver = self.chain_receiver.replace(Rc::new(chain_receiver));
+        let link = self.resolve_desugared(&synthesizer, |typer| {
+            typer.resolve_expr(link.as_ref(), None)
+        });
+        self.chain_receiver = outer_receiver;
+        let link = link?;
+
+        let ty = self.optional_of(Type::inner(link.ty()));
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::OptionalChain(OptionalChainExpr { link }),
+        );
+        Ok(Rc::new(HirExpr::new(inner, expr.position(), ty)))
+    }
+
+    /// `value ?? fallback` is the unwrapped value, or the fallback when the value is none.
+    fn resolve_coalesce(
+        &mut self,
+        value: &Expr,
+        fallback: &Expr,
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        let value = self.resolve_expr(value, None)?;
+        let value_type = Type::inner(value.ty());
+        let unwrapped = match value_type.kind() {
+            TypeKind::Option { inner } => inner.clone(),
+            _ => {
+                let err = Error::non_optional_coalesce(value_type.as_ref());
+                return Err(err.with_position(value.position()));
+            }
+        };
+
+        // a fallback leaving the enclosing loop or function never produces a value.
+        let expected = match fallback.kind() {
+            ExprKind::Break | ExprKind::Continue | ExprKind::Return(..) => None,
+            _ => Some(unwrapped.clone()),
+        };
+        let fallback = self.resolve_expr(fallback, expected)?;
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Coalesce(CoalesceExpr { value, fallback }),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, unwrapped)))
+    }
+
+    /// flags are echoed by the names of their variants, every other value as it is.
+    pub(crate) fn echoed_value(&mut self, expr: Rc<HirExpr>) -> Rc<HirExpr> {
+        let ty = Type::inner(expr.ty());
+        match ty.kind() {
+            TypeKind::Enum { entity } if ty.is_flags() => {
+                let synthesizer = Synthesizer::new(Desugaring::FlagsEcho, expr.position());
+                let display = synthesizer.hir_expr(
+                    HirExprKind::EnumFunction(EnumFunctionExpr {
+                        enum_entity: entity.clone(),
+                        function: EnumFunction::Display,
+                        actuals: vec![expr.clone()],
+                    }),
+                    self.type_map.get_string(),
+                );
+                self.trace_desugaring(&synthesizer, expr.as_ref(), Lowered::Hir(&display));
+                display
+            }
+            _ => expr,
+        }
+    }
+
+    /// only enums without data can be cast, and only to an integer type that holds every
+    /// discriminant. A bool is cast to 0 or 1 and an integer to a bool is true when it is not
+    /// zero, a constant operand is folded.
+    fn resolve_cast(
+        &mut self,
+        operand: &Expr,
+        spec: &Spec,
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        let operand = self.resolve_expr(operand, None)?;
+        if operand.inner().meta().is_type {
+            let err = Error::expecting_instance_type(operand.ty().as_ref());
+            return Err(err.with_position(operand.position()));
+        }
+
+        let target_type = self.resolve_spec(spec)?.ty();
+        let operand_type = Type::inner(operand.ty());
+        if operand_type.is_bool() && target_type.is_integer()
+            || operand_type.is_integer() && target_type.is_bool()
+        {
+            let folded = match operand.inner().kind() {
+                HirExprKind::Bool(val) => Some(HirExprKind::Integer(*val as i64)),
+                HirExprKind::Integer(val) => Some(HirExprKind::Bool(*val != 0)),
+                _ => None,
+            };
+            let inner = match folded {
+                Some(kind) => HirExprInner::new(AddressMode::Value, ResultMeta::literal(), kind),
+                None => HirExprInner::new(
+                    AddressMode::Value,
+                    ResultMeta::new(false, false, false, true, false),
+                    HirExprKind::Cast(CastExpr { operand }),
+                ),
+            };
+            return Ok(Rc::new(HirExpr::new(inner, position, target_type)));
+        }
+
+        match operand_type.kind() {
+            TypeKind::Enum { entity } => {
+                let entity_borrow = entity.deref().borrow();
+                let enum_info = entity_borrow.as_enum();
+                if !enum_info.is_unit_only() {
+                    let err = Error::data_enum_integer_conversion(operand_type.as_ref());
+                    return Err(err.with_position(position));
+                }
+
+                if !target_type.is_integer() {
+                    let err = Error::invalid_cast(operand_type.as_ref(), target_type.as_ref());
+                    return Err(err.with_position(position));
+                }
+
+                if let Some(variant) = enum_info
+                    .variants
+                    .iter()
+                    .find(|variant| !target_type.fits_integer(variant.discriminant))
+                {
+                    let err = Error::discriminant_does_not_fit(
+                        variant.name.as_str(),
+                        variant.discriminant,
+                        target_type.as_ref(),
+                    );
+                    return Err(err.with_position(position));
+                }
+            }
+            _ => {
+                let err = Error::invalid_cast(operand_type.as_ref(), target_type.as_ref());
+                return Err(err.with_position(position));
+            }
+        }
+
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Cast(CastExpr { operand }),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, target_type)))
+    }
+
+    /// `log` is a built in module, it is shadowed by any entity with the same name.
+    fn is_log_receiver(&self, receiver: &Expr) -> bool {
+        self.is_builtin(receiver, LOG_MODULE)
+    }
+
+    /// whether expr names the built in name, it is shadowed by any entity with the same name.
+    pub(super) fn is_builtin(&self, expr: &Expr, builtin: &str) -> bool {
+        match expr.kind() {
+            ExprKind::Name(ident) => {
+                let name = ident.kind().value.as_str();
+                name == builtin && self.deep_lookup(name).is_none()
+            }
+            _ => false,
         }
     }
 
-    pub(crate) fn resolve_call(
+    /// `black_box(value)` is value, the passes treat it as an opaque use so the work computing
+    /// value is kept and nothing is folded through it.
+    fn resolve_black_box(
         &mut self,
-        operand: &Expr,
         actuals: &[Box<Expr>],
+        expected_type: Option<Rc<Type>>,
+        position: Position,
     ) -> Result<Rc<HirExpr>, Error> {
-        let mir_operand = self.resolve_expr(operand, None)?;
-        // println!("Function Type: {}", mir_operand.ty());
-        let function_type = mir_operand.ty();
-        match function_type.kind() {
-            TypeKind::Function {
-                params,
-                return_type,
-            } => {
-                if params.len() != actuals.len() {
-                    let err = Error::invalid_actuals(params.len(), actuals.len())
-                        .with_position(operand.position());
-                    return Err(err);
-                }
-                let mut mir_actuals = vec![];
-                for (act, param) in actuals.iter().zip(params) {
-                    let mir_actual = self.resolve_expr(act, Some(param.clone()))?;
-                    mir_actuals.push(mir_actual);
-                }
-                let call_expr = CallExpr {
-                    operand: mir_operand,
-                    function_type: function_type.clone(),
-                    actuals: mir_actuals,
-                };
-                let result_used = self.check_state(EXPR_RESULT_USED);
-                let mutable = ResultMeta::funct(
-                    false,
-                    false,
-                    return_type.is_mutable(),
-                    false,
-                    false,
-                    true,
-                    result_used,
-                );
-                let inner =
-                    HirExprInner::new(AddressMode::Value, mutable, HirExprKind::Call(call_expr));
-                Ok(Rc::new(HirExpr::new(
-                    inner,
-                    operand.position(),
-                    return_type.clone(),
-                )))
-            }
-            _ => {
-                let err = Error::invalid_call_on_type(mir_operand.ty().as_ref());
-                return Err(err.with_position(operand.position()));
-            }
+        if actuals.len() != 1 {
+            let err = Error::invalid_actuals(1, actuals.len());
+            return Err(err.with_position(position));
         }
+
+        let value = self.resolve_expr(actuals[0].as_ref(), expected_type)?;
+        let ty = value.ty();
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::BlackBox(value),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
     }
 
-    pub(crate) fn resolve_method_call(
+    /// `eval(source)` compiles source as a script and runs it, it is `Result[any, string]`. The
+    /// error is the message of the error that stopped the script from compiling or running.
+    fn resolve_eval(
         &mut self,
-        name: &Identifier,
         actuals: &[Box<Expr>],
         position: Position,
     ) -> Result<Rc<HirExpr>, Error> {
-        assert!(actuals.len() >= 1);
-        let receiver_expr = actuals.first().unwrap();
-        let mir_expr = self.resolve_expr(receiver_expr.as_ref(), None)?;
-        let struct_type = mir_expr.ty();
+        if actuals.len() != 1 {
+            let err = Error::invalid_actuals(1, actuals.len());
+            return Err(err.with_position(position));
+        }
 
-        let mir_entity = match mir_expr.inner().kind() {
-            HirExprKind::Field(field_expr) => field_expr.field.clone(),
-            HirExprKind::Name(entity) => entity.clone(),
-            HirExprKind::SelfLit(entity) => entity.clone(),
-            _ => {
-                let err = Error::invalid_lvalue();
-                return Err(err.with_position(mir_expr.position()));
+        let source = self.resolve_expr(actuals[0].as_ref(), Some(self.type_map.get_string()))?;
+        let ty = self.insert_type(TypeKind::Result {
+            ok: self.type_map.get_any(),
+            err: self.type_map.get_string(),
+        });
+        let inner = HirExprInner::new(
+            ty.address_mode(),
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Eval(source),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
+    }
+
+    fn resolve_log_call(
+        &mut self,
+        name: &Identifier,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        let level = match LogLevel::from_name(name.kind().value.as_str()) {
+            Some(level) => level,
+            None => {
+                let err = Error::unknown_log_function(name.kind().value.as_str());
+                return Err(err.with_position(name.position()));
             }
         };
 
-        let name_str = name.kind().value.as_str();
-        match struct_type.kind() {
-            TypeKind::Struct { entity } => {
-                if let EntityInfo::Structure(structure_info) = entity.deref().borrow().kind() {
-                    if let Some(method) = structure_info.methods.get(name_str) {
-                        self.resolve_method_from_entity(
-                            mir_entity.clone(),
-                            method.clone(),
-                            mir_expr,
-                            actuals,
-                            name,
-                            position,
-                        )
-                    } else if let Some(_field) = structure_info.fields.get(name_str) {
-                        panic!()
-                    } else {
-                        return Err(Error::unknown_subentity(
-                            "associated function",
-                            name_str,
-                            struct_type.as_ref(),
-                        )
-                        .with_position(name.position()));
-                    }
-                } else {
-                    panic!("Compiler Error: structure type entity is not a struct entity")
-                }
-            }
-            _ => panic!(),
+        if actuals.len() != 1 {
+            let err = Error::invalid_actuals(1, actuals.len());
+            return Err(err.with_position(position));
         }
+
+        let message = self.resolve_expr(actuals[0].as_ref(), None)?;
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::default(),
+            HirExprKind::Log(LogExpr { level, message }),
+        );
+        Ok(Rc::new(HirExpr::new(
+            inner,
+            position,
+            self.type_map.get_unit(),
+        )))
     }
 
     pub(crate) fn resolve_method_from_entity(
         &mut self,
-        associated_type: EntityRef,
+        associated_type: Option<EntityRef>,
         method_entity: EntityRef,
         receiver: Rc<HirExpr>,
         actuals: &[Box<Expr>],
@@ -776,157 +1884,155 @@ impl<'src> Typer<'src> {
         position: Position,
     ) -> Result<Rc<HirExpr>, Error> {
         let method_borrow = method_entity.deref().borrow();
-        println!("associated_type: {}", associated_type.borrow().type_name());
-        match method_borrow.kind() {
-            EntityInfo::AssociatedFunction(associated_function_info) => {
-                let method_type = method_borrow.ty();
-                if associated_type.deref().borrow().is_type() {
-                    if associated_function_info.entity.deref().borrow().id()
-                        != associated_type.deref().borrow().id()
-                    {
-                        panic!("Compiler Error: invalid expected receiver type with found receiver: {} -> {}", associated_function_info.entity.deref().borrow().full_name(), associated_type.deref().borrow().full_name());
+        let receiver_is_instance = associated_type.as_ref().is_none_or(|entity| {
+            let entity = entity.deref().borrow();
+            entity.is_instance() || entity.is_self()
+        });
+        let (structure, takes_self) = match method_borrow.kind() {
+            EntityInfo::AssociatedFunction(associated_function_info) => (
+                associated_function_info.entity.clone(),
+                associated_function_info.takes_self,
+            ),
+            // a method whose body is not resolved yet is called by its signature.
+            EntityInfo::Declared(DeclaredInfo {
+                entity: Some(entity),
+                takes_self,
+            }) => (entity.clone(), *takes_self),
+            _ => {
+                let err = Error::invalid_call_on_type(method_borrow.ty().as_ref())
+                    .with_position(name.position());
+                return Err(err);
+            }
+        };
+        let method_type = method_borrow.ty();
+        if let Some(associated_type) =
+            associated_type.filter(|entity| entity.deref().borrow().is_type())
+        {
+            if structure.deref().borrow().id() != associated_type.deref().borrow().id() {
+                panic!("Compiler Error: invalid expected receiver type with found receiver: {} -> {}", structure.deref().borrow().full_name(), associated_type.deref().borrow().full_name());
+            }
+            if takes_self {
+                let err = Error::invalid_associated_function_receiver(name.kind().value.as_str());
+                return Err(err.with_position(name.position()));
+            }
+
+            match method_type.kind() {
+                TypeKind::Function {
+                    params,
+                    return_type,
+                } => {
+                    let function = name.kind().value.as_str();
+                    Self::check_trailing_block(function, params, &actuals[1..])?;
+                    if params.len() != actuals.len() - 1 {
+                        let err = Error::invalid_actuals(params.len(), actuals.len())
+                            .with_position(name.position());
+                        return Err(err);
                     }
-                    if associated_function_info.takes_self {
-                        let err =
-                            Error::invalid_associated_function_receiver(name.kind().value.as_str());
-                        return Err(err.with_position(name.position()));
+                    let mut mir_actuals = vec![receiver.clone()];
+                    for (act, param) in actuals.iter().skip(1).zip(params) {
+                        let mir_actual = self.resolve_expr(act, Some(param.clone()))?;
+                        mir_actuals.push(mir_actual);
                     }
 
-                    match method_type.kind() {
-                        TypeKind::Function {
-                            params,
-                            return_type,
-                        } => {
-                            if params.len() != actuals.len() - 1 {
-                                let err = Error::invalid_actuals(params.len(), actuals.len())
-                                    .with_position(name.position());
-                                return Err(err);
-                            }
-                            let mut mir_actuals = vec![receiver.clone()];
-                            for (act, param) in actuals.iter().skip(1).zip(params) {
-                                let mir_actual = self.resolve_expr(act, Some(param.clone()))?;
-                                mir_actuals.push(mir_actual);
-                            }
-
-                            let ty = Type::inner(associated_type.borrow().ty());
-
-                            let struct_entity = match ty.kind() {
-                                TypeKind::Struct { entity } => entity.clone(),
-                                _ => panic!("reciever is not a struct, invalid entity"),
-                            };
+                    let associated_function_expr = AssociatedFunctionExpr {
+                        struct_entity: structure.clone(),
+                        function_type: method_type.clone(),
+                        name: name.kind().value.clone(),
+                        actuals: mir_actuals,
+                    };
 
-                            let associated_function_expr = AssociatedFunctionExpr {
-                                struct_entity,
-                                function_type: method_type.clone(),
-                                name: name.kind().value.clone(),
-                                actuals: mir_actuals,
-                            };
+                    let mutable = ResultMeta::funct(
+                        false,
+                        false,
+                        return_type.is_mutable(),
+                        false,
+                        false,
+                        true,
+                        self.check_state(EXPR_RESULT_USED),
+                    );
+                    // the address_mode should be determined by the address mode of the returned expression
+                    let inner = HirExprInner::new(
+                        AddressMode::Address,
+                        mutable,
+                        HirExprKind::AssociatedFunction(associated_function_expr),
+                    );
+                    Ok(Rc::new(HirExpr::new(inner, position, return_type.clone())))
+                }
+                _ => {
+                    let err = Error::invalid_call_on_type(method_borrow.ty().as_ref());
+                    Err(err.with_position(name.position()))
+                }
+            }
+        } else if receiver_is_instance {
+            if !takes_self {
+                let err = Error::associated_function_invalid_receiver(name.kind().value.as_str());
+                return Err(err.with_position(name.position()));
+            }
 
-                            let mutable = ResultMeta::funct(
-                                false,
-                                false,
-                                return_type.is_mutable(),
-                                false,
-                                false,
-                                true,
-                                self.check_state(EXPR_RESULT_USED),
-                            );
-                            // the address_mode should be determined by the address mode of the returned expression
-                            let inner = HirExprInner::new(
-                                AddressMode::Address,
-                                mutable,
-                                HirExprKind::AssociatedFunction(associated_function_expr),
-                            );
-                            Ok(Rc::new(HirExpr::new(inner, position, return_type.clone())))
-                        }
-                        _ => {
-                            let err = Error::invalid_call_on_type(method_borrow.ty().as_ref());
-                            return Err(err.with_position(name.position()));
-                        }
+            match method_type.kind() {
+                TypeKind::Function {
+                    params,
+                    return_type,
+                } => {
+                    let function = name.kind().value.as_str();
+                    Self::check_trailing_block(function, &params[1..], &actuals[1..])?;
+                    if params.len() != actuals.len() {
+                        let err = Error::invalid_actuals(params.len(), actuals.len())
+                            .with_position(name.position());
+                        return Err(err);
                     }
-                } else if associated_type.deref().borrow().is_instance()
-                    || associated_type.deref().borrow().is_self()
-                {
-                    if !associated_function_info.takes_self {
-                        let err =
-                            Error::associated_function_invalid_receiver(name.kind().value.as_str());
+
+                    let receiver_mutablility = receiver.inner().meta();
+                    let expected_receiver_type = params.first().unwrap();
+
+                    if self.compare_types(
+                        expected_receiver_type.clone(),
+                        receiver.ty(),
+                        receiver_mutablility.mutable,
+                    ) {
+                        let err = Error::incompatible_types(
+                            expected_receiver_type.as_ref(),
+                            receiver.ty().as_ref(),
+                        );
                         return Err(err.with_position(name.position()));
                     }
 
-                    match method_type.kind() {
-                        TypeKind::Function {
-                            params,
-                            return_type,
-                        } => {
-                            if params.len() != actuals.len() {
-                                let err = Error::invalid_actuals(params.len(), actuals.len())
-                                    .with_position(name.position());
-                                return Err(err);
-                            }
-
-                            let receiver_mutablility = receiver.inner().meta();
-                            let expected_receiver_type = params.first().unwrap();
-
-                            if self.compare_types(
-                                expected_receiver_type.clone(),
-                                receiver.ty(),
-                                receiver_mutablility.mutable,
-                            ) {
-                                let err = Error::incompatible_types(
-                                    expected_receiver_type.as_ref(),
-                                    receiver.ty().as_ref(),
-                                );
-                                return Err(err.with_position(name.position()));
-                            }
-
-                            let mut mir_actuals = vec![receiver.clone()];
-                            for (act, param) in actuals.iter().zip(params).skip(1) {
-                                let mir_actual = self.resolve_expr(act, Some(param.clone()))?;
-                                mir_actuals.push(mir_actual);
-                            }
-
-                            let ty = Type::inner(associated_type.borrow().ty());
-                            let struct_entity = match ty.kind() {
-                                TypeKind::Struct { entity } => entity.clone(),
-                                _ => panic!("reciever is not a struct, invalid entity"),
-                            };
+                    let mut mir_actuals = vec![receiver.clone()];
+                    for (act, param) in actuals.iter().zip(params).skip(1) {
+                        let mir_actual = self.resolve_expr(act, Some(param.clone()))?;
+                        mir_actuals.push(mir_actual);
+                    }
+                    Self::check_reserve(&method_borrow, &mir_actuals)?;
 
-                            let method_expr = MethodExpr {
-                                struct_entity,
-                                function_type: method_type.clone(),
-                                name: name.kind().value.clone(),
-                                actuals: mir_actuals,
-                            };
+                    let method_expr = MethodExpr {
+                        struct_entity: structure.clone(),
+                        function_type: method_type.clone(),
+                        name: name.kind().value.clone(),
+                        actuals: mir_actuals,
+                    };
 
-                            let mutable = ResultMeta::new(
-                                false,
-                                false,
-                                return_type.is_mutable(),
-                                false,
-                                false,
-                            );
-                            // the address_mode should be determined by the address mode of the returned expression
-                            let inner = HirExprInner::new(
-                                AddressMode::Address,
-                                mutable,
-                                HirExprKind::Method(method_expr),
-                            );
-                            Ok(Rc::new(HirExpr::new(inner, position, return_type.clone())))
-                        }
-                        _ => {
-                            let err = Error::invalid_call_on_type(method_borrow.ty().as_ref());
-                            return Err(err.with_position(name.position()));
-                        }
-                    }
-                } else {
-                    unimplemented!()
+                    let mutable = ResultMeta::new(
+                        false,
+                        false,
+                        return_type.is_mutable(),
+                        false,
+                        false,
+                    );
+                    // the address_mode should be determined by the address mode of the returned expression
+                    let inner = HirExprInner::new(
+                        AddressMode::Address,
+                        mutable,
+                        HirExprKind::Method(method_expr),
+                    );
+                    Ok(Rc::new(HirExpr::new(inner, position, return_type.clone())))
+                }
+                _ => {
+                    let err = Error::invalid_call_on_type(method_borrow.ty().as_ref());
+                    Err(err.with_position(name.position()))
                 }
             }
-            _ => {
-                let err = Error::invalid_call_on_type(method_borrow.ty().as_ref())
-                    .with_position(name.position());
-                Err(err)
-            }
+        } else {
+            unimplemented!()
         }
     }
 
@@ -1051,9 +2157,40 @@ impl<'src> Typer<'src> {
         Ok(Rc::new(HirExpr::new(mir_inner, position, struct_type)))
     }
 
+    /// the expression naming entity, a resolved item or local.
+    pub(super) fn name_expr(name: EntityRef, position: Position) -> HirExprPtr {
+        name.deref().borrow().assert_live();
+        let ty = name.deref().borrow().ty();
+        let mutable = match name.deref().borrow().kind() {
+            EntityInfo::Variable(variable) => {
+                ResultMeta::new(variable.mutable, false, ty.is_mutable(), false, false)
+            }
+            EntityInfo::Structure(_) | EntityInfo::Enumeration(_) | EntityInfo::TypeParam => {
+                ResultMeta::new(false, false, false, false, true)
+            }
+            EntityInfo::Param(_local_info) => {
+                ResultMeta::new(false, false, ty.is_mutable(), false, false)
+            }
+            _ => ResultMeta::new(false, false, false, false, false),
+        };
+
+        Rc::new(HirExpr::new(
+            HirExprInner::new(AddressMode::Address, mutable, HirExprKind::Name(name)),
+            position,
+            ty,
+        ))
+    }
+
     pub(crate) fn resolve_type_expr(&mut self, expr: &Expr) -> Result<EntityRef, Error> {
         match expr.kind() {
             ExprKind::Name(name) => self.resolve_ident(name),
+            ExprKind::Field(operand, name) => match self.module_path(operand.as_ref()) {
+                Some(module) => self.module_item(&module, name.as_ref()),
+                None => {
+                    let err = Error::unknown_module(Self::dotted_name(operand.as_ref()).as_str());
+                    Err(err.with_position(operand.position()))
+                }
+            },
             // ExprKind::Field(operand, name) => {}
             // ExprKind::Call { operand, actual } => {
             //     let operand_entity = self.resolve_type_expr(operand.as_ref())?;
@@ -1086,11 +2223,73 @@ impl<'src> Typer<'src> {
         expected_type: Option<Rc<Type>>,
         position: Position,
     ) -> Result<Rc<HirExpr>, Error> {
-        let left = self.resolve_expr(lhs, expected_type.clone())?;
-        let right = self.resolve_expr(rhs, Some(left.ty()))?;
-        let left_type = Type::inner(left.ty());
-        let right_type = Type::inner(right.ty());
+        // the left operands of a chain, `1 + 2 + 3`, are resolved in a loop rather than nesting
+        // a call for every operator of it.
+        let mut chain = vec![(op, rhs, position)];
+        let mut lhs = lhs;
+        while let ExprKind::Binary(op, left, right) = lhs.kind() {
+            if self.generated_by(lhs).is_some() {
+                break;
+            }
+            chain.push((*op, right.as_ref(), lhs.position()));
+            lhs = left.as_ref();
+        }
+
+        let mut left = self.resolve_expr(lhs, expected_type)?;
+        while let Some((op, rhs, position)) = chain.pop() {
+            left = self.resolve_binary_operands(op, left, rhs, position)?;
+        }
+        Ok(left)
+    }
+
+    fn resolve_binary_operands(
+        &mut self,
+        op: BinaryOp,
+        left: HirExprPtr,
+        rhs: &Expr,
+        position: Position,
+    ) -> Result<Rc<HirExpr>, Error> {
+        // only comparisons apply to bools, anything else is an integer operation.
+        if !op.is_cmp() && Type::inner(left.ty()).is_bool() {
+            return Err(Error::bool_arithmetic(op).with_position(position));
+        }
+        // an operand that never produces a value takes the type of the other one.
+        let expected_type = Some(left.ty()).filter(|ty| !ty.is_never());
+        let right = match self.resolve_expr(rhs, expected_type) {
+            Err(err) if !op.is_cmp() && *err.pos() == rhs.position() => match err.kind() {
+                ErrorKind::IncompatibleTypes { right, .. } if right.is_bool() => {
+                    return Err(Error::bool_arithmetic(op).with_position(position));
+                }
+                _ => return Err(err),
+            },
+            right => right?,
+        };
+        let mut left_type = Type::inner(left.ty());
+        let mut right_type = Type::inner(right.ty());
+        if left_type.is_never() {
+            left_type = right_type.clone();
+        } else if right_type.is_never() {
+            right_type = left_type.clone();
+        }
+        let incompatible = || {
+            Error::incompatible_operands_for_binary_op(op, left_type.as_ref(), right_type.as_ref())
+                .with_position(position)
+        };
         let (address_mode, result_type) = match op {
+            // neither operand produces a value.
+            _ if left_type.is_never() => (AddressMode::Value, left_type),
+            // strings are concatenated and compared by their contents.
+            BinaryOp::Plus | BinaryOp::EqualEqual | BinaryOp::BangEqual
+                if left_type.is_string() || right_type.is_string() =>
+            {
+                if *left_type != *right_type {
+                    return Err(incompatible());
+                } else if op == BinaryOp::Plus {
+                    (AddressMode::Address, left_type)
+                } else {
+                    (AddressMode::Value, self.type_map.get_bool())
+                }
+            }
             BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Astrick | BinaryOp::Slash => {
                 if left_type.is_primitive() && right_type.is_primitive() {
                     if *left_type == *right_type {
@@ -1100,17 +2299,24 @@ impl<'src> Typer<'src> {
                     } else if left.is_literal() && right_type.is_signed() {
                         (AddressMode::Value, right_type)
                     } else {
-                        return Err(Error::incompatible_operands_for_binary_op(
-                            op,
-                            left_type.as_ref(),
-                            right_type.as_ref(),
-                        )
-                        .with_position(position));
+                        return Err(incompatible());
                     }
-                } else if left_type.is_struct() || right_type.is_struct() {
-                    todo!()
                 } else {
-                    todo!()
+                    return Err(incompatible());
+                }
+            }
+            // functions are compared by identity, only functions of the same type. Units are
+            // always equal.
+            BinaryOp::EqualEqual | BinaryOp::BangEqual
+                if left_type.is_function()
+                    || right_type.is_function()
+                    || left_type.is_unit()
+                    || right_type.is_unit() =>
+            {
+                if *left_type == *right_type {
+                    (AddressMode::Value, self.type_map.get_bool())
+                } else {
+                    return Err(incompatible());
                 }
             }
             BinaryOp::Less
@@ -1123,28 +2329,40 @@ impl<'src> Typer<'src> {
                     if *left_type == *right_type {
                         (AddressMode::Value, self.type_map.get_bool())
                     } else {
-                        return Err(Error::incompatible_operands_for_binary_op(
-                            op,
-                            left_type.as_ref(),
-                            right_type.as_ref(),
-                        )
-                        .with_position(position));
+                        return Err(incompatible());
                     }
-                } else if left_type.is_struct() || right_type.is_struct() {
-                    todo!()
                 } else {
-                    todo!()
+                    return Err(incompatible());
+                }
+            }
+            // flags combine into a value of the same enum.
+            BinaryOp::Pipe | BinaryOp::Ampersand | BinaryOp::Caret
+                if left_type.is_flags() || right_type.is_flags() =>
+            {
+                if *left_type == *right_type {
+                    (AddressMode::Value, left_type)
+                } else {
+                    return Err(incompatible());
                 }
             }
             BinaryOp::Percent
+            | BinaryOp::DivFloor
+            | BinaryOp::ModFloor
             | BinaryOp::Pipe
             | BinaryOp::Ampersand
-            | BinaryOp::LessLess
-            | BinaryOp::GreaterGreater => {
+            | BinaryOp::Caret => {
                 if left_type.is_integer() && right_type.is_integer() {
                     (AddressMode::Value, left_type)
                 } else {
-                    todo!()
+                    return Err(incompatible());
+                }
+            }
+            // the amount is shifted in the type of the value.
+            BinaryOp::LessLess | BinaryOp::GreaterGreater => {
+                if left_type.is_integer() && *left_type == *right_type {
+                    (AddressMode::Value, left_type)
+                } else {
+                    return Err(incompatible());
                 }
             }
         };
@@ -1172,7 +2390,7 @@ impl<'src> Typer<'src> {
         let ty = Type::inner(operand.ty());
         let (address_mode, result_type) = match op {
             UnaryOp::Bang => {
-                if ty.is_bool() || ty.is_integer() {
+                if ty.is_bool() || ty.is_integer() || ty.is_flags() {
                     (AddressMode::Value, ty)
                 } else {
                     return Err(Error::incompatible_operands_for_unary_op(op, ty.as_ref())
@@ -0,0 +1,89 @@
+use crate::analysis::typer::Typer;
+use crate::error::Error;
+use crate::ir::ast::{Expr, Node};
+use crate::ir::hir::{
+    AddressMode, HirExpr, HirExprInner, HirExprKind, HirExprPtr, MethodExpr, MirNode, ResultMeta,
+    WeakFunction,
+};
+use crate::syntax::Position;
+use crate::types::{Type, TypeKind};
+use std::ops::Deref;
+use std::rc::Rc;
+
+const WEAK: &str = "weak";
+const GC_COLLECT: &str = "gc_collect";
+
+impl<'a> Typer<'a> {
+    /// `weak` and `gc_collect` are built in functions, they are shadowed by any entity with the
+    /// same name.
+    pub(super) fn is_gc_builtin(&self, operand: &Expr) -> bool {
+        self.is_builtin(operand, WEAK) || self.is_builtin(operand, GC_COLLECT)
+    }
+
+    /// `Weak[T]`, only a heap object can be referred to weakly. Any other value is copied, there
+    /// is nothing that could be collected.
+    pub(crate) fn weak_of(
+        &mut self,
+        inner: Rc<Type>,
+        position: Position,
+    ) -> Result<Rc<Type>, Error> {
+        let inner = Type::inner(inner);
+        if !inner.kind().is_heap_object() {
+            return Err(Error::weak_to_non_heap_value(inner.as_ref()).with_position(position));
+        }
+        Ok(self.insert_type(TypeKind::Weak { inner }))
+    }
+
+    /// `weak(obj)` is a `Weak[T]` that does not keep obj alive, `get` on it is none once the
+    /// object has been collected. `gc_collect()` runs a full collection.
+    pub(super) fn resolve_gc_builtin(
+        &mut self,
+        operand: &Expr,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        if self.is_builtin(operand, GC_COLLECT) {
+            if !actuals.is_empty() {
+                let err = Error::invalid_actuals(0, actuals.len());
+                return Err(err.with_position(position));
+            }
+            let inner = HirExprInner::new(
+                AddressMode::Value,
+                ResultMeta::default(),
+                HirExprKind::GcCollect,
+            );
+            return Ok(Rc::new(HirExpr::new(
+                inner,
+                position,
+                self.type_map.get_unit(),
+            )));
+        }
+
+        let referent = match actuals {
+            [referent] => self.resolve_expr(referent.as_ref(), None)?,
+            _ => return Err(Error::invalid_actuals(1, actuals.len()).with_position(position)),
+        };
+        let ty = self.weak_of(referent.ty(), actuals[0].position())?;
+        let entity = self.collection_entity(ty.clone());
+        let name = WeakFunction::New.name();
+        let function_type = entity
+            .deref()
+            .borrow()
+            .as_struct()
+            .methods
+            .get(name)
+            .map(|method| method.deref().borrow().ty());
+        let method_expr = MethodExpr {
+            struct_entity: entity,
+            function_type: function_type.expect("the functions of a weak reference"),
+            name: name.to_owned(),
+            actuals: vec![referent],
+        };
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::default(),
+            HirExprKind::Method(method_expr),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
+    }
+}
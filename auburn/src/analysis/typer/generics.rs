@@ -0,0 +1,227 @@
+use crate::analysis::typer::{Typer, EXPR_RESULT_USED};
+use crate::analysis::{EntityInfo, EntityRef};
+use crate::error::Error;
+use crate::ir::ast::{Expr, ExprKind, Node};
+use crate::ir::hir::{
+    AddressMode, CallExpr, HirExpr, HirExprInner, HirExprKind, MirNode, ResultMeta,
+};
+use crate::passes::is_generic;
+use crate::syntax::Position;
+use crate::types::{Substitution, Type, TypeKind};
+use std::ops::Deref;
+use std::rc::Rc;
+
+impl<'a> Typer<'a> {
+    /// the generic function operand names when it is called, `identity(1)` or
+    /// `util.identity(1)`.
+    pub(super) fn generic_callee(&mut self, operand: &Expr) -> Result<Option<EntityRef>, Error> {
+        let entity = match operand.kind() {
+            ExprKind::Name(name) => self.resolve_ident(name)?,
+            ExprKind::Field(module, name) => match self.module_path(module.as_ref()) {
+                Some(module) => self.module_item(&module, name.as_ref())?,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(entity).filter(is_generic))
+    }
+
+    /// a generic function is only called, it is not a value until it is given type arguments.
+    pub(super) fn check_not_generic(entity: &EntityRef, position: Position) -> Result<(), Error> {
+        if is_generic(entity) {
+            let err = Error::generic_function_value(entity.deref().borrow().name());
+            return Err(err.with_position(position));
+        }
+        Ok(())
+    }
+
+    /// the type arguments of a call of a generic function are inferred from the types of the
+    /// actuals, in order. An actual is resolved expecting its parameter once the types of the
+    /// parameter are known from the actuals before it, so `pair(1, 2)` is `pair[i32]`. The
+    /// call calls the instance of the function for its type arguments.
+    pub(super) fn resolve_generic_call(
+        &mut self,
+        generic: EntityRef,
+        operand: &Expr,
+        actuals: &[Box<Expr>],
+    ) -> Result<Rc<HirExpr>, Error> {
+        let (name, function_type, type_params) = {
+            let borrow = generic.deref().borrow();
+            match borrow.kind() {
+                EntityInfo::Function(function) => (
+                    borrow.name().to_owned(),
+                    borrow.ty(),
+                    function.type_params.clone(),
+                ),
+                _ => unreachable!("only functions are generic"),
+            }
+        };
+        let params = match function_type.kind() {
+            TypeKind::Function { params, .. } => params.clone(),
+            _ => unreachable!("a function has a function type"),
+        };
+        Self::check_trailing_block(name.as_str(), &params, actuals)?;
+        if params.len() != actuals.len() {
+            let err = Error::invalid_actuals(params.len(), actuals.len());
+            return Err(err.with_position(operand.position()));
+        }
+
+        let mut substitution = Substitution::default();
+        let mut mir_actuals = vec![];
+        for (actual, param) in actuals.iter().zip(params.iter()) {
+            let expected = substitution.apply(param, self.type_map);
+            let expected = Some(expected).filter(|ty| !ty.kind().has_type_params());
+            let mir_actual = self.resolve_expr(actual, expected)?;
+            Self::unify(param, &mir_actual.ty(), &mut substitution)
+                .map_err(|err| err.with_position(actual.position()))?;
+            mir_actuals.push(mir_actual);
+        }
+        let mut type_args = vec![];
+        for param in type_params.iter() {
+            match substitution.get(param) {
+                Some(ty) => type_args.push(ty),
+                None => {
+                    let param = param.deref().borrow();
+                    let err = Error::cannot_infer_type_param(name.as_str(), param.name());
+                    return Err(err.with_position(operand.position()));
+                }
+            }
+        }
+
+        let position = operand.position();
+        let instance = self
+            .instances
+            .instantiate(&generic, type_args, position, self.type_map);
+        let mir_operand = Self::name_expr(instance, position);
+        let function_type = mir_operand.ty();
+        let return_type = match function_type.kind() {
+            TypeKind::Function { return_type, .. } => return_type.clone(),
+            _ => unreachable!("a function has a function type"),
+        };
+        let call_expr = CallExpr {
+            operand: mir_operand,
+            function_type,
+            actuals: mir_actuals,
+        };
+        let result_used = self.check_state(EXPR_RESULT_USED);
+        let meta = ResultMeta::funct(
+            false,
+            false,
+            return_type.is_mutable(),
+            false,
+            false,
+            true,
+            result_used,
+        );
+        let inner = HirExprInner::new(AddressMode::Value, meta, HirExprKind::Call(call_expr));
+        Ok(Rc::new(HirExpr::new(inner, position, return_type)))
+    }
+
+    /// binds the type parameters named by param to the parts of found they match, a parameter
+    /// bound by an earlier actual must match the same type.
+    fn unify(
+        param: &Rc<Type>,
+        found: &Rc<Type>,
+        substitution: &mut Substitution,
+    ) -> Result<(), Error> {
+        let found = Type::inner(found.clone());
+        let mismatch = || Error::incompatible_types(param.as_ref(), found.as_ref());
+        if !param.kind().has_type_params() {
+            return if Type::inner(param.clone()).kind() == found.kind() {
+                Ok(())
+            } else {
+                Err(mismatch())
+            };
+        }
+        match (param.kind(), found.kind()) {
+            (TypeKind::Param { entity }, _) => match substitution.get(entity) {
+                Some(bound) if bound.kind() != found.kind() => {
+                    let name = entity.deref().borrow().name().to_owned();
+                    Err(Error::conflicting_type_argument(&name, &bound, &found))
+                }
+                Some(_) => Ok(()),
+                None => {
+                    substitution.bind(entity.clone(), found.clone());
+                    Ok(())
+                }
+            },
+            (TypeKind::Mutable { inner }, _) => Self::unify(inner, &found, substitution),
+            (TypeKind::Option { inner: param }, TypeKind::Option { inner: found })
+            | (TypeKind::Weak { inner: param }, TypeKind::Weak { inner: found })
+            | (TypeKind::Task { result: param }, TypeKind::Task { result: found })
+            | (
+                TypeKind::Vector {
+                    element_type: param,
+                },
+                TypeKind::Vector {
+                    element_type: found,
+                },
+            )
+            | (
+                TypeKind::Slice {
+                    element_type: param,
+                },
+                TypeKind::Slice {
+                    element_type: found,
+                },
+            ) => Self::unify(param, found, substitution),
+            (
+                TypeKind::Array {
+                    element_type: param,
+                    size: param_size,
+                },
+                TypeKind::Array {
+                    element_type: found,
+                    size: found_size,
+                },
+            ) if param_size == found_size => Self::unify(param, found, substitution),
+            (
+                TypeKind::Result { ok, err },
+                TypeKind::Result {
+                    ok: found_ok,
+                    err: found_err,
+                },
+            ) => {
+                Self::unify(ok, found_ok, substitution)?;
+                Self::unify(err, found_err, substitution)
+            }
+            (
+                TypeKind::Map {
+                    key_type,
+                    value_type,
+                },
+                TypeKind::Map {
+                    key_type: found_key,
+                    value_type: found_value,
+                },
+            ) => {
+                Self::unify(key_type, found_key, substitution)?;
+                Self::unify(value_type, found_value, substitution)
+            }
+            (TypeKind::Tuple { elements }, TypeKind::Tuple { elements: found })
+                if elements.len() == found.len() =>
+            {
+                for (element, found) in elements.iter().zip(found.iter()) {
+                    Self::unify(element, found, substitution)?;
+                }
+                Ok(())
+            }
+            (
+                TypeKind::Function {
+                    params,
+                    return_type,
+                },
+                TypeKind::Function {
+                    params: found_params,
+                    return_type: found_return,
+                },
+            ) if params.len() == found_params.len() => {
+                for (param, found) in params.iter().zip(found_params.iter()) {
+                    Self::unify(param, found, substitution)?;
+                }
+                Self::unify(return_type, found_return, substitution)
+            }
+            _ => Err(mismatch()),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::analysis::scope::{Scope, ScopeKind};
+use crate::analysis::typer::contracts::{ENSURES_ATTRIBUTE, REQUIRES_ATTRIBUTE};
 use crate::analysis::typer::Typer;
 use crate::analysis::typer::{
     ASSOCIATIVE_FUNCTION, EXPR_RESULT_USED, FUNCTION, FUNCTION_BODY, FUNCTION_PARAM,
@@ -6,21 +7,35 @@ use crate::analysis::typer::{
 };
 use crate::analysis::{Entity, EntityInfo, EntityRef};
 use crate::error::Error;
-use crate::ir::ast::{Expr, FunctionBody, Identifier, Item, ItemKind, Node, Spec, Visibility};
-use crate::ir::hir::{HirExprPtr, HirSpec, HirSpecKind, HirSpecPtr, MirNode};
+use crate::ir::ast::{
+    Attribute, EnumVariant, Expr, FunctionBody, Identifier, Item, ItemKind, Node, Spec, UnaryOp,
+    Visibility,
+};
+use crate::ir::hir::{
+    AddressMode, HirExprInner, HirExprKind, HirExprPtr, HirSpec, HirSpecKind, HirSpecPtr, MirNode,
+    ResultMeta,
+};
 use crate::syntax::Position;
 use crate::types::{Type, TypeKind};
 use crate::utils::{new_ptr, Ptr};
 use crate::{
     analysis::entity::{
-        AssociatedFunctionInfo, FunctionInfo, LocalInfo, Path, StructureInfo, VariableInfo,
+        AssociatedFunctionInfo, EnumInfo, FunctionInfo, LocalInfo, Path, StructureInfo,
+        VariableInfo, VariantInfo,
     },
     ir::ast::ExprKind,
 };
 use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::ops::Deref;
 use std::rc::Rc;
 
+/// attribute making every variant of an enum a bit of a set.
+pub(super) const FLAGS_ATTRIBUTE: &str = "flags";
+
+/// attribute of a struct whose `finalize` method runs once an instance is collected.
+pub(super) const FINALIZE_ATTRIBUTE: &str = "finalize";
+
 macro_rules! with_state {
     ($typer:expr, $state:expr, $body:tt) => {{
         let old_state = $typer.state;
@@ -32,11 +47,22 @@ macro_rules! with_state {
 }
 
 impl<'src> Typer<'src> {
+    /// an item of the module, every kind of item can be declared by the module.
     pub(crate) fn resolve_top_level_item(&mut self, item: &Item) -> Result<EntityRef, Error> {
         if let Some(name) = item.get_name() {
             if let Some(entity) = self.shallow_lookup(name.kind().value.as_str()) {
-                entity.deref().borrow_mut().to_resolving();
-                self.resolve_item_impl(item, entity, true, true)
+                {
+                    // a declared function keeps its signature while its body is resolved, a
+                    // declared structure is resolved and only its methods are left.
+                    let mut borrow = entity.deref().borrow_mut();
+                    if !borrow.is_declared() && !borrow.is_struct() {
+                        borrow.to_resolving();
+                    }
+                }
+                self.items.push(entity.clone());
+                let resolved = self.resolve_item_impl(item, entity, true, true);
+                self.items.pop();
+                resolved
             } else {
                 let err = Error::other(
                     "Compiler Error: attempting to resolve top level item but failed to find entity"
@@ -49,21 +75,49 @@ impl<'src> Typer<'src> {
         }
     }
 
+    /// an item of a body, it is a local of the body and can not be used outside of it.
     pub(crate) fn resolve_item(&mut self, item: &Item) -> Result<EntityRef, Error> {
+        Self::check_local_placement(item)?;
         if let Some(name) = item.get_name() {
             let vis = item.get_visibility();
             self.check_duplicate_item_name(name)?;
-            let entity = Ptr::new(RefCell::new(Entity::resolving(
-                vis,
-                name.kind().value.clone(),
-                self.type_map.get_invalid(),
-            )));
+            self.check_shadowed_name(name)?;
+            let entity = Ptr::new(RefCell::new(
+                Entity::resolving(vis, name.kind().value.clone(), self.type_map.get_invalid())
+                    .declared_at(name.position()),
+            ));
+            if let ItemKind::Function { .. } = item.kind() {
+                // a local function is not a method of the structure whose method declares it.
+                let state = self.state;
+                self.state &= !ASSOCIATIVE_FUNCTION;
+                let res = self.resolve_item_impl(item, entity, false, false);
+                self.state = state;
+                return res;
+            }
             self.resolve_item_impl(item, entity, false, false)
         } else {
             panic!("Compiler Error: attempting to resolving a field, param, or self as an item. These should be resolved locally");
         }
     }
 
+    /// a body declares variables and functions, the types are only declared by the module.
+    fn check_local_placement(item: &Item) -> Result<(), Error> {
+        let kind = match item.kind() {
+            ItemKind::Struct { .. } => "structures",
+            ItemKind::Enum { .. } => "enumerations",
+            _ => {
+                if item.get_visibility() == Visibility::Public {
+                    let err = Error::only_at_module_scope("pub");
+                    return Err(err.with_position(item.position()));
+                }
+                return Ok(());
+            }
+        };
+        let name = item.get_name().expect("structures and enumerations are named");
+        let err = Error::nested_item_not_supported(kind, name.kind().value.as_str());
+        Err(err.with_position(name.position()))
+    }
+
     pub(crate) fn resolve_item_impl(
         &mut self,
         item: &Item,
@@ -89,17 +143,38 @@ impl<'src> Typer<'src> {
                 declared,
                 is_file_scope,
             ),
-            ItemKind::Struct { vis, name, fields } => self.resolve_struct(
+            ItemKind::Struct {
+                vis,
+                name,
+                attributes,
+                fields,
+            } => self.resolve_struct(
                 entity,
                 *vis,
                 name,
+                attributes.as_slice(),
                 fields.as_slice(),
                 item.position(),
                 declared,
             ),
+            ItemKind::Enum {
+                vis,
+                name,
+                attributes,
+                variants,
+            } => self.resolve_enum(
+                entity,
+                *vis,
+                name,
+                attributes.as_slice(),
+                variants.as_slice(),
+                declared,
+            ),
             ItemKind::Function {
                 vis,
                 name,
+                attributes,
+                type_params,
                 params,
                 ret,
                 body,
@@ -108,6 +183,8 @@ impl<'src> Typer<'src> {
                     entity,
                     *vis,
                     name,
+                    attributes.as_slice(),
+                    type_params.as_slice(),
                     params.as_slice(),
                     ret.as_ref(),
                     body,
@@ -132,6 +209,59 @@ impl<'src> Typer<'src> {
         }
     }
 
+    /// with no shadowing, a local can not reuse a name declared by an enclosing scope. The
+    /// locals hidden by desugarings and the primitives are not declarations of the program.
+    pub(crate) fn check_shadowed_name(&self, name: &Identifier) -> Result<(), Error> {
+        let value = name.kind().value.as_str();
+        if !self.checks.no_shadowing || value.starts_with("__") {
+            return Ok(());
+        }
+        let shadowed = self.scope_stack.iter().rev().skip(1).any(|scope| {
+            !matches!(
+                scope.kind(),
+                ScopeKind::Prelude
+                    | ScopeKind::Struct(_)
+                    | ScopeKind::StructMethods(_)
+                    | ScopeKind::Enum(_)
+            ) && scope.shallow_lookup(value).is_some()
+        });
+        if shadowed {
+            Err(Error::shadowed_name(value).with_position(name.position()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// with annotated signatures, a public function writes out the types of its parameters and
+    /// the type it returns, a block body without a return type returns unit.
+    fn check_signature_annotated(
+        &self,
+        vis: Visibility,
+        name: &Identifier,
+        params: &[Box<Item>],
+        return_spec: &Spec,
+        body: &FunctionBody,
+    ) -> Result<(), Error> {
+        if !self.checks.annotated_signatures || vis != Visibility::Public {
+            return Ok(());
+        }
+        let function = name.kind().value.as_str();
+        for param in params {
+            if let ItemKind::Param {
+                names, spec: None, ..
+            } = param.kind()
+            {
+                let err = Error::missing_param_annotation(function, names[0].kind().value.as_str());
+                return Err(err.with_position(param.position()));
+            }
+        }
+        if matches!(body, FunctionBody::Expression(_)) && return_spec.is_infer() {
+            let err = Error::missing_return_annotation(function);
+            return Err(err.with_position(name.position()));
+        }
+        Ok(())
+    }
+
     pub(crate) fn resolve_variable(
         &mut self,
         entity: EntityRef,
@@ -144,7 +274,19 @@ impl<'src> Typer<'src> {
         declared: bool,
         is_file_scope: bool,
     ) -> Result<EntityRef, Error> {
-        let (spec, init, result_type) = self.resolve_local(spec, init, position)?;
+        let (spec, mut init, result_type) = self.resolve_local(spec, init, position)?;
+        // a constant is initialized by the integer it folds to, the vm only initializes globals
+        // with literals.
+        if is_file_scope && !mutable {
+            init = init.map(|init| match Self::constant_integer(init.as_ref()) {
+                Some(value) if !init.is_literal() => {
+                    let kind = HirExprKind::Integer(value);
+                    let inner = HirExprInner::new(AddressMode::Value, ResultMeta::literal(), kind);
+                    Rc::new(init.with_inner(inner))
+                }
+                _ => init,
+            });
+        }
 
         let variable_info = VariableInfo {
             spec,
@@ -171,10 +313,96 @@ impl<'src> Typer<'src> {
         entity: EntityRef,
         _vis: Visibility,
         name: &Identifier,
+        attributes: &[Attribute],
         fields: &[Box<Item>],
         _position: Position,
         declared: bool,
     ) -> Result<EntityRef, Error> {
+        let mut finalized = false;
+        for attribute in attributes {
+            let name = attribute.name.kind().value.as_str();
+            match name {
+                FINALIZE_ATTRIBUTE if attribute.argument.is_some() => {
+                    let err = Error::unexpected_attribute_argument(name);
+                    return Err(err.with_position(attribute.name.position()));
+                }
+                FINALIZE_ATTRIBUTE => finalized = true,
+                FLAGS_ATTRIBUTE => {
+                    let err = Error::misplaced_attribute(name, "an enum");
+                    return Err(err.with_position(attribute.name.position()));
+                }
+                REQUIRES_ATTRIBUTE | ENSURES_ATTRIBUTE => {
+                    let err = Error::misplaced_attribute(name, "a function");
+                    return Err(err.with_position(attribute.name.position()));
+                }
+                other => {
+                    let err = Error::unknown_attribute(other);
+                    return Err(err.with_position(attribute.name.position()));
+                }
+            }
+        }
+
+        // a structure is declared before the bodies of the module, see `declare_items`.
+        if !entity.deref().borrow().is_struct() {
+            self.declare_struct(entity.clone(), name, fields)?;
+        }
+
+        let (_, methods) = Self::split_struct_members(fields);
+        let entities = entity.deref().borrow().as_struct().methods.elements().to_vec();
+        with_state!(self, ASSOCIATIVE_FUNCTION, {
+            self.set_self(entity.clone());
+            for (idx, (&method, entity)) in methods.iter().zip(entities).enumerate() {
+                self.resolve_method(entity, method, idx)?;
+            }
+            self.unset_self();
+        });
+
+        if finalized {
+            self.check_finalizer(&entity, name)?;
+            entity.deref().borrow_mut().as_struct_mut().finalized = true;
+        }
+
+        if !declared {
+            self.insert_entity(name.kind().value.as_str(), entity.clone());
+        }
+
+        Ok(entity)
+    }
+
+    /// a finalized structure needs a method `finalize(self)` returning unit, the collector calls
+    /// it with the instance that became unreachable.
+    fn check_finalizer(&self, entity: &EntityRef, name: &Identifier) -> Result<(), Error> {
+        let methods = entity.deref().borrow().as_struct().methods.clone();
+        let valid = methods.get(FINALIZE_ATTRIBUTE).is_some_and(|method| {
+            let method = method.deref().borrow();
+            let takes_self = match method.kind() {
+                EntityInfo::AssociatedFunction(info) => info.takes_self,
+                _ => false,
+            };
+            match method.ty().kind() {
+                TypeKind::Function {
+                    params,
+                    return_type,
+                } => takes_self && params.len() == 1 && return_type.is_unit(),
+                _ => false,
+            }
+        });
+        if !valid {
+            let err = Error::missing_finalizer(name.kind().value.as_str());
+            return Err(err.with_position(name.position()));
+        }
+        Ok(())
+    }
+
+    /// resolves the fields of a structure and gives it its type, its methods are declared by
+    /// name so the bodies of the module can find them. The bodies of the methods are resolved
+    /// where the structure is reached.
+    pub(crate) fn declare_struct(
+        &mut self,
+        entity: EntityRef,
+        name: &Identifier,
+        fields: &[Box<Item>],
+    ) -> Result<(), Error> {
         let (fields, methods) = Self::split_struct_members(fields);
         let fields_scope = with_state!(self, STRUCT, {
             self.push_scope(ScopeKind::Struct(name.kind().value.clone()));
@@ -210,6 +438,7 @@ impl<'src> Typer<'src> {
         let structure_info = StructureInfo {
             fields: fields_scope,
             methods: Scope::new_ref(ScopeKind::Invalid, None),
+            finalized: false,
         };
 
         let path = self.current_path_from_root();
@@ -218,57 +447,200 @@ impl<'src> Typer<'src> {
             .borrow_mut()
             .resolve(ty, EntityInfo::Structure(structure_info), path);
 
-        with_state!(self, ASSOCIATIVE_FUNCTION, {
-            let mut entities = vec![];
-
-            self.set_self(entity.clone());
-            self.push_scope(ScopeKind::StructMethods(name.kind().value.clone()));
+        self.push_scope(ScopeKind::StructMethods(name.kind().value.clone()));
 
-            // pre-declare all methods in method scope.
-            for method in methods.iter() {
-                if let ItemKind::Function { vis, name, .. } = method.kind() {
-                    let entity = new_ptr(Entity::unresolved(
+        // pre-declare all methods in method scope.
+        for method in methods.iter() {
+            if let ItemKind::Function { vis, name, .. } = method.kind() {
+                let entity = new_ptr(
+                    Entity::unresolved(
                         *vis,
                         name.kind().value.clone(),
                         method.deref().clone(),
                         self.type_map.get_invalid(),
-                    ));
-                    entities.push(entity.clone());
-                    self.insert_entity(name.kind().value.as_str(), entity);
+                    )
+                    .declared_at(name.position()),
+                );
+                self.insert_entity(name.kind().value.as_str(), entity);
+            }
+        }
+
+        let method_scope = self.pop_scope();
+        entity.deref().borrow_mut().as_struct_mut().methods = method_scope;
+        Ok(())
+    }
+
+    /// resolves the method at idx of the structure self is, its body is resolved along with
+    /// its signature.
+    pub(crate) fn resolve_method(
+        &mut self,
+        entity: EntityRef,
+        method: &Item,
+        idx: usize,
+    ) -> Result<EntityRef, Error> {
+        if !entity.deref().borrow().is_declared() {
+            entity.deref().borrow_mut().to_resolving();
+        }
+        match method.kind() {
+            ItemKind::Function {
+                vis,
+                name,
+                attributes,
+                type_params,
+                params,
+                ret,
+                body,
+            } => self.resolve_function(
+                entity,
+                *vis,
+                name,
+                attributes,
+                type_params,
+                params,
+                ret,
+                body,
+                method.position(),
+                true,
+                Some(idx),
+            ),
+            _ => todo!(),
+        }
+    }
+
+    pub(crate) fn resolve_enum(
+        &mut self,
+        entity: EntityRef,
+        vis: Visibility,
+        name: &Identifier,
+        attributes: &[Attribute],
+        variants: &[EnumVariant],
+        declared: bool,
+    ) -> Result<EntityRef, Error> {
+        let mut flags = false;
+        for attribute in attributes {
+            let name = attribute.name.kind().value.as_str();
+            match name {
+                FLAGS_ATTRIBUTE if attribute.argument.is_some() => {
+                    let err = Error::unexpected_attribute_argument(name);
+                    return Err(err.with_position(attribute.name.position()));
                 }
+                FLAGS_ATTRIBUTE => flags = true,
+                FINALIZE_ATTRIBUTE => {
+                    let err = Error::misplaced_attribute(name, "a struct");
+                    return Err(err.with_position(attribute.name.position()));
+                }
+                REQUIRES_ATTRIBUTE | ENSURES_ATTRIBUTE => {
+                    let err = Error::misplaced_attribute(name, "a function");
+                    return Err(err.with_position(attribute.name.position()));
+                }
+                other => {
+                    let err = Error::unknown_attribute(other);
+                    return Err(err.with_position(attribute.name.position()));
+                }
+            }
+        }
+
+        let mut variant_infos: Vec<VariantInfo> = Vec::with_capacity(variants.len());
+        // discriminants count up from the previous variant, starting at 0. flags use the
+        // next bit instead, starting at 1.
+        let mut next_discriminant = Some(if flags { 1 } else { 0 });
+        for variant in variants {
+            let variant_name = variant.name.kind().value.as_str();
+            if variant_infos.iter().any(|info| info.name == variant_name) {
+                let err = Error::duplicate_name(variant_name.to_owned());
+                return Err(err.with_position(variant.name.position()));
             }
 
-            let method_scope = self.pop_scope();
-            entity.deref().borrow_mut().as_struct_mut().methods = method_scope;
+            let mut fields = Vec::with_capacity(variant.fields.len());
+            for field in variant.fields.iter() {
+                fields.push(self.resolve_spec(field.as_ref())?.ty());
+            }
 
-            for (idx, (&method, entity)) in methods.iter().zip(entities).enumerate() {
-                match method.kind() {
-                    ItemKind::Function {
-                        vis,
-                        name,
-                        params,
-                        ret,
-                        body,
-                    } => {
-                        self.resolve_function(
-                            entity,
-                            *vis,
-                            name,
-                            params,
-                            ret,
-                            body,
-                            method.position(),
-                            true,
-                            Some(idx),
-                        )?;
-                    }
-                    _ => todo!(),
+            if flags && !fields.is_empty() {
+                let err = Error::flags_variant_with_data(variant_name);
+                return Err(err.with_position(variant.position));
+            }
+
+            let discriminant = match variant.discriminant.as_ref() {
+                Some(expr) if !fields.is_empty() => {
+                    let err = Error::discriminant_on_data_variant(variant_name);
+                    return Err(err.with_position(expr.position()));
                 }
+                Some(expr) => match Self::discriminant_literal(expr.as_ref()) {
+                    Some(value) => value,
+                    None => {
+                        let err = Error::invalid_discriminant(variant_name);
+                        return Err(err.with_position(expr.position()));
+                    }
+                },
+                None => match next_discriminant {
+                    Some(value) => value,
+                    None => {
+                        let err = Error::discriminant_overflow(variant_name);
+                        return Err(err.with_position(variant.position));
+                    }
+                },
+            };
+
+            if flags && (discriminant <= 0 || discriminant.count_ones() != 1) {
+                let err = Error::flags_discriminant_not_power_of_two(variant_name, discriminant);
+                return Err(err.with_position(variant.position));
             }
 
-            self.unset_self();
+            if let Some(other) = variant_infos
+                .iter()
+                .find(|info| info.discriminant == discriminant)
+            {
+                let err =
+                    Error::duplicate_discriminant(variant_name, discriminant, other.name.as_str());
+                return Err(err.with_position(variant.position));
+            }
+
+            next_discriminant = if flags {
+                discriminant.checked_mul(2).filter(|next| *next > 0)
+            } else {
+                discriminant.checked_add(1)
+            };
+            variant_infos.push(VariantInfo {
+                name: variant_name.to_owned(),
+                discriminant,
+                fields,
+            });
+        }
+
+        let ty = self.insert_type(TypeKind::Enum {
+            entity: entity.clone(),
         });
 
+        let enum_info = EnumInfo {
+            variants: variant_infos.clone(),
+            scope: Scope::new_ref(ScopeKind::Invalid, None),
+            flags,
+        };
+
+        let path = self.current_path_from_root();
+        entity
+            .borrow_mut()
+            .resolve(ty.clone(), EntityInfo::Enumeration(enum_info), path);
+
+        // each variant is an entity of the enum, so what names it can point at it.
+        self.push_scope(ScopeKind::Enum(name.kind().value.clone()));
+        let path = self.current_path_from_root();
+        for (variant, info) in variants.iter().zip(variant_infos) {
+            let variant_entity = Entity::new(
+                vis,
+                info.name.clone(),
+                ty.clone(),
+                EntityInfo::Variant(info),
+                path.clone(),
+            )
+            .declared_at(variant.name.position());
+            let variant_name = variant.name.kind().value.as_str();
+            self.insert_entity(variant_name, new_ptr(variant_entity));
+        }
+        let variant_scope = self.pop_scope();
+        entity.deref().borrow_mut().as_enum_mut().scope = variant_scope;
+
         if !declared {
             self.insert_entity(name.kind().value.as_str(), entity.clone());
         }
@@ -276,6 +648,18 @@ impl<'src> Typer<'src> {
         Ok(entity)
     }
 
+    /// value of an explicit discriminant, only integer literals and their negation are allowed.
+    fn discriminant_literal(expr: &Expr) -> Option<i64> {
+        match expr.kind() {
+            ExprKind::Integer(val) => i64::try_from(*val).ok(),
+            ExprKind::Unary(UnaryOp::Minus, operand) => match operand.kind() {
+                ExprKind::Integer(val) => i64::try_from(-i128::from(*val)).ok(),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     pub(crate) fn resolve_local(
         &mut self,
         spec: Option<&Box<Spec>>,
@@ -288,7 +672,12 @@ impl<'src> Typer<'src> {
         };
 
         let init = match init {
-            Some(init) => Some(self.resolve_expr(init.as_ref(), None)?),
+            Some(init) => {
+                let expected_type = spec.as_ref().map(|spec| spec.ty());
+                Some(with_state!(self, EXPR_RESULT_USED, {
+                    self.resolve_expr(init.as_ref(), expected_type)?
+                }))
+            }
             None => None,
         };
 
@@ -297,7 +686,7 @@ impl<'src> Typer<'src> {
                 let spec_type = spec.ty();
                 let init_type = init.ty();
 
-                if *spec_type == *init_type {
+                if *spec_type == *init_type || init_type.is_never() {
                     spec_type
                 } else {
                     return Err(
@@ -308,7 +697,7 @@ impl<'src> Typer<'src> {
             }
             (Some(spec), None) => spec.ty(),
             (None, Some(init)) => init.ty(),
-            (None, None) => return Err(Error::invalid_local_item().with_position(position)),
+            (None, None) => return Err(Error::cannot_infer_type().with_position(position)),
         };
 
         Ok((spec, init, result_type))
@@ -334,13 +723,16 @@ impl<'src> Typer<'src> {
                 default: init.clone(),
             };
             let info = EntityInfo::Field(local_info);
-            let entity = new_ptr(Entity::new(
-                vis,
-                name.kind().value.clone(),
-                ty.clone(),
-                info,
-                self.current_path_from_root(),
-            ));
+            let entity = new_ptr(
+                Entity::new(
+                    vis,
+                    name.kind().value.clone(),
+                    ty.clone(),
+                    info,
+                    self.current_path_from_root(),
+                )
+                .declared_at(name.position()),
+            );
             self.insert_entity(name.kind().value.as_str(), entity.clone());
             fields.push(entity);
         });
@@ -366,8 +758,10 @@ impl<'src> Typer<'src> {
     pub(crate) fn resolve_function(
         &mut self,
         entity: EntityRef,
-        _vis: Visibility,
+        vis: Visibility,
         name: &Identifier,
+        attributes: &[Attribute],
+        type_params: &[Identifier],
         params: &[Box<Item>],
         return_spec: &Spec,
         body: &FunctionBody,
@@ -375,11 +769,48 @@ impl<'src> Typer<'src> {
         declared: bool,
         index: Option<usize>,
     ) -> Result<EntityRef, Error> {
+        self.check_signature_annotated(vis, name, params, return_spec, body)?;
+        let contracts = Rc::new(Self::contracts_of(
+            name.kind().value.as_str(),
+            attributes,
+            params,
+            return_spec,
+            position,
+        )?);
+        let instrumented = self.contracts && !contracts.is_empty();
         let mut function_params = Vec::with_capacity(params.len());
         let mut takes_self = false;
+        let mut function_type_params = Vec::with_capacity(type_params.len());
         let _mir_items = with_state!(self, FUNCTION_PARAM, {
             let mut mir_items = vec![];
             self.push_scope(ScopeKind::Param(name.kind().value.clone()));
+            // a generic function is instantiated by the module using it, see `Monomorphizer`.
+            if let Some(type_param) = type_params.first() {
+                if index.is_some() || !declared {
+                    let err = Error::generic_not_supported().with_position(type_param.position());
+                    return Err(err);
+                }
+            }
+            for type_param in type_params {
+                self.check_duplicate_item_name(type_param)?;
+                let param_name = type_param.kind().value.as_str();
+                let entity = new_ptr(
+                    Entity::new(
+                        Visibility::Private,
+                        param_name.to_owned(),
+                        self.type_map.get_invalid(),
+                        EntityInfo::TypeParam,
+                        Path::empty(),
+                    )
+                    .declared_at(type_param.position()),
+                );
+                let ty = self.insert_type(TypeKind::Param {
+                    entity: entity.clone(),
+                });
+                entity.borrow_mut().resolve(ty, EntityInfo::TypeParam, Path::empty());
+                self.insert_entity(param_name, entity.clone());
+                function_type_params.push(entity);
+            }
             let mut start_index = 0;
             for param in params {
                 match param.kind() {
@@ -439,7 +870,10 @@ impl<'src> Typer<'src> {
             Ok(mir_items)
         })?;
 
-        let (return_type, _mir_spec, mir_expr) =
+        // a `return` of a function declared in the body does not check the contracts of this one.
+        let outer_postconditions = self.postconditions.take();
+        let outer_return_type = self.return_type.take();
+        let resolved_body =
             with_state!(self, FUNCTION_BODY | EXPR_RESULT_USED, {
                 match body {
                     FunctionBody::Block(expr) => {
@@ -452,9 +886,15 @@ impl<'src> Typer<'src> {
                         } else {
                             self.resolve_spec(return_spec)?
                         };
+                        // the value of the body and of every `return` is the declared type.
+                        let expected = (!return_spec.is_infer()).then(|| mir_spec.ty());
+                        self.return_type = expected.clone();
 
-                        let mir_expr = if let ExprKind::Block(stmts) = expr.kind() {
-                            self.resolve_block_expression(stmts, true, expr.position())?
+                        let mir_expr = if instrumented {
+                            self.check_contracts(contracts.as_ref(), Some(mir_spec.ty()))?;
+                            self.resolve_contract_body(contracts.clone(), body, expected)?
+                        } else if let ExprKind::Block(stmts) = expr.kind() {
+                            self.resolve_block_expression(stmts, true, expected, expr.position())?
                         } else {
                             unreachable!()
                         };
@@ -468,8 +908,14 @@ impl<'src> Typer<'src> {
                             Some(self.resolve_spec(return_spec)?)
                         };
 
-                        let mir_expr = self
-                            .resolve_expr(expr.as_ref(), mir_spec.as_ref().map(|spec| spec.ty()))?;
+                        let expected = mir_spec.as_ref().map(|spec| spec.ty());
+                        self.return_type = expected.clone();
+                        let mir_expr = if instrumented {
+                            self.check_contracts(contracts.as_ref(), expected.clone())?;
+                            self.resolve_contract_body(contracts.clone(), body, expected)?
+                        } else {
+                            self.resolve_expr(expr.as_ref(), expected)?
+                        };
 
                         let return_spec = match mir_spec {
                             Some(ty) => ty,
@@ -481,8 +927,14 @@ impl<'src> Typer<'src> {
                         Ok((return_spec.ty(), return_spec, mir_expr))
                     }
                 }
-            })?;
+            });
+        self.postconditions = outer_postconditions;
+        self.return_type = outer_return_type;
+        let (return_type, _mir_spec, mir_expr) = resolved_body?;
 
+        if !instrumented {
+            self.check_contracts(contracts.as_ref(), Some(return_type.clone()))?;
+        }
         let params_scope = self.pop_scope();
 
         let function_kind = TypeKind::Function {
@@ -503,6 +955,8 @@ impl<'src> Typer<'src> {
                 body: mir_expr,
                 takes_self,
                 index: index.expect("associated function should have an index"),
+                metrics: None,
+                builtin: None,
             };
 
             entity.borrow_mut().resolve(
@@ -515,6 +969,9 @@ impl<'src> Typer<'src> {
                 params: params_scope,
                 body_scope,
                 body: mir_expr,
+                metrics: None,
+                local: !declared,
+                type_params: function_type_params,
             };
 
             entity
@@ -539,6 +996,9 @@ impl<'src> Typer<'src> {
     ) -> Result<Vec<EntityRef>, Error> {
         let mut fields = vec![];
         let (spec, init, ty) = self.resolve_local(spec, init, position)?;
+        for name in names {
+            self.check_shadowed_name(name)?;
+        }
         names.iter().enumerate().for_each(|(idx, name)| {
             let index = start_index + idx;
             let local_info = LocalInfo {
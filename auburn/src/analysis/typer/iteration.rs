@@ -0,0 +1,578 @@
+use crate::analysis::typer::desugar::{Lowered, Synthesizer};
+use crate::analysis::typer::Typer;
+use crate::analysis::{EntityInfo, EntityRef};
+use crate::error::Error;
+use crate::ir::ast::{
+    AssignmentOp, BinaryOp, Expr, ExprKind, Identifier, ItemKind, Node, Stmt, StmtKind, UnaryOp,
+    Visibility,
+};
+use crate::ir::hir::{Desugaring, HirExprKind, HirExprPtr, MirNode};
+use crate::types::{Type, TypeKind};
+use std::rc::Rc;
+
+/// method of an iterator returning the next element, none once it is exhausted.
+const NEXT_METHOD: &str = "next";
+
+/// method of an iterable returning an iterator over it.
+const ITER_METHOD: &str = "iter";
+
+/// hidden locals of a lowered for loop, they can not be named by the user.
+const ITERABLE_LOCAL: &str = "__iterable__";
+const ITERATOR_LOCAL: &str = "__iterator__";
+
+/// built in function iterated by a counting for loop, it is shadowed by any entity with the
+/// same name.
+const RANGE_FUNCTION: &str = "range";
+
+/// method of a range iterating it from its last element.
+const REV_METHOD: &str = "rev";
+
+/// hidden locals of a lowered counting loop.
+const START_LOCAL: &str = "__start__";
+const END_LOCAL: &str = "__end__";
+const STEP_LOCAL: &str = "__step__";
+const ZERO_LOCAL: &str = "__zero__";
+const COUNT_LOCAL: &str = "__count__";
+const REMAINING_LOCAL: &str = "__remaining__";
+
+/// runtime error of a step that is only known to be zero once the loop is reached.
+const ZERO_STEP_MESSAGE: &str = "the step of a range is zero";
+
+/// `range(start, end, <step>)` or `start..end` as the value of a for loop, reversed by
+/// `.rev()`.
+struct Range<'e> {
+    start: &'e Expr,
+    end: &'e Expr,
+    step: Option<&'e Expr>,
+    reversed: bool,
+}
+
+/// how a for loop gets an iterator from the value it is given.
+enum Iteration {
+    /// the value is the iterator.
+    Iterator,
+    /// the iterator is returned by the `iter` method of the value.
+    Iterable,
+}
+
+impl<'a> Typer<'a> {
+    /// `for x in value { body }` is lowered to
+    /// ```text
+    /// {
+    ///     mut __iterator__ = value            // or value.iter()
+    ///     loop {
+    ///         let x = __iterator__.next() ?? break
+    ///         body
+    ///     }
+    /// }
+    /// ```
+    /// the element type is the type `next` returns, unwrapped.
+    pub(crate) fn resolve_for(&mut self, expr: &Expr) -> Result<HirExprPtr, Error> {
+        let (element, iterable, body) = match expr.kind() {
+            ExprKind::For {
+                element,
+                expr,
+                body,
+            } => (element, expr, body),
+            _ => unreachable!(),
+        };
+        if let Some(range) = self.range_of(iterable.as_ref())? {
+            return self.resolve_range_for(expr, element, range, body.as_ref());
+        }
+
+        // the value is resolved first, how it is iterated depends on its type.
+        let iterable = self.resolve_expr(iterable.as_ref(), None)?;
+        let iteration = self
+            .iteration_of(iterable.ty())
+            .map_err(|err| err.with_position(iterable.position()))?;
+
+        let mut synthesizer = Synthesizer::new(Desugaring::ForLoop, expr.position());
+        let value = synthesizer.resolved(iterable);
+        let mut stmts = vec![];
+        let iterator = match iteration {
+            Iteration::Iterator => value,
+            Iteration::Iterable => {
+                stmts.push(Self::local(&mut synthesizer, false, ITERABLE_LOCAL, value));
+                let iterable = Self::name(&mut synthesizer, ITERABLE_LOCAL);
+                synthesizer.expr(ExprKind::Method {
+                    name: Box::new(synthesizer.ident(ITER_METHOD)),
+                    actual: vec![iterable],
+                })
+            }
+        };
+        stmts.push(Self::local(&mut synthesizer, true, ITERATOR_LOCAL, iterator));
+
+        let iterator = Self::name(&mut synthesizer, ITERATOR_LOCAL);
+        let next = synthesizer.expr(ExprKind::Method {
+            name: Box::new(synthesizer.ident(NEXT_METHOD)),
+            actual: vec![iterator],
+        });
+        let exhausted = synthesizer.expr(ExprKind::Break);
+        let next = synthesizer.expr(ExprKind::Coalesce(next, exhausted));
+        let element = Self::local(&mut synthesizer, false, element.kind().value.as_str(), next);
+        let body = synthesizer.stmt(StmtKind::Expr(body.clone()));
+        let loop_body = synthesizer.expr(ExprKind::Block(vec![element, body]));
+        let loop_expr = synthesizer.expr(ExprKind::Loop(loop_body));
+        stmts.push(synthesizer.stmt(StmtKind::Expr(loop_expr)));
+        let lowered = synthesizer.expr(ExprKind::Block(stmts));
+
+        self.trace_desugaring(&synthesizer, expr, Lowered::Ast(&lowered));
+        self.resolve_lowered(&mut synthesizer, &lowered)
+    }
+
+    /// whether the call is the one to `next` a for loop is lowered to.
+    pub(super) fn is_for_loop_next(&self, expr: &Expr, name: &Identifier) -> bool {
+        self.generated_by(expr) == Some(Desugaring::ForLoop) && name.kind().value == NEXT_METHOD
+    }
+
+    /// `while let some(x) = value { body }` is lowered to
+    /// ```text
+    /// loop {
+    ///     let x = value ?? break
+    ///     body
+    /// }
+    /// ```
+    /// the value is evaluated at the start of every iteration, so a `continue` takes the next
+    /// one, and the loop ends at the first none.
+    pub(crate) fn resolve_while_let(&mut self, expr: &Expr) -> Result<HirExprPtr, Error> {
+        let (element, value, body) = match expr.kind() {
+            ExprKind::WhileLet {
+                element,
+                expr,
+                body,
+            } => (element, expr, body),
+            _ => unreachable!(),
+        };
+
+        let value = self.resolve_expr(value.as_ref(), None)?;
+        if !Type::inner(value.ty()).is_option() {
+            let err = Error::non_optional_while_let(value.ty().as_ref());
+            return Err(err.with_position(value.position()));
+        }
+
+        let mut synthesizer = Synthesizer::new(Desugaring::WhileLet, expr.position());
+        let next = synthesizer.resolved(value);
+        let exhausted = synthesizer.expr(ExprKind::Break);
+        let next = synthesizer.expr(ExprKind::Coalesce(next, exhausted));
+        let element = Self::local(&mut synthesizer, false, element.kind().value.as_str(), next);
+        let body = synthesizer.stmt(StmtKind::Expr(body.clone()));
+        let loop_body = synthesizer.expr(ExprKind::Block(vec![element, body]));
+        let lowered = synthesizer.expr(ExprKind::Loop(loop_body));
+
+        self.trace_desugaring(&synthesizer, expr, Lowered::Ast(&lowered));
+        self.resolve_lowered(&mut synthesizer, &lowered)
+    }
+
+    /// resolves a lowered loop whose `Resolved` operands are the ones of synthesizer.
+    fn resolve_lowered(
+        &mut self,
+        synthesizer: &mut Synthesizer,
+        lowered: &Expr,
+    ) -> Result<HirExprPtr, Error> {
+        let outer = std::mem::replace(&mut self.resolved, synthesizer.take_operands());
+        let lowered =
+            self.resolve_desugared(synthesizer, |typer| typer.resolve_expr(lowered, None));
+        self.resolved = outer;
+        lowered
+    }
+
+    /// the range iterated when expr calls the built in `range` or is a range expression.
+    fn range_of<'e>(&self, expr: &'e Expr) -> Result<Option<Range<'e>>, Error> {
+        let actual = match expr.kind() {
+            ExprKind::Method { name, actual } if name.kind().value == REV_METHOD => {
+                let range = match self.range_of(actual[0].as_ref())? {
+                    Some(range) => range,
+                    None => return Ok(None),
+                };
+                if actual.len() != 1 {
+                    let err = Error::invalid_actuals(0, actual.len() - 1);
+                    return Err(err.with_position(expr.position()));
+                }
+                return Ok(Some(Range {
+                    reversed: !range.reversed,
+                    ..range
+                }));
+            }
+            ExprKind::Call { operand, actual } => match operand.kind() {
+                ExprKind::Name(ident)
+                    if ident.kind().value == RANGE_FUNCTION
+                        && self.deep_lookup(RANGE_FUNCTION).is_none() =>
+                {
+                    actual
+                }
+                _ => return Ok(None),
+            },
+            ExprKind::Range { start, end } => {
+                return Ok(Some(Range {
+                    start: start.as_ref(),
+                    end: end.as_ref(),
+                    step: None,
+                    reversed: false,
+                }))
+            }
+            _ => return Ok(None),
+        };
+
+        let (start, end, step) = match actual.as_slice() {
+            [start, end] => (start, end, None),
+            [start, end, step] => (start, end, Some(step.as_ref())),
+            _ => {
+                let expected = if actual.len() < 2 { 2 } else { 3 };
+                let err = Error::invalid_actuals(expected, actual.len());
+                return Err(err.with_position(expr.position()));
+            }
+        };
+        Ok(Some(Range {
+            start: start.as_ref(),
+            end: end.as_ref(),
+            step,
+            reversed: false,
+        }))
+    }
+
+    /// `for x in range(start, end, step) { body }` counts the elements of the range instead
+    /// of creating an iterator, it is lowered to
+    /// ```text
+    /// {
+    ///     let __start__ = start
+    ///     let __end__ = end
+    ///     let __step__ = step                 // 1 when it is not given
+    ///     let __zero__ = 0
+    ///     if __step__ == 0 { <trap> }         // only when the step is not a constant
+    ///     let __count__ = <elements of the range>
+    ///     mut __remaining__ = __count__
+    ///     while __remaining__ > 0 {
+    ///         __remaining__ -= 1
+    ///         let x = __start__ + (__count__ - 1 - __remaining__) * __step__
+    ///         body
+    ///     }
+    /// }
+    /// ```
+    /// the end is excluded, a negative step counts down to it. A reversed range has the
+    /// element `__start__ + __remaining__ * __step__`, the same elements from the last one.
+    /// The count is a literal when the bounds and step are constants.
+    fn resolve_range_for(
+        &mut self,
+        expr: &Expr,
+        element: &Identifier,
+        range: Range,
+        body: &Expr,
+    ) -> Result<HirExprPtr, Error> {
+        // like the operands of a binary, literals take the type of the other bounds.
+        let bounds = [Some(range.start), Some(range.end), range.step];
+        let typed = bounds
+            .iter()
+            .flatten()
+            .find(|bound| !Self::is_integer_literal(bound))
+            .copied()
+            .unwrap_or(range.start);
+        let typed_bound = self.resolve_expr(typed, None)?;
+        let ty = Type::inner(typed_bound.ty());
+        if !ty.is_integer() {
+            let err = Error::invalid_range_bound(ty.as_ref());
+            return Err(err.with_position(typed.position()));
+        }
+
+        let mut synthesizer = Synthesizer::new(Desugaring::ForLoop, expr.position());
+        let mut resolved = vec![];
+        for bound in bounds.iter() {
+            let bound = match bound {
+                Some(bound) if std::ptr::eq(*bound, typed) => typed_bound.clone(),
+                Some(bound) => {
+                    let resolved = self.resolve_expr(bound, Some(ty.clone()))?;
+                    if *Type::inner(resolved.ty()) != *ty {
+                        let err = Error::incompatible_types(ty.as_ref(), resolved.ty().as_ref());
+                        return Err(err.with_position(bound.position()));
+                    }
+                    resolved
+                }
+                None => synthesizer.hir_expr(HirExprKind::Integer(1), ty.clone()),
+            };
+            resolved.push(bound);
+        }
+        let (start, end, step) = (
+            resolved[0].clone(),
+            resolved[1].clone(),
+            resolved[2].clone(),
+        );
+
+        let constant_step = Self::constant_integer(step.as_ref());
+        if let (Some(0), Some(step)) = (constant_step, range.step) {
+            return Err(Error::zero_range_step().with_position(step.position()));
+        }
+        let count = match (
+            Self::constant_integer(start.as_ref()),
+            Self::constant_integer(end.as_ref()),
+            constant_step,
+        ) {
+            (Some(start), Some(end), Some(step)) => Some(Self::range_count(start, end, step)),
+            _ => None,
+        };
+
+        // a literal is only typed by the other operand of a binary, the zero a count falls
+        // back to is given the type of the range.
+        let zero = synthesizer.hir_expr(HirExprKind::Integer(0), ty.clone());
+        let mut stmts = vec![];
+        let locals = [START_LOCAL, END_LOCAL, STEP_LOCAL, ZERO_LOCAL];
+        for (name, value) in locals.iter().zip(vec![start, end, step, zero]) {
+            let value = synthesizer.resolved(value);
+            stmts.push(Self::local(&mut synthesizer, false, name, value));
+        }
+        if constant_step.is_none() {
+            // a zero step would never reach the end.
+            let step = Self::name(&mut synthesizer, STEP_LOCAL);
+            let zero = synthesizer.expr(ExprKind::Integer(0));
+            let is_zero = synthesizer.expr(ExprKind::Binary(BinaryOp::EqualEqual, step, zero));
+            let trap = synthesizer.expr(ExprKind::Trap {
+                message: ZERO_STEP_MESSAGE.to_owned(),
+                values: vec![],
+            });
+            let trap = Self::block(&mut synthesizer, trap);
+            let check = synthesizer.expr(ExprKind::If {
+                cond: is_zero,
+                body: trap,
+                else_if: None,
+            });
+            stmts.push(synthesizer.stmt(StmtKind::Expr(check)));
+        }
+        let count = match count {
+            Some(count) => {
+                let count = synthesizer.hir_expr(HirExprKind::Integer(count), ty);
+                synthesizer.resolved(count)
+            }
+            None => Self::range_count_expr(&mut synthesizer, constant_step),
+        };
+        stmts.push(Self::local(&mut synthesizer, false, COUNT_LOCAL, count));
+        let count = Self::name(&mut synthesizer, COUNT_LOCAL);
+        stmts.push(Self::local(&mut synthesizer, true, REMAINING_LOCAL, count));
+
+        let remaining = Self::name(&mut synthesizer, REMAINING_LOCAL);
+        let one = synthesizer.expr(ExprKind::Integer(1));
+        let decrement = synthesizer.stmt(StmtKind::Assignment {
+            op: AssignmentOp::MinusAssign,
+            op_position: synthesizer.origin(),
+            lvalue: remaining,
+            rhs: one,
+        });
+        let taken = if range.reversed {
+            Self::name(&mut synthesizer, REMAINING_LOCAL)
+        } else {
+            let count = Self::name(&mut synthesizer, COUNT_LOCAL);
+            let one = synthesizer.expr(ExprKind::Integer(1));
+            let last = synthesizer.expr(ExprKind::Binary(BinaryOp::Minus, count, one));
+            let remaining = Self::name(&mut synthesizer, REMAINING_LOCAL);
+            synthesizer.expr(ExprKind::Binary(BinaryOp::Minus, last, remaining))
+        };
+        let step = Self::name(&mut synthesizer, STEP_LOCAL);
+        let offset = synthesizer.expr(ExprKind::Binary(BinaryOp::Astrick, taken, step));
+        let start = Self::name(&mut synthesizer, START_LOCAL);
+        let value = synthesizer.expr(ExprKind::Binary(BinaryOp::Plus, start, offset));
+        let element = Self::local(
+            &mut synthesizer,
+            false,
+            element.kind().value.as_str(),
+            value,
+        );
+        let body = synthesizer.stmt(StmtKind::Expr(Box::new(body.clone())));
+        let loop_body = synthesizer.expr(ExprKind::Block(vec![decrement, element, body]));
+
+        let remaining = Self::name(&mut synthesizer, REMAINING_LOCAL);
+        let zero = synthesizer.expr(ExprKind::Integer(0));
+        let cond = synthesizer.expr(ExprKind::Binary(BinaryOp::Greater, remaining, zero));
+        let loop_expr = synthesizer.expr(ExprKind::While(cond, loop_body));
+        stmts.push(synthesizer.stmt(StmtKind::Expr(loop_expr)));
+        let lowered = synthesizer.expr(ExprKind::Block(stmts));
+
+        self.trace_desugaring(&synthesizer, expr, Lowered::Ast(&lowered));
+        self.resolve_lowered(&mut synthesizer, &lowered)
+    }
+
+    fn is_integer_literal(expr: &Expr) -> bool {
+        match expr.kind() {
+            ExprKind::Integer(_) => true,
+            ExprKind::Unary(UnaryOp::Minus, operand) => Self::is_integer_literal(operand),
+            _ => false,
+        }
+    }
+
+    /// the number of elements of a range with constant bounds.
+    fn range_count(start: i64, end: i64, step: i64) -> i64 {
+        let (start, end, step) = (start as i128, end as i128, step as i128);
+        let count = if step > 0 && end > start {
+            (end - start - 1) / step + 1
+        } else if step < 0 && start > end {
+            (end - start + 1) / step + 1
+        } else {
+            0
+        };
+        count as i64
+    }
+
+    /// the number of elements of a range, known only once the loop is reached. The sign
+    /// of the step is tested when it is not a constant.
+    fn range_count_expr(synthesizer: &mut Synthesizer, step: Option<i64>) -> Box<Expr> {
+        match step {
+            Some(step) => Self::count_towards_end(synthesizer, step > 0),
+            None => {
+                let step = Self::name(synthesizer, STEP_LOCAL);
+                let zero = synthesizer.expr(ExprKind::Integer(0));
+                let cond = synthesizer.expr(ExprKind::Binary(BinaryOp::Greater, step, zero));
+                let up = Self::count_towards_end(synthesizer, true);
+                let down = Self::count_towards_end(synthesizer, false);
+                Self::if_else(synthesizer, cond, up, down)
+            }
+        }
+    }
+
+    /// `(__end__ - __start__ - 1) / __step__ + 1` counting up to the end, `+ 1` in place of
+    /// `- 1` counting down, and no elements when the start is already past the end.
+    fn count_towards_end(synthesizer: &mut Synthesizer, up: bool) -> Box<Expr> {
+        let (before, after) = if up {
+            (START_LOCAL, END_LOCAL)
+        } else {
+            (END_LOCAL, START_LOCAL)
+        };
+        let before = Self::name(synthesizer, before);
+        let after = Self::name(synthesizer, after);
+        let cond = synthesizer.expr(ExprKind::Binary(BinaryOp::Greater, after, before));
+
+        let end = Self::name(synthesizer, END_LOCAL);
+        let start = Self::name(synthesizer, START_LOCAL);
+        let span = synthesizer.expr(ExprKind::Binary(BinaryOp::Minus, end, start));
+        let one = synthesizer.expr(ExprKind::Integer(1));
+        let op = if up { BinaryOp::Minus } else { BinaryOp::Plus };
+        let span = synthesizer.expr(ExprKind::Binary(op, span, one));
+        let step = Self::name(synthesizer, STEP_LOCAL);
+        let steps = synthesizer.expr(ExprKind::Binary(BinaryOp::Slash, span, step));
+        let one = synthesizer.expr(ExprKind::Integer(1));
+        let count = synthesizer.expr(ExprKind::Binary(BinaryOp::Plus, steps, one));
+
+        let none = Self::name(synthesizer, ZERO_LOCAL);
+        Self::if_else(synthesizer, cond, count, none)
+    }
+
+    fn if_else(
+        synthesizer: &mut Synthesizer,
+        cond: Box<Expr>,
+        body: Box<Expr>,
+        otherwise: Box<Expr>,
+    ) -> Box<Expr> {
+        let body = Self::block(synthesizer, body);
+        let otherwise = Self::block(synthesizer, otherwise);
+        synthesizer.expr(ExprKind::If {
+            cond,
+            body,
+            else_if: Some(otherwise),
+        })
+    }
+
+    fn block(synthesizer: &mut Synthesizer, value: Box<Expr>) -> Box<Expr> {
+        let value = synthesizer.stmt(StmtKind::Expr(value));
+        synthesizer.expr(ExprKind::Block(vec![value]))
+    }
+
+    fn local(
+        synthesizer: &mut Synthesizer,
+        mutable: bool,
+        name: &str,
+        init: Box<Expr>,
+    ) -> Box<Stmt> {
+        let item = synthesizer.item(ItemKind::Variable {
+            vis: Visibility::Private,
+            mutable,
+            name: synthesizer.ident(name),
+            init: Some(init),
+            spec: None,
+        });
+        synthesizer.stmt(StmtKind::Item(item))
+    }
+
+    fn name(synthesizer: &mut Synthesizer, name: &str) -> Box<Expr> {
+        let ident = synthesizer.ident(name);
+        synthesizer.expr(ExprKind::Name(ident))
+    }
+
+    /// a value is iterated when it is an iterator, a struct with `next(mut self) Option[T]`, or
+    /// an iterable, a struct with `iter(self)` returning an iterator.
+    fn iteration_of(&self, ty: Rc<Type>) -> Result<Iteration, Error> {
+        let ty = Type::inner(ty);
+        let methods = match ty.kind() {
+            TypeKind::Struct { entity } => entity.borrow().as_struct().methods.clone(),
+            _ => return Err(Error::not_iterable(ty.as_ref())),
+        };
+
+        if let Some(next) = methods.get(NEXT_METHOD) {
+            Self::check_next(ty.as_ref(), next)?;
+            return Ok(Iteration::Iterator);
+        }
+
+        match methods.get(ITER_METHOD) {
+            Some(iter) => {
+                let (takes_self, method_type) = Self::method_signature(iter);
+                let iterator = match method_type.kind() {
+                    TypeKind::Function {
+                        params,
+                        return_type,
+                    } if takes_self && params.len() == 1 && !params[0].is_mutable() => {
+                        Self::iterator_next(return_type.clone())
+                    }
+                    _ => None,
+                };
+                match iterator {
+                    Some((iterator, next)) => Self::check_next(iterator.as_ref(), &next)
+                        .map(|_| Iteration::Iterable),
+                    None => Err(Error::invalid_iterator_method(
+                        ty.as_ref(),
+                        ITER_METHOD,
+                        method_type.as_ref(),
+                        "(Self) I, where I is an iterator",
+                    )),
+                }
+            }
+            None => Err(Error::not_iterable(ty.as_ref())),
+        }
+    }
+
+    /// the iterator type returned by `iter` along with its `next` method.
+    fn iterator_next(ty: Rc<Type>) -> Option<(Rc<Type>, EntityRef)> {
+        let ty = Type::inner(ty);
+        let next = match ty.kind() {
+            TypeKind::Struct { entity } => entity.borrow().as_struct().methods.get(NEXT_METHOD)?.clone(),
+            _ => return None,
+        };
+        Some((ty, next))
+    }
+
+    fn check_next(ty: &Type, next: &EntityRef) -> Result<(), Error> {
+        let (takes_self, method_type) = Self::method_signature(next);
+        match method_type.kind() {
+            TypeKind::Function {
+                params,
+                return_type,
+            } if takes_self
+                && params.len() == 1
+                && params[0].is_mutable()
+                && Type::inner(return_type.clone()).is_option() =>
+            {
+                Ok(())
+            }
+            _ => Err(Error::invalid_iterator_method(
+                ty,
+                NEXT_METHOD,
+                method_type.as_ref(),
+                "(mut Self) Option[T]",
+            )),
+        }
+    }
+
+    fn method_signature(method: &EntityRef) -> (bool, Rc<Type>) {
+        let method = method.borrow();
+        let takes_self = match method.kind() {
+            EntityInfo::AssociatedFunction(info) => info.takes_self,
+            EntityInfo::Declared(info) => info.takes_self,
+            _ => false,
+        };
+        (takes_self, method.ty())
+    }
+}
+
@@ -0,0 +1,73 @@
+use crate::analysis::typer::Typer;
+use crate::error::Error;
+use crate::ir::ast::{Expr, ExprKind, Node};
+use crate::ir::hir::{
+    AddressMode, HirExpr, HirExprInner, HirExprKind, HirExprPtr, ResultMeta, TrapExpr,
+};
+use crate::syntax::Position;
+use std::rc::Rc;
+
+const TODO: &str = "todo";
+const UNREACHABLE: &str = "unreachable";
+
+impl<'a> Typer<'a> {
+    /// `todo` and `unreachable` are built in functions, they are shadowed by any entity with the
+    /// same name.
+    pub(super) fn is_marker(&self, operand: &Expr) -> bool {
+        self.is_builtin(operand, TODO) || self.is_builtin(operand, UNREACHABLE)
+    }
+
+    /// `todo()`, `todo("message")` and `unreachable()` never return, they are typed never and fit
+    /// where any type is expected and the end of the block they are in is never reached. Both
+    /// raise a runtime error naming where they are. Above opt level 0 an `unreachable()` is
+    /// trusted to never be reached unless the build checks it, the branches reaching it are
+    /// removed.
+    pub(super) fn resolve_marker(
+        &mut self,
+        operand: &Expr,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let location = format!("{}:{}", self.module_name, position.start());
+        let kind = if self.is_builtin(operand, TODO) {
+            let message = match actuals {
+                [] => format!("not yet implemented at {}", location),
+                [message] => match message.kind() {
+                    ExprKind::String(message) => {
+                        format!("not yet implemented: {} at {}", message, location)
+                    }
+                    _ => {
+                        let err = Error::todo_message_not_literal();
+                        return Err(err.with_position(message.position()));
+                    }
+                },
+                _ => return Err(Error::invalid_actuals(1, actuals.len()).with_position(position)),
+            };
+            let trap = TrapExpr {
+                message,
+                values: vec![],
+            };
+            HirExprKind::Trap(trap)
+        } else {
+            if !actuals.is_empty() {
+                let err = Error::invalid_actuals(0, actuals.len());
+                return Err(err.with_position(position));
+            }
+            let message = format!("entered unreachable code at {}", location);
+            if self.build_info.opt_level == 0 || self.build_info.checked_unreachable {
+                HirExprKind::Trap(TrapExpr {
+                    message,
+                    values: vec![],
+                })
+            } else {
+                HirExprKind::Unreachable(message)
+            }
+        };
+        let inner = HirExprInner::new(AddressMode::Value, ResultMeta::default(), kind);
+        Ok(Rc::new(HirExpr::new(
+            inner,
+            position,
+            self.type_map.get_never(),
+        )))
+    }
+}
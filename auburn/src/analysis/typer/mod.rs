@@ -1,21 +1,42 @@
-use crate::analysis::{Entity, EntityInfo, EntityRef};
+use crate::analysis::{Checks, Entity, EntityInfo, EntityRef, VariableInfo};
 use crate::error::Error;
-use crate::ir::ast::{Identifier, ItemKind, Node, StmtKind};
-use crate::ir::hir::HirFile;
-use crate::syntax::ParsedFile;
+use crate::ir::ast::{Identifier, Item, ItemKind, Node, NodeId, Stmt, StmtKind, Visibility};
+use crate::ir::hir::{Desugaring, HirExpr, HirFile, HirStmt, MirNode};
+use crate::passes::{Instances, Monomorphizer};
+use crate::syntax::{ParsedFile, DEFAULT_MAX_EXPR_DEPTH};
 use crate::types::{Type, TypeKind, TypeMap};
+use crate::utils::timing::{self, PhaseTimer};
 use crate::{analysis::entity::Path, LanguageMode};
 use crate::{
     analysis::scope::{Scope, ScopeKind, ScopeRef},
     syntax::{FilePos, Position, Span},
 };
+use closures::ResolvingClosure;
+use contracts::Contracts;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ops::Deref;
 use std::rc::Rc;
 
+pub use sys::{BuildInfo, LANGUAGE_VERSION};
+
+mod closures;
+mod collections;
+mod contracts;
+mod declarations;
+mod desugar;
 mod expressions;
+mod gc;
+mod generics;
 mod items;
+mod iteration;
+mod markers;
+mod modules;
+mod patterns;
+mod reflect;
 mod statements;
+mod sys;
+mod tasks;
 mod type_specs;
 
 type State = u64;
@@ -29,7 +50,23 @@ const FUNCTION_PARAM: State = 1 << 4;
 const FUNCTION_BODY: State = 1 << 5;
 const ASSOCIATIVE_FUNCTION: State = 1 << 6;
 const ALLOW_CONTROL_FLOW_EXPRESSIONS: State = 1 << 7;
+/// the statement is the last of a block, an expression statement is the value of the block and
+/// is used when the block is.
+const BLOCK_RESULT: State = 1 << 8;
 const SELF_PARAM_IDENT: &'static str = "__self__";
+const ENTRY_NAME: &str = "main";
+
+/// what went wrong in a file resolved with `Typer::with_recovery`.
+#[derive(Default)]
+pub(super) struct Recovered {
+    /// the errors of the statements that failed, in the order they were found.
+    pub failures: Vec<Error>,
+    /// a tombstone for every item that failed, see `EntityInfo::Failed`.
+    pub failed: Vec<EntityRef>,
+    /// the items whose bodies had a statement fail, with the first error of each. They become
+    /// tombstones once they are resolved, their uses are checked against their types.
+    poisoned: Vec<(EntityRef, Error)>,
+}
 
 pub(super) struct Typer<'a> {
     type_map: &'a mut TypeMap,
@@ -37,6 +74,51 @@ pub(super) struct Typer<'a> {
     state: State,
     mode: LanguageMode,
     self_entity: Option<EntityRef>,
+    /// nodes synthesized by desugarings.
+    generated: HashMap<NodeId, Desugaring>,
+    trace_desugar: bool,
+    checks: Checks,
+    /// the unwrapped receiver of the optional chain being resolved.
+    chain_receiver: Option<Rc<HirExpr>>,
+    /// the operands a desugaring resolved before it was built, by the id of their node.
+    resolved: HashMap<NodeId, Rc<HirExpr>>,
+    /// the types of the `SpecKind::Resolved` specs of the function literals, by the id of the
+    /// spec.
+    resolved_specs: HashMap<NodeId, Rc<Type>>,
+    /// whether the contracts of functions are checked at runtime.
+    contracts: bool,
+    /// the contracts of the function whose body is resolved when it has postconditions, a
+    /// `return` checks them.
+    postconditions: Option<Rc<Contracts>>,
+    /// the declared return type of the function whose body is resolved, the value of a
+    /// `return` is resolved with it.
+    return_type: Option<Rc<Type>>,
+    /// the type expected of the value of the block being resolved, its last statement is
+    /// resolved with it.
+    block_result_type: Option<Rc<Type>>,
+    /// name of the module of the file when it is part of a program.
+    module: Option<String>,
+    /// public items of the modules the file imports.
+    imports: Option<Scope>,
+    /// why the names of items of the imported modules that are not in the scope of the imports
+    /// can not be used, they are private or more than one module provides them.
+    hidden_imports: HashMap<String, Error>,
+    /// set when a statement of the file that fails is recorded here and skipped instead of
+    /// ending the resolution.
+    recovered: Option<&'a mut Recovered>,
+    /// the function literals whose bodies are being resolved, innermost last.
+    closures: Vec<ResolvingClosure>,
+    /// the instances of the generic functions the file calls.
+    instances: Instances,
+    /// the items of the module being resolved, an item resolved while another uses it is last.
+    items: Vec<EntityRef>,
+    /// the name of the module of the file, or of the file when it is compiled on its own.
+    module_name: String,
+    /// how the program is built, see `Typer::resolve_sys_call`.
+    build_info: BuildInfo,
+    /// the number of expressions being resolved, each nested in the one before.
+    depth: usize,
+    max_depth: usize,
 }
 
 impl<'a> Typer<'a> {
@@ -51,9 +133,81 @@ impl<'a> Typer<'a> {
             state: DEFAULT,
             mode,
             self_entity: None,
+            generated: HashMap::new(),
+            trace_desugar: false,
+            checks: Checks::default(),
+            chain_receiver: None,
+            resolved: HashMap::new(),
+            resolved_specs: HashMap::new(),
+            contracts: true,
+            postconditions: None,
+            return_type: None,
+            block_result_type: None,
+            module: None,
+            imports: None,
+            hidden_imports: HashMap::new(),
+            recovered: None,
+            closures: vec![],
+            instances: Instances::default(),
+            items: vec![],
+            module_name: String::new(),
+            build_info: BuildInfo::default(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_EXPR_DEPTH,
         }
     }
 
+    /// resolves the file as the module `name` of a program, names that are not declared by the
+    /// file are looked up in the scope of its imports.
+    pub fn in_module(
+        mut self,
+        name: &str,
+        imports: Scope,
+        hidden_imports: HashMap<String, Error>,
+    ) -> Self {
+        self.module = Some(name.to_owned());
+        self.imports = Some(imports);
+        self.hidden_imports = hidden_imports;
+        self
+    }
+
+    /// prints every construct that is desugared before and after it is lowered.
+    pub fn with_trace_desugar(mut self, enabled: bool) -> Self {
+        self.trace_desugar = enabled;
+        self
+    }
+
+    pub fn with_checks(mut self, checks: Checks) -> Self {
+        self.checks = checks;
+        self
+    }
+
+    /// checks the `@requires` and `@ensures` of functions at runtime, without they are only
+    /// type checked.
+    pub fn with_contracts(mut self, enabled: bool) -> Self {
+        self.contracts = enabled;
+        self
+    }
+
+    pub fn with_build_info(mut self, build_info: BuildInfo) -> Self {
+        self.build_info = build_info;
+        self
+    }
+
+    /// limits how deeply the expressions may nest, the parser checks the source against the
+    /// same limit. The passes after the typer walk what it resolves recursively.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// keeps resolving the file after a statement fails, its error and the items it left
+    /// unresolved are recorded in recovered. The items that did resolve make up the file.
+    pub fn with_recovery(mut self, recovered: &'a mut Recovered) -> Self {
+        self.recovered = Some(recovered);
+        self
+    }
+
     fn check_state(&self, state: State) -> bool {
         (self.state & state) == state
     }
@@ -75,6 +229,24 @@ impl<'a> Typer<'a> {
         self.self_entity = None;
     }
 
+    /// resolves an item used before it is reached. The item is resolved as if it were reached,
+    /// so the state and self of the item using it, a method referencing another struct, are
+    /// put aside until it is done. Only the fields of a structure are, its methods are resolved
+    /// where it is reached.
+    fn resolve_out_of_order(&mut self, item: &Item, entity: EntityRef) -> Result<EntityRef, Error> {
+        let state = std::mem::replace(&mut self.state, DEFAULT);
+        let self_entity = self.self_entity.take();
+        let res = match item.kind() {
+            ItemKind::Struct { name, fields, .. } => self
+                .declare_struct(entity.clone(), name, fields)
+                .map(|_| entity),
+            _ => self.resolve_item_impl(item, entity, true, false),
+        };
+        self.state = state;
+        self.self_entity = self_entity;
+        res
+    }
+
     fn is_default_mode(&self) -> bool {
         self.mode == LanguageMode::Default
     }
@@ -95,6 +267,14 @@ impl<'a> Typer<'a> {
         self.type_map.insert_type(kind)
     }
 
+    /// `Option[inner]`, an inner type that is already optional is not wrapped again.
+    fn optional_of(&mut self, inner: Rc<Type>) -> Rc<Type> {
+        match inner.kind() {
+            TypeKind::Option { .. } => inner,
+            _ => self.insert_type(TypeKind::Option { inner }),
+        }
+    }
+
     fn current_scope_mut(&mut self) -> &mut Scope {
         self.scope_stack.last_mut().expect("Scope stack is emtpy")
     }
@@ -112,6 +292,32 @@ impl<'a> Typer<'a> {
         None
     }
 
+    /// the local function using name when name is a local of a function enclosing it. Local
+    /// functions are not closures, the locals of the function declaring them are out of reach
+    /// but the other local functions it declared before are not. Function literals are, see
+    /// `Typer::capture`.
+    fn captured_by(&self, name: &str) -> Option<String> {
+        let mut function = None;
+        for (index, scope) in self.scope_stack.iter().enumerate().rev() {
+            if let Some(entity) = scope.shallow_lookup(name) {
+                if entity.deref().borrow().is_function() {
+                    return None;
+                }
+                return match scope.kind() {
+                    ScopeKind::Param(_) | ScopeKind::Block => function,
+                    _ => None,
+                };
+            }
+            if let ScopeKind::Param(name) = scope.kind() {
+                if self.is_closure_scope(index) {
+                    continue;
+                }
+                function.get_or_insert_with(|| name.clone());
+            }
+        }
+        None
+    }
+
     pub fn shallow_lookup(&self, name: &str) -> Option<EntityRef> {
         self.current_scope().shallow_lookup(name)
     }
@@ -140,10 +346,14 @@ impl<'a> Typer<'a> {
                     file_id: _,
                     file_name,
                 } => {
-                    path.push_path(file_name.as_str());
+                    // a segment for each module of the path, `shapes.square`.
+                    for module in file_name.split('.') {
+                        path.push_path(module);
+                    }
                 }
                 ScopeKind::Struct(name) => path.push_path(name.as_str()),
                 ScopeKind::StructMethods(name) => path.push_path(name.as_str()),
+                ScopeKind::Enum(name) => path.push_path(name.as_str()),
                 _ => {}
             }
         }
@@ -152,17 +362,31 @@ impl<'a> Typer<'a> {
 }
 
 impl<'src> Typer<'src> {
-    pub fn resolve_root(self, parsed_file: ParsedFile) -> Result<HirFile, Error> {
-        let mut file = self.resolve_file(parsed_file)?;
-        if let Some(entity) = file.find_entity_by_name("main") {
+    pub fn resolve_root(mut self, parsed_file: ParsedFile) -> Result<HirFile, Error> {
+        let mut file = self.resolve_file_inner(parsed_file)?;
+        match self.recovered.as_mut() {
+            None => Self::set_entry(&mut file)?,
+            // the failure of main is already recorded.
+            Some(recovered) if Self::has_failed(recovered, ENTRY_NAME) => {}
+            Some(recovered) => {
+                if let Err(err) = Self::set_entry(&mut file) {
+                    recovered.failures.push(err);
+                }
+            }
+        }
+        Ok(file)
+    }
+
+    fn set_entry(file: &mut HirFile) -> Result<(), Error> {
+        if let Some(entity) = file.find_entity_by_name(ENTRY_NAME) {
             let entity_borrow = entity.borrow();
             if let EntityInfo::Function(_) = entity_borrow.kind() {
                 //std::mem::drop(entity_borrow);
                 file.set_entry(entity.clone());
-                Ok(file)
+                Ok(())
             } else {
                 let err = Error::entry_not_function(
-                    "main".to_string(),
+                    ENTRY_NAME.to_string(),
                     entity_borrow.type_name().to_string(),
                 );
                 Err(err.with_position(Position::new(
@@ -172,7 +396,7 @@ impl<'src> Typer<'src> {
                 )))
             }
         } else {
-            let err = Error::entry_not_found("main".to_string());
+            let err = Error::entry_not_found(ENTRY_NAME.to_string());
             Err(err.with_position(Position::new(
                 Span::default(),
                 FilePos::default(),
@@ -181,19 +405,56 @@ impl<'src> Typer<'src> {
         }
     }
 
+    fn has_failed(recovered: &Recovered, name: &str) -> bool {
+        recovered
+            .failed
+            .iter()
+            .any(|entity| entity.borrow().name() == name)
+    }
+
     pub fn resolve_file(mut self, parsed_file: ParsedFile) -> Result<HirFile, Error> {
+        self.resolve_file_inner(parsed_file)
+    }
+
+    fn resolve_file_inner(&mut self, parsed_file: ParsedFile) -> Result<HirFile, Error> {
+        let file_name = match self.module.clone() {
+            Some(module) => module,
+            None => parsed_file.stem().to_owned(),
+        };
+        self.module_name = file_name.clone();
+        let imported = match self.imports.take() {
+            Some(imports) => {
+                self.scope_stack.push(imports);
+                true
+            }
+            None => {
+                // a file compiled on its own has no other modules to import.
+                if let Some(import) = parsed_file.imports.first() {
+                    return Err(Error::unknown_module(import.module_name().as_str())
+                        .with_note(
+                            "compile the directory of the program to import its modules"
+                                .to_string(),
+                        )
+                        .with_position(import.position));
+                }
+                false
+            }
+        };
         self.push_scope(ScopeKind::File {
             file_id: parsed_file.file_id,
             // file_name: parsed_file.file_name.clone(),
-            file_name: parsed_file.stem().to_owned(),
+            file_name: file_name.clone(),
         });
         // println!("Stmts: {}", parsed_file.stmts.len());
 
+        let timer = PhaseTimer::start(timing::CHECK);
+        let collect = PhaseTimer::start(timing::COLLECT);
         for stmt in &parsed_file.stmts {
             match stmt.kind() {
                 StmtKind::Item(item) => match item.kind() {
                     ItemKind::Variable { name, vis, .. }
                     | ItemKind::Struct { name, vis, .. }
+                    | ItemKind::Enum { name, vis, .. }
                     | ItemKind::Function { name, vis, .. } => {
                         let entity = Rc::new(RefCell::new(Entity::unresolved(
                             *vis,
@@ -202,6 +463,7 @@ impl<'src> Typer<'src> {
                             self.type_map.get_invalid(),
                         )));
                         self.insert_entity(name.kind().value.as_str(), entity);
+                        collect.count(1);
                     }
                     _ => {}
                 },
@@ -209,47 +471,264 @@ impl<'src> Typer<'src> {
             }
         }
 
+        std::mem::drop(collect);
+
+        let declare = PhaseTimer::start(timing::DECLARE);
+        let declared = self.declare_items(&parsed_file.stmts);
+        declare.count(declared);
+        std::mem::drop(declare);
+
         let mut globals = vec![];
         for stmt in &parsed_file.stmts {
-            let stmt = self.resolve_stmt_inner(stmt.as_ref(), true)?;
-            globals.push(stmt.clone())
+            let item = PhaseTimer::start(timing::ITEM);
+            if self.recovered.is_some() {
+                globals.extend(self.recover_stmt(stmt.as_ref()));
+            } else {
+                let stmt = self.resolve_stmt_inner(stmt.as_ref(), true)?;
+                globals.push(stmt.clone())
+            }
+            item.count(1);
         }
+        timer.count(1);
 
         self.pop_scope();
+        if imported {
+            self.pop_scope();
+        }
 
-        Ok(HirFile::new(
-            parsed_file.file_id,
-            parsed_file.stem().to_owned(),
-            globals,
-        ))
+        let mut file = HirFile::new(parsed_file.file_id, file_name, globals);
+        Monomorphizer::new(self.type_map, &mut self.instances).run(&mut file);
+        Ok(file)
+    }
+
+    /// resolves a statement of the file, when it fails the error is recorded and the items that
+    /// were being resolved, the item of the statement and those it used, become tombstones.
+    fn recover_stmt(&mut self, stmt: &Stmt) -> Option<Rc<HirStmt>> {
+        let entity = match stmt.kind() {
+            StmtKind::Item(item) => item
+                .get_name()
+                .and_then(|name| self.shallow_lookup(name.kind().value.as_str())),
+            _ => None,
+        };
+        // an item that failed while an item using it was resolved is not resolved again.
+        if let Some(entity) = entity.as_ref() {
+            if entity.deref().borrow().failure().is_some() {
+                return None;
+            }
+        }
+
+        let depth = self.scope_stack.len();
+        let state = self.state;
+        let err = match self.resolve_stmt_inner(stmt, true) {
+            Ok(resolved) => {
+                self.fail_poisoned(entity.as_ref(), stmt.position());
+                return match entity {
+                    Some(entity) if entity.deref().borrow().failure().is_some() => None,
+                    _ => Some(resolved),
+                };
+            }
+            Err(err) => err,
+        };
+        self.scope_stack.truncate(depth);
+        self.closures.clear();
+        self.state = state;
+        self.self_entity = None;
+        self.chain_receiver = None;
+        self.resolved.clear();
+
+        let failed = self
+            .current_scope()
+            .elements()
+            .iter()
+            .filter(|failed| {
+                // a declared function is not resolving while its body is.
+                let is_item = entity.as_ref().is_some_and(|entity| Rc::ptr_eq(entity, failed));
+                let failed = failed.borrow();
+                failed.is_resolving() || (is_item && failed.is_declared())
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        let recovered = self.recovered.as_mut().unwrap();
+        for failed in failed {
+            // an item used by the statement is marked where the error is.
+            let position = match entity.as_ref() {
+                Some(entity) if Rc::ptr_eq(entity, &failed) => stmt.position(),
+                _ => *err.pos(),
+            };
+            failed.deref().borrow_mut().to_failed(err.clone(), position);
+            recovered.failed.push(failed);
+        }
+        recovered.failures.push(err);
+        self.fail_poisoned(entity.as_ref(), stmt.position());
+        None
+    }
+
+    /// the items whose bodies had a statement fail become tombstones once they are resolved,
+    /// the item of the statement at position.
+    fn fail_poisoned(&mut self, entity: Option<&EntityRef>, position: Position) {
+        let recovered = self.recovered.as_mut().unwrap();
+        let done = recovered
+            .poisoned
+            .iter()
+            .filter(|(item, _)| {
+                let item = item.deref().borrow();
+                !item.is_resolving() && item.failure().is_none()
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for (item, err) in done {
+            let position = match entity {
+                Some(entity) if Rc::ptr_eq(entity, &item) => position,
+                _ => *err.pos(),
+            };
+            item.deref().borrow_mut().to_failed(err, position);
+            recovered.failed.push(item);
+        }
+    }
+
+    /// resolves a statement of a body. When checking recovers from errors a statement that
+    /// fails is recorded and left out, the rest of the body is still checked. A local it
+    /// declares is given the invalid type so the errors about its uses, which follow from this
+    /// one, are dropped by `Diagnostics::finish`.
+    pub(super) fn resolve_body_stmt(&mut self, stmt: &Stmt) -> Result<Option<Rc<HirStmt>>, Error> {
+        if self.recovered.is_none() {
+            return self.resolve_stmt(stmt).map(Some);
+        }
+        let depth = self.scope_stack.len();
+        let state = self.state;
+        let closures = self.closures.len();
+        let chain_receiver = self.chain_receiver.clone();
+        let err = match self.resolve_stmt(stmt) {
+            Ok(stmt) => return Ok(Some(stmt)),
+            Err(err) => err,
+        };
+        self.scope_stack.truncate(depth);
+        self.state = state;
+        self.closures.truncate(closures);
+        self.chain_receiver = chain_receiver;
+
+        self.poison_local(stmt);
+        self.record_failure(err)?;
+        Ok(None)
+    }
+
+    /// records the error of a statement of a body when checking recovers from errors, the item
+    /// whose body it is fails. Without recovery it is returned.
+    pub(super) fn record_failure(&mut self, err: Error) -> Result<(), Error> {
+        let recovered = match self.recovered.as_mut() {
+            Some(recovered) => recovered,
+            None => return Err(err),
+        };
+        if let Some(item) = self.items.last() {
+            if !recovered.poisoned.iter().any(|(poisoned, _)| Rc::ptr_eq(poisoned, item)) {
+                recovered.poisoned.push((item.clone(), err.clone()));
+            }
+        }
+        recovered.failures.push(err);
+        Ok(())
+    }
+
+    /// whether entity is an item of the module whose body had a statement fail. It is used by
+    /// its type so the item using it is checked, but fails with it without another error.
+    fn use_poisoned(&mut self, entity: &EntityRef) -> bool {
+        let recovered = match self.recovered.as_mut() {
+            Some(recovered) => recovered,
+            None => return false,
+        };
+        let err = match recovered
+            .poisoned
+            .iter()
+            .find(|(poisoned, _)| Rc::ptr_eq(poisoned, entity))
+        {
+            Some((_, err)) if !entity.deref().borrow().ty().kind().has_type_params() => err.clone(),
+            _ => return false,
+        };
+        if let Some(item) = self.items.last() {
+            if !recovered.poisoned.iter().any(|(poisoned, _)| Rc::ptr_eq(poisoned, item)) {
+                recovered.poisoned.push((item.clone(), err));
+            }
+        }
+        true
+    }
+
+    /// declares the local of a statement that failed with the invalid type, or the type it is
+    /// declared with when that resolves.
+    fn poison_local(&mut self, stmt: &Stmt) {
+        let (name, mutable, spec) = match stmt.kind() {
+            StmtKind::Item(item) => match item.kind() {
+                ItemKind::Variable {
+                    name,
+                    mutable,
+                    spec,
+                    ..
+                } => (name, *mutable, spec.as_ref()),
+                ItemKind::Function { name, .. } => (name, false, None),
+                _ => return,
+            },
+            _ => return,
+        };
+        let name = name.kind().value.as_str();
+        if self.shallow_lookup(name).is_some() {
+            return;
+        }
+        let ty = match spec.map(|spec| self.resolve_spec(spec.as_ref())) {
+            Some(Ok(spec)) => spec.ty(),
+            _ => self.type_map.get_invalid(),
+        };
+        let variable = VariableInfo {
+            spec: None,
+            mutable,
+            global: false,
+            default: None,
+        };
+        let entity = Entity::new_ref(
+            Visibility::Private,
+            name.to_owned(),
+            ty,
+            EntityInfo::Variable(variable),
+            Path::empty(),
+        );
+        self.insert_entity(name, entity);
     }
 
     fn resolve_ident(&mut self, ident: &Identifier) -> Result<EntityRef, Error> {
+        if let Some(function) = self.captured_by(ident.kind().value.as_str()) {
+            let err = Error::captured_local(function.as_str(), ident.kind().value.as_str());
+            return Err(err.with_position(ident.position()));
+        }
+        self.capture(ident)?;
         if let Some(entity) = self.deep_lookup(ident.kind().value.as_str()) {
             // println!("Resolving Name: {}", ident.kind().value);
+            if entity.deref().borrow().failure().is_some() && self.use_poisoned(&entity) {
+                return Ok(entity);
+            }
             let entity_borrow = entity.deref().borrow();
-            if entity_borrow.is_resolved() {
+            if entity_borrow.failure().is_some() {
+                let err = Error::use_of_failed_item(ident.kind().value.as_str());
+                Err(err.with_position(ident.position()))
+            } else if entity_borrow.is_resolved() || entity_borrow.is_declared() {
                 // println!("\tName Resolves is resolved");
                 std::mem::drop(entity_borrow);
                 Ok(entity)
             } else if entity_borrow.is_resolving() {
-                let err =
-                    Error::other("Cyclic references are not supported at the moment".to_string())
-                        .with_position(ident.position());
-                Err(err)
+                let err = Error::cyclic_reference(ident.kind().value.as_str());
+                Err(err.with_position(ident.position()))
             } else {
                 // println!("\tEntity is unresolved, resolving");
                 if let EntityInfo::Unresolved(item) = entity_borrow.kind().clone() {
                     std::mem::drop(entity_borrow);
-                    let entity = self.resolve_item_impl(item.as_ref(), entity, true, false)?;
-                    Ok(entity)
+                    self.resolve_out_of_order(item.as_ref(), entity)
                 } else {
                     unreachable!("Compiler Error")
                 }
             }
         } else {
-            Err(Error::undeclared_identifier(ident.kind().value.clone())
-                .with_position(ident.position()))
+            let name = ident.kind().value.as_str();
+            let err = match self.hidden_imports.get(name) {
+                Some(err) => err.clone(),
+                None => Error::undeclared_identifier(name.to_owned()),
+            };
+            Err(err.with_position(ident.position()))
         }
     }
 }
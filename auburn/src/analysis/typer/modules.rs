@@ -0,0 +1,80 @@
+use crate::analysis::scope::{ScopeKind, ScopeRef};
+use crate::analysis::typer::Typer;
+use crate::analysis::EntityRef;
+use crate::error::Error;
+use crate::ir::ast::{Expr, ExprKind, Identifier, Node, Visibility};
+use std::ops::Deref;
+
+impl<'a> Typer<'a> {
+    /// the scope of the imported module expr names, `shapes.square` once the file uses it. A
+    /// name declared by the file, its imports or a local hides the module it starts with.
+    pub(super) fn module_path(&self, expr: &Expr) -> Option<ScopeRef> {
+        match expr.kind() {
+            ExprKind::Name(name) => {
+                let name = name.kind().value.as_str();
+                if self.deep_lookup(name).is_some() {
+                    return None;
+                }
+                let imports = self
+                    .scope_stack
+                    .iter()
+                    .find(|scope| *scope.kind() == ScopeKind::Imports)?;
+                Self::submodule(imports.children(), name)
+            }
+            ExprKind::Field(operand, name) => {
+                let module = self.module_path(operand.as_ref())?;
+                Self::submodule(module.children(), name.kind().value.as_str())
+            }
+            _ => None,
+        }
+    }
+
+    /// `shapes.square`, the path of names expr is written as.
+    pub(super) fn dotted_name(expr: &Expr) -> String {
+        match expr.kind() {
+            ExprKind::Name(name) => name.kind().value.clone(),
+            ExprKind::Field(operand, name) => {
+                format!("{}.{}", Self::dotted_name(operand.as_ref()), name.kind().value)
+            }
+            _ => expr.name().to_owned(),
+        }
+    }
+
+    fn submodule(children: &[ScopeRef], name: &str) -> Option<ScopeRef> {
+        children
+            .iter()
+            .find(|child| match child.kind() {
+                ScopeKind::Module(path) => path.rsplit('.').next() == Some(name),
+                _ => false,
+            })
+            .cloned()
+    }
+
+    /// the item of module named name, only public items are used outside of their module.
+    pub(super) fn module_item(
+        &self,
+        module: &ScopeRef,
+        name: &Identifier,
+    ) -> Result<EntityRef, Error> {
+        let module_name = match module.kind() {
+            ScopeKind::Module(path) => path.as_str(),
+            _ => unreachable!("a module path is made of module scopes"),
+        };
+        let item = name.kind().value.as_str();
+        let err = match module.shallow_lookup(item) {
+            Some(entity) => {
+                let borrow = entity.deref().borrow();
+                if borrow.visibility() != Visibility::Public {
+                    Error::private_import(item, module_name)
+                } else if borrow.failure().is_some() {
+                    Error::use_of_failed_item(item)
+                } else {
+                    std::mem::drop(borrow);
+                    return Ok(entity);
+                }
+            }
+            None => Error::unknown_module_item(module_name, item),
+        };
+        Err(err.with_position(name.position()))
+    }
+}
@@ -0,0 +1,225 @@
+use crate::analysis::entity::{Path, VariableInfo};
+use crate::analysis::scope::ScopeKind;
+use crate::analysis::typer::expressions::BranchTypes;
+use crate::analysis::typer::{Typer, EXPR_RESULT_USED};
+use crate::analysis::{Entity, EntityInfo, EntityRef};
+use crate::error::Error;
+use crate::ir::ast::{
+    Expr, ExprKind, Identifier, MatchArm, Node, Pattern, PatternKind, UnaryOp, Visibility,
+};
+use crate::ir::hir::{
+    self, AddressMode, HirExpr, HirExprInner, HirExprKind, HirExprPtr, HirPattern, MatchExpr,
+    MirNode, ResultMeta,
+};
+use crate::syntax::Position;
+use crate::types::Type;
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+impl<'a> Typer<'a> {
+    /// a match, the arms are resolved in order each in a scope holding the names its pattern
+    /// binds. Like the branches of an if, the arms of a match whose value is used agree on a
+    /// type. The arms must match every value, the arms that are never taken are left to the
+    /// dead code pass.
+    pub(super) fn resolve_match(
+        &mut self,
+        value: &Expr,
+        arms: &[MatchArm],
+        expected_type: Option<Rc<Type>>,
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let result_used = self.check_state(EXPR_RESULT_USED);
+        let value = self.resolve_expr(value, None)?;
+        let value_type = value.ty();
+        let mut types = BranchTypes::new(expected_type);
+
+        let mut resolved = vec![];
+        for arm in arms {
+            self.push_scope(ScopeKind::Block);
+            let arm = self
+                .resolve_pattern(&arm.pattern, &value_type)
+                .and_then(|pattern| {
+                    let body = self.resolve_branch(arm.body.as_ref(), &mut types, result_used)?;
+                    Ok(hir::MatchArm {
+                        pattern,
+                        body,
+                        position: arm.pattern.position,
+                    })
+                });
+            self.pop_scope();
+            resolved.push(arm?);
+        }
+
+        let missing = MatchExpr::missing_patterns(&resolved, value_type.clone());
+        if !missing.is_empty() {
+            let err = Error::non_exhaustive_match(value_type.as_ref(), missing);
+            return Err(err.with_position(position));
+        }
+
+        let unit = self.type_map.get_unit();
+        // every arm diverges, the match has no value.
+        let ty = types.into_type().unwrap_or_else(|| unit.clone());
+        let mut agree = true;
+        for arm in resolved.iter_mut() {
+            agree &= Self::unify_branch(&mut arm.body, &ty, result_used)?;
+        }
+        let ty = if agree { ty } else { unit };
+
+        let match_expr = MatchExpr {
+            value,
+            arms: resolved,
+        };
+        let mutable = ResultMeta::new(false, ty.is_mutable(), false, false, false);
+        let inner = HirExprInner::new(ty.address_mode(), mutable, HirExprKind::Match(match_expr));
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
+    }
+
+    /// a pattern matching values of ty, the names it binds are added to the current scope.
+    fn resolve_pattern(&mut self, pattern: &Pattern, ty: &Rc<Type>) -> Result<HirPattern, Error> {
+        match &pattern.kind {
+            PatternKind::Wildcard => Ok(HirPattern::Wildcard),
+            PatternKind::Binding(name) => Ok(HirPattern::Binding(self.bind(name, ty.clone())?)),
+            PatternKind::Literal(literal) => {
+                Ok(HirPattern::Literal(self.resolve_literal_pattern(literal, ty)?))
+            }
+            PatternKind::Variant {
+                enum_name,
+                variant,
+                fields,
+            } => {
+                let entity = self.resolve_ident(enum_name)?;
+                let enum_type = entity.borrow().ty();
+                let is_enum = matches!(entity.borrow().kind(), EntityInfo::Enumeration(_));
+                if !is_enum || !Self::same_type(ty, &enum_type) {
+                    let err = Error::incompatible_types(ty.as_ref(), enum_type.as_ref());
+                    return Err(err.with_position(enum_name.position()));
+                }
+
+                let name = variant.kind().value.as_str();
+                let (discriminant, field_types) = match entity.borrow().as_enum().variant(name) {
+                    Some(variant) => (variant.discriminant, variant.fields.clone()),
+                    None => {
+                        let err = Error::unknown_subentity("variant", name, enum_type.as_ref());
+                        return Err(err.with_position(variant.position()));
+                    }
+                };
+                if fields.len() != field_types.len() {
+                    let err = Error::invalid_actuals(field_types.len(), fields.len())
+                        .with_position(pattern.position);
+                    let declared = entity.borrow().as_enum().declared(name);
+                    return Err(Self::with_declaration(err, declared, name));
+                }
+
+                let fields = fields
+                    .iter()
+                    .zip(field_types)
+                    .map(|(field, field_type)| match &field.kind {
+                        PatternKind::Wildcard => Ok(None),
+                        PatternKind::Binding(name) => self.bind(name, field_type).map(Some),
+                        _ => Err(Error::invalid_field_pattern().with_position(field.position)),
+                    })
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok(HirPattern::Variant {
+                    enum_entity: entity,
+                    discriminant,
+                    fields,
+                })
+            }
+            PatternKind::Range { start, end } => {
+                let start = self.resolve_pattern_bound(start, ty)?;
+                let end = self.resolve_pattern_bound(end, ty)?;
+                if let (HirExprKind::Integer(first), HirExprKind::Integer(last)) =
+                    (start.inner().kind(), end.inner().kind())
+                {
+                    if first >= last {
+                        let err = Error::empty_range_pattern(*first, *last);
+                        return Err(err.with_position(pattern.position));
+                    }
+                }
+                Ok(HirPattern::Range { start, end })
+            }
+        }
+    }
+
+    /// a bound of a range pattern, a constant is folded into a literal like a negative integer.
+    fn resolve_pattern_bound(&mut self, bound: &Expr, ty: &Rc<Type>) -> Result<HirExprPtr, Error> {
+        if matches!(bound.kind(), ExprKind::Integer(_) | ExprKind::Unary(..)) {
+            return self.resolve_literal_pattern(bound, ty);
+        }
+        let constant = self.resolve_expr(bound, None)?;
+        let value = match Self::constant_integer(constant.as_ref()) {
+            Some(value) => self.integer_pattern(i128::from(value), ty, bound)?,
+            None => return Err(Error::non_constant_pattern_bound().with_position(bound.position())),
+        };
+        let inner = HirExprInner::new(AddressMode::Value, ResultMeta::literal(), value);
+        Ok(Rc::new(HirExpr::new(inner, bound.position(), ty.clone())))
+    }
+
+    /// an integer or a bool compared with values of ty, a negative integer is folded into the
+    /// literal.
+    fn resolve_literal_pattern(
+        &mut self,
+        literal: &Expr,
+        ty: &Rc<Type>,
+    ) -> Result<HirExprPtr, Error> {
+        let value = match literal.kind() {
+            ExprKind::Bool(val) => {
+                let bool_type = self.type_map.get_bool();
+                if !Self::same_type(ty, &bool_type) {
+                    let err = Error::incompatible_types(ty.as_ref(), bool_type.as_ref());
+                    return Err(err.with_position(literal.position()));
+                }
+                HirExprKind::Bool(*val)
+            }
+            ExprKind::Integer(val) => self.integer_pattern(i128::from(*val), ty, literal)?,
+            ExprKind::Unary(UnaryOp::Minus, operand) => match operand.kind() {
+                ExprKind::Integer(val) => self.integer_pattern(-i128::from(*val), ty, literal)?,
+                _ => unreachable!("the parser only negates integer patterns"),
+            },
+            _ => unreachable!("the parser only produces integer and bool literal patterns"),
+        };
+        let inner = HirExprInner::new(AddressMode::Value, ResultMeta::literal(), value);
+        Ok(Rc::new(HirExpr::new(inner, literal.position(), ty.clone())))
+    }
+
+    fn integer_pattern(
+        &mut self,
+        value: i128,
+        ty: &Rc<Type>,
+        literal: &Expr,
+    ) -> Result<HirExprKind, Error> {
+        let inner = Type::inner(ty.clone());
+        if !inner.is_integer() {
+            let err = Error::incompatible_types(ty.as_ref(), self.type_map.get_i32().as_ref());
+            return Err(err.with_position(literal.position()));
+        }
+        match i64::try_from(value) {
+            Ok(value) if inner.fits_integer(value) => Ok(HirExprKind::Integer(value)),
+            _ => {
+                let err = Error::pattern_does_not_fit(value, ty.as_ref());
+                Err(err.with_position(literal.position()))
+            }
+        }
+    }
+
+    /// a local of the arm named by a pattern.
+    fn bind(&mut self, name: &Identifier, ty: Rc<Type>) -> Result<EntityRef, Error> {
+        self.check_duplicate_item_name(name)?;
+        self.check_shadowed_name(name)?;
+        let info = VariableInfo {
+            spec: None,
+            mutable: false,
+            global: false,
+            default: None,
+        };
+        let entity = Entity::new_ref(
+            Visibility::Private,
+            name.kind().value.clone(),
+            ty,
+            EntityInfo::Variable(info),
+            Path::empty(),
+        );
+        self.insert_entity(name.kind().value.as_str(), entity.clone());
+        Ok(entity)
+    }
+}
@@ -0,0 +1,187 @@
+use crate::analysis::typer::Typer;
+use crate::error::Error;
+use crate::ir::ast::{Expr, ExprKind, Identifier, Node, Visibility};
+use crate::ir::hir::{
+    AddressMode, ArrayExpr, GetFieldExpr, HirExpr, HirExprInner, HirExprKind, HirExprPtr,
+    MirNode, ResultMeta, TupleExpr,
+};
+use crate::syntax::Position;
+use crate::types::{Type, TypeKind};
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// name of the built in reflection module.
+const REFLECT_MODULE: &str = "reflect";
+
+const FIELDS: &str = "fields";
+const VARIANTS: &str = "variants";
+const GET_FIELD: &str = "get_field";
+
+impl<'a> Typer<'a> {
+    /// `reflect` is a built in module, it is shadowed by any entity with the same name.
+    pub(crate) fn is_reflect_receiver(&self, receiver: &Expr) -> bool {
+        self.is_builtin(receiver, REFLECT_MODULE)
+    }
+
+    /// whether operand is `reflect.<name>[T]`, the function called with a type argument.
+    pub(crate) fn is_reflect_type_call(&self, operand: &Expr) -> bool {
+        match operand.kind() {
+            ExprKind::Index { operand, .. } => match operand.kind() {
+                ExprKind::Field(module, _) => self.is_reflect_receiver(module.as_ref()),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// `reflect.fields[T]()` is the public fields of the structure T as `[(string, string)]`,
+    /// the name and the type of each in the order they are declared. `reflect.variants[T]()`
+    /// is the names of the variants of the enum T as `[string]`. Both are folded to an array
+    /// of literals, nothing is looked up while running.
+    pub(crate) fn resolve_reflect_type_call(
+        &mut self,
+        operand: &Expr,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let (name, argument) = match operand.kind() {
+            ExprKind::Index { operand, index } => match operand.kind() {
+                ExprKind::Field(_, name) => (name.as_ref(), index.as_ref()),
+                _ => unreachable!("a reflect call with a type argument names a function"),
+            },
+            _ => unreachable!("a reflect call with a type argument is indexed"),
+        };
+        let name_str = name.kind().value.as_str();
+        if name_str != FIELDS && name_str != VARIANTS {
+            let err = Error::unknown_reflect_function(name_str);
+            return Err(err.with_position(name.position()));
+        }
+        if !actuals.is_empty() {
+            let err = Error::invalid_actuals(0, actuals.len());
+            return Err(err.with_position(position));
+        }
+
+        let ty = self.resolve_named_spec_type(argument)?;
+        let string = self.type_map.get_string();
+        let (elements, element_type) = match Type::inner(ty.clone()).kind() {
+            TypeKind::Struct { entity } if name_str == FIELDS => {
+                let entity = entity.deref().borrow();
+                let fields = entity.as_struct().fields.clone();
+                let elements = fields
+                    .elements()
+                    .iter()
+                    .map(|field| field.deref().borrow())
+                    .filter(|field| field.visibility() == Visibility::Public)
+                    .map(|field| {
+                        let name = Self::string_literal(field.name(), string.clone(), position);
+                        let ty = field.ty().to_string();
+                        let ty = Self::string_literal(&ty, string.clone(), position);
+                        self.literal_tuple(vec![name, ty], position)
+                    })
+                    .collect::<Vec<_>>();
+                let element_type = self.insert_type(TypeKind::Tuple {
+                    elements: vec![string.clone(), string],
+                });
+                (elements, element_type)
+            }
+            TypeKind::Enum { entity } if name_str == VARIANTS => {
+                let elements = entity
+                    .deref()
+                    .borrow()
+                    .as_enum()
+                    .variants
+                    .iter()
+                    .map(|variant| Self::string_literal(&variant.name, string.clone(), position))
+                    .collect();
+                (elements, string)
+            }
+            _ => {
+                let err = if name_str == FIELDS {
+                    Error::expected_struct_type(ty.as_ref())
+                } else {
+                    Error::expected_enum_type(ty.as_ref())
+                };
+                return Err(err.with_position(argument.position()));
+            }
+        };
+
+        let ty = self.insert_type(TypeKind::Slice { element_type });
+        let inner = HirExprInner::new(
+            AddressMode::Address,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Array(ArrayExpr { elements }),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
+    }
+
+    /// `reflect.get_field(value, name)` is the public field of value named by the string, it
+    /// is `Option[any]` and none when the structure of value has no such field. The field is
+    /// looked up in the field table of the structure while running, so value can be `any`.
+    pub(crate) fn resolve_reflect_call(
+        &mut self,
+        name: &Identifier,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let name_str = name.kind().value.as_str();
+        if name_str == FIELDS || name_str == VARIANTS {
+            let err = Error::type_argument_count(&format!("reflect.{}", name_str), 1, 0);
+            return Err(err.with_position(name.position()));
+        }
+        if name_str != GET_FIELD {
+            let err = Error::unknown_reflect_function(name_str);
+            return Err(err.with_position(name.position()));
+        }
+        if actuals.len() != 2 {
+            let err = Error::invalid_actuals(2, actuals.len());
+            return Err(err.with_position(position));
+        }
+
+        let operand = self.resolve_expr(actuals[0].as_ref(), None)?;
+        if operand.inner().meta().is_type {
+            let err = Error::expecting_instance_type(operand.ty().as_ref());
+            return Err(err.with_position(operand.position()));
+        }
+        if !matches!(
+            Type::inner(operand.ty()).kind(),
+            TypeKind::Struct { .. } | TypeKind::Any
+        ) {
+            let err = Error::expected_struct_type(operand.ty().as_ref());
+            return Err(err.with_position(operand.position()));
+        }
+        let string = self.type_map.get_string();
+        let field = self.resolve_expr(actuals[1].as_ref(), Some(string))?;
+
+        let ty = self.optional_of(self.type_map.get_any());
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::GetField(GetFieldExpr {
+                operand,
+                name: field,
+            }),
+        );
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
+    }
+
+    fn string_literal(value: &str, string: Rc<Type>, position: Position) -> HirExprPtr {
+        let inner = HirExprInner::new(
+            AddressMode::Address,
+            ResultMeta::literal(),
+            HirExprKind::String(value.to_owned()),
+        );
+        Rc::new(HirExpr::new(inner, position, string))
+    }
+
+    fn literal_tuple(&mut self, elements: Vec<HirExprPtr>, position: Position) -> HirExprPtr {
+        let ty = self.insert_type(TypeKind::Tuple {
+            elements: elements.iter().map(|element| element.ty()).collect(),
+        });
+        let inner = HirExprInner::new(
+            AddressMode::Value,
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Tuple(TupleExpr { elements }),
+        );
+        Rc::new(HirExpr::new(inner, position, ty))
+    }
+}
@@ -1,11 +1,15 @@
+use crate::analysis::typer::desugar::{Lowered, Synthesizer};
 use crate::analysis::typer::Typer;
-use crate::analysis::typer::EXPR_RESULT_USED;
+use crate::analysis::typer::{BLOCK_RESULT, EXPR_RESULT_USED};
 use crate::error::Error;
-use crate::ir::ast::{AssignmentOp, Node, Stmt, StmtKind};
-use crate::ir::hir::{Assignment, HirStmt, HirStmtKind, MirNode};
+use crate::ir::ast::{AssignmentOp, Expr, ExprKind, Node, Stmt, StmtKind};
+use crate::ir::hir::{Assignment, Desugaring, HirExprKind, HirStmt, HirStmtKind, MirNode};
 use std::ops::Deref;
 use std::rc::Rc;
 
+/// the name assigned to discard a value, `_ = f()`.
+const DISCARD_NAME: &str = "_";
+
 #[allow(unused)]
 macro_rules! with_state {
     ($typer:expr, $state:expr, $body:tt) => {{
@@ -27,6 +31,15 @@ impl<'src> Typer<'src> {
         stmt: &Stmt,
         top_level: bool,
     ) -> Result<Rc<HirStmt>, Error> {
+        let generated = self.generated_by(stmt);
+        let resolved = self.resolve_stmt_kind(stmt, top_level)?;
+        if let Some(desugaring) = generated {
+            resolved.mark_generated(desugaring);
+        }
+        Ok(resolved)
+    }
+
+    fn resolve_stmt_kind(&mut self, stmt: &Stmt, top_level: bool) -> Result<Rc<HirStmt>, Error> {
         // println!("Resolving Stmt {}", stmt.kind().name());
 
         match stmt.kind() {
@@ -37,9 +50,16 @@ impl<'src> Typer<'src> {
                 }
 
                 let old_state = self.state;
-                self.state &= !EXPR_RESULT_USED;
-                let expr = self.resolve_expr(expr.as_ref(), None)?;
+                let expected_type = if self.check_state(BLOCK_RESULT) {
+                    self.block_result_type.take()
+                } else {
+                    self.state &= !EXPR_RESULT_USED;
+                    None
+                };
+                self.state &= !BLOCK_RESULT;
+                let expr = self.resolve_expr(expr.as_ref(), expected_type);
                 self.state = old_state;
+                let expr = expr?;
                 let position = expr.position();
                 let ty = expr.ty();
                 Ok(Rc::new(HirStmt::new(HirStmtKind::Expr(expr), position, ty)))
@@ -59,23 +79,49 @@ impl<'src> Typer<'src> {
                     self.type_map.get_unit(),
                 )))
             }
-            StmtKind::Assignment { op, lvalue, rhs } => {
+            StmtKind::Assignment {
+                op,
+                op_position,
+                lvalue,
+                rhs,
+            } => {
                 if self.is_default_mode() && top_level {
                     let err = Error::invalid_assignment_in_mode(self.mode);
                     return Err(err.with_position(stmt.position()));
                 }
 
+                if *op == AssignmentOp::Assign && Self::is_discard(lvalue.as_ref()) {
+                    // `_ = f()` evaluates the call for its effects, its result is not used.
+                    let old_state = self.state;
+                    self.state &= !EXPR_RESULT_USED;
+                    let expr = self.resolve_expr(rhs.as_ref(), None)?;
+                    self.state = old_state;
+                    let position = stmt.position();
+                    let ty = expr.ty();
+                    return Ok(Rc::new(HirStmt::new(HirStmtKind::Expr(expr), position, ty)));
+                }
+
                 let (entity, mir_lvalue) = self.resolve_expr_to_entity(lvalue.as_ref())?;
                 // let lvalue_type = mir_lvalue.ty();
                 let mutability = mir_lvalue.inner().meta();
                 if !mutability.mutable {
-                    let err = Error::immutable_entity(entity.deref().borrow().name());
-                    return Err(err.with_position(lvalue.position()));
+                    let entity = entity.deref().borrow();
+                    let err = Error::immutable_entity(entity.name());
+                    let err = err.with_position(lvalue.position());
+                    return Err(match entity.position() {
+                        Some(declared) => err.with_label(
+                            declared,
+                            format!("'{}' is declared immutable here", entity.name()),
+                        ),
+                        None => err,
+                    });
                 }
                 match op {
                     AssignmentOp::Assign => {
                         let lvalue_type = mir_lvalue.ty();
-                        let rhs = self.resolve_expr(rhs.as_ref(), Some(lvalue_type))?;
+                        let rhs = with_state!(self, EXPR_RESULT_USED, {
+                            self.resolve_expr(rhs.as_ref(), Some(lvalue_type))?
+                        });
                         let assignment = Assignment {
                             op: *op,
                             lvalue: mir_lvalue,
@@ -88,7 +134,42 @@ impl<'src> Typer<'src> {
                             self.type_map.get_unit(),
                         )))
                     }
-                    _ => todo!("Assignment operator {} is not implemented", op),
+                    _ => {
+                        // x op= y is checked as x = x op y, the operation is placed at the
+                        // operator which is where an operand type it does not apply to is
+                        // reported. The assignment keeps the operator and the operand, a backend
+                        // emits the fused operation or a load, the operation and a store.
+                        let binary_op = op.get_binary_op().unwrap();
+                        let mut synthesizer =
+                            Synthesizer::new(Desugaring::CompoundAssignment, stmt.position());
+                        let value = synthesizer.expr_at(
+                            ExprKind::Binary(binary_op, lvalue.clone(), rhs.clone()),
+                            *op_position,
+                        );
+                        let assignment = synthesizer.stmt(StmtKind::Assignment {
+                            op: AssignmentOp::Assign,
+                            op_position: *op_position,
+                            lvalue: lvalue.clone(),
+                            rhs: value,
+                        });
+
+                        self.trace_desugaring(&synthesizer, stmt, Lowered::Ast(&assignment));
+                        let lowered = self.resolve_desugared(&synthesizer, |typer| {
+                            typer.resolve_stmt_inner(assignment.as_ref(), top_level)
+                        })?;
+                        let (lvalue, value) = match lowered.inner() {
+                            HirStmtKind::Assignment(assignment) => {
+                                (assignment.lvalue.clone(), assignment.rhs.clone())
+                            }
+                            _ => unreachable!("compound assignment lowered to {:?}", lowered),
+                        };
+                        let rhs = match value.inner().kind() {
+                            HirExprKind::Binary(binary) => binary.right.clone(),
+                            _ => unreachable!("compound assignment computed by {:?}", value),
+                        };
+                        let assignment = Assignment { op: *op, lvalue, rhs };
+                        Ok(Rc::new(lowered.with_inner(HirStmtKind::Assignment(assignment))))
+                    }
                 }
             }
             StmtKind::Echo(param) => {
@@ -97,7 +178,10 @@ impl<'src> Typer<'src> {
                     return Err(err.with_position(stmt.position()));
                 }
 
-                let expr = self.resolve_expr(param.as_ref(), None)?;
+                let expr = with_state!(self, EXPR_RESULT_USED, {
+                    self.resolve_expr(param.as_ref(), None)?
+                });
+                let expr = self.echoed_value(expr);
                 Ok(Rc::new(HirStmt::new(
                     HirStmtKind::Echo(expr),
                     param.position(),
@@ -107,4 +191,8 @@ impl<'src> Typer<'src> {
             StmtKind::Empty => unreachable!("{:?}", stmt.position()),
         }
     }
+
+    fn is_discard(lvalue: &Expr) -> bool {
+        matches!(lvalue.kind(), ExprKind::Name(name) if name.kind().value == DISCARD_NAME)
+    }
 }
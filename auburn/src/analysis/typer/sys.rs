@@ -0,0 +1,94 @@
+use crate::analysis::typer::Typer;
+use crate::error::Error;
+use crate::ir::ast::{Expr, Identifier, Node};
+use crate::ir::hir::{AddressMode, HirExpr, HirExprInner, HirExprKind, HirExprPtr, ResultMeta};
+use crate::syntax::Position;
+use std::rc::Rc;
+
+/// name of the built in module describing the program and what runs it.
+const SYS_MODULE: &str = "sys";
+
+const LANGUAGE_VERSION_FUNCTION: &str = "language_version";
+const MODULE_NAME: &str = "module_name";
+const OPT_LEVEL: &str = "opt_level";
+const DEBUG_INFO: &str = "debug_info";
+const HOST: &str = "host";
+
+/// the version of the language a program is compiled for, `sys.language_version()`.
+pub const LANGUAGE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// how the program is built, the `sys` functions describing the build are folded to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub opt_level: u8,
+    /// whether the source map of the program is built with it.
+    pub debug_info: bool,
+    /// whether `unreachable()` raises its error at every opt level, above level 0 the program
+    /// is built trusting it is never reached unless it is set.
+    pub checked_unreachable: bool,
+}
+
+impl Default for BuildInfo {
+    fn default() -> Self {
+        Self {
+            opt_level: 0,
+            debug_info: true,
+            checked_unreachable: false,
+        }
+    }
+}
+
+impl<'a> Typer<'a> {
+    /// `sys` is a built in module, it is shadowed by any entity with the same name.
+    pub(crate) fn is_sys_receiver(&self, receiver: &Expr) -> bool {
+        self.is_builtin(receiver, SYS_MODULE)
+    }
+
+    /// `sys.language_version()`, `sys.module_name()`, `sys.opt_level()` and `sys.debug_info()`
+    /// are known when the program is compiled, they are literals so a comparison of them folds
+    /// and the branches it gates are removed. `sys.host()` is the string the program embedding
+    /// the vm set, it is only known while running.
+    pub(crate) fn resolve_sys_call(
+        &mut self,
+        name: &Identifier,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let name_str = name.kind().value.as_str();
+        let (kind, ty) = match name_str {
+            LANGUAGE_VERSION_FUNCTION => (
+                HirExprKind::String(LANGUAGE_VERSION.to_owned()),
+                self.type_map.get_string(),
+            ),
+            MODULE_NAME => (
+                HirExprKind::String(self.module_name.clone()),
+                self.type_map.get_string(),
+            ),
+            OPT_LEVEL => (
+                HirExprKind::Integer(self.build_info.opt_level as i64),
+                self.type_map.get_i64(),
+            ),
+            DEBUG_INFO => (
+                HirExprKind::Bool(self.build_info.debug_info),
+                self.type_map.get_bool(),
+            ),
+            HOST => (HirExprKind::Host, self.type_map.get_string()),
+            _ => {
+                let err = Error::unknown_sys_function(name_str);
+                return Err(err.with_position(name.position()));
+            }
+        };
+        if !actuals.is_empty() {
+            let err = Error::invalid_actuals(0, actuals.len());
+            return Err(err.with_position(position));
+        }
+
+        // a string is used by its address like any other string.
+        let address_mode = match kind {
+            HirExprKind::String(_) | HirExprKind::Host => AddressMode::Address,
+            _ => AddressMode::Value,
+        };
+        let inner = HirExprInner::new(address_mode, ResultMeta::literal(), kind);
+        Ok(Rc::new(HirExpr::new(inner, position, ty)))
+    }
+}
@@ -0,0 +1,122 @@
+use crate::analysis::typer::Typer;
+use crate::error::Error;
+use crate::ir::ast::{Expr, ExprKind, Identifier, Node};
+use crate::ir::hir::{
+    HirExpr, HirExprInner, HirExprKind, HirExprPtr, MirNode, ResultMeta, TaskExpr, TaskFunction,
+};
+use crate::syntax::Position;
+use crate::types::{Type, TypeKind};
+use std::rc::Rc;
+
+/// name of the built in task module.
+const TASK_MODULE: &str = "task";
+
+impl<'a> Typer<'a> {
+    /// `task` is a built in module, it is shadowed by any entity with the same name.
+    pub(crate) fn is_task_receiver(&self, receiver: &Expr) -> bool {
+        match receiver.kind() {
+            ExprKind::Name(ident) => {
+                let name = ident.kind().value.as_str();
+                name == TASK_MODULE && self.deep_lookup(name).is_none()
+            }
+            _ => false,
+        }
+    }
+
+    /// `task.spawn(f, <actuals>)` calls f with the actuals on a thread of its own, it is
+    /// `Task[T]` for a function returning T. Whether the actuals can be sent to the task is
+    /// only known once they are copied when it is spawned.
+    pub(crate) fn resolve_task_call(
+        &mut self,
+        name: &Identifier,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        if name.kind().value != TaskFunction::Spawn.name() {
+            let err = Error::unknown_task_function(name.kind().value.as_str());
+            return Err(err.with_position(name.position()));
+        }
+        if actuals.is_empty() {
+            let err = Error::invalid_actuals(1, 0);
+            return Err(err.with_position(position));
+        }
+
+        let function = self.resolve_expr(actuals[0].as_ref(), None)?;
+        let (params, return_type) = match function.ty().kind() {
+            TypeKind::Function {
+                params,
+                return_type,
+            } => (params.clone(), return_type.clone()),
+            _ => {
+                let err = Error::spawn_of_non_function(function.ty().as_ref());
+                return Err(err.with_position(function.position()));
+            }
+        };
+        if params.len() != actuals.len() - 1 {
+            let err = Error::invalid_actuals(params.len(), actuals.len() - 1);
+            return Err(err.with_position(position));
+        }
+
+        let mut resolved = vec![function];
+        for (actual, param) in actuals[1..].iter().zip(params) {
+            resolved.push(self.resolve_expr(actual.as_ref(), Some(param))?);
+        }
+        let ty = self.insert_type(TypeKind::Task {
+            result: Type::inner(return_type),
+        });
+        Ok(Self::task_expr(TaskFunction::Spawn, resolved, position, ty))
+    }
+
+    /// the methods of the values of the task module, `<Task[T]>.join()` waits for the task and
+    /// is `Result[T, String]`, the error is the message of the task's runtime error. The value
+    /// or error of a result is taken with `ok()` and `err()`, each are optional.
+    pub(crate) fn resolve_task_method(
+        &mut self,
+        receiver: HirExprPtr,
+        name: &Identifier,
+        actuals: &[Box<Expr>],
+        position: Position,
+    ) -> Result<HirExprPtr, Error> {
+        let receiver_type = Type::inner(receiver.ty());
+        let name_str = name.kind().value.as_str();
+        let (function, ty) = match receiver_type.kind() {
+            TypeKind::Task { result } if name_str == TaskFunction::Join.name() => {
+                let ty = self.insert_type(TypeKind::Result {
+                    ok: result.clone(),
+                    err: self.type_map.get_string(),
+                });
+                (TaskFunction::Join, ty)
+            }
+            TypeKind::Result { ok, .. } if name_str == TaskFunction::Ok.name() => {
+                (TaskFunction::Ok, self.optional_of(ok.clone()))
+            }
+            TypeKind::Result { err, .. } if name_str == TaskFunction::Err.name() => {
+                (TaskFunction::Err, self.optional_of(err.clone()))
+            }
+            _ => {
+                let err = Error::unknown_subentity("method", name_str, receiver_type.as_ref());
+                return Err(err.with_position(name.position()));
+            }
+        };
+
+        if !actuals.is_empty() {
+            let err = Error::invalid_actuals(0, actuals.len());
+            return Err(err.with_position(position));
+        }
+        Ok(Self::task_expr(function, vec![receiver], position, ty))
+    }
+
+    fn task_expr(
+        function: TaskFunction,
+        actuals: Vec<HirExprPtr>,
+        position: Position,
+        ty: Rc<Type>,
+    ) -> HirExprPtr {
+        let inner = HirExprInner::new(
+            ty.address_mode(),
+            ResultMeta::new(false, false, false, true, false),
+            HirExprKind::Task(TaskExpr { function, actuals }),
+        );
+        Rc::new(HirExpr::new(inner, position, ty))
+    }
+}
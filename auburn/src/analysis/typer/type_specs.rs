@@ -1,11 +1,18 @@
+use crate::analysis::typer::collections::{MAP_TYPE, VEC_TYPE, WEAK_TYPE};
 use crate::analysis::typer::*;
+use crate::analysis::{EntityInfo, VariableInfo};
 use crate::error::Error;
-use crate::ir::ast::{Node, Spec, SpecKind};
-use crate::ir::hir::{HirSpec, HirSpecKind, MirNode};
-use crate::types::TypeKind;
+use crate::ir::ast::{Expr, ExprKind, Node, Spec, SpecKind, UnaryOp};
+use crate::ir::hir::{BinaryExpr, HirExpr, HirExprKind, HirSpec, HirSpecKind, MirNode, UnaryExpr};
+use crate::passes::integer_operation;
+use crate::types::{Type, TypeKind};
 use std::ops::Deref;
 use std::rc::Rc;
 
+/// name of the built in optional type.
+const OPTION_TYPE: &str = "Option";
+const TASK_TYPE: &str = "Task";
+
 #[allow(unused)]
 macro_rules! with_state {
     ($typer:expr, $state:expr, $body:tt) => {{
@@ -21,36 +28,61 @@ impl<'src> Typer<'src> {
     pub(crate) fn resolve_spec(&mut self, spec: &Spec) -> Result<Rc<HirSpec>, Error> {
         match spec.kind() {
             SpecKind::Named(expr) => {
-                let entity = self.resolve_type_expr(expr.as_ref())?;
-                if entity.deref().borrow().is_type() {
+                let ty = self.resolve_named_spec_type(expr.as_ref())?;
+                let kind = match ty.kind() {
+                    TypeKind::Option { .. } => HirSpecKind::Option,
+                    _ => HirSpecKind::Named,
+                };
+                Ok(Rc::new(HirSpec::new(kind, spec.position(), ty)))
+            }
+            SpecKind::SelfType => match self.self_entity.as_ref() {
+                Some(entity) => {
                     let ty = entity.deref().borrow().ty();
                     Ok(Rc::new(HirSpec::new(
                         HirSpecKind::Named,
                         spec.position(),
                         ty,
                     )))
-                } else {
-                    panic!()
                 }
+                None => Err(Error::invalid_self_type_in_context().with_position(spec.position())),
+            },
+            SpecKind::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.resolve_spec(element.as_ref()).map(|spec| spec.ty()))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let ty = self.insert_type(TypeKind::Tuple { elements });
+                Ok(Rc::new(HirSpec::new(HirSpecKind::Tuple, spec.position(), ty)))
+            }
+            SpecKind::Unit => {
+                let ty = self.type_map.get_unit();
+                Ok(Rc::new(HirSpec::new(HirSpecKind::Unit, spec.position(), ty)))
+            }
+            SpecKind::Infer => Err(Error::cannot_infer_type().with_position(spec.position())),
+            SpecKind::Resolved => {
+                let ty = self
+                    .resolved_specs
+                    .get(&spec.id())
+                    .cloned()
+                    .expect("Compiler Error: resolved spec outside of a function literal");
+                Ok(Rc::new(HirSpec::new(HirSpecKind::Infer, spec.position(), ty)))
             }
-            SpecKind::SelfType => todo!("Self type not implemented"),
-            SpecKind::Tuple(_) | SpecKind::Unit | SpecKind::Infer => todo!("{:?}", spec),
             SpecKind::Array(element_type, size) => {
                 let mir_spec = self.resolve_spec(element_type.as_ref())?;
                 match size.as_ref() {
                     Some(size) => {
                         let mir_size = self.resolve_expr(size.as_ref(), None)?;
                         let mir_size_type = mir_size.ty();
-                        // reduce the expression if possible
-                        // let mir_size = MirExpr::reduce(mir_size)?;
-                        // the resulting expression must be a constant value.
                         // @TODO: Add support for constant expressions
-                        if mir_size.is_literal() {
-                            if mir_size_type.is_integer() {
-                                let size = mir_size.as_integer() as usize;
+                        if !mir_size_type.is_integer() {
+                            let err = Error::invalid_array_size_type(mir_size_type.as_ref());
+                            return Err(err.with_position(size.position()));
+                        }
+                        match Self::constant_integer(mir_size.as_ref()) {
+                            Some(size) if size >= 0 => {
                                 let ty = self.insert_type(TypeKind::Array {
                                     element_type: mir_spec.ty(),
-                                    size,
+                                    size: size as usize,
                                 });
 
                                 Ok(Rc::new(HirSpec::new(
@@ -58,13 +90,11 @@ impl<'src> Typer<'src> {
                                     spec.position(),
                                     ty,
                                 )))
-                            } else {
-                                let err = Error::invalid_array_size_type(mir_size_type.as_ref());
-                                Err(err)
                             }
-                        } else {
-                            let err = Error::invalid_array_size_type(mir_size_type.as_ref());
-                            Err(err)
+                            _ => {
+                                let err = Error::non_constant_array_size();
+                                Err(err.with_position(size.position()))
+                            }
                         }
                     }
                     None => {
@@ -92,6 +122,100 @@ impl<'src> Typer<'src> {
                     ty,
                 )))
             }
+            SpecKind::Function { params, ret } => {
+                let params = params
+                    .iter()
+                    .map(|param| self.resolve_spec(param.as_ref()).map(|spec| spec.ty()))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let return_type = if ret.is_infer() {
+                    self.type_map.get_unit()
+                } else {
+                    self.resolve_spec(ret.as_ref())?.ty()
+                };
+                let ty = self.insert_type(TypeKind::Function {
+                    params,
+                    return_type,
+                });
+                Ok(Rc::new(HirSpec::new(HirSpecKind::Function, spec.position(), ty)))
+            }
+        }
+    }
+
+    /// `Option[T]`, `Task[T]`, `Vec[T]`, `Map[K, V]` and `Weak[T]` are built in unless the name
+    /// is shadowed, `Option[Option[T]]` is `Option[T]`.
+    pub(crate) fn resolve_named_spec_type(&mut self, expr: &Expr) -> Result<Rc<Type>, Error> {
+        if let ExprKind::Spec(spec) = expr.kind() {
+            return Ok(self.resolve_spec(spec.as_ref())?.ty());
+        }
+        if let ExprKind::Index { operand, index } = expr.kind() {
+            if let ExprKind::Name(name) = operand.kind() {
+                let name = name.kind().value.as_str();
+                if name == OPTION_TYPE && self.deep_lookup(name).is_none() {
+                    let inner = self.resolve_named_spec_type(index.as_ref())?;
+                    return Ok(self.optional_of(inner));
+                }
+                if name == TASK_TYPE && self.deep_lookup(name).is_none() {
+                    let result = self.resolve_named_spec_type(index.as_ref())?;
+                    return Ok(self.insert_type(TypeKind::Task { result }));
+                }
+                if name == VEC_TYPE && self.deep_lookup(name).is_none() {
+                    let element_type = self.resolve_named_spec_type(index.as_ref())?;
+                    return Ok(self.insert_type(TypeKind::Vector { element_type }));
+                }
+                if name == MAP_TYPE && self.deep_lookup(name).is_none() {
+                    return self.resolve_map_type(index.as_ref());
+                }
+                if name == WEAK_TYPE && self.deep_lookup(name).is_none() {
+                    let inner = self.resolve_named_spec_type(index.as_ref())?;
+                    return self.weak_of(inner, index.position());
+                }
+            }
+        }
+
+        let entity = self.resolve_type_expr(expr)?;
+        if entity.deref().borrow().is_type() {
+            Ok(entity.deref().borrow().ty())
+        } else {
+            panic!()
+        }
+    }
+
+    /// the value of an integer known while compiling, a literal, an immutable global whose
+    /// initializer is known or arithmetic of them that does not overflow its type. The global
+    /// may be imported, it is already resolved by then.
+    pub(crate) fn constant_integer(expr: &HirExpr) -> Option<i64> {
+        match expr.inner().kind() {
+            HirExprKind::Integer(val) => Some(*val),
+            // the left operands of a chain are evaluated in a loop, from the first operand.
+            HirExprKind::Binary(binary) => {
+                let mut chain = vec![(expr, binary)];
+                let mut first = binary.left.as_ref();
+                while let HirExprKind::Binary(binary) = first.inner().kind() {
+                    chain.push((first, binary));
+                    first = binary.left.as_ref();
+                }
+                let mut value = Self::constant_integer(first)?;
+                for (expr, BinaryExpr { op, right, .. }) in chain.into_iter().rev() {
+                    let right = Self::constant_integer(right.as_ref())?;
+                    value = integer_operation(*op, value, right)
+                        .filter(|value| Type::inner(expr.ty()).fits_integer(*value))?;
+                }
+                Some(value)
+            }
+            HirExprKind::Unary(UnaryExpr {
+                op: UnaryOp::Minus,
+                operand,
+            }) => Self::constant_integer(operand.as_ref())?.checked_neg(),
+            HirExprKind::Name(entity) => match entity.deref().borrow().kind() {
+                EntityInfo::Variable(VariableInfo {
+                    mutable: false,
+                    global: true,
+                    default: Some(default),
+                    ..
+                }) => Self::constant_integer(default.as_ref()),
+                _ => None,
+            },
+            _ => None,
         }
     }
 }
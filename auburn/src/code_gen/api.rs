@@ -0,0 +1,165 @@
+use std::ops::Deref;
+
+use oxide::compiled::{ApiField, ApiFunction, ApiItem, ApiKind, ApiVariant, ModuleApi};
+
+use crate::analysis::{Entity, EntityInfo};
+use crate::ir::ast::Visibility;
+use crate::ir::hir::{HirFile, HirStmtKind};
+use crate::types::{Type, TypeKind};
+
+/// the declarations of a checked file, they are written to its module file so another version
+/// of the module can be compared against it.
+pub fn module_api(file: &HirFile) -> ModuleApi {
+    let items = file
+        .stmts()
+        .iter()
+        .filter_map(|stmt| match stmt.inner() {
+            HirStmtKind::Item(entity) => api_item(entity.deref().borrow().deref()),
+            _ => None,
+        })
+        .collect();
+    ModuleApi { items }
+}
+
+fn api_item(entity: &Entity) -> Option<ApiItem> {
+    let kind = match entity.kind() {
+        EntityInfo::Function(_) | EntityInfo::AssociatedFunction(_) => {
+            ApiKind::Function(api_function(entity.ty().as_ref()))
+        }
+        EntityInfo::Structure(info) => {
+            let fields = info
+                .fields
+                .elements()
+                .iter()
+                .map(|field| {
+                    let field = field.deref().borrow();
+                    ApiField {
+                        name: field.name().to_owned(),
+                        ty: field.ty().to_string(),
+                        public: field.visibility() == Visibility::Public,
+                    }
+                })
+                .collect();
+            let methods = info
+                .methods
+                .elements()
+                .iter()
+                .filter_map(|method| api_item(method.deref().borrow().deref()))
+                .collect();
+            ApiKind::Struct { fields, methods }
+        }
+        EntityInfo::Enumeration(info) => ApiKind::Enum {
+            variants: info
+                .variants
+                .iter()
+                .map(|variant| ApiVariant {
+                    name: variant.name.clone(),
+                    fields: variant.fields.iter().map(ToString::to_string).collect(),
+                })
+                .collect(),
+        },
+        EntityInfo::Variable(info) => ApiKind::Variable {
+            ty: entity.ty().to_string(),
+            mutable: info.mutable,
+        },
+        _ => return None,
+    };
+    Some(ApiItem {
+        name: entity.name().to_owned(),
+        public: entity.visibility() == Visibility::Public,
+        kind,
+    })
+}
+
+fn api_function(ty: &Type) -> ApiFunction {
+    match ty.kind() {
+        TypeKind::Function {
+            params,
+            return_type,
+        } => ApiFunction {
+            params: params.iter().map(ToString::to_string).collect(),
+            ret: return_type.to_string(),
+        },
+        _ => unreachable!("the type of a function is a function type"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::module_api;
+    use crate::analysis::Analysis;
+    use crate::code_gen::CodeGen;
+    use crate::syntax::Parser;
+    use crate::system::{File, FileMap};
+    use crate::LanguageMode;
+    use oxide::{compiled::ModuleApi, CompiledModule, Vm};
+
+    const BEFORE: &str = "pub struct Point {\n    pub x i64\n    pub y i64\n    \
+        pub fn len(self) i64 = self.x + self.y\n}\n\
+        pub enum Shape { Circle(i64), Square(i64), Dot }\n\
+        pub fn area(w i64) i64 = w * w\n\
+        pub fn perimeter(w i64) i64 = w + w\n\
+        pub fn volume(w i64) i64 = w * w\n\
+        pub let unit i64 = 1\n\
+        fn helper() i64 = 1\n\
+        fn main() i64 = area(2)\n";
+
+    const AFTER: &str = "pub struct Point {\n    pub y i64\n    pub x i64\n    pub z i64\n    \
+        pub fn len(self) i64 = self.x + self.y\n    pub fn scale(self) i64 = self.x\n}\n\
+        pub enum Shape { Circle(f64), Dot, Line }\n\
+        pub fn area(w i32) i64 = 1\n\
+        fn perimeter(w i64) i64 = w + w\n\
+        pub mut unit i64 = 1\n\
+        pub fn helper() i64 = 1\n\
+        fn main() i64 = area(2)\n";
+
+    // the api as it is read back from the module file.
+    fn compile(source: &str) -> ModuleApi {
+        let file = File::raw_test(source.to_string());
+        let mut parser = Parser::new(&file);
+        let parsed = parser.init().and_then(|_| parser.parse_file()).unwrap();
+        let hir_file = Analysis::new().check(parsed, LanguageMode::Default).unwrap();
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        let module = CodeGen::build(&FileMap::new(), &hir_file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        let compiled = CompiledModule::from_module(module)
+            .unwrap()
+            .with_api(module_api(&hir_file));
+        let bytes = compiled.to_bytes();
+        CompiledModule::from_bytes(&bytes).unwrap().api().unwrap().clone()
+    }
+
+    #[test]
+    fn test_diff_reports_each_kind_of_change() {
+        let before = compile(BEFORE);
+        let after = compile(AFTER);
+        assert!(before.diff(&before).changes.is_empty());
+
+        let diff = before.diff(&after);
+        assert!(diff.is_breaking());
+        assert_eq!(
+            diff.to_string(),
+            "breaking changes:\n\
+            \x20 field 'x' of 'Point' moved from position 0 to 1\n\
+            \x20 field 'y' of 'Point' moved from position 1 to 0\n\
+            \x20 variant 'Circle(i64)' of 'Shape' is now 'Circle(f64)'\n\
+            \x20 variant 'Square' of 'Shape' was removed\n\
+            \x20 'area' changed from '(i64) i64' to '(i32) i64'\n\
+            \x20 'perimeter' is no longer public\n\
+            \x20 'volume' was removed\n\
+            \x20 'unit' changed from 'let i64' to 'mut i64'\n\
+            non-breaking changes:\n\
+            \x20 public field 'z' of 'Point' was added\n\
+            \x20 'Point.scale' was added\n\
+            \x20 variant 'Line' of 'Shape' was added\n\
+            \x20 'helper' was added\n"
+        );
+
+        // what was removed is added going back.
+        let back = after.diff(&before);
+        assert!(back
+            .additive()
+            .any(|change| change.to_string() == "'volume' was added"));
+    }
+}
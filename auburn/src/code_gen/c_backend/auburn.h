@@ -0,0 +1,196 @@
+/* the runtime of a program translated to C by `aubc build --emit c`, it is copied to the top of
+ * every generated file so the file compiles on its own. Objects are never freed, a program
+ * relying on the collector to reclaim memory is not supported by the C backend. */
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+typedef const char *au_str;
+
+static inline void au_panic(const char *message) {
+    fflush(stdout);
+    fprintf(stderr, "runtime error: %s\n", message);
+    exit(1);
+}
+
+static inline void *au_alloc(size_t size) {
+    void *object = calloc(1, size ? size : 1);
+    if (!object) {
+        au_panic("out of memory");
+    }
+    return object;
+}
+
+/* integer arithmetic wraps like the interpreter, it is done in an unsigned type wide enough to
+ * not be promoted to int. */
+#define AU_INTEGER(name, T, U)                                                                   \
+    static inline T au_add_##name(T a, T b) { return (T)((U)a + (U)b); }                         \
+    static inline T au_sub_##name(T a, T b) { return (T)((U)a - (U)b); }                         \
+    static inline T au_mul_##name(T a, T b) { return (T)((U)a * (U)b); }                         \
+    static inline T au_neg_##name(T a) { return (T)((U)0 - (U)a); }                              \
+    static inline T au_div_##name(T a, T b) {                                                    \
+        if (b == 0) {                                                                            \
+            au_panic("division by zero");                                                        \
+        }                                                                                        \
+        /* the only quotient that overflows is the minimum divided by -1, it wraps to itself. */ \
+        if ((T)-1 < 0 && b == (T)-1) {                                                           \
+            return au_neg_##name(a);                                                             \
+        }                                                                                        \
+        return a / b;                                                                            \
+    }                                                                                            \
+    static inline T au_rem_##name(T a, T b) {                                                    \
+        if (b == 0) {                                                                            \
+            au_panic("division by zero");                                                        \
+        }                                                                                        \
+        /* the remainder of the minimum divided by -1 is zero, computing it would overflow. */   \
+        if ((T)-1 < 0 && b == (T)-1) {                                                           \
+            return 0;                                                                            \
+        }                                                                                        \
+        return a % b;                                                                            \
+    }                                                                                            \
+    /* the floored division and remainder round toward negative infinity. */                     \
+    static inline T au_div_floor_##name(T a, T b) {                                              \
+        T quotient = au_div_##name(a, b);                                                        \
+        T remainder = au_rem_##name(a, b);                                                       \
+        if (remainder != 0 && (remainder < 0) != (b < 0)) {                                      \
+            quotient = au_sub_##name(quotient, 1);                                               \
+        }                                                                                        \
+        return quotient;                                                                         \
+    }                                                                                            \
+    static inline T au_mod_floor_##name(T a, T b) {                                              \
+        T remainder = au_rem_##name(a, b);                                                       \
+        if (remainder != 0 && (remainder < 0) != (b < 0)) {                                      \
+            remainder = au_add_##name(remainder, b);                                             \
+        }                                                                                        \
+        return remainder;                                                                        \
+    }
+
+AU_INTEGER(i8, int8_t, unsigned int)
+AU_INTEGER(i16, int16_t, unsigned int)
+AU_INTEGER(i32, int32_t, uint32_t)
+AU_INTEGER(i64, int64_t, uint64_t)
+AU_INTEGER(u8, uint8_t, unsigned int)
+AU_INTEGER(u16, uint16_t, unsigned int)
+AU_INTEGER(u32, uint32_t, uint32_t)
+AU_INTEGER(u64, uint64_t, uint64_t)
+
+/* values are shown the way the interpreter echoes them. */
+static inline void au_show_signed(int64_t value) { printf("%lld", (long long)value); }
+static inline void au_show_unsigned(uint64_t value) { printf("%llu", (unsigned long long)value); }
+static inline void au_show_bool(bool value) { fputs(value ? "true" : "false", stdout); }
+static inline void au_show_unit(void) { fputs("()", stdout); }
+
+/* a float is shown with the fewest digits that read back as the same value, like the
+ * interpreter shows it. The ones with a decimal exponent outside of -4..16 are shown with one. */
+static inline void au_show_float(double value, int is_f32) {
+    if (isnan(value)) {
+        fputs("nan", stdout);
+        return;
+    }
+    if (isinf(value)) {
+        fputs(value < 0 ? "-inf" : "inf", stdout);
+        return;
+    }
+
+    char scientific[64];
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(scientific, sizeof(scientific), "%.*e", precision, value);
+        if (is_f32 ? strtof(scientific, NULL) == (float)value
+                   : strtod(scientific, NULL) == value) {
+            break;
+        }
+    }
+
+    /* split d.ddde[+-]x into its digits and exponent. */
+    char digits[64];
+    int count = 0;
+    const char *at = scientific;
+    if (*at == '-') {
+        fputc('-', stdout);
+        at++;
+    }
+    for (; *at && *at != 'e'; at++) {
+        if (*at != '.') {
+            digits[count++] = *at;
+        }
+    }
+    digits[count] = '\0';
+    int exponent = atoi(at + 1);
+
+    if (exponent < -4 || exponent >= 16) {
+        fputc(digits[0], stdout);
+        if (count > 1) {
+            fputc('.', stdout);
+            fputs(digits + 1, stdout);
+        }
+        printf("e%d", exponent);
+    } else if (exponent < 0) {
+        fputs("0.", stdout);
+        for (int i = 0; i < -exponent - 1; i++) {
+            fputc('0', stdout);
+        }
+        fputs(digits, stdout);
+    } else if (count <= exponent + 1) {
+        fputs(digits, stdout);
+        for (int i = count; i < exponent + 1; i++) {
+            fputc('0', stdout);
+        }
+        fputs(".0", stdout);
+    } else {
+        fwrite(digits, 1, (size_t)exponent + 1, stdout);
+        fputc('.', stdout);
+        fputs(digits + exponent + 1, stdout);
+    }
+}
+
+/* a string within another value is quoted. */
+static inline void au_show_str(au_str value) {
+    fputc('"', stdout);
+    for (const unsigned char *at = (const unsigned char *)value; *at; at++) {
+        switch (*at) {
+        case '"': fputs("\\\"", stdout); break;
+        case '\\': fputs("\\\\", stdout); break;
+        case '\n': fputs("\\n", stdout); break;
+        case '\r': fputs("\\r", stdout); break;
+        case '\t': fputs("\\t", stdout); break;
+        case '\0': fputs("\\0", stdout); break;
+        default:
+            if (*at < 0x20 || *at == 0x7F) {
+                printf("\\u{%x}", *at);
+            } else {
+                fputc(*at, stdout);
+            }
+        }
+    }
+    fputc('"', stdout);
+}
+
+/* instances being shown, an instance reached again is shown as a cycle. */
+#define AU_MAX_DEPTH 16
+static const void *au_parents[AU_MAX_DEPTH];
+static int au_depth = 0;
+
+/* shows the start of an instance, false when its fields are elided. */
+static inline bool au_enter(const void *object, const char *open, const char *close) {
+    for (int i = 0; i < au_depth; i++) {
+        if (au_parents[i] == object) {
+            printf("%s<cycle>%s", open, close);
+            return false;
+        }
+    }
+    if (au_depth >= AU_MAX_DEPTH) {
+        printf("%s...%s", open, close);
+        return false;
+    }
+    au_parents[au_depth++] = object;
+    fputs(open, stdout);
+    return true;
+}
+
+static inline void au_leave(const char *close) {
+    au_depth--;
+    fputs(close, stdout);
+}
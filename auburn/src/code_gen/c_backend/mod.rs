@@ -0,0 +1,1138 @@
+use std::fmt::Write;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use itertools::Itertools;
+
+use crate::analysis::{Entity, EntityInfo, EntityRef};
+use crate::error::Error;
+use crate::ir::ast::{AssignmentOp, BinaryOp, UnaryOp};
+use crate::ir::hir::{
+    AssociatedFunctionExpr, Assignment, Desugaring, HirExpr, HirExprKind, HirFile, HirStmt,
+    HirStmtKind, IfExprBranch, MethodExpr, MirNode,
+};
+use crate::syntax::{FilePos, Position, Span};
+use crate::system::FileId;
+use crate::types::{Type, TypeKind};
+
+/// the runtime every generated file starts with.
+const RUNTIME: &str = include_str!("auburn.h");
+
+/// translates a checked program to a single C file. Constructs the C backend can not translate
+/// are all reported, along with where they are, instead of emitting code that does not work.
+pub fn emit_c(file: &HirFile) -> Result<String, Vec<Error>> {
+    let entry = match file.get_entry() {
+        Some(entry) => entry,
+        None => return Err(vec![Error::entry_not_found("main".to_owned())]),
+    };
+
+    let mut backend = CBackend {
+        file_id: file.id(),
+        lines: vec![],
+        temps: 0,
+        return_type: None,
+        errors: vec![],
+    };
+
+    let mut structs = vec![];
+    let mut functions = vec![];
+    let mut globals = vec![];
+    for stmt in file.stmts() {
+        match stmt.inner() {
+            HirStmtKind::Item(entity) => match entity.deref().borrow().kind() {
+                EntityInfo::Structure(info) => {
+                    structs.push(entity.clone());
+                    functions.extend(info.methods.elements().iter().cloned());
+                }
+                EntityInfo::Function(_) => functions.push(entity.clone()),
+                EntityInfo::Variable(_) => globals.push(entity.clone()),
+                // an enum that is never used needs no code, a use of one is reported.
+                _ => {}
+            },
+            _ => backend.unsupported("a statement outside of a function", stmt.position()),
+        }
+    }
+
+    let mut out = String::from(RUNTIME);
+    out.push('\n');
+    for structure in &structs {
+        let name = struct_name(&structure.deref().borrow());
+        writeln!(out, "typedef struct {0} {0};", name).unwrap();
+    }
+    for structure in &structs {
+        out.push_str(&backend.struct_definition(&structure.deref().borrow()));
+    }
+    for structure in &structs {
+        let name = struct_name(&structure.deref().borrow());
+        writeln!(out, "static void {}({} *value);", show_name(&name), name).unwrap();
+    }
+    let signatures = functions
+        .iter()
+        .map(|function| backend.signature(&function.deref().borrow()))
+        .collect_vec();
+    for signature in &signatures {
+        writeln!(out, "static {};", signature).unwrap();
+    }
+    for global in &globals {
+        let global = global.deref().borrow();
+        let ty = backend.c_type(&global.ty(), backend.file_position());
+        writeln!(out, "static {} {};", ty, global_name(global.name())).unwrap();
+    }
+    out.push('\n');
+
+    for structure in &structs {
+        out.push_str(&backend.struct_show(&structure.deref().borrow()));
+    }
+    for (function, signature) in functions.iter().zip(&signatures) {
+        out.push_str(&backend.function(&function.deref().borrow(), signature));
+    }
+    out.push_str(&backend.init(&globals));
+
+    writeln!(
+        out,
+        "int main(void) {{\n    au_init();\n    {}();\n    return 0;\n}}",
+        function_name(&entry.deref().borrow())
+    )
+    .unwrap();
+
+    if backend.errors.is_empty() {
+        Ok(out)
+    } else {
+        Err(backend.errors)
+    }
+}
+
+/// the value of an expression, a C expression without side effects. None when it is unit.
+type CValue = Option<String>;
+
+struct CBackend {
+    file_id: FileId,
+    /// the body of the function being written.
+    lines: Vec<String>,
+    /// temporaries made in the function being written.
+    temps: usize,
+    /// none when the function being written returns unit.
+    return_type: Option<Rc<Type>>,
+    errors: Vec<Error>,
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect()
+}
+
+fn struct_name(entity: &Entity) -> String {
+    format!("au_s_{}", sanitize(entity.name()))
+}
+
+fn show_name(struct_name: &str) -> String {
+    format!("au_show_{}", struct_name)
+}
+
+fn global_name(name: &str) -> String {
+    format!("au_g_{}", sanitize(name))
+}
+
+/// a parameter or local, the id tells apart locals of the same name.
+fn local_name(entity: &Entity) -> String {
+    format!("l_{}_{}", sanitize(entity.name()), entity.id().0)
+}
+
+fn field_name(name: &str) -> String {
+    format!("f_{}", sanitize(name))
+}
+
+fn function_name(entity: &Entity) -> String {
+    match entity.kind() {
+        EntityInfo::AssociatedFunction(info) => format!(
+            "au_m_{}_{}",
+            sanitize(info.entity.deref().borrow().name()),
+            sanitize(entity.name())
+        ),
+        _ => format!("au_f_{}", sanitize(entity.name())),
+    }
+}
+
+/// what the structure is when its methods are those of a built in type.
+fn builtin_type(structure: &EntityRef) -> Option<&'static str> {
+    match structure.deref().borrow().ty().kind() {
+        TypeKind::Vector { .. } => Some("a vector"),
+        TypeKind::Map { .. } => Some("a map"),
+        TypeKind::Weak { .. } => Some("a weak reference"),
+        _ => None,
+    }
+}
+
+/// the fields of a structure in the order they are stored.
+fn fields(structure: &Entity) -> Vec<EntityRef> {
+    structure
+        .as_struct()
+        .fields
+        .elements()
+        .iter()
+        .cloned()
+        .sorted_by_key(|field| field.deref().borrow().as_local().index)
+        .collect()
+}
+
+fn string_literal(value: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in value.bytes() {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            0x20..=0x7e => out.push(byte as char),
+            _ => write!(out, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// the suffix of the runtime functions of an integer type.
+fn integer_suffix(ty: &Type) -> &'static str {
+    match ty.kind() {
+        TypeKind::I8 => "i8",
+        TypeKind::I16 => "i16",
+        TypeKind::I32 | TypeKind::Integer => "i32",
+        TypeKind::I64 => "i64",
+        TypeKind::U8 => "u8",
+        TypeKind::U16 => "u16",
+        TypeKind::U32 => "u32",
+        TypeKind::U64 => "u64",
+        _ => unreachable!("'{}' is not an integer type", ty),
+    }
+}
+
+/// a value that reads no local, global or field keeps its value when code runs after it is
+/// read. Temporaries are never assigned once they are read.
+fn is_stable(value: &str) -> bool {
+    value.starts_with('"')
+        || !["l_", "au_g_", "self", "->"]
+            .iter()
+            .any(|mutable| value.contains(mutable))
+}
+
+impl CBackend {
+    fn file_position(&self) -> Position {
+        Position::new(Span::default(), FilePos::default(), self.file_id)
+    }
+
+    fn unsupported(&mut self, construct: &str, position: Position) {
+        self.errors
+            .push(Error::not_supported_by_c_backend(construct).with_position(position));
+    }
+
+    fn line(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn temp(&mut self, ty: &str, value: &str) -> String {
+        self.temps += 1;
+        let name = format!("t_{}", self.temps);
+        self.line(format!("{} {} = {};", ty, name, value));
+        name
+    }
+
+    /// the C type of a value, none for unit.
+    fn value_type(&mut self, ty: &Type, position: Position) -> Option<String> {
+        let name = match ty.kind() {
+            TypeKind::Unit => return None,
+            TypeKind::I8 => "int8_t".to_owned(),
+            TypeKind::I16 => "int16_t".to_owned(),
+            TypeKind::I32 | TypeKind::Integer => "int32_t".to_owned(),
+            TypeKind::I64 => "int64_t".to_owned(),
+            TypeKind::U8 => "uint8_t".to_owned(),
+            TypeKind::U16 => "uint16_t".to_owned(),
+            TypeKind::U32 => "uint32_t".to_owned(),
+            TypeKind::U64 => "uint64_t".to_owned(),
+            TypeKind::F32 | TypeKind::Float => "float".to_owned(),
+            TypeKind::F64 => "double".to_owned(),
+            TypeKind::Bool => "bool".to_owned(),
+            // the checker gives string literals the char type, so both are strings here.
+            TypeKind::Char | TypeKind::String => "au_str".to_owned(),
+            TypeKind::Mutable { inner } => return self.value_type(inner, position),
+            TypeKind::Struct { entity } => format!("{} *", struct_name(&entity.deref().borrow())),
+            _ => {
+                self.unsupported(&format!("the type '{}'", ty), position);
+                "int".to_owned()
+            }
+        };
+        Some(name)
+    }
+
+    /// the C type of a value, void for unit.
+    fn c_type(&mut self, ty: &Type, position: Position) -> String {
+        self.value_type(ty, position)
+            .unwrap_or_else(|| "void".to_owned())
+    }
+
+    fn struct_definition(&mut self, structure: &Entity) -> String {
+        let mut out = format!("struct {} {{\n", struct_name(structure));
+        for field in fields(structure) {
+            let field = field.deref().borrow();
+            let position = field
+                .as_local()
+                .spec
+                .as_ref()
+                .map(|spec| spec.position())
+                .unwrap_or_else(|| self.file_position());
+            let ty = self.c_type(&field.ty(), position);
+            writeln!(out, "    {} {};", ty, field_name(field.name())).unwrap();
+        }
+        // C does not allow a struct without members.
+        if structure.as_struct().fields.len() == 0 {
+            out.push_str("    char empty;\n");
+        }
+        out.push_str("};\n");
+        out
+    }
+
+    /// shows an instance the way the interpreter renders it.
+    fn struct_show(&mut self, structure: &Entity) -> String {
+        let name = struct_name(structure);
+        let open = string_literal(&format!("{} {{ ", structure.name()));
+        let mut out = format!(
+            "static void {}({} *value) {{\n    if (au_enter(value, {}, \" }}\")) {{\n",
+            show_name(&name),
+            name,
+            open
+        );
+        for (idx, field) in fields(structure).iter().enumerate() {
+            let field = field.deref().borrow();
+            if idx != 0 {
+                out.push_str("        fputs(\", \", stdout);\n");
+            }
+            let value = format!("value->{}", field_name(field.name()));
+            writeln!(
+                out,
+                "        {}",
+                self.show(Some(value), &field.ty(), false)
+            )
+            .unwrap();
+        }
+        out.push_str("        au_leave(\" }\");\n    }\n}\n\n");
+        out
+    }
+
+    /// the statement showing a value, a string is only quoted when it is within another value.
+    fn show(&mut self, value: CValue, ty: &Type, top_level: bool) -> String {
+        let value = match value {
+            Some(value) => value,
+            None => return "au_show_unit();".to_owned(),
+        };
+        match ty.kind() {
+            TypeKind::Mutable { inner } => self.show(Some(value), inner, top_level),
+            TypeKind::Char | TypeKind::String if top_level => {
+                format!("fputs({}, stdout);", value)
+            }
+            TypeKind::Char | TypeKind::String => format!("au_show_str({});", value),
+            TypeKind::Bool => format!("au_show_bool({});", value),
+            TypeKind::F32 | TypeKind::Float => format!("au_show_float({}, 1);", value),
+            TypeKind::F64 => format!("au_show_float({}, 0);", value),
+            TypeKind::Struct { entity } => format!(
+                "{}({});",
+                show_name(&struct_name(&entity.deref().borrow())),
+                value
+            ),
+            kind if kind.is_signed() && kind.is_integer() => {
+                format!("au_show_signed({});", value)
+            }
+            kind if kind.is_integer() => format!("au_show_unsigned({});", value),
+            // the type was already reported.
+            _ => "au_show_unit();".to_owned(),
+        }
+    }
+
+    fn signature(&mut self, function: &Entity) -> String {
+        let (params, body) = match function.kind() {
+            EntityInfo::Function(info) => (info.params.clone(), info.body.clone()),
+            EntityInfo::AssociatedFunction(info) => (info.params.clone(), info.body.clone()),
+            _ => unreachable!("only functions have a signature"),
+        };
+        let return_type = match function.ty().kind() {
+            TypeKind::Function { return_type, .. } => return_type.clone(),
+            _ => unreachable!("the type of a function is a function type"),
+        };
+
+        let ret = self.c_type(&return_type, body.position());
+        let params = params
+            .elements()
+            .iter()
+            .map(|param| {
+                let param = param.deref().borrow();
+                match param.kind() {
+                    EntityInfo::SelfParam { .. } => {
+                        format!("{} self", self.c_type(&param.ty(), body.position()))
+                    }
+                    _ => {
+                        let position = param
+                            .as_local()
+                            .spec
+                            .as_ref()
+                            .map(|spec| spec.position())
+                            .unwrap_or_else(|| body.position());
+                        format!(
+                            "{} {}",
+                            self.c_type(&param.ty(), position),
+                            local_name(&param)
+                        )
+                    }
+                }
+            })
+            .collect_vec();
+        let params = if params.is_empty() {
+            "void".to_owned()
+        } else {
+            params.join(", ")
+        };
+        format!("{} {}({})", ret, function_name(function), params)
+    }
+
+    fn function(&mut self, function: &Entity, signature: &str) -> String {
+        let body = match function.kind() {
+            EntityInfo::Function(info) => info.body.clone(),
+            EntityInfo::AssociatedFunction(info) => info.body.clone(),
+            _ => unreachable!("only functions have a body"),
+        };
+        self.return_type = match function.ty().kind() {
+            TypeKind::Function { return_type, .. }
+                if !Type::inner(return_type.clone()).kind().eq(&TypeKind::Unit) =>
+            {
+                Some(return_type.clone())
+            }
+            _ => None,
+        };
+        self.temps = 0;
+
+        let value = self.expr(body.as_ref());
+        if let (Some(value), Some(_)) = (value, self.return_type.as_ref()) {
+            self.line(format!("return {};", value));
+        }
+
+        let body = self.take_lines(1);
+        format!("static {} {{\n{}}}\n\n", signature, body)
+    }
+
+    /// globals are set in the order they are declared before the entry is called.
+    fn init(&mut self, globals: &[EntityRef]) -> String {
+        self.return_type = None;
+        for global in globals {
+            let global = global.deref().borrow();
+            if let EntityInfo::Variable(info) = global.kind() {
+                if let Some(default) = info.default.as_ref() {
+                    if let Some(value) = self.expr(default.as_ref()) {
+                        self.line(format!("{} = {};", global_name(global.name()), value));
+                    }
+                }
+            }
+        }
+        format!("static void au_init(void) {{\n{}}}\n\n", self.take_lines(1))
+    }
+
+    /// the lines written so far, indented by the braces they are within.
+    fn take_lines(&mut self, base: usize) -> String {
+        let mut depth = base;
+        let mut out = String::new();
+        for line in self.lines.drain(..) {
+            if line.starts_with('}') {
+                depth -= 1;
+            }
+            writeln!(out, "{}{}", "    ".repeat(depth), line).unwrap();
+            if line.ends_with('{') {
+                depth += 1;
+            }
+        }
+        out
+    }
+
+    /// evaluates the expressions in order. A value read before code is written for a later one
+    /// is kept in a temporary, so it is the value from before that code ran.
+    fn operands(&mut self, exprs: &[&HirExpr]) -> Vec<CValue> {
+        self.operands_after(exprs, vec![])
+    }
+
+    /// evaluates the expressions after those whose values are given, like `operands`.
+    fn operands_after(&mut self, exprs: &[&HirExpr], mut values: Vec<CValue>) -> Vec<CValue> {
+        for expr in &exprs[values.len()..] {
+            let start = self.lines.len();
+            let value = self.expr(expr);
+            if self.lines.len() > start {
+                let mut saved = vec![];
+                for (idx, earlier) in values.iter_mut().enumerate() {
+                    if let Some(earlier_value) = earlier.as_ref().filter(|v| !is_stable(v)) {
+                        let ty = self
+                            .value_type(&exprs[idx].ty(), exprs[idx].position())
+                            .unwrap_or_default();
+                        self.temps += 1;
+                        let name = format!("t_{}", self.temps);
+                        saved.push(format!("{} {} = {};", ty, name, earlier_value));
+                        *earlier = Some(name);
+                    }
+                }
+                for (offset, line) in saved.into_iter().enumerate() {
+                    self.lines.insert(start + offset, line);
+                }
+            }
+            values.push(value);
+        }
+        values
+    }
+
+    fn stmt(&mut self, stmt: &HirStmt) {
+        match stmt.inner() {
+            HirStmtKind::Expr(expr) => {
+                if let Some(value) = self.expr(expr.as_ref()) {
+                    if !value.starts_with("t_") {
+                        self.line(format!("(void)({});", value));
+                    }
+                }
+            }
+            HirStmtKind::Item(entity) => {
+                let entity = entity.deref().borrow();
+                match entity.kind() {
+                    EntityInfo::Variable(info) => {
+                        let value = info
+                            .default
+                            .as_ref()
+                            .and_then(|init| self.expr(init.as_ref()));
+                        if let Some(ty) = self.value_type(&entity.ty(), stmt.position()) {
+                            let value = value.unwrap_or_else(|| "0".to_owned());
+                            self.line(format!("{} {} = {};", ty, local_name(&entity), value));
+                        }
+                    }
+                    _ => self
+                        .unsupported(&format!("a local {}", entity.type_name()), stmt.position()),
+                }
+            }
+            HirStmtKind::Assignment(assignment) => self.assignment(assignment, stmt.position()),
+            HirStmtKind::Echo(expr) => {
+                let value = self.expr(expr.as_ref());
+                let show = self.show(value, &expr.ty(), true);
+                self.line(show);
+                self.line("fputc('\\n', stdout);".to_owned());
+            }
+        }
+    }
+
+    fn assignment(&mut self, assignment: &Assignment, position: Position) {
+        let (target, current) = match assignment.lvalue.inner().kind() {
+            HirExprKind::Name(entity) => {
+                let entity = entity.deref().borrow();
+                let name = match entity.kind() {
+                    EntityInfo::Variable(info) if info.global => global_name(entity.name()),
+                    _ => local_name(&entity),
+                };
+                (name.clone(), name)
+            }
+            HirExprKind::SelfLit(..) => ("self".to_owned(), "self".to_owned()),
+            HirExprKind::Field(field_expr) => {
+                // the instance is evaluated before the value stored in it.
+                let operand = self.expr(field_expr.operand.as_ref()).unwrap_or_default();
+                let operand = if is_stable(&operand) || operand == "self" {
+                    operand
+                } else {
+                    let ty = self
+                        .value_type(&field_expr.operand.ty(), position)
+                        .unwrap_or_default();
+                    self.temp(&ty, &operand)
+                };
+                let field = format!(
+                    "{}->{}",
+                    operand,
+                    field_name(field_expr.field.deref().borrow().name())
+                );
+                (field.clone(), field)
+            }
+            _ => {
+                self.unsupported("assigning to this expression", position);
+                return;
+            }
+        };
+
+        let value = self.expr(assignment.rhs.as_ref());
+        let value = match (assignment.op, assignment.op.get_binary_op(), value) {
+            (AssignmentOp::Assign, _, value) => value,
+            (_, Some(op), Some(value)) => {
+                Some(self.binary(op, &assignment.lvalue.ty(), current, value, position))
+            }
+            (_, _, value) => value,
+        };
+        if let Some(value) = value {
+            self.line(format!("{} = {};", target, value));
+        }
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        ty: &Type,
+        left: String,
+        right: String,
+        position: Position,
+    ) -> String {
+        let ty = Type::inner(Rc::new(ty.clone()));
+        match op {
+            BinaryOp::Plus
+            | BinaryOp::Minus
+            | BinaryOp::Astrick
+            | BinaryOp::Slash
+            | BinaryOp::Percent
+            | BinaryOp::DivFloor
+            | BinaryOp::ModFloor
+                if ty.is_integer() =>
+            {
+                let name = match op {
+                    BinaryOp::Plus => "add",
+                    BinaryOp::Minus => "sub",
+                    BinaryOp::Astrick => "mul",
+                    BinaryOp::Percent => "rem",
+                    BinaryOp::DivFloor => "div_floor",
+                    BinaryOp::ModFloor => "mod_floor",
+                    _ => "div",
+                };
+                format!("au_{}_{}({}, {})", name, integer_suffix(&ty), left, right)
+            }
+            BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Astrick | BinaryOp::Slash
+                if ty.is_float() =>
+            {
+                format!("({} {} {})", left, op, right)
+            }
+            BinaryOp::Less
+            | BinaryOp::Greater
+            | BinaryOp::LessEq
+            | BinaryOp::GreaterEq
+            | BinaryOp::EqualEqual
+            | BinaryOp::BangEqual
+                if !ty.is_string() && !ty.is_char() && !ty.is_struct() =>
+            {
+                format!("({} {} {})", left, op, right)
+            }
+            BinaryOp::Ampersand | BinaryOp::Pipe | BinaryOp::Caret if ty.is_integer() => {
+                let c_type = self.c_type(&ty, position);
+                format!("(({})({} {} {}))", c_type, left, op, right)
+            }
+            _ => {
+                self.unsupported(&format!("the operator '{}' on '{}'", op, ty), position);
+                "0".to_owned()
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &HirExpr) -> CValue {
+        let position = expr.position();
+        match expr.generated_by() {
+            Some(Desugaring::ForLoop) => {
+                self.unsupported("a for loop", position);
+                return Some("0".to_owned());
+            }
+            Some(Desugaring::WhileLet) => {
+                self.unsupported("a while let loop", position);
+                return Some("0".to_owned());
+            }
+            _ => {}
+        }
+
+        let ty = expr.ty();
+        match expr.inner().kind() {
+            HirExprKind::Integer(val) => {
+                let literal = match Type::inner(ty.clone()).kind() {
+                    TypeKind::I64 if *val == i64::MIN => "INT64_MIN".to_owned(),
+                    TypeKind::I64 => format!("INT64_C({})", val),
+                    TypeKind::U64 => format!("UINT64_C({})", *val as u64),
+                    _ => format!("({}){}", self.c_type(&ty, position), val),
+                };
+                Some(literal)
+            }
+            HirExprKind::Float(val) => {
+                let literal = format!("{:?}", val.into_inner());
+                match Type::inner(ty).kind() {
+                    TypeKind::F64 => Some(format!("({})", literal)),
+                    _ => Some(format!("({}f)", literal)),
+                }
+            }
+            HirExprKind::String(val) => Some(string_literal(val)),
+            HirExprKind::Char(ch) => Some(string_literal(&ch.to_string())),
+            HirExprKind::Bool(val) => Some(val.to_string()),
+            HirExprKind::Name(entity) => {
+                let entity = entity.deref().borrow();
+                match entity.kind() {
+                    EntityInfo::Variable(info) if info.global => Some(global_name(entity.name())),
+                    EntityInfo::Variable(_) | EntityInfo::Param(_) => Some(local_name(&entity)),
+                    _ => {
+                        self.unsupported(
+                            &format!(
+                                "the {} '{}' used as a value",
+                                entity.type_name(),
+                                entity.name()
+                            ),
+                            position,
+                        );
+                        Some("0".to_owned())
+                    }
+                }
+            }
+            HirExprKind::SelfLit(..) => Some("self".to_owned()),
+            HirExprKind::Binary(binary) => {
+                // the left operands of a chain are evaluated in a loop, from the first operand.
+                let mut chain = vec![(binary, position)];
+                let mut first = binary.left.as_ref();
+                while let HirExprKind::Binary(binary) = first.inner().kind() {
+                    chain.push((binary, first.position()));
+                    first = binary.left.as_ref();
+                }
+                let mut value = self.expr(first);
+                for (binary, position) in chain.into_iter().rev() {
+                    let exprs = [binary.left.as_ref(), binary.right.as_ref()];
+                    let (left, right) = self
+                        .operands_after(&exprs, vec![value])
+                        .into_iter()
+                        .map(Option::unwrap_or_default)
+                        .collect_tuple()
+                        .unwrap();
+                    // comparisons are made in the type of the operands.
+                    value = Some(self.binary(binary.op, &binary.left.ty(), left, right, position));
+                }
+                value
+            }
+            HirExprKind::Unary(unary) => {
+                let operand_type = Type::inner(unary.operand.ty());
+                let value = self.expr(unary.operand.as_ref()).unwrap_or_default();
+                match unary.op {
+                    UnaryOp::Minus if operand_type.is_integer() => Some(format!(
+                        "au_neg_{}({})",
+                        integer_suffix(&operand_type),
+                        value
+                    )),
+                    UnaryOp::Minus if operand_type.is_float() => Some(format!("(-{})", value)),
+                    UnaryOp::Bang if operand_type.is_bool() => Some(format!("(!{})", value)),
+                    UnaryOp::Bang if operand_type.is_integer() && !operand_type.is_enum() => {
+                        let c_type = self.c_type(&operand_type, position);
+                        Some(format!("(({})~{})", c_type, value))
+                    }
+                    op => {
+                        self.unsupported(
+                            &format!("the operator '{}' on '{}'", op, operand_type),
+                            position,
+                        );
+                        Some("0".to_owned())
+                    }
+                }
+            }
+            HirExprKind::Field(field_expr) => {
+                let operand = self.expr(field_expr.operand.as_ref()).unwrap_or_default();
+                Some(format!(
+                    "{}->{}",
+                    operand,
+                    field_name(field_expr.field.deref().borrow().name())
+                ))
+            }
+            HirExprKind::Call(call) => {
+                let function = match call.operand.inner().kind() {
+                    HirExprKind::Name(entity) if entity.deref().borrow().is_function() => {
+                        function_name(&entity.deref().borrow())
+                    }
+                    _ => {
+                        self.unsupported("calling a function value", position);
+                        return Some("0".to_owned());
+                    }
+                };
+                let actuals = call.actuals.iter().map(Rc::as_ref).collect_vec();
+                self.call(function, &actuals, &ty, position)
+            }
+            HirExprKind::Method(MethodExpr { struct_entity, .. })
+            | HirExprKind::AssociatedFunction(AssociatedFunctionExpr { struct_entity, .. })
+                if builtin_type(struct_entity).is_some() =>
+            {
+                let what = builtin_type(struct_entity).expect("a built in type");
+                self.unsupported_value(what, position)
+            }
+            HirExprKind::Method(method) => {
+                let function = self.method_name(&method.struct_entity, &method.name);
+                let actuals = method.actuals.iter().map(Rc::as_ref).collect_vec();
+                self.call(function, &actuals, &ty, position)
+            }
+            HirExprKind::AssociatedFunction(associated) => {
+                let function = self.method_name(&associated.struct_entity, &associated.name);
+                // the first actual is the structure itself.
+                let actuals = associated
+                    .actuals
+                    .iter()
+                    .skip(1)
+                    .map(Rc::as_ref)
+                    .collect_vec();
+                self.call(function, &actuals, &ty, position)
+            }
+            HirExprKind::Cast(cast) => {
+                let operand_type = Type::inner(cast.operand.ty());
+                let target_type = Type::inner(ty.clone());
+                let value = self.expr(cast.operand.as_ref()).unwrap_or_default();
+                let integer_like = |ty: &Type| (ty.is_integer() && !ty.is_enum()) || ty.is_bool();
+                if target_type.is_bool() && operand_type.is_integer() {
+                    Some(format!("({} != 0)", value))
+                } else if integer_like(&operand_type) && integer_like(&target_type) {
+                    Some(format!(
+                        "(({}){})",
+                        self.c_type(&target_type, position),
+                        value
+                    ))
+                } else {
+                    self.unsupported(
+                        &format!("a cast from '{}' to '{}'", operand_type, target_type),
+                        position,
+                    );
+                    Some("0".to_owned())
+                }
+            }
+            HirExprKind::Block(block) => {
+                let mut value = None;
+                for (idx, stmt) in block.stmts.iter().enumerate() {
+                    match stmt.inner() {
+                        // the last expression is the value of the block.
+                        HirStmtKind::Expr(expr) if idx + 1 == block.stmts.len() => {
+                            value = self.expr(expr.as_ref())
+                        }
+                        _ => self.stmt(stmt.as_ref()),
+                    }
+                }
+                if Type::inner(ty).kind() == &TypeKind::Unit {
+                    if let Some(value) = value.filter(|value| !value.starts_with("t_")) {
+                        self.line(format!("(void)({});", value));
+                    }
+                    None
+                } else {
+                    value
+                }
+            }
+            HirExprKind::If(if_expr) => {
+                let result = self
+                    .value_type(&ty, position)
+                    .map(|c_type| self.temp(&c_type, "0"));
+                self.branches(&if_expr.branches, result.as_deref());
+                result
+            }
+            HirExprKind::Loop(loop_expr) => {
+                self.line("for (;;) {".to_owned());
+                self.discard(loop_expr.body.as_ref());
+                self.line("}".to_owned());
+                None
+            }
+            HirExprKind::While(while_expr) => {
+                self.line("for (;;) {".to_owned());
+                let cond = self.expr(while_expr.cond.as_ref()).unwrap_or_default();
+                self.line(format!("if (!{}) {{", cond));
+                self.line("break;".to_owned());
+                self.line("}".to_owned());
+                self.discard(while_expr.body.as_ref());
+                self.line("}".to_owned());
+                None
+            }
+            HirExprKind::StructExpr(struct_expr) => {
+                let entity = match struct_expr.struct_type.kind() {
+                    TypeKind::Struct { entity } => entity.clone(),
+                    _ => unreachable!("a struct expression has a struct type"),
+                };
+                let structure = entity.deref().borrow();
+                // the c runtime never frees an instance, there is nothing to finalize it.
+                if structure.as_struct().finalized {
+                    self.unsupported("a struct declared with '@finalize'", position);
+                }
+                let exprs = struct_expr
+                    .fields
+                    .iter()
+                    .map(|(_, field)| field.as_ref())
+                    .collect_vec();
+                let values = self.operands(&exprs);
+                let name = struct_name(&structure);
+                let instance = self.temp(
+                    &format!("{} *", name),
+                    &format!("au_alloc(sizeof({}))", name),
+                );
+                let fields = fields(&structure);
+                for ((idx, _), value) in struct_expr.fields.iter().zip(values) {
+                    if let Some(value) = value {
+                        let field = fields[*idx].deref().borrow();
+                        self.line(format!(
+                            "{}->{} = {};",
+                            instance,
+                            field_name(field.name()),
+                            value
+                        ));
+                    }
+                }
+                Some(instance)
+            }
+            HirExprKind::Break => {
+                self.line("break;".to_owned());
+                None
+            }
+            HirExprKind::Continue => {
+                self.line("continue;".to_owned());
+                None
+            }
+            HirExprKind::Return(value) => {
+                let value = self.expr(value.as_ref());
+                match (value, self.return_type.is_some()) {
+                    (Some(value), true) => self.line(format!("return {};", value)),
+                    _ => self.line("return;".to_owned()),
+                }
+                None
+            }
+            HirExprKind::Index(_) => self.unsupported_value("indexing", position),
+            HirExprKind::FieldAccess(_) => self.unsupported_value("a field access", position),
+            HirExprKind::Log(_) => self.unsupported_value("logging", position),
+            HirExprKind::Variant(_) => self.unsupported_value("an enum variant", position),
+            HirExprKind::EnumFunction(_) => {
+                self.unsupported_value("a function of an enum", position)
+            }
+            HirExprKind::Task(_) => self.unsupported_value("a task", position),
+            HirExprKind::GetField(_) => self.unsupported_value("reflection", position),
+            HirExprKind::Lambda(_) => self.unsupported_value("a closure", position),
+            HirExprKind::BlackBox(value) => self.expr(value.as_ref()),
+            HirExprKind::Match(_) => self.unsupported_value("a match", position),
+            HirExprKind::Trap(..) | HirExprKind::Unreachable(_) => {
+                self.unsupported_value("a trap", position)
+            }
+            HirExprKind::OptionalChain(_) | HirExprKind::ChainReceiver(_) => {
+                self.unsupported_value("an optional chain", position)
+            }
+            HirExprKind::Coalesce(_) => self.unsupported_value("'??'", position),
+            HirExprKind::NoneLit => self.unsupported_value("none", position),
+            HirExprKind::Host => self.unsupported_value("'sys.host()'", position),
+            HirExprKind::GcCollect => self.unsupported_value("'gc_collect()'", position),
+            HirExprKind::Eval(_) => self.unsupported_value("'eval'", position),
+            HirExprKind::Tuple(_) | HirExprKind::TupleIndex(_) => {
+                self.unsupported_value("a tuple", position)
+            }
+            HirExprKind::Array(_) => self.unsupported_value("an array", position),
+        }
+    }
+
+    fn unsupported_value(&mut self, construct: &str, position: Position) -> CValue {
+        self.unsupported(construct, position);
+        Some("0".to_owned())
+    }
+
+    /// evaluates an expression for its effects.
+    fn discard(&mut self, expr: &HirExpr) {
+        if let Some(value) = self.expr(expr).filter(|value| !value.starts_with("t_")) {
+            self.line(format!("(void)({});", value));
+        }
+    }
+
+    fn method_name(&self, structure: &EntityRef, name: &str) -> String {
+        let structure = structure.deref().borrow();
+        let method = structure
+            .as_struct()
+            .methods
+            .get(name)
+            .cloned()
+            .expect("a method call is resolved to a method of the structure");
+        let method = method.deref().borrow();
+        function_name(&method)
+    }
+
+    /// the result of a call is kept in a temporary, so the call is made where it is written.
+    fn call(
+        &mut self,
+        function: String,
+        actuals: &[&HirExpr],
+        ty: &Type,
+        position: Position,
+    ) -> CValue {
+        let values = self.operands(actuals);
+        let call = format!(
+            "{}({})",
+            function,
+            values.into_iter().map(Option::unwrap_or_default).join(", ")
+        );
+        match self.value_type(ty, position) {
+            Some(c_type) => Some(self.temp(&c_type, &call)),
+            None => {
+                self.line(format!("{};", call));
+                None
+            }
+        }
+    }
+
+    /// each condition is evaluated in the else of the ones before it.
+    fn branches(&mut self, branches: &[IfExprBranch], result: Option<&str>) {
+        let (branch, rest) = match branches.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+        match branch {
+            IfExprBranch::Conditional { cond, body, .. } => {
+                let cond = self.expr(cond.as_ref()).unwrap_or_default();
+                self.line(format!("if ({}) {{", cond));
+                self.branch_body(body.as_ref(), result);
+                if rest.is_empty() {
+                    self.line("}".to_owned());
+                } else {
+                    self.line("} else {".to_owned());
+                    self.branches(rest, result);
+                    self.line("}".to_owned());
+                }
+            }
+            IfExprBranch::Unconditional { body } => self.branch_body(body.as_ref(), result),
+        }
+    }
+
+    fn branch_body(&mut self, body: &HirExpr, result: Option<&str>) {
+        match result {
+            Some(result) => {
+                if let Some(value) = self.expr(body) {
+                    self.line(format!("{} = {};", result, value));
+                }
+            }
+            None => self.discard(body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::emit_c;
+    use crate::code_gen::tests::echoed;
+    use crate::analysis::Analysis;
+    use crate::syntax::Parser;
+    use crate::system::File;
+    use crate::LanguageMode;
+    use std::process::Command;
+
+    fn translate(source: &str) -> Result<String, Vec<String>> {
+        let file = File::raw_test(source.to_string());
+        let mut parser = Parser::new(&file);
+        let parsed = parser.init().and_then(|_| parser.parse_file()).unwrap();
+        let hir_file = Analysis::new()
+            .check(parsed, LanguageMode::Default)
+            .unwrap();
+        emit_c(&hir_file).map_err(|errs| errs.iter().map(ToString::to_string).collect())
+    }
+
+    // the output of the compiled program, the tests need a C compiler named `cc`.
+    fn run(name: &str, source: &str) -> String {
+        run_c(name, &translate(source).unwrap())
+    }
+
+    fn run_c(name: &str, c_source: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("auburn_c_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let c_file = dir.join("main.c");
+        let binary = dir.join("main");
+        std::fs::write(&c_file, c_source).unwrap();
+
+        let compiled = Command::new("cc")
+            .arg("-o")
+            .arg(&binary)
+            .arg(&c_file)
+            .output()
+            .expect("the C backend is tested with a C compiler, 'cc' could not be run");
+        assert!(
+            compiled.status.success(),
+            "{}",
+            String::from_utf8_lossy(&compiled.stderr)
+        );
+        let output = Command::new(&binary).output().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn test_compiled_program_matches_interpreter() {
+        let source = "struct Node {\n    pub value i64\n    pub next i64\n\
+            \x20   pub fn sum(self, other Node) i64 = self.value + other.value\n\
+            \x20   pub fn make(v i64) Node = Node { value: v, next: 0 }\n\
+            \x20   pub fn bump(mut self, by i64) {\n        self.value += by\n    }\n}\n\
+            let base i64 = 10\n\
+            fn fib(n i64) i64 {\n    mut a i64 = 0\n    mut b i64 = 1\n    mut i i64 = 0\n\
+            \x20   while i < n {\n        let t = a + b\n        a = b\n        b = t\n        i += 1\n    }\n    a\n}\n\
+            fn classify(n i64) = if n < 0 {\n        \"negative\"\n    } elif n < 10 {\n        \"small\"\n\
+            \x20   } else {\n        \"large\"\n    }\n\
+            fn main() {\n    echo fib(10)\n    echo classify(5)\n    echo classify(base * 3)\n\
+            \x20   mut n = Node.make(4)\n    let m = Node { value: 3, next: 1 }\n    echo n.sum(m)\n\
+            \x20   mut count = 0\n    loop {\n        count += 1\n        n.bump(1)\n\
+            \x20       if count > 5 {\n            break\n        }\n    }\n\
+            \x20   echo n\n    echo count\n    echo 2.5\n    echo (7 / 2)\n    echo (base - 20)\n\
+            \x20   let big u8 = 200\n    let three u8 = 3\n    echo (big * three)\n}\n";
+        let output = run("matches", source);
+        assert_eq!(
+            output,
+            "55\nsmall\nlarge\n7\nNode { 10, 0 }\n6\n2.5\n3\n-10\n88\n"
+        );
+    }
+
+    #[test]
+    fn test_compiled_divisions_round_like_the_interpreter() {
+        let source = "fn divide(a i64, b i64) {\n\
+            \x20   echo (a / b)\n    echo (a % b)\n\
+            \x20   echo a.div_floor(b)\n    echo a.mod_floor(b)\n}\n\
+            fn main() {\n    divide(7, 2)\n    divide(-7, 2)\n\
+            \x20   divide(7, -2)\n    divide(-7, -2)\n\
+            \x20   let min i64 = -9223372036854775807\n    divide(min - 1, -1)\n\
+            \x20   let small i8 = -7\n    echo small.mod_floor(2)\n}\n";
+        let output = run("divisions", source);
+        assert_eq!(
+            output,
+            "3\n1\n3\n1\n-3\n-1\n-4\n1\n-3\n1\n-4\n-1\n3\n-1\n3\n-1\n\
+            -9223372036854775808\n0\n-9223372036854775808\n0\n1\n"
+        );
+    }
+
+    // the programs of the golden suite the backend supports print what the interpreter echoes.
+    #[test]
+    fn test_golden_suite_matches_interpreter() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../test/vm");
+        let mut paths = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "au"))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut compiled = 0;
+        for path in paths.iter() {
+            // a file without a main is not a program.
+            let source = std::fs::read_to_string(path).unwrap();
+            if !source.contains("fn main") {
+                continue;
+            }
+            let c_source = match translate(&source) {
+                Ok(c_source) => c_source,
+                Err(_) => continue,
+            };
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            let output = run_c(name, &c_source);
+            assert_eq!(output, echoed(&source), "{}", path.display());
+            compiled += 1;
+        }
+        assert!(compiled > paths.len() / 2, "{} of {}", compiled, paths.len());
+    }
+
+    #[test]
+    fn test_unsupported_constructs_are_reported() {
+        let source = "struct Countdown {\n    pub left i64\n    fn next(mut self) Option[i64] {\n\
+            \x20       mut item Option[i64] = none\n        if self.left != 0 {\n\
+            \x20           self.left -= 1\n            item = self.left\n        } else {\n            item = none\n        }\n\
+            \x20       item\n    }\n}\n\
+            fn main() {\n    let c = Countdown { left: 2 }\n    for i in c {\n        echo i\n    }\n\
+            \x20   echo (5 << 2)\n}\n";
+        let errs = translate(source).err().unwrap();
+        assert_eq!(
+            errs,
+            vec![
+                "the type 'Option[i64]' is not supported by the C backend",
+                "none is not supported by the C backend",
+                "the type 'Option[i64]' is not supported by the C backend",
+                "none is not supported by the C backend",
+                "a for loop is not supported by the C backend",
+                "the operator '<<' on 'i32' is not supported by the C backend",
+            ]
+        );
+        assert!(translate("fn main() {\n    echo 1\n}\n").is_ok());
+    }
+}
@@ -2,9 +2,14 @@ use std::collections::HashMap;
 
 use oxide::{gc::Gc, vm::OpCode, OxFunction, OxModule, OxStruct, Section, Value};
 
+use super::BuildError;
+use crate::analysis::EntityId;
 use crate::ir::hir::HirFile;
 
-pub static SELF_GLOBAL_IDX: u8 = 0;
+pub static SELF_GLOBAL_IDX: u32 = 0;
+
+/// locals of a single function, every one of them is a slot on the stack of the vm.
+pub const MAX_LOCALS: usize = u16::MAX as usize + 1;
 
 #[derive(Debug, Clone, Default)]
 pub(crate) struct GlobalInfo {
@@ -27,7 +32,9 @@ pub(crate) struct LocalInfo {
 
 pub(crate) struct FunctionInfo {
     pub(crate) function: Gc<OxFunction>,
-    pub(crate) global_map: HashMap<usize, u8>,
+    pub(crate) global_map: HashMap<usize, u32>,
+    /// the globals of the local functions the function uses.
+    pub(crate) local_function_map: HashMap<EntityId, u32>,
     pub(crate) locals: Vec<LocalInfo>,
 }
 
@@ -36,6 +43,7 @@ impl FunctionInfo {
         Self {
             function,
             global_map: HashMap::new(),
+            local_function_map: HashMap::new(),
             locals: vec![],
         }
     }
@@ -48,6 +56,10 @@ impl FunctionInfo {
         self.function.section_mut()
     }
 
+    pub fn name(&self) -> String {
+        self.function.name().to_string()
+    }
+
     pub fn look_up_local(&self, name: &str) -> Option<&LocalInfo> {
         for local_info in self.locals.iter().rev() {
             if local_info.name == name {
@@ -74,17 +86,27 @@ pub(crate) struct FileContext<'ctx> {
     /// None when processing a function
     /// Some when processing an associated function
     pub(crate) structure: Option<Gc<OxStruct>>,
+    /// the functions declared in the bodies of functions, they are not objects of the module.
+    pub(crate) local_functions: HashMap<EntityId, Gc<OxFunction>>,
+    /// the functions of the file, created before their bodies are built. A function that is
+    /// not built yet is loaded from here.
+    pub(crate) declared_functions: HashMap<String, Gc<OxFunction>>,
+    /// index of this file in the source map.
+    pub(crate) source_file: usize,
 }
 
 impl<'ctx> FileContext<'ctx> {
-    pub fn new(file: &'ctx HirFile, module: Gc<OxModule>) -> Self {
+    pub fn new(file: &'ctx HirFile, module: Gc<OxModule>, source_file: usize) -> Self {
         Self {
             file,
+            source_file,
             globals: HashMap::new(),
             current_function: None,
             function_stack: vec![],
             module,
             structure: None,
+            local_functions: HashMap::new(),
+            declared_functions: HashMap::new(),
         }
     }
 
@@ -132,11 +154,11 @@ impl<'ctx> FileContext<'ctx> {
 
     pub fn load_constant(&mut self, op: OpCode, value: Value) {
         let section = self.current_section_mut();
-        let idx = section.add_constant(value);
-        section.write_arg(op, idx);
+        let idx = section.intern_constant(value);
+        section.write_index(op, idx);
     }
 
-    pub fn load_global_in_function(&mut self, name: &str) -> u8 {
+    pub fn load_global_in_function(&mut self, name: &str) -> u32 {
         // find the global by name.
         // panics if name doesnt exist:
         //  should be handled by the type checker.
@@ -160,6 +182,8 @@ impl<'ctx> FileContext<'ctx> {
         let value = self
             .module
             .get(global_idx)
+            .cloned()
+            .or_else(|| self.declared_functions.get(name).cloned().map(Value::from))
             .expect(
                 format!(
                     "{}: {} is out of bounds for module of with objects {}",
@@ -168,8 +192,7 @@ impl<'ctx> FileContext<'ctx> {
                     self.module.num_objects()
                 )
                 .as_str(),
-            )
-            .clone();
+            );
 
         // let value = self.values[global_idx].clone();
         if let Some(function) = self.current_function_mut() {
@@ -188,11 +211,37 @@ impl<'ctx> FileContext<'ctx> {
         }
     }
 
-    pub fn push_local(&mut self, name: &str, scope_level: usize) -> u8 {
+    /// a local function is loaded like a global, its object is a global of the function using it.
+    pub fn load_local_function_in_function(&mut self, id: EntityId) -> u32 {
+        let local_function = *self
+            .local_functions
+            .get(&id)
+            .expect("local functions are built before they are used");
+        let function = self
+            .current_function_mut()
+            .expect("local functions are used within a function");
+        if let Some(idx) = function.local_function_map.get(&id) {
+            return *idx;
+        }
+        let idx = function.section_mut().add_global();
+        function.local_function_map.insert(id, idx);
+        function
+            .section_mut()
+            .set_global(idx as usize, Value::from(local_function));
+        idx
+    }
+
+    pub fn push_local(&mut self, name: &str, scope_level: usize) -> Result<u32, BuildError> {
         let function = self
             .current_function_mut()
             .expect("unable to get current function");
         let local_idx = function.locals.len();
+        if local_idx == MAX_LOCALS {
+            return Err(BuildError::TooManyLocals {
+                function: function.name(),
+                limit: MAX_LOCALS,
+            });
+        }
 
         let local_info = LocalInfo {
             name: name.to_owned(),
@@ -201,13 +250,14 @@ impl<'ctx> FileContext<'ctx> {
         };
 
         function.locals.push(local_info);
+        function.section_mut().name_local(local_idx, name);
 
-        local_idx as u8
+        Ok(local_idx as u32)
     }
 
     pub fn set_self_global(&mut self, value: Value) {
         assert!(
-            self.globals.is_empty(),
+            self.current_section().globals().is_empty(),
             "there shouldn't be any globals loaded at this point"
         );
 
@@ -1,32 +1,50 @@
+mod api;
+mod c_backend;
 mod file_context;
 mod type_helpers;
 
+pub use api::module_api;
+pub use c_backend::emit_c;
+
 use crate::{
     analysis::{
-        AssociatedFunctionInfo, Entity, EntityInfo, FunctionInfo, Scope, StructureInfo,
-        VariableInfo,
+        AssociatedFunctionInfo, Entity, EntityInfo, EntityRef, EnumInfo, FunctionInfo,
+        PartialModule, Scope, StructureInfo, VariableInfo,
     },
     ir::{
         self,
-        ast::NodeType,
+        ast::{BinaryOp, NodeType, UnaryOp, Visibility},
         hir::{
-            Assignment, BlockExpr, FieldExpr, HirExpr, HirFile, HirStmt, HirStmtKind, HirStmtPtr,
-            IfExpr, IfExprBranch, MirNode, StructExpr, WhileExpr,
+            Assignment, BlockExpr, BuiltinFunction, CoalesceExpr, EnumFunction, FieldExpr, HirExpr,
+            HirExprPtr, HirFile, HirPattern, HirStmt, HirStmtKind, HirStmtPtr, IfExpr,
+            IfExprBranch, MapFunction, MatchExpr, MirNode, StructExpr, TaskFunction, VecFunction,
+            WeakFunction, WhileExpr,
         },
     },
+    syntax::Position,
     system::{FileId, FileMap},
     types::{Type, TypeKind},
+    utils::timing::{self, PhaseTimer},
 };
 use file_context::{FileContext, SELF_GLOBAL_IDX};
 use hir::HirExprKind;
 use ir::hir;
-use oxide::{gc::Gc, vm::OpCode, OxFunction, OxModule, OxStruct, Section, Value, Vm};
+use oxide::{
+    gc::Gc,
+    source_map::{FunctionMap, SourceFile, SourceMap},
+    vm::{NativeResult, OpCode},
+    Extract, OxFunction, OxModule, OxStruct, Section, Value, Vm, CLOSURE_NAME_PREFIX, MAX_JUMP,
+};
 
+use itertools::Itertools;
 use ordered_float::OrderedFloat;
 use std::{cell::RefCell, collections::HashMap, convert::TryInto, ops::Deref, rc::Rc};
 
 use self::file_context::GlobalInfo;
 
+/// the hidden local holding the value of a match while its arms are tested.
+const MATCH_LOCAL: &str = "__match";
+
 macro_rules! save_state {
     ($field:expr, $val:expr, $op:expr) => {
         let saved = $field;
@@ -52,6 +70,18 @@ pub enum BuildError {
 
     #[error("{0}")]
     CompilerError(String),
+
+    #[error("function '{function}' has more than {limit} locals")]
+    TooManyLocals { function: String, limit: usize },
+
+    #[error("function '{function}' has more than {limit} parameters")]
+    TooManyParameters { function: String, limit: usize },
+
+    #[error("function '{function}' jumps over more than {limit} bytes of code")]
+    JumpTooFar { function: String, limit: usize },
+
+    #[error("the module did not compile, {} failed: {}", failed.len(), failed.join(", "))]
+    PartialModule { failed: Vec<String> },
 }
 
 pub struct CodeGen<'vm, 'ctx> {
@@ -65,6 +95,20 @@ pub struct CodeGen<'vm, 'ctx> {
     result_used: bool,
     #[allow(dead_code)]
     is_function_scope: bool,
+    source_map: SourceMap,
+    /// jumps out of the optional chains being generated, one for each receiver.
+    chain_exits: Vec<usize>,
+    /// loops being generated, the inner most is last.
+    loops: Vec<LoopContext>,
+}
+
+/// where `continue` and `break` of a loop jump to.
+struct LoopContext {
+    start: usize,
+    /// scope the loop is in, locals of deeper scopes are popped when leaving an iteration.
+    scope_index: usize,
+    /// breaks patched to the end of the loop.
+    exits: Vec<usize>,
 }
 
 impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
@@ -74,8 +118,18 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         // hir_module: &HirModule, // not implemented yet
         vm: &mut Vm,
     ) -> Result<Gc<OxModule>, BuildError> {
+        Self::build_with_source_map(file_map, hir_file, vm).map(|(module, _)| module)
+    }
+
+    /// builds the module along with a source map describing the generated functions.
+    pub fn build_with_source_map(
+        file_map: &FileMap,
+        hir_file: &HirFile,
+        vm: &mut Vm,
+    ) -> Result<(Gc<OxModule>, SourceMap), BuildError> {
         // all gc objects that are allocated during code generation should never be deallocated.
         // Yes, this is a hack. Once a better solution is found this will be how it works.
+        let _timer = PhaseTimer::start(timing::CODEGEN);
         vm.force_no_collection(true);
         let mut code_gen = CodeGen {
             file_map,
@@ -86,22 +140,60 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
             handling_params: false,
             result_used: false,
             is_function_scope: false,
+            source_map: SourceMap::new(),
+            chain_exits: vec![],
+            loops: vec![],
         };
 
         let module = code_gen.build_module(hir_file);
+        let source_map = code_gen.source_map;
         vm.force_no_collection(false);
 
-        module
+        module.map(|module| (module, source_map))
+    }
+
+    /// builds a module that compiled completely, one with items that failed is an error. See
+    /// `build_subset` to run what did compile.
+    pub fn build_partial(
+        file_map: &FileMap,
+        partial: &PartialModule,
+        vm: &mut Vm,
+    ) -> Result<Gc<OxModule>, BuildError> {
+        if !partial.is_complete() {
+            let failed = partial
+                .failed
+                .iter()
+                .map(|entity| entity.borrow().name().to_owned())
+                .collect();
+            return Err(BuildError::PartialModule { failed });
+        }
+        Self::build(file_map, &partial.file, vm)
+    }
+
+    /// builds only the items of the module that compiled, for a repl that runs what it can. An
+    /// item that failed is missing from the module and main must have compiled.
+    pub fn build_subset(
+        file_map: &FileMap,
+        partial: &PartialModule,
+        vm: &mut Vm,
+    ) -> Result<Gc<OxModule>, BuildError> {
+        Self::build(file_map, &partial.file, vm)
     }
 }
 
+
 // impl<'ctx> CodeGen<'ctx> {
 impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
     fn push_context(&mut self, file: &'ctx HirFile) {
         let file_id = file.id();
         let name = self.vm.new_string_from_str(file.stem());
         let module = self.vm.new_empty_module(name);
-        let context = FileContext::new(file, module);
+        let source_file = match self.file_map.find(&file_id) {
+            Some(f) => SourceFile::new(f.path().display().to_string(), f.content()),
+            None => SourceFile::new(file.stem().to_owned(), ""),
+        };
+        let source_file = self.source_map.add_file(source_file);
+        let context = FileContext::new(file, module, source_file);
         self.file_context.insert(file_id, context);
     }
 
@@ -165,6 +257,11 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         self.emit(&[op as u8, arg]);
     }
 
+    /// the operand is widened when the index does not fit in a byte.
+    fn emit_index(&mut self, op: OpCode, index: u32) {
+        self.current_section_mut().write_index(op, index);
+    }
+
     fn emit_op_u16(&mut self, op: OpCode, args: u16) {
         let bytes = args.to_be_bytes();
         self.emit(&[op as u8, bytes[0], bytes[1]]);
@@ -181,9 +278,76 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         section.len() - 2
     }
 
-    fn emit_patch(&mut self, offset: usize) {
-        let section = self.current_section_mut();
-        section.patch_jmp(offset);
+    fn emit_patch(&mut self, offset: usize) -> Result<(), BuildError> {
+        let distance = self.current_section().jump_distance(offset);
+        self.check_jump(distance)?;
+        self.current_section_mut().patch_jmp(offset);
+        Ok(())
+    }
+
+    fn emit_loop(&mut self, start: usize) -> Result<(), BuildError> {
+        let distance = self.current_section().loop_distance(start);
+        self.check_jump(distance)?;
+        self.current_section_mut().write_loop(start);
+        Ok(())
+    }
+
+    fn check_jump(&self, distance: usize) -> Result<(), BuildError> {
+        if distance <= MAX_JUMP {
+            return Ok(());
+        }
+        let function = self
+            .current_context()
+            .current_function()
+            .expect("jumps are only emitted within a function");
+        Err(BuildError::JumpTooFar {
+            function: function.name(),
+            limit: MAX_JUMP,
+        })
+    }
+
+    /// marks the following instructions as generated from the statement at position.
+    fn mark_line(&mut self, position: Position) {
+        if let Some(function) = self.current_context_mut().current_function_mut() {
+            function.section_mut().mark_line(position.start().line());
+        }
+    }
+
+    /// marks the following instructions as generated from a part of an expression on line.
+    fn mark_expression_line(&mut self, line: usize) {
+        if let Some(function) = self.current_context_mut().current_function_mut() {
+            function.section_mut().mark_expression_line(line);
+        }
+    }
+
+    /// the line the instructions are being generated from.
+    fn current_line(&self) -> Option<usize> {
+        let function = self.current_context().current_function()?;
+        function.section().lines().current_line()
+    }
+
+    /// records the debug info of the function that was just generated.
+    fn record_function_map(&mut self, name: String, position: Position) {
+        // the files of a linked program share one context, the function is in the file of its
+        // position. The file of the context is not hashed again for each of its functions.
+        let file = match self.file_map.find(&position.file_id()) {
+            _ if position.file_id() == self.current_context().file.id() => {
+                self.current_context().source_file
+            }
+            Some(file) => self.source_map.add_file(SourceFile::new(
+                file.path().display().to_string(),
+                file.content(),
+            )),
+            None => self.current_context().source_file,
+        };
+        let context = self.current_context();
+        let lines = context.current_section().lines().clone();
+        self.source_map.add_function(FunctionMap {
+            name,
+            file,
+            span: position.span().as_tuple(),
+            lines,
+        });
     }
 
     fn push_scope(&mut self) {
@@ -251,6 +415,18 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
 
         std::mem::drop(context);
 
+        // the functions exist before any body is built, a body calls the function it is in and
+        // the functions built after it.
+        for item in items.iter() {
+            let entity = item.deref().borrow();
+            if let EntityInfo::Function(function_info) = entity.kind() {
+                let function = self.new_function(entity.name(), function_info.params.len())?;
+                self.current_context_mut()
+                    .declared_functions
+                    .insert(entity.name().to_owned(), function);
+            }
+        }
+
         for entity in items.into_iter() {
             match self.handle_entity(&entity.deref().borrow())? {
                 Some(value) => {
@@ -275,18 +451,63 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         name: &str,
         mir_function: &FunctionInfo,
     ) -> Result<Gc<OxFunction>, BuildError> {
-        self.push_scope();
+        self.build_function_capturing(name, mir_function, &[])
+    }
+
+    /// the captured locals of a closure are its last parameters, they are named like the locals
+    /// they are copied from.
+    fn build_function_capturing(
+        &mut self,
+        name: &str,
+        mir_function: &FunctionInfo,
+        captures: &[String],
+    ) -> Result<Gc<OxFunction>, BuildError> {
+        let function = self.new_function(name, mir_function.params.len() + captures.len())?;
+        self.build_function_body(function, mir_function, captures)
+    }
 
+    /// the object of a function without a body, it is built into the object later.
+    fn new_function(&mut self, name: &str, arity: usize) -> Result<Gc<OxFunction>, BuildError> {
+        if arity > u8::MAX as usize {
+            return Err(BuildError::TooManyParameters {
+                function: name.to_owned(),
+                limit: u8::MAX as usize,
+            });
+        }
         let name_string = self.vm.new_string_from_str(name);
         let section = self.vm.new_section();
-        let function = self
-            .vm
-            .new_function(name_string, mir_function.params.len() as u8, section);
+        Ok(self.vm.new_function(name_string, arity as u8, section))
+    }
+
+    fn build_function_body(
+        &mut self,
+        function: Gc<OxFunction>,
+        mir_function: &FunctionInfo,
+        captures: &[String],
+    ) -> Result<Gc<OxFunction>, BuildError> {
+        let name = function.name().to_string();
+        let timer = PhaseTimer::start(timing::FUNCTION);
+        self.push_scope();
+
         self.current_context_mut().push_function(function);
         self.handle_function_params(mir_function.params.as_ref(), false)?;
+        self.handling_params = true;
+        for capture in captures {
+            self.build_local(capture)?;
+        }
+        self.handling_params = false;
 
-        self.handle_expr_inner(mir_function.body.as_ref(), true)?;
+        self.mark_line(mir_function.body.position());
+        // the value of the body is returned.
+        save_state!(self.result_used, true, {
+            self.handle_expr_inner(mir_function.body.as_ref(), true)?
+        });
+        if Self::leaves_no_value(mir_function.body.as_ref()) {
+            self.emit_op(OpCode::LoadUnit);
+        }
         self.emit_op(OpCode::Return);
+        self.record_function_map(name, mir_function.body.position());
+        timer.count(self.current_section().len());
 
         self.pop_scope();
 
@@ -300,6 +521,56 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         Ok(function)
     }
 
+    /// a function declared in a body is built within the function declaring it, the functions
+    /// using it load it like a global.
+    fn build_local_function(&mut self, entity: &Entity) -> Result<(), BuildError> {
+        let function_info = match entity.kind() {
+            EntityInfo::Function(function_info) => function_info,
+            _ => unreachable!("only functions are local functions"),
+        };
+        let enclosing = self.current_context().current_function;
+        let function = self.build_function(entity.name(), function_info)?;
+        let context = self.current_context_mut();
+        context.current_function = enclosing;
+        context.local_functions.insert(entity.id(), function);
+        Ok(())
+    }
+
+    /// a function literal is built like a local function, named by where it is. Unless it
+    /// captures nothing, it is loaded along with the values of its captures and made a closure
+    /// of them.
+    fn handle_lambda(&mut self, entity: &Entity, position: Position) -> Result<(), BuildError> {
+        let closure = match entity.kind() {
+            EntityInfo::Closure(closure) => closure,
+            _ => unreachable!("a function literal is a closure"),
+        };
+        let captures = closure
+            .captures
+            .iter()
+            .map(|capture| capture.deref().borrow().name().to_owned())
+            .collect::<Vec<_>>();
+        let file = match self.file_map.find(&position.file_id()) {
+            Some(file) => file.path().display().to_string(),
+            None => self.current_context().file.stem().to_owned(),
+        };
+        let name = format!("{}{}:{}", CLOSURE_NAME_PREFIX, file, position.start().line());
+        let enclosing = self.current_context().current_function;
+        let function = self.build_function_capturing(&name, &closure.function, &captures)?;
+        let context = self.current_context_mut();
+        context.current_function = enclosing;
+        context.local_functions.insert(entity.id(), function);
+        let global_idx = context.load_local_function_in_function(entity.id());
+        self.emit_index(OpCode::LoadGlobal, global_idx);
+
+        if !captures.is_empty() {
+            for capture in closure.captures.iter() {
+                self.handle_name(&capture.deref().borrow())?;
+            }
+            self.emit_op_u8(OpCode::NewClosure, captures.len() as u8);
+        }
+        Ok(())
+    }
+
     fn handle_function_params(
         &mut self,
         param_scope: &Scope,
@@ -354,7 +625,9 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 (Some(_spec), None) => {
                     todo!()
                 }
-                (_, Some(init)) => self.handle_expr(init.as_ref())?,
+                (_, Some(init)) => {
+                    save_state!(self.result_used, true, self.handle_expr(init.as_ref())?);
+                }
                 (None, None) => {
                     unreachable!()
                 }
@@ -370,7 +643,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
     ) -> Result<(), BuildError> {
         let scope_index = self.scope_index;
         let context = self.current_context_mut();
-        context.push_local(name, scope_index);
+        context.push_local(name, scope_index)?;
         if !self.handling_params {
             self.emit_op(OpCode::PushLocal);
         }
@@ -386,6 +659,22 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         let methods = self.vm.vec_with_capacity(struct_info.methods.len());
         let mut structure = self.vm.new_struct(name, methods);
         self.current_context_mut().structure = Some(structure.clone());
+        // the public fields are the ones `reflect.get_field` finds.
+        let fields = struct_info
+            .fields
+            .elements()
+            .iter()
+            .filter_map(|field| {
+                let field = field.deref().borrow();
+                match field.kind() {
+                    EntityInfo::Field(local_info) if field.visibility() == Visibility::Public => {
+                        Some((field.name().to_owned(), local_info.index))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        structure.set_fields(fields);
 
         let method_entities = struct_info.methods.elements();
         for element in method_entities {
@@ -405,11 +694,116 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         Ok(structure)
     }
 
+    /// enums are represented by a struct object holding the generated associated functions. Only
+    /// enums without data have `from_int`, it compares the value against every discriminant and
+    /// returns the matching variant or none.
+    fn build_enum(&mut self, name: &str, enum_info: &EnumInfo) -> Result<Gc<OxStruct>, BuildError> {
+        let struct_name = self.vm.new_string_from_str(name);
+        let methods = self.vm.vec_with_capacity(3);
+        let mut structure = self.vm.new_struct(struct_name, methods);
+
+        if enum_info.flags {
+            // the methods are pushed in the order of EnumFunction::method_index.
+            let from_int = self.build_flags_from_int(enum_info);
+            structure.push(from_int);
+            let contains = self.build_flags_contains();
+            structure.push(contains);
+            let display = self.build_flags_display(name, enum_info);
+            structure.push(display);
+        } else if enum_info.is_unit_only() {
+            let mut section = self.vm.new_section();
+            for variant in enum_info.variants.iter() {
+                let discriminant = section.intern_constant(Value::I64(variant.discriminant));
+                section.write_arg(OpCode::LoadLocal, 0);
+                section.write_index(OpCode::LoadI64, discriminant);
+                section.write_op(OpCode::EqEqI64);
+                let next = section.write_jmp(OpCode::JmpFalse);
+                section.write_index(OpCode::LoadI64, discriminant);
+                section.write_op(OpCode::Return);
+                section.patch_jmp(next);
+            }
+            section.write_op(OpCode::LoadUnit);
+            section.write_op(OpCode::Return);
+
+            let function_name = self.vm.new_string_from_str(EnumFunction::FromInt.name());
+            let function = self.vm.new_function(function_name, 1, section);
+            structure.push(function);
+        }
+
+        Ok(structure)
+    }
+
+    /// any combination of the flags is a value of the enum.
+    fn build_flags_from_int(&mut self, enum_info: &EnumInfo) -> Gc<OxFunction> {
+        let mut section = self.vm.new_section();
+        let unknown_bits = section.intern_constant(Value::I64(!enum_info.all_flags()));
+        let zero = section.intern_constant(Value::I64(0));
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI64, unknown_bits);
+        section.write_op(OpCode::BinaryAndI64);
+        section.write_index(OpCode::LoadI64, zero);
+        section.write_op(OpCode::EqEqI64);
+        let none = section.write_jmp(OpCode::JmpFalse);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_op(OpCode::Return);
+        section.patch_jmp(none);
+        section.write_op(OpCode::LoadUnit);
+        section.write_op(OpCode::Return);
+
+        let function_name = self.vm.new_string_from_str(EnumFunction::FromInt.name());
+        self.vm.new_function(function_name, 1, section)
+    }
+
+    fn build_flags_contains(&mut self) -> Gc<OxFunction> {
+        let mut section = self.vm.new_section();
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_arg(OpCode::LoadLocal, 1);
+        section.write_op(OpCode::BinaryAndI64);
+        section.write_arg(OpCode::LoadLocal, 1);
+        section.write_op(OpCode::EqEqI64);
+        section.write_op(OpCode::Return);
+
+        let function_name = self.vm.new_string_from_str(EnumFunction::Contains.name());
+        self.vm.new_function(function_name, 2, section)
+    }
+
+    /// the names are joined by a native, the function only forwards the value to it.
+    fn build_flags_display(&mut self, name: &str, enum_info: &EnumInfo) -> Gc<OxFunction> {
+        let flags = enum_info
+            .variants
+            .iter()
+            .map(|variant| (variant.name.clone(), variant.discriminant))
+            .collect_vec();
+        let native = self.vm.register_native(
+            format!("{}.{}", name, EnumFunction::Display.name()).as_str(),
+            1,
+            Box::new(move |vm, actuals| {
+                let (value,): (i64,) = actuals.extract()?;
+                let names = display_flags(flags.as_slice(), value);
+
+                let names = vm.new_gc_string_from_str(names.as_str());
+                Ok(NativeResult::Return(Value::from(names)))
+            }),
+        );
+
+        let mut section = self.vm.new_section();
+        let global = section.add_global();
+        section.set_global(global as usize, native);
+        section.write_index(OpCode::LoadGlobal, global);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_arg(OpCode::Call, 1);
+        section.write_op(OpCode::Return);
+
+        let function_name = self.vm.new_string_from_str(EnumFunction::Display.name());
+        self.vm.new_function(function_name, 1, section)
+    }
+
     fn build_associated_function(
         &mut self,
         name: &str,
         associated_function: &AssociatedFunctionInfo,
     ) -> Result<Gc<OxFunction>, BuildError> {
+        let timer = PhaseTimer::start(timing::FUNCTION);
         self.push_scope();
 
         let name_string = self.vm.new_string_from_str(name);
@@ -424,9 +818,22 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
             associated_function.takes_self,
         )?;
 
-        self.handle_expr_inner(associated_function.body.as_ref(), true)?;
+        self.mark_line(associated_function.body.position());
+        save_state!(self.result_used, true, {
+            self.handle_expr_inner(associated_function.body.as_ref(), true)?
+        });
+        if Self::leaves_no_value(associated_function.body.as_ref()) {
+            self.emit_op(OpCode::LoadUnit);
+        }
         self.emit_op(OpCode::Return);
 
+        let mangled_name = match self.current_context().structure.as_ref() {
+            Some(structure) => format!("{}.{}", structure.name(), name),
+            None => name.to_owned(),
+        };
+        self.record_function_map(mangled_name, associated_function.body.position());
+        timer.count(self.current_section().len());
+
         self.pop_scope();
         Ok(function)
     }
@@ -441,8 +848,17 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 let structure = self.build_struct(name, struct_info)?;
                 Ok(Some(Value::from(structure)))
             }
+            EntityInfo::Enumeration(enum_info) => {
+                let structure = self.build_enum(name, enum_info)?;
+                Ok(Some(Value::from(structure)))
+            }
             EntityInfo::Function(function_info) => {
-                let function = self.build_function(name, function_info)?;
+                let declared = self.current_context().declared_functions.get(name).cloned();
+                let function = match declared {
+                    Some(function) => function,
+                    None => self.new_function(name, function_info.params.len())?,
+                };
+                let function = self.build_function_body(function, function_info, &[])?;
                 Ok(Some(Value::from(function)))
             }
             EntityInfo::Variable(variable_info) => {
@@ -481,21 +897,37 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
     //}
 
     fn handle_stmt(&mut self, stmt: &HirStmt) -> Result<(), BuildError> {
+        self.mark_line(stmt.position());
         match stmt.inner() {
+            // every branch of an if or arm of a match without a value leaves one, whatever its
+            // type, it is popped.
+            HirStmtKind::Expr(expr)
+                if matches!(expr.inner().kind(), HirExprKind::If(_) | HirExprKind::Match(_))
+                    && Type::inner(expr.ty()).is_unit() =>
+            {
+                save_state!(self.result_used, true, { self.handle_expr(expr.as_ref())? });
+                self.emit_op(OpCode::Pop);
+            }
             HirStmtKind::Expr(expr) => self.handle_expr(expr.as_ref())?,
-            HirStmtKind::Item(entity) => match self.handle_entity(&entity.deref().borrow())? {
-                Some(_value) => {
-                    let type_name = entity.deref().borrow().type_name();
-                    return Err(BuildError::FeatureNotSupported(format!(
-                        "local {} not supported",
-                        type_name
-                    )));
+            HirStmtKind::Item(entity) => {
+                let entity = entity.deref().borrow();
+                match entity.kind() {
+                    EntityInfo::Function(function_info) if function_info.local => {
+                        self.build_local_function(&entity)?
+                    }
+                    _ => {
+                        if self.handle_entity(&entity)?.is_some() {
+                            return Err(BuildError::FeatureNotSupported(format!(
+                                "local {} not supported",
+                                entity.type_name()
+                            )));
+                        }
+                    }
                 }
-                None => {}
-            },
+            }
             HirStmtKind::Assignment(assignment) => self.handle_assignment(assignment)?,
             HirStmtKind::Echo(expr) => {
-                self.handle_expr(expr.as_ref())?;
+                save_state!(self.result_used, true, self.handle_expr(expr.as_ref())?);
                 self.emit_op(OpCode::Echo);
             }
         }
@@ -503,8 +935,50 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         Ok(())
     }
 
+    /// an expression starting on a line of its own marks its code as generated from that line,
+    /// the code of the expression it is a part of that follows is marked with its line again.
     fn handle_expr(&mut self, expr: &HirExpr) -> Result<(), BuildError> {
-        self.handle_expr_inner(expr, false)
+        let line = self.current_line();
+        self.mark_expression_line(expr.position().start().line());
+        self.handle_expr_inner(expr, false)?;
+        if let Some(line) = line {
+            self.mark_expression_line(line);
+        }
+        Ok(())
+    }
+
+    /// the operators of a chain, `1 + 2 + 3`, are built in a loop over its left operands rather
+    /// than nesting a call for every one of them. They all start on the line of the chain.
+    fn handle_binary_chain(&mut self, expr: &HirExpr) -> Result<(), BuildError> {
+        let mut chain = vec![];
+        let mut left = expr;
+        while let HirExprKind::Binary(bin_expr) = left.inner().kind() {
+            chain.push((bin_expr, left.ty()));
+            left = bin_expr.left.as_ref();
+        }
+
+        save_state!(self.result_used, true, self.handle_expr(left)?);
+        while let Some((bin_expr, ty)) = chain.pop() {
+            save_state!(
+                self.result_used,
+                true,
+                self.handle_expr(bin_expr.right.as_ref())?
+            );
+
+            // an operand typed never does not produce a value, the operator takes the type of
+            // the other one and is not reached when neither does.
+            let ty = if !bin_expr.op.is_cmp() {
+                ty
+            } else if bin_expr.left.ty().is_never() {
+                bin_expr.right.ty()
+            } else {
+                bin_expr.left.ty()
+            };
+            if !ty.is_never() {
+                self.emit_op(type_helpers::binary_op_for_type(bin_expr.op, ty));
+            }
+        }
+        Ok(())
     }
 
     fn handle_expr_inner(&mut self, expr: &HirExpr, is_scope: bool) -> Result<(), BuildError> {
@@ -518,7 +992,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 self.load_float(ty, *val)?;
             }
             HirExprKind::String(val) => {
-                let ox_string = self.vm.new_gc_string_from_str(val);
+                let ox_string = self.vm.intern_string(val);
                 let value = Value::String(ox_string);
                 let context = self.current_context_mut();
                 context.load_constant(OpCode::LoadStr, value);
@@ -537,23 +1011,48 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 self.emit_op(op);
             }
             HirExprKind::Name(val) => self.handle_name(&val.deref().borrow())?,
-            HirExprKind::Binary(bin_expr) => {
+            HirExprKind::Binary(_) => self.handle_binary_chain(expr)?,
+            HirExprKind::Unary(un_expr) => {
+                // the complement of flags only keeps the bits of the enum's variants.
+                if let TypeKind::Enum { entity } = Type::inner(ty.clone()).kind() {
+                    let all_flags = entity.borrow().as_enum().all_flags();
+                    save_state!(
+                        self.result_used,
+                        true,
+                        self.handle_expr(un_expr.operand.as_ref())?
+                    );
+                    self.current_context_mut()
+                        .load_constant(OpCode::LoadI64, Value::I64(all_flags));
+                    self.emit_op(OpCode::BinaryXorI64);
+                } else if un_expr.op == UnaryOp::Minus {
+                    // negation is a subtraction from zero, negated literals are folded.
+                    match un_expr.operand.inner().kind() {
+                        HirExprKind::Integer(val) => self.load_integer(ty, -val)?,
+                        HirExprKind::Float(val) => self.load_float(ty, -*val)?,
+                        _ => {
+                            if ty.is_float() {
+                                self.load_float(ty.clone(), OrderedFloat(0.0))?;
+                            } else {
+                                self.load_integer(ty.clone(), 0)?;
+                            }
+                            save_state!(
+                                self.result_used,
+                                true,
+                                self.handle_expr(un_expr.operand.as_ref())?
+                            );
+                            self.emit_op(type_helpers::binary_op_for_type(BinaryOp::Minus, ty));
+                        }
+                    }
+                }
+            }
+            HirExprKind::Field(field_expr) => self.handle_field_expr(field_expr)?,
+            HirExprKind::Index(index) => {
                 save_state!(self.result_used, true, {
-                    self.handle_expr(bin_expr.left.as_ref())?;
-                    self.handle_expr(bin_expr.right.as_ref())?;
+                    self.handle_expr(index.operand.as_ref())?;
+                    self.handle_expr(index.index.as_ref())?;
                 });
-
-                let op = if bin_expr.op.is_cmp() {
-                    type_helpers::binary_op_for_type(bin_expr.op, bin_expr.left.ty())
-                } else {
-                    type_helpers::binary_op_for_type(bin_expr.op, ty.clone())
-                };
-
-                self.emit_op(op);
+                self.emit_op(OpCode::LoadIndex);
             }
-            HirExprKind::Unary(_un_expr) => {}
-            HirExprKind::Field(field_expr) => self.handle_field_expr(field_expr)?,
-            HirExprKind::Index(_) => {}
             HirExprKind::FieldAccess(_) => {}
             HirExprKind::Call(call_expr) => {
                 save_state!(self.result_used, true, {
@@ -563,9 +1062,13 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                     }
                 });
 
-                self.emit_op_u8(OpCode::Call, call_expr.actuals.len() as u8);
+                self.emit_call(call_expr.actuals.len() as u8);
             }
             HirExprKind::Method(method_expr) => {
+                let struct_entity = &method_expr.struct_entity;
+                if let Some(function) = Self::builtin_function(struct_entity, &method_expr.name) {
+                    return self.handle_builtin(function, &method_expr.actuals);
+                }
                 // determine the struct that object that should be loaded from globals.
                 let reciever_borrow = method_expr.struct_entity.borrow();
 
@@ -591,7 +1094,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 };
 
                 // lodas the struct object which stores the associated function
-                self.emit_op_u8(OpCode::LoadGlobal, struct_idx);
+                self.emit_index(OpCode::LoadGlobal, struct_idx);
                 // the loads associated function from the struct object just loaded.
                 if let Some(method) = reciever_borrow
                     .as_struct()
@@ -602,11 +1105,13 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                     self.emit_op_u8(OpCode::LoadAssoc, method_idx);
 
                     let num_actuals = method_expr.actuals.len();
-                    for actual in method_expr.actuals.iter() {
-                        self.handle_expr(actual.as_ref())?;
-                    }
+                    save_state!(self.result_used, true, {
+                        for actual in method_expr.actuals.iter() {
+                            self.handle_expr(actual.as_ref())?;
+                        }
+                    });
 
-                    self.emit_op_u8(OpCode::Call, num_actuals as u8);
+                    self.emit_call(num_actuals as u8);
                 } else {
                     return Err(BuildError::CompilerError(format!(
                         "unable to find associated function '{}' in entity '{}'",
@@ -615,6 +1120,11 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 }
             }
             HirExprKind::AssociatedFunction(associated_function_expr) => {
+                let struct_entity = &associated_function_expr.struct_entity;
+                let name = &associated_function_expr.name;
+                if let Some(function) = Self::builtin_function(struct_entity, name) {
+                    return self.handle_builtin(function, &associated_function_expr.actuals[1..]);
+                }
                 // determine the struct that object that should be loaded from globals.
                 let reciever_borrow = associated_function_expr.struct_entity.borrow();
 
@@ -625,7 +1135,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                     .load_global_in_function(type_name);
 
                 // lodas the struct object which stores the associated function
-                self.emit_op_u8(OpCode::LoadGlobal, struct_idx);
+                self.emit_index(OpCode::LoadGlobal, struct_idx);
                 // the loads associated function from the struct object just loaded.
                 if let Some(method) = reciever_borrow
                     .as_struct()
@@ -636,11 +1146,13 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                     self.emit_op_u8(OpCode::LoadAssoc, method_idx);
 
                     let num_actuals = associated_function_expr.actuals.len() - 1;
-                    for actual in associated_function_expr.actuals.iter().skip(1) {
-                        self.handle_expr(actual.as_ref())?;
-                    }
+                    save_state!(self.result_used, true, {
+                        for actual in associated_function_expr.actuals.iter().skip(1) {
+                            self.handle_expr(actual.as_ref())?;
+                        }
+                    });
 
-                    self.emit_op_u8(OpCode::Call, num_actuals as u8);
+                    self.emit_call(num_actuals as u8);
                 } else {
                     return Err(BuildError::CompilerError(format!(
                         "unable to find associated function '{}' in entity '{}'",
@@ -648,6 +1160,153 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                     )));
                 }
             }
+            HirExprKind::Log(log_expr) => {
+                // the message is only evaluated when the level is enabled.
+                let level = log_expr.level as u8;
+                self.emit_op_u8(OpCode::LogEnabled, level);
+                let skip = self
+                    .current_context_mut()
+                    .current_section_mut()
+                    .write_jmp(OpCode::JmpFalse);
+                save_state!(
+                    self.result_used,
+                    true,
+                    self.handle_expr(log_expr.message.as_ref())?
+                );
+                self.emit_op_u8(OpCode::Log, level);
+                self.emit_patch(skip)?;
+            }
+            HirExprKind::Variant(variant_expr) => {
+                let discriminant = Value::I64(variant_expr.discriminant);
+                self.current_context_mut()
+                    .load_constant(OpCode::LoadI64, discriminant);
+                // variants of enums with data are a tuple of the discriminant and the fields.
+                if !variant_expr.enum_entity.borrow().as_enum().is_unit_only() {
+                    save_state!(self.result_used, true, {
+                        for field in variant_expr.fields.iter() {
+                            self.handle_expr(field.as_ref())?;
+                        }
+                    });
+                    self.emit_op_u16(OpCode::NewTuple, variant_expr.fields.len() as u16 + 1);
+                }
+            }
+            HirExprKind::EnumFunction(enum_function_expr) => {
+                let enum_borrow = enum_function_expr.enum_entity.borrow();
+                let enum_idx = self
+                    .current_context_mut()
+                    .load_global_in_function(enum_borrow.name());
+                self.emit_index(OpCode::LoadGlobal, enum_idx);
+                self.emit_op_u8(
+                    OpCode::LoadAssoc,
+                    enum_function_expr.function.method_index(),
+                );
+                save_state!(self.result_used, true, {
+                    for actual in enum_function_expr.actuals.iter() {
+                        self.handle_expr(actual.as_ref())?;
+                    }
+                });
+                self.emit_call(enum_function_expr.actuals.len() as u8);
+            }
+            HirExprKind::Task(task_expr) => {
+                save_state!(self.result_used, true, {
+                    for actual in task_expr.actuals.iter() {
+                        self.handle_expr(actual.as_ref())?;
+                    }
+                });
+                // a result is the tuple (ok, err) where the missing one is none.
+                match task_expr.function {
+                    TaskFunction::Spawn => {
+                        self.emit_op_u8(OpCode::Spawn, task_expr.actuals.len() as u8 - 1)
+                    }
+                    TaskFunction::Join => self.emit_op(OpCode::Join),
+                    TaskFunction::Ok => self.emit_op_u16(OpCode::TupleAttr, 0),
+                    TaskFunction::Err => self.emit_op_u16(OpCode::TupleAttr, 1),
+                }
+            }
+            HirExprKind::GetField(get_field) => {
+                save_state!(self.result_used, true, {
+                    self.handle_expr(get_field.operand.as_ref())?;
+                    self.handle_expr(get_field.name.as_ref())?;
+                });
+                self.emit_op(OpCode::GetField);
+            }
+            HirExprKind::Trap(trap) => {
+                let message = Value::String(self.vm.new_gc_string_from_str(&trap.message));
+                self.current_context_mut()
+                    .load_constant(OpCode::LoadStr, message);
+                if trap.values.is_empty() {
+                    self.emit_op(OpCode::Trap);
+                } else {
+                    save_state!(self.result_used, true, {
+                        for value in &trap.values {
+                            self.handle_expr(value.as_ref())?;
+                        }
+                    });
+                    self.emit_op_u8(OpCode::TrapFormat, trap.values.len() as u8);
+                }
+            }
+            HirExprKind::Unreachable(message) => {
+                let message = Value::String(self.vm.new_gc_string_from_str(message));
+                self.current_context_mut()
+                    .load_constant(OpCode::LoadStr, message);
+                self.emit_op(OpCode::Trap);
+            }
+            HirExprKind::Cast(cast_expr) => {
+                save_state!(
+                    self.result_used,
+                    true,
+                    self.handle_expr(cast_expr.operand.as_ref())?
+                );
+                let operand_type = Type::inner(cast_expr.operand.ty());
+                let target_type = Type::inner(ty.clone());
+                if target_type.is_bool() {
+                    // an integer is true when it is not zero.
+                    self.load_integer(operand_type.clone(), 0)?;
+                    self.emit_op(type_helpers::binary_op_for_type(
+                        BinaryOp::BangEqual,
+                        operand_type,
+                    ));
+                } else if operand_type.is_bool() {
+                    // a bool is always converted, even to an i64.
+                    let kind = type_helpers::cast_int_kind(target_type).unwrap_or(3);
+                    self.emit_op_u8(OpCode::CastInt, kind);
+                } else if let Some(kind) = type_helpers::cast_int_kind(target_type) {
+                    // enums are stored as i64.
+                    self.emit_op_u8(OpCode::CastInt, kind);
+                }
+            }
+            HirExprKind::OptionalChain(chain_expr) => {
+                self.handle_optional_chain(chain_expr.link.as_ref())?
+            }
+            // the value is computed and kept on the stack even when it is not used.
+            HirExprKind::BlackBox(value) => {
+                save_state!(self.result_used, true, self.handle_expr(value.as_ref())?);
+                if !self.result_used {
+                    self.emit_op(OpCode::Pop);
+                }
+            }
+            HirExprKind::ChainReceiver(receiver) => {
+                save_state!(self.result_used, true, self.handle_expr(receiver.as_ref())?);
+                let exit = self.emit_jmp(OpCode::JmpUnit);
+                self.chain_exits.push(exit);
+            }
+            HirExprKind::Coalesce(coalesce_expr) => self.handle_coalesce(coalesce_expr)?,
+            HirExprKind::NoneLit => self.emit_op(OpCode::LoadUnit),
+            HirExprKind::Host => self.emit_op(OpCode::LoadHost),
+            HirExprKind::GcCollect => {
+                self.emit_op(OpCode::GcCollect);
+                if !self.result_used {
+                    self.emit_op(OpCode::Pop);
+                }
+            }
+            // a result is the tuple (value, error) where the missing one is none.
+            HirExprKind::Eval(source) => {
+                save_state!(self.result_used, true, self.handle_expr(source.as_ref())?);
+                self.emit_op(OpCode::Eval);
+                if !self.result_used {
+                    self.emit_op(OpCode::Pop);
+                }
+            }
             HirExprKind::Block(block_expr) => {
                 if self.result_used {
                     self.handle_returning_block(block_expr, 2, is_scope)?;
@@ -655,6 +1314,12 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                     self.handle_block(block_expr, is_scope)?;
                 }
             }
+            HirExprKind::Tuple(..) if Self::is_constant_literal(expr) => {
+                let value = self.constant_literal(expr)?;
+                let context = self.current_context_mut();
+                let idx = context.current_section_mut().intern_constant(value);
+                self.emit_index(OpCode::LoadConst, idx);
+            }
             HirExprKind::Tuple(tuple_expr) => {
                 save_state!(self.result_used, true, {
                     for element in tuple_expr.elements.iter() {
@@ -664,22 +1329,65 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
 
                 self.emit_op_u16(OpCode::NewTuple, tuple_expr.elements.len() as u16);
             }
+            HirExprKind::Array(array_expr)
+                if !array_expr.elements.is_empty()
+                    && array_expr
+                        .elements
+                        .iter()
+                        .all(|element| Self::is_constant_literal(element)) =>
+            {
+                // arrays can be assigned, the pooled elements are copied into a new array.
+                let mut elements = self.vm.new_vec();
+                for element in array_expr.elements.iter() {
+                    let value = self.constant_literal(element)?;
+                    elements.push(value);
+                }
+                let elements = self.vm.new_tuple(elements).map_err(Self::runtime_error)?;
+                let context = self.current_context_mut();
+                let idx = context
+                    .current_section_mut()
+                    .intern_constant(Value::from(elements));
+                self.emit_index(OpCode::NewArrayConst, idx);
+            }
+            HirExprKind::Array(array_expr) => {
+                save_state!(self.result_used, true, {
+                    for element in array_expr.elements.iter() {
+                        self.handle_expr(element.as_ref())?;
+                    }
+                });
+
+                self.emit_op_u16(OpCode::NewArray, array_expr.elements.len() as u16);
+            }
             HirExprKind::TupleIndex(tuple_index) => {
-                self.handle_expr(tuple_index.tuple.as_ref())?;
+                save_state!(
+                    self.result_used,
+                    true,
+                    self.handle_expr(tuple_index.tuple.as_ref())?
+                );
                 let index = tuple_index.field as u16;
                 self.emit_op_u16(OpCode::TupleAttr, index);
             }
-            HirExprKind::Loop(_loop_expr) => {}
+            HirExprKind::Loop(loop_expr) => {
+                let start = self.current_section().len();
+                self.handle_loop_body(start, loop_expr.body.as_ref(), vec![])?;
+            }
             HirExprKind::While(while_expr) => self.handle_while(while_expr)?,
-            HirExprKind::For(_for_expr) => {}
             HirExprKind::If(if_expr) => self.handle_if(if_expr)?,
+            HirExprKind::Match(match_expr) => self.handle_match(match_expr)?,
             HirExprKind::StructExpr(struct_expr) => self.handle_struct_expr(struct_expr)?,
             HirExprKind::SelfLit(..) => {
                 // self will always be the first local at 0
                 self.emit_op_u8(OpCode::LoadLocal, 0);
             }
-            HirExprKind::Break => {}
-            HirExprKind::Continue => {}
+            HirExprKind::Break => {
+                self.leave_iteration();
+                let exit = self.emit_jmp(OpCode::Jmp);
+                self.loops.last_mut().unwrap().exits.push(exit);
+            }
+            HirExprKind::Continue => {
+                let start = self.leave_iteration();
+                self.emit_loop(start)?;
+            }
             HirExprKind::Return(return_expr) => {
                 save_state!(
                     self.result_used,
@@ -688,6 +1396,9 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 );
                 self.emit_op(OpCode::Return);
             }
+            HirExprKind::Lambda(entity) => {
+                self.handle_lambda(&entity.deref().borrow(), expr.position())?
+            }
         }
 
         Ok(())
@@ -696,6 +1407,12 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
     fn handle_name(&mut self, name: &Entity) -> Result<(), BuildError> {
         let global = match name.kind() {
             EntityInfo::Variable(variable_info) => variable_info.global,
+            EntityInfo::Function(function_info) if function_info.local => {
+                let context = self.current_context_mut();
+                let global_idx = context.load_local_function_in_function(name.id());
+                self.emit_index(OpCode::LoadGlobal, global_idx);
+                return Ok(());
+            }
             EntityInfo::Param(_) => false,
             _ => true,
         };
@@ -703,7 +1420,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         if global {
             let context = self.current_context_mut();
             let global_idx = context.load_global_in_function(name.name());
-            self.emit_op_u8(OpCode::LoadGlobal, global_idx);
+            self.emit_index(OpCode::LoadGlobal, global_idx);
         } else {
             let context = self.current_context_mut();
 
@@ -711,11 +1428,11 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 .current_function()
                 .and_then(|funct| funct.look_up_local(name.name()))
                 .expect("failed to find local info")
-                .stack_idx as u8;
+                .stack_idx as u32;
 
             context
                 .current_section_mut()
-                .write_arg(OpCode::LoadLocal, stack_idx);
+                .write_index(OpCode::LoadLocal, stack_idx);
         }
 
         Ok(())
@@ -729,7 +1446,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
 
         // only output the scope cleanup if
         if !is_scope {
-            self.cleanup_top_scope();
+            self.cleanup_top_scope(false);
         }
 
         // pop locals
@@ -738,45 +1455,174 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         Ok(())
     }
 
+    /// a block whose value is used, it is left on top of the stack once the locals of the block
+    /// are popped.
     fn handle_returning_block(
         &mut self,
-        _block_expr: &BlockExpr,
+        block_expr: &BlockExpr,
         _dst: usize,
-        _is_scope: bool,
+        is_scope: bool,
     ) -> Result<(), BuildError> {
-        todo!()
-    }
-
-    fn handle_lvalue(&mut self, lvalue: &HirExpr) -> Result<(), BuildError> {
-        let inner = lvalue.inner();
-        match inner.kind() {
-            HirExprKind::Name(entity) => self.handle_name(&entity.deref().borrow())?,
-            HirExprKind::Field(field_expr) => {
-                self.handle_expr(field_expr.operand.as_ref())?;
+        self.push_scope();
+        let (last, stmts) = match block_expr.stmts.split_last() {
+            Some((last, stmts)) => (Some(last), stmts),
+            None => (None, &[][..]),
+        };
+        save_state!(self.result_used, false, {
+            for stmt in stmts {
+                self.handle_stmt(stmt.as_ref())?;
             }
-            HirExprKind::Index(index) => {
-                self.handle_expr(index.operand.as_ref())?;
-                self.handle_expr(index.index.as_ref())?;
+        });
+        match last.map(|stmt| stmt.inner()) {
+            Some(HirStmtKind::Expr(expr)) => {
+                // the value of the block is a statement of its own.
+                self.mark_line(expr.position());
+                self.handle_expr(expr.as_ref())?;
+                if Self::leaves_no_value(expr.as_ref()) {
+                    self.emit_op(OpCode::LoadUnit);
+                }
             }
-            //HirExprKind::TupleIndex(tuple_index) => {}
             _ => {
-                panic!(
-                    "Compiler Error: unexpected expression '{}' when handling expected lvalues",
-                    inner.name()
-                );
+                save_state!(self.result_used, false, {
+                    if let Some(stmt) = last {
+                        self.handle_stmt(stmt.as_ref())?;
+                    }
+                });
+                self.emit_op(OpCode::LoadUnit);
             }
         }
+
+        if !is_scope {
+            self.cleanup_top_scope(true);
+        }
+        self.pop_scope();
+
         Ok(())
     }
 
-    fn handle_assignment(&mut self, assignment: &Assignment) -> Result<(), BuildError> {
-        save_state!(
-            self.result_used,
-            true,
-            self.handle_expr(assignment.rhs.as_ref())?
-        );
-        self.handle_lvalue(assignment.lvalue.as_ref())?;
-        self.handle_expr(assignment.rhs.as_ref())?;
+    /// whether expr leaves nothing on the stack even when its value is used, the value of a log
+    /// or of a loop is unit.
+    fn leaves_no_value(expr: &HirExpr) -> bool {
+        matches!(
+            expr.inner().kind(),
+            HirExprKind::Log(_) | HirExprKind::While(_) | HirExprKind::Loop(_)
+        )
+    }
+
+    /// calls the function below its actuals, a result that is not used is popped.
+    fn emit_call(&mut self, arity: u8) {
+        self.emit_op_u8(OpCode::Call, arity);
+        if !self.result_used {
+            self.emit_op(OpCode::Pop);
+        }
+    }
+
+    /// the function of a built in type named name, the methods of `Vec[T]` for one.
+    fn builtin_function(struct_entity: &EntityRef, name: &str) -> Option<BuiltinFunction> {
+        let entity = struct_entity.borrow();
+        let method = entity.as_struct().methods.get(name)?;
+        let builtin = method.borrow().as_associated_function().builtin;
+        builtin
+    }
+
+    /// a function of a built in type is the instruction applied to its actuals, a result that is
+    /// not used is popped.
+    fn handle_builtin(
+        &mut self,
+        function: BuiltinFunction,
+        actuals: &[HirExprPtr],
+    ) -> Result<(), BuildError> {
+        save_state!(self.result_used, true, {
+            for actual in actuals {
+                self.handle_expr(actual.as_ref())?;
+            }
+        });
+        match function {
+            BuiltinFunction::Vec(VecFunction::New) => self.emit_op_u16(OpCode::NewArray, 0),
+            BuiltinFunction::Vec(VecFunction::Push) => self.emit_op(OpCode::ListPush),
+            BuiltinFunction::Vec(VecFunction::Pop) => self.emit_op(OpCode::ListPop),
+            BuiltinFunction::Vec(VecFunction::Len) => self.emit_op(OpCode::ListLen),
+            BuiltinFunction::Vec(VecFunction::WithCapacity) => {
+                self.emit_op(OpCode::ListWithCapacity)
+            }
+            BuiltinFunction::Vec(VecFunction::Extend) => self.emit_op(OpCode::ListExtend),
+            BuiltinFunction::Vec(VecFunction::CopyFrom) => self.emit_op(OpCode::ListCopy),
+            BuiltinFunction::Vec(VecFunction::Fill) => self.emit_op(OpCode::ListFill),
+            BuiltinFunction::Vec(VecFunction::FillRange) => self.emit_op(OpCode::ListFillRange),
+            BuiltinFunction::Vec(VecFunction::Reserve) => self.emit_op(OpCode::ListReserve),
+            BuiltinFunction::Vec(VecFunction::Capacity) => self.emit_op(OpCode::ListCapacity),
+            BuiltinFunction::Vec(VecFunction::ShrinkToFit) => {
+                self.emit_op(OpCode::ListShrinkToFit)
+            }
+            BuiltinFunction::Map(MapFunction::New) => self.emit_op(OpCode::NewMap),
+            BuiltinFunction::Map(MapFunction::Insert) => self.emit_op(OpCode::MapInsert),
+            BuiltinFunction::Map(MapFunction::Get) => self.emit_op(OpCode::MapGet),
+            BuiltinFunction::Map(MapFunction::Remove) => self.emit_op(OpCode::MapRemove),
+            BuiltinFunction::Map(MapFunction::Contains) => self.emit_op(OpCode::MapContains),
+            BuiltinFunction::Map(MapFunction::Len) => self.emit_op(OpCode::MapLen),
+            BuiltinFunction::Map(MapFunction::Reserve) => self.emit_op(OpCode::MapReserve),
+            BuiltinFunction::Weak(WeakFunction::New) => self.emit_op(OpCode::NewWeak),
+            BuiltinFunction::Weak(WeakFunction::Get) => self.emit_op(OpCode::WeakGet),
+        }
+        if !self.result_used {
+            self.emit_op(OpCode::Pop);
+        }
+        Ok(())
+    }
+
+    fn handle_lvalue(&mut self, lvalue: &HirExpr) -> Result<(), BuildError> {
+        let inner = lvalue.inner();
+        match inner.kind() {
+            HirExprKind::Name(entity) => self.handle_name(&entity.deref().borrow())?,
+            HirExprKind::Field(field_expr) => {
+                self.handle_expr(field_expr.operand.as_ref())?;
+            }
+            HirExprKind::Index(index) => {
+                self.handle_expr(index.operand.as_ref())?;
+                self.handle_expr(index.index.as_ref())?;
+            }
+            //HirExprKind::TupleIndex(tuple_index) => {}
+            _ => {
+                panic!(
+                    "Compiler Error: unexpected expression '{}' when handling expected lvalues",
+                    inner.name()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_assignment(&mut self, assignment: &Assignment) -> Result<(), BuildError> {
+        // the object of a field or index is below the value being stored.
+        match assignment.lvalue.inner().kind() {
+            HirExprKind::Name(..) => {}
+            _ => {
+                save_state!(
+                    self.result_used,
+                    true,
+                    self.handle_lvalue(assignment.lvalue.as_ref())?
+                );
+            }
+        }
+        match assignment.op.get_binary_op() {
+            // a load of the current value, the operation and the store, the object of a field is
+            // evaluated again for the load.
+            Some(op) => {
+                save_state!(self.result_used, true, {
+                    self.handle_expr(assignment.lvalue.as_ref())?;
+                    self.handle_expr(assignment.rhs.as_ref())?;
+                });
+                let ty = Type::inner(assignment.lvalue.ty());
+                self.emit_op(type_helpers::binary_op_for_type(op, ty));
+            }
+            None => {
+                save_state!(
+                    self.result_used,
+                    true,
+                    self.handle_expr(assignment.rhs.as_ref())?
+                );
+            }
+        }
 
         match assignment.lvalue.inner().kind() {
             HirExprKind::Name(entity) => match entity.deref().borrow().kind() {
@@ -791,7 +1637,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 }
                 _ => {}
             },
-            HirExprKind::Index(_index) => {}
+            HirExprKind::Index(_) => self.emit_op(OpCode::StoreIndex),
             _ => {}
         }
         Ok(())
@@ -802,30 +1648,236 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         for branch in if_expr.branches.as_slice() {
             match branch {
                 IfExprBranch::Conditional { cond, body, .. } => {
-                    self.handle_expr(cond.as_ref())?;
+                    save_state!(self.result_used, true, self.handle_expr(cond.as_ref())?);
                     let conditional_offset = self.emit_jmp(OpCode::JmpFalse);
                     self.handle_expr(body.as_ref())?;
                     offsets.push(self.emit_jmp(OpCode::Jmp));
-                    self.emit_patch(conditional_offset);
+                    self.emit_patch(conditional_offset)?;
                 }
                 IfExprBranch::Unconditional { body } => {
                     self.handle_expr(body.as_ref())?;
                 }
             }
         }
-        let section = self.current_section_mut();
-        offsets.iter().for_each(|offset| section.patch_jmp(*offset));
+        // without an else the if is unit, it is the value when no condition holds.
+        let has_else = matches!(
+            if_expr.branches.last(),
+            Some(IfExprBranch::Unconditional { .. })
+        );
+        if self.result_used && !has_else {
+            self.emit_op(OpCode::LoadUnit);
+        }
+        for offset in offsets {
+            self.emit_patch(offset)?;
+        }
+        Ok(())
+    }
+
+    /// the value is kept in a hidden local while the arms are tested in order, the arm taken
+    /// leaves its value in the slot of that local. The arms are exhaustive, the trap after the
+    /// last one is never reached by a checked program.
+    fn handle_match(&mut self, match_expr: &MatchExpr) -> Result<(), BuildError> {
+        self.push_scope();
+        save_state!(self.result_used, true, {
+            self.handle_expr(match_expr.value.as_ref())?
+        });
+        self.build_local(MATCH_LOCAL)?;
+        let value = self
+            .current_context()
+            .current_function()
+            .and_then(|function| function.look_up_local(MATCH_LOCAL))
+            .expect("failed to find local info")
+            .stack_idx as u32;
+
+        let mut ends = vec![];
+        for arm in &match_expr.arms {
+            let misses = self.emit_pattern_test(&arm.pattern, value)?;
+            self.push_scope();
+            match &arm.pattern {
+                HirPattern::Binding(entity) => {
+                    self.emit_index(OpCode::LoadLocal, value);
+                    self.build_local(entity.borrow().name())?;
+                }
+                HirPattern::Variant { fields, .. } => {
+                    for (index, field) in fields.iter().enumerate() {
+                        if let Some(field) = field {
+                            self.emit_index(OpCode::LoadLocal, value);
+                            self.emit_op_u16(OpCode::TupleAttr, index as u16 + 1);
+                            self.build_local(field.borrow().name())?;
+                        }
+                    }
+                }
+                HirPattern::Wildcard | HirPattern::Literal(_) | HirPattern::Range { .. } => {}
+            }
+            save_state!(self.result_used, true, {
+                self.handle_expr(arm.body.as_ref())?
+            });
+            self.cleanup_top_scope(true);
+            self.pop_scope();
+            ends.push(self.emit_jmp(OpCode::Jmp));
+            for miss in misses {
+                self.emit_patch(miss)?;
+            }
+        }
+        let message = Value::String(self.vm.new_gc_string_from_str("no arm of the match matched"));
+        self.current_context_mut()
+            .load_constant(OpCode::LoadStr, message);
+        self.emit_op(OpCode::Trap);
+
+        for end in ends {
+            self.emit_patch(end)?;
+        }
+        self.cleanup_top_scope(true);
+        self.pop_scope();
         Ok(())
     }
 
+    /// compares the value in the local with the pattern, returns the jumps taken when it does
+    /// not match.
+    fn emit_pattern_test(
+        &mut self,
+        pattern: &HirPattern,
+        value: u32,
+    ) -> Result<Vec<usize>, BuildError> {
+        match pattern {
+            HirPattern::Wildcard | HirPattern::Binding(_) => Ok(vec![]),
+            HirPattern::Literal(literal) => match literal.inner().kind() {
+                HirExprKind::Bool(true) => {
+                    self.emit_index(OpCode::LoadLocal, value);
+                    Ok(vec![self.emit_jmp(OpCode::JmpFalse)])
+                }
+                HirExprKind::Bool(false) => {
+                    self.emit_index(OpCode::LoadLocal, value);
+                    Ok(vec![self.emit_jmp(OpCode::JmpTrue)])
+                }
+                _ => {
+                    let miss = OpCode::JmpFalse;
+                    Ok(vec![self.emit_comparison(value, BinaryOp::EqualEqual, literal, miss)?])
+                }
+            },
+            // the value is not below start and below end.
+            HirPattern::Range { start, end } => Ok(vec![
+                self.emit_comparison(value, BinaryOp::Less, start, OpCode::JmpTrue)?,
+                self.emit_comparison(value, BinaryOp::Less, end, OpCode::JmpFalse)?,
+            ]),
+            HirPattern::Variant {
+                enum_entity,
+                discriminant,
+                ..
+            } => {
+                self.emit_index(OpCode::LoadLocal, value);
+                // variants of enums with data are a tuple of the discriminant and the fields.
+                if !enum_entity.borrow().as_enum().is_unit_only() {
+                    self.emit_op_u16(OpCode::TupleAttr, 0);
+                }
+                self.current_context_mut()
+                    .load_constant(OpCode::LoadI64, Value::I64(*discriminant));
+                self.emit_op(OpCode::EqEqI64);
+                Ok(vec![self.emit_jmp(OpCode::JmpFalse)])
+            }
+        }
+    }
+
+    /// `value op literal`, returns the jump miss taken on the result when the pattern does not
+    /// match.
+    fn emit_comparison(
+        &mut self,
+        value: u32,
+        op: BinaryOp,
+        literal: &HirExprPtr,
+        miss: OpCode,
+    ) -> Result<usize, BuildError> {
+        self.emit_index(OpCode::LoadLocal, value);
+        self.handle_expr(literal.as_ref())?;
+        let op = type_helpers::binary_op_for_type(op, Type::inner(literal.ty()));
+        self.emit_op(op);
+        Ok(self.emit_jmp(miss))
+    }
+
     fn handle_while(&mut self, while_expr: &WhileExpr) -> Result<(), BuildError> {
         let ip = self.current_section().len();
-        self.handle_expr(while_expr.cond.as_ref())?;
+        save_state!(self.result_used, true, self.handle_expr(while_expr.cond.as_ref())?);
         let exit_jmp = self.emit_jmp(OpCode::JmpFalse);
-        self.handle_expr(while_expr.body.as_ref())?;
-        let section = self.current_section_mut();
-        section.write_loop(ip);
-        section.patch_jmp(exit_jmp);
+        self.handle_loop_body(ip, while_expr.body.as_ref(), vec![exit_jmp])
+    }
+
+    /// the body loops back to start until a break jumps past it, the value of the body is never
+    /// used so an iteration leaves nothing on the stack.
+    fn handle_loop_body(
+        &mut self,
+        start: usize,
+        body: &HirExpr,
+        exits: Vec<usize>,
+    ) -> Result<(), BuildError> {
+        self.loops.push(LoopContext {
+            start,
+            scope_index: self.scope_index,
+            exits,
+        });
+        let result_used = std::mem::replace(&mut self.result_used, false);
+        let body = self.handle_expr(body).and_then(|_| self.emit_loop(start));
+        self.result_used = result_used;
+        let loop_context = self.loops.pop().unwrap();
+        body?;
+        for exit in loop_context.exits {
+            self.emit_patch(exit)?;
+        }
+        Ok(())
+    }
+
+    /// pops the locals of the current iteration before a break or continue, returns the start of
+    /// the loop.
+    fn leave_iteration(&mut self) -> usize {
+        let loop_context = self
+            .loops
+            .last()
+            .expect("break and continue are only resolved inside of a loop");
+        let (start, scope_index) = (loop_context.start, loop_context.scope_index);
+        let locals = self
+            .current_context()
+            .current_function()
+            .expect("invalid current function")
+            .locals
+            .iter()
+            .filter(|local| local.scope_level > scope_index)
+            .count();
+        (0..locals).for_each(|_| self.emit_op(OpCode::Pop));
+        start
+    }
+
+    /// the receiver of the link jumps past it when it is none, leaving none as the value of the
+    /// chain. A receiver is only evaluated once, a chain of links nests the chains.
+    fn handle_optional_chain(&mut self, link: &HirExpr) -> Result<(), BuildError> {
+        save_state!(self.result_used, true, self.handle_expr(link)?);
+        let exit = self
+            .chain_exits
+            .pop()
+            .expect("the link of an optional chain has a receiver");
+        match link.inner().kind() {
+            HirExprKind::Field(..) => self.emit_patch(exit),
+            // the function being called is below the receiver.
+            _ => {
+                let end = self.emit_jmp(OpCode::Jmp);
+                self.emit_patch(exit)?;
+                self.emit_op(OpCode::Pop);
+                self.emit_op(OpCode::Pop);
+                self.emit_op(OpCode::LoadUnit);
+                self.emit_patch(end)
+            }
+        }
+    }
+
+    /// the fallback is only evaluated when the value is none.
+    fn handle_coalesce(&mut self, coalesce_expr: &CoalesceExpr) -> Result<(), BuildError> {
+        save_state!(self.result_used, true, {
+            self.handle_expr(coalesce_expr.value.as_ref())?;
+            let fallback = self.emit_jmp(OpCode::JmpUnit);
+            let end = self.emit_jmp(OpCode::Jmp);
+            self.emit_patch(fallback)?;
+            self.emit_op(OpCode::Pop);
+            self.handle_expr(coalesce_expr.fallback.as_ref())?;
+            self.emit_patch(end)?;
+        });
         Ok(())
     }
 
@@ -847,17 +1899,112 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         let name_idx = self
             .current_context_mut()
             .current_section_mut()
-            .add_constant(Value::String(name));
+            .intern_constant(Value::String(name));
+
+        self.emit_index(OpCode::LoadStr, name_idx);
 
-        self.emit_op_u8(OpCode::LoadStr, name_idx);
-        for (_, field) in struct_expr.fields.iter() {
-            self.handle_expr(field.as_ref())?;
+        // constant fields are pooled once, the instance is copied from them when it is created.
+        if struct_expr
+            .fields
+            .iter()
+            .all(|(_, field)| Self::is_constant_literal(field))
+        {
+            let mut fields = self.vm.new_vec();
+            for (_, field) in struct_expr.fields.iter() {
+                let value = self.constant_literal(field)?;
+                fields.push(value);
+            }
+            let fields = self.vm.new_tuple(fields).map_err(Self::runtime_error)?;
+            let fields = Value::from(fields);
+            let context = self.current_context_mut();
+            let idx = context.current_section_mut().intern_constant(fields);
+            self.emit_index(OpCode::NewInstanceConst, idx);
+        } else {
+            save_state!(self.result_used, true, {
+                for (_, field) in struct_expr.fields.iter() {
+                    self.handle_expr(field.as_ref())?;
+                }
+            });
+            self.emit_op_u16(OpCode::NewInstance, struct_expr.fields.len() as u16);
         }
 
-        self.emit_op_u16(OpCode::NewInstance, struct_expr.fields.len() as u16);
+        self.register_finalizer(&struct_type);
         Ok(())
     }
 
+    /// an instance of a structure declared with `@finalize` is handed to the vm with its
+    /// `finalize` method, the vm calls it once the instance is collected.
+    fn register_finalizer(&mut self, struct_type: &Type) {
+        let entity = match struct_type.kind() {
+            TypeKind::Struct { entity } => entity.deref().borrow(),
+            _ => unreachable!(),
+        };
+        let structure = entity.as_struct();
+        if !structure.finalized {
+            return;
+        }
+        let struct_idx = self
+            .current_context_mut()
+            .load_global_in_function(entity.name());
+        self.emit_index(OpCode::LoadGlobal, struct_idx);
+        let finalizer = structure
+            .methods
+            .get("finalize")
+            .expect("a finalized structure has a finalize method");
+        let method_idx = finalizer.borrow().as_associated_function().index as u8;
+        self.emit_op_u8(OpCode::LoadAssoc, method_idx);
+        self.emit_op(OpCode::RegisterFinalizer);
+    }
+
+    /// literals, and tuples of them, that can be stored in the constant pool.
+    fn is_constant_literal(expr: &HirExpr) -> bool {
+        match expr.inner().kind() {
+            HirExprKind::Integer(..)
+            | HirExprKind::Float(..)
+            | HirExprKind::String(..)
+            | HirExprKind::Char(..)
+            | HirExprKind::Bool(..) => true,
+            HirExprKind::Tuple(tuple_expr) => tuple_expr
+                .elements
+                .iter()
+                .all(|element| Self::is_constant_literal(element)),
+            _ => false,
+        }
+    }
+
+    /// an allocation of the vm failing while the module is built.
+    fn runtime_error(err: oxide::Error) -> BuildError {
+        BuildError::CompilerError(err.to_string())
+    }
+
+    /// the pooled value of a constant literal. Pooled tuples are never copied, this is sound
+    /// because tuple elements cannot be assigned.
+    fn constant_literal(&mut self, expr: &HirExpr) -> Result<Value, BuildError> {
+        let value = match expr.inner().kind() {
+            HirExprKind::Integer(val) => Self::integer_constant(expr.ty(), *val).1,
+            HirExprKind::Float(val) => Self::float_constant(expr.ty(), *val).1,
+            HirExprKind::String(val) => Value::String(self.vm.intern_string(val)),
+            HirExprKind::Char(ch) => Value::Char(*ch),
+            HirExprKind::Bool(val) => Value::Bool(*val),
+            HirExprKind::Tuple(tuple_expr) => {
+                let mut elements = self.vm.new_vec();
+                for element in tuple_expr.elements.iter() {
+                    let value = self.constant_literal(element)?;
+                    elements.push(value);
+                }
+                Value::from(self.vm.new_tuple(elements).map_err(Self::runtime_error)?)
+            }
+            _ => {
+                return Err(BuildError::CompilerError(format!(
+                    "'{}' is not a constant literal",
+                    expr.inner().name()
+                )))
+            }
+        };
+
+        Ok(value)
+    }
+
     fn handle_field_expr(&mut self, field_expr: &FieldExpr) -> Result<(), BuildError> {
         save_state!(
             self.result_used,
@@ -878,7 +2025,14 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
     }
 
     fn load_integer(&mut self, ty: Rc<Type>, val: i64) -> Result<(), BuildError> {
-        let (op, value) = match ty.kind() {
+        let (op, value) = Self::integer_constant(ty, val);
+        let context = self.current_context_mut();
+        context.load_constant(op, value);
+        Ok(())
+    }
+
+    fn integer_constant(ty: Rc<Type>, val: i64) -> (OpCode, Value) {
+        match ty.kind() {
             TypeKind::U8 => {
                 let val: u8 = val.try_into().unwrap();
                 (OpCode::LoadU8, Value::U8(val))
@@ -909,23 +2063,22 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
             }
             TypeKind::I64 => (OpCode::LoadI64, Value::I64(val)),
             _ => panic!("Type Missmatch"),
-        };
+        }
+    }
 
+    fn load_float(&mut self, ty: Rc<Type>, val: OrderedFloat<f64>) -> Result<(), BuildError> {
+        let (op, value) = Self::float_constant(ty, val);
         let context = self.current_context_mut();
         context.load_constant(op, value);
         Ok(())
     }
 
-    fn load_float(&mut self, ty: Rc<Type>, val: OrderedFloat<f64>) -> Result<(), BuildError> {
-        let (op, value) = match ty.kind() {
+    fn float_constant(ty: Rc<Type>, val: OrderedFloat<f64>) -> (OpCode, Value) {
+        match ty.kind() {
             TypeKind::F32 => (OpCode::LoadF32, Value::F32(val.into_inner() as f32)),
             TypeKind::F64 => (OpCode::LoadF64, Value::F64(val.into_inner())),
             _ => panic!("Type Missmatch"),
-        };
-
-        let context = self.current_context_mut();
-        context.load_constant(op, value);
-        Ok(())
+        }
     }
 
     fn handle_variable(
@@ -945,7 +2098,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
 
             let global_idx = context.load_global_in_function(name);
             let section = context.current_section_mut();
-            section.write_arg(op, global_idx);
+            section.write_index(op, global_idx);
         } else {
             let op = if set_op {
                 OpCode::SetLocal
@@ -960,10 +2113,10 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 function
                     .look_up_local(name)
                     .expect("failed to find local")
-                    .stack_idx as u8
+                    .stack_idx as u32
             };
 
-            self.current_section_mut().write_arg(op, stack_idx);
+            self.emit_index(op, stack_idx);
         }
 
         Ok(())
@@ -990,7 +2143,7 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
                 _ => Err(BuildError::InvalidLiteralCast(expr.ty())),
             },
             HirExprKind::String(val) => {
-                let ox_string = self.vm.new_gc_string_from_str(val.as_str());
+                let ox_string = self.vm.intern_string(val.as_str());
                 Ok(Value::from(ox_string))
             }
             HirExprKind::Char(val) => Ok(Value::from(*val)),
@@ -999,7 +2152,9 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         }
     }
 
-    fn cleanup_top_scope(&mut self) {
+    /// pops the locals of the top scope. A value above them is kept, it is moved to the slot of
+    /// the first of them.
+    fn cleanup_top_scope(&mut self, keep_value: bool) {
         let current_level = self.scope_index;
         let locals = self
             .current_context()
@@ -1014,9 +2169,19 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
             }
             lost_locals += 1;
         }
+        let first_slot = locals
+            .get(locals.len() - lost_locals)
+            .map(|local| local.stack_idx);
         std::mem::drop(locals);
 
-        (0..lost_locals).for_each(|_| self.emit_op(OpCode::Pop));
+        let popped = match first_slot {
+            Some(slot) if keep_value => {
+                self.emit_index(OpCode::SetLocal, slot as u32);
+                lost_locals - 1
+            }
+            _ => lost_locals,
+        };
+        (0..popped).for_each(|_| self.emit_op(OpCode::Pop));
 
         let function = self
             .current_context_mut()
@@ -1028,3 +2193,3236 @@ impl<'vm, 'ctx> CodeGen<'vm, 'ctx> {
         assert_eq!(function.locals.len(), start);
     }
 }
+
+/// names of the flags set in value separated by `|`.
+fn display_flags(flags: &[(String, i64)], value: i64) -> String {
+    if value == 0 {
+        return "empty".to_owned();
+    }
+
+    flags
+        .iter()
+        .filter(|(_, bit)| value & bit != 0)
+        .map(|(name, _)| name.as_str())
+        .join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{module_api, BuildError, CodeGen};
+    use crate::{
+        analysis::{Analysis, EntityInfo},
+        error::Error,
+        ir::ast::AssignmentOp,
+        ir::hir::{HirExprKind, HirFile, HirStmtKind},
+        syntax::{Parser, BENCH_PREFIX},
+        system::{File, FileMap},
+        LanguageMode,
+    };
+    use oxide::{
+        compiled::MODULE_FILE_VERSION,
+        disassemble,
+        gc::Gc,
+        vm::{
+            BenchConfig, Budget, Capabilities, Capability, LogHandler, LogRecord, SliceResult,
+            Task, TaskEntry,
+        },
+        source_map::Step,
+        CompiledModule, OxModule, Value, Vm,
+    };
+    use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+    const STATUS: &str = "enum Status { Ok = 0, NotFound = 404, Gone }\n";
+
+    fn analyze(source: &str) -> Result<HirFile, Error> {
+        let file = File::raw_test(source.to_string());
+        let mut parser = Parser::new(&file);
+        let parsed = parser.init().and_then(|_| parser.parse_file())?;
+        Analysis::new().check(parsed, LanguageMode::Default)
+    }
+
+    fn check(source: &str) -> Result<HirFile, String> {
+        analyze(source).map_err(|err| err.to_string())
+    }
+
+    fn build(source: &str, vm: &mut Vm) -> Result<Gc<OxModule>, BuildError> {
+        let hir_file = check(source).unwrap();
+        vm.set_code_gen(true);
+        let module = CodeGen::build(&FileMap::new(), &hir_file, vm);
+        vm.set_code_gen(false);
+        module
+    }
+
+    fn run(source: &str) -> Value {
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        vm.run_module(module).unwrap();
+        *vm.top()
+    }
+
+    /// the value main returns, rendered while the vm holding it is alive.
+    fn render(source: &str) -> String {
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        vm.run_module(module).unwrap();
+        vm.top().to_string()
+    }
+
+    /// a sink for `echo` whose output is read after the run.
+    struct Echoed(Rc<RefCell<Vec<u8>>>);
+
+    impl std::io::Write for Echoed {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// what running main echoes.
+    pub(super) fn echoed(source: &str) -> String {
+        let mut vm = Vm::new();
+        let written = Rc::new(RefCell::new(vec![]));
+        vm.set_output(Box::new(Echoed(written.clone())));
+        let module = build(source, &mut vm).unwrap();
+        vm.run_module(module).unwrap();
+        let written = written.borrow().clone();
+        String::from_utf8(written).unwrap()
+    }
+
+    /// builds, verifies and runs every function of the module.
+    fn run_verified(source: &str) -> Value {
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        for value in module.objects().iter() {
+            if let Value::Function(function) = value {
+                function.section().verify().unwrap();
+            }
+        }
+        vm.run_module(module).unwrap();
+        *vm.top()
+    }
+
+    #[test]
+    fn test_enum_round_trips_through_integers() {
+        for (variant, discriminant) in &[("Ok", 0), ("NotFound", 404), ("Gone", 405)] {
+            let source = format!("{}fn main() i64 = Status.{} as i64\n", STATUS, variant);
+            assert!(matches!(run(&source), Value::I64(val) if val == *discriminant));
+
+            let source = format!(
+                "{}fn main() Option[Status] = Status.from_int(Status.{} as i64)\n",
+                STATUS, variant
+            );
+            assert!(matches!(run(&source), Value::I64(val) if val == *discriminant));
+        }
+
+        let source = format!("{}fn main() Option[Status] = Status.from_int(1)\n", STATUS);
+        assert!(matches!(run(&source), Value::Unit));
+        let source = format!("{}fn main() i16 = Status.Gone as i16\n", STATUS);
+        assert!(matches!(run(&source), Value::I16(405)));
+    }
+
+    #[test]
+    fn test_flags() {
+        let flags = "@flags\nenum Perm { Read, Write, Exec }\n";
+        let as_int =
+            |expr: &str| match run(&format!("{}fn main() i64 = ({}) as i64\n", flags, expr)) {
+                Value::I64(val) => val,
+                value => panic!("unexpected value {}", value),
+            };
+        assert_eq!(as_int("Perm.Read | Perm.Exec"), 5);
+        assert_eq!(as_int("(Perm.Read | Perm.Write) & !Perm.Write"), 1);
+        assert_eq!(as_int("!Perm.Write"), 5);
+        assert_eq!(as_int("Perm.Exec ^ Perm.Exec"), 0);
+        assert_eq!(as_int("Perm.empty()"), 0);
+
+        let contains = |expr: &str| match run(&format!("{}fn main() bool = {}\n", flags, expr)) {
+            Value::Bool(val) => val,
+            value => panic!("unexpected value {}", value),
+        };
+        assert!(contains("(Perm.Read | Perm.Write).contains(Perm.Write)"));
+        assert!(contains("(Perm.Read | Perm.Write).contains(Perm.empty())"));
+        assert!(!contains(
+            "(Perm.Read | Perm.Write).contains(Perm.Write | Perm.Exec)"
+        ));
+        assert!(!contains(
+            "((Perm.Read | Perm.Write) & !Perm.Write).contains(Perm.Write)"
+        ));
+
+        let display = |expr: &str| match run(&format!("{}fn main() = {}.display()\n", flags, expr))
+        {
+            Value::String(val) => val.as_str().to_owned(),
+            value => panic!("unexpected value {}", value),
+        };
+        assert_eq!(display("(Perm.Read | Perm.Write)"), "Read | Write");
+        assert_eq!(display("Perm.empty()"), "empty");
+
+        let source = format!("{}fn main() Option[Perm] = Perm.from_int(6)\n", flags);
+        assert!(matches!(run(&source), Value::I64(6)));
+        let source = format!("{}fn main() Option[Perm] = Perm.from_int(8)\n", flags);
+        assert!(matches!(run(&source), Value::Unit));
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let source = "fn main() i64 {\n    mut x i64 = 10\n    x -= 4\n    x *= 3\n    x\n}\n";
+        assert!(matches!(run(source), Value::I64(18)));
+
+        let error_at = |source: &str| {
+            let err = analyze(source).unwrap_err();
+            let start = err.pos().start();
+            (start.line(), start.column(), err.notes().to_vec())
+        };
+        // the operand is checked against the type of the generated binary expression but the
+        // error points at the user written operand.
+        let source = "fn main() i64 {\n    mut x i64 = 10\n    x += 1.5\n    x\n}\n";
+        assert_eq!(error_at(source), (3, 10, vec![]));
+        // an operator that does not apply to the lvalue is reported at the operator.
+        let point = "struct Point {\n    pub x i64\n}\n";
+        let source =
+            format!("{}fn main() {{\n    mut p = Point {{ x: 1 }}\n    p += p\n}}\n", point);
+        assert_eq!(error_at(&source), (6, 7, vec![]));
+        let source = "fn main() {\n    mut b = true\n    b += false\n}\n";
+        assert_eq!(error_at(source), (3, 7, vec![]));
+        assert_eq!(
+            check("fn main() {\n    let x i64 = 1\n    x *= 2\n}\n").map(|_| ()),
+            Err("attempting to mutable 'x' which is not mutable".to_string())
+        );
+
+        let source = format!(
+            "{}fn main() i64 {{\n    mut p = Point {{ x: 2 }}\n    p.x += 5\n    p.x *= 3\n    \
+            mut y i64 = 7\n    y -= p.x\n    y %= 5\n    y /= 2\n    y\n}}\n",
+            point
+        );
+        assert!(matches!(run(&source), Value::I64(-2)));
+        // the assignment keeps the operator and the operand for the backends.
+        let file = check(&source).unwrap();
+        let main = file.find_entity_by_name("main").unwrap();
+        let main = main.borrow();
+        let stmts = match main.kind() {
+            EntityInfo::Function(function) => match function.body.inner().kind() {
+                HirExprKind::Block(block) => block.stmts.clone(),
+                _ => panic!("main has no block"),
+            },
+            _ => panic!("main is not a function"),
+        };
+        let ops = stmts
+            .iter()
+            .filter_map(|stmt| match stmt.inner() {
+                HirStmtKind::Assignment(assignment) => Some((
+                    assignment.op,
+                    matches!(assignment.rhs.inner().kind(), HirExprKind::Binary(_)),
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            vec![
+                (AssignmentOp::PlusAssign, false),
+                (AssignmentOp::AstriskAssign, false),
+                (AssignmentOp::MinusAssign, false),
+                (AssignmentOp::PercentAssign, false),
+                (AssignmentOp::SlashAssign, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_flags() {
+        let cases = [
+            (
+                "@flags\nenum P { A = 1, B = 3 }\n",
+                "discriminant 3 of flags variant 'B' is not a power of two",
+            ),
+            (
+                "@flags\nenum P { A = 0 }\n",
+                "discriminant 0 of flags variant 'A' is not a power of two",
+            ),
+            (
+                "@flags\nenum P { A, B(i64) }\n",
+                "variant 'B' of a flags enum can not carry data",
+            ),
+            ("@bits\nenum P { A }\n", "unknown attribute 'bits'"),
+            (
+                "@flags\nfn main() i64 = 1\n",
+                "attribute 'flags' can only be applied to an enum",
+            ),
+            (
+                "@flags\nenum P { A }\nenum Q { B }\nfn main() P = P.A | Q.B\n",
+                "incompatible types, expected 'test.P' and found 'test.Q'",
+            ),
+        ];
+        for (source, error) in &cases {
+            assert_eq!(check(source).err().as_deref(), Some(*error));
+        }
+    }
+
+    #[test]
+    fn test_invalid_discriminants() {
+        let cases = [
+            (
+                "enum E { A = 1, B = 1 }\n",
+                "discriminant 1 of variant 'B' is already used by variant 'A'",
+            ),
+            // the implicit value after B collides with A.
+            (
+                "enum E { A = 1, B = 0, C }\n",
+                "discriminant 1 of variant 'C' is already used by variant 'A'",
+            ),
+            (
+                "enum E { A, B(i64) = 2 }\n",
+                "variant 'B' carries data and can not have a discriminant",
+            ),
+            (
+                "enum E { A = 300 }\nfn main() u8 = E.A as u8\n",
+                "discriminant 300 of variant 'A' does not fit in 'u8'",
+            ),
+            (
+                "enum E { A, B(i64) }\nfn main() i64 = E.A as i64\n",
+                "enum 'test.E' has variants with data and can not be converted to an integer",
+            ),
+        ];
+        for (source, error) in &cases {
+            assert_eq!(check(source).err().as_deref(), Some(*error));
+        }
+    }
+
+    #[test]
+    fn test_many_locals() {
+        let mut source = "fn main() i64 {\n".to_string();
+        for i in 0..1000 {
+            source += &format!("    mut x{} i64 = {}\n", i, i);
+        }
+        source += "    x0 + x300 + x999\n}\n";
+        assert!(matches!(run_verified(&source), Value::I64(1299)));
+    }
+
+    #[test]
+    fn test_let_bindings_infer_their_type() {
+        let source = "struct Point {\n    pub x i64\n    pub fn twice(self) i64 = self.x * 2\n}\n\
+            fn make() Point = Point { x: 3 }\n\
+            fn main() i64 {\n    let point = make()\n    let x = point.x\n    \
+            let twice = point.twice()\n    let pair = (x, make().x)\n    \
+            pair.0 + pair.1 + twice\n}\n";
+        assert!(matches!(run_verified(source), Value::I64(12)));
+        assert_eq!(
+            check(&source.replace("let x = point.x", "let x i32 = point.x")).map(|_| ()),
+            Err("incompatible types, expected 'i32' and found 'i64'".to_string())
+        );
+
+        // the type of a function or a global declared later is inferred the same.
+        let later = "fn main() i64 {\n    let point = make()\n    point.x\n}\n\
+            struct Point {\n    pub x i64\n}\nfn make() Point = Point { x: 3 }\n";
+        assert!(check(later).is_ok());
+        let file = check("let copy = limit\nlet limit u8 = 200\nfn main() u8 = copy\n").unwrap();
+        let copy = file.find_entity_by_name("copy").unwrap();
+        assert_eq!(copy.borrow().ty().to_string(), "u8");
+
+        // a literal takes the width of the annotation.
+        assert!(matches!(run("fn main() u8 {\n    let x u8 = 255\n    x\n}\n"), Value::U8(255)));
+        let cannot_infer =
+            Err("cannot infer type, a binding needs a type annotation or an initial value"
+                .to_string());
+        for source in [
+            "fn main() i64 {\n    let x\n    1\n}\n",
+            "fn main() i64 {\n    mut x\n    x = 1\n    x\n}\n",
+            "let x\nfn main() i64 = 1\n",
+        ] {
+            assert_eq!(check(source).map(|_| ()), cannot_infer);
+        }
+    }
+
+    #[test]
+    fn test_many_constants() {
+        // every constant is distinct so the pool holds all of them.
+        let mut body = String::new();
+        for i in 0..100_000 {
+            body += &format!("    x = {}\n", i);
+        }
+        let source = format!("fn main() i64 {{\n    mut x i64 = 0\n{}    x\n}}\n", body);
+        assert!(matches!(run_verified(&source), Value::I64(99_999)));
+
+        // branches keep a 16 bit distance, the diagnostic names the function.
+        let source = format!(
+            "fn long() i64 {{\n    mut x i64 = 0\n    while x < 0 {{\n{}    }}\n    x\n}}\nfn main() i64 = long()\n",
+            body
+        );
+        let mut vm = Vm::new();
+        let err = build(&source, &mut vm).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "function 'long' jumps over more than 65535 bytes of code"
+        );
+    }
+
+    #[test]
+    fn test_conditions_must_be_bool() {
+        let error = |body: &str| {
+            let err =
+                analyze(&format!("fn main(count i64, ratio f32) i64 = {}\n", body)).unwrap_err();
+            (err.to_string(), err.notes().len())
+        };
+        let integer = |construct: &str| {
+            (
+                format!(
+                    "the condition of '{}' must be 'bool', found 'i64'",
+                    construct
+                ),
+                1,
+            )
+        };
+        assert_eq!(error("if count { 1 } else { 0 }"), integer("if"));
+        assert_eq!(
+            error("if count > 1 { 1 } elif count { 2 } else { 0 }"),
+            integer("elif")
+        );
+        assert_eq!(
+            error("{\n    while count {\n        count\n    }\n    count\n}"),
+            integer("while")
+        );
+        // only an integer condition suggests comparing against zero.
+        assert_eq!(
+            error("if ratio { 1 } else { 0 }"),
+            (
+                "the condition of 'if' must be 'bool', found 'f32'".to_string(),
+                0
+            )
+        );
+        assert!(
+            analyze("fn main(count i64) bool = if count != 0 { true } else { false }\n").is_ok()
+        );
+
+        let arithmetic = |op: &str| {
+            format!(
+                "operator '{}' can not be applied to 'bool', cast it with 'as' to use it as an integer",
+                op
+            )
+        };
+        assert_eq!(error("(count > 0) + 1").0, arithmetic("+"));
+        assert_eq!(error("count * (ratio > 1.0)").0, arithmetic("*"));
+        assert_eq!(
+            error("((count > 0) & (count < 9)) as i64").0,
+            arithmetic("&")
+        );
+        assert!(analyze("fn main(count i64) bool = (count > 0) == true\n").is_ok());
+    }
+
+    #[test]
+    fn test_bool_integer_conversions() {
+        let run_main = |ty: &str, body: &str| run_verified(&format!("fn main() {} {}\n", ty, body));
+        assert!(matches!(run_main("i64", "= true as i64"), Value::I64(1)));
+        assert!(matches!(run_main("u8", "= false as u8"), Value::U8(0)));
+        assert!(matches!(
+            run_main("bool", "= 0 as bool"),
+            Value::Bool(false)
+        ));
+        assert!(matches!(
+            run_main("bool", "= 42 as bool"),
+            Value::Bool(true)
+        ));
+
+        let local = |ty: &str, init: &str, expr: &str| {
+            let body = format!("{{\n    mut x {} = {}\n    {}\n}}", ty, init, expr);
+            run_main(
+                if expr.ends_with("bool") {
+                    "bool"
+                } else {
+                    "i64"
+                },
+                &body,
+            )
+        };
+        assert!(matches!(local("bool", "true", "x as i64"), Value::I64(1)));
+        assert!(matches!(local("bool", "false", "x as i64"), Value::I64(0)));
+        assert!(matches!(local("i64", "0", "x as bool"), Value::Bool(false)));
+        assert!(matches!(
+            local("i64", "255", "x as bool"),
+            Value::Bool(true)
+        ));
+        assert!(matches!(
+            local("i64", "7", "(x as bool) as i64"),
+            Value::I64(1)
+        ));
+
+        // a constant operand is folded to a literal.
+        let file = check("fn main() i64 = true as i64\n").unwrap();
+        let main = file.find_entity_by_name("main").unwrap();
+        let main = main.borrow();
+        match main.kind() {
+            EntityInfo::Function(function) => {
+                assert!(matches!(
+                    function.body.inner().kind(),
+                    HirExprKind::Integer(1)
+                ))
+            }
+            _ => panic!("main is not a function"),
+        };
+    }
+
+    const FUNCTIONS: &str = "fn add(a i64, b i64) i64 = a + b\n\
+        fn sub(a i64, b i64) i64 = a - b\nfn neg(a i64) i64 = -a\n";
+
+    #[test]
+    fn test_function_equality() {
+        let main = |body: &str| {
+            format!(
+                "{}fn main() bool {{\n    let f = add\n    mut g = sub\n{}\n}}\n",
+                FUNCTIONS, body
+            )
+        };
+        let compare = |body: &str| match run(&main(body)) {
+            Value::Bool(val) => val,
+            value => panic!("unexpected value {}", value),
+        };
+        // two references to the same function are equal.
+        assert!(compare("    f == add"));
+        assert!(!compare("    f != add"));
+        assert!(!compare("    g == f"));
+        assert!(compare("    g = add\n    g == f"));
+
+        assert_eq!(
+            check(&main("    f == neg")).map(|_| ()),
+            Err("incompatible types, expected '(i64, i64) i64' and found '(i64) i64'".to_string())
+        );
+        // functions are not ordered.
+        assert_eq!(
+            check(&main("    f < g")).map(|_| ()),
+            Err("incompatible types for operator '<': left '(i64, i64) i64', \
+                right '(i64, i64) i64'"
+                .to_string())
+        );
+
+        // a closure is only equal to itself, the same literal evaluated twice is two closures
+        // even when they capture the same values. A literal capturing nothing is a function.
+        const CLOSURES: &str = "fn adder(n i64) fn(i64) i64 = fn(x i64) i64 = x + n\n\
+            fn one() fn(i64) i64 = fn(x i64) i64 = x + 1\n";
+        let closures = |body: &str| format!("{}{}", CLOSURES, main(body));
+        let compare = |body: &str| match run(&closures(body)) {
+            Value::Bool(val) => val,
+            value => panic!("unexpected value {}", value),
+        };
+        assert!(!compare("    adder(1) == adder(1)"));
+        assert!(compare("    let a = adder(1)\n    let b = a\n    a == b"));
+        assert!(compare("    one() == one()"));
+
+        // a function renders as its path, a literal as where it is.
+        let source = format!("{}{}fn main() = (sub, adder(1), one())\n", CLOSURES, FUNCTIONS);
+        assert_eq!(render(&source), "<fn sub>, <closure at test:1>, <closure at test:2>");
+    }
+
+    #[test]
+    fn test_dispatch_table() {
+        let source = format!(
+            "{}fn main() (i64, string, fn(i64, i64) i64) {{
+    let scale i64 = 10
+    mut table = Map[string, fn(i64, i64) i64].new()
+    table.insert(\"add\", add)
+    table.insert(\"sub\", sub)
+    table.insert(\"scaled\", fn(a i64, b i64) i64 = (a * scale) + b)
+    mut names = Map[fn(i64, i64) i64, string].new()
+    names.insert(add, \"add\")
+    names.insert(sub, \"sub\")
+
+    mut total i64 = 0
+    mut called = \"\"
+    let calls = [\"add\", \"scaled\", \"sub\", \"mul\"]
+    mut i i64 = 0
+    while i < 4 {{
+        let op = table.get(calls[i]) ?? sub
+        total = op(total, 3)
+        called += (names.get(op) ?? \"unnamed\") + \" \"
+        i += 1
+    }}
+    (total, called, table.get(\"add\") ?? sub)
+}}
+",
+            FUNCTIONS
+        );
+        assert_eq!(render(&source), "27, add unnamed sub sub , <fn add>");
+    }
+
+    #[test]
+    fn test_echo_of_calls() {
+        let golden = [
+            (include_str!("../../../test/vm/echo_call.au"), "6\n1\n1\n"),
+            (include_str!("../../../test/vm/echo_method_call.au"), "42\n1\n"),
+            (include_str!("../../../test/vm/echo_loop.au"), "0\n1\n4\n9\nDone\n"),
+        ];
+        for (source, output) in golden.iter() {
+            assert_eq!(echoed(source), *output);
+        }
+
+        let source = format!(
+            "{}fn main() {{\n    let add = fn(x i64) i64 = x + 1\n    echo add(1)\n    \
+            echo identity(3)\n    echo apply(add, 4)\n}}\n",
+            GENERICS
+        );
+        assert_eq!(echoed(&source), "2\n3\n5\n");
+
+        // the operands of other statements are used as well.
+        let source = "struct Pair {\n    pub left i64\n    pub right i64\n}\n\
+            fn one() i64 = 1\n\
+            fn main() i64 {
+    mut v Vec[i64] = Vec[i64].new()
+    v.push(0)
+    v.push(0)
+    v[one()] = 7
+    Pair { left: one(), right: v[one()] }
+    mut pair = Pair { left: one(), right: 2 }
+    pair.right = v[one()] + one()
+    v[1] + pair.right
+}
+";
+        assert!(matches!(run_verified(source), Value::I64(15)));
+    }
+
+    #[test]
+    fn test_strings() {
+        let source = "fn greet(name string) string = \"hello \" + name
+fn main() string {
+    mut greeting = greet(\"world\")
+    greeting += \"!\"
+    greeting
+}
+";
+        assert_eq!(run(source).to_string(), "hello world!");
+
+        let compare = |body: &str| match run(&format!("fn main() bool = {}\n", body)) {
+            Value::Bool(val) => val,
+            value => panic!("unexpected value {}", value),
+        };
+        assert!(compare("\"ab\" + \"c\" == \"abc\""));
+        assert!(compare("\"ab\" != \"abc\""));
+        assert!(!compare("\"ab\" == \"ba\""));
+
+        // strings are concatenated and compared, nothing else.
+        assert_eq!(
+            check("fn main() string = \"ab\" - \"b\"\n").map(|_| ()),
+            Err("incompatible types for operator '-': left 'string', right 'string'".to_string())
+        );
+        assert_eq!(
+            check("fn main() bool = \"ab\" == 1\n").map(|_| ()),
+            Err("incompatible types, expected 'string' and found 'i32'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_constant_literals_at_size() {
+        // the literals are pooled, every row kept in the map holds an array of its own and the
+        // pooled tuple is shared by every iteration.
+        let literal = (0..1000).map(|idx| idx.to_string()).collect::<Vec<_>>();
+        let literal = literal.join(", ");
+        let source = format!(
+            "struct Row {{
+    pub xs [i64; 1000]
+}}
+
+fn main() i32 {{
+    mut rows Map[i64, Row] = Map[i64, Row].new()
+    mut sum i32 = 0
+    mut i i64 = 0
+    while i < 1000 {{
+        mut row = Row {{ xs: [{literal}] }}
+        row.xs[i] = -1
+        if i % 100 == 0 {{
+            rows.insert(i, row)
+        }}
+        sum += ({literal}).999
+        i += 1
+    }}
+    gc_collect()
+    let missing = Row {{ xs: [{literal}] }}
+    mut copies i32 = 0
+    i = 0
+    while i < 1000 {{
+        let row = rows.get(i) ?? missing
+        let j = (i + 1) % 1000
+        if row.xs[i] < 0 {{
+            if row.xs[j] == j {{
+                copies += 1
+            }}
+        }}
+        i += 100
+    }}
+    copies * 1000000 + sum
+}}
+"
+        );
+        assert_eq!(run_verified(&source).to_string(), "10999000");
+    }
+
+    #[test]
+    fn test_arrays() {
+        let source = "fn main() i32 {
+    let xs [i32; 4] = [1, 2, 3, 4]
+    let i u8 = 3
+    xs[2] + xs[i]
+}
+";
+        assert_eq!(run_verified(source).to_string(), "7");
+        let source = "fn pair(a i64) [i64; 2] = [a, a * 2]\nfn main() i64 = pair(10)[1]\n";
+        assert_eq!(run(source).to_string(), "20");
+
+        // the index is checked when the program runs.
+        let source = "fn main() i32 {\n    let xs = [1, 2]\n    let i i64 = 5\n    xs[i]\n}\n";
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        assert_eq!(
+            vm.run_module(module).unwrap_err().to_string(),
+            "index 5 is out of bounds for an array of length 2"
+        );
+
+        let err = |source: &str| check(source).map(|_| ()).unwrap_err();
+        assert_eq!(
+            err("fn main() {\n    let xs [i32; 3] = [1, 2]\n}\n"),
+            "expected an array of 3 elements, found 2"
+        );
+        assert_eq!(
+            err("fn main() {\n    let xs = [1, true]\n}\n"),
+            "incompatible types, expected 'i32' and found 'bool'"
+        );
+        assert_eq!(
+            err("fn main() {\n    let xs = []\n}\n"),
+            "the element type of an empty array can not be inferred, declare its type"
+        );
+        assert_eq!(
+            err("fn main() i32 {\n    let xs = [1, 2]\n    xs[true]\n}\n"),
+            "incompatible types, expected '{integer}' and found 'bool'"
+        );
+    }
+
+    #[test]
+    fn test_vectors() {
+        let source = "fn main() i64 {
+    mut v Vec[i64] = Vec[i64].new()
+    mut i i64 = 0
+    while i < 1000 {
+        v.push(i)
+        i += 1
+    }
+    v[10] = 7
+    v[10] += 1
+    let last i64 = v.pop()
+    (last + v[10]) + v.len()
+}
+";
+        // the buffer is moved to a bigger one a few times as it grows, not on every push.
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        let before = vm.heap_stats().buffer_reallocations;
+        vm.run_module(module).unwrap();
+        let reallocations = vm.heap_stats().buffer_reallocations - before;
+        assert!((5..20).contains(&reallocations), "{}", reallocations);
+        assert_eq!(vm.top().to_string(), "2006");
+
+        // popping from an empty vector is an error of the program.
+        let source = "fn main() i64 {\n    mut v Vec[i64] = Vec[i64].new()\n    v.pop()\n}\n";
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        assert_eq!(
+            vm.run_module(module).unwrap_err().to_string(),
+            "cannot pop from an empty vector"
+        );
+
+        let err = |source: &str| check(source).map(|_| ()).unwrap_err();
+        assert_eq!(
+            err("fn main() {\n    let v Vec[i64] = Vec[i64].new()\n    v.push(1)\n}\n"),
+            "incompatible types, expected 'mut Vec[i64]' and found 'Vec[i64]'"
+        );
+        assert_eq!(
+            err("fn main() {\n    let v Vec[i64] = Vec[i64].new()\n    v[0] = 1\n}\n"),
+            "attempting to mutable 'v' which is not mutable"
+        );
+        assert_eq!(
+            err("fn main() i64 {\n    let v Vec[i64] = Vec[i64].new()\n    v.size()\n}\n"),
+            "use of undeclared method 'size' of type 'Vec[i64]'"
+        );
+        assert_eq!(
+            err("fn main() {\n    mut v Vec[i64] = Vec[i64].new()\n    v.push(true)\n}\n"),
+            "incompatible types, expected 'i64' and found 'bool'"
+        );
+    }
+
+    /// `VECTOR` is a vector of 0 to 7 and a second one of 100 to 103 as the source of copies.
+    const VECTOR: &str = "fn main() Vec[i64] {
+    mut v Vec[i64] = Vec[i64].with_capacity(8)
+    mut i i64 = 0
+    while i < 8 {
+        v.push(i)
+        i += 1
+    }
+    mut w Vec[i64] = Vec[i64].new()
+    w.push(100)
+    w.push(101)
+    w.push(102)
+    w.push(103)
+";
+
+    #[test]
+    fn test_vector_bulk_operations() {
+        // the vector is rendered while the vm holding it is alive.
+        let vector = |body: &str| {
+            let mut vm = Vm::new();
+            let module = build(&format!("{}{}    v\n}}\n", VECTOR, body), &mut vm).unwrap();
+            vm.run_module(module).unwrap();
+            vm.top().to_string()
+        };
+        assert_eq!(vector(""), "[0, 1, 2, 3, 4, 5, 6, 7]");
+        assert_eq!(vector("    v.extend(w)\n"), "[0, 1, 2, 3, 4, 5, 6, 7, 100, 101, 102, 103]");
+        assert_eq!(
+            vector("    v.extend(v)\n"),
+            "[0, 1, 2, 3, 4, 5, 6, 7, 0, 1, 2, 3, 4, 5, 6, 7]"
+        );
+        assert_eq!(vector("    v.copy_from(w, 1, 5, 3)\n"), "[0, 1, 2, 3, 4, 101, 102, 103]");
+        assert_eq!(vector("    v.fill(9)\n"), "[9, 9, 9, 9, 9, 9, 9, 9]");
+        assert_eq!(vector("    v.fill_range(2, 5, 9)\n"), "[0, 1, 9, 9, 9, 5, 6, 7]");
+        assert_eq!(vector("    v.fill_range(3, 3, 9)\n"), "[0, 1, 2, 3, 4, 5, 6, 7]");
+        assert_eq!(vector("    v = Vec[i64].with_capacity(0)\n"), "[]");
+
+        // copies within one vector behave as if through a temporary in both directions.
+        assert_eq!(vector("    v.copy_from(v, 0, 2, 5)\n"), "[0, 1, 0, 1, 2, 3, 4, 7]");
+        assert_eq!(vector("    v.copy_from(v, 2, 0, 5)\n"), "[2, 3, 4, 5, 6, 5, 6, 7]");
+        assert_eq!(vector("    v.copy_from(v, 0, 0, 8)\n"), "[0, 1, 2, 3, 4, 5, 6, 7]");
+    }
+
+    #[test]
+    fn test_vector_bulk_operation_bounds() {
+        let error = |body: &str| {
+            let mut vm = Vm::new();
+            let module = build(&format!("{}{}    v\n}}\n", VECTOR, body), &mut vm).unwrap();
+            vm.run_module(module).unwrap_err().to_string()
+        };
+        // the source range, the destination range and the length.
+        assert_eq!(
+            error("    v.copy_from(w, 2, 0, 3)\n"),
+            "range 2..5 is out of bounds for a list of length 4"
+        );
+        assert_eq!(
+            error("    v.copy_from(w, 0, 6, 3)\n"),
+            "range 6..9 is out of bounds for a list of length 8"
+        );
+        assert_eq!(
+            error("    v.copy_from(v, 4, 0, 5)\n"),
+            "range 4..9 is out of bounds for a list of length 8"
+        );
+        assert_eq!(
+            error("    v.copy_from(w, 5, 0, 0)\n"),
+            "range 5..5 is out of bounds for a list of length 4"
+        );
+        assert_eq!(
+            error("    v.copy_from(w, 0, 0, -1)\n"),
+            "index -1 is out of bounds for an array of length 4"
+        );
+        assert_eq!(
+            error("    v.copy_from(w, -1, 0, 1)\n"),
+            "index -1 is out of bounds for an array of length 4"
+        );
+        assert_eq!(
+            error("    v.copy_from(w, 0, -1, 1)\n"),
+            "index -1 is out of bounds for an array of length 8"
+        );
+        assert_eq!(
+            error("    v.fill_range(5, 9, 0)\n"),
+            "range 5..9 is out of bounds for a list of length 8"
+        );
+        assert_eq!(
+            error("    v.fill_range(5, 2, 0)\n"),
+            "range 5..2 is out of bounds for a list of length 8"
+        );
+        assert_eq!(
+            error("    v.fill_range(-1, 2, 0)\n"),
+            "index -1 is out of bounds for an array of length 8"
+        );
+        assert_eq!(
+            error("    v.fill_range(0, -2, 0)\n"),
+            "index -2 is out of bounds for an array of length 8"
+        );
+        assert_eq!(
+            error("    v = Vec[i64].with_capacity(-3)\n"),
+            "cannot reserve a negative capacity of -3"
+        );
+    }
+
+    #[test]
+    fn test_vector_bulk_operation_types() {
+        let err = |source: &str| check(source).map(|_| ()).unwrap_err();
+        let bools = "    let b Vec[bool] = Vec[bool].new()\n";
+        assert_eq!(
+            err(&format!("{}{}    v.extend(b)\n    v\n}}\n", VECTOR, bools)),
+            "incompatible types, expected 'Vec[i64]' and found 'Vec[bool]'"
+        );
+        assert_eq!(
+            err(&format!("{}{}    v.copy_from(b, 0, 0, 1)\n    v\n}}\n", VECTOR, bools)),
+            "incompatible types, expected 'Vec[i64]' and found 'Vec[bool]'"
+        );
+        assert_eq!(
+            err(&format!("{}    v.fill(true)\n    v\n}}\n", VECTOR)),
+            "incompatible types, expected 'i64' and found 'bool'"
+        );
+        assert_eq!(
+            err(&format!("{}    w.fill_range(0, 1)\n    v\n}}\n", VECTOR)),
+            "attempting to call function expecting 4 parameters with 3 parameters"
+        );
+        assert_eq!(
+            err("fn main() {\n    let v Vec[i64] = Vec[i64].new()\n    v.fill(0)\n}\n"),
+            "incompatible types, expected 'mut Vec[i64]' and found 'Vec[i64]'"
+        );
+    }
+
+    #[test]
+    fn test_reserve_and_shrink() {
+        let source = "fn main() (i64, i64, i64, i64, i64) {
+    mut v Vec[i64] = Vec[i64].new()
+    v.reserve(100)
+    let reserved i64 = v.capacity()
+    mut i i64 = 0
+    while i < 100 {
+        v.push(i)
+        i += 1
+    }
+    let filled i64 = v.capacity()
+    while v.len() > 10 {
+        v.pop()
+    }
+    v.shrink_to_fit()
+    let shrunk i64 = v.capacity()
+    v.push(7)
+    mut m Map[i64, i64] = Map[i64, i64].new()
+    m.reserve(1000)
+    m.insert(1, 2)
+    (reserved, filled, shrunk, v.len(), m.len())
+}
+";
+        assert_eq!(render(source), "100, 100, 10, 11, 1");
+
+        let error = |count: &str| {
+            let source = format!(
+                "fn main() {{\n    mut n i64 = {}\n    mut v Vec[i64] = Vec[i64].new()\n    \
+                v.reserve(n)\n}}\n",
+                count
+            );
+            let mut vm = Vm::new();
+            let module = build(&source, &mut vm).unwrap();
+            vm.run_module(module).unwrap_err().to_string()
+        };
+        assert_eq!(error("-4"), "cannot reserve a negative capacity of -4");
+    }
+
+    #[test]
+    fn test_negative_constant_reserve() {
+        let err = |source: &str| check(source).map(|_| ()).unwrap_err();
+        let vector = "fn main() {\n    mut v Vec[i64] = Vec[i64].new()\n";
+        assert_eq!(
+            err(&format!("{}    v.reserve(-1)\n}}\n", vector)),
+            "cannot reserve a negative capacity of -1"
+        );
+        // a count folded from immutable globals is known while compiling.
+        let globals = "let BASE i64 = 4\nlet COUNT i64 = BASE - 10\n";
+        assert_eq!(
+            err(&format!("{}{}    v.reserve(COUNT)\n}}\n", globals, vector)),
+            "cannot reserve a negative capacity of -6"
+        );
+        let map = "fn main() {\n    mut m Map[i64, i64] = Map[i64, i64].new()\n";
+        assert_eq!(
+            err(&format!("{}    m.reserve(-2)\n}}\n", map)),
+            "cannot reserve a negative capacity of -2"
+        );
+        assert_eq!(
+            err(&format!("{}    v.reserve(true)\n}}\n", vector)),
+            "incompatible types, expected 'i64' and found 'bool'"
+        );
+        assert!(check(&format!("{}    v.reserve(0)\n}}\n", vector)).is_ok());
+    }
+
+    #[test]
+    fn test_shrink_to_fit_releases_memory() {
+        // the memory in use once the vector was emptied, shrunk or not, and a push after it.
+        let usage = |shrink: &str| {
+            let source = format!(
+                "fn main() i64 {{
+    mut v Vec[i64] = Vec[i64].with_capacity(10000)
+    mut i i64 = 0
+    while i < 10000 {{
+        v.push(i)
+        i += 1
+    }}
+    while v.len() > 0 {{
+        v.pop()
+    }}
+{}    v.push(7)
+    v.push(8)
+    v[0] + v[1]
+}}
+",
+                shrink
+            );
+            let mut vm = Vm::new();
+            let module = build(&source, &mut vm).unwrap();
+            vm.run_module(module).unwrap();
+            (vm.top().to_string(), vm.heap_stats().memory_usage)
+        };
+        let (kept, kept_usage) = usage("");
+        let (shrunk, shrunk_usage) = usage("    v.shrink_to_fit()\n");
+        assert_eq!(kept, "15");
+        assert_eq!(shrunk, "15");
+        assert!(shrunk_usage + 9_000 * std::mem::size_of::<i64>() < kept_usage);
+    }
+
+    #[test]
+    fn test_maps() {
+        let source = "fn main() i64 {
+    mut m Map[i64, i64] = Map[i64, i64].new()
+    mut i i64 = 0
+    while i < 1000 {
+        m.insert(i, i * 2)
+        i += 1
+    }
+    m.insert(10, 7)
+    let removed i64 = m.remove(20) ?? 0
+    let missing i64 = m.get(20) ?? 5
+    (m.get(10) ?? 0) + (m.get(999) ?? 0) + removed + missing + m.len()
+}
+";
+        assert_eq!(run(source).to_string(), "3049");
+
+        let source = "fn main() bool {
+    mut names Map[string, bool] = Map[string, bool].new()
+    names.insert(\"ada\", true)
+    let key string = \"ad\" + \"a\"
+    if names.contains(\"bob\") {
+        return false
+    }
+    names.contains(key)
+}
+";
+        assert_eq!(run(source).to_string(), "true");
+
+        let err = |source: &str| check(source).map(|_| ()).unwrap_err();
+        assert_eq!(
+            err("fn main() {\n    let m Map[f64, i64] = Map[f64, i64].new()\n}\n"),
+            "map keys must be integers, bools, chars, strings or functions, found type 'f64'"
+        );
+        assert_eq!(
+            err("fn main() {\n    let m Map[i64] = Map[i64].new()\n}\n"),
+            "'Map' takes 2 type arguments, found 1"
+        );
+        let declared = "fn main() {\n    let m Map[i64, i64] = Map[i64, i64].new()\n";
+        assert_eq!(
+            err(&format!("{}    m.insert(1, 2)\n}}\n", declared)),
+            "incompatible types, expected 'mut Map[i64, i64]' and found 'Map[i64, i64]'"
+        );
+        // a missing key is none, the value is an option.
+        assert_eq!(
+            err(&format!("{}    let x i64 = m.get(1)\n}}\n", declared)),
+            "incompatible types, expected 'i64' and found 'Option[i64]'"
+        );
+    }
+
+    #[test]
+    fn test_weak_memoization_cache() {
+        // the cache holds weak references, once the values are no longer used they are
+        // collected and their entries read none.
+        let source = "struct Big {
+    pub id i64
+    pub items Vec[i64]
+}
+
+fn load(id i64) Big {
+    mut items Vec[i64] = Vec[i64].new()
+    mut i i64 = 0
+    while i < 16 {
+        items.push(id * i)
+        i += 1
+    }
+    Big { id: id, items: items }
+}
+
+fn live(cache Map[i64, Weak[Big]]) i64 {
+    mut count i64 = 0
+    mut id i64 = 0
+    while id < cache.len() {
+        if (cache.get(id)?.get()?.id ?? -1) == id {
+            count += 1
+        }
+        id += 1
+    }
+    count
+}
+
+fn main() i64 {
+    mut cache Map[i64, Weak[Big]] = Map[i64, Weak[Big]].new()
+    let kept = load(0)
+    cache.insert(0, weak(kept))
+    mut used Vec[Big] = Vec[Big].new()
+    mut id i64 = 1
+    while id < 4 {
+        let big = load(id)
+        cache.insert(id, weak(big))
+        used.push(big)
+        id += 1
+    }
+    let before = live(cache)
+    used = Vec[Big].new()
+    gc_collect()
+    (before * 10 + live(cache)) * 100 + kept.items.len()
+}
+";
+        assert_eq!(run(source).to_string(), "4116");
+
+        let err = |source: &str| check(source).map(|_| ()).unwrap_err();
+        assert_eq!(
+            err("fn main() {\n    let w = weak(1)\n}\n"),
+            "a weak reference can only refer to a heap object, found type 'i32'"
+        );
+        assert_eq!(
+            err("fn keep(w Weak[bool]) {}\n"),
+            "a weak reference can only refer to a heap object, found type 'bool'"
+        );
+        assert_eq!(
+            err("fn main() {\n    gc_collect(1)\n}\n"),
+            "attempting to call function expecting 0 parameters with 1 parameters"
+        );
+        let source = "fn main() {\n    let w = weak(\"name\")\n    let s string = w.get()\n}\n";
+        assert_eq!(err(source), "incompatible types, expected 'string' and found 'Option[string]'");
+    }
+
+    #[test]
+    fn test_finalized_struct() {
+        // every handle that is dropped is closed once, the one still in use is not.
+        let source = "@finalize
+struct Handle {
+    pub id i64
+    pub closed Vec[i64]
+
+    pub fn finalize(mut self) {
+        self.closed.push(self.id)
+    }
+}
+
+fn open(id i64, closed Vec[i64]) Handle {
+    Handle { id: id, closed: closed }
+}
+
+fn main() i64 {
+    mut closed Vec[i64] = Vec[i64].new()
+    let kept = open(100, closed)
+    mut id i64 = 0
+    while id < 5 {
+        open(id, closed)
+        id += 1
+    }
+    gc_collect()
+    gc_collect()
+    mut sum i64 = 0
+    id = 0
+    while id < closed.len() {
+        sum += closed[id]
+        id += 1
+    }
+    (closed.len() * 100 + sum) * 1000 + kept.id
+}
+";
+        assert_eq!(run(source).to_string(), "510100");
+
+        let err = |source: &str| check(source).map(|_| ()).unwrap_err();
+        let missing = "struct 'Handle' is declared '@finalize' without a method 'finalize(self)' \
+            returning unit";
+        assert_eq!(err("@finalize\nstruct Handle {\n    pub id i64\n}\n"), missing);
+        let source = "@finalize\nstruct Handle {\n    pub fn finalize(self) i64 = 1\n}\n";
+        assert_eq!(err(source), missing);
+        assert_eq!(
+            err("@finalize(1)\nstruct Handle {\n    pub fn finalize(self) {}\n}\n"),
+            "attribute 'finalize' takes no argument"
+        );
+        assert_eq!(
+            err("@finalize\nfn main() {}\n"),
+            "attribute 'finalize' can only be applied to a struct"
+        );
+        assert_eq!(
+            err("@flags\nstruct Handle {\n    pub id i64\n}\n"),
+            "attribute 'flags' can only be applied to an enum"
+        );
+    }
+
+    #[test]
+    fn test_finalizer_errors_are_reported() {
+        let source = "@finalize
+struct Handle {
+    pub id i64
+
+    pub fn finalize(self) {
+        todo()
+    }
+}
+
+fn open(id i64) Handle = Handle { id: id }
+
+fn main() i64 {
+    open(7)
+    gc_collect()
+    1
+}
+";
+        let mut vm = Vm::new();
+        let errors = Rc::new(RefCell::new(vec![]));
+        let record = errors.clone();
+        vm.set_finalizer_error_handler(Box::new(move |err| record.borrow_mut().push(err)));
+        let module = build(source, &mut vm).unwrap();
+        vm.run_module(module).unwrap();
+        // the failing finalizer does not stop the script.
+        assert_eq!(vm.top().to_string(), "1");
+        let errors = errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("finalizer for Handle failed: "), "{}", errors[0]);
+    }
+
+    #[test]
+    fn test_running_out_of_memory_is_an_error() {
+        let source = "struct Pair {\n    pub left i64\n    pub right string\n}\n\
+            fn main() i64 {
+    mut v Vec[i64] = Vec[i64].new()
+    mut m Map[i64, string] = Map[i64, string].new()
+    mut text string = \"\"
+    mut total i64 = 0
+    mut i i64 = 0
+    while i < 50 {
+        v.push(i)
+        text = text + \"x\"
+        m.insert(i, text)
+        let pair = (i, Pair { left: i, right: text })
+        total += pair.1.left
+        i += 1
+    }
+    total + v.len() + m.len()
+}
+";
+        // every allocation after the budget fails, the program either finishes or stops with
+        // the error, it never aborts.
+        let mut failed = 0;
+        let mut budget = 0;
+        loop {
+            let mut vm = Vm::new();
+            let module = build(source, &mut vm).unwrap();
+            vm.fail_allocations_after(Some(budget));
+            match vm.run_module(module) {
+                Ok(()) => {
+                    assert_eq!(vm.top().to_string(), "1325");
+                    break;
+                }
+                Err(err) => assert_eq!(err.to_string(), "out of memory", "{}", budget),
+            }
+            failed += 1;
+            budget += 3;
+        }
+        assert!(failed > 50, "{}", failed);
+    }
+
+    #[test]
+    fn test_float_spellings() {
+        let value = |ty: &str, expr: &str| {
+            let source = format!("fn main() {} {{\n    let x {} = {}\n    x\n}}\n", ty, ty, expr);
+            run(&source).to_string()
+        };
+        assert_eq!(value("f64", "1e300"), "1e300");
+        assert_eq!(value("f64", "0.1"), "0.1");
+        assert_eq!(value("f32", "3.0"), "3.0");
+        // negative zero is kept apart from zero as a constant and compares equal to it.
+        assert_eq!(value("f64", "-0.0"), "-0.0");
+        let source = "fn main() bool {
+    let zero f64 = 0.0
+    let negative f64 = -0.0
+    zero == negative
+}
+";
+        assert_eq!(run(source).to_string(), "true");
+        let source = "fn main() f64 {\n    let a f64 = 0.1\n    a + 0.2\n}\n";
+        assert_eq!(run(source).to_string(), "0.30000000000000004");
+    }
+
+    #[test]
+    fn test_shifts() {
+        let source = "fn main() i64 {
+    mut x i64 = 1
+    x <<= 10
+    x ^= 3
+    let m i64 = -16
+    (x >> 2) + (m >> 2)
+}
+";
+        assert_eq!(run(source).to_string(), "252");
+        let source = "fn main() u8 {\n    let y u8 = 255\n    y >> 4\n}\n";
+        assert_eq!(run(source).to_string(), "15");
+
+        // the amount is an integer of the type of the value.
+        assert_eq!(
+            check("fn main(x i64, n u8) i64 = x << n\n").map(|_| ()),
+            Err("incompatible types, expected 'i64' and found 'u8'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_string_literals_are_interned() {
+        let source = "fn first() string = \"shared\"
+fn second() string = \"shared\"
+fn main() bool = first() == second()
+";
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        // the literal of both functions is one string.
+        assert_eq!(vm.interned_strings(), 1);
+        vm.run_module(module).unwrap();
+        assert!(matches!(vm.top(), Value::Bool(true)));
+    }
+
+    const CONFIG: &str = "struct Port { pub number i64 }\n\
+        struct Server { pub port Option[Port] }\n\
+        struct Config { pub server Option[Server] }\n";
+
+    #[test]
+    fn test_optional_chain() {
+        let chain = |config: &str| {
+            let source = format!(
+                "{}fn main() Option[i64] {{\n    mut config Option[Config] = {}\n    config?.server?.port?.number\n}}\n",
+                CONFIG, config
+            );
+            run_verified(&source)
+        };
+        let port = "Config { server: Server { port: Port { number: 8080 } } }";
+        assert!(matches!(chain(port), Value::I64(8080)));
+        // the chain stops at the first link that is none.
+        assert!(matches!(
+            chain("Config { server: Server { port: none } }"),
+            Value::Unit
+        ));
+        assert!(matches!(chain("Config { server: none }"), Value::Unit));
+        assert!(matches!(chain("none"), Value::Unit));
+
+        let flags = "@flags\nenum Perm { Read, Write, Exec }\n";
+        let display = |value: i64| {
+            let source = format!(
+                "{}fn main() = Perm.from_int({})?.display() ?? \"invalid\"\n",
+                flags, value
+            );
+            match run_verified(&source) {
+                Value::String(val) => val.as_str().to_owned(),
+                value => panic!("unexpected value {}", value),
+            }
+        };
+        assert_eq!(display(6), "Write | Exec");
+        assert_eq!(display(8), "invalid");
+    }
+
+    #[test]
+    fn test_optional_chain_types() {
+        let check_main = |signature: &str| {
+            let config = CONFIG.replace(
+                "struct Server { pub port Option[Port] }",
+                "struct Server {\n    pub port Option[Port]\n    pub fn id(self) i64 = 7\n}",
+            );
+            let source = format!("{}fn main{}\n", config, signature);
+            check(&source).map(|_| ())
+        };
+        // an optional field is not wrapped again.
+        assert_eq!(
+            check_main("(config Config) i64 = config.server?.port"),
+            Err("incompatible types, expected 'i64' and found 'Option[test.Port]'".to_string())
+        );
+        assert_eq!(
+            check_main("(config Config) i64 = config.server?.port?.number"),
+            Err("incompatible types, expected 'i64' and found 'Option[i64]'".to_string())
+        );
+        assert!(check_main("(config Config) Option[Port] = config.server?.port").is_ok());
+        assert_eq!(
+            check_main("(config Config) i64 = config.server?.id()"),
+            Err("incompatible types, expected 'i64' and found 'Option[i64]'".to_string())
+        );
+        assert!(check_main("(server Option[Server]) i64 = server?.port?.number ?? 80").is_ok());
+
+        assert_eq!(
+            check_main("(config Config) = config?.server"),
+            Err("'?.' applied to 'test.Config' which is not optional, use '.' instead".to_string())
+        );
+        assert_eq!(
+            check_main("(server Option[Server]) = server ?? 1"),
+            Err("incompatible types, expected 'test.Server' and found 'i32'".to_string())
+        );
+        assert_eq!(
+            check_main("(server Server) = server ?? server"),
+            Err("'??' applied to 'test.Server' which is not optional".to_string())
+        );
+        assert_eq!(
+            check_main("() = none"),
+            Err("the type of 'none' can not be inferred here".to_string())
+        );
+    }
+
+    const RANGE: &str = "struct Range {\n\
+        \x20   pub current i64\n\
+        \x20   pub end i64\n\
+        \x20   pub fn next(mut self) Option[i64] {\n\
+        \x20       mut item Option[i64] = none\n\
+        \x20       if self.current != self.end {\n\
+        \x20           item = self.current\n\
+        \x20           self.current += 1\n\
+        \x20       } else {\n\
+        \x20           item = none\n\
+        \x20       }\n\
+        \x20       item\n\
+        \x20   }\n\
+        }\n\
+        fn range(start i64, end i64) Range = Range { current: start, end: end }\n";
+
+    fn sum(body: &str) -> Value {
+        let source = format!(
+            "{}struct Upto {{\n    pub limit i64\n    \
+            pub fn iter(self) Range = range(0, self.limit)\n}}\n\
+            fn main() i64 {{\n    mut total i64 = 0\n{}\n    total\n}}\n",
+            RANGE, body
+        );
+        run_verified(&source)
+    }
+
+    #[test]
+    fn test_for_user_iterator() {
+        let body = "    for i in range(1, 5) {\n        total += i\n    }";
+        assert!(matches!(sum(body), Value::I64(10)));
+        let body = "    for i in range(3, 3) {\n        total += 1\n    }";
+        assert!(matches!(sum(body), Value::I64(0)));
+
+        // every loop has its own iterator, the inner one is created on each iteration.
+        let body = "    for i in range(1, 4) {\n        let upto = Upto { limit: i }\n        \
+            for j in upto {\n            total += i * j\n        }\n    }";
+        assert!(matches!(sum(body), Value::I64(11)));
+
+        let body = "    for i in range(0, 10) {\n        if i == 5 {\n            break\n        } \
+            elif i < 3 {\n            continue\n        } else {\n            total += i\n        }\n    }";
+        assert!(matches!(sum(body), Value::I64(7)));
+    }
+
+    #[test]
+    fn test_for_requires_the_iterator_protocol() {
+        let check_main = |next: &str, body: &str| {
+            let source = format!(
+                "{}struct Stuck {{\n    {} = none\n}}\n\
+                fn main() {{\n    mut total i64 = 0\n    let stuck = Stuck {{}}\n{}\n}}\n",
+                RANGE, next, body
+            );
+            check(&source).map(|_| ())
+        };
+        let for_stuck = "    for i in stuck {\n        total += i\n    }";
+        assert!(check_main("fn next(mut self) Option[i64]", for_stuck).is_ok());
+        assert_eq!(
+            check_main("fn next(self) Option[i64]", for_stuck),
+            Err(
+                "'next' of 'test.Stuck' has the type '(test.Stuck) Option[i64]', the iterator \
+                protocol requires '(mut Self) Option[T]'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check_main("fn next(mut self, step i64) Option[i64]", for_stuck),
+            Err(
+                "'next' of 'test.Stuck' has the type '(mut test.Stuck, i64) Option[i64]', the \
+                iterator protocol requires '(mut Self) Option[T]'"
+                    .to_string()
+            )
+        );
+        // the element is the type returned by next.
+        assert_eq!(
+            check_main(
+                "fn next(mut self) Option[i64]",
+                "    for i in range(0, 3) {\n        let b bool = i\n    }"
+            ),
+            Err("incompatible types, expected 'bool' and found 'i64'".to_string())
+        );
+
+        let not_iterable = check_main("fn next(mut self) Option[i64]", "    for i in total {}");
+        assert_eq!(
+            not_iterable,
+            Err("'i64' can not be iterated by a for loop".to_string())
+        );
+        let err = analyze(&format!(
+            "{}fn main() {{\n    let r = Port {{ number: 1 }}\n    for i in r {{}}\n}}\n",
+            CONFIG
+        ))
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'test.Port' can not be iterated by a for loop"
+        );
+        assert_eq!(
+            err.notes(),
+            [
+                "an iterator has the method 'next(mut self) Option[T]'",
+                "an iterable has the method 'iter(self)' returning an iterator"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_while_break_and_continue() {
+        let body =
+            "    mut i i64 = 0\n    while i < 10 {\n        i += 1\n        total += i\n    }";
+        assert!(matches!(sum(body), Value::I64(55)));
+
+        // a break and a continue apply to the innermost loop.
+        let body = "    mut i i64 = 0\n    while i < 10 {\n        i += 1\n        \
+            if i % 2 == 0 {\n            continue\n        } else {\n            \
+            total += 0\n        }\n        mut j i64 = 0\n        while true {\n            \
+            j += 1\n            if j > i {\n                break\n            \
+            } else {\n                total += 1\n            }\n        }\n    }";
+        assert!(matches!(sum(body), Value::I64(25)));
+
+        let error_at = |body: &str| {
+            let err = analyze(&format!("fn main() {{\n{}\n}}\n", body)).unwrap_err();
+            let start = err.pos().start();
+            (start.line(), start.column(), err.to_string())
+        };
+        let outside = |keyword: &str| {
+            format!("'{}' is only allowed in loop, for, or while control loops", keyword)
+        };
+        assert_eq!(error_at("    break"), (2, 5, outside("break")));
+        // the body of a loop ends where the loop does.
+        assert_eq!(
+            error_at("    while false {\n    }\n    if true {\n        continue\n    } else {}"),
+            (5, 9, outside("continue"))
+        );
+    }
+
+    #[test]
+    fn test_if_expressions() {
+        let body = "    let ten i64 = 10
+    \
+            let x = if total > 0 { ten } elif total == 0 { ten * 2 } else { ten * 3 }
+    \
+            total = x";
+        assert!(matches!(sum(body), Value::I64(20)));
+
+        // a branch that diverges has no value, the branches of a discarded if can disagree.
+        let body = "    let upto = Upto { limit: 6 }
+    for i in upto {
+        \
+            let odd = if i % 2 == 0 { continue } else { i }
+        \
+            if odd == 1 { total += 100 }
+        \
+            if odd == 3 { 7 } elif odd == 5 { true } else { total += odd }
+        \
+            total += odd
+    }";
+        assert!(matches!(sum(body), Value::I64(110)));
+
+        let error_at = |body: &str| {
+            let err = analyze(&format!("fn main() {{\n{}\n}}\n", body)).unwrap_err();
+            let start = err.pos().start();
+            (start.line(), start.column(), err.to_string())
+        };
+        assert_eq!(
+            error_at("    let x = if true { 1 } else { false }"),
+            (
+                2,
+                34,
+                "incompatible types, expected 'i32' and found 'bool'".to_string()
+            )
+        );
+        assert_eq!(
+            error_at("    let x = if true { 1 }"),
+            (
+                2,
+                13,
+                "the value of an 'if' is used but it has no 'else' producing 'i32'".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_branches_take_the_expected_type() {
+        let source = "fn pick(c bool) i64 {\n    let r i64 = if c { 0 } else { 1 }\n    r\n}\n\
+            fn g(n i64) i64 = if n == 0 { 0 } else { n }\n\
+            fn h(n i64) i64 {\n    let r = if n > 1 { 0 } else { n }\n    r\n}\n\
+            fn early(n i64) i64 {\n    if n > 5 {\n        return 5\n    }\n    n\n}\n\
+            fn arm(b bool) i64 = match b { true => 10, false => 20 }\n\
+            fn main() i64 {\n    let block i64 = { 100 }\n    \
+            pick(false) + g(2) + h(1) + early(9) + arm(false) + block\n}\n";
+        assert!(matches!(run_verified(source), Value::I64(129)));
+
+        let source = "fn half(c bool, x f64) f64 {\n    \
+            let f f64 = if c { 0.5 } else { 2.0 }\n    let g = if c { 0.25 } else { x }\n    \
+            f + g\n}\n\
+            fn main() f64 = half(true, 8.0) + half(false, 8.0)\n";
+        assert!(matches!(run_verified(source), Value::F64(val) if val == 10.75));
+
+        // a literal that does not fit the type of the other branches is not given it.
+        let err = check("fn main(n i8) i8 = if n > 0 { 300 } else { n }\n").map(|_| ());
+        assert_eq!(err, Err("incompatible types, expected 'i8' and found 'i32'".to_string()));
+        let err = check("fn main() i64 {\n    \"x\"\n}\n").map(|_| ());
+        assert_eq!(
+            err,
+            Err("incompatible types, expected 'i64' and found 'string'".to_string())
+        );
+    }
+
+    /// functions returning a value of every kind a postfix applies to.
+    const POSTFIX: &str = "fn inc(a i64) i64 = a + 1
+struct Point {
+    pub x i64
+    pub y i64
+    pub fn sum(self) i64 = self.x + self.y
+    pub fn moved(self, by i64) Point = Point { x: self.x + by, y: self.y }
+    pub fn pair(self) = (self.x, self.y)
+    pub fn incer(self) = inc
+}
+struct Line {
+    pub from Point
+    pub to Point
+    pub span (i64, i64)
+}
+fn incer() = inc
+fn make(x i64) Point = Point { x: x, y: 2 }
+fn line() Line = Line { from: make(1), to: make(3), span: (4, 6) }
+fn both() = (make(5), inc)
+fn pairs(x i64) = (x, x + 1)
+fn nested() = (pairs(3), 1)
+";
+
+    #[test]
+    fn test_postfix_expressions() {
+        // every postfix applied to the result of every other one. A field is not called, a field
+        // cannot be declared with a function type.
+        let cases = [
+            ("incer()(4)", 5),
+            ("make(1).incer()(4)", 5),
+            ("both().1(4)", 5),
+            ("make(3).x", 3),
+            ("line().from.x", 1),
+            ("make(3).moved(4).x", 7),
+            ("both().0.x", 5),
+            ("make(3).sum()", 5),
+            ("line().to.sum()", 5),
+            ("make(3).moved(4).sum()", 9),
+            ("both().0.sum()", 7),
+            ("pairs(3).1", 4),
+            ("line().span.1", 6),
+            ("make(3).pair().1", 2),
+            ("nested().0.1", 4),
+            ("(incer())(4)", 5),
+            ("(both().0).x", 5),
+            ("(make(3).moved(1)).sum()", 6),
+            ("-make(3).moved(1).x * 2", -8),
+        ];
+        for (expr, expected) in cases.iter() {
+            let source = format!("{}fn main() i64 = {}\n", POSTFIX, expr);
+            match run(&source) {
+                Value::I64(value) => assert_eq!(value, *expected, "{}", expr),
+                value => panic!("{} is {:?}", expr, value),
+            }
+        }
+
+        // indexing is only type checked, there are no slices to run it on.
+        let source = format!(
+            "{}struct Holder {{\n    pub xs [i64]\n    pub fn all(self) [i64] = self.xs\n}}\n\
+            fn holder(xs [i64]) Holder = Holder {{ xs: xs }}\n\
+            fn slice(xs [i64]) [i64] = xs\n\
+            fn first(xs [i64], grid [[i64]], ps [Point], spans [(i64, i64)]) i64 = \
+            slice(xs)[0] + holder(xs).xs[0] + holder(xs).all()[0] + (xs, 1).0[0] + \
+            grid[0][1] + ps[0].x + ps[0].sum() + spans[0].1 + (slice(xs))[0]\n\
+            fn main() {{}}\n",
+            POSTFIX
+        );
+        assert!(check(&source).is_ok(), "{:?}", check(&source).err());
+
+        let error_at = |expr: &str| {
+            let source = format!("{}fn main() {{\n    {}\n}}\n", POSTFIX, expr);
+            let err = analyze(&source).unwrap_err();
+            let start = err.pos().start();
+            (start.column(), err.to_string())
+        };
+        // the result of a call is a temporary.
+        let not_assignable = (5, "expression is not assignable".to_string());
+        assert_eq!(error_at("make(1).x = 4"), not_assignable);
+        assert_eq!(error_at("line().from.y = 4"), not_assignable);
+        assert_eq!(error_at("both().0 = make(1)"), not_assignable);
+        assert_eq!(
+            error_at("let p = both().2"),
+            (13, "tuple '(test.Point, (i64) i64)' has no element 2".to_string())
+        );
+        assert_eq!(
+            error_at("let x = make(1).sum().y"),
+            (27, "type must be a struct, found 'i64'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_while_let_drains_an_optional() {
+        let body = "    mut it = range(1, 5)\n    while let some(i) = it.next() {\n        \
+            total += i\n    }";
+        assert!(matches!(sum(body), Value::I64(10)));
+
+        // a continue takes the next value and a break leaves the loop.
+        let body = "    mut it = range(0, 10)\n    while let some(i) = it.next() {\n        \
+            if i == 6 {\n            break\n        } elif i < 3 {\n            continue\n        \
+            } else {\n            total += i\n        }\n    }";
+        assert!(matches!(sum(body), Value::I64(12)));
+
+        // the loop ends at the first none a built in function returns.
+        let source = format!(
+            "{}fn main() i64 {{\n    mut code i64 = 0\n    mut total i64 = 0\n    \
+            while let some(status) = Status.from_int(code) {{\n        \
+            total += status as i64\n        code += 404\n    }}\n    total + code\n}}\n",
+            STATUS
+        );
+        assert!(matches!(run_verified(&source), Value::I64(1212)));
+    }
+
+    #[test]
+    fn test_while_let_requires_an_optional() {
+        let check_main = |body: &str| {
+            let source = format!(
+                "{}fn main() {{\n    mut it = range(0, 3)\n{}\n}}\n",
+                RANGE, body
+            );
+            check(&source).map(|_| ())
+        };
+        assert_eq!(
+            check_main("    while let some(i) = 3 {}"),
+            Err("'while let some(..)' applied to 'i32' which is not optional".to_string())
+        );
+        // the element is only declared in the body.
+        assert_eq!(
+            check_main("    while let some(i) = it.next() {}\n    let j = i"),
+            Err("use of undeclared identifier 'i'".to_string())
+        );
+        assert_eq!(
+            check_main("    while let other(i) = it.next() {}"),
+            Err("unexpected token: expected 'some' found 'other'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_methods_reference_structs() {
+        // a method takes, returns and constructs Self, by name and as Self.
+        let source = "struct List {\n    pub head i64\n\
+            \x20   pub fn merge(self, other List) i64 = self.head + other.head\n\
+            \x20   pub fn same(self, other Self) i64 = other.head\n\
+            \x20   pub fn copy(self) Self = List { head: self.head }\n\
+            \x20   pub fn fresh(self) List = Self { head: 1 }\n}\n\
+            fn main() i64 {\n    let a = List { head: 2 }\n    let b = a.fresh()\n\
+            \x20   (a.merge(a.copy()) + a.same(b))\n}\n";
+        assert!(matches!(run(source), Value::I64(5)));
+
+        // the methods of each struct construct the other, the second is resolved from within
+        // a method of the first.
+        let source = "struct A {\n    pub x i64\n    pub fn to_b(self) B = B { y: self.x }\n}\n\
+            struct B {\n    pub y i64\n    pub fn to_a(self) A = A { x: (self.y + 1) }\n}\n\
+            fn main() i64 {\n    let a = A { x: 3 }\n    let b = a.to_b()\n    let back = b.to_a()\n    back.x\n}\n";
+        assert!(matches!(run(source), Value::I64(4)));
+
+        assert_eq!(
+            check("fn main(x Self) i64 = 1\n").map(|_| ()),
+            Err("invalid 'Self' type is context".to_string())
+        );
+    }
+
+    #[test]
+    fn test_bodies_use_items_declared_after_them() {
+        // a method and a free function call each other, whichever is declared first.
+        let counter = "struct Counter {\n    pub n i64\n\
+            \x20   pub fn get(self) i64 = self.n\n\
+            \x20   pub fn doubled(self) i64 = twice(self)\n}\n";
+        let twice = "fn twice(c Counter) i64 = c.get() * 2\n";
+        let main = "fn main() i64 {\n    let c = Counter { n: 4 }\n    c.doubled()\n}\n";
+        assert!(check(&format!("{}{}{}", twice, counter, main)).is_ok());
+        assert!(check(&format!("{}{}{}", counter, twice, main)).is_ok());
+        let source = format!("fn twice(c Counter) i64 = c.n * 2\n{}{}", counter, main);
+        assert!(matches!(run(&source), Value::I64(8)));
+
+        // two structures whose methods take each other.
+        let source = "struct A {\n    pub x i64\n    pub fn with(self, b B) i64 = self.x + b.y\n}\n\
+            struct B {\n    pub y i64\n    pub fn with(self, a A) i64 = a.with(self)\n}\n\
+            fn main() i64 {\n    let a = A { x: 1 }\n    let b = B { y: 2 }\n    b.with(a)\n}\n";
+        assert!(matches!(run(source), Value::I64(3)));
+
+        // a function calls itself and two functions call each other by their signatures.
+        let source = "fn count(n i64) i64 = if n == 0 { 0 } else { count(n - 1) + 1 }\n\
+            fn g(n i64) i64 {\n    if n == 0 {\n        return 0\n    }\n    g(n - 1) + 1\n}\n\
+            fn main() i64 = count(3) + g(4)\n";
+        assert!(matches!(run(source), Value::I64(7)));
+        let source = "fn main() bool = even(7)\n\
+            fn even(n i64) bool = if n == 0 { true } else { odd(n - 1) }\n\
+            fn odd(n i64) bool = if n == 0 { false } else { even(n - 1) }\n";
+        assert!(matches!(run(source), Value::Bool(false)));
+
+        // a type only known from the body of an item that needs it is still a cycle.
+        assert_eq!(
+            check("fn first() = second()\nfn second() = first()\nfn main() i64 = 1\n").map(|_| ()),
+            Err("'first' depends on itself, its type is only known once it is resolved".to_string())
+        );
+        let source = "struct Point {\n    pub x i64 = origin()\n}\n\
+            fn origin() = Point { x: 0 }.x\nfn main() i64 = 1\n";
+        assert_eq!(
+            check(source).map(|_| ()),
+            Err("'Point' depends on itself, its type is only known once it is resolved".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unbounded_recursion_is_a_stack_overflow() {
+        let source = "fn f(n i64) i64 = f(n + 1)\nfn main() i64 = f(0)\n";
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        assert_eq!(
+            vm.run_module(module).unwrap_err().to_string(),
+            "stack overflow, calls are nested more than 1024 deep: f, f, f, f, f, f, f, f, .."
+        );
+    }
+
+    fn compile(source: &str) -> CompiledModule {
+        let hir_file = check(source).unwrap();
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        let module = CodeGen::build(&FileMap::new(), &hir_file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        CompiledModule::from_module(module)
+            .unwrap()
+            .with_api(module_api(&hir_file))
+    }
+
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl LogHandler for Recorder {
+        fn log(&mut self, record: &LogRecord) {
+            self.0.borrow_mut().push(record.message.to_string());
+        }
+    }
+
+    /// the messages logged by running the main of source.
+    fn run_logged(source: &str) -> Result<Vec<String>, String> {
+        run_granted(source, Capabilities::none())
+    }
+
+    /// the messages logged by running the main of source with the capabilities.
+    fn run_granted(source: &str, capabilities: Capabilities) -> Result<Vec<String>, String> {
+        let compiled = compile(source);
+        let messages = Rc::new(RefCell::new(vec![]));
+        let mut vm = Vm::new();
+        vm.set_capabilities(capabilities);
+        vm.set_log_handler(Box::new(Recorder(messages.clone())));
+        let module = compiled.load(&mut vm);
+        vm.run_module(module).map_err(|err| err.to_string())?;
+        let messages = messages.borrow().clone();
+        Ok(messages)
+    }
+
+    const COUNTER: &str = "fn value(i i64) i64 = i\nfn main() {\n    mut i i64 = 0\n\
+        \x20   while i < 8 {\n        log.info(value(i))\n        i += 1\n    }\n}\n";
+
+    #[test]
+    fn test_replace_function_body() {
+        let original = compile(COUNTER);
+        let replacement = compile(&COUNTER.replace("i64 = i\n", "i64 = (i + 100)\n"));
+
+        let mut vm = Vm::new();
+        let module = original.load(&mut vm);
+        vm.enter_module(module).unwrap();
+        let mut steps = 0;
+        while let SliceResult::Yielded = vm.run_slice(1) {
+            steps += 1;
+        }
+
+        // the body is swapped after every instruction, including those of the old body.
+        let mut swapped_mid_call = false;
+        for swap_at in 1..=steps {
+            let messages = Rc::new(RefCell::new(vec![]));
+            let mut vm = Vm::new();
+            vm.set_log_handler(Box::new(Recorder(messages.clone())));
+            let module = original.load(&mut vm);
+            vm.enter_module(module).unwrap();
+            assert!(matches!(vm.run_slice(swap_at), SliceResult::Yielded));
+            let in_call = vm.stack_trace()[0].starts_with("at value");
+            let logged = messages.borrow().len();
+            vm.replace_function("value", &replacement).unwrap();
+            // the old body is only referenced by the frame running it.
+            vm.gc_collect();
+            vm.run().unwrap();
+
+            // once a call returns the new result every later call does.
+            let messages = messages.borrow();
+            let old = messages
+                .iter()
+                .take_while(|message| message.parse::<i64>().unwrap() < 100)
+                .count();
+            let expected = (0..8)
+                .map(|i| if i < old { i } else { i + 100 }.to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(*messages, expected);
+            // a call in progress finishes on the old body, a result not yet logged is old too.
+            assert!(logged + in_call as usize <= old && old <= logged + 1);
+            swapped_mid_call |= in_call;
+        }
+        assert!(swapped_mid_call);
+    }
+
+    #[test]
+    fn test_replace_function_refuses_other_changes() {
+        let source = "struct Point {\n    pub x i64\n}\nlet base i64 = 10\n\
+            fn value(i i64) i64 = i\nfn main() i64 = value(base)\n";
+        let original = compile(source);
+        let mut vm = Vm::new();
+        assert_eq!(
+            vm.replace_function("value", &original)
+                .unwrap_err()
+                .to_string(),
+            "no module is running to replace 'value' in"
+        );
+        let module = original.load(&mut vm);
+        vm.enter_module(module).unwrap();
+
+        let prefix = "'value' can not be replaced without a restart: ";
+        let changes = [
+            (
+                source
+                    .replace("i64 = i\n", "i32 = 1\n")
+                    .replace("main() i64", "main() i32"),
+                "'value' changed from '(i64) i64' to '(i64) i32', \
+                'main' changed from '() i64' to '() i32'",
+            ),
+            (
+                source.replace("pub x i64\n", "pub x i64\n    pub y i64\n"),
+                "the layout of struct 'Point' changed",
+            ),
+            (
+                source.replace("10", "20"),
+                "the constant I64(10) is now the constant I64(20)",
+            ),
+            (
+                source.replace("fn main", "fn helper() i64 = 1\nfn main"),
+                "'helper' was added",
+            ),
+        ];
+        for (changed, mismatch) in changes.iter() {
+            let err = vm.replace_function("value", &compile(changed)).unwrap_err();
+            assert_eq!(err.to_string(), format!("{}{}", prefix, mismatch));
+        }
+        assert_eq!(
+            vm.replace_function("missing", &original)
+                .unwrap_err()
+                .to_string(),
+            "'missing' can not be replaced without a restart: \
+            'missing' is not a function of the running module"
+        );
+
+        // nothing was replaced by the refused changes.
+        vm.replace_function("value", &original).unwrap();
+        vm.run().unwrap();
+        assert!(matches!(vm.top(), Value::I64(10)));
+    }
+
+    const EXPORTS: &str = "struct Point {\n    pub x i64\n\
+        \x20   pub fn scaled(self, by i64) i64 = self.x * by\n}\n\
+        fn value(i i64) i64 = i\nfn greet(name string) string = \"hi \" + name\n\
+        fn main() i64 = value(1)\n";
+
+    #[test]
+    fn test_exported_functions_are_called_by_handle() {
+        let original = compile(EXPORTS);
+        let mut vm = Vm::new();
+        assert!(vm.lookup_export("value").is_none());
+        let module = original.load(&mut vm);
+        vm.run_module(module).unwrap();
+        vm.pop();
+        assert!(vm.lookup_export("missing").is_none());
+        assert_eq!(vm.lookup_export("Point.scaled").unwrap().arity(), 2);
+
+        let value = vm.lookup_export("value").unwrap();
+        assert_eq!(value.signature().unwrap().to_string(), "(i64) i64");
+        for i in 0..3 {
+            let result = value.call(&mut vm, &[Value::I64(i)]).unwrap();
+            assert!(matches!(result, Value::I64(found) if found == i));
+        }
+        let result = value.call_unchecked(&mut vm, &[Value::I64(7)]).unwrap();
+        assert!(matches!(result, Value::I64(7)));
+
+        let greet = vm.lookup_export("greet").unwrap();
+        let name = Value::String(vm.new_gc_string_from_str("bob"));
+        assert_eq!(greet.call(&mut vm, &[name]).unwrap().to_string(), "hi bob");
+        let by_name = vm.call_by_name("greet", &[name]).unwrap();
+        assert_eq!(by_name.to_string(), "hi bob");
+
+        let errors = [
+            (value.call(&mut vm, &[]), "'value' takes 1 arguments but 0 were given"),
+            (
+                value.call(&mut vm, &[Value::Bool(true)]),
+                "argument 0 of 'value' is bool, expected i64",
+            ),
+            (
+                vm.call_by_name("missing", &[]),
+                "'missing' is not a function of the running module",
+            ),
+        ];
+        for (result, message) in errors.iter() {
+            assert_eq!(result.as_ref().unwrap_err().to_string(), *message);
+        }
+        // nothing is left behind by the calls.
+        assert_eq!(vm.stack_trace().len(), 0);
+        assert!(matches!(value.call(&mut vm, &[Value::I64(4)]), Ok(Value::I64(4))));
+    }
+
+    #[test]
+    fn test_replace_function_makes_handles_stale() {
+        let original = compile(EXPORTS);
+        let replacement = compile(&EXPORTS.replace("i64 = i\n", "i64 = (i + 100)\n"));
+        let mut vm = Vm::new();
+        let module = original.load(&mut vm);
+        vm.run_module(module).unwrap();
+        vm.pop();
+
+        let value = vm.lookup_export("value").unwrap();
+        let greet = vm.lookup_export("greet").unwrap();
+        vm.replace_function("value", &replacement).unwrap();
+        vm.gc_collect();
+        let stale = "the handle of 'value' is stale, it is looked up again in the running module";
+        let err = value.call(&mut vm, &[Value::I64(1)]).unwrap_err();
+        assert_eq!(err.to_string(), stale);
+        // a handle of any function is refused, the one replaced may be called by it.
+        let name = Value::String(vm.new_gc_string_from_str("bob"));
+        assert!(greet.call(&mut vm, &[name]).is_err());
+
+        let value = vm.lookup_export("value").unwrap();
+        assert!(matches!(value.call(&mut vm, &[Value::I64(1)]), Ok(Value::I64(101))));
+        let by_name = vm.call_by_name("value", &[Value::I64(2)]).unwrap();
+        assert!(matches!(by_name, Value::I64(102)));
+
+        // a handle belongs to the module it was looked up in.
+        let other = original.load(&mut vm);
+        vm.run_module(other).unwrap();
+        vm.pop();
+        let err = value.call(&mut vm, &[Value::I64(1)]).unwrap_err();
+        assert_eq!(err.to_string(), stale);
+        let value = vm.lookup_export("value").unwrap();
+        assert!(matches!(value.call(&mut vm, &[Value::I64(1)]), Ok(Value::I64(1))));
+    }
+
+    /// the messages logged by running module in vm and the value main returns.
+    fn run_recorded(vm: &mut Vm, module: Gc<OxModule>) -> (Vec<String>, String) {
+        let messages = Rc::new(RefCell::new(vec![]));
+        vm.set_log_handler(Box::new(Recorder(messages.clone())));
+        vm.run_module(module).unwrap();
+        let messages = messages.borrow().clone();
+        (messages, vm.top().to_string())
+    }
+
+    #[test]
+    fn test_saved_module_runs_like_the_compiled_one() {
+        let source = "struct Point {\n    pub x i64\n    pub y i64\n\
+            \x20   pub fn len(self) i64 = self.x + self.y\n}\n\
+            fn name(kind string) string = \"point \" + kind\n\
+            fn main() i64 {\n    mut total i64 = 0\n    mut i i64 = 0\n    while i < 4 {\n\
+            \x20       let p = Point { x: i, y: (i * 2) }\n        log.info(name(\"len\"))\n\
+            \x20       log.info(p.len())\n\
+            \x20       total += p.len()\n        i += 1\n    }\n    total\n}\n";
+        let compiled = compile(source);
+        let dir = std::env::temp_dir().join(format!("auburn-save-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.oxb");
+        compiled.save(&path).unwrap();
+
+        let mut vm = Vm::new();
+        let module = compiled.load(&mut vm);
+        let expected = run_recorded(&mut vm, module);
+        assert_eq!(expected.0.len(), 8);
+        assert_eq!(expected.1, "18");
+        let mut vm = Vm::new();
+        let module = vm.load_module(&path).unwrap();
+        assert_eq!(run_recorded(&mut vm, module), expected);
+
+        // nothing is loaded from a file that is cut short or of another version.
+        let bytes = std::fs::read(&path).unwrap();
+        let mut other_version = bytes.clone();
+        other_version[4..6].copy_from_slice(&(MODULE_FILE_VERSION + 1).to_le_bytes());
+        let files = [
+            bytes[..bytes.len() / 2].to_vec(),
+            bytes[..3].to_vec(),
+            other_version,
+        ];
+        for file in files.iter() {
+            std::fs::write(&path, file).unwrap();
+            let err = vm.load_module(&path).unwrap_err().to_string();
+            assert!(err.starts_with("invalid module file at "), "{}", err);
+        }
+        let err = vm.load_module(&dir.join("missing.oxb")).unwrap_err().to_string();
+        assert!(err.starts_with("unable to read module file '"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_disassembly_of_a_saved_module() {
+        let compiled = compile(EXPORTS);
+        let dir = std::env::temp_dir().join(format!("auburn-dis-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("main.oxb");
+        compiled.save(&path).unwrap();
+
+        let mut vm = Vm::new();
+        let built = disassemble(compiled.load(&mut vm).as_ref());
+        let loaded = disassemble(vm.load_module(&path).unwrap().as_ref());
+        assert_eq!(built, loaded);
+        assert!(built.starts_with("module "), "{}", built);
+        for line in ["    struct Point\n", "        fn scaled (arity 2):\n", "(<fn value>)"] {
+            assert!(built.contains(line), "{}", built);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const TASKS: &str = "struct Point {\n    pub x i64\n}\n\
+        fn fib(n i64) i64 {\n    mut a i64 = 0\n    mut b i64 = 1\n    mut i i64 = 0\n\
+        \x20   while i < n {\n        let t = a + b\n        a = b\n        b = t\n        i += 1\n\
+        \x20   }\n    a\n}\n\
+        fn input(i i64) i64 = (i + 1) * 10\n\
+        fn divide(a i64, b i64) i64 = a / b\n\
+        fn point(x i64) Point = Point { x: x }\n\
+        fn x(p Point) i64 = p.x\n\
+        fn relay(n i64) i64 = task.spawn(fib, n).join().ok() ?? -1\n";
+
+    /// the messages logged by running main, it may spawn tasks.
+    fn run_tasks(main: &str) -> Result<Vec<String>, String> {
+        let mut capabilities = Capabilities::none();
+        capabilities.grant(Capability::Spawn);
+        run_granted(format!("{}fn main() {{\n{}}}\n", TASKS, main).as_str(), capabilities)
+    }
+
+    #[test]
+    fn test_tasks_map_in_parallel() {
+        // the elements of the list are input(0) to input(3).
+        let main = "    let a = task.spawn(fib, input(0))\n    let b = task.spawn(fib, input(1))\n\
+            \x20   let c = task.spawn(fib, input(2))\n    let d = task.spawn(fib, input(3))\n\
+            \x20   log.info((a.join().ok() ?? 0, b.join().ok() ?? 0, c.join().ok() ?? 0, \
+            d.join().ok() ?? 0))\n\
+            \x20   log.info((fib(input(0)), fib(input(1)), fib(input(2)), fib(input(3))))\n";
+        let messages = run_tasks(main).unwrap();
+        assert_eq!(messages[0], "(55, 6765, 832040, 102334155)");
+        assert_eq!(messages[0], messages[1]);
+    }
+
+    #[test]
+    fn test_task_errors_surface_through_join() {
+        let main = "    let r = task.spawn(divide, 1, 0).join()\n\
+            \x20   log.info(r.err() ?? \"none\")\n    log.info(r.ok() ?? 7)\n\
+            \x20   log.info(task.spawn(point, 1).join().err() ?? \"none\")\n\
+            \x20   let t = task.spawn(divide, 9, 3)\n    log.info(t.join().ok() ?? 0)\n\
+            \x20   log.info(t.join().err() ?? \"none\")\n";
+        assert_eq!(
+            run_tasks(main).unwrap(),
+            vec![
+                "integer division by zero",
+                "7",
+                "a value of type instance cannot be passed to or returned from a task",
+                "3",
+                "the task was already joined",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spawning_requires_the_capability() {
+        let main =
+            "    log.info(relay(10))\n    log.info(task.spawn(relay, 20).join().ok() ?? 0)\n";
+        assert_eq!(run_tasks(main).unwrap(), vec!["55", "6765"]);
+        let source = format!("{}fn main() {{\n{}}}\n", TASKS, main);
+        assert_eq!(run_logged(&source).unwrap_err(), "capability not granted: spawn");
+
+        // a task started by the host is granted nothing, its spawn fails the task.
+        let mut vm = Vm::new();
+        let compiled = compile(&source);
+        let module = compiled.load(&mut vm);
+        let relay = module
+            .objects()
+            .iter()
+            .position(|object| match object {
+                Value::Function(function) => function.name().as_str() == "relay",
+                _ => false,
+            })
+            .unwrap();
+        let task = Task::spawn(Arc::new(compiled), TaskEntry::Function(relay), &[Value::I64(1)]);
+        assert_eq!(task.unwrap().join().unwrap_err(), "capability not granted: spawn");
+    }
+
+    #[test]
+    fn test_spawn_rejects_unsendable_actuals() {
+        let main = "    let t = task.spawn(x, Point { x: 1 })\n    log.info(t.join().ok() ?? 0)\n";
+        assert_eq!(
+            run_tasks(main).unwrap_err(),
+            "a value of type instance cannot be passed to or returned from a task"
+        );
+
+        let check_main =
+            |main: &str| check(format!("{}fn main() {{\n{}}}\n", TASKS, main).as_str()).map(|_| ());
+        assert_eq!(
+            check_main("    let t = task.spawn(10)\n"),
+            Err("only a function can be run as a task, found 'i32'".to_string())
+        );
+        assert_eq!(
+            check_main("    let t = task.run(fib, 1)\n"),
+            Err("unknown function 'task.run', expected spawn".to_string())
+        );
+        assert_eq!(
+            check_main("    let t = task.spawn(divide, 1)\n"),
+            Err("attempting to call function expecting 2 parameters with 1 parameters".to_string())
+        );
+    }
+
+    /// the elements of each range, the ranges are given as the value of a for loop.
+    fn range_elements(ranges: &[&str]) -> Result<Vec<String>, String> {
+        let loops = ranges
+            .iter()
+            .map(|range| format!("    for i in {} {{\n        log.info(i)\n    }}\n", range))
+            .collect::<Vec<_>>()
+            .join("    log.info(\"|\")\n");
+        let source = format!(
+            "fn dynamic(step i64) i64 = step\nfn main() {{\n{}}}\n",
+            loops
+        );
+        let messages = run_logged(&source)?;
+        Ok(messages
+            .join(" ")
+            .split('|')
+            .map(|range| range.trim().to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_for_stepped_and_reversed_ranges() {
+        let ranges = [
+            "range(0, 10, 3)",
+            "range(0, 9, 3)",
+            "range(10, 0, -3)",
+            "range(0, 5, 10)",
+            "range(0, 10, 3).rev()",
+            "range(10, 0, -3).rev()",
+            "range(dynamic(0), -11, dynamic(-4))",
+            "range(dynamic(0), 11, dynamic(4)).rev()",
+            "range(5, 0)",
+        ];
+        assert_eq!(
+            range_elements(&ranges).unwrap(),
+            vec![
+                "0 3 6 9", "0 3 6", "10 7 4 1", "0", "9 6 3 0", "1 4 7 10", "0 -4 -8", "8 4 0", "",
+            ]
+        );
+        // a range stepping away from its end is empty.
+        assert_eq!(
+            range_elements(&["range(0, 5, -1)", "range(5, 0, dynamic(1))"]).unwrap(),
+            vec!["", ""]
+        );
+    }
+
+    #[test]
+    fn test_range_step_of_zero() {
+        assert_eq!(
+            range_elements(&["range(0, 3)", "range(0, 5, dynamic(0))"]),
+            Err("the step of a range is zero".to_string())
+        );
+        let check_range = |range: &str| {
+            check(format!("fn main() {{\n    for i in {} {{\n    }}\n}}\n", range).as_str())
+                .map(|_| ())
+        };
+        assert_eq!(
+            check_range("range(0, 5, 0)"),
+            Err("the step of a range can not be zero".to_string())
+        );
+        assert_eq!(
+            check_range("range(0, 5.0)"),
+            Err("the bounds and step of a range must be integers, found 'f32'".to_string())
+        );
+        assert_eq!(
+            check_range("range(0, 5, 1, 2)"),
+            Err("attempting to call function expecting 3 parameters with 4 parameters".to_string())
+        );
+    }
+
+    #[test]
+    fn test_range_expressions() {
+        let ranges = [
+            "0..4",
+            "3..3",
+            "5..0",
+            "(0..4).rev()",
+            "dynamic(-2)..dynamic(1)",
+            "1 + 1..2 * 3",
+        ];
+        // the end is excluded, a range ending before its start is empty.
+        assert_eq!(
+            range_elements(&ranges).unwrap(),
+            vec!["0 1 2 3", "", "", "3 2 1 0", "-2 -1 0", "2 3 4 5"]
+        );
+
+        let check_main = |main: &str| {
+            let source = format!(
+                "fn main() {{\n    let a i32 = 0\n    let b i64 = 4\n{}}}\n",
+                main
+            );
+            check(source.as_str()).map(|_| ())
+        };
+        // the type of the elements is inferred from the bounds, they have to agree.
+        assert_eq!(check_main("    for i in a..10 {\n    }\n"), Ok(()));
+        assert_eq!(
+            check_main("    for i in a..b {\n    }\n"),
+            Err("incompatible types, expected 'i32' and found 'i64'".to_string())
+        );
+        assert_eq!(
+            check_main("    for i in 0..b {\n        i = 2\n    }\n"),
+            Err("attempting to mutable 'i' which is not mutable".to_string())
+        );
+        // the element is only in scope in the body.
+        assert_eq!(
+            check_main("    for i in 0..b {\n    }\n    log.info(i)\n"),
+            Err("use of undeclared identifier 'i'".to_string())
+        );
+        assert_eq!(
+            check_main("    let r = 0..b\n"),
+            Err("a range can only be iterated by a for loop".to_string())
+        );
+    }
+
+    /// runs the main of source with the contracts checked or not, as the opt level selects them.
+    fn run_contracts(source: &str, contracts: bool) -> Result<Value, String> {
+        let file = File::raw_test(source.to_string());
+        let mut parser = Parser::new(&file);
+        let parsed = parser.init().and_then(|_| parser.parse_file()).unwrap();
+        let mut analysis = Analysis::new();
+        analysis.set_contracts(contracts);
+        let hir_file = analysis
+            .check(parsed, LanguageMode::Default)
+            .map_err(|err| err.to_string())?;
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        let module = CodeGen::build(&FileMap::new(), &hir_file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        vm.run_module(module).map_err(|err| err.to_string())?;
+        Ok(*vm.top())
+    }
+
+    const GROW: &str = "@requires(x > 0)\n@ensures(result >= x)\n\
+        fn grow(x i64, big bool) i64 {\n    if big {\n        return x - 20\n    } else {\n        \
+        x + 1\n    }\n}\n";
+
+    #[test]
+    fn test_contracts() {
+        let grow = |call: &str, contracts: bool| {
+            run_contracts(&format!("{}fn main() i64 = {}\n", GROW, call), contracts)
+        };
+        assert!(matches!(grow("grow(4, false)", true), Ok(Value::I64(5))));
+        assert_eq!(
+            grow("grow(0, false)", true).map(|_| ()),
+            Err("requires 'x > 0' of 'grow' violated: x = 0, big = false".to_string())
+        );
+        // the postconditions are checked on every path returning from the function.
+        assert_eq!(
+            grow("grow(30, true)", true).map(|_| ()),
+            Err("ensures 'result >= x' of 'grow' violated: x = 30, big = true, result = 10"
+                .to_string())
+        );
+        // without contracts nothing is checked.
+        assert!(matches!(grow("grow(0, false)", false), Ok(Value::I64(1))));
+        assert!(matches!(grow("grow(30, true)", false), Ok(Value::I64(10))));
+
+        // a postcondition sees the receiver as the body left it.
+        let counter = "struct Counter {\n    pub count i64\n    @ensures(self.count > before)\n    \
+            pub fn bump(mut self, before i64, by i64) i64 {\n        self.count += by\n        \
+            self.count\n    }\n}\nfn main() i64 {\n    mut c = Counter { count: 1 }\n    \
+            let a = c.bump(1, 2)\n    c.bump(a, MORE)\n}\n";
+        assert!(matches!(run_contracts(&counter.replace("MORE", "1"), true), Ok(Value::I64(4))));
+        assert_eq!(
+            run_contracts(&counter.replace("MORE", "0"), true).map(|_| ()),
+            Err("ensures 'self.count > before' of 'bump' violated: \
+                self = Counter { 3 }, before = 3, by = 0, result = 3"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_contracts() {
+        let check_contracts = |attributes: &str, contracts: bool| {
+            let source =
+                format!("{}fn half(x i64) i64 = x / 2\nfn main() i64 = half(4)\n", attributes);
+            run_contracts(&source, contracts).map(|_| ())
+        };
+        // the contracts are type checked whether they are checked at runtime or not.
+        for contracts in [true, false] {
+            assert_eq!(
+                check_contracts("@requires(x)\n", contracts),
+                Err("the condition of 'requires' must be 'bool', found 'i64'".to_string())
+            );
+            assert_eq!(
+                check_contracts("@ensures(result == y)\n", contracts),
+                Err("use of undeclared identifier 'y'".to_string())
+            );
+        }
+        assert_eq!(
+            check_contracts("@requires\n", true),
+            Err("attribute 'requires' expects a condition, '@requires(<condition>)'".to_string())
+        );
+        assert_eq!(
+            check_contracts("@flags\n", true),
+            Err("attribute 'flags' can only be applied to an enum".to_string())
+        );
+        assert_eq!(
+            check(&format!("@ensures(true)\n{}fn main() {{}}\n", STATUS)).map(|_| ()),
+            Err("attribute 'ensures' can only be applied to a function".to_string())
+        );
+    }
+
+    const SHAPE: &str = "enum Shape {
+    Circle(i64),
+    Rect(i64, i64),
+    Empty,
+}
+fn area(shape Shape) i64 = match shape {
+    Shape.Circle(r) => r * r * 3,
+    Shape.Rect(w, _) => w * w,
+    Shape.Empty => 0,
+}
+";
+
+    #[test]
+    fn test_match() {
+        let shapes = |call: &str| run(&format!("{}fn main() i64 = {}\n", SHAPE, call));
+        assert!(matches!(shapes("area(Shape.Circle(2))"), Value::I64(12)));
+        assert!(matches!(shapes("area(Shape.Rect(3, 4))"), Value::I64(9)));
+        assert!(matches!(shapes("area(Shape.Empty)"), Value::I64(0)));
+
+        // the arms are tried in order, a binding matches every value.
+        let classify = "fn classify(n i32) i32 = match n {
+    0 => 100,
+    -1 => 200,
+    other => other + 1,
+}
+fn flag(b bool) i32 = match b { true => 1, false => 2 }
+fn main() i32 = classify(0) + classify(-1) + classify(5) + (flag(true) * 10) + flag(false)
+";
+        assert!(matches!(run(classify), Value::I32(318)));
+
+        // the value is evaluated once, a match without a value is a statement.
+        let status = format!(
+            "{}fn main() i32 {{
+    mut calls = 0
+    mut total = 0
+    mut code i64 = 0
+    while code < 1000 {{
+        match Status.from_int(code) ?? Status.Gone {{
+            Status.Ok => {{ total += 1 }},
+            Status.NotFound => {{ total += 10 }},
+            Status.Gone => {{ total += 100 }},
+        }}
+        calls += 1
+        code += 404
+    }}
+    (total * 10) + calls
+}}
+",
+            STATUS
+        );
+        assert!(matches!(run_verified(&status), Value::I32(1113)));
+    }
+
+    #[test]
+    fn test_todo_and_unreachable_raise_where_they_are() {
+        let error = |source: &str| {
+            let mut vm = Vm::new();
+            let module = build(source, &mut vm).unwrap();
+            vm.run_module(module).unwrap_err().to_string()
+        };
+        // a todo arm makes the match exhaustive whatever the type of the other arms.
+        let source = format!(
+            "{}fn sides(s Shape) i64 = match s {{\n    Shape.Rect(w, _) => w,\n    \
+            _ => todo(\"round shapes\"),\n}}\n\
+            fn main() i64 = sides(Shape.Rect(1, 2)) + sides(Shape.Empty)\n",
+            SHAPE
+        );
+        assert_eq!(error(&source), "not yet implemented: round shapes at test:13:10");
+        assert_eq!(
+            error("fn later() string = todo()\nfn main() {\n    let name string = later()\n}\n"),
+            "not yet implemented at test:1:21"
+        );
+
+        // the code after a guard is reached when it is not.
+        let source = "fn double(x i64) i64 {\n    if x < 0 {\n        \
+            unreachable()\n    }\n    x * 2\n}\n\
+            fn main() i64 = double(4) + double(-1)\n";
+        assert_eq!(error(source), "entered unreachable code at test:3:9");
+        assert!(matches!(
+            run("fn double(x i64) i64 {\n    if x < 0 { unreachable() }\n    x * 2\n}\n\
+                fn main() i64 = double(4)\n"),
+            Value::I64(8)
+        ));
+
+        let err = check("fn main() {\n    unreachable(1)\n}\n").map(|_| ()).unwrap_err();
+        assert_eq!(err, "attempting to call function expecting 0 parameters with 1 parameters");
+        let err = check("fn main(s string) {\n    todo(s)\n}\n").map(|_| ()).unwrap_err();
+        assert_eq!(err, "the message of 'todo' must be a string literal");
+    }
+
+    #[test]
+    fn test_todo_and_unreachable_fit_any_type() {
+        let fits = |body: &str| {
+            let source = format!("fn main(x i64) i64 {{\n{}\n}}\n", body);
+            check(&source).map(|_| ())
+        };
+        assert_eq!(fits("    let y i64 = todo()\n    y"), Ok(()));
+        assert_eq!(fits("    let y string = unreachable()\n    x"), Ok(()));
+        assert_eq!(fits("    x + todo()"), Ok(()));
+        assert_eq!(fits("    todo(\"later\") * x"), Ok(()));
+        assert_eq!(fits("    if todo() < x { x } else { x + 1 }"), Ok(()));
+        assert_eq!(fits("    let y i64 = todo() - unreachable()\n    y"), Ok(()));
+        assert_eq!(
+            fits("    let y string = x + todo()\n    x"),
+            Err("incompatible types, expected 'string' and found 'i64'".to_string())
+        );
+
+        let error = |source: &str| {
+            let mut vm = Vm::new();
+            let module = build(source, &mut vm).unwrap();
+            vm.run_module(module).unwrap_err().to_string()
+        };
+        let source = "fn main() i64 {\n    let x i64 = 2\n    x + todo() * x\n}\n";
+        assert_eq!(error(source), "not yet implemented at test:3:9");
+        let source = "fn main() bool {\n    let x i64 = 2\n    todo() == x\n}\n";
+        assert_eq!(error(source), "not yet implemented at test:3:5");
+    }
+
+    #[test]
+    fn test_invalid_matches() {
+        let check_match = |arms: &str| {
+            check(&format!("{}fn main(s Shape) i64 = match s {{ {} }}\n", SHAPE, arms))
+                .map(|_| ())
+        };
+        assert_eq!(
+            check_match("Shape.Circle(r) => r"),
+            Err(
+                "match on 'test.Shape' is not exhaustive, missing Shape.Rect, Shape.Empty"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check_match("Shape.Rect(w) => w, _ => 0"),
+            Err("attempting to call function expecting 2 parameters with 1 parameters".to_string())
+        );
+        assert_eq!(
+            check_match("Shape.Square(w) => w, _ => 0"),
+            Err("use of undeclared variant 'Square' of type 'test.Shape'".to_string())
+        );
+        assert_eq!(
+            check_match("Shape.Circle(1) => 1, _ => 0"),
+            Err("a field of a variant is matched by a name or '_'".to_string())
+        );
+        assert_eq!(
+            check_match("Shape.Rect(w, w) => w, _ => 0"),
+            Err("declaration of duplicate name 'w'".to_string())
+        );
+        assert_eq!(
+            check_match("Shape.Empty => true, _ => 0"),
+            Err("incompatible types, expected 'i64' and found 'bool'".to_string())
+        );
+        assert_eq!(
+            check("fn main(b bool) i32 = match b { true => 1 }\n").map(|_| ()),
+            Err("match on 'bool' is not exhaustive, missing false".to_string())
+        );
+        assert_eq!(
+            check("fn main(n u8) i32 = match n { 0 => 1, 256 => 2, _ => 3 }\n").map(|_| ()),
+            Err("pattern '256' does not fit in 'u8'".to_string())
+        );
+        assert_eq!(
+            check("fn main(n i32) i32 = match n { 0 => 1, 1 => 2 }\n").map(|_| ()),
+            Err("match on 'i32' is not exhaustive, missing _".to_string())
+        );
+    }
+
+    #[test]
+    fn test_local_functions() {
+        // a local function can use the local functions declared before it.
+        let source = "fn main() i64 {
+    fn square(a i64) i64 = a * a
+    mut total i64 = 0
+    mut i i64 = 1
+    while i < 4 {
+        fn bump(a i64) i64 = square(a) + 1
+        total += bump(i)
+        i += 1
+    }
+    total
+}
+";
+        assert!(matches!(run(source), Value::I64(17)));
+
+        // the local functions of different functions do not collide.
+        let source = "fn one() i64 {
+    fn value() i64 = 1
+    value()
+}
+fn main() i64 {
+    fn value() i64 = 10
+    value() + one()
+}
+";
+        assert!(matches!(run(source), Value::I64(11)));
+    }
+
+    #[test]
+    fn test_item_placement() {
+        let in_body =
+            |item: &str| check(&format!("fn main() {{\n    {}\n}}\n", item)).map(|_| ());
+        let at_module = |item: &str| check(&format!("{}\nfn main() {{}}\n", item)).map(|_| ());
+
+        for item in [
+            "let x = 1",
+            "mut x = 1",
+            "struct Point { pub x i64 }",
+            "enum Color { Red, Green }",
+            "fn add(a i64, b i64) i64 = a + b",
+            "pub fn add(a i64, b i64) i64 = a + b",
+        ] {
+            assert_eq!(at_module(item), Ok(()), "{}", item);
+        }
+
+        assert_eq!(in_body("let x = 1"), Ok(()));
+        assert_eq!(in_body("mut x = 1"), Ok(()));
+        assert_eq!(in_body("fn add(a i64, b i64) i64 = a + b"), Ok(()));
+        assert_eq!(
+            in_body("struct Point { pub x i64 }"),
+            Err(
+                "nested structures are not yet supported — declare 'Point' at module scope"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            in_body("enum Color { Red, Green }"),
+            Err(
+                "nested enumerations are not yet supported — declare 'Color' at module scope"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            in_body("use math"),
+            Err("'use' is only allowed at module scope".to_string())
+        );
+        assert_eq!(
+            in_body("pub fn add(a i64, b i64) i64 = a + b"),
+            Err("'pub' is only allowed at module scope".to_string())
+        );
+        // a file compiled on its own has no modules to import.
+        assert_eq!(
+            at_module("use math"),
+            Err("module 'math' is not part of the program".to_string())
+        );
+    }
+
+    #[test]
+    fn test_local_functions_are_confined_to_their_body() {
+        assert_eq!(
+            check("fn main() {\n    fn add(a i64) i64 = a\n}\nfn other() i64 = add(1)\n")
+                .map(|_| ()),
+            Err("use of undeclared identifier 'add'".to_string())
+        );
+        let source =
+            "fn main() {\n    if true {\n        fn add(a i64) i64 = a\n    }\n    add(1)\n}\n";
+        assert_eq!(
+            check(source).map(|_| ()),
+            Err("use of undeclared identifier 'add'".to_string())
+        );
+        assert_eq!(
+            check("fn main(x i64) {\n    fn add(a i64) i64 = a + x\n}\n").map(|_| ()),
+            Err(
+                "local function 'add' can not use 'x', a local of the enclosing function"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = "fn apply(f fn(i64) i64, x i64) i64 = f(x)
+fn adder(n i64) fn(i64) i64 = fn(x i64) i64 = x + n
+fn main() i64 {
+    let base i64 = 100
+    let add = fn(x i64) i64 = x + base
+    let twice = fn(x i64) i64 {
+        let inner = fn(y i64) i64 = y + base
+        inner(inner(x))
+    }
+    let double = fn(x i64) i64 = x * 2
+    add(1) + apply(add, 2) + twice(3) + apply(double, 4) + adder(5)(6)
+}
+";
+        // 101 + 102 + 203 + 8 + 11
+        assert!(matches!(run(source), Value::I64(425)));
+
+        // the value captured is the one the local has when the closure is created.
+        let source = "fn main() i64 {
+    mut total i64 = 0
+    mut i i64 = 1
+    while i < 4 {
+        let n = i
+        let f = fn() i64 = n * 10
+        total += f()
+        i += 1
+    }
+    total
+}
+";
+        assert!(matches!(run(source), Value::I64(60)));
+    }
+
+    #[test]
+    fn test_trailing_blocks() {
+        let source = "fn apply(x i64, f fn(i64) i64) i64 = f(x)
+fn twice(f fn() i64) i64 = f() + f()
+fn combine(a i64, b i64, f fn(i64, i64) i64) i64 = f(a, b)
+struct Counter {
+    n i64
+    pub fn map(self, f fn(i64) i64) i64 = f(self.n)
+}
+fn main() i64 {
+    let base i64 = 100
+    let counter = Counter { n: 4 }
+    let a = apply(1) { |x| x + base }
+    let b = twice { || base / 20 }
+    let c = combine(2, 3) { |x, y|
+        let product = x * y
+        product + 1
+    }
+    let d = apply(3) { |x| apply(x) { |y| x * y } }
+    let e = counter.map { |n i64| n * 1000 }
+    a + b + c + d + e
+}
+";
+        // 101 + 10 + 7 + 9 + 4000
+        assert!(matches!(run(source), Value::I64(4127)));
+    }
+
+    #[test]
+    fn test_trailing_block_errors() {
+        let functions = "fn apply(x i64, f fn(i64) i64) i64 = f(x)
+fn map(f fn(i64) i64, x i64) i64 = f(x)
+fn square(x i64) i64 = x * x
+struct Counter {
+    n i64
+    pub fn map(self, f fn(i64) i64, x i64) i64 = f(x)
+}
+";
+        let err = |body: &str| {
+            check(&format!("{}fn main() i64 {{\n    {}\n}}\n", functions, body))
+                .map(|_| ())
+                .unwrap_err()
+        };
+        assert_eq!(
+            err("map(1) { |x| x }"),
+            "this call provides a trailing block but 'map' expects its function as argument 1 of 2"
+        );
+        assert_eq!(
+            err("apply { |x| x }"),
+            "this call provides a trailing block but 'apply' expects its function as argument 2 \
+             of 2"
+        );
+        assert_eq!(
+            err("square(2) { |x| x }"),
+            "this call provides a trailing block but 'square' takes no function"
+        );
+        assert_eq!(
+            err("square { |x| x }"),
+            "this call provides a trailing block but 'square' takes no function"
+        );
+        assert_eq!(
+            err("Counter { n: 1 }.map(2) { |x| x }"),
+            "this call provides a trailing block but 'map' expects its function as argument 1 of 2"
+        );
+        // the parameters and the result of the block are checked against the expected function.
+        assert_eq!(
+            err("apply(1) { |x bool| 1 }"),
+            "incompatible types, expected '(i64) i64' and found '(bool) i64'"
+        );
+        assert_eq!(
+            err("apply(1) { |x, y| x }"),
+            "the trailing block of this call takes 2 parameters but 'apply' passes it 1"
+        );
+    }
+
+    #[test]
+    fn test_literal_parameters_are_inferred() {
+        let apply = "fn apply(x i64, f fn(i64) i64) i64 = f(x)\n";
+        // each literal takes the types of the function expected where it is written.
+        let source = format!(
+            "{}fn main() i64 = apply(1, fn(x) = apply(x, fn(y) = y * 10) + x)\n",
+            apply
+        );
+        assert!(matches!(run(&source), Value::I64(11)));
+
+        let err = |body: &str| {
+            check(&format!("{}fn main() i64 {{\n    {}\n}}\n", apply, body))
+                .map(|_| ())
+                .unwrap_err()
+        };
+        assert_eq!(
+            err("apply(1, fn(x, y) = x)"),
+            "this function literal takes 2 parameters but a function of 1 is expected here"
+        );
+        assert_eq!(
+            err("let g = fn(x) = x\n    1"),
+            "cannot infer the type of parameter 'x', declare its type"
+        );
+        assert_eq!(
+            err("let g i64 = fn(x i64, y) = x\n    1"),
+            "cannot infer the type of parameter 'y', declare its type"
+        );
+    }
+
+    // builds two lists of a million closures, run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_long_chain_of_closures() {
+        // every closure captures the one before it, a list the collector frees while the next
+        // is built and the vm releases when it is dropped, neither recursing down the chain.
+        let source = "fn build(n i64) fn() i64 {
+    mut head = fn() i64 = 0
+    mut i i64 = 0
+    while i < n {
+        let next = head
+        head = fn() i64 = next() + 1
+        i += 1
+    }
+    head
+}
+
+fn main() i64 {
+    mut built i64 = 0
+    while built < 2 {
+        let list = build(1000000)
+        built += 1
+    }
+    built
+}
+";
+        assert!(matches!(run(source), Value::I64(2)));
+    }
+
+    #[test]
+    fn test_closure_captures_are_checked() {
+        assert_eq!(
+            check("fn main() {
+    mut n i64 = 1
+    let f = fn() i64 = n
+}
+").map(|_| ()),
+            Err("'n' is mutable, a closure captures locals by value and can only capture \
+                 immutable ones"
+                .to_string())
+        );
+        // a local function in a closure is not one.
+        let source = "fn main() {
+    let f = fn(x i64) i64 {
+        fn g() i64 = x
+        \
+                      g()
+    }
+}
+";
+        assert_eq!(
+            check(source).map(|_| ()),
+            Err(
+                "local function 'g' can not use 'x', a local of the enclosing function"
+                    .to_string()
+            )
+        );
+        let source = "struct Point {
+    x i64
+    fn getter(self) fn() i64 = fn() i64 = self.x
+\
+                      }
+fn main() {}
+";
+        assert_eq!(
+            check(source).map(|_| ()),
+            Err("a closure can not capture 'self'".to_string())
+        );
+        assert_eq!(
+            check("fn main() {
+    let f = fn(x i64) i64 = x
+    f(true)
+}
+").map(|_| ()),
+            Err("incompatible types, expected 'i64' and found 'bool'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_functions_are_values() {
+        let source = "fn double(x i64) i64 = x * 2
+fn square(x i64) i64 = x * x
+struct Op {
+    pub f fn(i64) i64
+}
+fn main() i64 {
+    fn inner(x i64) i64 = x + 100
+    let f = double
+    mut g fn(i64) i64 = square
+    g = inner
+    let op = Op { f: square }
+    let h = op.f
+    mut fs Vec[fn(i64) i64] = Vec[fn(i64) i64].new()
+    fs.push(double)
+    fs.push(inner)
+    let first = fs[1]
+    f(1) + g(2) + h(3) + first(4)
+}
+";
+        // 2 + 102 + 9 + 104
+        assert!(matches!(run(source), Value::I64(217)));
+
+        // calls through a variable are checked against the signature of the function.
+        let function = |body: &str| {
+            let source = format!("fn double(x i64) i64 = x * 2\nfn main() {{\n{}\n}}\n", body);
+            check(&source).map(|_| ())
+        };
+        assert_eq!(
+            function("    let f = double\n    f(1, 2)"),
+            Err("attempting to call function expecting 1 parameters with 2 parameters".to_string())
+        );
+        assert_eq!(
+            function("    let f = double\n    f(true)"),
+            Err("incompatible types, expected 'i64' and found 'bool'".to_string())
+        );
+        assert_eq!(
+            function("    let f fn(i64, i64) i64 = double"),
+            Err("incompatible types, expected '(i64, i64) i64' and found '(i64) i64'".to_string())
+        );
+        assert_eq!(
+            function("    let v = Vec[i64].new()\n    v[fn(i64) i64]"),
+            Err("expected a value, found a type".to_string())
+        );
+    }
+
+    #[test]
+    fn test_bench_items_are_measured_in_instructions() {
+        let source = "bench \"folded\" {
+    2 * 3
+}
+bench \"kept\" {
+    black_box(2) * 3
+}
+fn main() i64 {
+    let bench i64 = black_box(20)
+    bench + black_box(2)
+}
+";
+        let mut vm = Vm::new();
+        let module = build(source, &mut vm).unwrap();
+        let bench = |vm: &mut Vm, name: &str| {
+            let function = *module
+                .objects()
+                .iter()
+                .find(|object| match object {
+                    Value::Function(function) => {
+                        function.name().as_str() == format!("{}{}", BENCH_PREFIX, name)
+                    }
+                    _ => false,
+                })
+                .unwrap();
+            let config = BenchConfig {
+                warmup: Budget::Iterations(2),
+                measure: Budget::Iterations(10),
+            };
+            vm.bench(module, function, &config).unwrap()
+        };
+        let folded = bench(&mut vm, "folded");
+        let kept = bench(&mut vm, "kept");
+        assert_eq!(folded.iterations(), 10);
+        assert_eq!(folded.instructions.std_dev(), 0.0);
+        // nothing is folded without the passes, the black box costs no instruction.
+        assert_eq!(kept.instructions, folded.instructions);
+        assert_eq!(bench(&mut vm, "kept").instructions, kept.instructions);
+
+        // the benchmarks are not run by main, black_box is only the value.
+        assert!(matches!(run(source), Value::I64(22)));
+        assert_eq!(
+            check("fn main() i64 = black_box(1, 2)\n").map(|_| ()),
+            Err("attempting to call function expecting 1 parameters with 2 parameters".to_string())
+        );
+    }
+
+    const REFLECTED: &str = "struct Point {\n    pub x i64\n    y i64\n    pub label string\n}\n\
+        struct Pair {\n    pub first Point\n    pub count i32\n}\n\
+        enum Color { Red, Green }\n\
+        fn dump(value any, fields [(string, string)]) {\n\
+        \x20   mut i i64 = 0\n    while i < fields.len() {\n\
+        \x20       log.info(fields[i].0 + \" \" + fields[i].1)\n\
+        \x20       log.info(reflect.get_field(value, fields[i].0) ?? \"none\")\n\
+        \x20       i += 1\n    }\n}\n";
+
+    #[test]
+    fn test_structs_are_dumped_through_reflection() {
+        let main = "fn main() {\n    let point = Point { x: 1, y: 2, label: \"p\" }\n\
+            \x20   dump(point, reflect.fields[Point]())\n\
+            \x20   dump(Pair { first: point, count: 3 }, reflect.fields[Pair]())\n\
+            \x20   log.info(reflect.variants[Color]()[1])\n\
+            \x20   log.info(reflect.get_field(point, \"y\") ?? \"none\")\n\
+            \x20   log.info(reflect.get_field(point, \"z\") ?? \"none\")\n}\n";
+        // the tables of the fields go through the compiled module.
+        let messages = run_logged(&format!("{}{}", REFLECTED, main)).unwrap();
+        let expected = [
+            "x i64",
+            "1",
+            "label string",
+            "p",
+            "first test.Point",
+            "Point { 1, 2, \"p\" }",
+            "count i32",
+            "3",
+            "Green",
+            // a private field is not found, like a name that is not a field.
+            "none",
+            "none",
+        ];
+        assert_eq!(messages, expected);
+
+        let error = |main: &str| {
+            check(&format!("{}fn main() {{\n    {}\n}}\n", REFLECTED, main)).map(|_| ())
+        };
+        assert_eq!(
+            error("reflect.fields[Color]()"),
+            Err("type must be a struct, found 'test.Color'".to_string())
+        );
+        assert_eq!(
+            error("reflect.variants[Point]()"),
+            Err("type must be an enum, found 'test.Point'".to_string())
+        );
+        assert_eq!(
+            error("reflect.methods[Point]()"),
+            Err("unknown function 'reflect.methods', expected fields, variants or get_field"
+                .to_string())
+        );
+        assert_eq!(
+            error("reflect.fields()"),
+            Err("'reflect.fields' takes 1 type arguments, found 0".to_string())
+        );
+        assert_eq!(
+            error("reflect.get_field(1, \"x\")"),
+            Err("type must be a struct, found 'i32'".to_string())
+        );
+        // the value is an instance, the fields of a type are reflected through its name.
+        assert_eq!(
+            error("reflect.get_field(Point, \"x\")"),
+            Err("expecting an instance, found type 'test.Point'".to_string())
+        );
+    }
+
+    const GENERICS: &str = "fn identity[T](x T) T = x\n\
+        fn pair[A, B](a A, b B) (A, B) = (a, b)\n\
+        fn twice[T](x T) (T, T) {\n    let both = pair(x, x)\n    both\n}\n\
+        fn apply[T](f fn(T) T, x T) T = f(x)\n";
+
+    #[test]
+    fn test_generic_functions_are_instantiated() {
+        let main = "fn main() {\n    let a i64 = 1\n    log.info(identity(a))\n    \
+            log.info(identity(\"s\"))\n    log.info(pair(a, \"x\"))\n    \
+            log.info(twice(identity(a)))\n    log.info(twice(\"w\").1)\n}\n";
+        let source = format!("{}{}", GENERICS, main);
+        let messages = run_logged(&source).unwrap();
+        assert_eq!(messages, ["1", "s", "(1, \"x\")", "(1, 1)", "w"]);
+        let applied = format!("{}fn main() i64 = apply(fn(x i64) i64 = x + 1, 1)\n", GENERICS);
+        assert!(matches!(run(&applied), Value::I64(2)));
+
+        // an instance for each type arguments, the generic functions are not built.
+        let file = check(&source).unwrap();
+        let mut items = file
+            .stmts()
+            .iter()
+            .filter_map(|stmt| match stmt.inner() {
+                HirStmtKind::Item(entity) => Some(entity.borrow().name().to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        items.sort();
+        let expected = [
+            "identity[i64]",
+            "identity[string]",
+            "main",
+            "pair[i64, i64]",
+            "pair[i64, string]",
+            "pair[string, string]",
+            "twice[i64]",
+            "twice[string]",
+        ];
+        assert_eq!(items, expected);
+        let twice = file.find_entity_by_name("twice[i64]").unwrap();
+        assert_eq!(twice.borrow().ty().to_string(), "(i64) (i64, i64)");
+
+        let error = |main: &str| {
+            check(&format!("{}fn main() {{\n    {}\n}}\n", GENERICS, main)).map(|_| ())
+        };
+        assert_eq!(
+            error("let f = identity"),
+            Err("generic function 'identity' can only be called, it has no value".to_string())
+        );
+        assert_eq!(
+            error("identity(1, 2)"),
+            Err("attempting to call function expecting 1 parameters with 2 parameters".to_string())
+        );
+        assert_eq!(
+            error("let x i64 = identity(\"s\")"),
+            Err("incompatible types, expected 'i64' and found 'string'".to_string())
+        );
+        let conflict =
+            "fn first[T](both (T, T)) T = both.0\nfn main() {\n    first((1, \"x\"))\n}\n";
+        assert_eq!(
+            check(conflict).map(|_| ()),
+            Err("conflicting types for type parameter 'T', found 'i32' and 'string'".to_string())
+        );
+        let unused = "fn make[T]() i32 = 1\nfn main() {\n    make()\n}\n";
+        assert_eq!(
+            check(unused).map(|_| ()),
+            Err("cannot infer type parameter 'T' of 'make' from the arguments".to_string())
+        );
+        let not_supported =
+            Err("type parameters are only supported on functions declared at module scope"
+                .to_string());
+        let method = "struct Point {\n    pub x i64\n    pub fn get[T](self, x T) T = x\n}\n\
+            fn main() {}\n";
+        assert_eq!(check(method).map(|_| ()), not_supported);
+        let local = "fn main() {\n    fn inner[T](x T) T = x\n}\n";
+        assert_eq!(check(local).map(|_| ()), not_supported);
+    }
+
+    #[test]
+    fn test_unit_is_a_value() {
+        let source = "fn f() {}\nfn g() unit {}\nfn h() () = log.info(\"h\")\nfn main() {\n    \
+            let x unit = f()\n    log.info(x)\n    let units = [f(), g()]\n    \
+            log.info(units)\n    mut m Map[string, unit] = Map[string, unit].new()\n    \
+            m.insert(\"a\", h())\n    log.info(m)\n    log.info(x == g())\n    \
+            log.info(x != f())\n    f()\n    let after = 1\n    log.info(after)\n}\n";
+        let messages = run_logged(source).unwrap();
+        assert_eq!(
+            messages,
+            ["()", "[(), ()]", "h", "{\"a\": ()}", "true", "false", "1"]
+        );
+
+        // no return type, `unit` and `()` are the same type.
+        let same = "fn f() {}\nfn g() unit {}\nfn h() () {}\nfn main() {\n    mut p fn() = f\n    \
+            p = g\n    p = h\n}\n";
+        assert!(check(same).is_ok());
+        assert_eq!(
+            check("fn f() {}\nfn main() {\n    let x i64 = f()\n}\n").map(|_| ()),
+            Err("incompatible types, expected 'i64' and found 'unit'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_line_table_maps_every_line_with_code() {
+        let source = "fn add(a i64, b i64) i64 = a + b\n\nfn total(n i64) i64 {\n    \
+            mut sum i64 = 0\n\n    // every value up to n\n    mut i i64 = 0\n    \
+            while i < n {\n        sum += add(i,\n            1)\n        i += 1\n    }\n    \
+            sum\n}\n\nfn main() i64 = total(3)\n";
+        let hir_file = check(source).unwrap();
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        let (module, source_map) =
+            CodeGen::build_with_source_map(&FileMap::new(), &hir_file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+
+        let lines = &source_map.function("total").unwrap().lines;
+        assert_eq!(lines.mapped_lines(), vec![4, 7, 8, 9, 10, 11, 13]);
+        let statements = lines
+            .runs()
+            .iter()
+            .filter(|run| run.is_statement_start)
+            .map(|run| run.line)
+            .collect::<Vec<_>>();
+        assert_eq!(statements, vec![4, 7, 8, 9, 11, 13]);
+        // the argument on a line of its own is only a stop when stepping into.
+        let argument = lines.offset_of(10).unwrap();
+        assert!(lines.stops_at(argument, Step::Into));
+        assert!(!lines.stops_at(argument, Step::Over));
+
+        let breakpoint = |line| source_map.bind_breakpoint(0, line).map(|bp| bp.to_string());
+        assert_eq!(breakpoint(9).unwrap(), "breakpoint on line 9 of total");
+        // a blank line and a comment snap forward to the next line of the function.
+        for line in [5, 6] {
+            let moved = format!(
+                "breakpoint on line {} moved to line 7 of total, line {} has no code",
+                line, line
+            );
+            assert_eq!(breakpoint(line).unwrap(), moved);
+        }
+        assert_eq!(source_map.bind_breakpoint(0, 12).unwrap().line, 13);
+        // the blank line between functions is in none of them.
+        assert_eq!(breakpoint(2), None);
+
+        vm.run_module(module).unwrap();
+        assert!(matches!(vm.top(), Value::I64(6)));
+    }
+
+    #[test]
+    fn test_sys_describes_the_build_and_the_host() {
+        let source = "fn host() string = sys.host()\n\
+            fn main() {\n    log.info(sys.host())\n    log.info(sys.module_name())\n\
+            \x20   log.info(sys.language_version())\n    log.info(sys.opt_level())\n\
+            \x20   log.info(sys.debug_info())\n\
+            \x20   log.info(task.spawn(host).join().ok() ?? \"none\")\n}\n";
+        let compiled = compile(source);
+        let run = |host: Option<&str>| {
+            let mut vm = Vm::new();
+            let mut capabilities = Capabilities::none();
+            capabilities.grant(Capability::Spawn);
+            vm.set_capabilities(capabilities);
+            if let Some(host) = host {
+                vm.set_host(host);
+            }
+            let module = compiled.load(&mut vm);
+            run_recorded(&mut vm, module).0
+        };
+        let version = crate::analysis::LANGUAGE_VERSION;
+        assert_eq!(run(None), ["cli", "test", version, "0", "true", "cli"]);
+        // a task runs on the host of the vm spawning it.
+        assert_eq!(run(Some("editor")), ["editor", "test", version, "0", "true", "editor"]);
+
+        let error = |main: &str| check(&format!("fn main() {{\n    {}\n}}\n", main)).map(|_| ());
+        assert_eq!(
+            error("sys.uptime()"),
+            Err("unknown function 'sys.uptime', expected language_version, module_name, \
+                 opt_level, debug_info or host"
+                .to_string())
+        );
+        assert_eq!(
+            error("sys.host(1)"),
+            Err("attempting to call function expecting 0 parameters with 1 parameters".to_string())
+        );
+        // a name of the program hides the module.
+        assert!(error("let sys = 1\n    sys.host()").is_err());
+    }
+
+    #[test]
+    fn test_typer_limits_the_nesting() {
+        let check_with_depth = |source: &str, max_depth: usize| {
+            let file = File::raw_test(source.to_string());
+            let mut parser = Parser::new(&file);
+            let parsed = parser.init().and_then(|_| parser.parse_file()).unwrap();
+            let mut analysis = Analysis::new();
+            analysis.set_max_expr_depth(max_depth);
+            analysis
+                .check(parsed, LanguageMode::Default)
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        };
+        let source = format!("fn main() i32 = {}1\n", "-".repeat(6));
+        assert!(check_with_depth(&source, 8).is_ok());
+        let source = format!("fn main() i32 = {}1\n", "-".repeat(12));
+        assert_eq!(
+            check_with_depth(&source, 8),
+            Err("expression too deeply nested, the limit is 8".to_string())
+        );
+        // the operators of a chain resolve without nesting.
+        let source = format!("fn main() i32 = {}\n", vec!["1"; 100].join(" + "));
+        assert!(check_with_depth(&source, 8).is_ok());
+    }
+}
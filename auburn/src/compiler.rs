@@ -0,0 +1,784 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::analysis::{Analysis, ParsedModule, PartialModule, Program};
+use crate::error::Error;
+use crate::passes::{Limits, Metrics, MirPass, Pipeline};
+use crate::syntax::{ParsedFile, Parser, DEFAULT_MAX_EXPR_DEPTH};
+use crate::system::{module_name, resolve_imports, File, FileMap, ModuleResolver};
+use crate::utils::render_error;
+use crate::LanguageMode;
+
+/// checks programs whose source is held in memory, for tools embedding the compiler. Every
+/// virtual file is a module of the program, named by its path like a file of a directory. An
+/// import of another module is found by the resolver, there is none unless one is installed.
+pub struct Compiler {
+    file_map: FileMap,
+    analysis: Analysis,
+    mode: LanguageMode,
+    max_expr_depth: usize,
+    modules: Vec<Rc<File>>,
+    passes: Pipeline,
+    resolver: Option<Box<dyn ModuleResolver>>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            file_map: FileMap::new(),
+            analysis: Analysis::new(),
+            mode: LanguageMode::default(),
+            max_expr_depth: DEFAULT_MAX_EXPR_DEPTH,
+            modules: vec![],
+            passes: Pipeline::new(),
+            resolver: None,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: LanguageMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn file_map(&self) -> &FileMap {
+        &self.file_map
+    }
+
+    /// adds a module the program can import, `shapes/circle.au` is the module `shapes.circle`.
+    pub fn add_virtual_file<P: AsRef<Path>>(&mut self, name: P, source: &str) -> Rc<File> {
+        let file = self.file_map.add_virtual_file(name, source.to_owned());
+        self.modules.push(file.clone());
+        file
+    }
+
+    /// finds the modules imported by a program that are not virtual files, they are resolved
+    /// again for every program checked.
+    pub fn set_resolver(&mut self, resolver: Box<dyn ModuleResolver>) {
+        self.resolver = Some(resolver);
+    }
+
+    /// a pass run over every program that checks, along with the built in passes. The warnings
+    /// of a pass are warnings of the program.
+    pub fn add_pass(&mut self, pass: Box<dyn MirPass>) {
+        self.passes.add_pass(pass);
+    }
+
+    /// the limits of the metrics lints, a function over a limit is a warning of the program.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.passes.set_limits(limits);
+    }
+
+    /// the functions of the program checked last, see `Metrics::table`.
+    pub fn metrics(&self) -> &Metrics {
+        self.passes.metrics()
+    }
+
+    /// checks source named `name` as the program along with the virtual files added so far.
+    pub fn compile_str<P: AsRef<Path>>(&mut self, name: P, source: &str) -> Result<Program, Error> {
+        let (program, modules) = self.parse_program(name, source)?;
+        let mut program = self
+            .analysis
+            .check_program(program.as_str(), modules, self.mode)?;
+        let warnings = self
+            .passes
+            .run(&mut program.file, self.analysis.type_map_mut())
+            .map_err(|mut errors| errors.remove(0))?;
+        program.warnings.extend(warnings);
+        Ok(program)
+    }
+
+    /// checks source like `compile_str` but keeps what resolved when some items do not, the
+    /// errors of the failed items are its diagnostics. Source that does not parse is an error.
+    pub fn compile_str_partial<P: AsRef<Path>>(
+        &mut self,
+        name: P,
+        source: &str,
+    ) -> Result<PartialModule, Error> {
+        let (program, modules) = self.parse_program(name, source)?;
+        self.analysis
+            .check_program_partial(program.as_str(), modules, self.mode)
+    }
+
+    fn parse_program<P: AsRef<Path>>(
+        &mut self,
+        name: P,
+        source: &str,
+    ) -> Result<(String, Vec<ParsedModule>), Error> {
+        let program = name
+            .as_ref()
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.add_virtual_file(name, source);
+
+        let mut modules = vec![];
+        for file in &self.modules {
+            let path = PathBuf::from(file.path());
+            modules.push(ParsedModule {
+                name: module_name(&path),
+                path,
+                file: self.parse(file)?,
+                imports: HashMap::new(),
+            });
+        }
+
+        if let Some(resolver) = self.resolver.as_deref() {
+            let max_depth = self.max_expr_depth;
+            resolve_imports(&mut modules, &mut self.file_map, resolver, |file| {
+                Self::parse_with_depth(file, max_depth)
+            })?;
+        }
+        Ok((program, modules))
+    }
+
+    /// an error along with the line of source it points at.
+    pub fn render_error(&self, err: &Error) -> String {
+        render_error(&self.file_map, err)
+    }
+
+    fn parse(&self, file: &File) -> Result<ParsedFile, Error> {
+        Self::parse_with_depth(file, self.max_expr_depth)
+    }
+
+    fn parse_with_depth(file: &File, max_depth: usize) -> Result<ParsedFile, Error> {
+        let mut parser = Parser::new(file);
+        parser.set_max_depth(max_depth);
+        parser.init()?;
+        parser.parse_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::Compiler;
+    use crate::code_gen::CodeGen;
+    use crate::error::Error;
+    use crate::ir::ast::BinaryOp;
+    use crate::ir::hir::{BinaryExpr, HirExpr, HirExprKind, HirFile, HirStmtKind, MirNode};
+    use crate::ir::visit::MirVisitor;
+    use crate::passes::{MirPass, PassContext};
+    use crate::system::{ModuleResolver, ModuleSource, ResolveError};
+    use oxide::{Value, Vm};
+
+    #[test]
+    fn test_compile_virtual_files() {
+        let mut compiler = Compiler::new();
+        compiler.add_virtual_file("geometry/area.au", "pub fn square(x i64) i64 = x * x\n");
+        let program = compiler
+            .compile_str("app.au", "use geometry.area\nfn main() i64 = square(7)\n")
+            .unwrap();
+
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        let module = CodeGen::build(compiler.file_map(), &program.file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        vm.run_module(module).unwrap();
+        assert!(matches!(vm.top(), Value::I64(49)));
+    }
+
+    #[test]
+    fn test_render_error_of_virtual_file() {
+        let mut compiler = Compiler::new();
+        compiler.add_virtual_file(
+            "shapes.au",
+            "pub fn area(w i64) i64 = w * w\n\npub fn volume(w i64) i64 = area(w) * depth\n",
+        );
+        let err = compiler
+            .compile_str("app.au", "use shapes\nfn main() i64 = volume(2)\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            compiler.render_error(&err),
+            "shapes.au:3:38| use of undeclared identifier 'depth'\n\
+            >\tpub fn volume(w i64) i64 = area(w) * depth\n \t                                     ^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_array_size_of_imported_constant() {
+        let check = |app: &str| {
+            let mut compiler = Compiler::new();
+            compiler.add_virtual_file(
+                "geometry.au",
+                "let CORNERS = 4\npub let MAX_POINTS = CORNERS\nlet HIDDEN = 2\n\
+                pub let GRID = (MAX_POINTS * 2) + 1\npub let HUGE = 2147483647 + GRID\n",
+            );
+            let source = format!("use geometry\n{}\nfn main() {{}}\n", app);
+            let err = compiler.compile_str("app.au", &source).err().unwrap();
+            err.to_string()
+        };
+
+        assert_eq!(
+            check("fn first(p [i64; MAX_POINTS]) i64 = p"),
+            "incompatible types, expected 'i64' and found '[i64; 4]'"
+        );
+        assert_eq!(
+            check("fn first(p [i64; geometry.GRID - 1]) i64 = p"),
+            "incompatible types, expected 'i64' and found '[i64; 8]'"
+        );
+        assert_eq!(
+            check("fn first(p [i64; HIDDEN]) i64 = p"),
+            "'HIDDEN' is private to module 'geometry'"
+        );
+        // a constant that overflows its type is left for the runtime.
+        assert_eq!(
+            check("fn first(p [i64; HUGE]) i64 = p"),
+            "array size must be known while compiling, built from integer literals and \
+            immutable globals initialized by them"
+        );
+        assert_eq!(
+            check("fn first(n i64, p [i64; n]) i64 = p"),
+            "array size must be known while compiling, built from integer literals and \
+            immutable globals initialized by them"
+        );
+    }
+
+    #[test]
+    fn test_match_range_of_imported_constant() {
+        let compile = |arms: &str| {
+            let mut compiler = Compiler::new();
+            compiler.add_virtual_file(
+                "geometry.au",
+                "pub let MAX_POINTS = 4\npub let LIMIT = MAX_POINTS * 4\n",
+            );
+            let source = format!(
+                "use geometry\nfn size(n i32) i32 = match n {{\n{}\n}}\n\
+                fn main() i32 = size(3) + (size(4) * 10) + (size(-1) * 100) + (size(16) * 1000)\n",
+                arms
+            );
+            let program = compiler.compile_str("app.au", &source);
+            (compiler, program)
+        };
+
+        let (compiler, program) = compile(
+            "    0..geometry.MAX_POINTS => 1,\n    MAX_POINTS..LIMIT => 2,\n    -5..0 => 3,\n    \
+            _ => 4,",
+        );
+        let program = program.unwrap();
+        assert!(program.warnings.is_empty());
+        assert!(matches!(run(&compiler, &program.file), Value::I32(4321)));
+
+        // a range within an earlier one is never taken.
+        let (compiler, program) =
+            compile("    0..LIMIT => 1,\n    2..MAX_POINTS => 2,\n    _ => 3,");
+        let warnings = program
+            .unwrap()
+            .warnings
+            .iter()
+            .map(|warning| compiler.render_error(warning))
+            .collect::<String>();
+        assert_eq!(
+            warnings,
+            "app.au:4:5| this arm is never taken, the arms before it match every value it \
+            matches\n>\t    2..MAX_POINTS => 2,\n \t    ^^^^^^^^^^^^^\n"
+        );
+
+        let error = |arms: &str| compile(arms).1.err().unwrap().to_string();
+        assert_eq!(
+            error("    LIMIT..MAX_POINTS => 1,\n    _ => 2,"),
+            "range pattern '16..4' matches no value, its end is not after its start"
+        );
+        assert_eq!(
+            error("    0..n => 1,\n    _ => 2,"),
+            "a bound of a range pattern must be an integer or an immutable global known while \
+            compiling"
+        );
+        assert_eq!(
+            error("    0..MISSING => 1,\n    _ => 2,"),
+            "use of undeclared identifier 'MISSING'"
+        );
+    }
+
+    #[test]
+    fn test_constant_cycle_across_modules() {
+        let compile = |geometry: &str, limits: &str| {
+            let mut compiler = Compiler::new();
+            compiler.add_virtual_file("geometry.au", geometry);
+            compiler.add_virtual_file("limits.au", limits);
+            let source = "use geometry\nfn main() i32 = MAX_POINTS\n";
+            compiler.compile_str("app.au", source).err().unwrap().to_string()
+        };
+        assert_eq!(
+            compile(
+                "use limits\npub let MAX_POINTS = limits.LIMIT / 2\n",
+                "use geometry\npub let LIMIT = MAX_POINTS * 2\n",
+            ),
+            "constants of modules that import each other are initialized by each other: \
+            MAX_POINTS (geometry.au) -> LIMIT (limits.au) -> MAX_POINTS (geometry.au)"
+        );
+        // without a chain of constants through both it is only a cycle of the imports.
+        assert_eq!(
+            compile(
+                "use limits\npub let MAX_POINTS = LIMIT / 2\n",
+                "use geometry\npub let LIMIT = 8\n",
+            ),
+            "modules import each other: geometry -> limits -> geometry"
+        );
+    }
+
+    #[test]
+    fn test_member_misuse_points_at_declaration() {
+        let stats = "struct Stats {\n    pub count i64\n    fn len(self) i64 = self.count\n}\n";
+        let render = |main: &str| {
+            let mut compiler = Compiler::new();
+            let source = format!("{}fn size(s Stats) i64 = {}\nfn main() {{}}\n", stats, main);
+            let err = compiler.compile_str("app.au", &source).err().unwrap();
+            compiler.render_error(&err)
+        };
+
+        assert_eq!(
+            render("s.count()"),
+            "app.au:5:26| 'count' is a field of 'app.Stats', not a method\n\
+            note: remove the parentheses to read it\n\
+            >\tfn size(s Stats) i64 = s.count()\n \t                         ^^^^^\n\
+            app.au:2:9| note: 'count' is declared here\n\
+            >\t    pub count i64\n \t        ^^^^^\n"
+        );
+        assert_eq!(
+            render("s.len"),
+            "app.au:5:26| 'len' is a method of 'app.Stats', not a field\n\
+            note: add '()' to call it, a method can not be taken as a value\n\
+            >\tfn size(s Stats) i64 = s.len\n \t                         ^^^\n\
+            app.au:3:8| note: 'len' is declared here\n\
+            >\t    fn len(self) i64 = self.count\n \t       ^^^\n"
+        );
+
+        // a field holding a function is still called.
+        let mut compiler = Compiler::new();
+        let source = "fn double(x i64) i64 = x * 2\n\
+            struct Ops {\n    pub op = double\n}\n\
+            fn apply(o Ops) i64 = o.op(21)\nfn main() {}\n";
+        assert!(compiler.compile_str("app.au", source).is_ok());
+    }
+
+    #[test]
+    fn test_variant_misuse_points_at_declaration() {
+        let shape = "enum Shape {\n    Dot,\n    Rect(i64, i64)\n}\n";
+        let render = |main: &str| {
+            let mut compiler = Compiler::new();
+            let source = format!("{}fn main() i64 {{\n    {}\n}}\n", shape, main);
+            let err = compiler.compile_str("app.au", &source).err().unwrap();
+            compiler.render_error(&err)
+        };
+
+        assert_eq!(
+            render("Shape.Rect(1)"),
+            "app.au:6:5| attempting to call function expecting 2 parameters with 1 parameters\n\
+            >\t    Shape.Rect(1)\n \t    ^^^^^^^^^^^^^\n\
+            app.au:3:5| note: 'Rect' is declared here\n\
+            >\t    Rect(i64, i64)\n \t    ^^^^\n"
+        );
+        assert_eq!(
+            render("match Shape.Dot { Shape.Rect(w) => w, _ => 0 }"),
+            "app.au:6:23| attempting to call function expecting 2 parameters with 1 parameters\n\
+            >\t    match Shape.Dot { Shape.Rect(w) => w, _ => 0 }\n \t                      \
+            ^^^^^^^^^^^^^\n\
+            app.au:3:5| note: 'Rect' is declared here\n\
+            >\t    Rect(i64, i64)\n \t    ^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_assignment_of_immutable_points_at_declaration() {
+        let mut compiler = Compiler::new();
+        let source = "fn main() i64 {\n    let total = 1\n    total = 2\n    total\n}\n";
+        let err = compiler.compile_str("app.au", source).err().unwrap();
+        assert_eq!(
+            compiler.render_error(&err),
+            "app.au:3:5| attempting to mutable 'total' which is not mutable\n\
+            >\t    total = 2\n \t    ^^^^^\n\
+            app.au:2:9| note: 'total' is declared immutable here\n\
+            >\t    let total = 1\n \t        ^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_partial_module_keeps_resolved_items() {
+        let mut compiler = Compiler::new();
+        let source = "fn area(w i64) i64 = w * w\n\
+            fn volume(w i64) i64 = area(w) * depth\n\
+            fn main() i64 = area(7)\n";
+        let partial = compiler.compile_str_partial("app.au", source).unwrap();
+        assert!(!partial.is_complete());
+
+        let resolved = partial
+            .file
+            .stmts()
+            .iter()
+            .filter_map(|stmt| match stmt.inner() {
+                HirStmtKind::Item(entity) => Some(entity.borrow().name().to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(resolved, ["app::area", "app::main"]);
+        let diagnostics = partial
+            .diagnostics
+            .iter()
+            .map(|err| compiler.render_error(err))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            [
+                "app.au:2:34| use of undeclared identifier 'depth'\n\
+            >\tfn volume(w i64) i64 = area(w) * depth\n \t                                 ^^^^^\n"
+            ]
+        );
+
+        let volume = partial.find_entity("app::volume").unwrap();
+        assert_eq!(volume.borrow().type_name(), "failed");
+        assert_eq!(
+            volume
+                .borrow()
+                .failure()
+                .map(ToString::to_string)
+                .as_deref(),
+            Some("use of undeclared identifier 'depth'")
+        );
+
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        let err = CodeGen::build_partial(compiler.file_map(), &partial, &mut vm).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the module did not compile, 1 failed: app::volume"
+        );
+        let module = CodeGen::build_subset(compiler.file_map(), &partial, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        vm.run_module(module).unwrap();
+        assert!(matches!(vm.top(), Value::I64(49)));
+    }
+
+    #[test]
+    fn test_partial_module_reports_uses_of_failed_items() {
+        let mut compiler = Compiler::new();
+        compiler.add_virtual_file(
+            "shapes.au",
+            "pub fn area(w i64) i64 = w * depth\npub fn side() i64 = 2\n",
+        );
+        let source = "use shapes\nfn twice() i64 = area(2) * 2\nfn main() i64 = side()\n";
+        let partial = compiler.compile_str_partial("app.au", source).unwrap();
+        let diagnostics = partial
+            .diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            [
+                "use of undeclared identifier 'depth'",
+                "'area' failed to compile and can not be used"
+            ]
+        );
+        let failed = partial
+            .failed
+            .iter()
+            .map(|entity| entity.borrow().name().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(failed, ["shapes::area", "app::twice"]);
+        assert!(partial.file.get_entry().is_some());
+
+        // a program that checks is complete.
+        let mut compiler = Compiler::new();
+        let partial = compiler
+            .compile_str_partial("app.au", "fn main() i64 = 1\n")
+            .unwrap();
+        assert!(partial.is_complete());
+    }
+
+    #[test]
+    fn test_partial_module_reports_a_mistake_once() {
+        let mut compiler = Compiler::new();
+        compiler.add_virtual_file(
+            "shapes.au",
+            "pub fn side() i64 = 2\npub fn corner() i64 = depth\n",
+        );
+        let source = "use shapes\n\
+            fn area(s Shape) i64 = 1\n\
+            fn perimeter(s Shape) i64 = 2\n\
+            fn width(s Shape) i64 = 3\n\
+            fn height(s Shape) i64 = 4\n\
+            fn depth(s Shape) i64 = 5\n\
+            fn scale(s Shape, by i64) i64 = by\n\
+            fn sum(s Shape, t Shape) i64 = 6\n\
+            fn first(s Shape) i64 = 7\n\
+            fn last(s Shape) i64 = 8\n\
+            fn empty(s Shape) bool = true\n\
+            fn half() i64 = side() / \"two\"\n\
+            fn main() i64 = side()\n";
+        let partial = compiler.compile_str_partial("app.au", source).unwrap();
+        let diagnostics = partial
+            .diagnostics
+            .iter()
+            .map(|err| compiler.render_error(err))
+            .collect::<String>();
+        // the storm of 'Shape' is reported at its first use, and the module that failed
+        // first is still reported first.
+        assert_eq!(
+            diagnostics,
+            "shapes.au:2:23| use of undeclared identifier 'depth'\n\
+            >\tpub fn corner() i64 = depth\n \t                      ^^^^^\n\
+            app.au:2:11| use of undeclared identifier 'Shape'\n\
+            note: …and 9 more similar errors\n\
+            >\tfn area(s Shape) i64 = 1\n \t          ^^^^^\n\
+            app.au:12:26| incompatible types, expected 'i64' and found 'string'\n\
+            >\tfn half() i64 = side() / \"two\"\n \t                         ^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_partial_module_reports_every_failed_statement() {
+        let mut compiler = Compiler::new();
+        let source = "fn helper() i64 {\n    let a i64 = \"one\"\n    let b = a + 1\n    \
+            let c bool = 3\n    b\n}\n\
+            fn main() i64 {\n    let x = undefined\n    let y = x + 2\n    \
+            log.info(y)\n    helper()\n}\n";
+        let partial = compiler.compile_str_partial("app.au", source).unwrap();
+        let diagnostics = partial
+            .diagnostics
+            .iter()
+            .map(|err| format!("{} {}", err.pos().start(), err))
+            .collect::<Vec<_>>();
+        // the uses of `x` follow from its failed initializer, `a` has the type it is declared.
+        assert_eq!(
+            diagnostics,
+            [
+                "2:17 incompatible types, expected 'i64' and found 'string'",
+                "4:18 incompatible types, expected 'bool' and found 'i32'",
+                "8:13 use of undeclared identifier 'undefined'"
+            ]
+        );
+        let failed = partial
+            .failed
+            .iter()
+            .map(|entity| entity.borrow().name().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(failed, ["app::helper", "app::main"]);
+    }
+
+    /// counts divisions and warns of a remainder of one, as a tool outside of the compiler would.
+    struct Divisions {
+        count: Rc<Cell<usize>>,
+        warnings: Vec<Error>,
+    }
+
+    impl MirVisitor for Divisions {
+        fn visit_binary(&mut self, expr: &HirExpr, binary: &BinaryExpr) {
+            match (binary.op, binary.right.inner().kind()) {
+                (BinaryOp::Slash, _) => self.count.set(self.count.get() + 1),
+                (BinaryOp::Percent, HirExprKind::Integer(1)) => {
+                    let message = "the remainder of dividing by one is always zero".to_owned();
+                    let warning = Error::pass_diagnostic("divisions", message);
+                    self.warnings.push(warning.with_position(expr.position()));
+                }
+                _ => {}
+            }
+            self.visit_expr(&binary.right);
+        }
+    }
+
+    impl MirPass for Divisions {
+        fn name(&self) -> &str {
+            "divisions"
+        }
+
+        fn run(&mut self, file: &mut HirFile, cx: &mut PassContext) {
+            self.visit_file(file);
+            self.warnings.drain(..).for_each(|warning| cx.warn(warning));
+        }
+    }
+
+    #[test]
+    fn test_external_pass_runs_with_the_built_in_passes() {
+        let count = Rc::new(Cell::new(0));
+        let mut compiler = Compiler::new();
+        compiler.add_pass(Box::new(Divisions {
+            count: count.clone(),
+            warnings: vec![],
+        }));
+        let source = "fn half(x i32) i32 = (x / 2) + (8 / 4)\n\
+            fn rest(x i32) i32 = x % 1\n\
+            fn main() i32 = half(10) + rest(3) + (7 % 1)\n";
+        let program = compiler.compile_str("app.au", source).unwrap();
+        // '8 / 4' is folded before the pass runs, '7 % 1' is folded to 0.
+        assert_eq!(count.get(), 1);
+        let warnings = program
+            .warnings
+            .iter()
+            .map(|warning| compiler.render_error(warning))
+            .collect::<String>();
+        assert_eq!(
+            warnings,
+            "app.au:2:22| the remainder of dividing by one is always zero [divisions]\n\
+            >\tfn rest(x i32) i32 = x % 1\n \t                     ^^^^^\n"
+        );
+    }
+
+    /// modules held in memory by name. A path of one name is looked up next to the importer
+    /// first, `text` imported by `util.format` is `util.text`.
+    struct Memory {
+        modules: Modules,
+        resolved: Rc<Cell<usize>>,
+    }
+
+    impl ModuleResolver for Memory {
+        fn resolve(&self, importer: &str, path: &str) -> Result<ModuleSource, ResolveError> {
+            self.resolved.set(self.resolved.get() + 1);
+            if path == "locked" {
+                return Err(ResolveError::Failed {
+                    reason: "the module store is locked".to_string(),
+                });
+            }
+            let sibling = match importer.rsplit_once('.') {
+                Some((package, _)) if !path.contains('.') => format!("{}.{}", package, path),
+                _ => path.to_string(),
+            };
+            [sibling.as_str(), path]
+                .iter()
+                .find_map(|name| self.modules.iter().find(|(module, _)| module == name))
+                .map(|(name, source)| ModuleSource::new(name, source))
+                .ok_or(ResolveError::NotFound)
+        }
+    }
+
+    type Modules = &'static [(&'static str, &'static str)];
+
+    fn memory_compiler(modules: Modules) -> (Compiler, Rc<Cell<usize>>) {
+        let resolved = Rc::new(Cell::new(0));
+        let mut compiler = Compiler::new();
+        compiler.set_resolver(Box::new(Memory {
+            modules,
+            resolved: resolved.clone(),
+        }));
+        (compiler, resolved)
+    }
+
+    fn run(compiler: &Compiler, file: &HirFile) -> Value {
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        let module = CodeGen::build(compiler.file_map(), file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        vm.run_module(module).unwrap();
+        *vm.top()
+    }
+
+    #[test]
+    fn test_resolver_serves_imported_modules() {
+        let (mut compiler, resolved) = memory_compiler(&[
+            ("geometry", "use util.math\npub fn area(w i64, h i64) i64 = product(w, h)\n"),
+            ("util.math", "pub fn product(a i64, b i64) i64 = a * b\n"),
+        ]);
+        let program = compiler
+            .compile_str("app.au", "use geometry\nfn main() i64 = area(6, 7)\n")
+            .unwrap();
+        assert_eq!(resolved.get(), 2);
+        assert!(program.warnings.is_empty());
+        assert!(program.file.find_entity_by_name("util::math::product").is_some());
+        assert!(matches!(run(&compiler, &program.file), Value::I64(42)));
+
+        // the files of resolved modules are named by the module.
+        let (mut compiler, _) = memory_compiler(&[("geometry", "pub fn area() i64 = depth\n")]);
+        let err = compiler
+            .compile_str("app.au", "use geometry\nfn main() i64 = area()\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            compiler.render_error(&err),
+            "geometry:1:21| use of undeclared identifier 'depth'\n\
+            >\tpub fn area() i64 = depth\n \t                    ^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_resolver_error_points_at_import() {
+        let render = |source: &str| {
+            let (mut compiler, _) =
+                memory_compiler(&[("util.format", "use locked\npub fn pad() i64 = 1\n")]);
+            let err = compiler.compile_str("app.au", source).err().unwrap();
+            compiler.render_error(&err)
+        };
+        assert_eq!(
+            render("fn helper() i64 = 1\nuse shapes.circle\nfn main() i64 = 1\n"),
+            "app.au:2:1| module 'shapes.circle' can not be resolved: no module is found by that \
+            name\n>\tuse shapes.circle\n \t^^^^^^^^^^^^^^^^^\n"
+        );
+        assert_eq!(
+            render("use util.format\nfn main() i64 = pad()\n"),
+            "util.format:1:1| module 'locked' can not be resolved: the module store is locked\n\
+            >\tuse locked\n \t^^^^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_resolver_loads_a_module_once() {
+        let (mut compiler, resolved) = memory_compiler(&[
+            ("util.format", "use text\npub fn pad(x i64) i64 = twice(x) + 1\n"),
+            ("util.text", "pub fn twice(x i64) i64 = x + x\n"),
+        ]);
+        let source = "use util.text\nuse util.format\nfn main() i64 = pad(twice(5))\n";
+        let program = compiler.compile_str("app.au", source).unwrap();
+        // `util.text` and `text` name the same module, it is resolved twice but loaded once.
+        assert_eq!(resolved.get(), 3);
+        assert!(program.warnings.is_empty());
+        assert!(matches!(run(&compiler, &program.file), Value::I64(21)));
+
+        // an import cycle is reported by the names of the modules, however they are spelled.
+        let (mut compiler, _) = memory_compiler(&[
+            ("util.format", "use text\npub fn pad() i64 = 1\n"),
+            ("util.text", "use util.format\npub fn twice() i64 = 2\n"),
+        ]);
+        let err = compiler
+            .compile_str("app.au", "use util.format\nfn main() i64 = pad()\n")
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "modules import each other: util.format -> util.text -> util.format"
+        );
+    }
+
+    #[test]
+    fn test_long_chain_of_operators() {
+        // the chain is not nested, every pass walks it in a loop within the stack of a main
+        // thread. Nesting is limited, the parser recurses on it and a debug build needs more
+        // stack than a main thread has to reach the default limit.
+        let compile = |source: String, stack_size: usize| {
+            std::thread::Builder::new()
+                .stack_size(stack_size)
+                .spawn(move || -> Result<i32, String> {
+                    let mut compiler = Compiler::new();
+                    let program = compiler
+                        .compile_str("app.au", &source)
+                        .map_err(|err| err.to_string())?;
+                    match run(&compiler, &program.file) {
+                        Value::I32(val) => Ok(val),
+                        value => panic!("unexpected value {}", value),
+                    }
+                })
+                .unwrap()
+                .join()
+                .unwrap()
+        };
+        let main = |body: String| format!("fn main() i32 {{\n    let x = 1\n    {}\n}}\n", body);
+        let chain = vec!["x"; 100_000].join(" + ");
+        assert_eq!(compile(main(chain), 8 * 1024 * 1024), Ok(100_000));
+        let constant = vec!["1"; 100_000].join(" + ");
+        let global = format!("let N = {}\nfn main() i32 = N\n", constant);
+        assert_eq!(compile(main(constant), 8 * 1024 * 1024), Ok(100_000));
+        assert_eq!(compile(global, 8 * 1024 * 1024), Ok(100_000));
+        let nested = format!("{}x{}", "(x + ".repeat(300), ")".repeat(300));
+        assert_eq!(
+            compile(main(nested), 64 * 1024 * 1024).err().unwrap(),
+            "expression too deeply nested, the limit is 256"
+        );
+    }
+}
@@ -1,6 +1,8 @@
-use std::fmt::{Display, Formatter};
+use std::fmt::{Display, Formatter, Write};
+use std::path::Path;
 
 use crate::syntax::{Operator, Position, Token};
+use crate::system::{FileId, ResolveError};
 use crate::types::Type;
 use crate::{
     ir::ast::{BinaryOp, UnaryOp},
@@ -21,6 +23,9 @@ pub enum ErrorKind {
     #[error("unknown escaped character: '{0}'")]
     UnknownEscapedCharacter(char),
 
+    #[error("invalid number literal: '{}'", literal)]
+    InvalidNumber { literal: String },
+
     #[error("unexpected end of file")]
     UnexpectedEOF,
 
@@ -45,6 +50,9 @@ pub enum ErrorKind {
     #[error("expecting an identifier, found '{0}'")]
     ExpectingIdentifier(String),
 
+    #[error("expecting a pattern, found '{0}'")]
+    ExpectedPattern(String),
+
     #[error("invalid token '{0}' in types expression")]
     InvalidTypeExpression(String),
 
@@ -75,12 +83,15 @@ pub enum ErrorKind {
     #[error("incompatible types, expected '{}' and found '{}'", left, right)]
     IncompatibleTypes { left: Type, right: Type },
 
-    #[error("must have initialization expression or type annotation")]
-    InvalidLocalItem,
+    #[error("cannot infer type, a binding needs a type annotation or an initial value")]
+    CannotInferType,
 
     #[error("type must be a struct, found '{}'", ty)]
     MustBeStruct { ty: Type },
 
+    #[error("type must be an enum, found '{}'", ty)]
+    MustBeEnum { ty: Type },
+
     #[error(
         "undefined name '{}' in name binding of struct expression of type '{}'",
         name,
@@ -104,12 +115,15 @@ pub enum ErrorKind {
     InvalidSelfExpression,
 
     #[error(
-        "unable to access field '{}' of {} '{}'",
+        "unable to access private {} '{}' of {} '{}'",
+        member,
         field,
         entity_type,
         struct_type
     )]
     InaccessibleSubEntity {
+        /// what the member is, a field or an associated function.
+        member: String,
         entity_type: String,
         struct_type: Type,
         field: String,
@@ -165,12 +179,67 @@ pub enum ErrorKind {
     #[error("array size must be an integer, found type '{}'", ty)]
     InvalidArraySizeType { ty: Type },
 
+    #[error(
+        "array size must be known while compiling, built from integer literals and immutable \
+        globals initialized by them"
+    )]
+    NonConstantArraySize,
+
+    #[error("expected an array of {} elements, found {}", expected, found)]
+    ArrayLengthMismatch { expected: usize, found: usize },
+
+    #[error("the element type of an empty array can not be inferred, declare its type")]
+    UntypedEmptyArray,
+
     #[error("expecting an instance, found type '{}'", ty)]
     ExpectingInstanceType { ty: Type },
 
     #[error("unable to index type '{}'", ty)]
     InvalidIndexType { ty: Type },
 
+    #[error("'{}' takes {} type arguments, found {}", name, expected, found)]
+    TypeArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("cannot infer type parameter '{}' of '{}' from the arguments", param, function)]
+    CannotInferTypeParam { function: String, param: String },
+
+    #[error(
+        "conflicting types for type parameter '{}', found '{}' and '{}'",
+        param,
+        first,
+        second
+    )]
+    ConflictingTypeArgument {
+        param: String,
+        first: Type,
+        second: Type,
+    },
+
+    #[error("generic function '{}' can only be called, it has no value", name)]
+    GenericFunctionValue { name: String },
+
+    #[error("type parameters are only supported on functions declared at module scope")]
+    GenericNotSupported,
+
+    #[error(
+        "map keys must be integers, bools, chars, strings or functions, found type '{}'",
+        ty
+    )]
+    InvalidMapKeyType { ty: Type },
+
+    #[error("cannot reserve a negative capacity of {}", count)]
+    NegativeReserve { count: i64 },
+
+    #[error("a weak reference can only refer to a heap object, found type '{}'", ty)]
+    WeakToNonHeapValue { ty: Type },
+
+    #[error("expected a value, found a type")]
+    TypeAsValue,
+
     #[error("invalid {} found in langauge mode '{}'", element, mode)]
     InvalidElementInMode { element: String, mode: LanguageMode },
 
@@ -190,19 +259,434 @@ pub enum ErrorKind {
         field_name: String,
     },
 
+    #[error("expression too deeply nested, the limit is {}", limit)]
+    ExprTooDeeplyNested { limit: usize },
+
+    #[error("unknown function 'log.{}', expected debug, info, warn or error", name)]
+    UnknownLogFunction { name: String },
+
+    #[error("unknown function 'task.{}', expected spawn", name)]
+    UnknownTaskFunction { name: String },
+
+    #[error(
+        "unknown function 'reflect.{}', expected fields, variants or get_field",
+        name
+    )]
+    UnknownReflectFunction { name: String },
+
+    #[error(
+        "unknown function 'sys.{}', expected language_version, module_name, opt_level, \
+         debug_info or host",
+        name
+    )]
+    UnknownSysFunction { name: String },
+
+    #[error("the message of 'todo' must be a string literal")]
+    TodoMessageNotLiteral,
+
+    #[error("'{}' depends on itself, its type is only known once it is resolved", name)]
+    CyclicReference { name: String },
+
+    #[error("only a function can be run as a task, found '{}'", ty)]
+    SpawnOfNonFunction { ty: Type },
+
+    #[error("the bounds and step of a range must be integers, found '{}'", ty)]
+    InvalidRangeBound { ty: Type },
+
+    #[error("the step of a range can not be zero")]
+    ZeroRangeStep,
+
+    #[error("a range can only be iterated by a for loop")]
+    RangeOutsideFor,
+
+    #[error("the value of an 'if' is used but it has no 'else' producing '{}'", ty)]
+    IfWithoutElse { ty: Type },
+
+    #[error("match on '{}' is not exhaustive, missing {}", ty, missing.join(", "))]
+    NonExhaustiveMatch { ty: Type, missing: Vec<String> },
+
+    #[error("pattern '{}' does not fit in '{}'", value, ty)]
+    PatternDoesNotFit { value: i128, ty: Type },
+
+    #[error("a field of a variant is matched by a name or '_'")]
+    InvalidFieldPattern,
+
+    #[error(
+        "a bound of a range pattern must be an integer or an immutable global known while \
+        compiling"
+    )]
+    NonConstantPatternBound,
+
+    #[error("range pattern '{}..{}' matches no value, its end is not after its start", start, end)]
+    EmptyRangePattern { start: i64, end: i64 },
+
+    #[error("this arm is never taken, the arms before it match every value it matches")]
+    UnreachableArm,
+
+    #[error("this statement is never run, the '{}' before it always leaves the block", exit)]
+    UnreachableStatement { exit: &'static str },
+
+    #[error("expression is not assignable")]
+    NotAssignable,
+
+    #[error("tuple '{}' has no element {}", ty, element)]
+    TupleElementOutOfRange { ty: Type, element: u64 },
+
+    #[error("'{}' is a field of '{}', not a method", field, ty)]
+    FieldCalledAsMethod { field: String, ty: Type },
+
+    #[error("'{}' is a method of '{}', not a field", method, ty)]
+    MethodReadAsField { method: String, ty: Type },
+
+    #[error(
+        "discriminant {} of variant '{}' is already used by variant '{}'",
+        value,
+        variant,
+        other
+    )]
+    DuplicateDiscriminant {
+        variant: String,
+        value: i64,
+        other: String,
+    },
+
+    #[error("variant '{}' carries data and can not have a discriminant", variant)]
+    DiscriminantOnDataVariant { variant: String },
+
+    #[error("discriminant of variant '{}' must be an integer literal", variant)]
+    InvalidDiscriminant { variant: String },
+
+    #[error("discriminant of variant '{}' overflows i64", variant)]
+    DiscriminantOverflow { variant: String },
+
+    #[error(
+        "discriminant {} of variant '{}' does not fit in '{}'",
+        value,
+        variant,
+        ty
+    )]
+    DiscriminantDoesNotFit {
+        variant: String,
+        value: i64,
+        ty: Type,
+    },
+
+    #[error(
+        "enum '{}' has variants with data and can not be converted to an integer",
+        ty
+    )]
+    DataEnumIntegerConversion { ty: Type },
+
+    #[error("invalid cast from '{}' to '{}'", from, to)]
+    InvalidCast { from: Type, to: Type },
+
+    #[error("attribute '{}' can only be applied to {}", name, target)]
+    MisplacedAttribute { name: String, target: String },
+
+    #[error("unknown attribute '{}'", name)]
+    UnknownAttribute { name: String },
+
+    #[error("attribute '{}' expects a condition, '@{}(<condition>)'", name, name)]
+    MissingAttributeArgument { name: String },
+
+    #[error("attribute '{}' takes no argument", name)]
+    UnexpectedAttributeArgument { name: String },
+
+    #[error(
+        "struct '{}' is declared '@finalize' without a method 'finalize(self)' returning unit",
+        name
+    )]
+    MissingFinalizer { name: String },
+
+    #[error("variant '{}' of a flags enum can not carry data", variant)]
+    FlagsVariantWithData { variant: String },
+
+    #[error(
+        "discriminant {} of flags variant '{}' is not a power of two",
+        value,
+        variant
+    )]
+    FlagsDiscriminantNotPowerOfTwo { variant: String, value: i64 },
+
+    #[error("'?.' applied to '{}' which is not optional, use '.' instead", ty)]
+    NonOptionalChain { ty: Type },
+
+    #[error("'??' applied to '{}' which is not optional", ty)]
+    NonOptionalCoalesce { ty: Type },
+
+    #[error("the type of 'none' can not be inferred here")]
+    UninferredNone,
+
+    #[error("the condition of '{}' must be 'bool', found '{}'", construct, ty)]
+    NonBoolCondition { construct: String, ty: Type },
+
+    #[error(
+        "operator '{}' can not be applied to 'bool', cast it with 'as' to use it as an integer",
+        op
+    )]
+    BoolArithmetic { op: BinaryOp },
+
+    #[error("'{}' can not be iterated by a for loop", ty)]
+    NotIterable { ty: Type },
+
+    #[error("'while let some(..)' applied to '{}' which is not optional", ty)]
+    NonOptionalWhileLet { ty: Type },
+
+    #[error(
+        "'{}' of '{}' has the type '{}', the iterator protocol requires '{}'",
+        method,
+        ty,
+        found,
+        expected
+    )]
+    InvalidIteratorMethod {
+        ty: Type,
+        method: String,
+        found: Type,
+        expected: String,
+    },
+
+    #[error("module '{}' is not part of the program", name)]
+    UnknownModule { name: String },
+
+    #[error("module '{}' can not be resolved: {}", name, reason)]
+    UnresolvedImport { name: String, reason: ResolveError },
+
+    #[error("module '{}' is defined by more than one file", name)]
+    DuplicateModule { name: String },
+
+    #[error("modules import each other: {}", cycle.join(" -> "))]
+    ImportCycle { cycle: Vec<String> },
+
+    #[error(
+        "constants of modules that import each other are initialized by each other: {}",
+        chain.join(" -> ")
+    )]
+    ConstantCycle { chain: Vec<String> },
+
+    #[error("'{}' is imported from both '{}' and '{}'", name, first, second)]
+    AmbiguousImport {
+        name: String,
+        first: String,
+        second: String,
+    },
+
+    #[error("'{}' is private to module '{}'", name, module)]
+    PrivateImport { name: String, module: String },
+
+    #[error("module '{}' has no item named '{}'", module, name)]
+    UnknownModuleItem { module: String, name: String },
+
+    #[error(
+        "entry function '{}' is defined by both '{}' and '{}'",
+        name,
+        first,
+        second
+    )]
+    DuplicateEntry {
+        name: String,
+        first: String,
+        second: String,
+    },
+
+    #[error("module '{}' is never imported, it is checked but not linked", name)]
+    UnusedModule { name: String },
+
+    #[error("public function '{}' must declare its return type", name)]
+    MissingReturnAnnotation { name: String },
+
+    #[error(
+        "parameter '{}' of public function '{}' must declare its type",
+        param,
+        function
+    )]
+    MissingParamAnnotation { function: String, param: String },
+
+    #[error("'{}' shadows a declaration of an enclosing scope", name)]
+    ShadowedName { name: String },
+
+    #[error("'{}' is only allowed at module scope", construct)]
+    OnlyAtModuleScope { construct: String },
+
+    #[error("nested {} are not yet supported — declare '{}' at module scope", kind, name)]
+    NestedItemNotSupported { kind: String, name: String },
+
+    #[error(
+        "local function '{}' can not use '{}', a local of the enclosing function",
+        function,
+        name
+    )]
+    CapturedLocal { function: String, name: String },
+
+    #[error(
+        "'{}' is mutable, a closure captures locals by value and can only capture immutable ones",
+        name
+    )]
+    CapturedMutable { name: String },
+
+    #[error("a closure can not capture 'self'")]
+    CapturedSelf,
+
+    #[error(
+        "this call provides a trailing block but '{}' expects its function as argument {} of {}",
+        name,
+        index,
+        count
+    )]
+    MisplacedTrailingBlock {
+        name: String,
+        index: usize,
+        count: usize,
+    },
+
+    #[error("this call provides a trailing block but '{}' takes no function", name)]
+    TrailingBlockWithoutFunction { name: String },
+
+    #[error(
+        "the trailing block of this call takes {} parameters but '{}' passes it {}",
+        found,
+        name,
+        expected
+    )]
+    TrailingBlockParams {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error(
+        "this function literal takes {} parameters but a function of {} is expected here",
+        found,
+        expected
+    )]
+    LambdaParams { expected: usize, found: usize },
+
+    #[error("cannot infer the type of parameter '{}', declare its type", name)]
+    UninferredParam { name: String },
+
+    #[error("the result of this call is unused")]
+    UnusedResult { ty: Type },
+
+    #[error("{construct} is not supported by the C backend")]
+    NotSupportedByCBackend { construct: String },
+
+    #[error("'{}' failed to compile and can not be used", name)]
+    UseOfFailedItem { name: String },
+
+    #[error(
+        "internal compiler error: '{}' is from generation {} but generation {} is being checked",
+        name,
+        generation,
+        current
+    )]
+    StaleEntity {
+        name: String,
+        generation: usize,
+        current: usize,
+    },
+
+    #[error("{} [{}]", message, pass)]
+    PassDiagnostic { pass: String, message: String },
+
+    #[error(
+        "function '{}' has {} {}, the limit is {} [{}]",
+        function,
+        value,
+        measure,
+        limit,
+        lint
+    )]
+    MetricExceeded {
+        function: String,
+        lint: String,
+        measure: String,
+        value: usize,
+        limit: usize,
+    },
+
+    #[error(
+        "'{}' is {}, '{}' rounds toward zero; '{}' rounds down to {}",
+        expression,
+        value,
+        op,
+        floored,
+        floored_value
+    )]
+    TruncatedDivision {
+        expression: String,
+        value: i64,
+        op: BinaryOp,
+        floored: String,
+        floored_value: i64,
+    },
+
+    #[error("'{}' divides by zero, its divisor is the constant 0", op)]
+    DivisionByZero { op: BinaryOp },
+
     #[error("Other: {0}")]
     Other(String),
 }
 
+impl ErrorKind {
+    /// the types the error mentions.
+    pub fn types(&self) -> Vec<&Type> {
+        match self {
+            ErrorKind::IncompatibleBinaryOpTypes { left, right, .. }
+            | ErrorKind::IncompatibleTypes { left, right } => vec![left, right],
+            ErrorKind::InvalidCast { from, to } => vec![from, to],
+            ErrorKind::InvalidIteratorMethod { ty, found, .. } => vec![ty, found],
+            ErrorKind::IncompatibleUnaryOpTypes { expr, .. } => vec![expr],
+            ErrorKind::InaccessibleSubEntity { struct_type, .. }
+            | ErrorKind::UnknownSubEntity { struct_type, .. }
+            | ErrorKind::UndeclaredStructEntity { struct_type, .. } => vec![struct_type],
+            ErrorKind::MustBeStruct { ty }
+            | ErrorKind::MustBeEnum { ty }
+            | ErrorKind::UndefinedFieldInStructBinding { ty, .. }
+            | ErrorKind::InvalidCallOnType { ty }
+            | ErrorKind::InvalidArraySizeType { ty }
+            | ErrorKind::ExpectingInstanceType { ty }
+            | ErrorKind::InvalidIndexType { ty }
+            | ErrorKind::InvalidMapKeyType { ty }
+            | ErrorKind::WeakToNonHeapValue { ty }
+            | ErrorKind::SpawnOfNonFunction { ty }
+            | ErrorKind::InvalidRangeBound { ty }
+            | ErrorKind::FieldCalledAsMethod { ty, .. }
+            | ErrorKind::MethodReadAsField { ty, .. }
+            | ErrorKind::DiscriminantDoesNotFit { ty, .. }
+            | ErrorKind::DataEnumIntegerConversion { ty }
+            | ErrorKind::NonOptionalChain { ty }
+            | ErrorKind::NonOptionalCoalesce { ty }
+            | ErrorKind::NonBoolCondition { ty, .. }
+            | ErrorKind::IfWithoutElse { ty }
+            | ErrorKind::NonExhaustiveMatch { ty, .. }
+            | ErrorKind::PatternDoesNotFit { ty, .. }
+            | ErrorKind::TupleElementOutOfRange { ty, .. }
+            | ErrorKind::NotIterable { ty }
+            | ErrorKind::NonOptionalWhileLet { ty }
+            | ErrorKind::UnusedResult { ty } => vec![ty],
+            _ => vec![],
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Error {
     kind: ErrorKind,
     position: Position,
+    /// extra context printed after the error.
+    notes: Vec<String>,
+    /// other places in the source the error refers to, shown after the notes.
+    labels: Vec<(Position, String)>,
 }
 
 impl Error {
     pub fn new(kind: ErrorKind, position: Position) -> Self {
-        Self { kind, position }
+        Self {
+            kind,
+            position,
+            notes: vec![],
+            labels: vec![],
+        }
     }
 
     pub fn kind(&self) -> &ErrorKind {
@@ -212,6 +696,29 @@ impl Error {
     pub fn pos(&self) -> &Position {
         &self.position
     }
+
+    pub fn notes(&self) -> &[String] {
+        self.notes.as_slice()
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn labels(&self) -> &[(Position, String)] {
+        self.labels.as_slice()
+    }
+
+    pub fn with_label(mut self, position: Position, label: String) -> Self {
+        self.labels.push((position, label));
+        self
+    }
+
+    /// an error about a value of the invalid type follows from an error already reported.
+    pub fn mentions_poisoned_type(&self) -> bool {
+        self.kind.types().iter().any(|ty| ty.kind().is_poisoned())
+    }
 }
 
 impl<'src> Error {
@@ -222,23 +729,23 @@ impl<'src> Error {
     // }
 
     fn new_default(kind: ErrorKind) -> Self {
-        Self {
-            position: Position::default(),
-            kind,
-        }
+        Self::new(kind, Position::default())
     }
 
     pub fn with_position(self, position: Position) -> Self {
-        Self {
-            position,
-            kind: self.kind,
-        }
+        Self { position, ..self }
     }
 
     pub fn unknown_escape_character(ch: char) -> Self {
         Self::new_default(ErrorKind::UnknownEscapedCharacter(ch))
     }
 
+    pub fn invalid_number(literal: &str) -> Self {
+        Self::new_default(ErrorKind::InvalidNumber {
+            literal: literal.to_owned(),
+        })
+    }
+
     pub fn unexpected_eof() -> Self {
         Self::new_default(ErrorKind::UnexpectedEOF)
     }
@@ -281,6 +788,10 @@ impl<'src> Error {
         Self::new_default(ErrorKind::ExpectingIdentifier(token.to_string()))
     }
 
+    pub fn expected_pattern(token: &Token) -> Self {
+        Self::new_default(ErrorKind::ExpectedPattern(token.to_string()))
+    }
+
     pub fn invalid_type_expression(token: &Token) -> Self {
         Self::new_default(ErrorKind::InvalidTypeExpression(format!("{}", token)))
     }
@@ -319,14 +830,18 @@ impl<'src> Error {
         })
     }
 
-    pub fn invalid_local_item() -> Self {
-        Self::new_default(ErrorKind::InvalidLocalItem)
+    pub fn cannot_infer_type() -> Self {
+        Self::new_default(ErrorKind::CannotInferType)
     }
 
     pub fn expected_struct_type(ty: &Type) -> Self {
         Self::new_default(ErrorKind::MustBeStruct { ty: ty.clone() })
     }
 
+    pub fn expected_enum_type(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::MustBeEnum { ty: ty.clone() })
+    }
+
     pub fn undeclared_field_in_struct_binding(name: &str, ty: &Type) -> Self {
         Self::new_default(ErrorKind::UndefinedFieldInStructBinding {
             name: name.to_owned(),
@@ -362,8 +877,14 @@ impl<'src> Error {
         })
     }
 
-    pub fn inaccessible_subentity(entity_type: &str, struct_type: &Type, field: String) -> Self {
+    pub fn inaccessible_subentity(
+        member: &str,
+        entity_type: &str,
+        struct_type: &Type,
+        field: String,
+    ) -> Self {
         Self::new_default(ErrorKind::InaccessibleSubEntity {
+            member: member.to_owned(),
             entity_type: entity_type.to_owned(),
             struct_type: struct_type.clone(),
             field,
@@ -422,6 +943,18 @@ impl<'src> Error {
         Self::new_default(ErrorKind::InvalidArraySizeType { ty: ty.clone() })
     }
 
+    pub fn non_constant_array_size() -> Self {
+        Self::new_default(ErrorKind::NonConstantArraySize)
+    }
+
+    pub fn array_length_mismatch(expected: usize, found: usize) -> Self {
+        Self::new_default(ErrorKind::ArrayLengthMismatch { expected, found })
+    }
+
+    pub fn untyped_empty_array() -> Self {
+        Self::new_default(ErrorKind::UntypedEmptyArray)
+    }
+
     pub fn expecting_instance_type(ty: &Type) -> Self {
         Self::new_default(ErrorKind::ExpectingInstanceType { ty: ty.clone() })
     }
@@ -430,6 +963,55 @@ impl<'src> Error {
         Self::new_default(ErrorKind::InvalidIndexType { ty: ty.clone() })
     }
 
+    pub fn type_argument_count(name: &str, expected: usize, found: usize) -> Self {
+        Self::new_default(ErrorKind::TypeArgumentCount {
+            name: name.to_owned(),
+            expected,
+            found,
+        })
+    }
+
+    pub fn cannot_infer_type_param(function: &str, param: &str) -> Self {
+        Self::new_default(ErrorKind::CannotInferTypeParam {
+            function: function.to_owned(),
+            param: param.to_owned(),
+        })
+    }
+
+    pub fn conflicting_type_argument(param: &str, first: &Type, second: &Type) -> Self {
+        Self::new_default(ErrorKind::ConflictingTypeArgument {
+            param: param.to_owned(),
+            first: first.clone(),
+            second: second.clone(),
+        })
+    }
+
+    pub fn generic_function_value(name: &str) -> Self {
+        Self::new_default(ErrorKind::GenericFunctionValue {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn generic_not_supported() -> Self {
+        Self::new_default(ErrorKind::GenericNotSupported)
+    }
+
+    pub fn invalid_map_key_type(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::InvalidMapKeyType { ty: ty.clone() })
+    }
+
+    pub fn negative_reserve(count: i64) -> Self {
+        Self::new_default(ErrorKind::NegativeReserve { count })
+    }
+
+    pub fn weak_to_non_heap_value(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::WeakToNonHeapValue { ty: ty.clone() })
+    }
+
+    pub fn type_as_value() -> Self {
+        Self::new_default(ErrorKind::TypeAsValue)
+    }
+
     pub fn invalid_assignment_in_mode(mode: LanguageMode) -> Self {
         Self::new_default(ErrorKind::InvalidElementInMode {
             element: "assignment".to_string(),
@@ -467,7 +1049,480 @@ impl<'src> Error {
         Self::new_default(ErrorKind::MissingFieldInStructInit { field_name })
     }
 
+    pub fn expr_too_deeply_nested(limit: usize) -> Self {
+        Self::new_default(ErrorKind::ExprTooDeeplyNested { limit })
+    }
+
+    pub fn unknown_log_function(name: &str) -> Self {
+        Self::new_default(ErrorKind::UnknownLogFunction {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn unknown_task_function(name: &str) -> Self {
+        Self::new_default(ErrorKind::UnknownTaskFunction {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn unknown_reflect_function(name: &str) -> Self {
+        Self::new_default(ErrorKind::UnknownReflectFunction {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn unknown_sys_function(name: &str) -> Self {
+        Self::new_default(ErrorKind::UnknownSysFunction {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn todo_message_not_literal() -> Self {
+        Self::new_default(ErrorKind::TodoMessageNotLiteral)
+    }
+
+    pub fn cyclic_reference(name: &str) -> Self {
+        Self::new_default(ErrorKind::CyclicReference {
+            name: name.to_owned(),
+        })
+        .with_note("annotate the return type of the functions it uses".to_owned())
+    }
+
+    pub fn spawn_of_non_function(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::SpawnOfNonFunction { ty: ty.clone() })
+    }
+
+    pub fn invalid_range_bound(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::InvalidRangeBound { ty: ty.clone() })
+    }
+
+    pub fn zero_range_step() -> Self {
+        Self::new_default(ErrorKind::ZeroRangeStep)
+    }
+
+    pub fn range_outside_for() -> Self {
+        Self::new_default(ErrorKind::RangeOutsideFor)
+    }
+
+    pub fn if_without_else(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::IfWithoutElse { ty: ty.clone() })
+    }
+
+    pub fn non_exhaustive_match(ty: &Type, missing: Vec<String>) -> Self {
+        Self::new_default(ErrorKind::NonExhaustiveMatch {
+            ty: ty.clone(),
+            missing,
+        })
+    }
+
+    pub fn pattern_does_not_fit(value: i128, ty: &Type) -> Self {
+        Self::new_default(ErrorKind::PatternDoesNotFit {
+            value,
+            ty: ty.clone(),
+        })
+    }
+
+    pub fn invalid_field_pattern() -> Self {
+        Self::new_default(ErrorKind::InvalidFieldPattern)
+    }
+
+    pub fn non_constant_pattern_bound() -> Self {
+        Self::new_default(ErrorKind::NonConstantPatternBound)
+    }
+
+    pub fn empty_range_pattern(start: i64, end: i64) -> Self {
+        Self::new_default(ErrorKind::EmptyRangePattern { start, end })
+    }
+
+    pub fn unreachable_arm() -> Self {
+        Self::new_default(ErrorKind::UnreachableArm)
+    }
+
+    pub fn unreachable_statement(exit: &'static str) -> Self {
+        Self::new_default(ErrorKind::UnreachableStatement { exit })
+    }
+
+    pub fn not_assignable() -> Self {
+        Self::new_default(ErrorKind::NotAssignable)
+    }
+
+    pub fn tuple_element_out_of_range(ty: &Type, element: u64) -> Self {
+        Self::new_default(ErrorKind::TupleElementOutOfRange {
+            ty: ty.clone(),
+            element,
+        })
+    }
+
+    pub fn field_called_as_method(field: &str, ty: &Type) -> Self {
+        Self::new_default(ErrorKind::FieldCalledAsMethod {
+            field: field.to_owned(),
+            ty: ty.clone(),
+        })
+        .with_note("remove the parentheses to read it".to_owned())
+    }
+
+    pub fn method_read_as_field(method: &str, ty: &Type) -> Self {
+        Self::new_default(ErrorKind::MethodReadAsField {
+            method: method.to_owned(),
+            ty: ty.clone(),
+        })
+        .with_note("add '()' to call it, a method can not be taken as a value".to_owned())
+    }
+
+    pub fn duplicate_discriminant(variant: &str, value: i64, other: &str) -> Self {
+        Self::new_default(ErrorKind::DuplicateDiscriminant {
+            variant: variant.to_owned(),
+            value,
+            other: other.to_owned(),
+        })
+    }
+
+    pub fn discriminant_on_data_variant(variant: &str) -> Self {
+        Self::new_default(ErrorKind::DiscriminantOnDataVariant {
+            variant: variant.to_owned(),
+        })
+    }
+
+    pub fn invalid_discriminant(variant: &str) -> Self {
+        Self::new_default(ErrorKind::InvalidDiscriminant {
+            variant: variant.to_owned(),
+        })
+    }
+
+    pub fn discriminant_overflow(variant: &str) -> Self {
+        Self::new_default(ErrorKind::DiscriminantOverflow {
+            variant: variant.to_owned(),
+        })
+    }
+
+    pub fn discriminant_does_not_fit(variant: &str, value: i64, ty: &Type) -> Self {
+        Self::new_default(ErrorKind::DiscriminantDoesNotFit {
+            variant: variant.to_owned(),
+            value,
+            ty: ty.clone(),
+        })
+    }
+
+    pub fn data_enum_integer_conversion(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::DataEnumIntegerConversion { ty: ty.clone() })
+    }
+
+    pub fn invalid_cast(from: &Type, to: &Type) -> Self {
+        Self::new_default(ErrorKind::InvalidCast {
+            from: from.clone(),
+            to: to.clone(),
+        })
+    }
+
+    pub fn misplaced_attribute(name: &str, target: &str) -> Self {
+        Self::new_default(ErrorKind::MisplacedAttribute {
+            name: name.to_owned(),
+            target: target.to_owned(),
+        })
+    }
+
+    pub fn unknown_attribute(name: &str) -> Self {
+        Self::new_default(ErrorKind::UnknownAttribute {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn missing_attribute_argument(name: &str) -> Self {
+        Self::new_default(ErrorKind::MissingAttributeArgument {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn unexpected_attribute_argument(name: &str) -> Self {
+        Self::new_default(ErrorKind::UnexpectedAttributeArgument {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn missing_finalizer(name: &str) -> Self {
+        Self::new_default(ErrorKind::MissingFinalizer {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn flags_variant_with_data(variant: &str) -> Self {
+        Self::new_default(ErrorKind::FlagsVariantWithData {
+            variant: variant.to_owned(),
+        })
+    }
+
+    pub fn flags_discriminant_not_power_of_two(variant: &str, value: i64) -> Self {
+        Self::new_default(ErrorKind::FlagsDiscriminantNotPowerOfTwo {
+            variant: variant.to_owned(),
+            value,
+        })
+    }
+
+    pub fn non_optional_chain(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::NonOptionalChain { ty: ty.clone() })
+    }
+
+    pub fn non_optional_coalesce(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::NonOptionalCoalesce { ty: ty.clone() })
+    }
+
+    pub fn uninferred_none() -> Self {
+        Self::new_default(ErrorKind::UninferredNone)
+    }
+
+    /// an integer condition suggests comparing it against zero.
+    pub fn non_bool_condition(construct: &str, ty: &Type) -> Self {
+        let err = Self::new_default(ErrorKind::NonBoolCondition {
+            construct: construct.to_owned(),
+            ty: ty.clone(),
+        });
+        if ty.is_integer() {
+            err.with_note("compare the integer against zero, '<condition> != 0'".to_string())
+        } else {
+            err
+        }
+    }
+
+    pub fn bool_arithmetic(op: BinaryOp) -> Self {
+        Self::new_default(ErrorKind::BoolArithmetic { op })
+    }
+
+    pub fn non_optional_while_let(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::NonOptionalWhileLet { ty: ty.clone() })
+    }
+
+    pub fn not_iterable(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::NotIterable { ty: ty.clone() })
+            .with_note("an iterator has the method 'next(mut self) Option[T]'".to_string())
+            .with_note("an iterable has the method 'iter(self)' returning an iterator".to_string())
+    }
+
+    pub fn invalid_iterator_method(ty: &Type, method: &str, found: &Type, expected: &str) -> Self {
+        Self::new_default(ErrorKind::InvalidIteratorMethod {
+            ty: ty.clone(),
+            method: method.to_owned(),
+            found: found.clone(),
+            expected: expected.to_owned(),
+        })
+    }
+
+    pub fn unknown_module(name: &str) -> Self {
+        Self::new_default(ErrorKind::UnknownModule {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn unresolved_import(name: &str, reason: ResolveError) -> Self {
+        Self::new_default(ErrorKind::UnresolvedImport {
+            name: name.to_owned(),
+            reason,
+        })
+    }
+
+    pub fn duplicate_module(name: &str, first: &str) -> Self {
+        Self::new_default(ErrorKind::DuplicateModule {
+            name: name.to_owned(),
+        })
+        .with_note(format!("it is also defined by '{}'", first))
+    }
+
+    pub fn import_cycle(cycle: Vec<String>) -> Self {
+        Self::new_default(ErrorKind::ImportCycle { cycle })
+    }
+
+    pub fn constant_cycle(chain: Vec<String>) -> Self {
+        Self::new_default(ErrorKind::ConstantCycle { chain })
+    }
+
+    pub fn ambiguous_import(name: &str, first: &str, second: &str) -> Self {
+        Self::new_default(ErrorKind::AmbiguousImport {
+            name: name.to_owned(),
+            first: first.to_owned(),
+            second: second.to_owned(),
+        })
+    }
+
+    pub fn private_import(name: &str, module: &str) -> Self {
+        Self::new_default(ErrorKind::PrivateImport {
+            name: name.to_owned(),
+            module: module.to_owned(),
+        })
+        .with_note("declare it 'pub' to use it from another module".to_string())
+    }
+
+    pub fn unknown_module_item(module: &str, name: &str) -> Self {
+        Self::new_default(ErrorKind::UnknownModuleItem {
+            module: module.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn duplicate_entry(name: &str, first: &str, second: &str) -> Self {
+        Self::new_default(ErrorKind::DuplicateEntry {
+            name: name.to_owned(),
+            first: first.to_owned(),
+            second: second.to_owned(),
+        })
+    }
+
+    pub fn unused_module(name: &str) -> Self {
+        Self::new_default(ErrorKind::UnusedModule {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn missing_return_annotation(name: &str) -> Self {
+        Self::new_default(ErrorKind::MissingReturnAnnotation {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn missing_param_annotation(function: &str, param: &str) -> Self {
+        Self::new_default(ErrorKind::MissingParamAnnotation {
+            function: function.to_owned(),
+            param: param.to_owned(),
+        })
+    }
+
+    pub fn shadowed_name(name: &str) -> Self {
+        Self::new_default(ErrorKind::ShadowedName {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn only_at_module_scope(construct: &str) -> Self {
+        Self::new_default(ErrorKind::OnlyAtModuleScope {
+            construct: construct.to_owned(),
+        })
+    }
+
+    pub fn nested_item_not_supported(kind: &str, name: &str) -> Self {
+        Self::new_default(ErrorKind::NestedItemNotSupported {
+            kind: kind.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn captured_local(function: &str, name: &str) -> Self {
+        Self::new_default(ErrorKind::CapturedLocal {
+            function: function.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn captured_mutable(name: &str) -> Self {
+        Self::new_default(ErrorKind::CapturedMutable {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn captured_self() -> Self {
+        Self::new_default(ErrorKind::CapturedSelf)
+    }
+
+    /// index counts from 1, like the arguments of the call are counted by its reader.
+    pub fn misplaced_trailing_block(name: &str, index: usize, count: usize) -> Self {
+        Self::new_default(ErrorKind::MisplacedTrailingBlock {
+            name: name.to_owned(),
+            index,
+            count,
+        })
+    }
+
+    pub fn trailing_block_without_function(name: &str) -> Self {
+        Self::new_default(ErrorKind::TrailingBlockWithoutFunction {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn trailing_block_params(name: &str, expected: usize, found: usize) -> Self {
+        Self::new_default(ErrorKind::TrailingBlockParams {
+            name: name.to_owned(),
+            expected,
+            found,
+        })
+    }
+
+    pub fn lambda_params(expected: usize, found: usize) -> Self {
+        Self::new_default(ErrorKind::LambdaParams { expected, found })
+    }
+
+    pub fn uninferred_param(name: &str) -> Self {
+        Self::new_default(ErrorKind::UninferredParam {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn unused_result(ty: &Type) -> Self {
+        Self::new_default(ErrorKind::UnusedResult { ty: ty.clone() })
+            .with_note(format!("it is '{}', discard it with '_ = <call>'", ty))
+    }
+
+    pub fn not_supported_by_c_backend(construct: &str) -> Self {
+        Self::new_default(ErrorKind::NotSupportedByCBackend {
+            construct: construct.to_owned(),
+        })
+    }
+
+    pub fn use_of_failed_item(name: &str) -> Self {
+        Self::new_default(ErrorKind::UseOfFailedItem {
+            name: name.to_owned(),
+        })
+    }
+
+    pub fn stale_entity(name: &str, generation: usize, current: usize) -> Self {
+        Self::new_default(ErrorKind::StaleEntity {
+            name: name.to_owned(),
+            generation,
+            current,
+        })
+    }
+
+    pub fn pass_diagnostic(pass: &str, message: String) -> Self {
+        Self::new_default(ErrorKind::PassDiagnostic {
+            pass: pass.to_owned(),
+            message,
+        })
+    }
+
+    pub fn metric_exceeded(
+        function: &str,
+        lint: &str,
+        measure: &str,
+        value: usize,
+        limit: usize,
+    ) -> Self {
+        Self::new_default(ErrorKind::MetricExceeded {
+            function: function.to_owned(),
+            lint: lint.to_owned(),
+            measure: measure.to_owned(),
+            value,
+            limit,
+        })
+    }
+
+    pub fn truncated_division(
+        expression: String,
+        value: i64,
+        op: BinaryOp,
+        floored: String,
+        floored_value: i64,
+    ) -> Self {
+        Self::new_default(ErrorKind::TruncatedDivision {
+            expression,
+            value,
+            op,
+            floored,
+            floored_value,
+        })
+    }
+
+    pub fn division_by_zero(op: BinaryOp) -> Self {
+        Self::new_default(ErrorKind::DivisionByZero { op })
+    }
+
     pub fn other(err: String) -> Self {
+
         Self::new_default(ErrorKind::Other(err))
     }
 }
@@ -477,3 +1532,141 @@ impl Display for Error {
         write!(f, "{}", self.kind)
     }
 }
+
+/// the style of the message of an error and the underline of its span.
+const ERROR_STYLE: &str = "1;31";
+/// the style of the notes of an error and the underline of the places its labels point at.
+const NOTE_STYLE: &str = "1;36";
+
+/// the text of a file an error is shown against.
+#[derive(Debug, Clone, Copy)]
+pub struct SourceText<'a> {
+    pub path: &'a Path,
+    pub text: &'a str,
+}
+
+/// shows an error with the line of source it points at and its span underlined, then each of
+/// its labels the same way. A span of more than one line shows its first and last lines. The
+/// output is plain text unless color is asked for, so it can be compared in tests.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// whether the message and underlines are colored with ANSI escapes.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// err shown against source, the text of its file. A label pointing into another file is
+    /// shown without its line.
+    pub fn render_source(&self, err: &Error, source: SourceText) -> String {
+        let file_id = err.pos().file_id();
+        self.render(err, |id| (id == file_id).then_some(source))
+    }
+
+    /// err shown against the files given by source_of, the file of the error and those its
+    /// labels point into.
+    pub fn render<'a, F>(&self, err: &Error, source_of: F) -> String
+    where
+        F: Fn(FileId) -> Option<SourceText<'a>>,
+    {
+        let pos = err.pos();
+        let source = match source_of(pos.file_id()) {
+            Some(source) => source,
+            None => return format!("{}\nunable to find file of id: '{}'\n", err, pos.file_id().0),
+        };
+
+        let message = self.paint(ERROR_STYLE, &err.to_string());
+        let mut out = format!("{}| {}\n", Self::location(source, pos), message);
+        for note in err.notes() {
+            writeln!(out, "{}: {}", self.paint(NOTE_STYLE, "note"), note).unwrap();
+        }
+        self.excerpt(&mut out, source, pos, ERROR_STYLE);
+
+        for (pos, label) in err.labels() {
+            let note = self.paint(NOTE_STYLE, "note");
+            match source_of(pos.file_id()) {
+                Some(source) => {
+                    writeln!(out, "{}| {}: {}", Self::location(source, pos), note, label).unwrap();
+                    self.excerpt(&mut out, source, pos, NOTE_STYLE);
+                }
+                None => writeln!(out, "{}: {}", note, label).unwrap(),
+            }
+        }
+        out
+    }
+
+    fn location(source: SourceText, pos: &Position) -> String {
+        let start = pos.start();
+        format!(
+            "{}:{}:{}",
+            source.path.display(),
+            start.line(),
+            start.column()
+        )
+    }
+
+    /// the lines of pos with its span underlined. Of a span of more than one line the first is
+    /// underlined to its end and the last up to where the span ends, the lines between are
+    /// counted.
+    fn excerpt(&self, out: &mut String, source: SourceText, pos: &Position, style: &str) {
+        let (start, end) = (pos.start(), pos.end());
+        // an error of a whole file has no line to show.
+        if start.line() == 0 || start.column() == 0 {
+            return;
+        }
+        let line = |number: usize| {
+            let line = source.text.lines().nth(number - 1).unwrap_or("");
+            line.strip_suffix('\r').unwrap_or(line)
+        };
+
+        let first = line(start.line());
+        if end.line() <= start.line() {
+            let width = end.column().saturating_sub(start.column()).max(1);
+            self.underline(out, first, start.column(), width, style);
+            return;
+        }
+        let width = first.chars().count().saturating_sub(start.column() - 1).max(1);
+        self.underline(out, first, start.column(), width, style);
+        match end.line() - start.line() - 1 {
+            0 => {}
+            1 => writeln!(out, " \t{}", self.paint(style, "... 1 more line")).unwrap(),
+            count => {
+                let more = format!("... {} more lines", count);
+                writeln!(out, " \t{}", self.paint(style, &more)).unwrap();
+            }
+        }
+        let last = line(end.line());
+        let indent = last.chars().take_while(|ch| ch.is_whitespace()).count();
+        let width = (end.column().saturating_sub(1)).saturating_sub(indent).max(1);
+        self.underline(out, last, indent + 1, width, style);
+    }
+
+    /// line followed by width carets starting at column, a tab of the line is kept so the
+    /// carets line up with it.
+    fn underline(&self, out: &mut String, line: &str, column: usize, width: usize, style: &str) {
+        writeln!(out, ">\t{}", line).unwrap();
+        let offset = line
+            .chars()
+            .chain(std::iter::repeat(' '))
+            .take(column - 1)
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        writeln!(out, " \t{}{}", offset, self.paint(style, &"^".repeat(width))).unwrap();
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_owned()
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use oxide::gc::Gc;
+use oxide::vm::{EvalBinding, EvalCompiler};
+use oxide::{OxModule, Value, Vm};
+
+use crate::code_gen::CodeGen;
+use crate::compiler::Compiler;
+
+/// the file an evaluated script is compiled as.
+const SCRIPT_FILE: &str = "eval.au";
+
+/// the types a binding is declared with when its value has one, any other value is `any`.
+const BINDING_TYPES: [&str; 13] = [
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64", "bool", "string", "char",
+];
+
+/// compiles the source scripts give to `eval` in the vm running them, installed with
+/// `Vm::set_eval_compiler`. The source is the body of a main function taking the values the
+/// host exposed as its parameters, so it reads them but can not assign them. Nothing else of
+/// the running program is in scope, each source is compiled as a program of its own.
+#[derive(Debug, Default)]
+pub struct ScriptCompiler;
+
+impl ScriptCompiler {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// the program compiled for source, its last expression is the value main returns.
+    fn program(source: &str, bindings: &[EvalBinding]) -> String {
+        let params = bindings
+            .iter()
+            .map(|binding| format!("{} {}", binding.name, Self::binding_type(&binding.value)))
+            .collect::<Vec<_>>();
+        format!("fn main({}) any {{\n{}\n}}\n", params.join(", "), source)
+    }
+
+    fn binding_type(value: &Value) -> &'static str {
+        BINDING_TYPES
+            .iter()
+            .copied()
+            .find(|ty| *ty == value.ty())
+            .unwrap_or("any")
+    }
+}
+
+impl EvalCompiler for ScriptCompiler {
+    fn compile(
+        &mut self,
+        vm: &mut Vm,
+        source: &str,
+        bindings: &[EvalBinding],
+    ) -> Result<Gc<OxModule>, String> {
+        let mut compiler = Compiler::new();
+        let program = compiler
+            .compile_str(SCRIPT_FILE, Self::program(source, bindings).as_str())
+            .map_err(|err| err.to_string())?;
+        vm.set_code_gen(true);
+        let module = CodeGen::build(compiler.file_map(), &program.file, vm);
+        vm.set_code_gen(false);
+        module.map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use oxide::vm::Capability;
+    use oxide::{Error, Value, Vm};
+
+    use super::ScriptCompiler;
+    use crate::code_gen::CodeGen;
+    use crate::compiler::Compiler;
+
+    /// a vm evaluating scripts with base and name exposed to them.
+    fn plugin_vm() -> Vm {
+        let mut vm = Vm::new();
+        vm.set_eval_compiler(Box::new(ScriptCompiler::new()));
+        vm.expose_to_eval("base", Value::I64(40));
+        let name = Value::from(vm.new_gc_string_from_str("plugin"));
+        vm.expose_to_eval("name", name);
+        vm
+    }
+
+    fn run(vm: &mut Vm, source: &str) -> Result<Value, Error> {
+        let mut compiler = Compiler::new();
+        let program = compiler.compile_str("main.au", source).unwrap();
+        vm.set_code_gen(true);
+        let module = CodeGen::build(compiler.file_map(), &program.file, vm).unwrap();
+        vm.set_code_gen(false);
+        vm.run_module(module).map(|_| *vm.top())
+    }
+
+    /// the error of evaluating source, the script must fail.
+    fn eval_error(vm: &mut Vm, source: &str) -> String {
+        let program = format!("fn main() any = eval({:?}).err()\n", source);
+        let value = run(vm, program.as_str()).unwrap();
+        value.as_string().as_str().to_owned()
+    }
+
+    #[test]
+    fn test_eval_computes_over_exposed_values() {
+        let mut vm = plugin_vm();
+        vm.grant(Capability::Eval);
+        let source = "fn main() any = eval(\"if base > 10 { base * 2 + 2 } else { base }\").ok()\n";
+        assert!(matches!(run(&mut vm, source), Ok(Value::I64(82))));
+        let value = run(&mut vm, "fn main() any = eval(\"name\").ok()\n").unwrap();
+        assert_eq!(value.as_string().as_str(), "plugin");
+
+        // the exposed values are read, they can not be assigned.
+        assert_eq!(
+            eval_error(&mut vm, "base = 1\nbase"),
+            "attempting to mutable 'base' which is not mutable"
+        );
+    }
+
+    #[test]
+    fn test_eval_compile_error() {
+        let mut vm = plugin_vm();
+        vm.grant(Capability::Eval);
+        assert_eq!(
+            eval_error(&mut vm, "base + true"),
+            "operator '+' can not be applied to 'bool', cast it with 'as' to use it as an integer"
+        );
+        // only the exposed values are in scope.
+        assert_eq!(
+            eval_error(&mut vm, "missing * 2"),
+            "use of undeclared identifier 'missing'"
+        );
+    }
+
+    #[test]
+    fn test_eval_runs_out_of_fuel() {
+        let mut vm = plugin_vm();
+        vm.grant(Capability::Eval);
+        vm.set_eval_fuel(1000);
+        let source = "mut i i64 = 0\nwhile i < 1000000 {\n    i += 1\n}\ni";
+        assert_eq!(
+            eval_error(&mut vm, source),
+            "the evaluated script ran out of fuel after 1000 instructions"
+        );
+    }
+
+    #[test]
+    fn test_eval_requires_the_capability() {
+        let mut vm = plugin_vm();
+        let err = run(&mut vm, "fn main() any = eval(\"base\").ok()\n").unwrap_err();
+        assert!(matches!(err, Error::CapabilityNotGranted(_)));
+        assert_eq!(err.to_string(), "capability not granted: eval");
+    }
+}
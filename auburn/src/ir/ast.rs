@@ -2,6 +2,7 @@ use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use itertools::Itertools;
 use ordered_float::OrderedFloat;
 
 use crate::error::Error;
@@ -16,6 +17,11 @@ macro_rules! define_op {
             )*
         }
 
+        impl $ty {
+            /// every operator, in the order they are declared.
+            pub const ALL: &'static [Self] = &[$(Self::$en,)*];
+        }
+
         impl Display for $ty {
             fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -39,11 +45,14 @@ define_op!(
     ">=" => GreaterEq,
     "&" => Ampersand,
     "|" => Pipe,
+    "^" => Caret,
     "%" => Percent,
     "==" => EqualEqual,
     "!=" => BangEqual,
     "<<" => LessLess,
     ">>" => GreaterGreater,
+    "div_floor" => DivFloor,
+    "mod_floor" => ModFloor,
     BinaryOp
 );
 
@@ -55,6 +64,7 @@ define_op!(
     "/="  => SlashAssign,
     "&="  => AmpersandAssign,
     "|="  => PipeAssign,
+    "^="  => CaretAssign,
     "%="  => PercentAssign,
     "<<=" => LessLessAssign,
     ">>=" => GreaterGreaterAssign,
@@ -73,47 +83,31 @@ impl BinaryOp {
             _ => false,
         }
     }
+
+    /// the divisions of integers called as methods, `a.div_floor(b)`. They round toward negative
+    /// infinity where `/` and `%` truncate toward zero.
+    pub fn integer_method(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|op| op.declaration().token.is_none() && op.to_string() == name)
+    }
 }
 
 impl TryFrom<Operator> for BinaryOp {
     type Error = Error;
 
     fn try_from(value: Operator) -> Result<Self, Self::Error> {
-        match value {
-            Operator::Plus => Ok(Self::Plus),
-            Operator::Minus => Ok(Self::Minus),
-            Operator::Astrick => Ok(Self::Astrick),
-            Operator::Slash => Ok(Self::Slash),
-            Operator::Less => Ok(Self::Less),
-            Operator::Greater => Ok(Self::Greater),
-            Operator::LessEq => Ok(Self::LessEq),
-            Operator::GreaterEq => Ok(Self::GreaterEq),
-            Operator::Ampersand => Ok(Self::Ampersand),
-            Operator::Pipe => Ok(Self::Pipe),
-            Operator::Percent => Ok(Self::Percent),
-            Operator::LessLess => Ok(Self::LessLess),
-            Operator::GreaterGreater => Ok(Self::GreaterGreater),
-            Operator::EqualEqual => Ok(Self::EqualEqual),
-            Operator::BangEqual => Ok(Self::BangEqual),
-            _ => Err(Error::invalid_binary_operator(value)),
-        }
+        Self::from_token(value).ok_or_else(|| Error::invalid_binary_operator(value))
     }
 }
 
 impl AssignmentOp {
+    /// the operator of a compound assignment, none for `=`.
     pub fn get_binary_op(&self) -> Option<BinaryOp> {
-        match self {
-            Self::Assign => None,
-            Self::PlusAssign => Some(BinaryOp::Plus),
-            Self::MinusAssign => Some(BinaryOp::Minus),
-            Self::AstriskAssign => Some(BinaryOp::Astrick),
-            Self::SlashAssign => Some(BinaryOp::Slash),
-            Self::AmpersandAssign => Some(BinaryOp::Ampersand),
-            Self::PipeAssign => Some(BinaryOp::Pipe),
-            Self::PercentAssign => Some(BinaryOp::Percent),
-            Self::LessLessAssign => Some(BinaryOp::LessLess),
-            Self::GreaterGreaterAssign => Some(BinaryOp::GreaterGreater),
-        }
+        BinaryOp::ALL.iter().copied().find(|op| {
+            matches!(op.declaration().assignment, Some((_, assignment)) if assignment == *self)
+        })
     }
 }
 
@@ -121,19 +115,7 @@ impl TryFrom<Operator> for AssignmentOp {
     type Error = Error;
 
     fn try_from(value: Operator) -> Result<Self, Self::Error> {
-        match value {
-            Operator::Equal => Ok(Self::Assign),
-            Operator::PlusEq => Ok(Self::PlusAssign),
-            Operator::MinusEq => Ok(Self::MinusAssign),
-            Operator::AstriskEq => Ok(Self::AstriskAssign),
-            Operator::SlashEq => Ok(Self::SlashAssign),
-            Operator::AmpersandEq => Ok(Self::AmpersandAssign),
-            Operator::PipeEq => Ok(Self::PipeAssign),
-            Operator::PercentEq => Ok(Self::PercentAssign),
-            Operator::LessLessEq => Ok(Self::LessLessAssign),
-            Operator::GreaterGreaterEq => Ok(Self::GreaterGreaterAssign),
-            _ => Err(Error::invalid_assignment_operator(value)),
-        }
+        Self::from_token(value).ok_or_else(|| Error::invalid_assignment_operator(value))
     }
 }
 
@@ -180,6 +162,39 @@ pub enum StructExprField {
     Field(Box<Expr>),
 }
 
+/// what an arm of a match compares the value with.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PatternKind {
+    /// `_`, every value.
+    Wildcard,
+    /// `name`, every value, bound to name in the arm.
+    Binding(Identifier),
+    /// an integer, a negated integer or a bool.
+    Literal(Box<Expr>),
+    /// `Enum.Variant` or `Enum.Variant(<fields>)`, the fields are bindings or `_`.
+    Variant {
+        enum_name: Identifier,
+        variant: Identifier,
+        fields: Vec<Pattern>,
+    },
+    /// `start..end`, the integers from start up to end, the end is excluded. A bound is an
+    /// integer, a negated integer or the name of a constant, `geometry.MAX` when imported.
+    Range { start: Box<Expr>, end: Box<Expr> },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub position: Position,
+}
+
+/// `pattern => body`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Box<Expr>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ExprKind {
     Integer(u64),
@@ -201,6 +216,8 @@ pub enum ExprKind {
     },
     Block(Vec<Box<Stmt>>),
     Tuple(Vec<Box<Expr>>),
+    /// `[a, b, c]`, an array of the elements.
+    Array(Vec<Box<Expr>>),
     TupleIndex {
         operand: Box<Expr>,
         element: u64,
@@ -212,11 +229,22 @@ pub enum ExprKind {
         expr: Box<Expr>,
         body: Box<Expr>,
     },
+    /// `while let some(element) = expr { body }`
+    WhileLet {
+        element: Identifier,
+        expr: Box<Expr>,
+        body: Box<Expr>,
+    },
     If {
         cond: Box<Expr>,
         body: Box<Expr>,
         else_if: Option<Box<Expr>>,
     },
+    /// `match value { <arms> }`, the first arm whose pattern matches the value is taken.
+    Match {
+        value: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
     StructExpr {
         name: Box<Expr>,
         fields: Vec<StructExprField>,
@@ -226,10 +254,50 @@ pub enum ExprKind {
     Continue,
     Break,
     Return(Box<Expr>),
+    /// `fn(<params>) <ret> = body`, a function literal. It captures the locals of the enclosing
+    /// functions its body uses. A trailing one is the block following a call, `f(x) { |y| body }`,
+    /// passed as the last actual of the call.
+    Lambda {
+        params: Vec<Box<Item>>,
+        ret: Box<Spec>,
+        body: FunctionBody,
+        trailing: bool,
+    },
     Index {
         operand: Box<Expr>,
         index: Box<Expr>,
     },
+    Cast {
+        operand: Box<Expr>,
+        spec: Box<Spec>,
+    },
+    /// a type written as a spec rather than a name, `fn(i64) i64` in `Vec[fn(i64) i64]`.
+    Spec(Box<Spec>),
+    /// `operand?.name`
+    OptionalField(Box<Expr>, Box<Identifier>),
+    /// `operand?.name(<actuals>)`, the operand is the first actual.
+    OptionalMethod {
+        name: Box<Identifier>,
+        actual: Vec<Box<Expr>>,
+    },
+    /// `value ?? fallback`
+    Coalesce(Box<Expr>, Box<Expr>),
+    /// `start..end`, the end is excluded. Only a for loop iterates it.
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+    },
+    NoneLit,
+    /// the unwrapped receiver of an optional chain, only built when the chain is desugared.
+    ChainReceiver,
+    /// an operand resolved before the desugaring it is part of was built.
+    Resolved,
+    /// raises a runtime error with the message, only built by desugarings. Every `{}` of the
+    /// message is replaced by the next of the values.
+    Trap {
+        message: String,
+        values: Vec<Box<Expr>>,
+    },
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -238,6 +306,8 @@ pub enum StmtKind {
     Item(Box<Item>),
     Assignment {
         op: AssignmentOp,
+        /// the position of the operator token.
+        op_position: Position,
         lvalue: Box<Expr>,
         rhs: Box<Expr>,
     },
@@ -267,6 +337,41 @@ pub enum Visibility {
     Public,
 }
 
+/// a variant of an enum, only unit variants may have an explicit discriminant.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EnumVariant {
+    pub name: Identifier,
+    pub fields: Vec<Box<Spec>>,
+    pub discriminant: Option<Box<Expr>>,
+    pub position: Position,
+}
+
+/// `@name` written before an item, `@name(argument)` gives it an expression.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Attribute {
+    pub name: Identifier,
+    pub argument: Option<Box<Expr>>,
+    /// the argument as it is written, empty when there is none.
+    pub source: String,
+}
+
+/// `use shapes.circle`, makes the public items of another module of the program visible.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Import {
+    pub path: Vec<Identifier>,
+    pub position: Position,
+}
+
+impl Import {
+    /// the name of the imported module, `shapes.circle`.
+    pub fn module_name(&self) -> String {
+        self.path
+            .iter()
+            .map(|name| name.kind().value.as_str())
+            .join(".")
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ItemKind {
     Variable {
@@ -279,11 +384,24 @@ pub enum ItemKind {
     Struct {
         vis: Visibility,
         name: Identifier,
+        /// the `@attribute`s written before the struct.
+        attributes: Vec<Attribute>,
         fields: Vec<Box<Item>>,
     },
+    Enum {
+        vis: Visibility,
+        name: Identifier,
+        /// the `@attribute`s written before the enum.
+        attributes: Vec<Attribute>,
+        variants: Vec<EnumVariant>,
+    },
     Function {
         vis: Visibility,
         name: Identifier,
+        /// the `@attribute`s written before the function.
+        attributes: Vec<Attribute>,
+        /// the type parameters of a generic function, `fn identity[T](x T) T`.
+        type_params: Vec<Identifier>,
         params: Vec<Box<Item>>,
         ret: Box<Spec>,
         body: FunctionBody,
@@ -313,6 +431,11 @@ pub enum SpecKind {
     Infer,
     SelfType,
     Mutable { inner: Box<Spec> },
+    /// `fn(<params>) <ret>`, a function or closure, without a return it returns unit.
+    Function { params: Vec<Box<Spec>>, ret: Box<Spec> },
+    /// a type the typer inferred rather than one written in the source, see
+    /// `Typer::resolve_lambda`.
+    Resolved,
 }
 
 #[derive(Debug, Clone, Copy, Hash, Ord, PartialOrd, Eq, PartialEq)]
@@ -340,15 +463,30 @@ pub trait Node {
 pub trait NodeType: Clone {
     fn name(&self) -> &'static str;
     fn ty(&self) -> AstNodeType;
+
+    /// the copy of a node, a kind whose nodes nest deeply copies them without recursing.
+    fn clone_kind(&self) -> Self {
+        self.clone()
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq)]
 pub struct AstNode<Kind> {
     id: NodeId,
     position: Position,
     kind: Kind,
 }
 
+impl<Kind: NodeType> Clone for AstNode<Kind> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            position: self.position,
+            kind: self.kind.clone_kind(),
+        }
+    }
+}
+
 impl<Kind: NodeType> AstNode<Kind> {
     pub fn new(kind: Kind) -> Self {
         Self {
@@ -369,6 +507,10 @@ impl<Kind: NodeType> AstNode<Kind> {
     pub fn kind(&self) -> &Kind {
         &self.kind
     }
+
+    pub fn into_kind(self) -> Kind {
+        self.kind
+    }
 }
 
 impl<Kind: NodeType> Node for AstNode<Kind> {
@@ -409,24 +551,68 @@ impl NodeType for ExprKind {
             Self::Method { .. } => "Method",
             Self::Block(..) => "Block",
             Self::Tuple(..) => "Tuple",
+            Self::Array(..) => "Array",
             Self::TupleIndex { .. } => "Tuple Index",
             Self::Loop(..) => "Loop",
             Self::While(..) => "While",
             Self::For { .. } => "For",
+            Self::WhileLet { .. } => "While Let",
             Self::If { .. } => "If",
+            Self::Match { .. } => "Match",
             Self::StructExpr { .. } => "Struct Expr",
             Self::SelfType => "Self Type",
             Self::SelfLit => "Self Literal",
             Self::Break => "Break",
             Self::Continue => "Continue",
             Self::Return(..) => "Return",
+            Self::Lambda { .. } => "Lambda",
             Self::Index { .. } => "Index",
+            Self::Cast { .. } => "Cast",
+            Self::Spec(..) => "Spec",
+            Self::OptionalField(..) => "Optional Field",
+            Self::OptionalMethod { .. } => "Optional Method",
+            Self::Coalesce(..) => "Coalesce",
+            Self::Range { .. } => "Range",
+            Self::NoneLit => "None Literal",
+            Self::ChainReceiver => "Chain Receiver",
+            Self::Resolved => "Resolved",
+            Self::Trap { .. } => "Trap",
         }
     }
 
     fn ty(&self) -> AstNodeType {
         AstNodeType::Expr
     }
+
+    /// the left operands of a chain, `1 + 2 + 3`, nest in one another as deep as the chain is
+    /// long. They are copied in a loop, only the right operands are copied recursively.
+    fn clone_kind(&self) -> Self {
+        let mut chain = vec![];
+        let mut kind = self;
+        while let Self::Binary(op, left, right) = kind {
+            chain.push((*op, left.id, left.position, right.clone()));
+            kind = &left.kind;
+        }
+        let mut cloned = kind.clone();
+        while let Some((op, id, position, right)) = chain.pop() {
+            let left = Box::new(Expr { id, position, kind: cloned });
+            cloned = Self::Binary(op, left, right);
+        }
+        cloned
+    }
+}
+
+/// the left operands of a chain are dropped in a loop, like they are copied.
+impl Drop for ExprKind {
+    fn drop(&mut self) {
+        let mut next = match self {
+            Self::Binary(_, left, _) => std::mem::replace(&mut left.kind, Self::Break),
+            _ => return,
+        };
+        while let Self::Binary(_, left, _) = &mut next {
+            next = std::mem::replace(&mut left.kind, Self::Break);
+        }
+    }
 }
 
 impl NodeType for StmtKind {
@@ -451,6 +637,7 @@ impl NodeType for ItemKind {
             Self::Variable { .. } => "Variable",
             Self::Function { .. } => "Function",
             Self::Struct { .. } => "Struct",
+            Self::Enum { .. } => "Enum",
             Self::Param { .. } => "Param",
             Self::SelfParam { .. } => "SelfParam",
             Self::Field { .. } => "Field",
@@ -472,6 +659,8 @@ impl NodeType for SpecKind {
             Self::SelfType => "Self",
             Self::Array { .. } => "Array",
             Self::Mutable { .. } => "Mut",
+            Self::Function { .. } => "Function",
+            Self::Resolved => "Resolved",
         }
     }
 
@@ -510,6 +699,7 @@ impl Item {
         match self.kind() {
             ItemKind::Variable { name, .. }
             | ItemKind::Struct { name, .. }
+            | ItemKind::Enum { name, .. }
             | ItemKind::Function { name, .. } => Some(name),
             ItemKind::Param { .. } | ItemKind::Field { .. } | ItemKind::SelfParam { .. } => None,
         }
@@ -519,6 +709,7 @@ impl Item {
         match self.kind() {
             ItemKind::Variable { vis, .. }
             | ItemKind::Struct { vis, .. }
+            | ItemKind::Enum { vis, .. }
             | ItemKind::Function { vis, .. }
             | ItemKind::Field { vis, .. } => *vis,
             ItemKind::Param { .. } | ItemKind::SelfParam { .. } => Visibility::Private,
@@ -1,7 +1,9 @@
-use std::fmt::Debug;
+use std::cell::Cell;
+use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
 
 use ordered_float::OrderedFloat;
+use oxide::vm::LogLevel;
 
 use crate::analysis::EntityRef;
 use crate::ir::ast::{
@@ -9,7 +11,7 @@ use crate::ir::ast::{
 };
 use crate::syntax::Position;
 use crate::system::FileId;
-use crate::types::Type;
+use crate::types::{Type, TypeKind};
 
 #[derive(Debug, Clone)]
 pub struct BinaryExpr {
@@ -65,6 +67,212 @@ pub struct AssociatedFunctionExpr {
     pub actuals: Vec<Rc<HirExpr>>,
 }
 
+// log.<level>(<message>)
+#[derive(Debug, Clone)]
+pub struct LogExpr {
+    pub level: LogLevel,
+    pub message: HirExprPtr,
+}
+
+// Enum.Variant or Enum.Variant(<fields>)
+#[derive(Debug, Clone)]
+pub struct VariantExpr {
+    pub enum_entity: EntityRef,
+    pub discriminant: i64,
+    pub fields: Vec<HirExprPtr>,
+}
+
+/// a function generated for an enum, stored as a method of the enum's structure.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EnumFunction {
+    FromInt,
+    /// the remaining functions are only generated for flags.
+    Contains,
+    Display,
+}
+
+impl EnumFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::FromInt => "from_int",
+            Self::Contains => "contains",
+            Self::Display => "display",
+        }
+    }
+
+    /// index of the function in the methods of the enum's structure.
+    pub fn method_index(&self) -> u8 {
+        match self {
+            Self::FromInt => 0,
+            Self::Contains => 1,
+            Self::Display => 2,
+        }
+    }
+}
+
+// Enum.from_int(<value>), <flags>.contains(<flag>) or <flags>.display()
+#[derive(Debug, Clone)]
+pub struct EnumFunctionExpr {
+    pub enum_entity: EntityRef,
+    pub function: EnumFunction,
+    pub actuals: Vec<HirExprPtr>,
+}
+
+/// a function of the built in task module or of the values it returns.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TaskFunction {
+    Spawn,
+    Join,
+    Ok,
+    Err,
+}
+
+impl TaskFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Spawn => "spawn",
+            Self::Join => "join",
+            Self::Ok => "ok",
+            Self::Err => "err",
+        }
+    }
+}
+
+/// a function of a built in type, each compiles to an instruction rather than a call.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BuiltinFunction {
+    Vec(VecFunction),
+    Map(MapFunction),
+    Weak(WeakFunction),
+}
+
+impl BuiltinFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Vec(function) => function.name(),
+            Self::Map(function) => function.name(),
+            Self::Weak(function) => function.name(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VecFunction {
+    New,
+    Push,
+    Pop,
+    Len,
+    WithCapacity,
+    Extend,
+    CopyFrom,
+    Fill,
+    FillRange,
+    Reserve,
+    Capacity,
+    ShrinkToFit,
+}
+
+impl VecFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Push => "push",
+            Self::Pop => "pop",
+            Self::Len => "len",
+            Self::WithCapacity => "with_capacity",
+            Self::Extend => "extend",
+            Self::CopyFrom => "copy_from",
+            Self::Fill => "fill",
+            Self::FillRange => "fill_range",
+            Self::Reserve => "reserve",
+            Self::Capacity => "capacity",
+            Self::ShrinkToFit => "shrink_to_fit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MapFunction {
+    New,
+    Insert,
+    Get,
+    Remove,
+    Contains,
+    Len,
+    Reserve,
+}
+
+impl MapFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Insert => "insert",
+            Self::Get => "get",
+            Self::Remove => "remove",
+            Self::Contains => "contains",
+            Self::Len => "len",
+            Self::Reserve => "reserve",
+        }
+    }
+}
+
+/// `weak(obj)` creates the handle, it is the function `new` of the type.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WeakFunction {
+    New,
+    Get,
+}
+
+impl WeakFunction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Get => "get",
+        }
+    }
+}
+
+// task.spawn(<function>, <actuals>), <task>.join(), <result>.ok() or <result>.err()
+#[derive(Debug, Clone)]
+pub struct TaskExpr {
+    pub function: TaskFunction,
+    pub actuals: Vec<HirExprPtr>,
+}
+
+// reflect.get_field(<operand>, <name>), the public field of the instance named by the string or
+// none. The field is looked up while running, the operand can be of any structure.
+#[derive(Debug, Clone)]
+pub struct GetFieldExpr {
+    pub operand: HirExprPtr,
+    pub name: HirExprPtr,
+}
+
+// a runtime error, every `{}` of the message is replaced by the next of the values rendered.
+#[derive(Debug, Clone)]
+pub struct TrapExpr {
+    pub message: String,
+    pub values: Vec<HirExprPtr>,
+}
+
+// <operand> as <type>, the target type is the type of the node.
+#[derive(Debug, Clone)]
+pub struct CastExpr {
+    pub operand: HirExprPtr,
+}
+
+// <receiver>?.<link>, the link is a field or method of the unwrapped receiver.
+#[derive(Debug, Clone)]
+pub struct OptionalChainExpr {
+    pub link: HirExprPtr,
+}
+
+// <value> ?? <fallback>
+#[derive(Debug, Clone)]
+pub struct CoalesceExpr {
+    pub value: HirExprPtr,
+    pub fallback: HirExprPtr,
+}
+
 #[derive(Debug, Clone)]
 pub struct BlockExpr {
     pub stmts: Vec<Rc<HirStmt>>,
@@ -76,6 +284,11 @@ pub struct TupleExpr {
     pub elements: Vec<Rc<HirExpr>>,
 }
 
+#[derive(Debug, Clone)]
+pub struct ArrayExpr {
+    pub elements: Vec<Rc<HirExpr>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct TupleIndex {
     pub tuple: Rc<HirExpr>,
@@ -93,13 +306,6 @@ pub struct WhileExpr {
     pub body: Rc<HirExpr>,
 }
 
-#[derive(Debug, Clone)]
-pub struct ForExpr {
-    pub element: Identifier,
-    pub expr: Rc<HirExpr>,
-    pub body: Rc<HirExpr>,
-}
-
 #[derive(Debug, Clone)]
 pub enum IfExprBranch {
     Conditional {
@@ -112,11 +318,159 @@ pub enum IfExprBranch {
     },
 }
 
+impl IfExprBranch {
+    pub fn body(&self) -> &HirExprPtr {
+        match self {
+            Self::Conditional { body, .. } | Self::Unconditional { body } => body,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IfExpr {
     pub branches: Vec<IfExprBranch>,
 }
 
+/// what an arm of a match compares the value with.
+#[derive(Debug, Clone)]
+pub enum HirPattern {
+    Wildcard,
+    /// every value, bound to a local of the arm.
+    Binding(EntityRef),
+    /// an integer or a bool of the type of the value.
+    Literal(HirExprPtr),
+    /// a variant of the enum of the value, a field that is not `_` is bound to a local of the
+    /// arm.
+    Variant {
+        enum_entity: EntityRef,
+        discriminant: i64,
+        fields: Vec<Option<EntityRef>>,
+    },
+    /// the integers from start up to end, both are integer literals of the type of the value.
+    Range {
+        start: HirExprPtr,
+        end: HirExprPtr,
+    },
+}
+
+impl HirPattern {
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Self::Wildcard | Self::Binding(_))
+    }
+
+    /// the integers the pattern matches, from the first up to the second.
+    fn integer_bounds(&self) -> Option<(i128, i128)> {
+        let value = |expr: &HirExprPtr| match expr.inner().kind() {
+            HirExprKind::Integer(value) => Some(i128::from(*value)),
+            _ => None,
+        };
+        match self {
+            Self::Literal(literal) => value(literal).map(|value| (value, value + 1)),
+            Self::Range { start, end } => Some((value(start)?, value(end)?)),
+            _ => None,
+        }
+    }
+
+    /// whether self matches every value other matches.
+    pub fn covers(&self, other: &HirPattern) -> bool {
+        if let (Some(left), Some(right)) = (self.integer_bounds(), other.integer_bounds()) {
+            return left.0 <= right.0 && right.1 <= left.1;
+        }
+        match (self, other) {
+            (Self::Wildcard | Self::Binding(_), _) => true,
+            (Self::Literal(left), Self::Literal(right)) => {
+                match (left.inner().kind(), right.inner().kind()) {
+                    (HirExprKind::Bool(left), HirExprKind::Bool(right)) => left == right,
+                    _ => false,
+                }
+            }
+            (
+                Self::Variant {
+                    discriminant: left, ..
+                },
+                Self::Variant {
+                    discriminant: right,
+                    ..
+                },
+            ) => left == right,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: HirPattern,
+    pub body: HirExprPtr,
+    /// the position of the pattern.
+    pub position: Position,
+}
+
+/// the value is evaluated once and compared with the patterns of the arms in order, the first
+/// arm that matches is taken.
+#[derive(Debug, Clone)]
+pub struct MatchExpr {
+    pub value: HirExprPtr,
+    pub arms: Vec<MatchArm>,
+}
+
+impl MatchExpr {
+    /// the values of ty none of the arms match, written as patterns. The arms are exhaustive
+    /// when there are none. Integers are only exhausted by `_` or a binding, like the
+    /// combinations of a flags enum.
+    pub fn missing_patterns(arms: &[MatchArm], ty: Rc<Type>) -> Vec<String> {
+        if arms.iter().any(|arm| arm.pattern.is_irrefutable()) {
+            return vec![];
+        }
+        let ty = Type::inner(ty);
+        match ty.kind() {
+            TypeKind::Enum { entity } if !entity.borrow().as_enum().flags => {
+                let entity = entity.borrow();
+                entity
+                    .as_enum()
+                    .variants
+                    .iter()
+                    .filter(|variant| {
+                        !arms.iter().any(|arm| {
+                            matches!(arm.pattern, HirPattern::Variant { discriminant, .. }
+                                if discriminant == variant.discriminant)
+                        })
+                    })
+                    .map(|variant| format!("{}.{}", entity.name(), variant.name))
+                    .collect()
+            }
+            TypeKind::Bool => [true, false]
+                .iter()
+                .filter(|value| {
+                    !arms.iter().any(|arm| {
+                        matches!(&arm.pattern, HirPattern::Literal(literal)
+                            if matches!(literal.inner().kind(), HirExprKind::Bool(matched)
+                                if matched == *value))
+                    })
+                })
+                .map(ToString::to_string)
+                .collect(),
+            _ => vec!["_".to_owned()],
+        }
+    }
+
+    /// the arms that are never taken, the arms before each of them match every value it
+    /// matches.
+    pub fn unreachable_arms(&self) -> Vec<&MatchArm> {
+        self.arms
+            .iter()
+            .enumerate()
+            .filter(|(idx, arm)| {
+                let before = &self.arms[..*idx];
+                before.iter().any(|earlier| earlier.pattern.covers(&arm.pattern))
+                    || (!before.is_empty()
+                        && Self::missing_patterns(before, self.value.ty()).is_empty())
+            })
+            .map(|(_, arm)| arm)
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StructExpr {
     pub struct_type: Rc<Type>,
@@ -139,15 +493,42 @@ pub enum HirExprKind {
     Call(CallExpr),
     Method(MethodExpr),
     AssociatedFunction(AssociatedFunctionExpr),
+    Log(LogExpr),
+    Variant(VariantExpr),
+    EnumFunction(EnumFunctionExpr),
+    Task(TaskExpr),
+    GetField(GetFieldExpr),
+    /// raises a runtime error with the message.
+    Trap(TrapExpr),
+    /// an `unreachable()` the program is built trusting, the passes remove the branches that
+    /// reach it. When it is reached anyway it raises a runtime error with the message.
+    Unreachable(String),
+    Cast(CastExpr),
+    OptionalChain(OptionalChainExpr),
+    /// the receiver of an optional chain, the chain exits when it is none.
+    ChainReceiver(HirExprPtr),
+    Coalesce(CoalesceExpr),
+    NoneLit,
+    /// the string the program embedding the vm identifies itself by, `sys.host()`.
+    Host,
+    /// runs a full collection of the heap of the vm, `gc_collect()`.
+    GcCollect,
     Block(BlockExpr),
     Tuple(TupleExpr),
+    Array(ArrayExpr),
     TupleIndex(TupleIndex),
     Loop(LoopExpr),
     While(WhileExpr),
-    For(ForExpr),
     If(IfExpr),
+    Match(MatchExpr),
     StructExpr(StructExpr),
     SelfLit(EntityRef),
+    /// a function literal, the entity is its closure.
+    Lambda(EntityRef),
+    /// the value, the passes do not look into it.
+    BlackBox(HirExprPtr),
+    /// the source compiled and run as a script by `eval`.
+    Eval(HirExprPtr),
     Break,
     Continue,
     Return(HirExprPtr),
@@ -245,6 +626,23 @@ impl HirExprInner {
     }
 }
 
+/// the left operands of a chain, `1 + 2 + 3`, nest as deep as the chain is long. They are
+/// dropped in a loop, as long as no other node shares them.
+impl Drop for HirExprInner {
+    fn drop(&mut self) {
+        if !matches!(self.kind, HirExprKind::Binary(_)) {
+            return;
+        }
+        let mut kind = std::mem::replace(&mut self.kind, HirExprKind::Break);
+        while let HirExprKind::Binary(BinaryExpr { left, .. }) = kind {
+            kind = match Rc::try_unwrap(left) {
+                Ok(mut left) => std::mem::replace(&mut left.inner.kind, HirExprKind::Break),
+                Err(_) => return,
+            };
+        }
+    }
+}
+
 impl NodeType for HirExprInner {
     fn name(&self) -> &'static str {
         self.kind.name()
@@ -280,15 +678,33 @@ impl NodeType for HirExprKind {
             Self::Call { .. } => "Call",
             Self::Method { .. } => "Method",
             Self::AssociatedFunction(..) => "Associated Function",
+            Self::Log(..) => "Log",
+            Self::Variant(..) => "Variant",
+            Self::EnumFunction(..) => "Enum Function",
+            Self::Task(..) => "Task",
+            Self::GetField(..) => "Get Field",
+            Self::Trap(..) => "Trap",
+            Self::Unreachable(..) => "Unreachable",
+            Self::Cast(..) => "Cast",
+            Self::OptionalChain(..) => "Optional Chain",
+            Self::ChainReceiver(..) => "Chain Receiver",
+            Self::Coalesce(..) => "Coalesce",
+            Self::NoneLit => "None Literal",
+            Self::Host => "Host",
+            Self::GcCollect => "Gc Collect",
             Self::Block(..) => "Block",
             Self::Tuple(..) => "Tuple",
+            Self::Array(..) => "Array",
             Self::TupleIndex(..) => "Tuple Index",
             Self::Loop(..) => "Loop",
             Self::While(..) => "While",
-            Self::For { .. } => "For",
             Self::If { .. } => "If",
+            Self::Match(..) => "Match",
             Self::StructExpr { .. } => "Struct Expr",
             Self::SelfLit(..) => "Self Literal",
+            Self::Lambda(..) => "Lambda",
+            Self::BlackBox(..) => "Black Box",
+            Self::Eval(..) => "Eval",
             Self::Break => "Break",
             Self::Continue => "Continue",
             Self::Return(..) => "Return",
@@ -343,6 +759,8 @@ impl NodeType for HirSpecKind {
             Self::Array => "Array",
             Self::Slice => "Slice",
             Self::Mutable => "Mutable",
+            Self::Option => "Option",
+            Self::Function => "Function",
         }
     }
 
@@ -378,6 +796,8 @@ pub enum HirSpecKind {
     Array,
     Slice,
     Mutable,
+    Option,
+    Function,
 }
 
 #[derive(Debug, Clone)]
@@ -448,12 +868,44 @@ pub enum HirItemKind {
     Function(Function),
 }
 
+/// a construct the typer lowers into other syntax before it is checked.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Desugaring {
+    CompoundAssignment,
+    FlagsEcho,
+    OptionalChain,
+    ForLoop,
+    WhileLet,
+    Contract,
+}
+
+impl Desugaring {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CompoundAssignment => "compound assignment",
+            Self::FlagsEcho => "echo of flags",
+            Self::OptionalChain => "optional chain",
+            Self::ForLoop => "for loop",
+            Self::WhileLet => "while let",
+            Self::Contract => "contract",
+        }
+    }
+}
+
+impl Display for Desugaring {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HirNodeBase<Inner> {
     id: NodeId,
     position: Position,
     inner: Inner,
     ty: Rc<Type>,
+    /// set when the node was synthesized by a desugaring instead of written by the user.
+    generated: Cell<Option<Desugaring>>,
 }
 
 pub trait MirNode {
@@ -471,12 +923,44 @@ impl<Inner: NodeType> HirNodeBase<Inner> {
             inner,
             position,
             ty,
+            generated: Cell::new(None),
         }
     }
 
     pub fn inner(&self) -> &Inner {
         &self.inner
     }
+
+    /// the node with its inner replaced, it keeps the id and position of this node.
+    pub fn with_inner(&self, inner: Inner) -> Self {
+        Self {
+            id: self.id,
+            position: self.position,
+            inner,
+            ty: self.ty.clone(),
+            generated: self.generated.clone(),
+        }
+    }
+
+    /// the node with its type replaced, an instance of a generic function is its body with the
+    /// types of the instance.
+    pub fn with_type(&self, ty: Rc<Type>) -> Self {
+        Self {
+            id: self.id,
+            position: self.position,
+            inner: self.inner.clone(),
+            ty,
+            generated: self.generated.clone(),
+        }
+    }
+
+    pub fn generated_by(&self) -> Option<Desugaring> {
+        self.generated.get()
+    }
+
+    pub fn mark_generated(&self, desugaring: Desugaring) {
+        self.generated.set(Some(desugaring));
+    }
 }
 
 impl<Inner> MirNode for HirNodeBase<Inner> {
@@ -535,7 +1019,7 @@ impl HirExpr {
                     _ => None,
                 })
                 .unwrap(),
-            HirExprKind::While(..) | HirExprKind::For(..) | HirExprKind::Loop(..) => None,
+            HirExprKind::While(..) | HirExprKind::Loop(..) => None,
             _ => Some(self),
         }
     }
@@ -567,6 +1051,10 @@ impl HirFile {
         self.stmts.as_slice()
     }
 
+    pub fn set_stmts(&mut self, stmts: Vec<HirStmtPtr>) {
+        self.stmts = stmts;
+    }
+
     pub fn id(&self) -> FileId {
         self.id
     }
@@ -1,3 +1,5 @@
 pub mod ast;
 pub mod hir;
+pub mod operators;
+pub mod visit;
 // pub mod lir;
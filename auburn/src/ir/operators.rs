@@ -0,0 +1,329 @@
+use oxide::vm::OpCode;
+
+use crate::ir::ast::{AssignmentOp, BinaryOp};
+use crate::syntax::Operator;
+use crate::types::{Type, TypeKind};
+
+/// the numbers a binary operator applies to. Its family has an opcode for each of them in the
+/// order i8, i16, i32, i64, u8, u16, u32, u64, f32, f64.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Operands {
+    /// integers and floats.
+    Numbers,
+    /// integers, the family stops at u64.
+    Integers,
+}
+
+/// the declaration of a binary operator, the syntax tree and the mir share the operator.
+#[derive(Debug, Clone, Copy)]
+pub struct BinaryOperator {
+    /// the token of the operator, none for one called as a method of integers.
+    pub token: Option<Operator>,
+    /// the token of its compound assignment, `x op= y`, and the assignment.
+    pub assignment: Option<(Operator, AssignmentOp)>,
+    /// how tightly the operator binds its operands, it is zero for a method.
+    pub precedence: u8,
+    pub operands: Operands,
+    /// the opcode of the first type of the family.
+    pub family: OpCode,
+    /// whether it combines the flags of an enum, they are stored as i64.
+    pub flags: bool,
+    /// the opcode comparing functions by identity, it compares units too as they are all the
+    /// same value.
+    pub functions: Option<OpCode>,
+    /// the opcode for strings.
+    pub strings: Option<OpCode>,
+}
+
+impl BinaryOperator {
+    const fn new(token: Operator, precedence: u8, operands: Operands, family: OpCode) -> Self {
+        Self {
+            token: Some(token),
+            assignment: None,
+            precedence,
+            operands,
+            family,
+            flags: false,
+            functions: None,
+            strings: None,
+        }
+    }
+
+    const fn method(family: OpCode) -> Self {
+        Self {
+            token: None,
+            assignment: None,
+            precedence: 0,
+            operands: Operands::Integers,
+            family,
+            flags: false,
+            functions: None,
+            strings: None,
+        }
+    }
+
+    const fn assigned_with(self, token: Operator, op: AssignmentOp) -> Self {
+        Self {
+            assignment: Some((token, op)),
+            ..self
+        }
+    }
+
+    const fn with_flags(self) -> Self {
+        Self {
+            flags: true,
+            ..self
+        }
+    }
+
+    const fn with_functions(self, op: OpCode) -> Self {
+        Self {
+            functions: Some(op),
+            ..self
+        }
+    }
+
+    const fn with_strings(self, op: OpCode) -> Self {
+        Self {
+            strings: Some(op),
+            ..self
+        }
+    }
+
+    /// the opcode of the operator for operands of ty, none when it does not apply to them.
+    pub fn opcode(&self, ty: &Type) -> Option<OpCode> {
+        let index = match ty.kind() {
+            TypeKind::I8 => 0,
+            TypeKind::I16 => 1,
+            TypeKind::Integer | TypeKind::I32 => 2,
+            TypeKind::I64 => 3,
+            TypeKind::U8 => 4,
+            TypeKind::U16 => 5,
+            TypeKind::U32 => 6,
+            TypeKind::U64 => 7,
+            TypeKind::Float | TypeKind::F32 => 8,
+            TypeKind::F64 => 9,
+            TypeKind::Enum { .. } if self.flags => 3,
+            TypeKind::Function { .. } | TypeKind::Unit => return self.functions,
+            TypeKind::String => return self.strings,
+            _ => return None,
+        };
+        let types = match self.operands {
+            Operands::Numbers => 10,
+            Operands::Integers => 8,
+        };
+        if index < types {
+            OpCode::from_u8(self.family as u8 + index)
+        } else {
+            None
+        }
+    }
+}
+
+impl BinaryOp {
+    /// every binary operator is declared here, the parser, the typer and the backends look up
+    /// its token, precedence, compound assignment and opcodes in the declaration.
+    pub const fn declaration(self) -> BinaryOperator {
+        use Operands::*;
+        match self {
+            Self::Plus => BinaryOperator::new(Operator::Plus, 12, Numbers, OpCode::AddI8)
+                .assigned_with(Operator::PlusEq, AssignmentOp::PlusAssign)
+                .with_strings(OpCode::ConcatStr),
+            Self::Minus => BinaryOperator::new(Operator::Minus, 12, Numbers, OpCode::SubI8)
+                .assigned_with(Operator::MinusEq, AssignmentOp::MinusAssign),
+            Self::Astrick => BinaryOperator::new(Operator::Astrick, 13, Numbers, OpCode::MultI8)
+                .assigned_with(Operator::AstriskEq, AssignmentOp::AstriskAssign),
+            Self::Slash => BinaryOperator::new(Operator::Slash, 13, Numbers, OpCode::DivI8)
+                .assigned_with(Operator::SlashEq, AssignmentOp::SlashAssign),
+            Self::Percent => BinaryOperator::new(Operator::Percent, 13, Numbers, OpCode::RemI8)
+                .assigned_with(Operator::PercentEq, AssignmentOp::PercentAssign),
+            Self::Less => BinaryOperator::new(Operator::Less, 10, Numbers, OpCode::LessI8),
+            Self::Greater => {
+                BinaryOperator::new(Operator::Greater, 10, Numbers, OpCode::GreaterI8)
+            }
+            Self::LessEq => BinaryOperator::new(Operator::LessEq, 4, Numbers, OpCode::LessEqI8),
+            Self::GreaterEq => {
+                BinaryOperator::new(Operator::GreaterEq, 4, Numbers, OpCode::GreaterEqI8)
+            }
+            Self::EqualEqual => {
+                BinaryOperator::new(Operator::EqualEqual, 10, Numbers, OpCode::EqEqI8)
+                    .with_functions(OpCode::EqEqFn)
+                    .with_strings(OpCode::EqEqStr)
+            }
+            Self::BangEqual => {
+                BinaryOperator::new(Operator::BangEqual, 10, Numbers, OpCode::NotEqI8)
+                    .with_functions(OpCode::NotEqFn)
+                    .with_strings(OpCode::NotEqStr)
+            }
+            Self::Ampersand => {
+                BinaryOperator::new(Operator::Ampersand, 9, Integers, OpCode::BinaryAndI8)
+                    .assigned_with(Operator::AmpersandEq, AssignmentOp::AmpersandAssign)
+                    .with_flags()
+            }
+            Self::Pipe => BinaryOperator::new(Operator::Pipe, 6, Integers, OpCode::BinaryOrI8)
+                .assigned_with(Operator::PipeEq, AssignmentOp::PipeAssign)
+                .with_flags(),
+            Self::Caret => BinaryOperator::new(Operator::Caret, 8, Integers, OpCode::BinaryXorI8)
+                .assigned_with(Operator::CaretEq, AssignmentOp::CaretAssign)
+                .with_flags(),
+            Self::LessLess => {
+                BinaryOperator::new(Operator::LessLess, 11, Integers, OpCode::ShiftLeftI8)
+                    .assigned_with(Operator::LessLessEq, AssignmentOp::LessLessAssign)
+            }
+            Self::GreaterGreater => BinaryOperator::new(
+                Operator::GreaterGreater,
+                11,
+                Integers,
+                OpCode::ShiftRightI8,
+            )
+            .assigned_with(Operator::GreaterGreaterEq, AssignmentOp::GreaterGreaterAssign),
+            Self::DivFloor => BinaryOperator::method(OpCode::DivFloorI8),
+            Self::ModFloor => BinaryOperator::method(OpCode::ModFloorI8),
+        }
+    }
+
+    /// the operator written as token.
+    pub fn from_token(token: Operator) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|op| op.declaration().token == Some(token))
+    }
+}
+
+impl AssignmentOp {
+    /// the assignment written as token.
+    pub fn from_token(token: Operator) -> Option<Self> {
+        if token == Operator::Equal {
+            return Some(Self::Assign);
+        }
+        BinaryOp::ALL
+            .iter()
+            .find_map(|op| match op.declaration().assignment {
+                Some((assignment_token, assignment)) if assignment_token == token => {
+                    Some(assignment)
+                }
+                _ => None,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use oxide::{vm::Instruction, Vm};
+
+    use crate::ir::ast::{AssignmentOp, BinaryOp};
+    use crate::syntax::{Operator, Token};
+    use crate::types::{Type, TypeKind};
+
+    fn operand_types() -> Vec<Type> {
+        let function = TypeKind::Function {
+            params: vec![],
+            return_type: Rc::new(Type::new(TypeKind::Unit)),
+        };
+        vec![
+            TypeKind::I8,
+            TypeKind::I16,
+            TypeKind::I32,
+            TypeKind::I64,
+            TypeKind::U8,
+            TypeKind::U16,
+            TypeKind::U32,
+            TypeKind::U64,
+            TypeKind::F32,
+            TypeKind::F64,
+            TypeKind::Bool,
+            TypeKind::Char,
+            TypeKind::String,
+            function,
+        ]
+        .into_iter()
+        .map(Type::new)
+        .collect()
+    }
+
+    #[test]
+    fn test_operator_tokens_round_trip() {
+        for op in BinaryOp::ALL.iter().copied() {
+            let declaration = op.declaration();
+            match declaration.token {
+                Some(token) => {
+                    assert_eq!(token.to_string(), op.to_string());
+                    assert_eq!(BinaryOp::from_token(token), Some(op));
+                    assert_eq!(Token::Op(token).precedence(), declaration.precedence);
+                    assert!(declaration.precedence > 0, "{}", op);
+                }
+                None => assert_eq!(BinaryOp::integer_method(&op.to_string()), Some(op)),
+            }
+            if let Some((token, assignment)) = declaration.assignment {
+                assert_eq!(token.to_string(), assignment.to_string());
+                assert_eq!(assignment.to_string(), format!("{}=", op));
+                assert_eq!(AssignmentOp::from_token(token), Some(assignment));
+                assert_eq!(assignment.get_binary_op(), Some(op));
+                assert!(token.is_assignment());
+            }
+        }
+
+        // every token of an operator or an assignment is declared by exactly one of them.
+        for token in Operator::ALL.iter().copied() {
+            let operators = BinaryOp::ALL
+                .iter()
+                .filter(|op| op.declaration().token == Some(token))
+                .count();
+            let assignments = BinaryOp::ALL
+                .iter()
+                .filter(|op| matches!(op.declaration().assignment, Some((t, _)) if t == token))
+                .count();
+            assert!(operators + assignments <= 1, "{}", token);
+            assert_eq!(
+                token.is_assignment(),
+                token == Operator::Equal || assignments == 1,
+                "{}",
+                token
+            );
+        }
+        for assignment in AssignmentOp::ALL.iter().copied() {
+            let token = Operator::ALL
+                .iter()
+                .copied()
+                .find(|token| token.to_string() == assignment.to_string())
+                .unwrap();
+            assert_eq!(AssignmentOp::from_token(token), Some(assignment));
+        }
+    }
+
+    #[test]
+    fn test_operators_lower_to_their_family() {
+        let mut vm = Vm::new();
+        for op in BinaryOp::ALL.iter().copied() {
+            let declaration = op.declaration();
+            let mut lowered = 0;
+            for ty in operand_types() {
+                let opcode = match declaration.opcode(&ty) {
+                    Some(opcode) => opcode,
+                    None => continue,
+                };
+                lowered += 1;
+                let name = opcode.to_string();
+                match ty.kind() {
+                    TypeKind::Function { .. } => assert!(name.ends_with("_fn"), "{}", name),
+                    TypeKind::String => assert!(name.ends_with("_str"), "{}", name),
+                    _ => assert!(name.ends_with(&format!("_{}", ty)), "{} on {}", name, ty),
+                }
+
+                let mut section = vm.new_section();
+                section.write_op(opcode);
+                assert_eq!(section.disassemble(), vec![Instruction::simple(0, opcode)]);
+            }
+            let numbers = match declaration.operands {
+                super::Operands::Numbers => 10,
+                super::Operands::Integers => 8,
+            };
+            let others = [declaration.functions, declaration.strings];
+            let others = others.iter().filter(|opcode| opcode.is_some()).count();
+            assert_eq!(lowered, numbers + others, "{}", op);
+        }
+    }
+}
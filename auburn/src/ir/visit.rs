@@ -0,0 +1,588 @@
+use std::ops::Deref;
+use std::rc::Rc;
+
+use ordered_float::OrderedFloat;
+
+use crate::analysis::{EntityInfo, EntityRef};
+use crate::ir::hir::{
+    ArrayExpr, Assignment, AssociatedFunctionExpr, BinaryExpr, BlockExpr, CallExpr, CastExpr,
+    CoalesceExpr, EnumFunctionExpr, FieldAccessExpr, FieldExpr, GetFieldExpr, HirExpr,
+    HirExprInner, HirExprKind, HirExprPtr, HirFile, HirStmt, HirStmtKind, HirStmtPtr, IfExpr,
+    IfExprBranch, IndexExpr, LogExpr, LoopExpr, MatchArm, MatchExpr, MethodExpr,
+    OptionalChainExpr, StructExpr, TaskExpr, TrapExpr, TupleExpr, TupleIndex, UnaryExpr,
+    VariantExpr, WhileExpr,
+};
+
+/// walks the checked program, for passes that look at it without changing it. Every kind of
+/// statement and expression has a method, by default each visits the nodes under it. A method
+/// that is overridden walks what is under the node by calling the `walk_` function of it.
+pub trait MirVisitor {
+    fn visit_file(&mut self, file: &HirFile) {
+        walk_file(self, file)
+    }
+
+    /// the item of a statement, the bodies of functions and methods and the value of globals.
+    fn visit_item(&mut self, entity: &EntityRef) {
+        walk_item(self, entity)
+    }
+
+    fn visit_stmt(&mut self, stmt: &HirStmt) {
+        walk_stmt(self, stmt)
+    }
+
+    fn visit_expr_stmt(&mut self, _stmt: &HirStmt, expr: &HirExpr) {
+        self.visit_expr(expr)
+    }
+
+    fn visit_item_stmt(&mut self, _stmt: &HirStmt, entity: &EntityRef) {
+        self.visit_item(entity)
+    }
+
+    fn visit_assignment(&mut self, _stmt: &HirStmt, assignment: &Assignment) {
+        self.visit_expr(&assignment.lvalue);
+        self.visit_expr(&assignment.rhs);
+    }
+
+    fn visit_echo(&mut self, _stmt: &HirStmt, expr: &HirExpr) {
+        self.visit_expr(expr)
+    }
+
+    fn visit_expr(&mut self, expr: &HirExpr) {
+        walk_expr(self, expr)
+    }
+
+    fn visit_integer(&mut self, _expr: &HirExpr, _value: i64) {}
+
+    fn visit_float(&mut self, _expr: &HirExpr, _value: OrderedFloat<f64>) {}
+
+    fn visit_string(&mut self, _expr: &HirExpr, _value: &str) {}
+
+    fn visit_char(&mut self, _expr: &HirExpr, _value: char) {}
+
+    fn visit_bool(&mut self, _expr: &HirExpr, _value: bool) {}
+
+    fn visit_name(&mut self, _expr: &HirExpr, _entity: &EntityRef) {}
+
+    /// an operator is visited after its left operand, which `walk_expr` visits. The left
+    /// operands of a chain, `1 + 2 + 3`, nest as deep as the chain is long and are visited in
+    /// a loop, from the first operand.
+    fn visit_binary(&mut self, _expr: &HirExpr, binary: &BinaryExpr) {
+        self.visit_expr(&binary.right);
+    }
+
+    fn visit_unary(&mut self, _expr: &HirExpr, unary: &UnaryExpr) {
+        self.visit_expr(&unary.operand)
+    }
+
+    fn visit_field(&mut self, _expr: &HirExpr, field: &FieldExpr) {
+        self.visit_expr(&field.operand)
+    }
+
+    fn visit_index(&mut self, _expr: &HirExpr, index: &IndexExpr) {
+        self.visit_expr(&index.operand);
+        self.visit_expr(&index.index);
+    }
+
+    fn visit_field_access(&mut self, _expr: &HirExpr, _access: &FieldAccessExpr) {}
+
+    fn visit_call(&mut self, _expr: &HirExpr, call: &CallExpr) {
+        self.visit_expr(&call.operand);
+        call.actuals.iter().for_each(|actual| self.visit_expr(actual));
+    }
+
+    fn visit_method(&mut self, _expr: &HirExpr, method: &MethodExpr) {
+        method.actuals.iter().for_each(|actual| self.visit_expr(actual));
+    }
+
+    fn visit_associated_function(&mut self, _expr: &HirExpr, function: &AssociatedFunctionExpr) {
+        function.actuals.iter().for_each(|actual| self.visit_expr(actual));
+    }
+
+    fn visit_log(&mut self, _expr: &HirExpr, log: &LogExpr) {
+        self.visit_expr(&log.message)
+    }
+
+    fn visit_variant(&mut self, _expr: &HirExpr, variant: &VariantExpr) {
+        variant.fields.iter().for_each(|field| self.visit_expr(field));
+    }
+
+    fn visit_enum_function(&mut self, _expr: &HirExpr, function: &EnumFunctionExpr) {
+        function.actuals.iter().for_each(|actual| self.visit_expr(actual));
+    }
+
+    fn visit_task(&mut self, _expr: &HirExpr, task: &TaskExpr) {
+        task.actuals.iter().for_each(|actual| self.visit_expr(actual));
+    }
+
+    fn visit_get_field(&mut self, _expr: &HirExpr, get_field: &GetFieldExpr) {
+        self.visit_expr(&get_field.operand);
+        self.visit_expr(&get_field.name);
+    }
+
+    fn visit_trap(&mut self, _expr: &HirExpr, trap: &TrapExpr) {
+        trap.values.iter().for_each(|value| self.visit_expr(value));
+    }
+
+    fn visit_cast(&mut self, _expr: &HirExpr, cast: &CastExpr) {
+        self.visit_expr(&cast.operand)
+    }
+
+    fn visit_optional_chain(&mut self, _expr: &HirExpr, chain: &OptionalChainExpr) {
+        self.visit_expr(&chain.link)
+    }
+
+    fn visit_chain_receiver(&mut self, _expr: &HirExpr, receiver: &HirExpr) {
+        self.visit_expr(receiver)
+    }
+
+    fn visit_black_box(&mut self, _expr: &HirExpr, value: &HirExpr) {
+        self.visit_expr(value)
+    }
+
+    fn visit_eval(&mut self, _expr: &HirExpr, source: &HirExpr) {
+        self.visit_expr(source)
+    }
+
+    fn visit_coalesce(&mut self, _expr: &HirExpr, coalesce: &CoalesceExpr) {
+        self.visit_expr(&coalesce.value);
+        self.visit_expr(&coalesce.fallback);
+    }
+
+    fn visit_none(&mut self, _expr: &HirExpr) {}
+
+    fn visit_host(&mut self, _expr: &HirExpr) {}
+
+    fn visit_gc_collect(&mut self, _expr: &HirExpr) {}
+
+    fn visit_unreachable(&mut self, _expr: &HirExpr, _message: &str) {}
+
+    fn visit_block(&mut self, _expr: &HirExpr, block: &BlockExpr) {
+        block.stmts.iter().for_each(|stmt| self.visit_stmt(stmt));
+    }
+
+    fn visit_tuple(&mut self, _expr: &HirExpr, tuple: &TupleExpr) {
+        tuple.elements.iter().for_each(|element| self.visit_expr(element));
+    }
+
+    fn visit_array(&mut self, _expr: &HirExpr, array: &ArrayExpr) {
+        array.elements.iter().for_each(|element| self.visit_expr(element));
+    }
+
+    fn visit_tuple_index(&mut self, _expr: &HirExpr, index: &TupleIndex) {
+        self.visit_expr(&index.tuple)
+    }
+
+    fn visit_loop(&mut self, _expr: &HirExpr, loop_expr: &LoopExpr) {
+        self.visit_expr(&loop_expr.body)
+    }
+
+    fn visit_while(&mut self, _expr: &HirExpr, while_expr: &WhileExpr) {
+        self.visit_expr(&while_expr.cond);
+        self.visit_expr(&while_expr.body);
+    }
+
+    fn visit_if(&mut self, _expr: &HirExpr, if_expr: &IfExpr) {
+        for branch in &if_expr.branches {
+            match branch {
+                IfExprBranch::Conditional { cond, body, .. } => {
+                    self.visit_expr(cond);
+                    self.visit_expr(body);
+                }
+                IfExprBranch::Unconditional { body } => self.visit_expr(body),
+            }
+        }
+    }
+
+    fn visit_match(&mut self, _expr: &HirExpr, match_expr: &MatchExpr) {
+        self.visit_expr(&match_expr.value);
+        for arm in &match_expr.arms {
+            self.visit_expr(&arm.body);
+        }
+    }
+
+    fn visit_struct_expr(&mut self, _expr: &HirExpr, struct_expr: &StructExpr) {
+        struct_expr
+            .fields
+            .iter()
+            .for_each(|(_, field)| self.visit_expr(field));
+    }
+
+    fn visit_self(&mut self, _expr: &HirExpr, _entity: &EntityRef) {}
+
+    /// the body of the function literal is visited like the body of an item.
+    fn visit_lambda(&mut self, _expr: &HirExpr, entity: &EntityRef) {
+        self.visit_item(entity)
+    }
+
+    fn visit_break(&mut self, _expr: &HirExpr) {}
+
+    fn visit_continue(&mut self, _expr: &HirExpr) {}
+
+    fn visit_return(&mut self, _expr: &HirExpr, value: &HirExpr) {
+        self.visit_expr(value)
+    }
+}
+
+pub fn walk_file<V: MirVisitor + ?Sized>(visitor: &mut V, file: &HirFile) {
+    file.stmts().iter().for_each(|stmt| visitor.visit_stmt(stmt));
+}
+
+pub fn walk_item<V: MirVisitor + ?Sized>(visitor: &mut V, entity: &EntityRef) {
+    // the expressions are taken out of the entity so a visitor can borrow it, a recursive
+    // function names itself.
+    for expr in item_exprs(entity) {
+        visitor.visit_expr(&expr);
+    }
+    for method in item_methods(entity) {
+        visitor.visit_item(&method);
+    }
+}
+
+pub fn walk_stmt<V: MirVisitor + ?Sized>(visitor: &mut V, stmt: &HirStmt) {
+    match stmt.inner() {
+        HirStmtKind::Expr(expr) => visitor.visit_expr_stmt(stmt, expr),
+        HirStmtKind::Item(entity) => visitor.visit_item_stmt(stmt, entity),
+        HirStmtKind::Assignment(assignment) => visitor.visit_assignment(stmt, assignment),
+        HirStmtKind::Echo(expr) => visitor.visit_echo(stmt, expr),
+    }
+}
+
+/// calls the method of the kind of expr.
+pub fn walk_expr<V: MirVisitor + ?Sized>(visitor: &mut V, expr: &HirExpr) {
+    match expr.inner().kind() {
+        HirExprKind::Integer(value) => visitor.visit_integer(expr, *value),
+        HirExprKind::Float(value) => visitor.visit_float(expr, *value),
+        HirExprKind::String(value) => visitor.visit_string(expr, value),
+        HirExprKind::Char(value) => visitor.visit_char(expr, *value),
+        HirExprKind::Bool(value) => visitor.visit_bool(expr, *value),
+        HirExprKind::Name(entity) => visitor.visit_name(expr, entity),
+        HirExprKind::Binary(binary) => {
+            let mut chain = vec![(expr, binary)];
+            let mut left = binary.left.as_ref();
+            while let HirExprKind::Binary(binary) = left.inner().kind() {
+                chain.push((left, binary));
+                left = binary.left.as_ref();
+            }
+            visitor.visit_expr(left);
+            for (expr, binary) in chain.into_iter().rev() {
+                visitor.visit_binary(expr, binary);
+            }
+        }
+        HirExprKind::Unary(unary) => visitor.visit_unary(expr, unary),
+        HirExprKind::Field(field) => visitor.visit_field(expr, field),
+        HirExprKind::Index(index) => visitor.visit_index(expr, index),
+        HirExprKind::FieldAccess(access) => visitor.visit_field_access(expr, access),
+        HirExprKind::Call(call) => visitor.visit_call(expr, call),
+        HirExprKind::Method(method) => visitor.visit_method(expr, method),
+        HirExprKind::AssociatedFunction(function) => {
+            visitor.visit_associated_function(expr, function)
+        }
+        HirExprKind::Log(log) => visitor.visit_log(expr, log),
+        HirExprKind::Variant(variant) => visitor.visit_variant(expr, variant),
+        HirExprKind::EnumFunction(function) => visitor.visit_enum_function(expr, function),
+        HirExprKind::Task(task) => visitor.visit_task(expr, task),
+        HirExprKind::GetField(get_field) => visitor.visit_get_field(expr, get_field),
+        HirExprKind::Trap(trap) => visitor.visit_trap(expr, trap),
+        HirExprKind::Cast(cast) => visitor.visit_cast(expr, cast),
+        HirExprKind::OptionalChain(chain) => visitor.visit_optional_chain(expr, chain),
+        HirExprKind::ChainReceiver(receiver) => visitor.visit_chain_receiver(expr, receiver),
+        HirExprKind::Coalesce(coalesce) => visitor.visit_coalesce(expr, coalesce),
+        HirExprKind::NoneLit => visitor.visit_none(expr),
+        HirExprKind::Host => visitor.visit_host(expr),
+        HirExprKind::GcCollect => visitor.visit_gc_collect(expr),
+        HirExprKind::Unreachable(message) => visitor.visit_unreachable(expr, message),
+        HirExprKind::Block(block) => visitor.visit_block(expr, block),
+        HirExprKind::Tuple(tuple) => visitor.visit_tuple(expr, tuple),
+        HirExprKind::Array(array) => visitor.visit_array(expr, array),
+        HirExprKind::TupleIndex(index) => visitor.visit_tuple_index(expr, index),
+        HirExprKind::Loop(loop_expr) => visitor.visit_loop(expr, loop_expr),
+        HirExprKind::While(while_expr) => visitor.visit_while(expr, while_expr),
+        HirExprKind::If(if_expr) => visitor.visit_if(expr, if_expr),
+        HirExprKind::Match(match_expr) => visitor.visit_match(expr, match_expr),
+        HirExprKind::StructExpr(struct_expr) => visitor.visit_struct_expr(expr, struct_expr),
+        HirExprKind::SelfLit(entity) => visitor.visit_self(expr, entity),
+        HirExprKind::Lambda(entity) => visitor.visit_lambda(expr, entity),
+        HirExprKind::BlackBox(value) => visitor.visit_black_box(expr, value),
+        HirExprKind::Eval(source) => visitor.visit_eval(expr, source),
+        HirExprKind::Break => visitor.visit_break(expr),
+        HirExprKind::Continue => visitor.visit_continue(expr),
+        HirExprKind::Return(value) => visitor.visit_return(expr, value),
+    }
+}
+
+/// rewrites the checked program, for passes that change it. The nodes of the program are
+/// shared so a rewrite builds new nodes, by default a node is rebuilt from its rewritten
+/// children. The bodies of items are replaced in their entities.
+pub trait MirRewriter {
+    fn rewrite_file(&mut self, file: &mut HirFile) {
+        let stmts = self.rewrite_stmts(file.stmts());
+        file.set_stmts(stmts);
+    }
+
+    /// the bodies of functions and methods and the value of globals.
+    fn rewrite_item(&mut self, entity: &EntityRef) {
+        rewrite_item_children(self, entity)
+    }
+
+    /// the statements of a block or file, a statement can be removed or replaced by several.
+    fn rewrite_stmts(&mut self, stmts: &[HirStmtPtr]) -> Vec<HirStmtPtr> {
+        stmts.iter().map(|stmt| self.rewrite_stmt(stmt)).collect()
+    }
+
+    fn rewrite_stmt(&mut self, stmt: &HirStmtPtr) -> HirStmtPtr {
+        rewrite_stmt_children(self, stmt)
+    }
+
+    fn rewrite_expr(&mut self, expr: &HirExprPtr) -> HirExprPtr {
+        let expr = rewrite_expr_children(self, expr);
+        self.finish_expr(expr)
+    }
+
+    /// an expression once its children are rewritten. The left operands of a chain, `1 + 2 +
+    /// 3`, are rewritten in a loop rather than by `rewrite_expr`, each operator under the
+    /// first is only finished.
+    fn finish_expr(&mut self, expr: HirExprPtr) -> HirExprPtr {
+        expr
+    }
+}
+
+pub fn rewrite_item_children<R: MirRewriter + ?Sized>(rewriter: &mut R, entity: &EntityRef) {
+    let rewritten = item_exprs(entity)
+        .iter()
+        .map(|expr| rewriter.rewrite_expr(expr))
+        .collect::<Vec<_>>();
+    let mut rewritten = rewritten.into_iter();
+    match entity.borrow_mut().kind_mut() {
+        EntityInfo::Function(info) => info.body = rewritten.next().unwrap(),
+        EntityInfo::Closure(info) => info.function.body = rewritten.next().unwrap(),
+        EntityInfo::AssociatedFunction(info) => info.body = rewritten.next().unwrap(),
+        EntityInfo::Variable(info) => info.default = rewritten.next(),
+        _ => {}
+    }
+    for method in item_methods(entity) {
+        rewriter.rewrite_item(&method);
+    }
+}
+
+pub fn rewrite_stmt_children<R: MirRewriter + ?Sized>(
+    rewriter: &mut R,
+    stmt: &HirStmtPtr,
+) -> HirStmtPtr {
+    let kind = match stmt.inner() {
+        HirStmtKind::Expr(expr) => HirStmtKind::Expr(rewriter.rewrite_expr(expr)),
+        HirStmtKind::Item(entity) => {
+            rewriter.rewrite_item(entity);
+            return stmt.clone();
+        }
+        HirStmtKind::Assignment(assignment) => HirStmtKind::Assignment(Assignment {
+            op: assignment.op,
+            lvalue: rewriter.rewrite_expr(&assignment.lvalue),
+            rhs: rewriter.rewrite_expr(&assignment.rhs),
+        }),
+        HirStmtKind::Echo(expr) => HirStmtKind::Echo(rewriter.rewrite_expr(expr)),
+    };
+    Rc::new(stmt.with_inner(kind))
+}
+
+/// expr rebuilt with each expression and statement under it rewritten.
+pub fn rewrite_expr_children<R: MirRewriter + ?Sized>(
+    rewriter: &mut R,
+    expr: &HirExprPtr,
+) -> HirExprPtr {
+    let exprs = |exprs: &[HirExprPtr], rewriter: &mut R| {
+        exprs
+            .iter()
+            .map(|expr| rewriter.rewrite_expr(expr))
+            .collect::<Vec<_>>()
+    };
+    let kind = match expr.inner().kind() {
+        HirExprKind::Integer(_)
+        | HirExprKind::Float(_)
+        | HirExprKind::String(_)
+        | HirExprKind::Char(_)
+        | HirExprKind::Bool(_)
+        | HirExprKind::Name(_)
+        | HirExprKind::FieldAccess(_)
+        | HirExprKind::NoneLit
+        | HirExprKind::Host
+        | HirExprKind::GcCollect
+        | HirExprKind::Unreachable(_)
+        | HirExprKind::SelfLit(_)
+        | HirExprKind::Break
+        | HirExprKind::Continue => return expr.clone(),
+        // the body of a function literal is replaced in its entity.
+        HirExprKind::Lambda(entity) => {
+            rewriter.rewrite_item(entity);
+            return expr.clone();
+        }
+        HirExprKind::Binary(binary) => {
+            let mut chain = vec![];
+            let mut left = &binary.left;
+            while let HirExprKind::Binary(binary) = left.inner().kind() {
+                chain.push((left, binary));
+                left = &binary.left;
+            }
+            let mut left = rewriter.rewrite_expr(left);
+            for (expr, binary) in chain.into_iter().rev() {
+                let kind = HirExprKind::Binary(BinaryExpr {
+                    op: binary.op,
+                    left,
+                    right: rewriter.rewrite_expr(&binary.right),
+                });
+                let inner = expr.inner();
+                let inner = HirExprInner::new(inner.address_mode(), inner.meta(), kind);
+                left = rewriter.finish_expr(Rc::new(expr.with_inner(inner)));
+            }
+            HirExprKind::Binary(BinaryExpr {
+                op: binary.op,
+                left,
+                right: rewriter.rewrite_expr(&binary.right),
+            })
+        }
+        HirExprKind::Unary(unary) => HirExprKind::Unary(UnaryExpr {
+            op: unary.op,
+            operand: rewriter.rewrite_expr(&unary.operand),
+        }),
+        HirExprKind::Field(field) => HirExprKind::Field(FieldExpr {
+            operand: rewriter.rewrite_expr(&field.operand),
+            field: field.field.clone(),
+        }),
+        HirExprKind::Index(index) => HirExprKind::Index(IndexExpr {
+            operand: rewriter.rewrite_expr(&index.operand),
+            index: rewriter.rewrite_expr(&index.index),
+        }),
+        HirExprKind::Call(call) => HirExprKind::Call(CallExpr {
+            operand: rewriter.rewrite_expr(&call.operand),
+            function_type: call.function_type.clone(),
+            actuals: exprs(&call.actuals, rewriter),
+        }),
+        HirExprKind::Method(method) => HirExprKind::Method(MethodExpr {
+            actuals: exprs(&method.actuals, rewriter),
+            ..method.clone()
+        }),
+        HirExprKind::AssociatedFunction(function) => {
+            HirExprKind::AssociatedFunction(AssociatedFunctionExpr {
+                actuals: exprs(&function.actuals, rewriter),
+                ..function.clone()
+            })
+        }
+        HirExprKind::Log(log) => HirExprKind::Log(LogExpr {
+            level: log.level,
+            message: rewriter.rewrite_expr(&log.message),
+        }),
+        HirExprKind::Variant(variant) => HirExprKind::Variant(VariantExpr {
+            fields: exprs(&variant.fields, rewriter),
+            ..variant.clone()
+        }),
+        HirExprKind::EnumFunction(function) => HirExprKind::EnumFunction(EnumFunctionExpr {
+            actuals: exprs(&function.actuals, rewriter),
+            ..function.clone()
+        }),
+        HirExprKind::Task(task) => HirExprKind::Task(TaskExpr {
+            function: task.function,
+            actuals: exprs(&task.actuals, rewriter),
+        }),
+        HirExprKind::GetField(get_field) => HirExprKind::GetField(GetFieldExpr {
+            operand: rewriter.rewrite_expr(&get_field.operand),
+            name: rewriter.rewrite_expr(&get_field.name),
+        }),
+        HirExprKind::Trap(trap) => HirExprKind::Trap(TrapExpr {
+            message: trap.message.clone(),
+            values: exprs(&trap.values, rewriter),
+        }),
+        HirExprKind::Cast(cast) => HirExprKind::Cast(CastExpr {
+            operand: rewriter.rewrite_expr(&cast.operand),
+        }),
+        HirExprKind::OptionalChain(chain) => HirExprKind::OptionalChain(OptionalChainExpr {
+            link: rewriter.rewrite_expr(&chain.link),
+        }),
+        HirExprKind::ChainReceiver(receiver) => {
+            HirExprKind::ChainReceiver(rewriter.rewrite_expr(receiver))
+        }
+        HirExprKind::BlackBox(value) => HirExprKind::BlackBox(rewriter.rewrite_expr(value)),
+        HirExprKind::Eval(source) => HirExprKind::Eval(rewriter.rewrite_expr(source)),
+        HirExprKind::Coalesce(coalesce) => HirExprKind::Coalesce(CoalesceExpr {
+            value: rewriter.rewrite_expr(&coalesce.value),
+            fallback: rewriter.rewrite_expr(&coalesce.fallback),
+        }),
+        HirExprKind::Block(block) => HirExprKind::Block(BlockExpr {
+            stmts: rewriter.rewrite_stmts(&block.stmts),
+            function_block: block.function_block,
+        }),
+        HirExprKind::Tuple(tuple) => HirExprKind::Tuple(TupleExpr {
+            elements: exprs(&tuple.elements, rewriter),
+        }),
+        HirExprKind::Array(array) => HirExprKind::Array(ArrayExpr {
+            elements: exprs(&array.elements, rewriter),
+        }),
+        HirExprKind::TupleIndex(index) => HirExprKind::TupleIndex(TupleIndex {
+            tuple: rewriter.rewrite_expr(&index.tuple),
+            field: index.field,
+        }),
+        HirExprKind::Loop(loop_expr) => HirExprKind::Loop(LoopExpr {
+            body: rewriter.rewrite_expr(&loop_expr.body),
+        }),
+        HirExprKind::While(while_expr) => HirExprKind::While(WhileExpr {
+            cond: rewriter.rewrite_expr(&while_expr.cond),
+            body: rewriter.rewrite_expr(&while_expr.body),
+        }),
+        HirExprKind::If(if_expr) => HirExprKind::If(IfExpr {
+            branches: if_expr
+                .branches
+                .iter()
+                .map(|branch| match branch {
+                    IfExprBranch::Conditional { cond, body, first } => IfExprBranch::Conditional {
+                        cond: rewriter.rewrite_expr(cond),
+                        body: rewriter.rewrite_expr(body),
+                        first: *first,
+                    },
+                    IfExprBranch::Unconditional { body } => IfExprBranch::Unconditional {
+                        body: rewriter.rewrite_expr(body),
+                    },
+                })
+                .collect(),
+        }),
+        HirExprKind::Match(match_expr) => HirExprKind::Match(MatchExpr {
+            value: rewriter.rewrite_expr(&match_expr.value),
+            arms: match_expr
+                .arms
+                .iter()
+                .map(|arm| MatchArm {
+                    pattern: arm.pattern.clone(),
+                    body: rewriter.rewrite_expr(&arm.body),
+                    position: arm.position,
+                })
+                .collect(),
+        }),
+        HirExprKind::StructExpr(struct_expr) => HirExprKind::StructExpr(StructExpr {
+            struct_type: struct_expr.struct_type.clone(),
+            fields: struct_expr
+                .fields
+                .iter()
+                .map(|(index, field)| (*index, rewriter.rewrite_expr(field)))
+                .collect(),
+        }),
+        HirExprKind::Return(value) => HirExprKind::Return(rewriter.rewrite_expr(value)),
+    };
+    let inner = expr.inner();
+    let inner = HirExprInner::new(inner.address_mode(), inner.meta(), kind);
+    Rc::new(expr.with_inner(inner))
+}
+
+/// the expressions of an item, its body or the value of a global.
+fn item_exprs(entity: &EntityRef) -> Vec<HirExprPtr> {
+    match entity.borrow().kind() {
+        EntityInfo::Function(info) => vec![info.body.clone()],
+        EntityInfo::Closure(info) => vec![info.function.body.clone()],
+        EntityInfo::AssociatedFunction(info) => vec![info.body.clone()],
+        EntityInfo::Variable(info) => info.default.iter().cloned().collect(),
+        _ => vec![],
+    }
+}
+
+fn item_methods(entity: &EntityRef) -> Vec<EntityRef> {
+    match entity.borrow().deref().kind() {
+        EntityInfo::Structure(info) => info.methods.elements().to_vec(),
+        _ => vec![],
+    }
+}
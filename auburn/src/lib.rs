@@ -14,6 +14,7 @@ use std::{
     str::FromStr,
 };
 
+pub use crate::compiler::Compiler;
 use crate::error::Error;
 
 use crate::ir::hir::HirFile;
@@ -22,8 +23,11 @@ use crate::system::File;
 
 pub mod analysis;
 pub mod code_gen;
+mod compiler;
 pub mod error;
+pub mod eval;
 pub mod ir;
+pub mod passes;
 pub mod syntax;
 pub mod system;
 pub mod types;
@@ -0,0 +1,340 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use crate::analysis::{Entity, EntityId, EntityInfo, EntityRef};
+use crate::error::Error;
+use crate::ir::ast::Visibility;
+use crate::ir::hir::{
+    AssociatedFunctionExpr, HirExpr, HirExprInner, HirExprKind, HirExprPtr, HirFile, HirStmt,
+    HirStmtKind, HirStmtPtr, IfExpr, IfExprBranch, MatchExpr, MethodExpr, MirNode,
+};
+use crate::ir::visit::{walk_item, MirRewriter, MirVisitor};
+use crate::passes::{MirPass, PassContext};
+use crate::syntax::BENCH_PREFIX;
+
+/// removes the branches of an if whose condition is a constant, a branch that is never taken
+/// and those after a branch that is always taken. The arms of a match that are never taken are
+/// removed with a warning, they are a mistake of the program rather than a constant, as are the
+/// statements after a `return`, `break` or `continue`. The branches and arms starting with an
+/// `unreachable()` the program is built trusting are removed without one. The private functions
+/// and methods no entity the program keeps uses are removed so they are not built.
+#[derive(Default)]
+pub struct DeadCode {
+    warnings: Vec<Error>,
+}
+
+impl MirPass for DeadCode {
+    fn name(&self) -> &str {
+        "dead-code"
+    }
+
+    fn run(&mut self, file: &mut HirFile, cx: &mut PassContext) {
+        self.rewrite_file(file);
+        Reachable::prune(file);
+        self.warnings.drain(..).for_each(|warning| cx.warn(warning));
+    }
+}
+
+impl MirRewriter for DeadCode {
+    /// the statements after one that always leaves the block are dropped, except the local
+    /// functions among them as they can be used before they are declared.
+    fn rewrite_stmts(&mut self, stmts: &[HirStmtPtr]) -> Vec<HirStmtPtr> {
+        let mut stmts = stmts
+            .iter()
+            .map(|stmt| self.rewrite_stmt(stmt))
+            .collect::<Vec<_>>();
+        let exit = stmts
+            .iter()
+            .enumerate()
+            .find_map(|(idx, stmt)| Self::exit(stmt).map(|exit| (idx, exit)));
+        if let Some((idx, exit)) = exit {
+            let unreachable = stmts.split_off(idx + 1);
+            let (functions, dropped): (Vec<_>, Vec<_>) =
+                unreachable.into_iter().partition(|stmt| match stmt.inner() {
+                    HirStmtKind::Item(entity) => entity.borrow().is_function(),
+                    _ => false,
+                });
+            if let Some(first) = dropped.first() {
+                let warning = Error::unreachable_statement(exit).with_position(first.position());
+                self.warnings.push(warning);
+            }
+            stmts.extend(functions);
+        }
+        stmts
+    }
+
+    fn finish_expr(&mut self, expr: HirExprPtr) -> HirExprPtr {
+        let kind = match expr.inner().kind() {
+            HirExprKind::If(if_expr) => Self::live_branches(if_expr).map(HirExprKind::If),
+            HirExprKind::Match(match_expr) => self.live_arms(match_expr).map(HirExprKind::Match),
+            _ => None,
+        };
+        match kind {
+            Some(kind) => {
+                let inner = expr.inner();
+                let inner = HirExprInner::new(inner.address_mode(), inner.meta(), kind);
+                Rc::new(expr.with_inner(inner))
+            }
+            None => expr,
+        }
+    }
+}
+
+impl DeadCode {
+    /// the keyword of a statement that always leaves the block it is in.
+    fn exit(stmt: &HirStmt) -> Option<&'static str> {
+        match stmt.inner() {
+            HirStmtKind::Expr(expr) => match expr.inner().kind() {
+                HirExprKind::Return(_) => Some("return"),
+                HirExprKind::Break => Some("break"),
+                HirExprKind::Continue => Some("continue"),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// whether body starts with an `unreachable()` the program is built trusting, the branch
+    /// reaching it is never taken.
+    fn is_unreachable(body: &HirExpr) -> bool {
+        match body.inner().kind() {
+            HirExprKind::Unreachable(_) => true,
+            HirExprKind::Block(block) => block.stmts.first().is_some_and(|stmt| {
+                matches!(stmt.inner(), HirStmtKind::Expr(expr) if Self::is_unreachable(expr))
+            }),
+            _ => false,
+        }
+    }
+
+    /// the branches that can be taken, none when every branch can. When the else is unreachable
+    /// the branch before it is taken whenever the else would be.
+    fn live_branches(if_expr: &IfExpr) -> Option<IfExpr> {
+        let mut branches = vec![];
+        for branch in &if_expr.branches {
+            match branch {
+                IfExprBranch::Conditional { body, .. } if Self::is_unreachable(body) => {}
+                IfExprBranch::Unconditional { body } if Self::is_unreachable(body) => {
+                    if let Some(IfExprBranch::Conditional { body, .. }) = branches.pop() {
+                        branches.push(IfExprBranch::Unconditional { body });
+                    }
+                }
+                IfExprBranch::Conditional { cond, body, .. } => {
+                    match cond.inner().kind() {
+                        HirExprKind::Bool(false) => {}
+                        HirExprKind::Bool(true) => {
+                            branches.push(IfExprBranch::Unconditional { body: body.clone() });
+                            break;
+                        }
+                        _ => branches.push(IfExprBranch::Conditional {
+                            cond: cond.clone(),
+                            body: body.clone(),
+                            first: branches.is_empty(),
+                        }),
+                    }
+                }
+                IfExprBranch::Unconditional { body } => {
+                    branches.push(IfExprBranch::Unconditional { body: body.clone() })
+                }
+            }
+        }
+
+        // every branch of an if with a value is unreachable, it is left to raise the error.
+        let has_else = matches!(
+            if_expr.branches.last(),
+            Some(IfExprBranch::Unconditional { .. })
+        );
+        if (branches.is_empty() && has_else) || branches.len() == if_expr.branches.len() {
+            return None;
+        }
+        Some(IfExpr { branches })
+    }
+
+    /// the arms that can be taken, none when every arm can.
+    fn live_arms(&mut self, match_expr: &MatchExpr) -> Option<MatchExpr> {
+        let never_taken = match_expr.unreachable_arms();
+        self.warnings.extend(
+            never_taken
+                .iter()
+                .map(|arm| Error::unreachable_arm().with_position(arm.position)),
+        );
+        let arms = match_expr
+            .arms
+            .iter()
+            .filter(|arm| !never_taken.iter().any(|dead| std::ptr::eq(*dead, *arm)))
+            .filter(|arm| !Self::is_unreachable(&arm.body))
+            .cloned()
+            .collect::<Vec<_>>();
+        if arms.is_empty() || arms.len() == match_expr.arms.len() {
+            return None;
+        }
+        Some(MatchExpr {
+            value: match_expr.value.clone(),
+            arms,
+        })
+    }
+}
+
+/// the functions and methods used by the entities a program keeps whatever uses them: its
+/// entry, the benches, the public entities and the globals.
+#[derive(Default)]
+struct Reachable {
+    entry: Option<EntityId>,
+    reached: HashSet<EntityId>,
+    pending: Vec<EntityRef>,
+}
+
+impl Reachable {
+    /// removes the private functions of file and the private methods of its structures that
+    /// are not reached, the methods left are numbered again in order.
+    fn prune(file: &mut HirFile) {
+        let mut reachable = Self {
+            entry: file.get_entry().map(|entry| entry.borrow().id()),
+            ..Self::default()
+        };
+        for stmt in file.stmts() {
+            match stmt.inner() {
+                HirStmtKind::Item(entity) => reachable.visit_root(entity),
+                _ => reachable.visit_stmt(stmt),
+            }
+        }
+        while let Some(entity) = reachable.pending.pop() {
+            reachable.visit_function(&entity);
+        }
+
+        let stmts = file
+            .stmts()
+            .iter()
+            .filter(|stmt| match stmt.inner() {
+                HirStmtKind::Item(entity) => reachable.keeps(entity),
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        file.set_stmts(stmts);
+        for stmt in file.stmts() {
+            if let HirStmtKind::Item(entity) = stmt.inner() {
+                reachable.prune_methods(entity);
+            }
+        }
+    }
+
+    fn visit_root(&mut self, entity: &EntityRef) {
+        let (is_root, methods) = {
+            let borrow = entity.borrow();
+            let is_root = self.is_root(&borrow);
+            let methods = match borrow.kind() {
+                EntityInfo::Structure(info) => Some((info.fields.clone(), info.methods.clone())),
+                EntityInfo::Function(_) => None,
+                _ => {
+                    drop(borrow);
+                    walk_item(self, entity);
+                    return;
+                }
+            };
+            (is_root, methods)
+        };
+        match methods {
+            Some((fields, methods)) => {
+                for field in fields.elements() {
+                    if let EntityInfo::Field(info) = field.borrow().kind() {
+                        info.default.iter().for_each(|default| self.visit_expr(default));
+                    }
+                }
+                for method in methods.elements() {
+                    if method.borrow().visibility() == Visibility::Public {
+                        self.reach(method);
+                    }
+                }
+            }
+            None if is_root => self.reach(entity),
+            None => {}
+        }
+    }
+
+    fn reach(&mut self, entity: &EntityRef) {
+        if self.reached.insert(entity.borrow().id()) {
+            self.pending.push(entity.clone());
+        }
+    }
+
+    /// the body of a function reached and the defaults of its parameters.
+    fn visit_function(&mut self, entity: &EntityRef) {
+        let params = match entity.borrow().kind() {
+            EntityInfo::Function(info) => Some(info.params.clone()),
+            EntityInfo::AssociatedFunction(info) => Some(info.params.clone()),
+            _ => None,
+        };
+        for param in params.iter().flat_map(|params| params.elements()) {
+            if let EntityInfo::Param(info) = param.borrow().kind() {
+                info.default.iter().for_each(|default| self.visit_expr(default));
+            }
+        }
+        walk_item(self, entity);
+    }
+
+    fn reach_method(&mut self, struct_entity: &EntityRef, name: &str) {
+        let method = match struct_entity.borrow().kind() {
+            EntityInfo::Structure(info) => info.methods.get(name).cloned(),
+            _ => None,
+        };
+        if let Some(method) = method {
+            self.reach(&method);
+        }
+    }
+
+    fn is_root(&self, entity: &Entity) -> bool {
+        entity.visibility() == Visibility::Public
+            || self.entry == Some(entity.id())
+            || entity.name().starts_with(BENCH_PREFIX)
+    }
+
+    fn keeps(&self, entity: &EntityRef) -> bool {
+        let entity = entity.borrow();
+        match entity.kind() {
+            EntityInfo::Function(info) if !info.local => {
+                self.is_root(&entity) || self.reached.contains(&entity.id())
+            }
+            _ => true,
+        }
+    }
+
+    fn prune_methods(&self, entity: &EntityRef) {
+        let methods = match entity.borrow().kind() {
+            EntityInfo::Structure(info) => info.methods.clone(),
+            _ => return,
+        };
+        let keep = |method: &EntityRef| {
+            let method = method.borrow();
+            method.visibility() == Visibility::Public || self.reached.contains(&method.id())
+        };
+        if methods.elements().iter().all(keep) {
+            return;
+        }
+        let mut pruned = methods.as_ref().clone();
+        pruned.retain(keep);
+        for (index, method) in pruned.elements().iter().enumerate() {
+            if let EntityInfo::AssociatedFunction(info) = method.borrow_mut().kind_mut() {
+                info.index = index;
+            }
+        }
+        entity.borrow_mut().as_struct_mut().methods = Rc::new(pruned);
+    }
+}
+
+impl MirVisitor for Reachable {
+    fn visit_name(&mut self, _expr: &HirExpr, entity: &EntityRef) {
+        if entity.borrow().is_function() {
+            self.reach(entity);
+        }
+    }
+
+    fn visit_method(&mut self, _expr: &HirExpr, method: &MethodExpr) {
+        self.reach_method(&method.struct_entity, &method.name);
+        method.actuals.iter().for_each(|actual| self.visit_expr(actual));
+    }
+
+    fn visit_associated_function(&mut self, _expr: &HirExpr, function: &AssociatedFunctionExpr) {
+        self.reach_method(&function.struct_entity, &function.name);
+        function.actuals.iter().for_each(|actual| self.visit_expr(actual));
+    }
+}
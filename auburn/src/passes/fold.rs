@@ -0,0 +1,192 @@
+use std::rc::Rc;
+
+use oxide::vm::Division;
+
+use crate::error::Error;
+use crate::ir::ast::{BinaryOp, UnaryOp};
+use crate::ir::hir::{
+    AddressMode, HirExpr, HirExprInner, HirExprKind, HirExprPtr, HirFile, MirNode, ResultMeta,
+};
+use crate::ir::visit::MirRewriter;
+use crate::passes::{MirPass, PassContext};
+use crate::types::Type;
+
+/// replaces arithmetic and comparisons of integer literals, comparisons and negations of bools
+/// and concatenations and comparisons of strings with their result. An operation that would
+/// overflow at runtime is left for the runtime, a division by a constant zero is an error.
+/// Divisions are folded by the functions the vm divides with, a folded `/` or `%` that rounded
+/// toward zero where rounding down would not have is a warning. The branches of an if whose
+/// condition folds are removed by `DeadCode`.
+#[derive(Default)]
+pub struct ConstantFolding {
+    warnings: Vec<Error>,
+    errors: Vec<Error>,
+}
+
+impl MirPass for ConstantFolding {
+    fn name(&self) -> &str {
+        "constant-folding"
+    }
+
+    fn run(&mut self, file: &mut HirFile, cx: &mut PassContext) {
+        self.rewrite_file(file);
+        self.warnings.drain(..).for_each(|warning| cx.warn(warning));
+        self.errors.drain(..).for_each(|err| cx.error(err));
+    }
+}
+
+impl MirRewriter for ConstantFolding {
+    fn finish_expr(&mut self, expr: HirExprPtr) -> HirExprPtr {
+        if let Some(err) = divides_by_zero(&expr) {
+            self.errors.push(err.with_position(expr.position()));
+            return expr;
+        }
+        match fold(&expr) {
+            Some(kind) => {
+                if let Some(warning) = truncated(&expr, &kind) {
+                    self.warnings.push(warning.with_position(expr.position()));
+                }
+                // a string is used by its address like any other string literal.
+                let address_mode = match kind {
+                    HirExprKind::String(_) => AddressMode::Address,
+                    _ => AddressMode::Value,
+                };
+                let inner = HirExprInner::new(address_mode, ResultMeta::literal(), kind);
+                Rc::new(expr.with_inner(inner))
+            }
+            None => expr,
+        }
+    }
+}
+
+fn fold(expr: &HirExpr) -> Option<HirExprKind> {
+    match expr.inner().kind() {
+        HirExprKind::Binary(binary) => {
+            match (binary.left.inner().kind(), binary.right.inner().kind()) {
+                (HirExprKind::Integer(left), HirExprKind::Integer(right)) => {
+                    fold_integers(binary.op, *left, *right, expr)
+                }
+                (HirExprKind::Bool(left), HirExprKind::Bool(right)) => match binary.op {
+                    BinaryOp::EqualEqual => Some(HirExprKind::Bool(left == right)),
+                    BinaryOp::BangEqual => Some(HirExprKind::Bool(left != right)),
+                    _ => None,
+                },
+                (HirExprKind::String(left), HirExprKind::String(right)) => match binary.op {
+                    BinaryOp::Plus => Some(HirExprKind::String(format!("{}{}", left, right))),
+                    BinaryOp::EqualEqual => Some(HirExprKind::Bool(left == right)),
+                    BinaryOp::BangEqual => Some(HirExprKind::Bool(left != right)),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        HirExprKind::Unary(unary) => match (unary.op, unary.operand.inner().kind()) {
+            (UnaryOp::Minus, HirExprKind::Integer(value)) => integer(value.checked_neg(), expr),
+            (UnaryOp::Bang, HirExprKind::Bool(value)) => Some(HirExprKind::Bool(!value)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// the error of dividing an integer by a divisor that is the literal zero.
+fn divides_by_zero(expr: &HirExpr) -> Option<Error> {
+    let binary = match expr.inner().kind() {
+        HirExprKind::Binary(binary) => binary,
+        _ => return None,
+    };
+    let divides = matches!(
+        binary.op,
+        BinaryOp::Slash | BinaryOp::Percent | BinaryOp::DivFloor | BinaryOp::ModFloor
+    );
+    match binary.right.inner().kind() {
+        HirExprKind::Integer(0) if divides => Some(Error::division_by_zero(binary.op)),
+        _ => None,
+    }
+}
+
+fn fold_integers(op: BinaryOp, left: i64, right: i64, expr: &HirExpr) -> Option<HirExprKind> {
+    let value = match op {
+        BinaryOp::Less => return Some(HirExprKind::Bool(left < right)),
+        BinaryOp::Greater => return Some(HirExprKind::Bool(left > right)),
+        BinaryOp::LessEq => return Some(HirExprKind::Bool(left <= right)),
+        BinaryOp::GreaterEq => return Some(HirExprKind::Bool(left >= right)),
+        BinaryOp::EqualEqual => return Some(HirExprKind::Bool(left == right)),
+        BinaryOp::BangEqual => return Some(HirExprKind::Bool(left != right)),
+        _ => integer_operation(op, left, right),
+    };
+    integer(value, expr)
+}
+
+/// the integer an arithmetic or bitwise operation of two integers computes, none when it
+/// overflows an i64 or divides by zero. The constants of a program are evaluated with it too.
+pub(crate) fn integer_operation(op: BinaryOp, left: i64, right: i64) -> Option<i64> {
+    match op {
+        BinaryOp::Plus => left.checked_add(right),
+        BinaryOp::Minus => left.checked_sub(right),
+        BinaryOp::Astrick => left.checked_mul(right),
+        BinaryOp::Slash => left.checked_div(right),
+        BinaryOp::Percent => left.checked_modulo(right),
+        BinaryOp::DivFloor => left.checked_div_floor(right),
+        BinaryOp::ModFloor => left.checked_mod_floor(right),
+        BinaryOp::Ampersand => Some(left & right),
+        BinaryOp::Pipe => Some(left | right),
+        BinaryOp::Caret => Some(left ^ right),
+        BinaryOp::LessLess
+        | BinaryOp::GreaterGreater
+        | BinaryOp::Less
+        | BinaryOp::Greater
+        | BinaryOp::LessEq
+        | BinaryOp::GreaterEq
+        | BinaryOp::EqualEqual
+        | BinaryOp::BangEqual => None,
+    }
+}
+
+/// the value when it fits the type of expr.
+fn integer(value: Option<i64>, expr: &HirExpr) -> Option<HirExprKind> {
+    value
+        .filter(|value| Type::inner(expr.ty()).kind().fits_integer(*value))
+        .map(HirExprKind::Integer)
+}
+
+/// the warning of a division of literals that was folded to a truncated result, the same
+/// division rounded down is a different number.
+fn truncated(expr: &HirExpr, folded: &HirExprKind) -> Option<Error> {
+    let binary = match expr.inner().kind() {
+        HirExprKind::Binary(binary) => binary,
+        _ => return None,
+    };
+    let floor = match binary.op {
+        BinaryOp::Slash => BinaryOp::DivFloor,
+        BinaryOp::Percent => BinaryOp::ModFloor,
+        _ => return None,
+    };
+    let (left, right, value) = match (
+        binary.left.inner().kind(),
+        binary.right.inner().kind(),
+        folded,
+    ) {
+        (HirExprKind::Integer(left), HirExprKind::Integer(right), HirExprKind::Integer(value)) => {
+            (*left, *right, *value)
+        }
+        _ => return None,
+    };
+    let floored_value = match floor {
+        BinaryOp::DivFloor => left.checked_div_floor(right)?,
+        _ => left.checked_mod_floor(right)?,
+    };
+    if floored_value == value {
+        return None;
+    }
+    Some(Error::truncated_division(
+        format!("{} {} {}", left, binary.op, right),
+        value,
+        binary.op,
+        match left < 0 {
+            true => format!("({}).{}({})", left, floor, right),
+            false => format!("{}.{}({})", left, floor, right),
+        },
+        floored_value,
+    ))
+}
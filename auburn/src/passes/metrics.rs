@@ -0,0 +1,380 @@
+use std::fmt::{Display, Formatter, Write};
+use std::str::FromStr;
+
+use crate::analysis::{EntityInfo, EntityRef};
+use crate::error::Error;
+use crate::ir::hir::{
+    BlockExpr, Desugaring, HirExpr, HirExprKind, HirFile, HirStmt, HirStmtKind, IfExpr,
+    IfExprBranch, MatchExpr, MirNode,
+};
+use crate::ir::visit::{walk_expr, walk_item, walk_stmt, MirVisitor};
+use crate::passes::{MirPass, PassContext};
+
+/// the size and complexity of the body of a function as it was written. Code generated for a
+/// desugaring is not counted, a `for` loop counts like the `while` loop it could be written as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionMetrics {
+    /// the statements of the body, those of nested blocks included. A body that is an
+    /// expression is one statement.
+    pub statements: usize,
+    /// the most blocks a statement is nested in, the body itself is not counted.
+    pub depth: usize,
+    /// the parameters besides `self`.
+    pub params: usize,
+    /// one more than the number of branches, loops and optional exits of the body.
+    pub complexity: usize,
+}
+
+impl FunctionMetrics {
+    pub fn get(&self, metric: Metric) -> usize {
+        match metric {
+            Metric::Statements => self.statements,
+            Metric::Depth => self.depth,
+            Metric::Params => self.params,
+            Metric::Complexity => self.complexity,
+        }
+    }
+}
+
+/// a measure of a function that can be limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Statements,
+    Depth,
+    Params,
+    Complexity,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [
+        Metric::Statements,
+        Metric::Depth,
+        Metric::Params,
+        Metric::Complexity,
+    ];
+
+    /// the name of the lint limiting the metric.
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Statements => "max-statements",
+            Metric::Depth => "max-depth",
+            Metric::Params => "max-params",
+            Metric::Complexity => "max-complexity",
+        }
+    }
+
+    /// what is counted, as it reads after a number.
+    fn measure(self) -> &'static str {
+        match self {
+            Metric::Statements => "statements",
+            Metric::Depth => "nested blocks",
+            Metric::Params => "parameters",
+            Metric::Complexity => "complexity",
+        }
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// a limit on a metric of every function, written `max-depth=4`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lint {
+    pub metric: Metric,
+    pub limit: usize,
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let names = || {
+            let names = Metric::ALL
+                .iter()
+                .map(|metric| metric.name())
+                .collect::<Vec<_>>();
+            names.join(", ")
+        };
+        let (name, limit) = s.split_once('=').ok_or_else(|| {
+            format!(
+                "expected a lint and its limit like 'max-depth=4', found '{}'",
+                s
+            )
+        })?;
+        let metric = Metric::ALL
+            .iter()
+            .copied()
+            .find(|metric| metric.name() == name)
+            .ok_or_else(|| format!("unknown lint '{}', expected one of {}", name, names()))?;
+        let limit = limit
+            .parse()
+            .map_err(|_| format!("the limit of '{}' is not a number, found '{}'", name, limit))?;
+        Ok(Self { metric, limit })
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.metric, self.limit)
+    }
+}
+
+/// the limits of the lints that are enabled, every lint is off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    limits: [Option<usize>; 4],
+}
+
+impl Limits {
+    pub fn with(mut self, lint: Lint) -> Self {
+        self.limits[lint.metric as usize] = Some(lint.limit);
+        self
+    }
+
+    pub fn limit(&self, metric: Metric) -> Option<usize> {
+        self.limits[metric as usize]
+    }
+
+    pub fn enabled(&self) -> Vec<Lint> {
+        Metric::ALL
+            .iter()
+            .filter_map(|metric| {
+                self.limit(*metric).map(|limit| Lint {
+                    metric: *metric,
+                    limit,
+                })
+            })
+            .collect()
+    }
+}
+
+/// measures every function and method of a program, the metrics are stored with the function.
+/// A function over the limit of a lint is a warning.
+#[derive(Default)]
+pub struct Metrics {
+    limits: Limits,
+    /// the metrics of every function measured by the last run, in the order they are declared.
+    report: Vec<(String, FunctionMetrics)>,
+}
+
+impl Metrics {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            report: vec![],
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn report(&self) -> &[(String, FunctionMetrics)] {
+        self.report.as_slice()
+    }
+
+    /// the metrics of every function whether it is over a limit or not.
+    pub fn table(&self) -> String {
+        let mut out = format!(
+            "{:<32} {:>10} {:>6} {:>6} {:>10}\n",
+            "function", "statements", "depth", "params", "complexity"
+        );
+        for (name, metrics) in &self.report {
+            writeln!(
+                out,
+                "{:<32} {:>10} {:>6} {:>6} {:>10}",
+                name, metrics.statements, metrics.depth, metrics.params, metrics.complexity
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    fn measure_item(&mut self, entity: &EntityRef, owner: Option<&str>, cx: &mut PassContext) {
+        let (body, params) = match entity.borrow().kind() {
+            EntityInfo::Function(info) => (info.body.clone(), info.params.clone()),
+            EntityInfo::AssociatedFunction(info) => (info.body.clone(), info.params.clone()),
+            EntityInfo::Structure(info) => {
+                let name = entity.borrow().name().to_string();
+                for method in info.methods.elements() {
+                    self.measure_item(method, Some(name.as_str()), cx);
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        let mut measure = Measure::default();
+        // a body without a branch has a single path through it.
+        measure.metrics.complexity = 1;
+        match body.inner().kind() {
+            HirExprKind::Block(block) if block.function_block => {
+                block.stmts.iter().for_each(|stmt| measure.visit_stmt(stmt))
+            }
+            _ => {
+                measure.metrics.statements += 1;
+                measure.visit_expr(&body);
+            }
+        }
+        measure.metrics.params = params
+            .elements()
+            .iter()
+            .filter(|param| !matches!(param.borrow().kind(), EntityInfo::SelfParam { .. }))
+            .count();
+        let metrics = measure.metrics;
+
+        let mut entity = entity.borrow_mut();
+        let name = match owner {
+            Some(owner) => format!("{}.{}", owner, entity.name()),
+            None => entity.name().to_string(),
+        };
+        for lint in self.limits.enabled() {
+            let value = metrics.get(lint.metric);
+            if value > lint.limit {
+                let warning = Error::metric_exceeded(
+                    name.as_str(),
+                    lint.metric.name(),
+                    lint.metric.measure(),
+                    value,
+                    lint.limit,
+                );
+                cx.warn(warning.with_position(body.position()));
+            }
+        }
+        match entity.kind_mut() {
+            EntityInfo::Function(info) => info.metrics = Some(metrics),
+            EntityInfo::AssociatedFunction(info) => info.metrics = Some(metrics),
+            _ => unreachable!(),
+        }
+        self.report.push((name, metrics));
+    }
+}
+
+impl MirPass for Metrics {
+    fn name(&self) -> &str {
+        "metrics"
+    }
+
+    fn run(&mut self, file: &mut HirFile, cx: &mut PassContext) {
+        self.report.clear();
+        for stmt in file.stmts() {
+            if let HirStmtKind::Item(entity) = stmt.inner() {
+                self.measure_item(entity, None, cx);
+            }
+        }
+    }
+}
+
+/// walks the body of a function. Code generated for a construct counts like the construct, the
+/// node it is replaced by is counted and what is generated under it is not.
+#[derive(Default)]
+struct Measure {
+    metrics: FunctionMetrics,
+    depth: usize,
+    /// whether the node being visited is under a generated node, user written code inside of
+    /// a desugaring is not.
+    in_generated: bool,
+}
+
+impl Measure {
+    /// visits a node generated by a desugaring or written by the user. visit is given the
+    /// desugaring when the node is the root of the code generated for a construct.
+    fn enter<T>(
+        &mut self,
+        generated: Option<Desugaring>,
+        visit: impl FnOnce(&mut Self, Option<Desugaring>) -> T,
+    ) -> T {
+        let root = generated.filter(|_| !self.in_generated);
+        let outer = std::mem::replace(&mut self.in_generated, generated.is_some());
+        let result = visit(self, root);
+        self.in_generated = outer;
+        result
+    }
+}
+
+impl MirVisitor for Measure {
+    /// nested functions are measured on their own.
+    fn visit_item(&mut self, entity: &EntityRef) {
+        let nested = matches!(
+            entity.borrow().kind(),
+            EntityInfo::Function(_) | EntityInfo::AssociatedFunction(_) | EntityInfo::Structure(_)
+        );
+        if !nested {
+            walk_item(self, entity)
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &HirStmt) {
+        self.enter(stmt.generated_by(), |measure, root| {
+            if root.is_some() || !measure.in_generated {
+                measure.metrics.statements += 1;
+            }
+            walk_stmt(measure, stmt)
+        })
+    }
+
+    fn visit_expr(&mut self, expr: &HirExpr) {
+        self.enter(expr.generated_by(), |measure, root| {
+            let branches = match (root, expr.inner().kind()) {
+                // a loop lowered to a loop exits from inside of it.
+                (Some(Desugaring::ForLoop | Desugaring::WhileLet), _) => true,
+                (Some(_), _) => false,
+                (None, _) if measure.in_generated => false,
+                (
+                    None,
+                    HirExprKind::Loop(_)
+                    | HirExprKind::While(_)
+                    | HirExprKind::Coalesce(_)
+                    | HirExprKind::OptionalChain(_),
+                ) => true,
+                (None, _) => false,
+            };
+            if branches {
+                measure.metrics.complexity += 1;
+            }
+            walk_expr(measure, expr)
+        })
+    }
+
+    fn visit_block(&mut self, _expr: &HirExpr, block: &BlockExpr) {
+        let nested = !self.in_generated;
+        if nested {
+            self.depth += 1;
+            self.metrics.depth = self.metrics.depth.max(self.depth);
+        }
+        block.stmts.iter().for_each(|stmt| self.visit_stmt(stmt));
+        if nested {
+            self.depth -= 1;
+        }
+    }
+
+    fn visit_if(&mut self, _expr: &HirExpr, if_expr: &IfExpr) {
+        let in_generated = self.in_generated;
+        for branch in &if_expr.branches {
+            match branch {
+                IfExprBranch::Conditional { cond, body, .. } => {
+                    if !in_generated {
+                        self.metrics.complexity += 1;
+                    }
+                    self.visit_expr(cond);
+                    self.visit_expr(body);
+                }
+                IfExprBranch::Unconditional { body } => self.visit_expr(body),
+            }
+        }
+    }
+
+    /// an arm is a branch unless its pattern matches every value, like the else of an if.
+    fn visit_match(&mut self, _expr: &HirExpr, match_expr: &MatchExpr) {
+        self.visit_expr(&match_expr.value);
+        for arm in &match_expr.arms {
+            if !self.in_generated && !arm.pattern.is_irrefutable() {
+                self.metrics.complexity += 1;
+            }
+            self.visit_expr(&arm.body);
+        }
+    }
+}
@@ -0,0 +1,732 @@
+use crate::error::Error;
+use crate::ir::hir::HirFile;
+use crate::types::TypeMap;
+use crate::utils::Diagnostics;
+
+pub use dead_code::DeadCode;
+pub use fold::ConstantFolding;
+pub(crate) use fold::integer_operation;
+pub use metrics::{FunctionMetrics, Limits, Lint, Metric, Metrics};
+pub(crate) use monomorphize::{is_generic, Instances, Monomorphizer};
+
+mod dead_code;
+mod fold;
+mod metrics;
+mod monomorphize;
+
+/// what a pass can use besides the program, the types of the program and where its warnings
+/// and errors are reported.
+pub struct PassContext<'a> {
+    type_map: &'a mut TypeMap,
+    diagnostics: &'a mut Diagnostics,
+    errors: Vec<Error>,
+}
+
+impl<'a> PassContext<'a> {
+    pub fn new(type_map: &'a mut TypeMap, diagnostics: &'a mut Diagnostics) -> Self {
+        Self {
+            type_map,
+            diagnostics,
+            errors: vec![],
+        }
+    }
+
+    pub fn type_map(&mut self) -> &mut TypeMap {
+        self.type_map
+    }
+
+    /// reported with the warnings of the program.
+    pub fn warn(&mut self, warning: Error) {
+        self.diagnostics.push(warning);
+    }
+
+    /// a mistake found by a pass, the program is not built.
+    pub fn error(&mut self, err: Error) {
+        self.errors.push(err);
+    }
+
+    /// the errors reported so far, ordered like the errors of a check.
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        let mut errors = Diagnostics::new();
+        errors.extend(self.errors.drain(..));
+        errors.finish()
+    }
+}
+
+/// a pass over a checked program before it is built. A pass looks at the program with a
+/// `MirVisitor` or changes it with a `MirRewriter`. The value of a `black_box` is an opaque
+/// use, a pass may rewrite inside it but must not replace it by what it knows of the value.
+pub trait MirPass {
+    fn name(&self) -> &str;
+
+    fn run(&mut self, file: &mut HirFile, cx: &mut PassContext);
+}
+
+/// the passes run over a checked program. Passes added by a tool run after constants are
+/// folded, so they see the folded program, and before dead code is removed so what they leave
+/// unused is removed. Functions are measured last.
+pub struct Pipeline {
+    fold: ConstantFolding,
+    passes: Vec<Box<dyn MirPass>>,
+    dead_code: DeadCode,
+    metrics: Metrics,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self {
+            fold: ConstantFolding::default(),
+            passes: vec![],
+            dead_code: DeadCode::default(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// the limits of the metrics lints, every lint is off by default.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.metrics.set_limits(limits);
+    }
+
+    /// the functions measured by the last run.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// passes run in the order they are added.
+    pub fn add_pass(&mut self, pass: Box<dyn MirPass>) {
+        self.passes.push(pass);
+    }
+
+    /// the warnings of the passes, or their errors when a pass found a mistake.
+    pub fn run(
+        &mut self,
+        file: &mut HirFile,
+        type_map: &mut TypeMap,
+    ) -> Result<Vec<Error>, Vec<Error>> {
+        let mut diagnostics = Diagnostics::new();
+        let mut cx = PassContext::new(type_map, &mut diagnostics);
+        self.fold.run(file, &mut cx);
+        for pass in self.passes.iter_mut() {
+            pass.run(file, &mut cx);
+        }
+        self.dead_code.run(file, &mut cx);
+        self.metrics.run(file, &mut cx);
+        let errors = cx.take_errors();
+        if errors.is_empty() {
+            Ok(diagnostics.finish())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FunctionMetrics, Limits, Lint, Pipeline};
+    use crate::analysis::{Analysis, BuildInfo, EntityInfo, LANGUAGE_VERSION};
+    use crate::code_gen::CodeGen;
+    use crate::ir::hir::{HirExprKind, HirExprPtr, HirFile, HirStmtKind, IfExprBranch};
+    use crate::syntax::Parser;
+    use crate::system::{File, FileMap};
+    use crate::LanguageMode;
+    use oxide::{Value, Vm};
+
+    fn optimize(source: &str) -> HirFile {
+        let (file, warnings) = lint(source, &[]);
+        assert!(warnings.is_empty());
+        file
+    }
+
+    /// the program built as build_info describes, it has no warnings.
+    fn optimize_with(source: &str, build_info: BuildInfo) -> HirFile {
+        let (mut analysis, mut hir_file) = checked_with(source, build_info);
+        let warnings = Pipeline::new()
+            .run(&mut hir_file, analysis.type_map_mut())
+            .unwrap();
+        assert!(warnings.is_empty());
+        hir_file
+    }
+
+    /// the program and its warnings with the lints enabled.
+    fn lint(source: &str, lints: &[&str]) -> (HirFile, Vec<String>) {
+        let (mut analysis, mut hir_file) = checked(source);
+        let limits = lints.iter().fold(Limits::default(), |limits, lint| {
+            limits.with(lint.parse::<Lint>().unwrap())
+        });
+        let mut pipeline = Pipeline::new();
+        pipeline.set_limits(limits);
+        let warnings = pipeline.run(&mut hir_file, analysis.type_map_mut()).unwrap();
+        let warnings = warnings.iter().map(ToString::to_string).collect();
+        (hir_file, warnings)
+    }
+
+    /// the errors of the passes over a program they reject.
+    fn pass_errors(source: &str) -> Vec<String> {
+        let (mut analysis, mut hir_file) = checked(source);
+        let errors = Pipeline::new()
+            .run(&mut hir_file, analysis.type_map_mut())
+            .unwrap_err();
+        errors.iter().map(ToString::to_string).collect()
+    }
+
+    fn checked(source: &str) -> (Analysis, HirFile) {
+        checked_with(source, BuildInfo::default())
+    }
+
+    fn checked_with(source: &str, build_info: BuildInfo) -> (Analysis, HirFile) {
+        let file = File::raw_test(source.to_string());
+        let mut parser = Parser::new(&file);
+        let parsed = parser.init().and_then(|_| parser.parse_file()).unwrap();
+        let mut analysis = Analysis::new();
+        analysis.set_build_info(build_info);
+        let hir_file = analysis.check(parsed, LanguageMode::Default).unwrap();
+        (analysis, hir_file)
+    }
+
+    fn metrics(file: &HirFile, name: &str) -> FunctionMetrics {
+        let entity = file.find_entity_by_name(name).unwrap();
+        let entity = entity.borrow();
+        match entity.kind() {
+            EntityInfo::Function(function) => function.metrics.unwrap(),
+            _ => panic!("{} is not a function", name),
+        }
+    }
+
+    fn body(file: &HirFile, name: &str) -> HirExprKind {
+        let entity = file.find_entity_by_name(name).unwrap();
+        let entity = entity.borrow();
+        match entity.kind() {
+            EntityInfo::Function(function) => match function.body.inner().kind() {
+                HirExprKind::Block(block) if block.function_block => {
+                    match block.stmts.last().unwrap().inner() {
+                        HirStmtKind::Expr(expr) => expr.inner().kind().clone(),
+                        _ => panic!("{} does not end in an expression", name),
+                    }
+                }
+                kind => kind.clone(),
+            },
+            _ => panic!("{} is not a function", name),
+        }
+    }
+
+    fn run(file: &HirFile) -> Value {
+        run_with_traps(file, false).unwrap()
+    }
+
+    fn run_with_traps(file: &HirFile, traps: bool) -> Result<Value, String> {
+        let mut vm = Vm::new();
+        vm.set_overflow_traps(traps);
+        vm.set_code_gen(true);
+        let module = CodeGen::build(&FileMap::new(), file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        vm.run_module(module).map_err(|err| err.to_string())?;
+        Ok(*vm.top())
+    }
+
+    #[test]
+    fn test_constants_are_folded() {
+        let file = optimize(
+            "fn area() i32 = (2 * 3) + (10 / 4) - 1\n\
+            pub fn small() bool = 7 % 4 <= 3\n\
+            pub fn negative() i32 = -(4 - 9)\n\
+            pub fn greeting() string = \"hello, \" + \"world\"\n\
+            pub fn same() bool = \"a\" != \"a\"\n\
+            pub fn not() bool = !false\n\
+            pub fn wide() i32 = 2147483647 + 1\n\
+            fn main() i32 = area()\n",
+        );
+        assert!(matches!(body(&file, "area"), HirExprKind::Integer(7)));
+        assert!(matches!(body(&file, "small"), HirExprKind::Bool(true)));
+        assert!(matches!(body(&file, "negative"), HirExprKind::Integer(5)));
+        assert!(matches!(body(&file, "greeting"), HirExprKind::String(s) if s == "hello, world"));
+        assert!(matches!(body(&file, "same"), HirExprKind::Bool(false)));
+        assert!(matches!(body(&file, "not"), HirExprKind::Bool(true)));
+        // what overflows is left to trap at runtime.
+        assert!(matches!(body(&file, "wide"), HirExprKind::Binary(_)));
+        assert!(matches!(run(&file), Value::I32(7)));
+    }
+
+    #[test]
+    fn test_statements_after_leaving_a_block_are_removed_with_a_warning() {
+        let source = "fn first(x i32) i32 {\n    mut i = x\n    while i > 0 {\n        \
+            if i == 3 {\n            break\n            i = 0\n        }\n        \
+            i -= 1\n    }\n    return i\n    log.info(\"never\")\n    \
+            fn later() i32 = 2\n    i + 1\n}\n\
+            fn main() i32 = first(5)\n";
+        let (file, warnings) = lint(source, &[]);
+        let warning = |exit| {
+            format!(
+                "this statement is never run, the '{}' before it always leaves the block",
+                exit
+            )
+        };
+        assert_eq!(warnings, vec![warning("break"), warning("return")]);
+        let entity = file.find_entity_by_name("first").unwrap();
+        let stmts = match entity.borrow().kind() {
+            EntityInfo::Function(function) => match function.body.inner().kind() {
+                HirExprKind::Block(block) => block.stmts.clone(),
+                kind => panic!("unexpected body {:?}", kind),
+            },
+            _ => panic!("first is not a function"),
+        };
+        // the local function is kept, it could be used before it is declared.
+        let kinds = stmts
+            .iter()
+            .map(|stmt| match stmt.inner() {
+                HirStmtKind::Item(entity) => entity.borrow().name().to_owned(),
+                HirStmtKind::Expr(expr) => match expr.inner().kind() {
+                    HirExprKind::Return(_) => "return".to_owned(),
+                    _ => "expr".to_owned(),
+                },
+                _ => "stmt".to_owned(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["i", "expr", "return", "later"]);
+        assert!(matches!(run(&file), Value::I32(3)));
+    }
+
+    #[test]
+    fn test_unused_private_functions_are_removed() {
+        let file = optimize(
+            "struct Counter {\n    step i64\n\n    fn unused(self) i64 = self.step\n    \
+            fn twice(self) i64 = self.step * 2\n    \
+            pub fn advance(self, n i64) i64 = n + self.twice()\n    \
+            fn zero() i64 = 0\n    pub fn reset(self) i64 = 0\n}\n\
+            fn helper() i64 = 1\n\
+            fn only_helper() i64 = helper()\n\
+            fn scale(x i64) i64 = x * 2\n\
+            pub fn api() i64 = 3\n\
+            fn main() i64 {\n    let c = Counter { step: 3 }\n    c.advance(scale(2))\n}\n",
+        );
+        let names = file
+            .stmts()
+            .iter()
+            .filter_map(|stmt| match stmt.inner() {
+                HirStmtKind::Item(entity) => Some(entity.borrow().name().to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // a function only used by a function that is removed is removed with it.
+        assert_eq!(names, vec!["Counter", "scale", "api", "main"]);
+
+        let counter = file.find_entity_by_name("Counter").unwrap();
+        let methods = counter
+            .borrow()
+            .as_struct()
+            .methods
+            .elements()
+            .iter()
+            .map(|method| {
+                let method = method.borrow();
+                (method.name().to_owned(), method.as_associated_function().index)
+            })
+            .collect::<Vec<_>>();
+        let expected = [("twice", 0), ("advance", 1), ("reset", 2)];
+        let expected = expected.map(|(name, index)| (name.to_owned(), index));
+        assert_eq!(methods, expected);
+        let methods = counter.borrow().as_struct().methods.clone();
+        assert!(methods.get("unused").is_none() && methods.get("zero").is_none());
+        assert!(matches!(run(&file), Value::I64(10)));
+    }
+
+    #[test]
+    fn test_division_by_constant_zero_is_an_error() {
+        let errors = pass_errors(
+            "fn zero() i32 = 1 / 0\n\
+            fn rest(x i32) i32 = x % 0\n\
+            fn main() i32 = zero()\n",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "'/' divides by zero, its divisor is the constant 0",
+                "'%' divides by zero, its divisor is the constant 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reflection_of_types_is_folded_to_literals() {
+        let file = optimize(
+            "struct Point {\n    pub x i64\n    y i64\n    pub tag Option[string]\n}\n\
+            enum Color { Red, Green }\n\
+            fn fields() [(string, string)] = reflect.fields[Point]()\n\
+            pub fn variants() [string] = reflect.variants[Color]()\n\
+            fn main() i64 = fields().len()\n",
+        );
+        let strings = |elements: &[HirExprPtr]| {
+            elements
+                .iter()
+                .map(|element| match element.inner().kind() {
+                    HirExprKind::String(value) => value.clone(),
+                    _ => panic!("an element is not a string literal"),
+                })
+                .collect::<Vec<_>>()
+        };
+        // only the public fields, each with the name of its type.
+        let fields = match body(&file, "fields") {
+            HirExprKind::Array(array) => array
+                .elements
+                .iter()
+                .map(|field| match field.inner().kind() {
+                    HirExprKind::Tuple(tuple) => strings(&tuple.elements),
+                    _ => panic!("a field is not a tuple"),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("the reflection is not an array"),
+        };
+        assert_eq!(fields, [["x", "i64"], ["tag", "Option[string]"]]);
+        match body(&file, "variants") {
+            HirExprKind::Array(array) => assert_eq!(strings(&array.elements), ["Red", "Green"]),
+            _ => panic!("the reflection is not an array"),
+        }
+        assert!(matches!(run(&file), Value::I64(2)));
+    }
+
+    #[test]
+    fn test_divisions_fold_to_what_the_vm_computes() {
+        // (dividend, divisor, `/`, `%`, `div_floor`, `mod_floor`)
+        let cases: [(i64, i64, i64, i64, i64, i64); 6] = [
+            (7, 2, 3, 1, 3, 1),
+            (-7, 2, -3, -1, -4, 1),
+            (7, -2, -3, 1, -4, -1),
+            (-7, -2, 3, -1, 3, -1),
+            (6, -2, -3, 0, -3, 0),
+            (-6, -2, 3, 0, 3, 0),
+        ];
+        for (lhs, rhs, div, rem, div_floor, mod_floor) in cases {
+            let ops = [
+                ("a / b", div),
+                ("a % b", rem),
+                ("a.div_floor(b)", div_floor),
+                ("a.mod_floor(b)", mod_floor),
+            ];
+            for (op, expected) in ops {
+                let literal = op
+                    .replace('a', &format!("({})", lhs))
+                    .replace('b', &format!("({})", rhs));
+                let (folded, _) = lint(&format!("fn main() i32 = {}\n", literal), &[]);
+                let value = match body(&folded, "main") {
+                    HirExprKind::Integer(value) => value,
+                    kind => panic!("{} is not folded, found {:?}", literal, kind),
+                };
+                assert_eq!(value, expected, "{}", literal);
+                assert!(matches!(run(&folded), Value::I32(value) if value as i64 == expected));
+
+                let source = format!(
+                    "fn apply(a i32, b i32) i32 = {}\nfn main() i32 = apply({}, {})\n",
+                    op, lhs, rhs
+                );
+                let computed = run(&optimize(&source));
+                assert!(
+                    matches!(computed, Value::I32(value) if value as i64 == expected),
+                    "{} with a = {} and b = {} is {}, found {:?}",
+                    op,
+                    lhs,
+                    rhs,
+                    expected,
+                    computed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_truncated_divisions_of_literals_are_explained() {
+        let (_, warnings) = lint(
+            "fn quotient() i32 = (-7) / 2\n\
+            fn remainder() i32 = 7 % (-2)\n\
+            fn exact() i32 = (-6) / 2\n\
+            fn floored() i32 = (-7).div_floor(2)\n\
+            fn main() i32 = quotient() + remainder()\n",
+            &[],
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "'-7 / 2' is -3, '/' rounds toward zero; '(-7).div_floor(2)' rounds down to -4",
+                "'7 % -2' is 1, '%' rounds toward zero; '7.mod_floor(-2)' rounds down to -1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dividing_min_by_minus_one_wraps_unless_trapped() {
+        let source = "fn min() i64 {\n    let max i64 = 9223372036854775807\n    (-max) - 1\n}\n\
+            fn quotient() i64 = min() / (-1)\n\
+            fn floored() i64 = min().div_floor(-1)\n\
+            fn remainder() i64 = min() % (-1)\n\
+            pub fn literal() i32 = ((-2147483647) - 1).div_floor(-1)\n";
+        for (function, wrapped) in [
+            ("quotient()", Value::I64(i64::MIN)),
+            ("floored()", Value::I64(i64::MIN)),
+            ("literal()", Value::I32(i32::MIN)),
+        ] {
+            let ty = if function == "literal()" { "i32" } else { "i64" };
+            let file = optimize(&format!("{}fn main() {} = {}\n", source, ty, function));
+            let value = run_with_traps(&file, false).unwrap();
+            assert_eq!(value.to_string(), wrapped.to_string());
+            let err = run_with_traps(&file, true).err().unwrap();
+            assert_eq!(
+                err,
+                format!("integer overflow of a value of type {}", ty),
+                "{}",
+                function
+            );
+        }
+        // what overflows is not folded, the vm decides.
+        let file = optimize(&format!("{}fn main() i64 = remainder()\n", source));
+        assert!(matches!(body(&file, "literal"), HirExprKind::Binary(_)));
+        assert!(matches!(run_with_traps(&file, true), Ok(Value::I64(0))));
+    }
+
+    #[test]
+    fn test_branches_that_are_never_taken_are_removed() {
+        let file = optimize(
+            "fn pick(x i32) i32 = if 1 > 2 { 1 } elif x > 0 { 2 } elif 2 > 1 { 3 } else { 4 }\n\
+            fn main() i32 = pick(0)\n",
+        );
+        let branches = match body(&file, "pick") {
+            HirExprKind::If(if_expr) => if_expr.branches,
+            kind => panic!("unexpected body {:?}", kind),
+        };
+        assert_eq!(branches.len(), 2);
+        assert!(matches!(
+            branches[0],
+            IfExprBranch::Conditional { first: true, .. }
+        ));
+        assert!(matches!(branches[1], IfExprBranch::Unconditional { .. }));
+        assert!(matches!(run(&file), Value::I32(3)));
+    }
+
+    #[test]
+    fn test_version_comparisons_are_folded() {
+        let source = format!(
+            "fn gated() i32 = if sys.language_version() == \"{}\" {{ 1 }} else {{ 2 }}\n\
+            fn old() i32 = if sys.language_version() == \"0.0.1\" {{ 1 }} else {{ 2 }}\n\
+            pub fn optimized() bool = sys.opt_level() > 0\n\
+            pub fn stripped() bool = !sys.debug_info()\n\
+            fn main() i32 = gated() + old()\n",
+            LANGUAGE_VERSION
+        );
+        let file = optimize(&source);
+        // the comparison folds and only the branch taken is left.
+        let taken = |name: &str| match body(&file, name) {
+            HirExprKind::If(if_expr) => match if_expr.branches.as_slice() {
+                [IfExprBranch::Unconditional { body }] => match body.inner().kind() {
+                    HirExprKind::Block(block) => match block.stmts.last().unwrap().inner() {
+                        HirStmtKind::Expr(expr) => expr.inner().kind().clone(),
+                        _ => panic!("{} does not end in an expression", name),
+                    },
+                    kind => panic!("unexpected branch {:?}", kind),
+                },
+                branches => panic!("{} branches are left", branches.len()),
+            },
+            kind => panic!("unexpected body {:?}", kind),
+        };
+        assert!(matches!(taken("gated"), HirExprKind::Integer(1)));
+        assert!(matches!(taken("old"), HirExprKind::Integer(2)));
+        assert!(matches!(body(&file, "optimized"), HirExprKind::Bool(false)));
+        assert!(matches!(body(&file, "stripped"), HirExprKind::Bool(false)));
+        assert!(matches!(run(&file), Value::I32(3)));
+    }
+
+    #[test]
+    fn test_black_box_is_opaque_to_the_passes() {
+        let file = optimize(
+            "fn product() i32 = black_box(2) * 3\n\
+            fn pick() i32 = if black_box(1 > 2) { 1 } else { 2 }\n\
+            fn inner() i32 = black_box(2 * 3)\n\
+            fn discarded() i32 {\n    black_box(4 * 5)\n    1\n}\n\
+            fn main() i32 = product() + pick() + inner() + discarded()\n",
+        );
+        // nothing is folded through the value.
+        assert!(matches!(body(&file, "product"), HirExprKind::Binary(_)));
+        let branches = match body(&file, "pick") {
+            HirExprKind::If(if_expr) => if_expr.branches,
+            kind => panic!("unexpected body {:?}", kind),
+        };
+        assert_eq!(branches.len(), 2);
+        assert!(matches!(
+            &branches[0],
+            IfExprBranch::Conditional { cond, .. }
+                if matches!(cond.inner().kind(), HirExprKind::BlackBox(_))
+        ));
+        // what computes the value is still folded.
+        assert!(matches!(
+            body(&file, "inner"),
+            HirExprKind::BlackBox(value) if matches!(value.inner().kind(), HirExprKind::Integer(6))
+        ));
+        assert!(matches!(run(&file), Value::I32(15)));
+    }
+
+    #[test]
+    fn test_arms_that_are_never_taken_are_removed_with_a_warning() {
+        let (file, warnings) = lint(
+            "fn pick(x i32) i32 = match x {\n    1 => 10,\n    n => n,\n    2 => 20,\n    1 => 30,\n}\n\
+            fn flip(b bool) i32 = match b { true => 1, false => 2, _ => 3 }\n\
+            fn main() i32 = pick(2) + flip(false)\n",
+            &[],
+        );
+        let message = "this arm is never taken, the arms before it match every value it matches";
+        // the three arms are one warning, the others are counted in a note of the first.
+        assert_eq!(warnings, vec![message]);
+        let arms = match body(&file, "pick") {
+            HirExprKind::Match(match_expr) => match_expr.arms,
+            kind => panic!("unexpected body {:?}", kind),
+        };
+        assert_eq!(arms.len(), 2);
+        assert_eq!(metrics(&file, "pick").complexity, 2);
+        assert!(matches!(run(&file), Value::I32(4)));
+    }
+
+    #[test]
+    fn test_unreachable_branches_are_removed_above_level_0() {
+        let source = "fn sign(x i32) i32 = if x > 0 { 1 } elif x < 0 { -1 } \
+            else { unreachable() }\n\
+            fn double(x i32) i32 {\n    if x < 0 {\n        unreachable()\n    }\n    x * 2\n}\n\
+            fn pick(x i32) i32 = match x {\n    0 => 10,\n    _ => unreachable(),\n}\n\
+            fn main() i32 = sign(0) + double(-2) + pick(0)\n";
+        let trusted = BuildInfo {
+            opt_level: 1,
+            ..BuildInfo::default()
+        };
+        // the code after a guard is not reported as never run.
+        let file = optimize_with(source, trusted);
+        let branches = match body(&file, "sign") {
+            HirExprKind::If(if_expr) => if_expr.branches,
+            kind => panic!("unexpected body {:?}", kind),
+        };
+        assert_eq!(branches.len(), 2);
+        assert!(matches!(branches[1], IfExprBranch::Unconditional { .. }));
+        let arms = match body(&file, "pick") {
+            HirExprKind::Match(match_expr) => match_expr.arms,
+            kind => panic!("unexpected body {:?}", kind),
+        };
+        assert_eq!(arms.len(), 1);
+        // the guard has no branch left.
+        let entity = file.find_entity_by_name("double").unwrap();
+        let guard = match entity.borrow().kind() {
+            EntityInfo::Function(function) => match function.body.inner().kind() {
+                HirExprKind::Block(block) => match block.stmts[0].inner() {
+                    HirStmtKind::Expr(expr) => expr.inner().kind().clone(),
+                    stmt => panic!("unexpected statement {:?}", stmt),
+                },
+                kind => panic!("unexpected body {:?}", kind),
+            },
+            _ => panic!("double is not a function"),
+        };
+        assert!(matches!(guard, HirExprKind::If(if_expr) if if_expr.branches.is_empty()));
+        // the program is built trusting the code is never reached.
+        assert!(matches!(run(&file), Value::I32(5)));
+
+        let checked = BuildInfo {
+            checked_unreachable: true,
+            ..trusted
+        };
+        let file = optimize_with(source, checked);
+        assert!(matches!(
+            body(&file, "sign"),
+            HirExprKind::If(if_expr) if if_expr.branches.len() == 3
+        ));
+        assert_eq!(
+            run_with_traps(&file, false).unwrap_err(),
+            "entered unreachable code at test:1:62"
+        );
+    }
+
+    #[test]
+    fn test_lints_report_functions_over_their_limits() {
+        let source = "fn short(a i32, b i32, c i32) i32 {\n\
+            \x20   let x = a + b\n    let y = x + c\n    let z = y + 1\n    z\n}\n\
+            fn long(a i32, b i32, c i32, d i32) i32 {\n\
+            \x20   let x = a + b\n    let y = x + c\n    let z = y + d\n    let w = z + 1\n    w\n}\n\
+            fn shallow(x i32) i32 {\n    if x > 0 {\n        if x > 1 { 2 } else { 1 }\n    } else { 0 }\n}\n\
+            fn deep(x i32) i32 {\n    if x > 0 {\n        if x > 1 {\n            if x > 2 { 3 } else { 2 }\n\
+            \x20       } else { 1 }\n    } else { 0 }\n}\n\
+            pub fn simple(x i32) i32 = if x > 0 { 1 } elif x < 0 { 2 } else { 0 }\n\
+            pub fn branchy(x i32) i32 = if x > 0 { 1 } elif x < 0 { 2 } \
+            elif x == 0 { 3 } else { 0 }\n\
+            fn main() i32 = short(1, 2, 3) + long(1, 2, 3, 4) + deep(1) + shallow(1)\n";
+
+        let (file, warnings) = lint(source, &[]);
+        assert!(warnings.is_empty());
+        let measured = |name| {
+            let metrics = metrics(&file, name);
+            (
+                metrics.statements,
+                metrics.depth,
+                metrics.params,
+                metrics.complexity,
+            )
+        };
+        assert_eq!(measured("short"), (4, 0, 3, 1));
+        assert_eq!(measured("long"), (5, 0, 4, 1));
+        // the value of a branch is a statement of its block.
+        assert_eq!(measured("shallow"), (5, 2, 1, 3));
+        assert_eq!(measured("deep"), (7, 3, 1, 4));
+        assert_eq!(measured("simple"), (4, 1, 1, 3));
+        assert_eq!(measured("branchy"), (5, 1, 1, 4));
+
+        let lints = [
+            "max-statements=5",
+            "max-depth=2",
+            "max-params=3",
+            "max-complexity=3",
+        ];
+        let (_, warnings) = lint(source, &lints);
+        assert_eq!(
+            warnings,
+            vec![
+                "function 'long' has 4 parameters, the limit is 3 [max-params]",
+                "function 'deep' has 7 statements, the limit is 5 [max-statements]",
+                "function 'deep' has 3 nested blocks, the limit is 2 [max-depth]",
+                "function 'deep' has 4 complexity, the limit is 3 [max-complexity]",
+                "function 'branchy' has 4 complexity, the limit is 3 [max-complexity]",
+            ]
+        );
+        assert!("max-lines=3".parse::<Lint>().is_err());
+        assert!("max-depth".parse::<Lint>().is_err());
+    }
+
+    #[test]
+    fn test_generated_code_counts_like_what_was_written() {
+        let source = "struct Range {\n\
+            \x20   pub current i64\n\
+            \x20   pub end i64\n\
+            \x20   fn next(mut self) Option[i64] {\n\
+            \x20       mut item Option[i64] = none\n\
+            \x20       if self.current != self.end {\n\
+            \x20           item = self.current\n\
+            \x20           self.current += 1\n\
+            \x20       } else {\n\
+            \x20           item = none\n\
+            \x20       }\n\
+            \x20       item\n\
+            \x20   }\n\
+            }\n\
+            fn range(start i64, end i64) Range = Range { current: start, end: end }\n\
+            fn iterated(n i64) i64 {\n    mut total i64 = 0\n\
+            \x20   for i in range(0, n) {\n        total += i\n    }\n    total\n}\n\
+            fn counted(n i64) i64 {\n    mut total i64 = 0\n    mut i i64 = 0\n\
+            \x20   while i < n {\n        total = total + i\n        i = i + 1\n    }\n    total\n}\n\
+            fn main() i64 = iterated(3) + counted(3)\n";
+        let (file, _) = lint(source, &[]);
+        let iterated = metrics(&file, "iterated");
+        let counted = metrics(&file, "counted");
+        assert_eq!(
+            (iterated.statements, iterated.depth, iterated.complexity),
+            (4, 1, 2)
+        );
+        assert_eq!(
+            (counted.statements, counted.depth, counted.complexity),
+            (6, 1, 2)
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use itertools::Itertools;
+
+use crate::analysis::{
+    ClosureInfo, Entity, EntityId, EntityInfo, EntityRef, FunctionInfo, LocalInfo, Scope,
+    ScopeKind, VariableInfo,
+};
+use crate::ir::ast::Visibility;
+use crate::ir::hir::{
+    AssociatedFunctionExpr, CallExpr, HirExprInner, HirExprKind, HirExprPtr, HirFile, HirStmt,
+    HirStmtKind, HirStmtPtr, MethodExpr, MirNode,
+};
+use crate::ir::visit::{rewrite_expr_children, rewrite_stmt_children, MirRewriter};
+use crate::syntax::Position;
+use crate::types::{Substitution, Type, TypeMap};
+use crate::utils::new_ptr;
+
+/// a generic function given types for its type parameters.
+#[derive(Debug, Clone)]
+struct Instance {
+    generic: EntityRef,
+    type_args: Vec<Rc<Type>>,
+    entity: EntityRef,
+    /// where it is first used.
+    position: Position,
+}
+
+impl Instance {
+    /// the type arguments of an instance used by a generic function can name the type
+    /// parameters of that function, it is instantiated again for each instance of it.
+    fn is_open(&self) -> bool {
+        self.type_args.iter().any(|ty| ty.kind().has_type_params())
+    }
+}
+
+/// the instances of generic functions used by a module. There is one for each generic function
+/// and type arguments, so a function calling itself with the same types calls its instance.
+#[derive(Debug, Default)]
+pub(crate) struct Instances {
+    instances: Vec<Instance>,
+}
+
+impl Instances {
+    /// the instance of generic for type_args, named `identity[i64]`. Its type is the type of
+    /// generic with the arguments substituted, its body is built once the module is resolved.
+    pub fn instantiate(
+        &mut self,
+        generic: &EntityRef,
+        type_args: Vec<Rc<Type>>,
+        position: Position,
+        type_map: &mut TypeMap,
+    ) -> EntityRef {
+        let id = generic.borrow().id();
+        let found = self.instances.iter().find(|instance| {
+            instance.generic.borrow().id() == id
+                && instance
+                    .type_args
+                    .iter()
+                    .zip(type_args.iter())
+                    .all(|(instance, arg)| instance.kind() == arg.kind())
+        });
+        if let Some(instance) = found {
+            return instance.entity.clone();
+        }
+
+        let borrow = generic.borrow();
+        let type_params = match borrow.kind() {
+            EntityInfo::Function(function) => function.type_params.as_slice(),
+            _ => unreachable!("only functions are generic"),
+        };
+        let ty = Substitution::new(type_params, &type_args).apply(&borrow.ty(), type_map);
+        let name = format!("{}[{}]", borrow.name(), type_args.iter().join(", "));
+        let entity = Entity::new_ref(
+            Visibility::Private,
+            name,
+            ty,
+            EntityInfo::Resolving,
+            borrow.path().clone(),
+        );
+        std::mem::drop(borrow);
+
+        self.instances.push(Instance {
+            generic: generic.clone(),
+            type_args,
+            entity: entity.clone(),
+            position,
+        });
+        entity
+    }
+
+    fn find(&self, entity: &EntityRef) -> Option<&Instance> {
+        self.instances
+            .iter()
+            .find(|instance| Rc::ptr_eq(&instance.entity, entity))
+    }
+}
+
+/// builds the instances of the generic functions a module uses so only functions without type
+/// parameters are built. The body of an instance is a copy of the body of its generic function
+/// with every type substituted, the locals it declares are copied with it. Generic functions
+/// are replaced in the module by their instances, those of a function of another module are
+/// placed first.
+pub(crate) struct Monomorphizer<'a> {
+    type_map: &'a mut TypeMap,
+    instances: &'a mut Instances,
+    substitution: Substitution,
+    /// the copies of the locals of the generic function being instantiated.
+    locals: HashMap<EntityId, EntityRef>,
+}
+
+impl<'a> Monomorphizer<'a> {
+    pub fn new(type_map: &'a mut TypeMap, instances: &'a mut Instances) -> Self {
+        Self {
+            type_map,
+            instances,
+            substitution: Substitution::default(),
+            locals: HashMap::new(),
+        }
+    }
+
+    pub fn run(&mut self, file: &mut HirFile) {
+        // building an instance can instantiate the generic functions it calls.
+        let mut built = vec![];
+        let mut next = 0;
+        while let Some(instance) = self.instances.instances.get(next).cloned() {
+            next += 1;
+            if !instance.is_open() {
+                self.build(&instance);
+                built.push(instance);
+            }
+        }
+
+        let mut stmts = vec![];
+        let mut placed = vec![false; built.len()];
+        for stmt in file.stmts() {
+            let generic = match stmt.inner() {
+                HirStmtKind::Item(entity) if is_generic(entity) => entity,
+                _ => {
+                    stmts.push(stmt.clone());
+                    continue;
+                }
+            };
+            for (index, instance) in built.iter().enumerate() {
+                if Rc::ptr_eq(&instance.generic, generic) {
+                    placed[index] = true;
+                    let kind = HirStmtKind::Item(instance.entity.clone());
+                    stmts.push(Rc::new(stmt.with_inner(kind)));
+                }
+            }
+        }
+        let imported = built
+            .iter()
+            .zip(placed)
+            .filter(|(_, placed)| !placed)
+            .map(|(instance, _)| {
+                let kind = HirStmtKind::Item(instance.entity.clone());
+                let unit = self.type_map.get_unit();
+                Rc::new(HirStmt::new(kind, instance.position, unit))
+            })
+            .collect::<Vec<_>>();
+        file.set_stmts(imported.into_iter().chain(stmts).collect());
+    }
+
+    fn build(&mut self, instance: &Instance) {
+        let function = match instance.generic.borrow().kind() {
+            EntityInfo::Function(function) => function.clone(),
+            _ => unreachable!("only functions are generic"),
+        };
+        self.substitution = Substitution::new(&function.type_params, &instance.type_args);
+        self.locals.clear();
+        let name = instance.entity.borrow().name().to_owned();
+        let function = self.copy_function(&function, name);
+        let mut entity = instance.entity.borrow_mut();
+        let (ty, path) = (entity.ty(), entity.path().clone());
+        entity.resolve(ty, EntityInfo::Function(function), path);
+    }
+
+    /// function with its parameters and body copied, the type parameters are left out.
+    fn copy_function(&mut self, function: &FunctionInfo, name: String) -> FunctionInfo {
+        let mut params = Scope::new(ScopeKind::Param(name), None);
+        for param in function.params.elements() {
+            let kind = match param.borrow().kind() {
+                EntityInfo::TypeParam => continue,
+                EntityInfo::Param(local) => EntityInfo::Param(LocalInfo {
+                    index: local.index,
+                    spec: local.spec.clone(),
+                    default: local.default.as_ref().map(|default| self.rewrite_expr(default)),
+                }),
+                kind => kind.clone(),
+            };
+            let copy = self.copy_local(param, kind);
+            params.add_element(copy.borrow().name(), copy.clone());
+        }
+        FunctionInfo {
+            params: Rc::new(params),
+            body_scope: function.body_scope.clone(),
+            body: self.rewrite_expr(&function.body),
+            metrics: None,
+            local: function.local,
+            type_params: vec![],
+        }
+    }
+
+    /// a new entity for the local of the generic function, the names using it use the copy.
+    fn copy_local(&mut self, local: &EntityRef, kind: EntityInfo) -> EntityRef {
+        let borrow = local.borrow();
+        let ty = self.substitution.apply(&borrow.ty(), self.type_map);
+        let mut copy = Entity::new(
+            borrow.visibility(),
+            borrow.name().to_owned(),
+            ty,
+            kind,
+            borrow.path().clone(),
+        );
+        if let Some(position) = borrow.position() {
+            copy = copy.declared_at(position);
+        }
+        let copy = new_ptr(copy);
+        self.locals.insert(borrow.id(), copy.clone());
+        copy
+    }
+
+    /// the entity a name of the body of an instance refers to, a copied local or the instance
+    /// of a generic function called with the type parameters of the function instantiated.
+    fn rename(&mut self, entity: &EntityRef) -> Option<EntityRef> {
+        if let Some(local) = self.locals.get(&entity.borrow().id()) {
+            return Some(local.clone());
+        }
+        let instance = self.instances.find(entity)?.clone();
+        if !instance.is_open() {
+            return None;
+        }
+        let type_args = instance
+            .type_args
+            .iter()
+            .map(|ty| self.substitution.apply(ty, self.type_map))
+            .collect();
+        Some(self.instances.instantiate(
+            &instance.generic,
+            type_args,
+            instance.position,
+            self.type_map,
+        ))
+    }
+
+    fn copy_closure(&mut self, entity: &EntityRef) -> EntityRef {
+        let closure = match entity.borrow().kind() {
+            EntityInfo::Closure(closure) => closure.clone(),
+            _ => unreachable!("a function literal is a closure"),
+        };
+        let name = entity.borrow().name().to_owned();
+        let function = self.copy_function(&closure.function, name);
+        let captures = closure
+            .captures
+            .iter()
+            .map(|capture| self.rename(capture).unwrap_or_else(|| capture.clone()))
+            .collect();
+        let kind = EntityInfo::Closure(ClosureInfo { function, captures });
+        self.copy_local(entity, kind)
+    }
+
+    fn with_kind(expr: &HirExprPtr, kind: HirExprKind) -> HirExprPtr {
+        let inner = expr.inner();
+        let inner = HirExprInner::new(inner.address_mode(), inner.meta(), kind);
+        Rc::new(expr.with_inner(inner))
+    }
+}
+
+impl<'a> MirRewriter for Monomorphizer<'a> {
+    fn rewrite_stmt(&mut self, stmt: &HirStmtPtr) -> HirStmtPtr {
+        let entity = match stmt.inner() {
+            HirStmtKind::Item(entity) => entity,
+            _ => return rewrite_stmt_children(self, stmt),
+        };
+        let kind = match entity.borrow().kind() {
+            EntityInfo::Variable(variable) => Some(EntityInfo::Variable(VariableInfo {
+                default: variable.default.as_ref().map(|default| self.rewrite_expr(default)),
+                ..variable.clone()
+            })),
+            EntityInfo::Function(function) => {
+                let name = entity.borrow().name().to_owned();
+                Some(EntityInfo::Function(self.copy_function(function, name)))
+            }
+            _ => None,
+        };
+        match kind {
+            Some(kind) => {
+                let copy = self.copy_local(entity, kind);
+                Rc::new(stmt.with_inner(HirStmtKind::Item(copy)))
+            }
+            None => stmt.clone(),
+        }
+    }
+
+    fn rewrite_expr(&mut self, expr: &HirExprPtr) -> HirExprPtr {
+        let rewritten = match expr.inner().kind() {
+            HirExprKind::Name(entity) => match self.rename(entity) {
+                Some(entity) => Self::with_kind(expr, HirExprKind::Name(entity)),
+                None => expr.clone(),
+            },
+            HirExprKind::Lambda(entity) => {
+                let copy = self.copy_closure(entity);
+                Self::with_kind(expr, HirExprKind::Lambda(copy))
+            }
+            _ => rewrite_expr_children(self, expr),
+        };
+        self.finish_expr(rewritten)
+    }
+
+    fn finish_expr(&mut self, rewritten: HirExprPtr) -> HirExprPtr {
+        let kind = match rewritten.inner().kind() {
+            HirExprKind::Call(call) => Some(HirExprKind::Call(CallExpr {
+                function_type: self.substitution.apply(&call.function_type, self.type_map),
+                ..call.clone()
+            })),
+            HirExprKind::Method(method) => Some(HirExprKind::Method(MethodExpr {
+                function_type: self.substitution.apply(&method.function_type, self.type_map),
+                ..method.clone()
+            })),
+            HirExprKind::AssociatedFunction(function) => {
+                let function_type = self.substitution.apply(&function.function_type, self.type_map);
+                Some(HirExprKind::AssociatedFunction(AssociatedFunctionExpr {
+                    function_type,
+                    ..function.clone()
+                }))
+            }
+            _ => None,
+        };
+        let rewritten = match kind {
+            Some(kind) => Self::with_kind(&rewritten, kind),
+            None => rewritten,
+        };
+        let ty = self.substitution.apply(&rewritten.ty(), self.type_map);
+        Rc::new(rewritten.with_type(ty))
+    }
+}
+
+/// whether entity is a function with type parameters.
+pub(crate) fn is_generic(entity: &EntityRef) -> bool {
+    match entity.borrow().kind() {
+        EntityInfo::Function(function) => !function.type_params.is_empty(),
+        _ => false,
+    }
+}
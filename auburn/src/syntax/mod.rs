@@ -7,9 +7,9 @@ mod parse;
 pub mod token;
 mod tokenizer;
 
-pub use parse::Parser;
-pub use token::{Control, Keyword, Operator, PToken, PairKind, Token};
-pub use tokenizer::TokenCursor;
+pub use parse::{Parser, BENCH_PREFIX, DEFAULT_MAX_EXPR_DEPTH};
+pub use token::{Associative, Control, Keyword, Operator, PToken, PairKind, Token};
+pub use tokenizer::{tokenize, SourceToken, TokenCursor, TokenKind, TokenizeOptions};
 
 use crate::ir::ast;
 use crate::ir::ast::Node;
@@ -143,6 +143,13 @@ impl Position {
     pub fn end(&self) -> Coord {
         self.file_pos.end
     }
+
+    /// other is inside of this position, both are in the same file.
+    pub fn contains(&self, other: &Position) -> bool {
+        self.file_id == other.file_id
+            && self.span.start() <= other.span.start()
+            && other.span.end() <= self.span.end()
+    }
 }
 
 impl Display for Position {
@@ -159,7 +166,8 @@ impl Display for Position {
 pub struct ParsedFile {
     pub file_name: String,
     pub file_id: FileId,
-    // imports: Vec<Box<Import>>,
+    /// modules imported by `use` at the top of the file.
+    pub imports: Vec<ast::Import>,
     pub stmts: Vec<Box<ast::Stmt>>,
 }
 
@@ -168,7 +176,7 @@ impl ParsedFile {
         Self {
             file_name,
             file_id,
-            // imports: vec![],
+            imports: vec![],
             stmts: vec![],
         }
     }
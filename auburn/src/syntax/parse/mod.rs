@@ -1,3 +1,3 @@
-pub use parser::Parser;
+pub use parser::{Parser, BENCH_PREFIX, DEFAULT_MAX_EXPR_DEPTH};
 
 mod parser;
@@ -4,16 +4,20 @@ use itertools::Itertools;
 
 use crate::error::Error;
 use crate::ir::ast::{
-    Expr, ExprKind, FunctionBody, Ident, Identifier, Item, ItemKind, Node, Spec, SpecKind, Stmt,
-    StmtKind, StructExprField, UnaryOp, Visibility,
+    Attribute, EnumVariant, Expr, ExprKind, FunctionBody, Ident, Identifier, Import, Item, ItemKind,
+    MatchArm, Node, Pattern, PatternKind, Spec, SpecKind, Stmt, StmtKind, StructExprField, UnaryOp,
+    Visibility,
 };
 use crate::syntax::ast::*;
 use crate::syntax::tokenizer::TokenCursor;
-use crate::syntax::{Control, Keyword, Operator, PToken, Position, Token};
+use crate::syntax::{Associative, Control, Keyword, Operator, PToken, Position, Token};
 use crate::syntax::{PairKind, ParsedFile};
 use crate::system::File;
+use crate::utils::timing::{self, PhaseTimer};
 
 type Restriction = usize;
+/// the parameters, return type and body of a function.
+type Signature = (Vec<Box<Item>>, Box<Spec>, FunctionBody);
 
 const DEFAULT: Restriction = 0;
 const TYPE_EXPR: Restriction = 1 << 0;
@@ -25,8 +29,25 @@ const NAMED_FIELD_EXPR: Restriction = 1 << 2;
 // const : Restriction = 8;
 // const : Restriction = 16;
 
+/// default limit on how deeply expressions may nest before the parser gives up. The operands
+/// of a chain of left associative operators, `1 + 2 + 3`, are not nested in one another.
+pub const DEFAULT_MAX_EXPR_DEPTH: usize = 256;
+
+/// the pattern of a while let, it is not a keyword so it can still name an item.
+const SOME_PATTERN: &str = "some";
+/// the pattern of a match arm that matches every value.
+const WILDCARD_PATTERN: &str = "_";
+/// starts a benchmark at file scope when a string follows it, it can still name an item.
+const BENCH: &str = "bench";
+/// the name of the function of `bench "name" { ... }` is the name after the prefix.
+pub const BENCH_PREFIX: &str = "bench ";
+
 pub struct Parser<'src> {
     restriction: Restriction,
+    /// current expression nesting depth.
+    depth: usize,
+    /// maximum expression nesting depth allowed.
+    max_depth: usize,
     file: &'src File,
     cursor: TokenCursor<'src>,
     current: Option<PToken<'src>>,
@@ -37,6 +58,8 @@ impl<'src> Parser<'src> {
     pub fn new(file: &'src File) -> Self {
         Self {
             restriction: DEFAULT,
+            depth: 0,
+            max_depth: DEFAULT_MAX_EXPR_DEPTH,
             file,
             cursor: TokenCursor::new(file),
             current: None,
@@ -44,7 +67,14 @@ impl<'src> Parser<'src> {
         }
     }
 
+    /// sets the maximum nesting depth of expressions. The passes after the parser walk a chain
+    /// of operators iteratively, the nesting is what keeps them from overflowing the stack.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
     pub fn init(&mut self) -> Result<(), Error> {
+        let _timer = PhaseTimer::start(timing::PARSE);
         self.consume()?;
         self.consume()?;
         Ok(())
@@ -64,8 +94,10 @@ impl<'src> Parser<'src> {
         let current = self.current.clone();
         self.current = self.peek.clone();
 
+        let lex = PhaseTimer::start(timing::LEX);
         if let Some(res) = self.cursor.next() {
             self.peek = Some(res?);
+            lex.count(1);
         } else {
             self.peek = None;
         }
@@ -79,7 +111,7 @@ impl<'src> Parser<'src> {
             token = self.consume_inner()?;
 
             match self.current.as_ref() {
-                Some(token) if token.is_comment() => {}
+                Some(token) if token.is_trivia() => {}
                 Some(_) => break,
                 None => break,
             }
@@ -102,7 +134,6 @@ impl<'src> Parser<'src> {
         }
     }
 
-    #[allow(unused)]
     fn expected(&self, token: Token) -> Result<(), Error> {
         if self.check_for(token.clone()) {
             Ok(())
@@ -118,6 +149,20 @@ impl<'src> Parser<'src> {
         self.peek.as_ref().map(|t| t.token()) == Some(&token)
     }
 
+    /// whether the first token after the newlines starting at the current token is token, nothing
+    /// is consumed.
+    fn check_past_newlines(&self, token: Token) -> bool {
+        let buffered = self.current.iter().chain(self.peek.iter()).cloned().map(Ok);
+        for next in buffered.chain(self.cursor.clone()) {
+            match next {
+                Ok(next) if next.is_trivia() || *next.token() == Token::Newline => {}
+                Ok(next) => return *next.token() == token,
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+
     fn check_for_res(&self, res: Restriction) -> bool {
         (self.restriction & res) == res
     }
@@ -138,15 +183,26 @@ impl<'src> Parser<'src> {
     //----------------------------------------------------------------------------------------------
 
     pub fn parse_file(&mut self) -> Result<ParsedFile, Error> {
+        let timer = PhaseTimer::start(timing::PARSE);
         let mut parsed_file = ParsedFile::new(self.file.name().to_owned(), self.file.id());
         while let Some(current) = self.current.clone() {
             if current.is_eof() {
                 break;
             }
 
-            let stmt = self.parse_stmt()?;
-            if !stmt.kind().is_empty() {
-                parsed_file.push_stmt(stmt);
+            if self.check_for(Token::Kw(Keyword::Use)) {
+                let import = self.parse_import()?;
+                parsed_file.imports.push(import);
+            } else if self.check_for(Token::Ident(BENCH))
+                && matches!(self.peek.as_ref().map(|t| t.token()), Some(Token::String(_)))
+            {
+                let bench = self.parse_bench()?;
+                parsed_file.push_stmt(bench);
+            } else {
+                let stmt = self.parse_stmt()?;
+                if !stmt.kind().is_empty() {
+                    parsed_file.push_stmt(stmt);
+                }
             }
 
             if self.check_for(Token::Newline) {
@@ -156,15 +212,67 @@ impl<'src> Parser<'src> {
             }
         }
 
+        timer.count(parsed_file.stmts.len());
         Ok(parsed_file)
     }
 
+    /// `use name(.name)*`, only allowed at file scope.
+    fn parse_import(&mut self) -> Result<Import, Error> {
+        let position = self.current_position();
+        self.expect(Token::Kw(Keyword::Use))?;
+        let mut path = vec![self.parse_ident()?];
+        while self.check_for(Token::Op(Operator::Period)) {
+            self.consume()?;
+            path.push(self.parse_ident()?);
+        }
+
+        let position = position.extended_to(path.last().unwrap());
+        Ok(Import { path, position })
+    }
+
+    /// `bench "name" { ... }`, only allowed at file scope. It is a private function without
+    /// parameters named after the benchmark, the driver finds it by the prefix of its name.
+    fn parse_bench(&mut self) -> Result<Box<Stmt>, Error> {
+        let position = self.current_position();
+        self.consume()?;
+        let name_position = self.current_position();
+        let name = match self.consume()?.map(|token| token.to_token()) {
+            Some(Token::String(name)) => format!("{}{}", BENCH_PREFIX, name),
+            _ => unreachable!("a benchmark is named by a string"),
+        };
+        self.expected(Token::ControlPair(Control::Bracket, PairKind::Open))?;
+        let body = self.parse_expr()?;
+        let position = position.extended_to(body.as_ref());
+
+        let kind = ItemKind::Function {
+            vis: Visibility::Private,
+            name: Identifier::new_with_position(Ident::from(name.as_str()), name_position),
+            attributes: vec![],
+            type_params: vec![],
+            params: vec![],
+            ret: Box::new(Spec::new_with_position(SpecKind::Infer, name_position)),
+            body: FunctionBody::Block(body),
+        };
+        let item = Box::new(Item::new_with_position(kind, position));
+        Ok(Box::new(Stmt::new_with_position(StmtKind::Item(item), position)))
+    }
+
     pub fn parse_stmt(&mut self) -> Result<Box<Stmt>, Error> {
         let current = self.current_token().clone();
         let position = current.position();
         match current.to_token() {
+            // a function literal starts an expression rather than declaring a function.
+            Token::Kw(Keyword::Fn)
+                if self.peek_for(Token::ControlPair(Control::Paren, PairKind::Open)) =>
+            {
+                let expr = self.parse_expr()?;
+                let position = position.extended_to(expr.as_ref());
+                Ok(Box::new(Stmt::new_with_position(StmtKind::Expr(expr), position)))
+            }
             Token::Kw(Keyword::Pub)
             | Token::Kw(Keyword::Struct)
+            | Token::Kw(Keyword::Enum)
+            | Token::Op(Operator::At)
             | Token::Kw(Keyword::Fn)
             | Token::Kw(Keyword::Let)
             | Token::Kw(Keyword::Mut) => {
@@ -173,6 +281,11 @@ impl<'src> Parser<'src> {
                 let kind = StmtKind::Item(item);
                 Ok(Box::new(Stmt::new_with_position(kind, position)))
             }
+            // the imports of the file are parsed by `parse_file`, one reached here is in a body.
+            Token::Kw(Keyword::Use) => {
+                let import = self.parse_import()?;
+                Err(Error::only_at_module_scope("use").with_position(import.position))
+            }
             Token::Kw(Keyword::Echo) => {
                 self.consume()?;
                 let expr = self.parse_expr()?;
@@ -190,8 +303,10 @@ impl<'src> Parser<'src> {
                     self.consume()?;
                     let rhs = self.parse_expr()?;
                     let position = expr.position().extended_to(rhs.as_ref());
+                    let op_position = assignment_op.position();
                     let kind = StmtKind::Assignment {
                         op: AssignmentOp::try_from(assignment_op.to_token().as_op())?,
+                        op_position,
                         lvalue: expr,
                         rhs,
                     };
@@ -213,32 +328,92 @@ impl<'src> Parser<'src> {
     fn parse_expr_with_res(&mut self, new_res: Restriction) -> Result<Box<Expr>, Error> {
         let res = self.restriction;
         self.restriction = new_res;
-        let result = self.parse_assoc_expr(2);
+        let result = self.nested(1, |p| p.parse_assoc_expr(2));
         self.restriction = res;
         result
     }
 
+    /// parses an operand nested levels deeper than the expression it is a part of.
+    fn nested<F>(&mut self, levels: usize, parse: F) -> Result<Box<Expr>, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<Box<Expr>, Error>,
+    {
+        self.check_depth(levels)?;
+        self.depth += levels;
+        let result = parse(self);
+        self.depth -= levels;
+        result
+    }
+
+    /// an error when an operand nested levels deeper is over the limit.
+    fn check_depth(&self, levels: usize) -> Result<(), Error> {
+        if self.depth + levels > self.max_depth {
+            Err(Error::expr_too_deeply_nested(self.max_depth)
+                .with_position(self.current_position()))
+        } else {
+            Ok(())
+        }
+    }
+
     fn parse_assoc_expr(&mut self, min_prec: u8) -> Result<Box<Expr>, Error> {
         let mut expr = self.parse_unary()?;
 
         if self.check_for_res(TYPE_EXPR) {
             return Ok(expr);
         }
+
+        // casts bind tighter than any binary operator.
+        while self.check_for(Token::Kw(Keyword::As)) {
+            self.consume()?;
+            let spec = self.parse_spec()?;
+            let position = expr.position().extended_to(spec.as_ref());
+            let kind = ExprKind::Cast {
+                operand: expr,
+                spec,
+            };
+            expr = Box::new(Expr::new_with_position(kind, position));
+        }
+        // the operands of a left associative chain are parsed in a loop, they are not nested.
         // println!("Position: {}", position);
         while self.current_token().precedence() > min_prec {
             let token_tree = self.current_token().clone();
             let token = token_tree.to_token();
             let lhs_position = expr.position();
 
+            // a right associative operator takes the rest of the chain as its right hand side,
+            // which is nested in it.
+            let (rhs_prec, levels) = match token.associativity() {
+                Associative::Right => (token.precedence() - 1, 1),
+                _ => (token.precedence() + 1, 0),
+            };
+            self.check_depth(levels)?;
+
             let op = token.as_op();
             self.consume()?;
 
-            let rhs = self.parse_assoc_expr(token.precedence() + 1)?;
+            self.depth += levels;
+            let rhs = self.parse_assoc_expr(rhs_prec);
+            self.depth -= levels;
+            let rhs = rhs?;
             let position = lhs_position.extended_to(rhs.as_ref());
 
+            if op == Operator::QuestionQuestion {
+                let kind = ExprKind::Coalesce(expr, rhs);
+                expr = Box::new(Expr::new_with_position(kind, position));
+                continue;
+            }
+            if op == Operator::PeriodPeriod {
+                let kind = ExprKind::Range {
+                    start: expr,
+                    end: rhs,
+                };
+                expr = Box::new(Expr::new_with_position(kind, position));
+                continue;
+            }
+
             match BinaryOp::try_from(op.clone()) {
                 Ok(op) => {
-                    let kind = ExprKind::Binary(op, expr.clone(), rhs);
+                    let kind = ExprKind::Binary(op, expr, rhs);
                     expr = Box::new(Expr::new_with_position(kind, position))
                 }
                 Err(e) => {
@@ -302,7 +477,7 @@ impl<'src> Parser<'src> {
     }
 
     fn parse_primary(&mut self) -> Result<Box<Expr>, Error> {
-        let mut operand = self.parse_bottom()?;
+        let operand = self.parse_bottom()?;
 
         if self.check_for_res(NAMED_FIELD_EXPR) {
             if self.check_for(Token::Op(Operator::Comma))
@@ -319,56 +494,45 @@ impl<'src> Parser<'src> {
             }
         }
 
+        let depth = self.depth;
+        let result = self.parse_postfix(operand);
+        self.depth = depth;
+        result
+    }
+
+    /// the member accesses, calls, struct literals and indexes applied to operand. Each nests
+    /// the expression it is applied to a level deeper.
+    fn parse_postfix(&mut self, mut operand: Box<Expr>) -> Result<Box<Expr>, Error> {
         let position = operand.position();
         loop {
             let current = self.current_token().clone();
-            match current.token() {
+            operand = match current.token() {
                 Token::Op(Operator::Period) => {
                     self.consume()?;
-                    let current_token = self.current_token().clone();
-                    match current_token.to_token() {
-                        Token::Ident(_) => {
-                            let name = self.parse_ident()?;
-
-                            if self.check_for(Token::ControlPair(Control::Paren, PairKind::Open)) {
-                                let (actuals, end_paren) = self.parse_call_actual()?;
-                                let mut actual = vec![operand.clone()];
-                                actual.extend(actuals);
-                                let position = position.extended_to_token(end_paren);
-                                let kind = ExprKind::Method {
-                                    name: Box::new(name),
-                                    actual,
-                                };
-                                operand = Box::new(Expr::new_with_position(kind, position));
-                            } else {
-                                let position = position.extended_to(&name);
-                                let kind = ExprKind::Field(operand.clone(), Box::new(name));
-                                operand = Box::new(Expr::new_with_position(kind, position));
-                            }
+                    match self.current_token().clone().to_token() {
+                        Token::Ident(_) | Token::Kw(Keyword::Expand) => {
+                            self.parse_member(operand, position)?
                         }
-                        Token::Integer(val) => {
+                        Token::Integer(element) => {
                             let position = position.extended_to_token(current.clone());
                             self.consume()?;
-
-                            let kind = ExprKind::TupleIndex {
-                                operand: operand.clone(),
-                                element: val,
-                            };
-                            operand = Box::new(Expr::new_with_position(kind, position));
+                            let kind = ExprKind::TupleIndex { operand, element };
+                            Box::new(Expr::new_with_position(kind, position))
                         }
                         _ => {
                             break;
                         }
                     }
                 }
+                Token::Op(Operator::QuestionPeriod) => {
+                    self.consume()?;
+                    self.parse_optional_member(operand, position)?
+                }
                 Token::ControlPair(Control::Paren, PairKind::Open) => {
                     let (actual, end_paren) = self.parse_call_actual()?;
                     let position = position.extended_to_token(end_paren);
-                    let kind = ExprKind::Call {
-                        operand: operand.clone(),
-                        actual,
-                    };
-                    operand = Box::new(Expr::new_with_position(kind, position));
+                    let kind = ExprKind::Call { operand, actual };
+                    Box::new(Expr::new_with_position(kind, position))
                 }
                 Token::ControlPair(Control::Bracket, PairKind::Open) => {
                     if self.check_for_res(NO_STRUCT_EXPR) {
@@ -377,59 +541,151 @@ impl<'src> Parser<'src> {
                         //     .with_position(self.current_position());
                         // return Err(err);
                     }
-
-                    self.consume()?;
-                    self.allow_newline()?;
-
-                    let mut fields = vec![];
-                    loop {
-                        if self.check_for(Token::ControlPair(Control::Bracket, PairKind::Close)) {
-                            break;
-                        }
-
-                        let field = self.parse_struct_expr_field()?;
-                        fields.push(field);
-
-                        if self.check_for(Token::Op(Operator::Comma)) {
-                            self.consume()?;
-                            self.allow_newline()?;
-                        } else {
-                            break;
-                        }
+                    // a struct literal never starts with a pipe.
+                    if self.peek_for(Token::Op(Operator::Pipe)) {
+                        let block = self.parse_trailing_block()?;
+                        Self::with_trailing_block(operand, block, position)
+                    } else {
+                        self.parse_struct_literal(operand, position)?
                     }
-
-                    let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
-
-                    operand = Box::new(Expr::new_with_position(
-                        ExprKind::StructExpr {
-                            name: operand.clone(),
-                            fields,
-                        },
-                        position.extended_to_token(end),
-                    ))
                 }
                 Token::ControlPair(Control::Brace, PairKind::Open) => {
-                    self.consume()?;
-                    self.allow_newline()?;
-                    let expr = self.parse_expr()?;
-                    let end = self.expect(Token::ControlPair(Control::Brace, PairKind::Close))?;
-                    let position = position.extended_to_token(end);
-                    operand = Box::new(Expr::new_with_position(
-                        ExprKind::Index {
-                            operand: operand.clone(),
-                            index: expr,
-                        },
-                        position,
-                    ));
+                    self.parse_index_expr(operand, position)?
                 }
                 _ => {
                     break;
                 }
-            }
+            };
+            self.check_depth(1)?;
+            self.depth += 1;
         }
         Ok(operand)
     }
 
+    /// `operand.name` or `operand.name(actuals)`, the current token is the name.
+    fn parse_member(&mut self, operand: Box<Expr>, position: Position) -> Result<Box<Expr>, Error> {
+        // `extend` is reserved for items, after a period it names a member like `v.extend(w)`.
+        let name = if self.check_for(Token::Kw(Keyword::Expand)) {
+            let position = self.current_position();
+            self.consume()?;
+            let name = Keyword::Expand.to_string();
+            Identifier::new_with_position(Ident::from(name.as_str()), position)
+        } else {
+            self.parse_ident()?
+        };
+
+        if self.check_for(Token::ControlPair(Control::Paren, PairKind::Open)) {
+            let (actuals, end_paren) = self.parse_call_actual()?;
+            let mut actual = vec![operand];
+            actual.extend(actuals);
+            let position = position.extended_to_token(end_paren);
+            let kind = ExprKind::Method {
+                name: Box::new(name),
+                actual,
+            };
+            Ok(Box::new(Expr::new_with_position(kind, position)))
+        } else {
+            let position = position.extended_to(&name);
+            let kind = ExprKind::Field(operand, Box::new(name));
+            Ok(Box::new(Expr::new_with_position(kind, position)))
+        }
+    }
+
+    /// `operand?.name` or `operand?.name(actuals)`, the current token is the name.
+    fn parse_optional_member(
+        &mut self,
+        operand: Box<Expr>,
+        position: Position,
+    ) -> Result<Box<Expr>, Error> {
+        let name = self.parse_ident()?;
+
+        if self.check_for(Token::ControlPair(Control::Paren, PairKind::Open)) {
+            let (actuals, end_paren) = self.parse_call_actual()?;
+            let mut actual = vec![operand];
+            actual.extend(actuals);
+            let position = position.extended_to_token(end_paren);
+            let kind = ExprKind::OptionalMethod {
+                name: Box::new(name),
+                actual,
+            };
+            Ok(Box::new(Expr::new_with_position(kind, position)))
+        } else {
+            let position = position.extended_to(&name);
+            let kind = ExprKind::OptionalField(operand, Box::new(name));
+            Ok(Box::new(Expr::new_with_position(kind, position)))
+        }
+    }
+
+    /// `Name { field: value, ... }`, the current token is the opening brace.
+    fn parse_struct_literal(
+        &mut self,
+        name: Box<Expr>,
+        position: Position,
+    ) -> Result<Box<Expr>, Error> {
+        self.consume()?;
+        self.allow_newline()?;
+
+        let mut fields = vec![];
+        loop {
+            if self.check_for(Token::ControlPair(Control::Bracket, PairKind::Close)) {
+                break;
+            }
+
+            let field = self.parse_struct_expr_field()?;
+            fields.push(field);
+
+            if self.check_for(Token::Op(Operator::Comma)) {
+                self.consume()?;
+                self.allow_newline()?;
+            } else {
+                break;
+            }
+        }
+
+        let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
+
+        Ok(Box::new(Expr::new_with_position(
+            ExprKind::StructExpr { name, fields },
+            position.extended_to_token(end),
+        )))
+    }
+
+    /// `operand[index]`, the current token is the opening bracket.
+    fn parse_index_expr(
+        &mut self,
+        operand: Box<Expr>,
+        position: Position,
+    ) -> Result<Box<Expr>, Error> {
+        self.consume()?;
+        self.allow_newline()?;
+        let mut expr = self.parse_index()?;
+        // the arguments of a type taking several, `Map[K, V]`, are a tuple.
+        if self.check_for(Token::Op(Operator::Comma)) {
+            self.consume()?;
+            let mut elements = vec![expr];
+            elements.extend(self.parse_inner_pair(
+                |p| p.parse_index(),
+                Token::Op(Operator::Comma),
+                false,
+                false,
+                Control::Brace,
+            )?);
+            let last = elements.last().expect("the arguments are not empty");
+            let position = elements[0].position().extended_to(last.as_ref());
+            let kind = ExprKind::Tuple(elements);
+            expr = Box::new(Expr::new_with_position(kind, position));
+        }
+        let end = self.expect(Token::ControlPair(Control::Brace, PairKind::Close))?;
+        let position = position.extended_to_token(end);
+        Ok(Box::new(Expr::new_with_position(
+            ExprKind::Index {
+                operand,
+                index: expr,
+            },
+            position,
+        )))
+    }
+
     fn parse_struct_expr_field(&mut self) -> Result<StructExprField, Error> {
         if self.peek_for(Token::Op(Operator::Colon)) {
             let ident = self.parse_ident()?;
@@ -445,7 +701,6 @@ impl<'src> Parser<'src> {
 
     fn parse_bottom(&mut self) -> Result<Box<Expr>, Error> {
         let current = self.current_token().clone();
-        let position = current.position();
         if self.check_for_res(NAMED_FIELD_EXPR) {
             match current.token() {
                 Token::Ident(_) => {}
@@ -455,7 +710,36 @@ impl<'src> Parser<'src> {
                 }
             }
         }
+        // an expression containing others is parsed by a function of its own, the frame of
+        // this one is on the stack for every level an expression is nested.
         match current.to_token() {
+            t @ Token::ControlPair(Control::Bracket, PairKind::Open) => self.parse_block_expr(t),
+            t @ Token::ControlPair(Control::Paren, PairKind::Open) => self.parse_paren_expr(t),
+            t @ Token::ControlPair(Control::Brace, PairKind::Open) => self.parse_array_expr(t),
+            t @ Token::Kw(Keyword::Fn) => self.parse_lambda(t),
+            Token::Kw(Keyword::Return) => {
+                let position = self.current_position();
+                self.consume()?;
+                let expr = self.parse_expr()?;
+                let position = position.extended_to(expr.as_ref());
+                Ok(Box::new(Expr::new_with_position(
+                    ExprKind::Return(expr),
+                    position,
+                )))
+            }
+            t @ Token::Kw(Keyword::If)
+            | t @ Token::Kw(Keyword::While)
+            | t @ Token::Kw(Keyword::Loop)
+            | t @ Token::Kw(Keyword::For)
+            | t @ Token::Kw(Keyword::Match) => self.parse_branching(t),
+            t => self.parse_leaf(t),
+        }
+    }
+
+    /// a name, a literal or a keyword standing for a value, the current token is t.
+    fn parse_leaf(&mut self, t: Token) -> Result<Box<Expr>, Error> {
+        let position = self.current_position();
+        match t {
             Token::Ident(_) => {
                 let ident = self.parse_ident()?;
                 Ok(Box::new(Expr::new_with_position(
@@ -516,70 +800,12 @@ impl<'src> Parser<'src> {
                     position,
                 )))
             }
-            t @ Token::ControlPair(Control::Bracket, PairKind::Open) => {
-                if self.check_for_res(TYPE_EXPR) {
-                    let kind = Error::invalid_type_expression(&t).with_position(position);
-                    Err(kind)
-                } else {
-                    let open = self.consume()?.unwrap();
-                    self.allow_newline()?;
-
-                    let position = open.position();
-                    let stmts = self.parse_inner_pair(
-                        |p| p.parse_stmt(),
-                        Token::Newline,
-                        true,
-                        false,
-                        Control::Bracket,
-                    )?;
-
-                    let stmts = stmts
-                        .into_iter()
-                        .filter(|stmt| -> bool { !stmt.kind().is_empty() })
-                        .collect_vec();
-
-                    let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
-                    let position = position.extended_to_token(end);
-
-                    let kind = ExprKind::Block(stmts);
-                    Ok(Box::new(Expr::new_with_position(kind, position)))
-                }
-            }
-            t @ Token::ControlPair(Control::Paren, PairKind::Open) => {
-                if self.check_for_res(TYPE_EXPR) {
-                    let kind = Error::invalid_type_expression(&t).with_position(position);
-                    return Err(kind);
-                }
-
-                let open = self.consume()?.unwrap();
-                let position = open.position();
-                self.allow_newline()?;
-
-                let expr = self.parse_expr()?;
-
-                if self.check_for(Token::Op(Operator::Comma)) {
-                    self.consume()?;
-                    let mut elements = vec![expr];
-
-                    let rest_elements = self.parse_inner_pair(
-                        |p| p.parse_expr(),
-                        Token::Op(Operator::Comma),
-                        false,
-                        false,
-                        Control::Paren,
-                    )?;
-
-                    let end = self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
-                    let position = position.extended_to_token(end);
-
-                    elements.extend(rest_elements);
-
-                    let kind = ExprKind::Tuple(elements);
-                    Ok(Box::new(Expr::new_with_position(kind, position)))
-                } else {
-                    self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
-                    Ok(expr)
-                }
+            Token::Kw(Keyword::NoneLit) => {
+                self.consume()?;
+                Ok(Box::new(Expr::new_with_position(
+                    ExprKind::NoneLit,
+                    position,
+                )))
             }
             Token::Kw(Keyword::SelfType) => {
                 let position = self.current_position();
@@ -610,22 +836,217 @@ impl<'src> Parser<'src> {
                 self.consume()?;
                 Ok(Box::new(Expr::new_with_position(ExprKind::Break, position)))
             }
-            Token::Kw(Keyword::Return) => {
-                let position = self.current_position();
+            e => todo!("parse_leaf: unexpected token {}", e),
+        }
+    }
+
+    /// a block, `{ statements }`, the current token is t.
+    fn parse_block_expr(&mut self, t: Token) -> Result<Box<Expr>, Error> {
+        let position = self.current_position();
+        if self.check_for_res(TYPE_EXPR) {
+            let kind = Error::invalid_type_expression(&t).with_position(position);
+            Err(kind)
+        } else {
+            let open = self.consume()?.unwrap();
+            self.allow_newline()?;
+
+            let position = open.position();
+            let stmts = self.parse_inner_pair(
+                |p| p.parse_stmt(),
+                Token::Newline,
+                true,
+                false,
+                Control::Bracket,
+            )?;
+
+            let stmts = stmts
+                .into_iter()
+                .filter(|stmt| -> bool { !stmt.kind().is_empty() })
+                .collect_vec();
+
+            let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
+            let position = position.extended_to_token(end);
+
+            let kind = ExprKind::Block(stmts);
+            Ok(Box::new(Expr::new_with_position(kind, position)))
+        }
+    }
+
+    /// a parenthesized expression or a tuple, the current token is t.
+    fn parse_paren_expr(&mut self, t: Token) -> Result<Box<Expr>, Error> {
+        let position = self.current_position();
+        if self.check_for_res(TYPE_EXPR) {
+            let kind = Error::invalid_type_expression(&t).with_position(position);
+            return Err(kind);
+        }
+
+        let open = self.consume()?.unwrap();
+        let position = open.position();
+        self.allow_newline()?;
+
+        let expr = self.parse_expr()?;
+
+        if self.check_for(Token::Op(Operator::Comma)) {
+            self.consume()?;
+            let mut elements = vec![expr];
+
+            let rest_elements = self.parse_inner_pair(
+                |p| p.parse_expr(),
+                Token::Op(Operator::Comma),
+                false,
+                false,
+                Control::Paren,
+            )?;
+
+            let end = self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
+            let position = position.extended_to_token(end);
+
+            elements.extend(rest_elements);
+
+            let kind = ExprKind::Tuple(elements);
+            Ok(Box::new(Expr::new_with_position(kind, position)))
+        } else {
+            self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
+            Ok(expr)
+        }
+    }
+
+    /// an array, `[elements]`, the current token is t.
+    fn parse_array_expr(&mut self, t: Token) -> Result<Box<Expr>, Error> {
+        let position = self.current_position();
+        if self.check_for_res(TYPE_EXPR) {
+            let kind = Error::invalid_type_expression(&t).with_position(position);
+            return Err(kind);
+        }
+
+        self.consume()?;
+        self.allow_newline()?;
+        let elements = self.parse_inner_pair(
+            |p| p.parse_expr(),
+            Token::Op(Operator::Comma),
+            true,
+            true,
+            Control::Brace,
+        )?;
+        self.allow_newline()?;
+        let end = self.expect(Token::ControlPair(Control::Brace, PairKind::Close))?;
+        let position = position.extended_to_token(end);
+
+        let kind = ExprKind::Array(elements);
+        Ok(Box::new(Expr::new_with_position(kind, position)))
+    }
+
+    /// a function literal, the current token is t.
+    fn parse_lambda(&mut self, t: Token) -> Result<Box<Expr>, Error> {
+        let position = self.current_position();
+        if self.check_for_res(TYPE_EXPR) {
+            let kind = Error::invalid_type_expression(&t).with_position(position);
+            return Err(kind);
+        }
+        self.consume()?;
+        let (params, ret, body) = self.parse_signature()?;
+        let position = match &body {
+            FunctionBody::Block(expr) | FunctionBody::Expression(expr) => {
+                position.extended_to(expr.as_ref())
+            }
+        };
+        let kind = ExprKind::Lambda {
+            params,
+            ret,
+            body,
+            trailing: false,
+        };
+        Ok(Box::new(Expr::new_with_position(kind, position)))
+    }
+
+    /// `{ |<params>| statements }` following a call, a function literal passed as its last
+    /// actual. The types of the parameters may be left out, they are the ones the callee expects.
+    fn parse_trailing_block(&mut self) -> Result<Box<Expr>, Error> {
+        let open = self.consume()?.unwrap();
+        let position = open.position();
+        self.expect(Token::Op(Operator::Pipe))?;
+
+        let mut params = vec![];
+        while !self.check_for(Token::Op(Operator::Pipe)) {
+            let name = self.parse_ident()?;
+            let mut param_position = name.position();
+            let spec = if self.check_for(Token::Op(Operator::Comma))
+                || self.check_for(Token::Op(Operator::Pipe))
+            {
+                None
+            } else {
+                let spec = self.parse_spec()?;
+                param_position = param_position.extended_to(spec.as_ref());
+                Some(spec)
+            };
+            let kind = ItemKind::Param {
+                names: vec![name],
+                spec,
+                init: None,
+            };
+            params.push(Box::new(Item::new_with_position(kind, param_position)));
+            if self.check_for(Token::Op(Operator::Comma)) {
                 self.consume()?;
-                let expr = self.parse_expr()?;
-                let position = position.extended_to(expr.as_ref());
-                Ok(Box::new(Expr::new_with_position(
-                    ExprKind::Return(expr),
-                    position,
-                )))
+            } else {
+                break;
             }
-            t @ Token::Kw(Keyword::If)
-            | t @ Token::Kw(Keyword::While)
-            | t @ Token::Kw(Keyword::Loop)
-            | t @ Token::Kw(Keyword::For) => self.parse_branching(t),
-            e => todo!("parse_bottom: unexpected token {}", e),
         }
+        self.expect(Token::Op(Operator::Pipe))?;
+        self.allow_newline()?;
+
+        let stmts = self
+            .parse_inner_pair(
+                |p| p.parse_stmt(),
+                Token::Newline,
+                true,
+                false,
+                Control::Bracket,
+            )?
+            .into_iter()
+            .filter(|stmt| !stmt.kind().is_empty())
+            .collect_vec();
+        let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
+        let position = position.extended_to_token(end);
+
+        let body = Box::new(Expr::new_with_position(ExprKind::Block(stmts), position));
+        let kind = ExprKind::Lambda {
+            params,
+            ret: Box::new(Spec::new_with_position(SpecKind::Infer, position)),
+            body: FunctionBody::Block(body),
+            trailing: true,
+        };
+        Ok(Box::new(Expr::new_with_position(kind, position)))
+    }
+
+    /// operand called with block as its last actual, `f(x) { |y| body }` is `f(x, block)` and
+    /// `v.each { |x| body }` is `v.each(block)`.
+    fn with_trailing_block(operand: Box<Expr>, block: Box<Expr>, position: Position) -> Box<Expr> {
+        let position = position.extended_to(block.as_ref());
+        let kind = match operand.kind() {
+            ExprKind::Call { .. } | ExprKind::Method { .. } | ExprKind::Field(..) => {
+                let mut kind = operand.into_kind();
+                match &mut kind {
+                    ExprKind::Call { actual, .. } | ExprKind::Method { actual, .. } => {
+                        actual.push(block);
+                        kind
+                    }
+                    ExprKind::Field(operand, name) => {
+                        let taken = Box::new(Expr::new(ExprKind::Break));
+                        let operand = std::mem::replace(operand, taken);
+                        ExprKind::Method {
+                            name: name.clone(),
+                            actual: vec![operand, block],
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            _ => ExprKind::Call {
+                operand,
+                actual: vec![block],
+            },
+        };
+        Box::new(Expr::new_with_position(kind, position))
     }
 
     fn parse_inner_pair<E, F>(
@@ -686,6 +1107,18 @@ impl<'src> Parser<'src> {
         Ok((actual, close_paren))
     }
 
+    /// an index or a type argument, `fn` starts the type of a function rather than a literal so
+    /// `Vec[fn(i64) i64]` names a vector of functions.
+    fn parse_index(&mut self) -> Result<Box<Expr>, Error> {
+        if self.check_for(Token::Kw(Keyword::Fn)) {
+            let spec = self.parse_spec()?;
+            let position = spec.position();
+            Ok(Box::new(Expr::new_with_position(ExprKind::Spec(spec), position)))
+        } else {
+            self.parse_expr()
+        }
+    }
+
     fn parse_branching(&mut self, token: Token) -> Result<Box<Expr>, Error> {
         if self.check_for_res(TYPE_EXPR) {
             let kind =
@@ -699,6 +1132,7 @@ impl<'src> Parser<'src> {
                 Token::Kw(Keyword::While) => self.parse_while(position),
                 Token::Kw(Keyword::For) => self.parse_for(position),
                 Token::Kw(Keyword::If) => self.parse_if(position),
+                Token::Kw(Keyword::Match) => self.parse_match(position),
                 _ => unreachable!(),
             }
         }
@@ -724,6 +1158,10 @@ impl<'src> Parser<'src> {
     }
 
     fn parse_while(&mut self, position: Position) -> Result<Box<Expr>, Error> {
+        if self.check_for(Token::Kw(Keyword::Let)) {
+            self.consume()?;
+            return self.parse_while_let(position);
+        }
         let cond = self.parse_expr_with_res(NO_STRUCT_EXPR)?;
         if self.check_for(Token::ControlPair(Control::Bracket, PairKind::Open)) {
             let expr = self.parse_expr()?;
@@ -743,6 +1181,42 @@ impl<'src> Parser<'src> {
         }
     }
 
+    /// `while let some(element) = expr { body }`, the only pattern is `some`.
+    fn parse_while_let(&mut self, position: Position) -> Result<Box<Expr>, Error> {
+        let pattern = self.current_token().clone();
+        if pattern.token() != &Token::Ident(SOME_PATTERN) {
+            let err = Error::unexpected_token(Token::Ident(SOME_PATTERN), pattern.token());
+            return Err(err.with_position(pattern.position()));
+        }
+        self.consume()?;
+        self.expect(Token::ControlPair(Control::Paren, PairKind::Open))?;
+        let element = self.parse_ident()?;
+        self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
+        self.expect(Token::Op(Operator::Equal))?;
+
+        let expr = self.parse_expr_with_res(NO_STRUCT_EXPR)?;
+        if self.check_for(Token::ControlPair(Control::Bracket, PairKind::Open)) {
+            let body = self.parse_expr()?;
+            let position = position.extended_to(body.as_ref());
+
+            Ok(Box::new(Expr::new_with_position(
+                ExprKind::WhileLet {
+                    element,
+                    expr,
+                    body,
+                },
+                position,
+            )))
+        } else {
+            let token = self.current_token().token();
+            let err = Error::unexpected_token(
+                Token::ControlPair(Control::Bracket, PairKind::Open),
+                token,
+            );
+            Err(err.with_position(self.current_position()))
+        }
+    }
+
     fn parse_for(&mut self, position: Position) -> Result<Box<Expr>, Error> {
         let element = self.parse_ident()?;
 
@@ -752,15 +1226,15 @@ impl<'src> Parser<'src> {
 
         if self.check_for(Token::ControlPair(Control::Bracket, PairKind::Open)) {
             let body = self.parse_expr()?;
+            let position = position.extended_to(body.as_ref());
 
-            let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
             Ok(Box::new(Expr::new_with_position(
                 ExprKind::For {
                     element,
                     expr,
                     body,
                 },
-                position.extended_to_token(end),
+                position,
             )))
         } else {
             let token = self.current_token().token();
@@ -777,7 +1251,12 @@ impl<'src> Parser<'src> {
 
         if self.check_for(Token::ControlPair(Control::Bracket, PairKind::Open)) {
             let body = self.parse_expr()?;
-            self.allow_newline()?;
+            // the newlines before an `elif` or `else` are part of the if, otherwise they end it.
+            if self.check_past_newlines(Token::Kw(Keyword::Elif))
+                || self.check_past_newlines(Token::Kw(Keyword::Else))
+            {
+                self.allow_newline()?;
+            }
             let else_if = if self.check_for(Token::Kw(Keyword::Elif)) {
                 let position = self.current_position();
                 self.consume()?;
@@ -822,6 +1301,174 @@ impl<'src> Parser<'src> {
         }
     }
 
+    /// `match value { pattern => body }`, the arms are separated by a comma, a newline or both.
+    fn parse_match(&mut self, position: Position) -> Result<Box<Expr>, Error> {
+        let value = self.parse_expr_with_res(NO_STRUCT_EXPR)?;
+        self.expect(Token::ControlPair(Control::Bracket, PairKind::Open))?;
+        self.allow_newline()?;
+
+        let mut arms = vec![];
+        while !self.check_for(Token::ControlPair(Control::Bracket, PairKind::Close)) {
+            let pattern = self.parse_pattern()?;
+            self.expect(Token::Op(Operator::FatArrow))?;
+            let body = self.parse_expr()?;
+            arms.push(MatchArm { pattern, body });
+
+            let separated = self.check_for(Token::Op(Operator::Comma))
+                || self.check_for(Token::Newline);
+            if self.check_for(Token::Op(Operator::Comma)) {
+                self.consume()?;
+            }
+            self.allow_newline()?;
+            if !separated {
+                break;
+            }
+        }
+
+        let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
+        Ok(Box::new(Expr::new_with_position(
+            ExprKind::Match { value, arms },
+            position.extended_to_token(end),
+        )))
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, Error> {
+        let current = self.current_token().clone();
+        let position = current.position();
+        let kind = match current.to_token() {
+            Token::Ident(WILDCARD_PATTERN) => {
+                self.consume()?;
+                PatternKind::Wildcard
+            }
+            Token::Ident(_) if self.peek_for(Token::Op(Operator::Period)) => {
+                let enum_name = self.parse_ident()?;
+                self.consume()?;
+                let variant = self.parse_ident()?;
+                // a constant of an imported module, `geometry.MAX..`, starts a range.
+                if self.check_for(Token::Op(Operator::Period))
+                    || self.check_for(Token::Op(Operator::PeriodPeriod))
+                {
+                    let start = Self::name_expr(enum_name);
+                    let start = self.parse_bound_path(Self::field_expr(start, variant))?;
+                    return self.parse_range_pattern(start);
+                }
+                let mut position = position.extended_to(&variant);
+                let mut fields = vec![];
+                if self.check_for(Token::ControlPair(Control::Paren, PairKind::Open)) {
+                    self.consume()?;
+                    fields = self
+                        .parse_inner_pair(
+                            |p| p.parse_pattern().map(Box::new),
+                            Token::Op(Operator::Comma),
+                            true,
+                            true,
+                            Control::Paren,
+                        )?
+                        .into_iter()
+                        .map(|field| *field)
+                        .collect();
+                    let end = self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
+                    position = position.extended_to_token(end);
+                }
+                let kind = PatternKind::Variant {
+                    enum_name,
+                    variant,
+                    fields,
+                };
+                return Ok(Pattern { kind, position });
+            }
+            Token::Ident(_) if self.peek_for(Token::Op(Operator::PeriodPeriod)) => {
+                let start = self.parse_pattern_bound()?;
+                return self.parse_range_pattern(start);
+            }
+            Token::Ident(_) => PatternKind::Binding(self.parse_ident()?),
+            Token::Integer(_) | Token::Op(Operator::Minus) => {
+                let literal = self.parse_pattern_bound()?;
+                if self.check_for(Token::Op(Operator::PeriodPeriod)) {
+                    return self.parse_range_pattern(literal);
+                }
+                let position = literal.position();
+                return Ok(Pattern {
+                    kind: PatternKind::Literal(literal),
+                    position,
+                });
+            }
+            Token::Kw(keyword @ Keyword::True) | Token::Kw(keyword @ Keyword::False) => {
+                self.consume()?;
+                PatternKind::Literal(Box::new(Expr::new_with_position(
+                    ExprKind::Bool(keyword == Keyword::True),
+                    position,
+                )))
+            }
+            token => return Err(Error::expected_pattern(&token).with_position(position)),
+        };
+        Ok(Pattern { kind, position })
+    }
+
+    /// `start..end`, the current token is the `..` following start.
+    fn parse_range_pattern(&mut self, start: Box<Expr>) -> Result<Pattern, Error> {
+        self.expect(Token::Op(Operator::PeriodPeriod))?;
+        let end = self.parse_pattern_bound()?;
+        let position = start.position().extended_to(end.as_ref());
+        Ok(Pattern {
+            kind: PatternKind::Range { start, end },
+            position,
+        })
+    }
+
+    /// an integer, a negated integer or a name, `geometry.MAX`, compared with the value of a
+    /// match.
+    fn parse_pattern_bound(&mut self) -> Result<Box<Expr>, Error> {
+        let current = self.current_token().clone();
+        let position = current.position();
+        match current.to_token() {
+            Token::Integer(val) => {
+                self.consume()?;
+                Ok(Box::new(Expr::new_with_position(
+                    ExprKind::Integer(val),
+                    position,
+                )))
+            }
+            Token::Op(Operator::Minus)
+                if matches!(self.peek.as_ref().map(PToken::token), Some(Token::Integer(_))) =>
+            {
+                self.consume()?;
+                let operand = self.parse_pattern_bound()?;
+                let position = position.extended_to(operand.as_ref());
+                let kind = ExprKind::Unary(UnaryOp::Minus, operand);
+                Ok(Box::new(Expr::new_with_position(kind, position)))
+            }
+            Token::Ident(_) => {
+                let name = Self::name_expr(self.parse_ident()?);
+                self.parse_bound_path(name)
+            }
+            token => Err(Error::expected_pattern(&token).with_position(position)),
+        }
+    }
+
+    /// the names following a bound, `shapes.square.SIDES`.
+    fn parse_bound_path(&mut self, mut bound: Box<Expr>) -> Result<Box<Expr>, Error> {
+        while self.check_for(Token::Op(Operator::Period)) {
+            self.consume()?;
+            let name = self.parse_ident()?;
+            bound = Self::field_expr(bound, name);
+        }
+        Ok(bound)
+    }
+
+    fn name_expr(name: Identifier) -> Box<Expr> {
+        let position = name.position();
+        Box::new(Expr::new_with_position(ExprKind::Name(name), position))
+    }
+
+    fn field_expr(operand: Box<Expr>, name: Identifier) -> Box<Expr> {
+        let position = operand.position().extended_to(&name);
+        Box::new(Expr::new_with_position(
+            ExprKind::Field(operand, Box::new(name)),
+            position,
+        ))
+    }
+
     fn allow_newline(&mut self) -> Result<(), Error> {
         while self.check_for(Token::Newline) {
             self.consume()?;
@@ -853,8 +1500,14 @@ impl<'src> Parser<'src> {
                 )?;
 
                 let end = self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
+                // `()` is the unit type, the tuple without elements.
+                let kind = if inner.is_empty() {
+                    SpecKind::Unit
+                } else {
+                    SpecKind::Tuple(inner)
+                };
                 Ok(Box::new(Spec::new_with_position(
-                    SpecKind::Tuple(inner),
+                    kind,
                     position.extended_to_token(end),
                 )))
             }
@@ -891,6 +1544,29 @@ impl<'src> Parser<'src> {
                     position,
                 )))
             }
+            Token::Kw(Keyword::Fn) => {
+                self.consume()?;
+                self.expect(Token::ControlPair(Control::Paren, PairKind::Open))?;
+                let params = self.parse_inner_pair(
+                    |p| p.parse_spec(),
+                    Token::Op(Operator::Comma),
+                    true,
+                    true,
+                    Control::Paren,
+                )?;
+                let end = self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
+                let closed = position.extended_to_token(end);
+                let ret = self.parse_spec()?;
+                let position = if ret.is_infer() {
+                    closed
+                } else {
+                    position.extended_to(ret.as_ref())
+                };
+                Ok(Box::new(Spec::new_with_position(
+                    SpecKind::Function { params, ret },
+                    position,
+                )))
+            }
             _ => Ok(Box::new(Spec::new_with_position(SpecKind::Infer, position))),
         }
     }
@@ -898,16 +1574,32 @@ impl<'src> Parser<'src> {
     //----------------------------------------------------------------------------------------------
 
     fn parse_item(&mut self) -> Result<Box<Item>, Error> {
+        let attributes = self.parse_attributes()?;
         let vis = self.parse_possible_vis()?;
+        if let Some(attribute) = attributes.first() {
+            if !self.check_for(Token::Kw(Keyword::Enum))
+                && !self.check_for(Token::Kw(Keyword::Struct))
+                && !self.check_for(Token::Kw(Keyword::Fn))
+            {
+                let name = attribute.name.kind().value.as_str();
+                let err = Error::misplaced_attribute(name, "an enum, a struct or a function");
+                return Err(err.with_position(attribute.name.position()));
+            }
+        }
+
         let current = self.current_token();
         match current.token() {
             Token::Kw(Keyword::Struct) => {
                 std::mem::forget(current);
-                self.parse_struct(vis)
+                self.parse_struct(vis, attributes)
+            }
+            Token::Kw(Keyword::Enum) => {
+                std::mem::forget(current);
+                self.parse_enum(vis, attributes)
             }
             Token::Kw(Keyword::Fn) => {
                 std::mem::forget(current);
-                self.parse_function(vis)
+                self.parse_function(vis, attributes)
             }
             Token::Kw(Keyword::Let) | Token::Kw(Keyword::Mut) => {
                 std::mem::forget(current);
@@ -917,6 +1609,33 @@ impl<'src> Parser<'src> {
         }
     }
 
+    /// `@name` or `@name(expr)` attributes, each may be followed by a newline.
+    fn parse_attributes(&mut self) -> Result<Vec<Attribute>, Error> {
+        let mut attributes = vec![];
+        while self.check_for(Token::Op(Operator::At)) {
+            self.consume()?;
+            let name = self.parse_ident()?;
+            let (argument, source) =
+                if self.check_for(Token::ControlPair(Control::Paren, PairKind::Open)) {
+                    self.consume()?;
+                    let argument = self.parse_expr()?;
+                    self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
+                    let span = argument.position().span();
+                    let source = self.file.content()[span.start()..span.end()].to_owned();
+                    (Some(argument), source)
+                } else {
+                    (None, String::new())
+                };
+            attributes.push(Attribute {
+                name,
+                argument,
+                source,
+            });
+            self.allow_newline()?;
+        }
+        Ok(attributes)
+    }
+
     fn parse_possible_vis(&mut self) -> Result<Visibility, Error> {
         if self.check_for(Token::Kw(Keyword::Pub)) {
             self.consume()?;
@@ -926,7 +1645,11 @@ impl<'src> Parser<'src> {
         }
     }
 
-    fn parse_struct(&mut self, vis: Visibility) -> Result<Box<Item>, Error> {
+    fn parse_struct(
+        &mut self,
+        vis: Visibility,
+        attributes: Vec<Attribute>,
+    ) -> Result<Box<Item>, Error> {
         let position = self.current_position();
         self.expect(Token::Kw(Keyword::Struct))?;
 
@@ -939,9 +1662,16 @@ impl<'src> Parser<'src> {
             |p| {
                 if (p.check_for(Token::Kw(Keyword::Pub)) && p.peek_for(Token::Kw(Keyword::Fn)))
                     || p.check_for(Token::Kw(Keyword::Fn))
+                    || p.check_for(Token::Op(Operator::At))
                 {
+                    let attributes = p.parse_attributes()?;
                     let vis = p.parse_possible_vis()?;
-                    p.parse_function(vis)
+                    if !p.check_for(Token::Kw(Keyword::Fn)) {
+                        let name = attributes[0].name.kind().value.as_str();
+                        let err = Error::misplaced_attribute(name, "an enum or a function");
+                        return Err(err.with_position(attributes[0].name.position()));
+                    }
+                    p.parse_function(vis, attributes)
                 } else {
                     p.parse_field()
                 }
@@ -955,16 +1685,140 @@ impl<'src> Parser<'src> {
         let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
 
         Ok(Box::new(Item::new_with_position(
-            ItemKind::Struct { name, vis, fields },
+            ItemKind::Struct {
+                name,
+                vis,
+                attributes,
+                fields,
+            },
+            position.extended_to_token(end),
+        )))
+    }
+
+    fn parse_enum(
+        &mut self,
+        vis: Visibility,
+        attributes: Vec<Attribute>,
+    ) -> Result<Box<Item>, Error> {
+        let position = self.current_position();
+        self.expect(Token::Kw(Keyword::Enum))?;
+
+        let name = self.parse_ident()?;
+
+        self.expect(Token::ControlPair(Control::Bracket, PairKind::Open))?;
+        self.allow_newline()?;
+
+        // variants are separated by a comma, a newline or both.
+        let mut variants = vec![];
+        while !self.check_for(Token::ControlPair(Control::Bracket, PairKind::Close)) {
+            variants.push(self.parse_enum_variant()?);
+
+            let separated = self.check_for(Token::Op(Operator::Comma))
+                || self.check_for(Token::Newline);
+            if self.check_for(Token::Op(Operator::Comma)) {
+                self.consume()?;
+            }
+            self.allow_newline()?;
+            if !separated {
+                break;
+            }
+        }
+
+        let end = self.expect(Token::ControlPair(Control::Bracket, PairKind::Close))?;
+
+        Ok(Box::new(Item::new_with_position(
+            ItemKind::Enum {
+                vis,
+                name,
+                attributes,
+                variants,
+            },
             position.extended_to_token(end),
         )))
     }
 
-    fn parse_function(&mut self, vis: Visibility) -> Result<Box<Item>, Error> {
+    fn parse_enum_variant(&mut self) -> Result<EnumVariant, Error> {
+        let name = self.parse_ident()?;
+        let mut position = name.position();
+
+        let fields = if self.check_for(Token::ControlPair(Control::Paren, PairKind::Open)) {
+            self.consume()?;
+            let fields = self.parse_inner_pair(
+                |p| p.parse_spec(),
+                Token::Op(Operator::Comma),
+                true,
+                true,
+                Control::Paren,
+            )?;
+            let end = self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
+            position = position.extended_to_token(end);
+            fields
+        } else {
+            vec![]
+        };
+
+        let discriminant = if self.check_for(Token::Op(Operator::Equal)) {
+            self.consume()?;
+            let expr = self.parse_expr()?;
+            position = position.extended_to(expr.as_ref());
+            Some(expr)
+        } else {
+            None
+        };
+
+        Ok(EnumVariant {
+            name,
+            fields,
+            discriminant,
+            position,
+        })
+    }
+
+    fn parse_function(
+        &mut self,
+        vis: Visibility,
+        attributes: Vec<Attribute>,
+    ) -> Result<Box<Item>, Error> {
         let position = self.current_position();
         self.expect(Token::Kw(Keyword::Fn))?;
         let name = self.parse_ident()?;
+        let type_params = if self.check_for(Token::ControlPair(Control::Brace, PairKind::Open)) {
+            self.consume()?;
+            let type_params = self.parse_inner_pair(
+                |p| p.parse_ident().map(Box::new),
+                Token::Op(Operator::Comma),
+                false,
+                true,
+                Control::Brace,
+            )?;
+            self.expect(Token::ControlPair(Control::Brace, PairKind::Close))?;
+            type_params.into_iter().map(|name| *name).collect()
+        } else {
+            vec![]
+        };
+        let (params, ret, body) = self.parse_signature()?;
+        let position = match &body {
+            FunctionBody::Block(expr) | FunctionBody::Expression(expr) => {
+                position.extended_to(expr.as_ref())
+            }
+        };
 
+        let kind = ItemKind::Function {
+            vis,
+            name,
+            attributes,
+            type_params,
+            params,
+            ret,
+            body,
+        };
+
+        Ok(Box::new(Item::new_with_position(kind, position)))
+    }
+
+    /// the parameters, return type and body following the name of a function, or the `fn` of a
+    /// function literal.
+    fn parse_signature(&mut self) -> Result<Signature, Error> {
         self.expect(Token::ControlPair(Control::Paren, PairKind::Open))?;
         self.allow_newline()?;
 
@@ -982,15 +1836,11 @@ impl<'src> Parser<'src> {
         self.expect(Token::ControlPair(Control::Paren, PairKind::Close))?;
 
         let ret = self.parse_spec()?;
-        let (body, position) = if self.check_for(Token::Op(Operator::Equal)) {
+        let body = if self.check_for(Token::Op(Operator::Equal)) {
             self.consume()?;
-            let expr = self.parse_expr()?;
-            let position = position.extended_to(expr.as_ref());
-            (FunctionBody::Expression(expr), position)
+            FunctionBody::Expression(self.parse_expr()?)
         } else if self.check_for(Token::ControlPair(Control::Bracket, PairKind::Open)) {
-            let expr = self.parse_expr()?;
-            let position = position.extended_to(expr.as_ref());
-            (FunctionBody::Block(expr), position)
+            FunctionBody::Block(self.parse_expr()?)
         } else {
             return Err(Error::unexpected_token_multi(
                 vec![
@@ -1001,16 +1851,7 @@ impl<'src> Parser<'src> {
             )
             .with_position(self.current_position()));
         };
-
-        let kind = ItemKind::Function {
-            vis,
-            name,
-            params,
-            ret,
-            body,
-        };
-
-        Ok(Box::new(Item::new_with_position(kind, position)))
+        Ok((params, ret, body))
     }
 
     fn parse_param(&mut self) -> Result<Box<Item>, Error> {
@@ -1043,7 +1884,7 @@ impl<'src> Parser<'src> {
     fn parse_field(&mut self) -> Result<Box<Item>, Error> {
         let vis = self.parse_possible_vis()?;
         if self.check_for(Token::Kw(Keyword::Fn)) {
-            self.parse_function(vis)
+            self.parse_function(vis, vec![])
         } else {
             let (names, spec, init, position) = self.parse_field_param()?;
             Ok(Box::new(Item::new_with_position(
@@ -1118,12 +1959,13 @@ impl<'src> Parser<'src> {
 
         let name = self.parse_ident()?;
 
-        let spec = if !self.check_for(Token::Op(Operator::Equal)) {
-            let spec = self.parse_spec()?;
-            position = position.extended_to(spec.as_ref());
-            Some(spec)
-        } else {
-            None
+        // without an annotation the type is inferred from the initial value.
+        let spec = match self.parse_spec()? {
+            spec if spec.is_infer() => None,
+            spec => {
+                position = position.extended_to(spec.as_ref());
+                Some(spec)
+            }
         };
 
         let init = if self.check_for(Token::Op(Operator::Equal)) {
@@ -1147,3 +1989,112 @@ impl<'src> Parser<'src> {
         )))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Parser;
+    use crate::system::File;
+
+    // the parser is run on its own thread so the recursion limit is reached well before the
+    // default test thread runs out of stack in debug builds.
+    fn parse(input: String, max_depth: usize) -> Result<(), String> {
+        std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(move || {
+                let file = File::raw_test(input);
+                let mut parser = Parser::new(&file);
+                parser.set_max_depth(max_depth);
+                parser
+                    .init()
+                    .and_then(|_| parser.parse_file())
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_long_chain_within_limit() {
+        // the operands of a left associative chain are not nested.
+        let input = vec!["1"; 1000].join("+");
+        assert!(parse(input, 64).is_ok());
+        // `??` is right associative, the rest of the chain is nested in each operator.
+        let input = vec!["x"; 32].join(" ?? ");
+        assert!(parse(input, 64).is_ok());
+        let input = vec!["x"; 100].join(" ?? ");
+        assert_eq!(
+            parse(input, 64),
+            Err("expression too deeply nested, the limit is 64".to_string())
+        );
+    }
+
+    #[test]
+    fn test_long_chain_is_not_nested() {
+        // however long a left associative chain is, only the nesting of its operands counts.
+        let input = vec!["1"; 100_000].join("+");
+        assert!(parse(input, 64).is_ok());
+        let input = format!("{}1{}", "(1 + ".repeat(100), ")".repeat(100));
+        assert_eq!(
+            parse(input, 64),
+            Err("expression too deeply nested, the limit is 64".to_string())
+        );
+    }
+
+    #[test]
+    fn test_match_patterns() {
+        let parse_match = |arms: &str| {
+            parse(format!("fn f(x i32) i32 = match x {{ {} }}\n", arms), 64)
+        };
+        assert!(parse_match("0 => 1, -1 => 2, Shape.Rect(w, _) => w, n => n").is_ok());
+        // the arms are separated by commas, newlines or both.
+        assert!(parse_match("true => 1\n false => 2,\n _ => 3").is_ok());
+        let ranges = "-5..-1 => 1, 0..LOW => 2, geometry.LOW..shapes.square.SIDES => 3";
+        assert!(parse_match(ranges).is_ok());
+        assert_eq!(
+            parse_match("0.. => 1"),
+            Err("expecting a pattern, found '=>'".to_string())
+        );
+        assert_eq!(
+            parse_match("1.5 => 1"),
+            Err("expecting a pattern, found '1.5'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_bench_items() {
+        let source = "bench \"list push\" {\n    1 + 2\n}\nfn bench() i32 = 1\n";
+        assert!(parse(source.to_string(), 64).is_ok());
+        // without a name it is whatever `bench` names.
+        assert!(parse("fn f(bench i32) i32 {\n    bench\n}\n".to_string(), 64).is_ok());
+        assert_eq!(
+            parse("bench \"add\" = 1\n".to_string(), 64),
+            Err("unexpected token: expected '{' found '='".to_string())
+        );
+    }
+
+    #[test]
+    fn test_trailing_blocks() {
+        let parse_body = |body: &str| parse(format!("fn f() {{\n    {}\n}}\n", body), 64);
+        assert!(parse_body("apply(1) { |x| x + 1 }").is_ok());
+        assert!(parse_body("run { || 1 }").is_ok());
+        assert!(parse_body("v.each { |x i64, y|\n        x + y\n    }").is_ok());
+        assert!(parse_body("v.fold(0) { |a, b| a.apply(b) { |c| c } }").is_ok());
+        // without the pipes it is a struct literal.
+        assert!(parse_body("Point { x: 1 }").is_ok());
+        assert_eq!(
+            parse_body("apply(1) { |x }"),
+            Err("unexpected token: expected '|' found '}'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_nested_parens_too_deep() {
+        let input = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(
+            parse(input, 64),
+            Err("expression too deeply nested, the limit is 64".to_string())
+        );
+    }
+}
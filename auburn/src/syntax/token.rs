@@ -3,6 +3,7 @@ use std::fmt::{Display, Formatter};
 
 use ordered_float::OrderedFloat;
 
+use crate::ir::ast::{AssignmentOp, BinaryOp};
 use crate::syntax::Position;
 
 // auto generate the mapping of keyword to string
@@ -15,6 +16,11 @@ macro_rules! string_mapping {
             )*
         }
 
+        impl $ty {
+            /// every variant, in the order they are declared.
+            pub const ALL: &'static [Self] = &[$(Self::$en,)*];
+        }
+
         impl Display for $ty {
             fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -59,6 +65,7 @@ string_mapping!(
     "loop" => Loop,
     "for" => For,
     "in" => In,
+    "as" => As,
     "Self" => SelfType,
     "self" => SelfLit,
     "continue" => Continue,
@@ -66,8 +73,10 @@ string_mapping!(
     "return" => Return,
     "true" => True,
     "false" => False,
+    "none" => NoneLit,
     "echo" => Echo,
     "stack_alloc" => StackAlloc,
+    "match" => Match,
     Keyword
 );
 
@@ -82,45 +91,40 @@ string_mapping!(
     ">=" => GreaterEq,
     "&" => Ampersand,
     "|" => Pipe,
+    "^" => Caret,
     "%" => Percent,
     "=" => Equal,
     "==" => EqualEqual,
     "!=" => BangEqual,
     "!"  => Bang,
     "." => Period,
+    ".." => PeriodPeriod,
+    "?." => QuestionPeriod,
+    "??" => QuestionQuestion,
     ";" => Semicolon,
     ":" => Colon,
     "," => Comma,
+    "@" => At,
     "+="  => PlusEq,
     "-="  => MinusEq,
     "*="  => AstriskEq,
     "/="  => SlashEq,
     "&=" => AmpersandEq,
     "|=" => PipeEq,
+    "^=" => CaretEq,
     "%=" => PercentEq,
     "<<" => LessLess,
     ">>" => GreaterGreater,
     "<<=" => LessLessEq,
     ">>=" => GreaterGreaterEq,
+    "=>" => FatArrow,
     Operator
 );
 
 impl Operator {
     #[inline]
     pub fn is_assignment(&self) -> bool {
-        match self {
-            Self::Equal
-            | Self::PlusEq
-            | Self::MinusEq
-            | Self::AstriskEq
-            | Self::SlashEq
-            | Self::AmpersandEq
-            | Self::PipeEq
-            | Self::PercentEq
-            | Self::LessLessEq
-            | Self::GreaterGreaterEq => true,
-            _ => false,
-        }
+        AssignmentOp::from_token(*self).is_some()
     }
 }
 
@@ -154,6 +158,8 @@ pub enum Token<'a> {
     Float(OrderedFloat<f64>),
     String(String),
     Comment(String),
+    /// only scanned when the lexer keeps trivia.
+    Whitespace,
     Newline,
     Eof,
 }
@@ -177,6 +183,15 @@ impl<'a> Token<'a> {
         }
     }
 
+    /// comments and whitespace, skipped by the parser.
+    #[inline]
+    pub fn is_trivia(&self) -> bool {
+        match self {
+            Self::Comment(_) | Self::Whitespace => true,
+            _ => false,
+        }
+    }
+
     #[inline]
     pub fn is_op(&self) -> bool {
         match self {
@@ -219,34 +234,12 @@ impl<'a> Token<'a> {
 
     pub fn precedence(&self) -> u8 {
         match self {
+            // the binary operators declare their precedence.
             Self::Op(op) => match op {
-                Operator::Slash | Operator::Astrick | Operator::Percent => 13,
-                Operator::Plus | Operator::Minus => 12,
-                Operator::LessLess | Operator::GreaterGreater => 11,
-                Operator::Less | Operator::Greater | Operator::EqualEqual | Operator::BangEqual => 10,
-                Operator::Ampersand => 9,
-                // Op::Carrot => 8,
-                // Op::Tilde => 7,
-                Operator::Pipe => 6,
-                // and => 5
-                // or  => 4
-                Operator::LessEq
-                | Operator::GreaterEq => 4,
-                Operator::Bang => 3,
-                Operator::Equal
-                | Operator::PlusEq
-                | Operator::MinusEq
-                | Operator::SlashEq
-                | Operator::AstriskEq
-                //| Op::AstrickAstrickEq
-                | Operator::PercentEq
-                | Operator::PipeEq
-                | Operator::AmpersandEq
-                // | Operator::CarrotEq
-                | Operator::LessLessEq
-                | Operator::GreaterGreaterEq => 2,
-                // Op::Dollar | Op::Question => 1,
-                _ => 0,
+                Operator::PeriodPeriod => 5,
+                Operator::Bang | Operator::QuestionQuestion => 3,
+                op if op.is_assignment() => 2,
+                op => BinaryOp::from_token(*op).map_or(0, |op| op.declaration().precedence),
             },
             _ => 0,
         }
@@ -255,6 +248,7 @@ impl<'a> Token<'a> {
     pub fn associativity(&self) -> Associative {
         match self {
             Self::Op(op) => match op {
+                Operator::QuestionQuestion => Associative::Right,
                 _ => Associative::Left,
             },
             _ => Associative::None,
@@ -309,6 +303,10 @@ impl<'a> PToken<'a> {
         self.token.is_comment()
     }
 
+    pub fn is_trivia(&self) -> bool {
+        self.token.is_trivia()
+    }
+
     pub fn precedence(&self) -> u8 {
         self.token.precedence()
     }
@@ -335,6 +333,7 @@ impl<'a> std::fmt::Display for Token<'a> {
                 },
             },
             Token::Comment(_) => write!(f, "comment"),
+            Token::Whitespace => write!(f, "whitespace"),
             Token::Integer(val) => write!(f, "{}", val),
             Token::Float(val) => write!(f, "{}", val),
             Token::String(val) => write!(f, "{}", val),
@@ -1,14 +1,19 @@
 use std::convert::TryFrom;
 use std::str::Chars;
 
+use ordered_float::OrderedFloat;
+
 use crate::error::Error;
 use crate::syntax::token::{Control, Keyword, Operator, PToken, Token};
 use crate::syntax::{Coord, FilePos, PairKind, Position, Span};
 use crate::system::{File, FileId};
 
+#[derive(Clone)]
 pub struct Lexer<'src> {
-    /// the file to be processed
-    file: &'src File,
+    /// the source to be processed
+    source: &'src str,
+    /// the file the source is from
+    file: FileId,
     /// an iterator over the characters in the string.
     chars: Chars<'src>,
     /// current character
@@ -17,16 +22,24 @@ pub struct Lexer<'src> {
     span: Span,
     // the file position
     file_pos: FilePos,
+    /// whitespace is scanned as a token instead of being skipped.
+    trivia: bool,
 }
 
 impl<'src> Lexer<'src> {
     pub fn new(file: &'src File) -> Self {
+        Self::from_source(file.content(), file.id(), false)
+    }
+
+    fn from_source(source: &'src str, file: FileId, trivia: bool) -> Self {
         let mut lexer = Self {
+            source,
             file,
-            chars: file.content().chars(),
+            chars: source.chars(),
             ch: None,
             span: Span(0, 0),
             file_pos: FilePos::default(),
+            trivia,
         };
         lexer.init();
 
@@ -34,7 +47,7 @@ impl<'src> Lexer<'src> {
     }
 
     pub fn file(&self) -> FileId {
-        self.file.id()
+        self.file
     }
 
     fn init(&mut self) {
@@ -78,11 +91,14 @@ impl<'src> Lexer<'src> {
         PToken::new(source, token, Position::new(span, file_pos, self.file()))
     }
 
+    fn is_whitespace(ch: char) -> bool {
+        (ch.is_whitespace() || ch == '\r') && ch != '\n'
+    }
+
     fn skip_whitespace(&mut self) {
         // move past all of the whitespace
-        let whitespace_check = |ch: char| (ch.is_whitespace() || ch == '\r') && ch != '\n';
-        if self.check(whitespace_check) {
-            while self.check(whitespace_check) {
+        if self.check(Self::is_whitespace) {
+            while self.check(Self::is_whitespace) {
                 self.advance();
             }
 
@@ -106,7 +122,11 @@ impl<'src> Lexer<'src> {
 
     /// retrieves the sub string of the current span
     fn examine_span(&self) -> &'src str {
-        &self.file.content()[self.span.0..self.span.1]
+        &self.source[self.span.0..self.span.1]
+    }
+
+    fn error_position(&self) -> Position {
+        Position::new(self.span, self.file_pos, self.file())
     }
 
     /// If is use this interface, I do not need the Error token.
@@ -114,6 +134,18 @@ impl<'src> Lexer<'src> {
         self.scan()
     }
 
+    /// moves past the source scanned before an error was found, at least one character.
+    fn skip_error(&mut self) -> Position {
+        if self.span.0 == self.span.1 {
+            self.advance();
+        }
+
+        let position = self.error_position();
+        self.file_pos.start = self.file_pos.end;
+        self.span.0 = self.span.1;
+        position
+    }
+
     fn scan_ident_or_keyword(&mut self) -> Result<Token<'src>, Error> {
         //let is_unicode = |ch: char| (!ch.is_control() && !ch.is_whitespace() && !ch.is_alphanumeric());
         let is_unicode = |_ch: char| false;
@@ -132,26 +164,28 @@ impl<'src> Lexer<'src> {
     }
 
     fn scan_number(&mut self) -> Result<Token<'src>, Error> {
-        while self.check(char::is_numeric) {
+        let is_digit = |ch: char| ch.is_ascii_digit();
+        while self.check(is_digit) {
             self.advance();
         }
 
         let mut is_float = false;
 
-        if self.check_for('.') {
-            self.advance();
-            if self.check(|e| e.is_numeric() || e == 'e' || e == 'E') {
-                is_float = true;
-                self.advance();
-            } else {
-                let value = self.examine_span();
-                let value = value.parse::<u64>().unwrap();
-                let kind = Token::Integer(value);
+        // the number after the period of a field access is a tuple index, `t.0.1` is two of them.
+        let scanned = &self.source[..self.span.0];
+        let tuple_index = scanned.ends_with('.') && !scanned.ends_with("..");
 
-                return Ok(kind);
-            }
+        // a period that is not followed by a fraction or exponent is left for a field access.
+        let fraction = self.chars.clone().next();
+        if !tuple_index
+            && self.check_for('.')
+            && fraction.is_some_and(|e| e.is_ascii_digit() || e == 'e' || e == 'E')
+        {
+            is_float = true;
+            self.advance();
+            self.advance();
 
-            while self.check(char::is_numeric) {
+            while self.check(is_digit) {
                 self.advance();
             }
         }
@@ -164,7 +198,7 @@ impl<'src> Lexer<'src> {
                 self.advance();
             }
 
-            while self.check(char::is_numeric) {
+            while self.check(is_digit) {
                 self.advance();
             }
         }
@@ -172,13 +206,19 @@ impl<'src> Lexer<'src> {
         let value = self.examine_span();
 
         if is_float {
-            let value = value.parse::<f64>().unwrap();
-            let kind = Token::Float(value.into());
-            Ok(kind)
+            match value.parse::<f64>() {
+                Ok(value) => Ok(Token::Float(value.into())),
+                Err(_) => Err(Error::invalid_number(value).with_position(self.error_position())),
+            }
         } else {
-            let value = value.parse::<u64>().unwrap();
-            let kind = Token::Integer(value);
-            Ok(kind)
+            self.integer_literal(value)
+        }
+    }
+
+    fn integer_literal(&self, value: &str) -> Result<Token<'src>, Error> {
+        match value.parse::<u64>() {
+            Ok(value) => Ok(Token::Integer(value)),
+            Err(_) => Err(Error::invalid_number(value).with_position(self.error_position())),
         }
     }
 
@@ -188,11 +228,7 @@ impl<'src> Lexer<'src> {
 
         if self.ch.is_none() {
             // let (line, column) = self.coords.start();
-            return Err(Error::unexpected_eof().with_position(Position::new(
-                self.span,
-                self.file_pos,
-                self.file(),
-            )));
+            return Err(Error::unexpected_eof().with_position(self.error_position()));
         }
 
         let val = match self.ch.unwrap() {
@@ -201,7 +237,7 @@ impl<'src> Lexer<'src> {
             '\\' => '\\' as u8,
             _ => {
                 return Err(Error::unknown_escape_character(self.ch.unwrap())
-                    .with_position(Position::new(self.span, self.file_pos, self.file())));
+                    .with_position(self.error_position()));
             }
         };
         Ok(val as char)
@@ -210,10 +246,18 @@ impl<'src> Lexer<'src> {
     fn scan_string(&mut self) -> Result<Token<'src>, Error> {
         let _start_index = self.span.0 + 1;
         let mut value = String::new();
+        // an invalid escape is reported once the whole string is scanned.
+        let mut invalid_escape = None;
 
         while self.ch.is_some() && !self.check_for('"') {
             let ch = if self.check_for('\\') {
-                self.validate_escape()?
+                match self.validate_escape() {
+                    Ok(ch) => ch,
+                    Err(err) => {
+                        invalid_escape.get_or_insert(err);
+                        continue;
+                    }
+                }
             } else {
                 self.ch.unwrap()
             };
@@ -224,24 +268,32 @@ impl<'src> Lexer<'src> {
         }
 
         if self.ch.is_none() {
-            return Err(Error::expected_quotation().with_position(Position::new(self.span, self.file_pos, self.file())));
+            return Err(Error::expected_quotation().with_position(self.error_position()));
         }
 
         self.advance();
 
-        let kind = Token::String(value);
-
-        Ok(kind)
+        match invalid_escape {
+            Some(err) => Err(err),
+            None => Ok(Token::String(value)),
+        }
     }
 
     fn scan(&mut self) -> Result<PToken<'src>, Error> {
+        if self.trivia && self.check(Self::is_whitespace) {
+            while self.check(Self::is_whitespace) {
+                self.advance();
+            }
+            return Ok(self.complete_token(Token::Whitespace));
+        }
+
         self.skip_whitespace();
         if self.ch.is_none() {
             Ok(self.complete_token(Token::Eof))
-        } else if self.check(char::is_alphabetic) {
+        } else if self.check(|ch| ch.is_alphabetic() || ch == '_') {
             let t = self.scan_ident_or_keyword()?;
             Ok(self.complete_token(t))
-        } else if self.check(char::is_numeric) {
+        } else if self.check(|ch| ch.is_ascii_digit()) {
             let t = self.scan_number()?;
             Ok(self.complete_token(t))
         } else {
@@ -257,10 +309,15 @@ impl<'src> Lexer<'src> {
                 '[' => Token::ControlPair(Control::Brace, PairKind::Open),
                 ']' => Token::ControlPair(Control::Brace, PairKind::Close),
                 '"' => self.scan_string()?,
+                '.' if self.check_for('.') => {
+                    self.advance();
+                    Token::Op(Operator::PeriodPeriod)
+                }
                 '.' => Token::Op(Operator::Period),
                 ',' => Token::Op(Operator::Comma),
                 ':' => Token::Op(Operator::Colon),
                 ';' => Token::Op(Operator::Semicolon),
+                '@' => Token::Op(Operator::At),
                 '+' => {
                     if self.check_for('=') {
                         self.advance();
@@ -287,7 +344,8 @@ impl<'src> Lexer<'src> {
                 }
                 '/' => {
                     if self.check_for('/') {
-                        while !self.check_for('\n') {
+                        // the carriage return of a crlf line ending is not part of the comment.
+                        while self.check(|ch| ch != '\n' && ch != '\r') {
                             self.advance();
                         }
                         let value = self.examine_span();
@@ -315,6 +373,14 @@ impl<'src> Lexer<'src> {
                         Token::Op(Operator::Pipe)
                     }
                 }
+                '^' => {
+                    if self.check_for('=') {
+                        self.advance();
+                        Token::Op(Operator::CaretEq)
+                    } else {
+                        Token::Op(Operator::Caret)
+                    }
+                }
                 '%' => {
                     if self.check_for('=') {
                         self.advance();
@@ -328,6 +394,9 @@ impl<'src> Lexer<'src> {
                     if self.check_for('=') {
                         self.advance();
                         Token::Op(Operator::EqualEqual)
+                    } else if self.check_for('>') {
+                        self.advance();
+                        Token::Op(Operator::FatArrow)
                     } else {
                         Token::Op(Operator::Equal)
                     }
@@ -374,12 +443,16 @@ impl<'src> Lexer<'src> {
                         Token::Op(Operator::Bang)
                     }
                 }
+                '?' if self.check_for('.') => {
+                    self.advance();
+                    Token::Op(Operator::QuestionPeriod)
+                }
+                '?' if self.check_for('?') => {
+                    self.advance();
+                    Token::Op(Operator::QuestionQuestion)
+                }
                 _ => {
-                    return Err(Error::invalid_character(ch).with_position(Position::new(
-                        self.span,
-                        self.file_pos,
-                        self.file(),
-                    )));
+                    return Err(Error::invalid_character(ch).with_position(self.error_position()));
                 }
             };
 
@@ -388,6 +461,7 @@ impl<'src> Lexer<'src> {
     }
 }
 
+#[derive(Clone)]
 pub struct TokenCursor<'src> {
     lexer: Lexer<'src>,
     is_eof: bool,
@@ -424,8 +498,87 @@ impl<'src> std::iter::Iterator for TokenCursor<'src> {
     }
 }
 
+/// options of [`tokenize`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct TokenizeOptions {
+    /// report comments and whitespace, without them the tokens are the ones the parser sees.
+    pub trivia: bool,
+}
+
+/// kind of a token returned by [`tokenize`]. Tools outside of the compiler depend on it, a kind
+/// may be added but an existing one is never changed or removed.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum TokenKind {
+    Keyword(Keyword),
+    Operator(Operator),
+    Ident,
+    Open(Control),
+    Close(Control),
+    Integer(u64),
+    Float(OrderedFloat<f64>),
+    /// the value of the string, escapes are decoded.
+    String(String),
+    Newline,
+    Comment,
+    Whitespace,
+    /// source that is not a token along with why, scanning continues after it.
+    Error(String),
+}
+
+/// a token returned by [`tokenize`], the span is the byte range of the source it covers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceToken {
+    pub kind: TokenKind,
+    pub span: Span,
+    pub start: Coord,
+    pub end: Coord,
+}
+
+/// scans all of source, this never fails or panics: source that is not a token is an error
+/// token. Source that is not utf-8 is decoded with `String::from_utf8_lossy` first, an invalid
+/// sequence then becomes an error token at its replacement character.
+pub fn tokenize(source: &str, options: TokenizeOptions) -> Vec<SourceToken> {
+    let mut lexer = Lexer::from_source(source, FileId::invalid(), options.trivia);
+    let mut tokens = vec![];
+    loop {
+        let (kind, position) = match lexer.scan() {
+            Ok(token) if token.is_eof() => break,
+            Ok(token) if token.is_comment() && !options.trivia => continue,
+            Ok(token) => {
+                let position = token.position();
+                let kind = match token.to_token() {
+                    Token::Kw(keyword) => TokenKind::Keyword(keyword),
+                    Token::Op(operator) => TokenKind::Operator(operator),
+                    Token::Ident(_) => TokenKind::Ident,
+                    Token::ControlPair(control, PairKind::Open) => TokenKind::Open(control),
+                    Token::ControlPair(control, PairKind::Close) => TokenKind::Close(control),
+                    Token::Integer(value) => TokenKind::Integer(value),
+                    Token::Float(value) => TokenKind::Float(value),
+                    Token::String(value) => TokenKind::String(value),
+                    Token::Comment(_) => TokenKind::Comment,
+                    Token::Whitespace => TokenKind::Whitespace,
+                    Token::Newline => TokenKind::Newline,
+                    Token::Eof => unreachable!(),
+                };
+                (kind, position)
+            }
+            Err(err) => (TokenKind::Error(err.to_string()), lexer.skip_error()),
+        };
+
+        tokens.push(SourceToken {
+            kind,
+            span: position.span(),
+            start: position.start(),
+            end: position.end(),
+        });
+    }
+    tokens
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{tokenize, SourceToken, TokenKind, TokenizeOptions};
     use crate::syntax::token::{Operator, PToken};
     use crate::syntax::tokenizer::Lexer;
     use crate::syntax::{Coord, FilePos, Keyword, Position, Span, Token};
@@ -488,6 +641,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_fat_arrow_tokenizer() {
+        let input = "=>";
+        let input = File::raw_test(input.to_string());
+        let mut tokenizer = Lexer::new(&input);
+        assert_eq!(
+            tokenizer.scan().unwrap(),
+            PToken::new(
+                "=>",
+                Token::Op(Operator::FatArrow),
+                Position::new(
+                    Span(0, 2),
+                    FilePos::new(Coord(1, 1), Coord(1, 3)),
+                    FileId(0),
+                ),
+            )
+        );
+    }
+
     #[test]
     fn test_integer_tokenizer() {
         let input = "123";
@@ -526,6 +698,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_tuple_index_tokenizer() {
+        let tokens = tokenize("t.0.1.e", TokenizeOptions { trivia: false });
+        let kinds = tokens.into_iter().map(|token| token.kind).collect::<Vec<_>>();
+        let period = TokenKind::Operator(Operator::Period);
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Ident,
+                period.clone(),
+                TokenKind::Integer(0),
+                period.clone(),
+                TokenKind::Integer(1),
+                period,
+                TokenKind::Ident,
+            ]
+        );
+    }
+
     #[test]
     fn test_complex_float_tokenizer() {
         let input = "1.3e-5";
@@ -651,6 +842,124 @@ mod tests {
         );
     }
 
+    /// the spans of the tokens cover all of source in order without a gap or overlap.
+    fn assert_tiles(source: &str, tokens: &[SourceToken]) {
+        let mut end = 0;
+        for token in tokens {
+            assert_eq!(token.span.start(), end, "gap or overlap before {:?}", token);
+            assert!(
+                token.span.end() > token.span.start(),
+                "empty token {:?}",
+                token
+            );
+            end = token.span.end();
+        }
+        assert_eq!(end, source.len());
+    }
+
+    #[test]
+    fn test_tokenize_tiles_the_source() {
+        let source = "// a comment\r\n\
+            @flags\n\
+            enum Perm { Read = 1, Write }\n\
+            struct Point {\n\tpub x f64\n    fn len(mut self) f64 = self.x * 2.5e-3\n}\n\
+            fn main() i64 {\n    mut n i64 = 0\n    n += 1 << 2\n    n >>= 1\n\
+            \x20   let t = (1, \"a\\tb\\n\", 'c')\n    t.0.max ?? 1.0\n\
+            \x20   if n != 0 && n <= 9 { n } elif !true { -n } else { n % 3 }\n\
+            \x20   x?.y ?? none ^ n | n & n // trailing\n}\n\
+            £ \"bad \\q escape\" 99999999999999999999 ünïcode 1.e\n";
+        let tokens = tokenize(source, TokenizeOptions { trivia: true });
+        assert_tiles(source, &tokens);
+
+        let kinds = |text: &str| {
+            tokens
+                .iter()
+                .filter(|token| &source[token.span.start()..token.span.end()] == text)
+                .map(|token| token.kind.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds("// a comment"), [TokenKind::Comment]);
+        assert_eq!(kinds("\r\n"), []);
+        assert_eq!(kinds("\r"), [TokenKind::Whitespace]);
+        assert_eq!(
+            kinds("\"a\\tb\\n\""),
+            [TokenKind::String("a\tb\n".to_string())]
+        );
+        assert_eq!(kinds("2.5e-3"), [TokenKind::Float(0.0025.into())]);
+        assert_eq!(kinds("?."), [TokenKind::Operator(Operator::QuestionPeriod)]);
+        assert_eq!(kinds("ünïcode"), [TokenKind::Ident]);
+        assert_eq!(kinds("elif"), [TokenKind::Keyword(Keyword::Elif)]);
+        // the period after the tuple index in `t.0.max` is not part of a float.
+        assert_eq!(kinds("0"), vec![TokenKind::Integer(0); 3]);
+
+        // errors are reported in the stream and scanning continues after them.
+        assert_eq!(
+            kinds("£"),
+            [TokenKind::Error("invalid character: '£'".to_string())]
+        );
+        assert_eq!(
+            kinds("\"bad \\q escape\""),
+            [TokenKind::Error(
+                "unknown escaped character: 'q'".to_string()
+            )]
+        );
+        assert_eq!(
+            kinds("99999999999999999999"),
+            [TokenKind::Error(
+                "invalid number literal: '99999999999999999999'".to_string()
+            )]
+        );
+        assert_eq!(
+            kinds("1.e"),
+            [TokenKind::Error(
+                "invalid number literal: '1.e'".to_string()
+            )]
+        );
+        let last = tokens.last().unwrap();
+        assert_eq!(
+            (last.kind.clone(), last.start, last.end),
+            (TokenKind::Newline, Coord(17, 51), Coord(18, 1))
+        );
+
+        // without trivia the tokens are the ones the parser sees.
+        let tokens = tokenize(source, TokenizeOptions::default());
+        assert!(tokens
+            .iter()
+            .all(|token| !matches!(token.kind, TokenKind::Comment | TokenKind::Whitespace)));
+        assert_eq!(tokens[0].kind, TokenKind::Newline);
+    }
+
+    #[test]
+    fn test_tokenize_never_panics() {
+        let unterminated = "let s = \"abc\\";
+        let tokens = tokenize(unterminated, TokenizeOptions { trivia: true });
+        assert_tiles(unterminated, &tokens);
+        assert_eq!(
+            tokens.last().unwrap().kind,
+            TokenKind::Error("expecting '\"' found eof".to_string())
+        );
+
+        // a comment at the end of the source and invalid utf-8.
+        let source = String::from_utf8_lossy(b"x // end\xff").into_owned();
+        let tokens = tokenize(&source, TokenizeOptions { trivia: true });
+        assert_tiles(&source, &tokens);
+        assert_eq!(tokens.last().unwrap().kind, TokenKind::Comment);
+        let source = String::from_utf8_lossy(b"a\xc3b \"\xff\"").into_owned();
+        let tokens = tokenize(&source, TokenizeOptions { trivia: true });
+        assert_tiles(&source, &tokens);
+        assert_eq!(
+            tokens[1].kind,
+            TokenKind::Error("invalid character: '\u{fffd}'".to_string())
+        );
+
+        // every prefix of a source ends in the middle of some token.
+        let source = "fn f() = \"a\\n\" ?? 1.5e+3 >>= x?.y // c\n";
+        for end in (0..=source.len()).filter(|end| source.is_char_boundary(*end)) {
+            let tokens = tokenize(&source[..end], TokenizeOptions { trivia: true });
+            assert_tiles(&source[..end], &tokens);
+        }
+    }
+
     // #[test]
     // fn hierarchy_tokenizer() {
     //     let input = "{+.}";
@@ -1,9 +1,8 @@
-use std::fs::read_to_string;
+use std::fs::metadata;
+use std::io::{ErrorKind, Read};
 pub use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use itertools::Itertools;
-
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct FileId(pub usize);
 
@@ -22,12 +21,17 @@ impl FileId {
     }
 }
 
+/// the largest source file that is read, in bytes, 4 GiB less a byte so every byte offset of a
+/// file fits in a u32. A larger file is refused with a file too large error before it is read.
+pub const MAX_SOURCE_LEN: u64 = u32::MAX as u64;
+
 #[derive(Debug, Clone)]
 pub struct File {
     content: String,
     path: PathBuf,
     fid: FileId,
-    lines: Vec<String>,
+    /// the byte offset each line starts at, the lines are sliced from the content.
+    line_starts: Vec<usize>,
 }
 
 impl File {
@@ -37,46 +41,73 @@ impl File {
         println!("File name: {}", path_buff.display());
         println!("Ext: {:?}", path_buff.extension());
 
-        let content = read_to_string(path)?;
+        // the size is checked before reading so a huge file is not read only to be refused.
+        Self::check_len(metadata(&path)?.len())?;
+        let content = Self::read_source(std::fs::File::open(path)?)?;
 
-        let mut file = Self {
-            content,
-            path: path_buff,
-            fid: FileId::next(),
-            lines: vec![],
-        };
+        Ok(Self::new(path_buff, content, FileId::next()))
+    }
 
-        file.separate_lines();
+    /// a file held in memory, its path is only the name it is reported by.
+    pub fn from_source<P: Into<PathBuf>>(path: P, content: String) -> Self {
+        Self::new(path.into(), content, FileId::next())
+    }
 
-        Ok(file)
+    /// reads all of the source from reader, at most MAX_SOURCE_LEN bytes of it.
+    pub fn read_source<R: Read>(reader: R) -> Result<String, std::io::Error> {
+        let mut content = String::new();
+        reader.take(MAX_SOURCE_LEN + 1).read_to_string(&mut content)?;
+        Self::check_len(content.len() as u64)?;
+        Ok(content)
     }
 
-    fn separate_lines(&mut self) {
-        self.lines = self.content.lines().map(|x| x.to_string()).collect_vec();
+    fn check_len(len: u64) -> Result<(), std::io::Error> {
+        if len > MAX_SOURCE_LEN {
+            let message = format!(
+                "file too large, it is {} bytes and the limit is {} bytes",
+                len, MAX_SOURCE_LEN
+            );
+            return Err(std::io::Error::new(ErrorKind::FileTooLarge, message));
+        }
+        Ok(())
     }
 
-    #[cfg(test)]
-    pub fn raw_test(content: String) -> Self {
+    fn new(path: PathBuf, content: String, fid: FileId) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
         Self {
             content,
-            path: PathBuf::new(),
-            fid: FileId(0),
-            lines: vec![],
+            path,
+            fid,
+            line_starts,
         }
     }
 
+    #[cfg(test)]
+    pub fn raw_test(content: String) -> Self {
+        Self::new(PathBuf::from("test.au"), content, FileId(0))
+    }
+
+    /// the text of a line without its line ending, the first line is 1. A line past the end of
+    /// the file is empty.
     pub fn get_line(&self, line: usize) -> &str {
-        self.lines[line - 1].as_str()
+        let start = match line.checked_sub(1).and_then(|index| self.line_starts.get(index)) {
+            Some(start) => *start,
+            None => return "",
+        };
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.content.len());
+        let text = &self.content[start..end];
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        text.strip_suffix('\r').unwrap_or(text)
     }
 
     pub fn get_lines(&self, start: usize, end: usize) -> Vec<&str> {
-        let mut lines = Vec::with_capacity(end - start + 1);
-
-        for line in start..(end + 1) {
-            lines.push(self.get_line(line - 1));
-        }
-
-        lines
+        (start..=end).map(|line| self.get_line(line)).collect()
     }
 
     pub fn content(&self) -> &str {
@@ -107,3 +138,55 @@ impl File {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{File, MAX_SOURCE_LEN};
+    use crate::syntax::Parser;
+    use std::io::{repeat, ErrorKind, Read};
+
+    #[test]
+    fn test_lines_are_sliced_from_the_content() {
+        let file = File::raw_test("fn main() i64 =\r\n    1\n\nlast".to_string());
+        assert_eq!(file.get_line(1), "fn main() i64 =");
+        assert_eq!(file.get_line(2), "    1");
+        assert_eq!(file.get_line(3), "");
+        assert_eq!(file.get_line(4), "last");
+        assert_eq!(file.get_lines(1, 2), vec!["fn main() i64 =", "    1"]);
+        // a line outside of the file is empty instead of a panic.
+        assert_eq!(file.get_line(0), "");
+        assert_eq!(file.get_line(5), "");
+    }
+
+    #[test]
+    fn test_source_over_the_limit_is_refused() {
+        assert!(File::check_len(MAX_SOURCE_LEN).is_ok());
+        let err = File::check_len(MAX_SOURCE_LEN + 1).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+        assert!(err.to_string().starts_with("file too large"));
+    }
+
+    /// a program padded with spaces to len bytes.
+    fn generated_source(len: u64) -> impl Read {
+        let program = "fn main() i64 = 0\n".as_bytes();
+        program.chain(repeat(b' ').take(len - program.len() as u64))
+    }
+
+    // reads and parses 4 GiB of source, run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_source_at_the_limit() {
+        let content = File::read_source(generated_source(MAX_SOURCE_LEN)).unwrap();
+        let file = File::from_source("large.au", content);
+        assert_eq!(file.content().len() as u64, MAX_SOURCE_LEN);
+        assert_eq!(file.get_line(1), "fn main() i64 = 0");
+        let mut parser = Parser::new(&file);
+        parser.init().unwrap();
+        assert!(parser.parse_file().is_ok());
+        std::mem::drop(parser);
+        std::mem::drop(file);
+
+        let err = File::read_source(generated_source(MAX_SOURCE_LEN + 1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::FileTooLarge);
+    }
+}
@@ -25,6 +25,14 @@ impl FileMap {
         Ok(self.files.get(&id).unwrap().clone())
     }
 
+    /// adds source that is not read from disk, it is found by name like an opened file.
+    pub fn add_virtual_file<P: AsRef<Path>>(&mut self, name: P, source: String) -> Rc<File> {
+        let file = Rc::new(File::from_source(name.as_ref(), source));
+        self.paths.insert(name.as_ref().to_path_buf(), file.id());
+        self.files.insert(file.id(), file.clone());
+        file
+    }
+
     pub fn file_by_path<P: AsRef<Path>>(&self, path: P) -> Option<Rc<File>> {
         if let Some(id) = self.paths.get(&path.as_ref().to_path_buf()) {
             self.file_by_id(id)
@@ -1,5 +1,11 @@
-pub use file::{File, FileId};
+pub use file::{File, FileId, MAX_SOURCE_LEN};
 pub use file_map::FileMap;
+pub use modules::{discover_sources, module_name, SOURCE_EXTENSION};
+pub use resolver::{
+    resolve_imports, FileSystemResolver, ModuleResolver, ModuleSource, ResolveError,
+};
 
 mod file;
 mod file_map;
+mod modules;
+mod resolver;
@@ -0,0 +1,43 @@
+use std::io;
+
+use crate::system::file::{Path, PathBuf};
+
+/// extension of a source file.
+pub const SOURCE_EXTENSION: &str = "au";
+
+/// the source files under root relative to it. They are sorted so a program is always
+/// resolved and linked in the same order, hidden files and directories are skipped.
+pub fn discover_sources(root: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    fn visit(root: &Path, relative: &Path, sources: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+        for entry in std::fs::read_dir(root.join(relative))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let path = relative.join(&name);
+            if entry.file_type()?.is_dir() {
+                visit(root, &path, sources)?;
+            } else if path.extension().is_some_and(|ext| ext == SOURCE_EXTENSION) {
+                sources.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut sources = vec![];
+    visit(root, Path::new(""), &mut sources)?;
+    sources.sort();
+    Ok(sources)
+}
+
+/// the module defined by a source file, `shapes/circle.au` is the module `shapes.circle`.
+pub fn module_name(relative: &Path) -> String {
+    let module = relative.with_extension("");
+    module
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(".")
+}
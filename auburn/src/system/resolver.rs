@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::analysis::ParsedModule;
+use crate::error::Error;
+use crate::syntax::ParsedFile;
+use crate::system::file::{File, PathBuf};
+use crate::system::modules::{module_name, SOURCE_EXTENSION};
+use crate::system::FileMap;
+
+/// the source of a module found by a resolver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleSource {
+    /// the name the module is known by however it was imported, two imports resolving to the
+    /// same name load it once. Diagnostics and stack traces name the module by it.
+    pub name: String,
+    pub source: String,
+}
+
+impl ModuleSource {
+    pub fn new(name: &str, source: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            source: source.to_owned(),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError {
+    #[error("no module is found by that name")]
+    NotFound,
+
+    #[error("{}", reason)]
+    Failed { reason: String },
+}
+
+/// finds the modules a program imports, a host embedding the compiler serves them from memory,
+/// a database or anything else that is not the file system.
+pub trait ModuleResolver {
+    /// the module imported as path, `shapes.circle`, by the module named importer.
+    fn resolve(&self, importer: &str, path: &str) -> Result<ModuleSource, ResolveError>;
+}
+
+/// resolves `shapes.circle` to the file `shapes/circle.au` of the first root that has it.
+pub struct FileSystemResolver {
+    roots: Vec<PathBuf>,
+}
+
+impl FileSystemResolver {
+    pub fn new(roots: Vec<PathBuf>) -> Self {
+        Self { roots }
+    }
+}
+
+impl ModuleResolver for FileSystemResolver {
+    fn resolve(&self, _importer: &str, path: &str) -> Result<ModuleSource, ResolveError> {
+        let relative = path
+            .split('.')
+            .collect::<PathBuf>()
+            .with_extension(SOURCE_EXTENSION);
+        for root in &self.roots {
+            let full = root.join(&relative);
+            if !full.is_file() {
+                continue;
+            }
+            return match std::fs::read_to_string(&full) {
+                Ok(source) => Ok(ModuleSource {
+                    name: module_name(&relative),
+                    source,
+                }),
+                Err(err) => Err(ResolveError::Failed {
+                    reason: format!("{}: {}", full.display(), err),
+                }),
+            };
+        }
+        Err(ResolveError::NotFound)
+    }
+}
+
+/// adds the modules imported by modules through resolver, and those they import in turn. An
+/// import naming one of the modules is not resolved. A module is loaded once by the name the
+/// resolver gives it, an import spelled otherwise is recorded in `ParsedModule::imports`.
+pub fn resolve_imports<F>(
+    modules: &mut Vec<ParsedModule>,
+    file_map: &mut FileMap,
+    resolver: &dyn ModuleResolver,
+    mut parse: F,
+) -> Result<(), Error>
+where
+    F: FnMut(&File) -> Result<ParsedFile, Error>,
+{
+    let mut next = 0;
+    while next < modules.len() {
+        let imports = modules[next]
+            .file
+            .imports
+            .iter()
+            .map(|import| (import.module_name(), import.position))
+            .collect::<Vec<_>>();
+
+        let mut resolved = HashMap::new();
+        for (path, position) in imports {
+            if resolved.contains_key(&path) || modules.iter().any(|module| module.name == path) {
+                continue;
+            }
+            let importer = modules[next].name.as_str();
+            let found = resolver
+                .resolve(importer, path.as_str())
+                .map_err(|err| {
+                    Error::unresolved_import(path.as_str(), err).with_position(position)
+                })?;
+            if !modules.iter().any(|module| module.name == found.name) {
+                let file = file_map.add_virtual_file(found.name.as_str(), found.source);
+                modules.push(ParsedModule {
+                    name: found.name.clone(),
+                    path: PathBuf::from(found.name.as_str()),
+                    file: parse(&file)?,
+                    imports: HashMap::new(),
+                });
+            }
+            resolved.insert(path, found.name);
+        }
+
+        resolved.retain(|path, name| path != name);
+        modules[next].imports.extend(resolved);
+        next += 1;
+    }
+    Ok(())
+}
@@ -1,5 +1,7 @@
+pub use substitution::Substitution;
 pub use ty::{Type, TypeKind};
 pub use type_map::TypeMap;
 
+mod substitution;
 mod ty;
 mod type_map;
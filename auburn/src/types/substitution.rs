@@ -0,0 +1,93 @@
+use std::rc::Rc;
+
+use crate::analysis::EntityRef;
+use crate::types::{Type, TypeKind, TypeMap};
+
+/// the types given to the type parameters of a generic function, an instance of the function
+/// is its signature and body with each parameter replaced by its type.
+#[derive(Debug, Clone, Default)]
+pub struct Substitution {
+    bindings: Vec<(EntityRef, Rc<Type>)>,
+}
+
+impl Substitution {
+    pub fn new(params: &[EntityRef], args: &[Rc<Type>]) -> Self {
+        Self {
+            bindings: params.iter().cloned().zip(args.iter().cloned()).collect(),
+        }
+    }
+
+    pub fn get(&self, param: &EntityRef) -> Option<Rc<Type>> {
+        let id = param.borrow().id();
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound.borrow().id() == id)
+            .map(|(_, ty)| ty.clone())
+    }
+
+    pub fn bind(&mut self, param: EntityRef, ty: Rc<Type>) {
+        self.bindings.push((param, ty));
+    }
+
+    /// ty with the parameters bound here replaced, the types that do not name one are
+    /// returned as they are.
+    pub fn apply(&self, ty: &Rc<Type>, type_map: &mut TypeMap) -> Rc<Type> {
+        if !ty.kind().has_type_params() {
+            return ty.clone();
+        }
+        let kind = match ty.kind() {
+            TypeKind::Param { entity } => {
+                return self.get(entity).unwrap_or_else(|| ty.clone());
+            }
+            TypeKind::Mutable { inner } => TypeKind::Mutable {
+                inner: self.apply(inner, type_map),
+            },
+            TypeKind::Option { inner } => TypeKind::Option {
+                inner: self.apply(inner, type_map),
+            },
+            TypeKind::Weak { inner } => TypeKind::Weak {
+                inner: self.apply(inner, type_map),
+            },
+            TypeKind::Task { result } => TypeKind::Task {
+                result: self.apply(result, type_map),
+            },
+            TypeKind::Result { ok, err } => TypeKind::Result {
+                ok: self.apply(ok, type_map),
+                err: self.apply(err, type_map),
+            },
+            TypeKind::Function {
+                params,
+                return_type,
+            } => TypeKind::Function {
+                params: params.iter().map(|param| self.apply(param, type_map)).collect(),
+                return_type: self.apply(return_type, type_map),
+            },
+            TypeKind::Tuple { elements } => TypeKind::Tuple {
+                elements: elements.iter().map(|element| self.apply(element, type_map)).collect(),
+            },
+            TypeKind::Array { element_type, size } => TypeKind::Array {
+                element_type: self.apply(element_type, type_map),
+                size: *size,
+            },
+            TypeKind::Vector { element_type } => TypeKind::Vector {
+                element_type: self.apply(element_type, type_map),
+            },
+            TypeKind::Slice { element_type } => TypeKind::Slice {
+                element_type: self.apply(element_type, type_map),
+            },
+            TypeKind::Map {
+                key_type,
+                value_type,
+            } => TypeKind::Map {
+                key_type: self.apply(key_type, type_map),
+                value_type: self.apply(value_type, type_map),
+            },
+            _ => return ty.clone(),
+        };
+        // `Option[T]` with T an option is the inner option.
+        match kind {
+            TypeKind::Option { inner } if inner.is_option() => inner,
+            kind => type_map.insert_type(kind),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{
@@ -37,6 +38,12 @@ pub enum TypeKind {
     Char,
     String,
     Unit,
+    /// a value whose type is only known while running, `reflect.get_field` is one. Any value
+    /// can be given where one is expected.
+    Any,
+    /// the type of an expression that never produces a value, `todo()` is one. It fits where
+    /// any type is expected.
+    Never,
     Mutable {
         inner: Rc<Type>,
     },
@@ -48,6 +55,22 @@ pub enum TypeKind {
     Struct {
         entity: EntityRef,
     },
+    Enum {
+        entity: EntityRef,
+    },
+    /// an optional value, none has no payload.
+    Option {
+        inner: Rc<Type>,
+    },
+    /// handle of a task running a function returning result.
+    Task {
+        result: Rc<Type>,
+    },
+    /// the outcome of joining a task, either ok or err.
+    Result {
+        ok: Rc<Type>,
+        err: Rc<Type>,
+    },
     Tuple {
         elements: Vec<Rc<Type>>,
     },
@@ -55,12 +78,27 @@ pub enum TypeKind {
         element_type: Rc<Type>,
         size: usize,
     },
-    // Vector {
-    //     element_type: Rc<Type>,
-    // },
+    /// the built in growable vector, `Vec[T]`.
+    Vector {
+        element_type: Rc<Type>,
+    },
+    /// the built in hash map, `Map[K, V]`.
+    Map {
+        key_type: Rc<Type>,
+        value_type: Rc<Type>,
+    },
     Slice {
         element_type: Rc<Type>,
     },
+    /// a handle to a heap object that does not keep it alive, `Weak[T]`.
+    Weak {
+        inner: Rc<Type>,
+    },
+    /// a type parameter of a generic function, it is substituted by the type argument of each
+    /// instance, see `Substitution`.
+    Param {
+        entity: EntityRef,
+    },
     Integer,
     Float,
 }
@@ -94,6 +132,14 @@ impl TypeKind {
         }
     }
 
+    pub fn is_unit(&self) -> bool {
+        matches!(self, TypeKind::Unit)
+    }
+
+    pub fn is_never(&self) -> bool {
+        matches!(self, TypeKind::Never)
+    }
+
     pub fn is_string(&self) -> bool {
         match self {
             TypeKind::String => true,
@@ -112,6 +158,10 @@ impl TypeKind {
         self.is_integer() || self.is_float() || self.is_bool() || self.is_char()
     }
 
+    pub fn is_function(&self) -> bool {
+        matches!(self, TypeKind::Function { .. })
+    }
+
     pub fn is_struct(&self) -> bool {
         match self {
             TypeKind::Struct { .. } => true,
@@ -119,10 +169,63 @@ impl TypeKind {
         }
     }
 
+    pub fn is_enum(&self) -> bool {
+        match self {
+            TypeKind::Enum { .. } => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_flags(&self) -> bool {
+        match self {
+            TypeKind::Enum { entity } => entity.borrow().as_enum().flags,
+            _ => false,
+        }
+    }
+
+    pub fn is_option(&self) -> bool {
+        match self {
+            TypeKind::Option { .. } => true,
+            _ => false,
+        }
+    }
+
     pub fn is_arithmetic(&self) -> bool {
         self.is_integer() || self.is_float()
     }
 
+    /// whether a value of the type is an object of the heap of the vm, a weak handle can only
+    /// refer to one.
+    pub fn is_heap_object(&self) -> bool {
+        match self {
+            TypeKind::String
+            | TypeKind::Function { .. }
+            | TypeKind::Struct { .. }
+            | TypeKind::Tuple { .. }
+            | TypeKind::Array { .. }
+            | TypeKind::Vector { .. }
+            | TypeKind::Map { .. }
+            | TypeKind::Slice { .. } => true,
+            TypeKind::Mutable { inner } => inner.kind.is_heap_object(),
+            _ => false,
+        }
+    }
+
+    /// true when value can be represented by this integer type.
+    pub fn fits_integer(&self, value: i64) -> bool {
+        match self {
+            TypeKind::U8 => u8::try_from(value).is_ok(),
+            TypeKind::U16 => u16::try_from(value).is_ok(),
+            TypeKind::U32 => u32::try_from(value).is_ok(),
+            TypeKind::U64 => u64::try_from(value).is_ok(),
+            TypeKind::I8 => i8::try_from(value).is_ok(),
+            TypeKind::I16 => i16::try_from(value).is_ok(),
+            TypeKind::I32 => i32::try_from(value).is_ok(),
+            TypeKind::I64 | TypeKind::Integer => true,
+            _ => false,
+        }
+    }
+
     pub fn is_signed(&self) -> bool {
         match self {
             TypeKind::I8
@@ -145,6 +248,64 @@ impl TypeKind {
             _ => false,
         }
     }
+
+    /// the invalid type, or a type built from it, is the type of something that already failed
+    /// to check.
+    pub fn is_poisoned(&self) -> bool {
+        match self {
+            TypeKind::Invalid => true,
+            TypeKind::Mutable { inner } | TypeKind::Option { inner } | TypeKind::Weak { inner } => {
+                inner.kind.is_poisoned()
+            }
+            TypeKind::Task { result } => result.kind.is_poisoned(),
+            TypeKind::Result { ok, err } => ok.kind.is_poisoned() || err.kind.is_poisoned(),
+            TypeKind::Function {
+                params,
+                return_type,
+            } => {
+                params.iter().any(|param| param.kind.is_poisoned())
+                    || return_type.kind.is_poisoned()
+            }
+            TypeKind::Tuple { elements } => elements.iter().any(|ty| ty.kind.is_poisoned()),
+            TypeKind::Array { element_type, .. }
+            | TypeKind::Vector { element_type }
+            | TypeKind::Slice { element_type } => element_type.kind.is_poisoned(),
+            TypeKind::Map {
+                key_type,
+                value_type,
+            } => key_type.kind.is_poisoned() || value_type.kind.is_poisoned(),
+            _ => false,
+        }
+    }
+
+    /// whether the type names a type parameter, it is only known once the generic function
+    /// using it is instantiated.
+    pub fn has_type_params(&self) -> bool {
+        match self {
+            TypeKind::Param { .. } => true,
+            TypeKind::Mutable { inner } | TypeKind::Option { inner } | TypeKind::Weak { inner } => {
+                inner.kind.has_type_params()
+            }
+            TypeKind::Task { result } => result.kind.has_type_params(),
+            TypeKind::Result { ok, err } => ok.kind.has_type_params() || err.kind.has_type_params(),
+            TypeKind::Function {
+                params,
+                return_type,
+            } => {
+                params.iter().any(|param| param.kind.has_type_params())
+                    || return_type.kind.has_type_params()
+            }
+            TypeKind::Tuple { elements } => elements.iter().any(|ty| ty.kind.has_type_params()),
+            TypeKind::Array { element_type, .. }
+            | TypeKind::Vector { element_type }
+            | TypeKind::Slice { element_type } => element_type.kind.has_type_params(),
+            TypeKind::Map {
+                key_type,
+                value_type,
+            } => key_type.kind.has_type_params() || value_type.kind.has_type_params(),
+            _ => false,
+        }
+    }
 }
 
 impl PartialEq for TypeKind {
@@ -164,7 +325,9 @@ impl PartialEq for TypeKind {
             | (Self::Bool, Self::Bool)
             | (Self::Char, Self::Char)
             | (Self::String, Self::String)
-            | (Self::Unit, Self::Unit) => true,
+            | (Self::Unit, Self::Unit)
+            | (Self::Any, Self::Any)
+            | (Self::Never, Self::Never) => true,
             (Self::Integer, _) => other.is_integer(),
             (_, Self::Integer) => self.is_integer(),
             (Self::Float, _) => other.is_float(),
@@ -183,6 +346,19 @@ impl PartialEq for TypeKind {
             (Self::Struct { entity: lentity }, Self::Struct { entity: rentityt }) => {
                 lentity.borrow().id() == rentityt.borrow().id()
             }
+            (Self::Enum { entity: lentity }, Self::Enum { entity: rentity })
+            | (Self::Param { entity: lentity }, Self::Param { entity: rentity }) => {
+                lentity.borrow().id() == rentity.borrow().id()
+            }
+            (Self::Option { inner: linner }, Self::Option { inner: rinner })
+            | (Self::Weak { inner: linner }, Self::Weak { inner: rinner }) => linner == rinner,
+            (Self::Task { result: lresult }, Self::Task { result: rresult }) => lresult == rresult,
+            (Self::Tuple { elements: lelements }, Self::Tuple { elements: relements }) => {
+                lelements == relements
+            }
+            (Self::Result { ok: lok, err: lerr }, Self::Result { ok: rok, err: rerr }) => {
+                lok == rok && lerr == rerr
+            }
             (
                 Self::Array {
                     element_type: ltype,
@@ -200,7 +376,25 @@ impl PartialEq for TypeKind {
                 Self::Slice {
                     element_type: rtype,
                 },
+            )
+            | (
+                Self::Vector {
+                    element_type: ltype,
+                },
+                Self::Vector {
+                    element_type: rtype,
+                },
             ) => ltype == rtype,
+            (
+                Self::Map {
+                    key_type: lkey,
+                    value_type: lvalue,
+                },
+                Self::Map {
+                    key_type: rkey,
+                    value_type: rvalue,
+                },
+            ) => lkey == rkey && lvalue == rvalue,
             (_, _) => false,
         }
     }
@@ -242,6 +436,14 @@ impl Type {
         self.kind.is_bool()
     }
 
+    pub fn is_unit(&self) -> bool {
+        self.kind.is_unit()
+    }
+
+    pub fn is_never(&self) -> bool {
+        self.kind.is_never()
+    }
+
     pub fn is_string(&self) -> bool {
         self.kind.is_string()
     }
@@ -254,14 +456,34 @@ impl Type {
         self.kind.is_primitive()
     }
 
+    pub fn is_function(&self) -> bool {
+        self.kind.is_function()
+    }
+
     pub fn is_struct(&self) -> bool {
         self.kind.is_struct()
     }
 
+    pub fn is_enum(&self) -> bool {
+        self.kind.is_enum()
+    }
+
+    pub fn is_flags(&self) -> bool {
+        self.kind.is_flags()
+    }
+
+    pub fn is_option(&self) -> bool {
+        self.kind.is_option()
+    }
+
     pub fn is_arithmetic(&self) -> bool {
         self.is_integer() || self.is_float()
     }
 
+    pub fn fits_integer(&self, value: i64) -> bool {
+        self.kind.fits_integer(value)
+    }
+
     pub fn is_signed(&self) -> bool {
         self.kind.is_signed()
     }
@@ -286,6 +508,8 @@ impl Type {
             TypeKind::Function { .. }
             | TypeKind::Struct { .. }
             | TypeKind::String
+            | TypeKind::Task { .. }
+            | TypeKind::Result { .. }
             | TypeKind::Tuple { .. } => AddressMode::Address,
             TypeKind::Mutable { inner } => inner.address_mode(),
             TypeKind::Invalid => AddressMode::Error,
@@ -318,8 +542,10 @@ impl Display for Type {
             TypeKind::F64 => write!(f, "f64"),
             TypeKind::Bool => write!(f, "bool"),
             TypeKind::Char => write!(f, "char"),
-            TypeKind::String => write!(f, "String"),
-            TypeKind::Unit => write!(f, "<>"),
+            TypeKind::String => write!(f, "string"),
+            TypeKind::Unit => write!(f, "unit"),
+            TypeKind::Any => write!(f, "any"),
+            TypeKind::Never => write!(f, "never"),
             TypeKind::Mutable { inner } => write!(f, "mut {}", inner),
             TypeKind::Function {
                 params,
@@ -334,20 +560,25 @@ impl Display for Type {
                     .join(", "),
                 return_type
             ),
-            TypeKind::Struct { entity } => {
+            TypeKind::Struct { entity } | TypeKind::Enum { entity } => {
                 let path = entity.deref().borrow().full_name();
                 write!(f, "{}", path.to_string())
             }
+            TypeKind::Option { inner } => write!(f, "Option[{}]", inner),
+            TypeKind::Task { result } => write!(f, "Task[{}]", result),
+            TypeKind::Result { ok, err } => write!(f, "Result[{}, {}]", ok, err),
             TypeKind::Tuple { elements } => {
                 write!(f, "({})", elements.iter().map(|e| e.to_string()).join(", "))
             }
             TypeKind::Array { element_type, size } => write!(f, "[{}; {}]", element_type, size),
-            // TypeKind::Vector {
-            //     element_type,
-            // } => {
-            //     write!(f, "[{}]", element_type)
-            // }
+            TypeKind::Vector { element_type } => write!(f, "Vec[{}]", element_type),
+            TypeKind::Map {
+                key_type,
+                value_type,
+            } => write!(f, "Map[{}, {}]", key_type, value_type),
             TypeKind::Slice { element_type } => write!(f, "[{}]", element_type),
+            TypeKind::Weak { inner } => write!(f, "Weak[{}]", inner),
+            TypeKind::Param { entity } => write!(f, "{}", entity.deref().borrow().name()),
             TypeKind::Float => write!(f, "{{float}}"),
             TypeKind::Integer => write!(f, "{{integer}}"),
         }
@@ -1,16 +1,24 @@
 use std::collections::BTreeMap;
 use std::rc::Rc;
 
+use crate::analysis::EntityRef;
 use crate::types::ty::{Type, TypeId, TypeKind};
 
 pub struct TypeMap {
     map: BTreeMap<TypeId, Rc<Type>>,
+    /// type ids are shared by every map, so the primitives are looked up by creation order.
+    primitives: Vec<TypeId>,
+    /// the entities holding the methods of the built in types, one for each `Vec[T]` and
+    /// `Map[K, V]`.
+    builtins: Vec<EntityRef>,
 }
 
 impl TypeMap {
     pub fn new() -> Self {
         Self {
             map: BTreeMap::new(),
+            primitives: vec![],
+            builtins: vec![],
         }
     }
 
@@ -32,9 +40,19 @@ impl TypeMap {
         self.create_type(Type::new(TypeKind::Bool));
         self.create_type(Type::new(TypeKind::Char));
         self.create_type(Type::new(TypeKind::Unit));
+        self.create_type(Type::new(TypeKind::String));
+        self.create_type(Type::new(TypeKind::Any));
+        self.create_type(Type::new(TypeKind::Never));
     }
 
     pub fn insert_type(&mut self, kind: TypeKind) -> Rc<Type> {
+        // a type of a definition of an earlier check is not made again.
+        match &kind {
+            TypeKind::Struct { entity }
+            | TypeKind::Enum { entity }
+            | TypeKind::Param { entity } => entity.borrow().assert_live(),
+            _ => {}
+        }
         for ty in self.map.values() {
             if *ty.kind() == kind {
                 return ty.clone();
@@ -47,83 +65,93 @@ impl TypeMap {
         new_type
     }
 
+    /// the entity of the built in type ty, its methods are looked up like those of a structure.
+    pub fn builtin_entity(&self, ty: &Type) -> Option<EntityRef> {
+        self.builtins
+            .iter()
+            .find(|entity| *entity.borrow().ty() == *ty)
+            .cloned()
+    }
+
+    pub fn add_builtin_entity(&mut self, entity: EntityRef) {
+        self.builtins.push(entity);
+    }
+
     fn create_type(&mut self, ty: Type) {
         let id = ty.id();
+        self.primitives.push(id);
         self.map.insert(id, Rc::new(ty));
     }
 
+    fn primitive(&self, idx: usize) -> Rc<Type> {
+        self.map[&self.primitives[idx]].clone()
+    }
+
     pub fn get_u8(&self) -> Rc<Type> {
-        let id = TypeId(1);
-        self.map[&id].clone()
+        self.primitive(1)
     }
 
     pub fn get_u16(&self) -> Rc<Type> {
-        let id = TypeId(2);
-        self.map[&id].clone()
+        self.primitive(2)
     }
 
     pub fn get_u32(&self) -> Rc<Type> {
-        let id = TypeId(3);
-        self.map[&id].clone()
+        self.primitive(3)
     }
 
     pub fn get_u64(&self) -> Rc<Type> {
-        let id = TypeId(4);
-        self.map[&id].clone()
+        self.primitive(4)
     }
 
     pub fn get_i8(&self) -> Rc<Type> {
-        let id = TypeId(5);
-        self.map[&id].clone()
+        self.primitive(5)
     }
 
     pub fn get_i16(&self) -> Rc<Type> {
-        let id = TypeId(6);
-        self.map[&id].clone()
+        self.primitive(6)
     }
 
     pub fn get_i32(&self) -> Rc<Type> {
-        let id = TypeId(7);
-        self.map[&id].clone()
+        self.primitive(7)
     }
 
     pub fn get_i64(&self) -> Rc<Type> {
-        let id = TypeId(8);
-        self.map[&id].clone()
+        self.primitive(8)
     }
 
     pub fn get_f32(&self) -> Rc<Type> {
-        let id = TypeId(9);
-        self.map[&id].clone()
+        self.primitive(9)
     }
 
     pub fn get_f64(&self) -> Rc<Type> {
-        let id = TypeId(10);
-        self.map[&id].clone()
+        self.primitive(10)
     }
 
     pub fn get_bool(&self) -> Rc<Type> {
-        let id = TypeId(11);
-        self.map[&id].clone()
+        self.primitive(11)
     }
 
     pub fn get_char(&self) -> Rc<Type> {
-        let id = TypeId(12);
-        self.map[&id].clone()
+        self.primitive(12)
     }
 
     pub fn get_string(&self) -> Rc<Type> {
-        let id = TypeId(12);
-        self.map[&id].clone()
+        self.primitive(14)
+    }
+
+    pub fn get_any(&self) -> Rc<Type> {
+        self.primitive(15)
+    }
+
+    pub fn get_never(&self) -> Rc<Type> {
+        self.primitive(16)
     }
 
     pub fn get_invalid(&self) -> Rc<Type> {
-        let id = TypeId(0);
-        self.map[&id].clone()
+        self.primitive(0)
     }
 
     pub fn get_unit(&self) -> Rc<Type> {
-        let id = TypeId(13);
-        self.map[&id].clone()
+        self.primitive(13)
     }
 }
@@ -0,0 +1,523 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::Deref;
+use std::rc::Rc;
+
+use itertools::Itertools;
+
+use crate::analysis::{EntityInfo, EntityRef};
+use crate::ir::ast::{
+    Expr, ExprKind, FunctionBody, Identifier, Item, ItemKind, Node, Stmt, StmtKind,
+    StructExprField,
+};
+use crate::ir::hir::{HirFile, HirStmt, MirNode};
+use crate::ir::visit::{walk_item, MirVisitor};
+use crate::syntax::{ParsedFile, Position};
+use crate::system::FileId;
+use crate::types::{Type, TypeKind};
+use crate::utils::entity_dump::json_string;
+
+/// a type annotation written into the source, the text goes at the offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Insertion {
+    /// the binding or function the annotation is for.
+    pub name: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+}
+
+/// a binding or function left without an annotation, the reason is reported as a note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    pub name: String,
+    pub position: Position,
+    pub reason: &'static str,
+}
+
+/// the annotations of the un-annotated `let` bindings and inferred function return types of a
+/// file, in the order they are written. The types are the ones the typer inferred for them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotations {
+    insertions: Vec<Insertion>,
+    skipped: Vec<Skipped>,
+}
+
+/// where an annotation goes, found in the parsed file.
+struct Site<'a> {
+    name: &'a Identifier,
+    /// the item is looked up in the checked program by this position.
+    key: Position,
+    kind: SiteKind,
+}
+
+enum SiteKind {
+    /// after the name of a binding, a closure literal has no type that can be written.
+    Binding { closure: bool },
+    /// before the `=` of a function returning an expression.
+    Return { at: Position },
+}
+
+impl Annotations {
+    /// parsed is the file as it was parsed before it was checked into file.
+    pub fn of(parsed: &ParsedFile, file: &HirFile) -> Self {
+        let mut sites = vec![];
+        for stmt in parsed.stmts.iter() {
+            collect_stmt(&mut sites, stmt.as_ref());
+        }
+        let mut inferred = Inferred::default();
+        inferred.visit_file(file);
+
+        let mut annotations = Self::default();
+        for site in sites {
+            let name = site.name.kind().value.clone();
+            let ty = match inferred.types.get(&key(site.key)) {
+                Some(ty) => ty,
+                None => continue,
+            };
+            let rendered = match site.kind {
+                SiteKind::Binding { closure: true } => Err("a closure has no type to write"),
+                _ => source_type(ty, file.stem()),
+            };
+            let rendered = match rendered {
+                Ok(rendered) => rendered,
+                Err(reason) => {
+                    annotations.skipped.push(Skipped {
+                        name,
+                        position: site.name.position(),
+                        reason,
+                    });
+                    continue;
+                }
+            };
+            let insertion = match site.kind {
+                SiteKind::Binding { .. } => {
+                    let end = site.name.position();
+                    Insertion {
+                        name,
+                        offset: end.span().end(),
+                        line: end.end().line(),
+                        column: end.end().column(),
+                        text: format!(" {}", rendered),
+                    }
+                }
+                SiteKind::Return { at } => Insertion {
+                    name,
+                    offset: at.span().start(),
+                    line: at.start().line(),
+                    column: at.start().column(),
+                    text: format!("{} ", rendered),
+                },
+            };
+            annotations.insertions.push(insertion);
+        }
+        annotations
+    }
+
+    pub fn insertions(&self) -> &[Insertion] {
+        self.insertions.as_slice()
+    }
+
+    pub fn skipped(&self) -> &[Skipped] {
+        self.skipped.as_slice()
+    }
+
+    /// source with every annotation inserted.
+    pub fn apply(&self, source: &str) -> String {
+        let mut annotated = source.to_owned();
+        for insertion in self.insertions.iter().sorted_by_key(|i| i.offset).rev() {
+            annotated.insert_str(insertion.offset, insertion.text.as_str());
+        }
+        annotated
+    }
+
+    /// the insertions as suggestions a tool can apply without a person checking them, a json
+    /// object on each line.
+    pub fn to_suggestions(&self, path: &str) -> String {
+        let mut out = String::new();
+        for insertion in self.insertions.iter() {
+            writeln!(
+                out,
+                "{{\"file\":{},\"name\":{},\"line\":{},\"column\":{},\"offset\":{},\
+                 \"insert\":{},\"applicability\":\"machine-applicable\"}}",
+                json_string(path),
+                json_string(insertion.name.as_str()),
+                insertion.line,
+                insertion.column,
+                insertion.offset,
+                json_string(insertion.text.as_str())
+            )
+            .unwrap();
+        }
+        out
+    }
+}
+
+fn key(position: Position) -> (FileId, (usize, usize)) {
+    (position.file_id(), position.span().as_tuple())
+}
+
+/// the type as it is written in the source of the module, the items of other modules are
+/// named through their path. Types that can not be written give the reason.
+fn source_type(ty: &Type, module: &str) -> Result<String, &'static str> {
+    let list = |types: &[Rc<Type>]| -> Result<String, &'static str> {
+        let types = types
+            .iter()
+            .map(|ty| source_type(ty, module))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(types.join(", "))
+    };
+    let written = match ty.kind() {
+        TypeKind::Invalid => return Err("its type is the error type"),
+        TypeKind::Unit => return Err("unit has no type to write"),
+        TypeKind::Result { .. } => return Err("the result of a task has no type to write"),
+        TypeKind::Integer | TypeKind::Float => return Err("the type of the literal is unknown"),
+        TypeKind::Struct { entity } | TypeKind::Enum { entity } => {
+            let entity = entity.deref().borrow();
+            let path = entity.path().to_string();
+            if path.is_empty() || path == module {
+                entity.name().to_owned()
+            } else {
+                format!("{}.{}", path, entity.name())
+            }
+        }
+        TypeKind::Mutable { inner } => format!("mut {}", source_type(inner, module)?),
+        TypeKind::Function {
+            params,
+            return_type,
+        } if return_type.is_unit() => format!("fn({})", list(params)?),
+        TypeKind::Function {
+            params,
+            return_type,
+        } => format!("fn({}) {}", list(params)?, source_type(return_type, module)?),
+        TypeKind::Option { inner } => format!("Option[{}]", source_type(inner, module)?),
+        TypeKind::Task { result } => format!("Task[{}]", source_type(result, module)?),
+        TypeKind::Weak { inner } => format!("Weak[{}]", source_type(inner, module)?),
+        TypeKind::Tuple { elements } => format!("({})", list(elements)?),
+        TypeKind::Array { element_type, size } => {
+            format!("[{}; {}]", source_type(element_type, module)?, size)
+        }
+        TypeKind::Vector { element_type } => {
+            format!("Vec[{}]", source_type(element_type, module)?)
+        }
+        TypeKind::Map {
+            key_type,
+            value_type,
+        } => format!(
+            "Map[{}, {}]",
+            source_type(key_type, module)?,
+            source_type(value_type, module)?
+        ),
+        TypeKind::Slice { element_type } => format!("[{}]", source_type(element_type, module)?),
+        _ => ty.to_string(),
+    };
+    Ok(written)
+}
+
+fn collect_stmt<'a>(sites: &mut Vec<Site<'a>>, stmt: &'a Stmt) {
+    match stmt.kind() {
+        StmtKind::Expr(expr) | StmtKind::Echo(expr) => collect_expr(sites, expr),
+        StmtKind::Item(item) => collect_item(sites, item, false),
+        StmtKind::Assignment { lvalue, rhs, .. } => {
+            collect_expr(sites, lvalue);
+            collect_expr(sites, rhs);
+        }
+        StmtKind::Empty => {}
+    }
+}
+
+/// a method is found by its name in the checked program, other items by their statement.
+fn collect_item<'a>(sites: &mut Vec<Site<'a>>, item: &'a Item, method: bool) {
+    match item.kind() {
+        ItemKind::Variable {
+            name, init, spec, ..
+        } => {
+            if spec.is_none() {
+                let closure = matches!(
+                    init.as_ref().map(|init| init.kind()),
+                    Some(ExprKind::Lambda { .. })
+                );
+                sites.push(Site {
+                    name,
+                    key: item.position(),
+                    kind: SiteKind::Binding { closure },
+                });
+            }
+            if let Some(init) = init {
+                collect_expr(sites, init);
+            }
+        }
+        ItemKind::Struct { fields, .. } => {
+            for field in fields {
+                collect_item(sites, field, true);
+            }
+        }
+        ItemKind::Function {
+            name,
+            params,
+            ret,
+            body,
+            ..
+        } => {
+            if ret.is_infer() && matches!(body, FunctionBody::Expression(_)) {
+                sites.push(Site {
+                    name,
+                    key: if method { name.position() } else { item.position() },
+                    kind: SiteKind::Return { at: ret.position() },
+                });
+            }
+            for param in params {
+                collect_item(sites, param, false);
+            }
+            collect_body(sites, body);
+        }
+        ItemKind::Param { init, .. } | ItemKind::Field { init, .. } => {
+            if let Some(init) = init {
+                collect_expr(sites, init);
+            }
+        }
+        ItemKind::Enum { .. } | ItemKind::SelfParam { .. } => {}
+    }
+}
+
+fn collect_body<'a>(sites: &mut Vec<Site<'a>>, body: &'a FunctionBody) {
+    match body {
+        FunctionBody::Block(expr) | FunctionBody::Expression(expr) => collect_expr(sites, expr),
+    }
+}
+
+fn collect_exprs<'a>(sites: &mut Vec<Site<'a>>, exprs: &'a [Box<Expr>]) {
+    for expr in exprs {
+        collect_expr(sites, expr);
+    }
+}
+
+fn collect_expr<'a>(sites: &mut Vec<Site<'a>>, expr: &'a Expr) {
+    match expr.kind() {
+        ExprKind::Block(stmts) => {
+            for stmt in stmts {
+                collect_stmt(sites, stmt);
+            }
+        }
+        ExprKind::Binary(_, left, right)
+        | ExprKind::While(left, right)
+        | ExprKind::Coalesce(left, right)
+        | ExprKind::Range {
+            start: left,
+            end: right,
+        }
+        | ExprKind::Index {
+            operand: left,
+            index: right,
+        }
+        | ExprKind::For {
+            expr: left,
+            body: right,
+            ..
+        }
+        | ExprKind::WhileLet {
+            expr: left,
+            body: right,
+            ..
+        } => {
+            collect_expr(sites, left);
+            collect_expr(sites, right);
+        }
+        ExprKind::Unary(_, operand)
+        | ExprKind::Field(operand, _)
+        | ExprKind::OptionalField(operand, _)
+        | ExprKind::TupleIndex { operand, .. }
+        | ExprKind::Cast { operand, .. }
+        | ExprKind::Loop(operand)
+        | ExprKind::Return(operand) => collect_expr(sites, operand),
+        ExprKind::Call { operand, actual } => {
+            collect_expr(sites, operand);
+            collect_exprs(sites, actual);
+        }
+        ExprKind::Method { actual, .. }
+        | ExprKind::OptionalMethod { actual, .. }
+        | ExprKind::Tuple(actual)
+        | ExprKind::Array(actual)
+        | ExprKind::Trap { values: actual, .. } => collect_exprs(sites, actual),
+        ExprKind::If {
+            cond,
+            body,
+            else_if,
+        } => {
+            collect_expr(sites, cond);
+            collect_expr(sites, body);
+            if let Some(else_if) = else_if {
+                collect_expr(sites, else_if);
+            }
+        }
+        ExprKind::Match { value, arms } => {
+            collect_expr(sites, value);
+            for arm in arms {
+                collect_expr(sites, arm.body.as_ref());
+            }
+        }
+        ExprKind::StructExpr { fields, .. } => {
+            for field in fields {
+                match field {
+                    StructExprField::Bind(_, value) | StructExprField::Field(value) => {
+                        collect_expr(sites, value)
+                    }
+                }
+            }
+        }
+        ExprKind::Lambda { params, body, .. } => {
+            for param in params {
+                collect_item(sites, param, false);
+            }
+            collect_body(sites, body);
+        }
+        _ => {}
+    }
+}
+
+/// the types of the bindings and the return types of the functions of a checked program, by
+/// the position of their item, or of the name of a method.
+#[derive(Default)]
+struct Inferred {
+    types: HashMap<(FileId, (usize, usize)), Rc<Type>>,
+}
+
+impl Inferred {
+    fn record(&mut self, position: Position, entity: &EntityRef) {
+        let entity = entity.deref().borrow();
+        let ty = match entity.kind() {
+            EntityInfo::Variable(_) => entity.ty(),
+            EntityInfo::Function(_) | EntityInfo::AssociatedFunction(_) => {
+                match entity.ty().kind() {
+                    TypeKind::Function { return_type, .. } => return_type.clone(),
+                    _ => return,
+                }
+            }
+            _ => return,
+        };
+        self.types.insert(key(position), ty);
+    }
+}
+
+impl MirVisitor for Inferred {
+    fn visit_item_stmt(&mut self, stmt: &HirStmt, entity: &EntityRef) {
+        self.record(stmt.position(), entity);
+        self.visit_item(entity)
+    }
+
+    fn visit_item(&mut self, entity: &EntityRef) {
+        let method = match entity.deref().borrow().kind() {
+            EntityInfo::AssociatedFunction(_) => entity.deref().borrow().position(),
+            _ => None,
+        };
+        if let Some(position) = method {
+            self.record(position, entity);
+        }
+        walk_item(self, entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Deref;
+
+    use super::Annotations;
+    use crate::analysis::{Analysis, EntityRef};
+    use crate::ir::hir::{HirExpr, HirFile, MirNode};
+    use crate::ir::visit::{walk_expr, walk_item, MirVisitor};
+    use crate::syntax::{ParsedFile, Parser};
+    use crate::system::File;
+    use crate::LanguageMode;
+
+    const CORPUS: &str = include_str!("../../../examples/annotate/inferred.au");
+
+    fn check(source: &str) -> (ParsedFile, HirFile) {
+        let file = File::raw_test(source.to_string());
+        let mut parser = Parser::new(&file);
+        let parsed = parser.init().and_then(|_| parser.parse_file()).unwrap();
+        let hir_file = Analysis::new()
+            .check(parsed.clone(), LanguageMode::Default)
+            .unwrap();
+        (parsed, hir_file)
+    }
+
+    /// the types of the items and expressions of a checked program in the order they are
+    /// visited, what it is without its positions.
+    #[derive(Default)]
+    struct Shape(Vec<String>);
+
+    impl MirVisitor for Shape {
+        fn visit_item(&mut self, entity: &EntityRef) {
+            let entity_borrow = entity.deref().borrow();
+            let item = format!("{} {}", entity_borrow.name(), entity_borrow.ty());
+            std::mem::drop(entity_borrow);
+            self.0.push(item);
+            walk_item(self, entity)
+        }
+
+        fn visit_expr(&mut self, expr: &HirExpr) {
+            self.0.push(expr.ty().to_string());
+            walk_expr(self, expr)
+        }
+    }
+
+    fn shape(file: &HirFile) -> Vec<String> {
+        let mut shape = Shape::default();
+        shape.visit_file(file);
+        shape.0
+    }
+
+    #[test]
+    fn test_annotating_keeps_the_program() {
+        let (parsed, file) = check(CORPUS);
+        let annotations = Annotations::of(&parsed, &file);
+        let inserted = annotations
+            .insertions()
+            .iter()
+            .map(|insertion| format!("{}:{}", insertion.name, insertion.text.trim()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            inserted,
+            [
+                "sum:i64",
+                "moved:Point",
+                "make:Point",
+                "pair:(i64, i64)",
+                "base:i32",
+                "p:Point",
+                "total:i64",
+                "q:Point",
+                "g:fn(i64) Point",
+                "m:Option[i64]",
+                "doubled:i64",
+                "t:(i64, i64)",
+            ]
+        );
+        let skipped = &annotations.skipped()[0];
+        assert_eq!(skipped.name, "f");
+        assert_eq!(skipped.reason, "a closure has no type to write");
+
+        // the annotated file checks to the same program and has nothing left to annotate.
+        let annotated = annotations.apply(CORPUS);
+        assert!(annotated.contains("    let g fn(i64) Point = make\n"));
+        let (parsed_again, file_again) = check(annotated.as_str());
+        assert_eq!(shape(&file_again), shape(&file));
+        let again = Annotations::of(&parsed_again, &file_again);
+        assert!(again.insertions().is_empty());
+        assert_eq!(again.apply(annotated.as_str()), annotated);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let source = "fn unit() {}\nfn main() {\n    let u = unit()\n    let name = \"ab\"\n}\n";
+        let (parsed, file) = check(source);
+        let annotations = Annotations::of(&parsed, &file);
+        assert_eq!(annotations.skipped()[0].reason, "unit has no type to write");
+        assert_eq!(
+            annotations.to_suggestions("my \"main\".au"),
+            "{\"file\":\"my \\\"main\\\".au\",\"name\":\"name\",\"line\":4,\"column\":13,\
+             \"offset\":56,\"insert\":\" string\",\"applicability\":\"machine-applicable\"}\n"
+        );
+    }
+}
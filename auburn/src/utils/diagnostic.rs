@@ -0,0 +1,191 @@
+use std::cmp::Reverse;
+use std::mem::{discriminant, Discriminant};
+
+use crate::error::ErrorKind;
+
+use crate::error::{Error, Renderer, SourceText};
+use crate::system::{FileId, FileMap};
+
+/// an error along with the line of source it points at. The line is taken from the file map,
+/// so source held in memory is shown the same as a file on disk.
+pub fn render_error(file_map: &FileMap, err: &Error) -> String {
+    Renderer::new().render(err, |id| source_text(file_map, id))
+}
+
+/// the source of the file of id in the file map, for `Renderer::render`.
+pub fn source_text(file_map: &FileMap, id: FileId) -> Option<SourceText<'_>> {
+    file_map.find(&id).map(|file| SourceText {
+        path: file.path(),
+        text: file.content(),
+    })
+}
+
+/// collects the errors of a check so one mistake is reported once, instead of once for every
+/// place it reaches.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    errors: Vec<Error>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, err: Error) {
+        self.errors.push(err);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// the report, ordered by file and then position whatever order the errors were found in.
+    /// Errors of the invalid type are dropped, they follow from an error already reported. Of
+    /// the errors of one family, one repeated at the same place is dropped and one inside of
+    /// another, or with the same message, is counted in a note of the first.
+    pub fn finish(self) -> Vec<Error> {
+        let mut errors = self.errors;
+        errors.retain(|err| !err.mentions_poisoned_type());
+        // the widest error at a place comes first so the errors inside of it follow it.
+        errors.sort_by_key(|err| {
+            let pos = err.pos();
+            (pos.file_id(), pos.span().start(), Reverse(pos.span().end()))
+        });
+
+        let mut reported: Vec<(Error, usize)> = vec![];
+        for err in errors {
+            let code = family(err.kind());
+            let duplicate = reported.iter().any(|(first, _)| {
+                family(first.kind()) == code
+                    && first.pos().contains(err.pos())
+                    && err.pos().contains(first.pos())
+            });
+            if duplicate {
+                continue;
+            }
+            let similar = reported.iter_mut().find(|(first, _)| {
+                family(first.kind()) == code
+                    && (first.pos().contains(err.pos())
+                        || first.kind().to_string() == err.kind().to_string())
+            });
+            match similar {
+                Some((_, count)) => *count += 1,
+                None => reported.push((err, 0)),
+            }
+        }
+
+        reported
+            .into_iter()
+            .map(|(err, count)| match count {
+                0 => err,
+                1 => err.with_note("…and 1 more similar error".to_owned()),
+                count => err.with_note(format!("…and {} more similar errors", count)),
+            })
+            .collect()
+    }
+}
+
+impl Extend<Error> for Diagnostics {
+    fn extend<T: IntoIterator<Item = Error>>(&mut self, iter: T) {
+        self.errors.extend(iter);
+    }
+}
+
+/// the kind of mistake an error is about, every lint of a function is a mistake of its own.
+fn family(kind: &ErrorKind) -> (Discriminant<ErrorKind>, &str) {
+    match kind {
+        ErrorKind::MetricExceeded { lint, .. } => (discriminant(kind), lint.as_str()),
+        _ => (discriminant(kind), ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::Diagnostics;
+    use crate::error::{Error, Renderer, SourceText};
+    use crate::syntax::{Coord, FilePos, Position, Span};
+    use crate::system::FileId;
+    use crate::types::{Type, TypeKind};
+
+    fn at(file: usize, start: usize, end: usize) -> Position {
+        let pos = FilePos::new(Coord(1, start + 1), Coord(1, end + 1));
+        Position::new(Span(start, end), pos, FileId(file))
+    }
+
+    fn report(errors: Vec<Error>) -> Vec<String> {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.extend(errors);
+        diagnostics
+            .finish()
+            .iter()
+            .map(|err| {
+                let pos = err.pos();
+                format!(
+                    "{}:{}-{} {} {:?}",
+                    pos.file_id().0,
+                    pos.span().start(),
+                    pos.span().end(),
+                    err,
+                    err.notes()
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_errors_of_one_mistake_are_reported_once() {
+        let invalid = Type::new(TypeKind::Invalid);
+        let int = Type::new(TypeKind::I64);
+        let errors = vec![
+            Error::undeclared_identifier("y".to_owned()).with_position(at(1, 20, 21)),
+            Error::incompatible_types(&int, &invalid).with_position(at(1, 20, 30)),
+            Error::invalid_lvalue().with_position(at(1, 10, 40)),
+            Error::invalid_lvalue().with_position(at(1, 12, 14)),
+            Error::invalid_lvalue().with_position(at(1, 10, 40)),
+            Error::undeclared_identifier("x".to_owned()).with_position(at(0, 4, 5)),
+            Error::undeclared_identifier("y".to_owned()).with_position(at(1, 2, 3)),
+            Error::invalid_return().with_position(at(1, 12, 14)),
+        ];
+        assert_eq!(
+            report(errors),
+            [
+                "0:4-5 use of undeclared identifier 'x' []",
+                "1:2-3 use of undeclared identifier 'y' [\"…and 1 more similar error\"]",
+                "1:10-40 invalid lvalue [\"…and 1 more similar error\"]",
+                "1:12-14 return not in function or associated function scope []",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_span_of_several_lines() {
+        let source = SourceText {
+            path: Path::new("app.au"),
+            text: "fn main() {\n    let x = 1\n    let y = 2\n    x + y\n    }\n",
+        };
+        let pos = FilePos::new(Coord(1, 11), Coord(5, 6));
+        let pos = Position::new(Span(10, 53), pos, FileId(1));
+        let err = Error::invalid_return().with_position(pos);
+        // the lines between the first and the last are counted.
+        assert_eq!(
+            Renderer::new().render_source(&err, source),
+            "app.au:1:11| return not in function or associated function scope\n\
+            >\tfn main() {\n \t          ^\n \
+            \t... 3 more lines\n\
+            >\t    }\n \t    ^\n"
+        );
+        assert_eq!(
+            Renderer::new()
+                .with_color(true)
+                .render_source(&err.with_note("a note".to_owned()), source),
+            "app.au:1:11| \x1b[1;31mreturn not in function or associated function scope\x1b[0m\n\
+            \x1b[1;36mnote\x1b[0m: a note\n\
+            >\tfn main() {\n \t          \x1b[1;31m^\x1b[0m\n \
+            \t\x1b[1;31m... 3 more lines\x1b[0m\n\
+            >\t    }\n \t    \x1b[1;31m^\x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,374 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::analysis::{EntityId, EntityInfo, EntityRef, PartialModule, Scope, ScopeKind};
+use crate::ir::ast::Visibility;
+use crate::ir::hir::{HirFile, HirStmtKind, MirNode};
+use crate::syntax::Position;
+use crate::system::FileMap;
+
+/// an entity as the typer left it, with the scopes it declares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpedEntity {
+    /// entities are numbered in the order they are dumped, unlike an `EntityId` the number does
+    /// not change when the compiler creates more entities elsewhere.
+    pub id: usize,
+    /// see `Entity::type_name`.
+    pub kind: &'static str,
+    pub path: String,
+    pub visibility: &'static str,
+    pub ty: String,
+    /// `file:line:column` of the declaration, none for entities the compiler declares.
+    pub position: Option<String>,
+    /// the slot of a parameter or a field.
+    pub index: Option<usize>,
+    /// why a tombstone failed to resolve.
+    pub failure: Option<String>,
+    pub scopes: Vec<DumpedScope>,
+}
+
+/// a scope along with the scopes nested in it, scopes without an entity are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpedScope {
+    pub kind: &'static str,
+    pub entities: Vec<DumpedEntity>,
+    pub scopes: Vec<DumpedScope>,
+}
+
+/// every entity of a checked module. Items are in the order they are declared and the members
+/// of a scope in the order they were added, the dump of the same source is always the same.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityDump {
+    entities: Vec<DumpedEntity>,
+}
+
+impl EntityDump {
+    pub fn of_file(file: &HirFile, files: &FileMap) -> Self {
+        Self::new(file, &[], files)
+    }
+
+    /// the items that resolved along with the tombstones of those that failed.
+    pub fn of_partial(partial: &PartialModule, files: &FileMap) -> Self {
+        Self::new(&partial.file, partial.failed.as_slice(), files)
+    }
+
+    fn new(file: &HirFile, failed: &[EntityRef], files: &FileMap) -> Self {
+        // an item that resolved is declared where its statement is.
+        let mut items = file
+            .stmts()
+            .iter()
+            .filter_map(|stmt| match stmt.inner() {
+                HirStmtKind::Item(entity) => {
+                    let position = entity.borrow().position().unwrap_or(stmt.position());
+                    Some((entity.clone(), Some(position)))
+                }
+                _ => None,
+            })
+            .chain(
+                failed
+                    .iter()
+                    .map(|entity| (entity.clone(), entity.borrow().position())),
+            )
+            .collect::<Vec<_>>();
+        // a tombstone is shown where its item is declared, items of the modules of a program
+        // follow each other by file.
+        items.sort_by_cached_key(|(_, position)| {
+            position.map(|position| (Self::file_name(files, position), position.span().start()))
+        });
+
+        let mut dumper = Dumper {
+            files,
+            ids: HashMap::new(),
+            scopes: HashSet::new(),
+        };
+        Self {
+            entities: items
+                .iter()
+                .map(|(entity, position)| dumper.entity(entity, *position))
+                .collect(),
+        }
+    }
+
+    fn file_name(files: &FileMap, position: Position) -> String {
+        files
+            .find(&position.file_id())
+            .map(|file| file.path().display().to_string())
+            .unwrap_or_else(|| "<unknown>".to_string())
+    }
+
+    pub fn entities(&self) -> &[DumpedEntity] {
+        self.entities.as_slice()
+    }
+
+    /// one line for each entity and each scope, nested ones are indented.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for entity in &self.entities {
+            Self::entity_text(&mut out, entity, 0);
+        }
+        out
+    }
+
+    fn entity_text(out: &mut String, entity: &DumpedEntity, indent: usize) {
+        write!(
+            out,
+            "{:indent$}#{} {} {} {} : {}",
+            "",
+            entity.id,
+            entity.kind,
+            entity.path,
+            entity.visibility,
+            entity.ty,
+            indent = indent * 2
+        )
+        .unwrap();
+        if let Some(index) = entity.index {
+            write!(out, " index {}", index).unwrap();
+        }
+        if let Some(position) = &entity.position {
+            write!(out, " @ {}", position).unwrap();
+        }
+        if let Some(failure) = &entity.failure {
+            write!(out, " failed: {}", failure).unwrap();
+        }
+        out.push('\n');
+        for scope in &entity.scopes {
+            Self::scope_text(out, scope, indent + 1);
+        }
+    }
+
+    fn scope_text(out: &mut String, scope: &DumpedScope, indent: usize) {
+        writeln!(out, "{:indent$}{}:", "", scope.kind, indent = indent * 2).unwrap();
+        for entity in &scope.entities {
+            Self::entity_text(out, entity, indent + 1);
+        }
+        for nested in &scope.scopes {
+            Self::scope_text(out, nested, indent + 1);
+        }
+    }
+
+    /// the dump as a json object with the list of the items, a scope is an object with its
+    /// entities and nested scopes.
+    pub fn to_json(&self) -> String {
+        let entities = self
+            .entities
+            .iter()
+            .map(Self::entity_json)
+            .collect::<Vec<_>>();
+        format!("{{\"entities\":[{}]}}", entities.join(","))
+    }
+
+    fn entity_json(entity: &DumpedEntity) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        let scopes = entity
+            .scopes
+            .iter()
+            .map(Self::scope_json)
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"id\":{},\"kind\":{},\"path\":{},\"visibility\":{},\"type\":{},\
+            \"position\":{},\"index\":{},\"failure\":{},\"scopes\":[{}]}}",
+            entity.id,
+            json_string(entity.kind),
+            json_string(entity.path.as_str()),
+            json_string(entity.visibility),
+            json_string(entity.ty.as_str()),
+            optional(entity.position.as_deref().map(json_string)),
+            optional(entity.index.map(|index| index.to_string())),
+            optional(entity.failure.as_deref().map(json_string)),
+            scopes.join(",")
+        )
+    }
+
+    fn scope_json(scope: &DumpedScope) -> String {
+        let entities = scope
+            .entities
+            .iter()
+            .map(Self::entity_json)
+            .collect::<Vec<_>>();
+        let scopes = scope
+            .scopes
+            .iter()
+            .map(Self::scope_json)
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"scope\":{},\"entities\":[{}],\"scopes\":[{}]}}",
+            json_string(scope.kind),
+            entities.join(","),
+            scopes.join(",")
+        )
+    }
+}
+
+pub(super) fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => write!(out, "\\u{:04x}", ch as u32).unwrap(),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+struct Dumper<'a> {
+    files: &'a FileMap,
+    /// the number of every entity dumped so far.
+    ids: HashMap<EntityId, usize>,
+    /// the scopes already dumped, the scope of a nested function is also a child of the block
+    /// it is declared in.
+    scopes: HashSet<*const Scope>,
+}
+
+impl Dumper<'_> {
+    fn entity(&mut self, entity: &EntityRef, position: Option<Position>) -> DumpedEntity {
+        let entity = entity.borrow();
+        let next = self.ids.len() + 1;
+        let id = *self.ids.entry(entity.id()).or_insert(next);
+        let position = position.map(|position| {
+            let file = EntityDump::file_name(self.files, position);
+            format!("{}:{}", file, position.start())
+        });
+
+        let (index, scopes) = match entity.kind() {
+            EntityInfo::Structure(info) => (None, vec![&info.fields, &info.methods]),
+            EntityInfo::Function(info) => (None, vec![&info.params]),
+            EntityInfo::AssociatedFunction(info) => (None, vec![&info.params]),
+            EntityInfo::Param(info) | EntityInfo::Field(info) => (Some(info.index), vec![]),
+            _ => (None, vec![]),
+        };
+        let scopes = scopes
+            .into_iter()
+            .filter_map(|scope| self.scope(scope))
+            .collect();
+
+        DumpedEntity {
+            id,
+            kind: entity.type_name(),
+            path: entity.full_name().to_string(),
+            visibility: match entity.visibility() {
+                Visibility::Public => "public",
+                Visibility::Private => "private",
+            },
+            ty: entity.ty().to_string(),
+            position,
+            index,
+            failure: entity.failure().map(ToString::to_string),
+            scopes,
+        }
+    }
+
+    fn scope(&mut self, scope: &Rc<Scope>) -> Option<DumpedScope> {
+        if !self.scopes.insert(Rc::as_ptr(scope)) {
+            return None;
+        }
+        let entities = scope
+            .elements()
+            .iter()
+            .map(|entity| self.entity(entity, entity.borrow().position()))
+            .collect::<Vec<_>>();
+        let scopes = scope
+            .children()
+            .iter()
+            .filter_map(|child| self.scope(child))
+            .collect::<Vec<_>>();
+        if entities.is_empty() && scopes.is_empty() {
+            return None;
+        }
+        let kind = match scope.kind() {
+            ScopeKind::Invalid => "invalid",
+            ScopeKind::Prelude => "prelude",
+            ScopeKind::Imports => "imports",
+            ScopeKind::File { .. } => "file",
+            ScopeKind::Module(_) => "module",
+            ScopeKind::Param(_) => "params",
+            ScopeKind::Block => "block",
+            ScopeKind::Struct(_) => "fields",
+            ScopeKind::StructMethods(_) => "methods",
+            ScopeKind::Enum(_) => "variants",
+        };
+        Some(DumpedScope {
+            kind,
+            entities,
+            scopes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EntityDump;
+    use crate::Compiler;
+
+    const SOURCE: &str = "struct Point {\n    pub x i64\n    y i64\n\
+        fn sum(self, scale i64) i64 = (self.x + self.y) * scale\n}\n\
+        fn area(w i64) i64 {\n    let side = w\n\
+        \x20   let doubled = if w > 0 {\n        let twice = side * 2\n        twice\n\
+        \x20   } else {\n        side\n    }\n    side * doubled\n}\n\
+        fn volume(w i64) i64 = area(w) * depth\n\
+        fn main() i64 = area(7)\n";
+
+    #[test]
+    fn test_dump_of_a_partial_module() {
+        let mut compiler = Compiler::new();
+        let partial = compiler.compile_str_partial("app.au", SOURCE).unwrap();
+        let dump = EntityDump::of_partial(&partial, compiler.file_map());
+        assert_eq!(
+            dump.to_text(),
+            "#1 structure app.app::Point private : app.app::Point @ app.au:1:1\n\
+            \x20 fields:\n\
+            \x20   #2 field app.Point.x public : i64 index 0 @ app.au:2:9\n\
+            \x20   #3 field app.Point.y private : i64 index 1 @ app.au:3:5\n\
+            \x20 methods:\n\
+            \x20   #4 associated function app.sum private : (app.app::Point, i64) i64 \
+            @ app.au:4:4\n\
+            \x20     params:\n\
+            \x20       #5 self self private : app.app::Point\n\
+            \x20       #6 param scale private : i64 index 1\n\
+            #7 function app.app::area private : (i64) i64 @ app.au:6:1\n\
+            \x20 params:\n\
+            \x20   #8 param w private : i64 index 0\n\
+            \x20   block:\n\
+            \x20     #9 variable side private : i64 @ app.au:7:9\n\
+            \x20     #10 variable doubled private : i64 @ app.au:8:9\n\
+            \x20     block:\n\
+            \x20       #11 variable twice private : i64 @ app.au:9:13\n\
+            #12 failed app.app::volume private : (i64) i64 @ app.au:16:1 \
+            failed: use of undeclared identifier 'depth'\n\
+            #13 function app.app::main private : () i64 @ app.au:17:1\n"
+        );
+
+        // checking the same source again dumps the same entities.
+        let mut compiler = Compiler::new();
+        let again = compiler.compile_str_partial("app.au", SOURCE).unwrap();
+        assert_eq!(EntityDump::of_partial(&again, compiler.file_map()), dump);
+    }
+
+    #[test]
+    fn test_dump_as_json() {
+        let mut compiler = Compiler::new();
+        let source = "fn twice(x i64) i64 = x * 2\nfn main() i64 = twice(true)\n";
+        let partial = compiler.compile_str_partial("app.au", source).unwrap();
+        let dump = EntityDump::of_partial(&partial, compiler.file_map());
+        assert_eq!(
+            dump.to_json(),
+            "{\"entities\":[\
+            {\"id\":1,\"kind\":\"function\",\"path\":\"app.app::twice\",\"visibility\":\"private\",\
+            \"type\":\"(i64) i64\",\"position\":\"app.au:1:1\",\"index\":null,\"failure\":null,\
+            \"scopes\":[{\"scope\":\"params\",\"entities\":[\
+            {\"id\":2,\"kind\":\"param\",\"path\":\"x\",\"visibility\":\"private\",\
+            \"type\":\"i64\",\"position\":null,\"index\":0,\"failure\":null,\"scopes\":[]}],\
+            \"scopes\":[]}]},\
+            {\"id\":3,\"kind\":\"failed\",\"path\":\"app.app::main\",\"visibility\":\"private\",\
+            \"type\":\"() i64\",\"position\":\"app.au:2:1\",\"index\":null,\
+            \"failure\":\"incompatible types, expected 'i64' and found 'bool'\",\"scopes\":[]}]}"
+        );
+    }
+}
@@ -1,6 +1,8 @@
 use std::borrow::Borrow;
 use std::ops::Deref;
 
+use itertools::Itertools;
+
 use crate::analysis::{Entity, EntityInfo};
 use crate::utils::MirPrinter;
 
@@ -31,6 +33,9 @@ impl EntityPrinter {
         match entity.kind() {
             EntityInfo::Unresolved(..) => println!("{}Unresolved", Self::indent(indent + 1)),
             EntityInfo::Resolving => println!("{}Resolving", Self::indent(indent + 1)),
+            EntityInfo::Declared(..) => println!("{}Declared", Self::indent(indent + 1)),
+            EntityInfo::Failed(err) => println!("{}Failed: {}", Self::indent(indent + 1), err),
+
             EntityInfo::Structure(structure) => {
                 for member in structure.fields.elements() {
                     Self::print_impl(&member.deref().borrow(), indent + 1);
@@ -40,6 +45,34 @@ impl EntityPrinter {
                     Self::print_impl(&member.deref().borrow(), indent + 1);
                 }
             }
+            EntityInfo::Enumeration(enumeration) => {
+                for variant in enumeration.scope.elements() {
+                    Self::print_impl(&variant.deref().borrow(), indent + 1);
+                }
+            }
+            EntityInfo::Variant(variant) => {
+                println!(
+                    "{}Variant({}, fields: {})",
+                    Self::indent(indent + 1),
+                    variant.discriminant,
+                    variant.fields.iter().map(ToString::to_string).join(", ")
+                );
+            }
+            EntityInfo::Closure(closure) => {
+                let captures = closure
+                    .captures
+                    .iter()
+                    .map(|capture| capture.deref().borrow().name().to_owned())
+                    .join(", ");
+                println!("{}Captures: {}", Self::indent(indent), captures);
+                let function = &closure.function;
+                println!("{}Params:", Self::indent(indent));
+                for member in function.params.elements() {
+                    Self::print_impl(&member.deref().borrow(), indent + 1);
+                }
+                println!("{}Body Expression:", Self::indent(indent));
+                MirPrinter::print_expr_inner(function.body.as_ref(), indent + 1);
+            }
             EntityInfo::Function(function) => {
                 println!("{}Params:", Self::indent(indent));
                 for member in function.params.elements() {
@@ -100,7 +133,7 @@ impl EntityPrinter {
                 println!("{}Body Expression:", Self::indent(indent));
                 MirPrinter::print_expr_inner(associated_function.body.as_ref(), indent + 1);
             }
-            EntityInfo::Primitive => {}
+            EntityInfo::Primitive | EntityInfo::TypeParam => {}
         }
     }
 }
@@ -3,7 +3,7 @@ use std::ops::Deref;
 use crate::ir::ast::NodeType;
 use crate::ir::hir::{
     HirExpr, HirExprKind, HirField, HirFile, HirItem, HirItemKind, HirNodeBase, HirParam, HirSpec,
-    HirStmt, HirStmtKind, IfExprBranch, MirNode,
+    HirPattern, HirStmt, HirStmtKind, IfExprBranch, MirNode,
 };
 use crate::utils::EntityPrinter;
 
@@ -26,12 +26,21 @@ impl MirPrinter {
     }
 
     fn print_header<N: NodeType>(base: &HirNodeBase<N>, indent: usize) {
-        println!(
-            "{}{} - {}",
-            Self::indent(indent),
-            base.inner().name(),
-            base.ty()
-        );
+        match base.generated_by() {
+            Some(desugaring) => println!(
+                "{}{} - {} (generated by {})",
+                Self::indent(indent),
+                base.inner().name(),
+                base.ty(),
+                desugaring
+            ),
+            None => println!(
+                "{}{} - {}",
+                Self::indent(indent),
+                base.inner().name(),
+                base.ty()
+            ),
+        }
     }
 
     pub(crate) fn print_stmt_inner(stmt: &HirStmt, indent: usize) {
@@ -60,9 +69,29 @@ impl MirPrinter {
         match expr.inner().kind() {
             HirExprKind::Name(_) => {}
             HirExprKind::Binary(binary_expr) => {
-                println!("{}Op: {}", Self::indent(indent + 1), binary_expr.op);
-                Self::print_expr_inner(binary_expr.left.as_ref(), indent + 1);
-                Self::print_expr_inner(binary_expr.right.as_ref(), indent + 1);
+                // the left operands of a chain are printed in a loop, the right operands after
+                // them from the innermost operator out.
+                let mut rights = vec![];
+                let (mut binary_expr, mut level) = (binary_expr, indent);
+                loop {
+                    println!("{}Op: {}", Self::indent(level + 1), binary_expr.op);
+                    rights.push((binary_expr.right.as_ref(), level + 1));
+                    let left = binary_expr.left.as_ref();
+                    match left.inner().kind() {
+                        HirExprKind::Binary(inner) => {
+                            Self::print_header(left, level + 1);
+                            binary_expr = inner;
+                            level += 1;
+                        }
+                        _ => {
+                            Self::print_expr_inner(left, level + 1);
+                            break;
+                        }
+                    }
+                }
+                for (right, level) in rights.into_iter().rev() {
+                    Self::print_expr_inner(right, level);
+                }
             }
             HirExprKind::Unary(unary_expr) => {
                 println!("{}Op: {}", Self::indent(indent + 1), unary_expr.op);
@@ -122,6 +151,12 @@ impl MirPrinter {
                     Self::print_expr_inner(element.as_ref(), indent + 1);
                 }
             }
+            HirExprKind::Array(array_expr) => {
+                println!("{}Elements:", Self::indent(indent));
+                for element in array_expr.elements.iter() {
+                    Self::print_expr_inner(element.as_ref(), indent + 1);
+                }
+            }
             HirExprKind::TupleIndex(tuple_index) => {
                 println!("{}Operand:", Self::indent(indent));
                 Self::print_expr_inner(tuple_index.tuple.as_ref(), indent + 1);
@@ -137,7 +172,6 @@ impl MirPrinter {
                 println!("{}Body:", Self::indent(indent));
                 Self::print_expr_inner(while_expr.body.as_ref(), indent + 1);
             }
-            HirExprKind::For(_for_expr) => {}
             HirExprKind::If(if_expr) => {
                 for branch in if_expr.branches.as_slice() {
                     match branch {
@@ -155,6 +189,52 @@ impl MirPrinter {
                     }
                 }
             }
+            HirExprKind::Match(match_expr) => {
+                println!("{}Value:", Self::indent(indent));
+                Self::print_expr_inner(&match_expr.value, indent + 1);
+                for arm in &match_expr.arms {
+                    match &arm.pattern {
+                        HirPattern::Wildcard => println!("{}Pattern: _", Self::indent(indent)),
+                        HirPattern::Binding(entity) => println!(
+                            "{}Pattern: {}",
+                            Self::indent(indent),
+                            entity.borrow().name()
+                        ),
+                        HirPattern::Literal(literal) => {
+                            println!("{}Pattern:", Self::indent(indent));
+                            Self::print_expr_inner(literal, indent + 1);
+                        }
+                        HirPattern::Range { start, end } => {
+                            println!("{}Pattern: Range", Self::indent(indent));
+                            Self::print_expr_inner(start, indent + 1);
+                            Self::print_expr_inner(end, indent + 1);
+                        }
+                        HirPattern::Variant {
+                            enum_entity,
+                            discriminant,
+                            fields,
+                        } => {
+                            let fields = fields
+                                .iter()
+                                .map(|field| {
+                                    field.as_ref().map_or("_".to_owned(), |field| {
+                                        field.borrow().name().to_owned()
+                                    })
+                                })
+                                .collect::<Vec<_>>();
+                            println!(
+                                "{}Pattern: {} {}({})",
+                                Self::indent(indent),
+                                enum_entity.borrow().name(),
+                                discriminant,
+                                fields.join(", ")
+                            );
+                        }
+                    }
+                    println!("{}Body:", Self::indent(indent));
+                    Self::print_expr_inner(&arm.body, indent + 1);
+                }
+            }
             HirExprKind::StructExpr(struct_expr) => {
                 struct_expr.fields.iter().for_each(|(index, mir)| {
                     println!("{}Index: {}", Self::indent(indent + 1), index);
@@ -164,20 +244,93 @@ impl MirPrinter {
             HirExprKind::Return(expr) => {
                 Self::print_expr_inner(expr, indent + 1);
             }
+            HirExprKind::Log(log_expr) => {
+                println!("{}Level: {}", Self::indent(indent + 1), log_expr.level);
+                Self::print_expr_inner(log_expr.message.as_ref(), indent + 1);
+            }
             HirExprKind::Index(index_expr) => {
                 println!("{}Operand:", Self::indent(indent));
                 Self::print_expr_inner(index_expr.operand.as_ref(), indent + 1);
                 println!("{}Index:", Self::indent(indent));
                 Self::print_expr_inner(index_expr.index.as_ref(), indent + 1);
             }
+            HirExprKind::Variant(variant_expr) => {
+                println!(
+                    "{}Discriminant: {}",
+                    Self::indent(indent + 1),
+                    variant_expr.discriminant
+                );
+                variant_expr
+                    .fields
+                    .iter()
+                    .for_each(|field| Self::print_expr_inner(field.as_ref(), indent + 1));
+            }
+            HirExprKind::EnumFunction(enum_function_expr) => {
+                println!(
+                    "{}Function: {}",
+                    Self::indent(indent + 1),
+                    enum_function_expr.function.name()
+                );
+                enum_function_expr
+                    .actuals
+                    .iter()
+                    .for_each(|actual| Self::print_expr_inner(actual.as_ref(), indent + 1));
+            }
+            HirExprKind::GetField(get_field) => {
+                Self::print_expr_inner(get_field.operand.as_ref(), indent + 1);
+                Self::print_expr_inner(get_field.name.as_ref(), indent + 1);
+            }
+            HirExprKind::Task(task_expr) => {
+                println!(
+                    "{}Function: {}",
+                    Self::indent(indent + 1),
+                    task_expr.function.name()
+                );
+                task_expr
+                    .actuals
+                    .iter()
+                    .for_each(|actual| Self::print_expr_inner(actual.as_ref(), indent + 1));
+            }
+            HirExprKind::Trap(trap) => {
+                println!("{}Message: {}", Self::indent(indent + 1), trap.message);
+                trap.values
+                    .iter()
+                    .for_each(|value| Self::print_expr_inner(value.as_ref(), indent + 1));
+            }
+            HirExprKind::Unreachable(message) => {
+                println!("{}Message: {}", Self::indent(indent + 1), message);
+            }
+            HirExprKind::Cast(cast_expr) => {
+                Self::print_expr_inner(cast_expr.operand.as_ref(), indent + 1);
+            }
+            HirExprKind::OptionalChain(chain_expr) => {
+                Self::print_expr_inner(chain_expr.link.as_ref(), indent + 1);
+            }
+            HirExprKind::ChainReceiver(receiver)
+            | HirExprKind::BlackBox(receiver)
+            | HirExprKind::Eval(receiver) => {
+                Self::print_expr_inner(receiver.as_ref(), indent + 1);
+            }
+            HirExprKind::Coalesce(coalesce_expr) => {
+                println!("{}Value:", Self::indent(indent));
+                Self::print_expr_inner(coalesce_expr.value.as_ref(), indent + 1);
+                println!("{}Fallback:", Self::indent(indent));
+                Self::print_expr_inner(coalesce_expr.fallback.as_ref(), indent + 1);
+            }
             HirExprKind::SelfLit(..) => {
                 // EntityPrinter::print_impl(entity.borrow().as_ref(), indent + 1);
             }
+            HirExprKind::Lambda(entity) => {
+                EntityPrinter::print_impl(&entity.deref().borrow(), indent + 1);
+            }
             HirExprKind::Integer(_)
             | HirExprKind::Float(_)
             | HirExprKind::String(_)
             | HirExprKind::Char(_)
             | HirExprKind::Bool(_)
+            | HirExprKind::NoneLit
+            | HirExprKind::Host
+            | HirExprKind::GcCollect
             | HirExprKind::Continue
             | HirExprKind::Break => {}
         }
@@ -1,11 +1,18 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+pub use annotate::{Annotations, Insertion, Skipped};
+pub use diagnostic::{render_error, source_text, Diagnostics};
+pub use entity_dump::{DumpedEntity, DumpedScope, EntityDump};
 pub use entity_printer::EntityPrinter;
 pub use mir_printer::MirPrinter;
 
+mod annotate;
+mod diagnostic;
+mod entity_dump;
 mod entity_printer;
 mod mir_printer;
+pub mod timing;
 
 pub type Ptr<T> = Rc<RefCell<T>>;
 
@@ -0,0 +1,299 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// the phases of the compiler, a nested phase is named by the phases it runs within.
+pub const PARSE: &str = "parse";
+pub const LEX: &str = "lex";
+pub const CHECK: &str = "check";
+pub const COLLECT: &str = "collect";
+pub const DECLARE: &str = "declare";
+pub const ITEM: &str = "item";
+pub const DESUGAR: &str = "desugar";
+pub const CODEGEN: &str = "codegen";
+pub const FUNCTION: &str = "function";
+
+/// every phase a build of a program goes through.
+pub const KNOWN_PHASES: &[&str] = &[
+    "parse",
+    "parse/lex",
+    "check",
+    "check/collect",
+    "check/declare",
+    "check/item",
+    "check/item/desugar",
+    "codegen",
+    "codegen/function",
+];
+
+thread_local! {
+    static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
+}
+
+/// the time spent in a phase summed over every time it ran.
+#[derive(Debug, Clone)]
+pub struct PhaseStats {
+    /// `check/item` for the items resolved while checking.
+    pub path: String,
+    pub calls: usize,
+    /// how many things the phase made: tokens, statements, items or functions.
+    pub count: usize,
+    pub time: Duration,
+}
+
+impl PhaseStats {
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or_default()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.path.matches('/').count()
+    }
+
+    pub fn parent(&self) -> Option<&str> {
+        self.path.rfind('/').map(|idx| &self.path[..idx])
+    }
+}
+
+struct Recorder {
+    started: Instant,
+    phases: Vec<PhaseStats>,
+    /// the phases that are running, innermost last.
+    open: Vec<usize>,
+}
+
+impl Recorder {
+    fn open(&mut self, name: &'static str) -> Option<usize> {
+        let path = match self.open.last() {
+            // a phase running within itself, a for loop in a for loop, is the same phase.
+            Some(idx) if self.phases[*idx].name() == name => return None,
+            Some(idx) => format!("{}/{}", self.phases[*idx].path, name),
+            None => name.to_owned(),
+        };
+        let idx = match self.phases.iter().position(|phase| phase.path == path) {
+            Some(idx) => idx,
+            None => {
+                self.phases.push(PhaseStats {
+                    path,
+                    calls: 0,
+                    count: 0,
+                    time: Duration::default(),
+                });
+                self.phases.len() - 1
+            }
+        };
+        self.phases[idx].calls += 1;
+        self.open.push(idx);
+        Some(idx)
+    }
+
+    fn close(&mut self, idx: usize, time: Duration) {
+        if let Some(position) = self.open.iter().rposition(|open| *open == idx) {
+            self.open.truncate(position);
+            self.phases[idx].time += time;
+        }
+    }
+}
+
+/// times a phase until it is dropped. Nothing is recorded unless timing is started, so a pass
+/// is timed by making a timer when it runs.
+pub struct PhaseTimer {
+    phase: Option<(usize, Instant)>,
+}
+
+impl PhaseTimer {
+    pub fn start(name: &'static str) -> Self {
+        let phase = RECORDER.with(|recorder| recorder.borrow_mut().as_mut()?.open(name));
+        Self {
+            phase: phase.map(|idx| (idx, Instant::now())),
+        }
+    }
+
+    /// adds to the things made by the phase.
+    pub fn count(&self, count: usize) {
+        if let Some((idx, _)) = self.phase {
+            RECORDER.with(|recorder| {
+                if let Some(recorder) = recorder.borrow_mut().as_mut() {
+                    recorder.phases[idx].count += count;
+                }
+            });
+        }
+    }
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        if let Some((idx, start)) = self.phase {
+            let time = start.elapsed();
+            RECORDER.with(|recorder| {
+                if let Some(recorder) = recorder.borrow_mut().as_mut() {
+                    recorder.close(idx, time);
+                }
+            });
+        }
+    }
+}
+
+/// records the phases run on this thread until the timing is finished.
+pub fn start_timing() {
+    RECORDER.with(|recorder| {
+        *recorder.borrow_mut() = Some(Recorder {
+            started: Instant::now(),
+            phases: vec![],
+            open: vec![],
+        })
+    });
+}
+
+/// the phases run since timing was started, none when it was not.
+pub fn finish_timing() -> Option<TimingReport> {
+    let recorder = RECORDER.with(|recorder| recorder.borrow_mut().take())?;
+    let total = recorder.started.elapsed();
+    let phases = recorder.phases;
+
+    // each phase follows the phase it runs within, siblings in the order they first ran.
+    let first_ran = |path: &str| {
+        let mut prefix = String::new();
+        path.split('/')
+            .map(|name| {
+                if !prefix.is_empty() {
+                    prefix.push('/');
+                }
+                prefix.push_str(name);
+                phases.iter().position(|phase| phase.path == prefix)
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut keyed = phases
+        .iter()
+        .map(|phase| (first_ran(phase.path.as_str()), phase.clone()))
+        .collect::<Vec<_>>();
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Some(TimingReport {
+        total,
+        phases: keyed.into_iter().map(|(_, phase)| phase).collect(),
+    })
+}
+
+/// the time spent in each phase of a build.
+#[derive(Debug, Clone)]
+pub struct TimingReport {
+    /// from when timing started to when it finished.
+    pub total: Duration,
+    pub phases: Vec<PhaseStats>,
+}
+
+fn millis(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
+
+impl TimingReport {
+    pub fn phase(&self, path: &str) -> Option<&PhaseStats> {
+        self.phases.iter().find(|phase| phase.path == path)
+    }
+
+    /// a table of the phases, a nested phase is indented under the one it runs within.
+    pub fn table(&self) -> String {
+        let mut out = format!(
+            "{:<24} {:>8} {:>8} {:>12} {:>8}\n",
+            "phase", "calls", "count", "time (ms)", "%"
+        );
+        for phase in &self.phases {
+            let name = format!("{}{}", "  ".repeat(phase.depth()), phase.name());
+            let percent = if self.total.is_zero() {
+                0.0
+            } else {
+                100.0 * phase.time.as_secs_f64() / self.total.as_secs_f64()
+            };
+            writeln!(
+                out,
+                "{:<24} {:>8} {:>8} {:>12.3} {:>7.1}%",
+                name,
+                phase.calls,
+                phase.count,
+                millis(phase.time),
+                percent
+            )
+            .unwrap();
+        }
+        writeln!(out, "{:<24} {:>8} {:>8} {:>12.3}", "total", "", "", millis(self.total)).unwrap();
+        out
+    }
+
+    /// the report as json, for tracking the time of a build over time.
+    pub fn to_json(&self) -> String {
+        let phases = self
+            .phases
+            .iter()
+            .map(|phase| {
+                format!(
+                    "{{\"phase\":\"{}\",\"calls\":{},\"count\":{},\"ms\":{:.3}}}",
+                    phase.path,
+                    phase.calls,
+                    phase.count,
+                    millis(phase.time)
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"total_ms\":{:.3},\"phases\":[{}]}}",
+            millis(self.total),
+            phases.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{finish_timing, start_timing, KNOWN_PHASES};
+    use crate::analysis::Analysis;
+    use crate::code_gen::CodeGen;
+    use crate::syntax::Parser;
+    use crate::system::{File, FileMap};
+    use crate::LanguageMode;
+    use oxide::Vm;
+
+    #[test]
+    fn test_report_lists_every_phase() {
+        let source = "fn double(x i64) i64 = x + x\n\
+            fn main() i64 {\n    mut total i64 = 1\n    total += double(2)\n    total\n}\n";
+        assert!(finish_timing().is_none());
+        start_timing();
+        let file = File::raw_test(source.to_string());
+        let mut parser = Parser::new(&file);
+        let parsed = parser.init().and_then(|_| parser.parse_file()).unwrap();
+        let hir_file = Analysis::new().check(parsed, LanguageMode::Default).unwrap();
+        let mut vm = Vm::new();
+        vm.set_code_gen(true);
+        CodeGen::build(&FileMap::new(), &hir_file, &mut vm).unwrap();
+        vm.set_code_gen(false);
+        let report = finish_timing().unwrap();
+
+        let paths = report.phases.iter().map(|p| p.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths, KNOWN_PHASES);
+        assert_eq!(report.phase("check/collect").unwrap().count, 2);
+        assert_eq!(report.phase("check/declare").unwrap().count, 2);
+        assert_eq!(report.phase("check/item").unwrap().calls, 2);
+        assert_eq!(report.phase("codegen/function").unwrap().calls, 2);
+        assert!(report.phase("parse/lex").unwrap().count > 20);
+
+        // a nested phase runs within its parent, so the time of the children is part of it.
+        let top_level = report.phases.iter().filter(|p| p.parent().is_none());
+        assert!(top_level.map(|p| p.time).sum::<std::time::Duration>() <= report.total);
+        for phase in &report.phases {
+            let children = report
+                .phases
+                .iter()
+                .filter(|child| child.parent() == Some(phase.path.as_str()))
+                .map(|child| child.time)
+                .sum();
+            assert!(phase.time >= children, "{}", phase.path);
+        }
+
+        let table = report.table();
+        assert!(table.lines().any(|line| line.starts_with("    desugar ")));
+        assert!(report.to_json().starts_with("{\"total_ms\":"));
+        assert!(report.to_json().contains("{\"phase\":\"check/item/desugar\",\"calls\":1,"));
+    }
+}
@@ -0,0 +1,89 @@
+//! calls `second(a, b) = b` from the host by name and through an exported function handle,
+//! with and without checking the arguments. The module declares FILLERS functions before it, a
+//! name is found by walking them as it is in a module of that size.
+//! Run with `cargo bench -p oxide --bench exports --profile dev > /dev/null`, release builds of
+//! the vm do not compile. The vm prints each time a run completes, the times are written to
+//! stderr.
+use std::time::{Duration, Instant};
+
+use oxide::{gc::Gc, vm::OpCode, OxModule, Value, Vm};
+
+const CALLS: i64 = 200_000;
+const FILLERS: usize = 32;
+
+#[derive(Clone, Copy)]
+enum Path {
+    ByName,
+    Handle,
+    Unchecked,
+}
+
+fn module(vm: &mut Vm) -> Gc<OxModule> {
+    vm.force_no_collection(true);
+    let name = vm.new_string_from_str("exports");
+    let mut module = vm.new_empty_module(name);
+    for i in 0..FILLERS {
+        let mut section = vm.new_section();
+        section.write_op(OpCode::LoadUnit);
+        section.write_op(OpCode::Return);
+        let name = vm.new_string_from_str(&format!("filler{}", i));
+        let function = vm.new_function(name, 0, section);
+        module.as_ref_mut().add_object(Value::from(function));
+    }
+
+    let mut section = vm.new_section();
+    section.write_arg(OpCode::LoadLocal, 1);
+    section.write_op(OpCode::Return);
+    let name = vm.new_string_from_str("second");
+    let function = vm.new_function(name, 2, section);
+    module.as_ref_mut().add_object(Value::from(function));
+
+    let mut section = vm.new_section();
+    section.write_op(OpCode::LoadUnit);
+    section.write_op(OpCode::Return);
+    let name = vm.new_string_from_str("main");
+    let main = vm.new_function(name, 0, section);
+    let entry = module.as_ref_mut().add_object(Value::from(main));
+    module.as_ref_mut().set_entry(entry);
+    vm.force_no_collection(false);
+    module
+}
+
+fn calls(path: Path) -> Duration {
+    let mut vm = Vm::new();
+    let module = module(&mut vm);
+    vm.run_module(module).unwrap();
+    vm.pop();
+    let second = vm.lookup_export("second").unwrap();
+
+    let started = Instant::now();
+    let mut sum = 0;
+    for i in 0..CALLS {
+        let args = [Value::I64(1), Value::I64(i)];
+        let result = match path {
+            Path::ByName => vm.call_by_name("second", &args),
+            Path::Handle => second.call(&mut vm, &args),
+            Path::Unchecked => second.call_unchecked(&mut vm, &args),
+        };
+        sum += result.unwrap().as_i64();
+    }
+    let elapsed = started.elapsed();
+    assert_eq!(sum, CALLS * (CALLS - 1) / 2);
+    elapsed
+}
+
+fn main() {
+    for (name, path) in [
+        ("by name", Path::ByName),
+        ("handle", Path::Handle),
+        ("unchecked handle", Path::Unchecked),
+    ] {
+        let elapsed = calls(path);
+        eprintln!(
+            "{:>16}: {:>10.3?} total, {:>8.1?} per call",
+            name,
+            elapsed,
+            elapsed / CALLS as u32
+        );
+    }
+}
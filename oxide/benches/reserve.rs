@@ -0,0 +1,42 @@
+//! pushes 100k values into a list with and without reserving room for them first.
+//! Run with `cargo bench -p oxide --bench reserve --profile dev`, the allocation records are
+//! only compiled into debug builds.
+use std::time::{Duration, Instant};
+
+use oxide::{Value, Vm};
+
+const PUSHES: usize = 100_000;
+const ROUNDS: usize = 20;
+
+/// the time and the buffer reallocations of filling a list ROUNDS times.
+fn fill(reserve: bool) -> (Duration, usize) {
+    let mut vm = Vm::new();
+    vm.force_no_collection(true);
+    let before = vm.heap_stats().buffer_reallocations;
+    let started = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut list = vm.new_list_with_capacity(0).unwrap();
+        vm.push_stack(Value::from(list));
+        if reserve {
+            vm.list_reserve(list, PUSHES as i64).unwrap();
+        }
+        for i in 0..PUSHES {
+            list.as_ref_mut().push(Value::I64(i as i64)).unwrap();
+        }
+        vm.pop();
+    }
+    let elapsed = started.elapsed();
+    (elapsed, vm.heap_stats().buffer_reallocations - before)
+}
+
+fn main() {
+    for (name, reserve) in [("push", false), ("reserve + push", true)] {
+        let (elapsed, reallocations) = fill(reserve);
+        println!(
+            "{:>16}: {:>10.3?} per list, {} reallocations per list",
+            name,
+            elapsed / ROUNDS as u32,
+            reallocations / ROUNDS
+        );
+    }
+}
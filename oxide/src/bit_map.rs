@@ -45,10 +45,30 @@ impl BitMap {
         self.buffer[idx] = (byte & !(1 << offset)) | ((value as u8) << offset);
     }
 
+    /// makes room for len bits, the bits added are not set.
+    pub fn grow(&mut self, len: usize) {
+        let bytes = len.div_ceil(8);
+        if bytes > self.buffer.len() {
+            self.buffer.resize(bytes, 0x00);
+        }
+    }
+
     pub fn first_zero(&self) -> Option<usize> {
-        for (idx, element) in self.buffer.iter().enumerate() {
+        self.first_zero_from(0)
+    }
+
+    /// the first bit that is not set at or after start.
+    pub fn first_zero_from(&self, start: usize) -> Option<usize> {
+        let first = start / 8;
+        for (idx, element) in self.buffer.iter().enumerate().skip(first) {
+            // the bits of the first byte before start are treated as set.
+            let element = if idx == first {
+                *element | ((1u8 << (start % 8)) - 1)
+            } else {
+                *element
+            };
             // if not all bits are set then find the first bit in this byte
-            if *element < 0xff {
+            if element < 0xff {
                 // @TODO: hack
                 return Some(idx * 8 + element.trailing_ones() as usize);
             }
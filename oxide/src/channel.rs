@@ -0,0 +1,267 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    compiled::Constant,
+    runtime,
+    vm::{NativeResult, Vm},
+    Extract, Value,
+};
+
+
+#[derive(Debug)]
+struct Queue {
+    values: VecDeque<Constant>,
+    capacity: usize,
+    closed: bool,
+}
+
+/// bounded queue connecting two vms. Values are copied off the heap of the sending vm, only
+/// scalars, strings and tuples of them can be sent so no object is ever shared between heaps.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    queue: Arc<Mutex<Queue>>,
+}
+
+impl Channel {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "a channel needs room for at least one value");
+        Self {
+            queue: Arc::new(Mutex::new(Queue {
+                values: VecDeque::with_capacity(capacity),
+                capacity,
+                closed: false,
+            })),
+        }
+    }
+
+    /// copies value into the channel, blocks when the channel is full.
+    pub fn send(&self, value: &Value) -> Result<NativeResult, runtime::Error> {
+        let constant = Constant::from_value(value)
+            .map_err(|_| runtime::Error::unsendable_value(value.ty()))?;
+
+        let mut queue = self.queue.lock().expect("failed to retreive channel lock");
+        if queue.closed {
+            return Err(runtime::Error::SendOnClosedChannel);
+        }
+
+        if queue.values.len() == queue.capacity {
+            Ok(NativeResult::Blocked)
+        } else {
+            queue.values.push_back(constant);
+            Ok(NativeResult::Return(Value::Unit))
+        }
+    }
+
+    /// moves the oldest value onto the heap of vm, blocks when the channel is empty.
+    /// once the channel is closed and drained this returns unit.
+    pub fn recv(&self, vm: &mut Vm) -> NativeResult {
+        let mut queue = self.queue.lock().expect("failed to retreive channel lock");
+        match queue.values.pop_front() {
+            Some(constant) => NativeResult::Return(constant.to_value(vm)),
+            None if queue.closed => NativeResult::Return(Value::Unit),
+            None => NativeResult::Blocked,
+        }
+    }
+
+    pub fn close(&self) {
+        self.queue
+            .lock()
+            .expect("failed to retreive channel lock")
+            .closed = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue
+            .lock()
+            .expect("failed to retreive channel lock")
+            .values
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Vm {
+    /// registers the `send(v)` native of the channel.
+    pub fn register_channel_send(&mut self, channel: &Channel) -> Value {
+        let channel = channel.clone();
+        self.register_native(
+            "send",
+            1,
+            Box::new(move |_, actuals| {
+                let (value,): (Value,) = actuals.extract()?;
+                channel.send(&value)
+            }),
+
+        )
+    }
+
+    /// registers the `recv()` native of the channel.
+    pub fn register_channel_recv(&mut self, channel: &Channel) -> Value {
+        let channel = channel.clone();
+        self.register_native("recv", 0, Box::new(move |vm, _| Ok(channel.recv(vm))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Channel;
+    use crate::{
+        gc::Gc,
+        vm::{OpCode, SliceResult},
+        OxModule, Value, Vm,
+    };
+
+    const VALUES: i32 = 50;
+
+    fn module(vm: &mut Vm, main: crate::Section) -> Gc<OxModule> {
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, main);
+        let name = vm.new_string_from_str("script");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        module
+    }
+
+    // sends 0..VALUES followed by -1.
+    fn producer(vm: &mut Vm, channel: &Channel) -> Gc<OxModule> {
+        vm.force_no_collection(true);
+        let send = vm.register_channel_send(channel);
+        let mut section = vm.new_section();
+        let send_idx = section.add_global();
+        section.set_global(send_idx as usize, send);
+        let zero = section.add_constant(Value::I32(0));
+        let one = section.add_constant(Value::I32(1));
+        let len = section.add_constant(Value::I32(VALUES));
+        let done = section.add_constant(Value::I32(-1));
+
+        section.write_index(OpCode::LoadI32, zero);
+        let start = section.len();
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, len);
+        section.write_op(OpCode::LessI32);
+        let exit = section.write_jmp(OpCode::JmpFalse);
+        section.write_index(OpCode::LoadGlobal, send_idx);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_arg(OpCode::Call, 1);
+        section.write_op(OpCode::Pop);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, one);
+        section.write_op(OpCode::AddI32);
+        section.write_arg(OpCode::SetLocal, 0);
+        section.write_loop(start);
+        section.patch_jmp(exit);
+        section.write_index(OpCode::LoadGlobal, send_idx);
+        section.write_index(OpCode::LoadI32, done);
+        section.write_arg(OpCode::Call, 1);
+        section.write_op(OpCode::Return);
+
+        let module = module(vm, section);
+        vm.force_no_collection(false);
+        module
+    }
+
+    // sums the received values until -1.
+    fn consumer(vm: &mut Vm, channel: &Channel) -> Gc<OxModule> {
+        vm.force_no_collection(true);
+        let recv = vm.register_channel_recv(channel);
+        let mut section = vm.new_section();
+        let recv_idx = section.add_global();
+        section.set_global(recv_idx as usize, recv);
+        let zero = section.add_constant(Value::I32(0));
+        let done = section.add_constant(Value::I32(-1));
+
+        // local 0 is the sum, local 1 the last value received.
+        section.write_index(OpCode::LoadI32, zero);
+        section.write_index(OpCode::LoadI32, zero);
+        let start = section.len();
+        section.write_index(OpCode::LoadGlobal, recv_idx);
+        section.write_arg(OpCode::Call, 0);
+        section.write_arg(OpCode::SetLocal, 1);
+        section.write_index(OpCode::LoadI32, done);
+        section.write_arg(OpCode::LoadLocal, 1);
+        section.write_op(OpCode::LessI32);
+        let exit = section.write_jmp(OpCode::JmpFalse);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_arg(OpCode::LoadLocal, 1);
+        section.write_op(OpCode::AddI32);
+        section.write_arg(OpCode::SetLocal, 0);
+        section.write_loop(start);
+        section.patch_jmp(exit);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_op(OpCode::Return);
+
+        let module = module(vm, section);
+        vm.force_no_collection(false);
+        module
+    }
+
+    #[test]
+    fn test_producer_consumer() {
+        let channel = Channel::new(4);
+        let mut producer_vm = Vm::new();
+        let module = producer(&mut producer_vm, &channel);
+        producer_vm.enter_module(module).unwrap();
+        let mut consumer_vm = Vm::new();
+        let module = consumer(&mut consumer_vm, &channel);
+        consumer_vm.enter_module(module).unwrap();
+
+        let mut producer_done = false;
+        let mut blocked = 0;
+        let sum = loop {
+            if !producer_done {
+                match producer_vm.run_slice(100) {
+                    SliceResult::Completed(_) => producer_done = true,
+                    SliceResult::Blocked => blocked += 1,
+                    SliceResult::Yielded => {}
+                    SliceResult::Errored(err) => panic!("{}", err),
+                }
+            }
+            match consumer_vm.run_slice(100) {
+                SliceResult::Completed(value) => break value.as_i32(),
+                SliceResult::Blocked => blocked += 1,
+                SliceResult::Yielded => {}
+                SliceResult::Errored(err) => panic!("{}", err),
+            }
+        };
+
+        assert_eq!(sum, (0..VALUES).sum::<i32>());
+        assert!(producer_done);
+        assert!(blocked > 0);
+        assert!(channel.is_empty());
+    }
+
+    #[test]
+    fn test_send_rejects_heap_objects() {
+        let channel = Channel::new(1);
+        let mut vm = Vm::new();
+        let name = vm.new_gc_string_from_str("Point");
+        let fields = vm.new_vec();
+        let instance = vm.new_instance(name, fields).unwrap();
+        assert!(channel.send(&Value::from(instance)).is_err());
+        assert!(channel.send(&Value::from(name)).is_ok());
+    }
+
+    #[test]
+    fn test_recv_after_close() {
+        let channel = Channel::new(1);
+        let mut vm = Vm::new();
+        channel.send(&Value::I32(1)).unwrap();
+        channel.close();
+        assert!(channel.send(&Value::I32(2)).is_err());
+        match channel.recv(&mut vm) {
+            super::NativeResult::Return(value) => assert_eq!(value.as_i32(), 1),
+            _ => panic!(),
+        }
+        assert!(matches!(
+            channel.recv(&mut vm),
+            super::NativeResult::Return(Value::Unit)
+        ));
+    }
+}
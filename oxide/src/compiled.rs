@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+mod api;
+mod encoding;
+mod legacy;
+mod pool;
+
+pub use api::{
+    ApiChange, ApiDiff, ApiField, ApiFunction, ApiItem, ApiKind, ApiVariant, ChangeKind, ModuleApi,
+};
+pub use encoding::{MODULE_FILE_MAGIC, MODULE_FILE_VERSION};
+pub use pool::LazyConstant;
+
+use crate::{
+    gc::{Address, Gc},
+    runtime,
+    source_map::{LineTable, LocalTable},
+    AttributeAccess, OxFunction, OxModule, Value, Vm,
+};
+
+// fails to compile if the compiled artifact can no longer be shared between threads.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<CompiledModule>();
+    assert_send_sync::<CompiledFunction>();
+};
+
+/// a constant that does not live on any vm heap.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    Char(char),
+    String(String),
+    Tuple(Vec<Constant>),
+    Unit,
+    /// a string or tuple of a module file that is read when the function using it is loaded,
+    /// see `CompiledModule::from_reader`.
+    Lazy(LazyConstant),
+}
+
+/// the value of a function global, objects are referenced by their index in the module.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompiledValue {
+    Constant(Constant),
+    Object(usize),
+    /// method of the structure at the given object index.
+    Method(usize, usize),
+    Module,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledFunction {
+    pub name: String,
+    pub arity: u8,
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    pub globals: Vec<CompiledValue>,
+    pub lines: LineTable,
+    pub locals: LocalTable,
+}
+
+/// what one object of a module is made of apart from the code of its functions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectLayout {
+    Function { name: String, arity: u8 },
+    Struct { name: String, methods: Vec<String> },
+    Constant(Constant),
+}
+
+/// the declarations and objects of a loaded module. A function of the module can only be
+/// replaced by one of a module with the same layout, see `Vm::replace_function`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleLayout {
+    pub api: Option<ModuleApi>,
+    pub objects: Vec<ObjectLayout>,
+}
+
+/// what a module was built with, it is written to its module file so the file says which
+/// compiler and options produced it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ModuleMetadata {
+    pub compiler_version: String,
+    /// the options given to the compiler by name, in the order they were given.
+    pub options: Vec<(String, String)>,
+}
+
+impl ModuleMetadata {
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl Display for ModuleMetadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "compiler {}", self.compiler_version)?;
+        for (name, value) in &self.options {
+            write!(f, "\n{}: {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// something about a module file that did not keep it from being read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadWarning {
+    /// the file is of a previous version of the format, it is upgraded when it is read.
+    DeprecatedVersion(u16),
+}
+
+impl Display for LoadWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DeprecatedVersion(version) => write!(
+                f,
+                "module file version {} is deprecated, recompile it to version {}",
+                version, MODULE_FILE_VERSION
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompiledObject {
+    Function(CompiledFunction),
+    Struct {
+        name: String,
+        /// the public fields of the instances and their slots.
+        fields: Vec<(String, usize)>,
+        methods: Vec<CompiledFunction>,
+    },
+    Constant(Constant),
+}
+
+/// heap independent copy of a module. It is immutable once created and can be shared between
+/// threads, every thread loads it into its own `Vm` with `CompiledModule::load`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledModule {
+    name: String,
+    entry: Option<usize>,
+    objects: Vec<CompiledObject>,
+    /// the declarations of the module, it is only known to the compiler that built it.
+    api: Option<ModuleApi>,
+    /// how the module was built, it is only known to the compiler that built it.
+    metadata: Option<ModuleMetadata>,
+}
+
+impl Constant {
+    pub(crate) fn from_value(value: &Value) -> Result<Self, runtime::Error> {
+        let constant = match value {
+            Value::I8(val) => Self::I8(*val),
+            Value::I16(val) => Self::I16(*val),
+            Value::I32(val) => Self::I32(*val),
+            Value::I64(val) => Self::I64(*val),
+            Value::U8(val) => Self::U8(*val),
+            Value::U16(val) => Self::U16(*val),
+            Value::U32(val) => Self::U32(*val),
+            Value::U64(val) => Self::U64(*val),
+            Value::F32(val) => Self::F32(*val),
+            Value::F64(val) => Self::F64(*val),
+            Value::Bool(val) => Self::Bool(*val),
+            Value::Char(val) => Self::Char(*val),
+            Value::String(val) => Self::String(val.as_str().to_string()),
+            Value::Tuple(val) => Self::Tuple(
+                val.elements()
+                    .iter()
+                    .map(Self::from_value)
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            Value::Unit => Self::Unit,
+            _ => return Err(runtime::Error::uncompilable_value(value.ty())),
+        };
+
+        Ok(constant)
+    }
+
+    pub(crate) fn to_value(&self, vm: &mut Vm) -> Value {
+        match self {
+            Self::I8(val) => Value::from(*val),
+            Self::I16(val) => Value::from(*val),
+            Self::I32(val) => Value::from(*val),
+            Self::I64(val) => Value::from(*val),
+            Self::U8(val) => Value::from(*val),
+            Self::U16(val) => Value::from(*val),
+            Self::U32(val) => Value::from(*val),
+            Self::U64(val) => Value::from(*val),
+            Self::F32(val) => Value::from(*val),
+            Self::F64(val) => Value::from(*val),
+            Self::Bool(val) => Value::from(*val),
+            Self::Char(val) => Value::from(*val),
+            Self::String(val) => Value::from(vm.intern_string(val)),
+            Self::Tuple(val) => {
+                // the elements are not rooted until the tuple exists.
+                let no_collection = vm.collection_disabled();
+                vm.force_no_collection(true);
+                let mut elements = vm.new_vec();
+                for element in val {
+                    let element = element.to_value(vm);
+                    elements.push(element);
+                }
+                let tuple = vm.new_tuple(elements);
+                vm.force_no_collection(no_collection);
+                Value::from(tuple.expect("out of memory loading a constant"))
+            }
+            Self::Unit => Value::Unit,
+            Self::Lazy(lazy) => lazy
+                .load()
+                .expect("the data section of the module file can not be read")
+                .to_value(vm),
+        }
+    }
+}
+
+impl CompiledFunction {
+    fn from_function(
+        function: &OxFunction,
+        objects: &HashMap<Address, CompiledValue>,
+        module: Address,
+    ) -> Result<Self, runtime::Error> {
+        let section = function.section();
+        let constants = section
+            .constants()
+            .iter()
+            .map(Constant::from_value)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let globals = section
+            .globals()
+            .iter()
+            .map(|global| match global.addr() {
+                Some(address) if address == module => Ok(CompiledValue::Module),
+                Some(address) => objects
+                    .get(&address)
+                    .cloned()
+                    .ok_or_else(|| runtime::Error::uncompilable_value(global.ty())),
+                None => Constant::from_value(global).map(CompiledValue::Constant),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            name: function.name().as_str().to_string(),
+            arity: function.arity(),
+            code: section.data().to_vec(),
+            constants,
+            globals,
+            lines: section.lines().clone(),
+            locals: section.locals().clone(),
+        })
+    }
+
+    pub(crate) fn load(&self, vm: &mut Vm) -> Gc<OxFunction> {
+        let mut section = vm.new_section();
+        section.write_bytes(self.code.as_slice());
+        for constant in &self.constants {
+            let value = constant.to_value(vm);
+            section.add_constant(value);
+        }
+        section.set_lines(self.lines.clone());
+        section.set_locals(self.locals.clone());
+
+        let name = vm.new_string_from_str(self.name.as_str());
+        vm.new_function(name, self.arity, section)
+    }
+
+    /// resolves the globals of a loaded copy of the function against the objects of `module`.
+    pub(crate) fn link(&self, mut loaded: Gc<OxFunction>, module: Gc<OxModule>, vm: &mut Vm) {
+        for global in &self.globals {
+            let value = match global {
+                CompiledValue::Constant(constant) => constant.to_value(vm),
+                CompiledValue::Object(idx) => *module.get(*idx).expect("invalid object index"),
+                CompiledValue::Method(idx, method) => {
+                    let structure = module.get(*idx).expect("invalid object index");
+                    Value::from(*structure.as_struct().get_attr(*method))
+                }
+                CompiledValue::Module => Value::from(module),
+            };
+            let section = loaded.as_ref_mut().section_mut();
+            let idx = section.add_global();
+            section.set_global(idx as usize, value);
+        }
+    }
+}
+
+impl ObjectLayout {
+    fn of(object: &CompiledObject) -> Self {
+        match object {
+            CompiledObject::Function(function) => Self::Function {
+                name: function.name.clone(),
+                arity: function.arity,
+            },
+            CompiledObject::Struct { name, methods, .. } => Self::Struct {
+                name: name.clone(),
+                methods: methods.iter().map(|method| method.name.clone()).collect(),
+            },
+            CompiledObject::Constant(constant) => Self::Constant(constant.clone()),
+        }
+    }
+}
+
+impl Display for ObjectLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Function { name, .. } => write!(f, "function '{}'", name),
+            Self::Struct { name, .. } => write!(f, "struct '{}'", name),
+            Self::Constant(constant) => write!(f, "the constant {:?}", constant),
+        }
+    }
+}
+
+impl CompiledModule {
+    /// copies a module out of the vm heap.
+    pub fn from_module(module: Gc<OxModule>) -> Result<Self, runtime::Error> {
+        let mut references = HashMap::new();
+        for (idx, object) in module.objects().iter().enumerate() {
+            if let Value::Struct(structure) = object {
+                for (method, function) in structure.methods().iter().enumerate() {
+                    references.insert(function.ptr(), CompiledValue::Method(idx, method));
+                }
+            }
+            if let Some(address) = object.addr() {
+                references.insert(address, CompiledValue::Object(idx));
+            }
+        }
+
+        let objects = module
+            .objects()
+            .iter()
+            .map(|object| match object {
+                Value::Function(function) => {
+                    CompiledFunction::from_function(function, &references, module.ptr())
+                        .map(CompiledObject::Function)
+                }
+                Value::Struct(structure) => {
+                    let methods = structure
+                        .methods()
+                        .iter()
+                        .map(|function| {
+                            CompiledFunction::from_function(function, &references, module.ptr())
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(CompiledObject::Struct {
+                        name: structure.name().as_str().to_string(),
+                        fields: structure.fields().to_vec(),
+                        methods,
+                    })
+                }
+                _ => Constant::from_value(object).map(CompiledObject::Constant),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let entry = module
+            .entry()
+            .and_then(|entry| entry.addr())
+            .and_then(|address| match references.get(&address) {
+                Some(CompiledValue::Object(idx)) => Some(*idx),
+                _ => None,
+            });
+
+        Ok(Self {
+            name: module.name().as_str().to_string(),
+            entry,
+            objects,
+            api: None,
+            metadata: None,
+        })
+    }
+
+    /// records the declarations of the module, they are written to its module file.
+    pub fn with_api(mut self, api: ModuleApi) -> Self {
+        self.api = Some(api);
+        self
+    }
+
+    pub fn api(&self) -> Option<&ModuleApi> {
+        self.api.as_ref()
+    }
+
+    /// records how the module was built, it is written to its module file.
+    pub fn with_metadata(mut self, metadata: ModuleMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// how the module was built, none for a module file of a version before metadata was
+    /// written or a module built without it.
+    pub fn metadata(&self) -> Option<&ModuleMetadata> {
+        self.metadata.as_ref()
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn objects(&self) -> &[CompiledObject] {
+        self.objects.as_slice()
+    }
+
+    pub fn layout(&self) -> ModuleLayout {
+        ModuleLayout {
+            api: self.api.clone(),
+            objects: self.objects.iter().map(ObjectLayout::of).collect(),
+        }
+    }
+
+    /// the compiled function with the name a source map gives it, `Point.len` for a method.
+    pub fn function(&self, name: &str) -> Option<&CompiledFunction> {
+        let (structure, name) = match name.rsplit_once('.') {
+            Some((structure, method)) => (Some(structure), method),
+            None => (None, name),
+        };
+        self.objects
+            .iter()
+            .find_map(|object| match (object, structure) {
+                (CompiledObject::Function(function), None) if function.name == name => {
+                    Some(function)
+                }
+                (
+                    CompiledObject::Struct {
+                        name: found,
+                        methods,
+                        ..
+                    },
+                    Some(structure),
+                ) if found == structure => methods.iter().find(|method| method.name == name),
+                _ => None,
+            })
+    }
+
+    /// creates a new copy of the module on the heap of the vm, the compiled module is left
+    /// untouched so it can be loaded by any number of vms.
+    pub fn load(&self, vm: &mut Vm) -> Gc<OxModule> {
+        // the objects are not rooted until the module is complete, collect before instead.
+        if vm.should_collect() {
+            vm.gc_collect();
+        }
+        vm.force_no_collection(true);
+
+        let name = vm.new_string_from_str(self.name.as_str());
+        let mut module = vm.new_empty_module(name);
+        if let Some(entry) = self.entry {
+            module.as_ref_mut().set_entry(entry);
+        }
+        module.as_ref_mut().set_layout(self.layout());
+
+        let mut functions = vec![];
+        for object in &self.objects {
+            let value = match object {
+                CompiledObject::Function(function) => {
+                    let loaded = function.load(vm);
+                    functions.push((loaded, function));
+                    Value::from(loaded)
+                }
+                CompiledObject::Struct {
+                    name,
+                    fields,
+                    methods,
+                } => {
+                    let mut loaded_methods = vm.new_vec();
+                    for method in methods {
+                        let loaded = method.load(vm);
+                        functions.push((loaded, method));
+                        loaded_methods.push(loaded);
+                    }
+                    let name = vm.new_string_from_str(name.as_str());
+                    let mut structure = vm.new_struct(name, loaded_methods);
+                    structure.as_ref_mut().set_fields(fields.clone());
+                    Value::from(structure)
+                }
+                CompiledObject::Constant(constant) => constant.to_value(vm),
+            };
+            module.as_ref_mut().add_object(value);
+        }
+
+        // globals can refer to any object of the module, resolve them once all exist.
+        for (loaded, function) in functions {
+            function.link(loaded, module, vm);
+        }
+
+        vm.force_no_collection(false);
+        module
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CompiledModule;
+    use crate::{gc::Gc, vm::OpCode, OxModule, Value, Vm};
+    use std::{io::Cursor, sync::Arc, thread};
+
+    // main calls a helper through a global and returns its result.
+    fn build_module(vm: &mut Vm) -> Gc<OxModule> {
+        let mut helper = vm.new_section();
+        helper.add_constant(Value::I32(40));
+        helper.add_constant(Value::I32(2));
+        helper.write_arg(OpCode::LoadI32, 0);
+        helper.write_arg(OpCode::LoadI32, 1);
+        helper.write_op(OpCode::AddI32);
+        helper.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("helper");
+        let helper = vm.new_function(name, 0, helper);
+
+        let mut main = vm.new_section();
+        let idx = main.add_global();
+        main.set_global(idx as usize, Value::from(helper));
+        let greeting = vm.new_gc_string_from_str("hello");
+        let idx = main.add_constant(Value::from(greeting));
+        main.write_index(OpCode::LoadStr, idx);
+        main.write_op(OpCode::Pop);
+        main.write_arg(OpCode::LoadGlobal, 0);
+        main.write_arg(OpCode::Call, 0);
+        main.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, main);
+
+        let name = vm.new_string_from_str("test");
+        let mut module = vm.new_empty_module(name);
+        module.as_ref_mut().add_object(Value::from(helper));
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        module
+    }
+
+    fn run(vm: &mut Vm, compiled: &CompiledModule) -> i32 {
+        let module = compiled.load(vm);
+        vm.run_module(module).unwrap();
+        let result = vm.pop().as_i32();
+        vm.pop();
+        result
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let module = build_module(&mut vm);
+        let compiled = CompiledModule::from_module(module).unwrap();
+
+        let mut other = Vm::new();
+        let loaded = compiled.load(&mut other);
+        assert_eq!(CompiledModule::from_module(loaded).unwrap(), compiled);
+        assert_eq!(run(&mut other, &compiled), 42);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let compiled = {
+            let mut vm = Vm::new();
+            vm.force_no_collection(true);
+            let module = build_module(&mut vm);
+            Arc::new(CompiledModule::from_module(module).unwrap())
+        };
+
+        let threads = (0..8)
+            .map(|_| {
+                let compiled = compiled.clone();
+                thread::spawn(move || {
+                    let mut vm = Vm::new();
+                    (0..1000).all(|_| run(&mut vm, &compiled) == 42)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for thread in threads {
+            assert!(thread.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_lazy_module_file() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let module = build_module(&mut vm);
+        let compiled = CompiledModule::from_module(module).unwrap();
+
+        let read = CompiledModule::from_reader(Cursor::new(compiled.to_bytes())).unwrap();
+        let mut other = Vm::new();
+        assert_eq!(run(&mut other, &read), 42);
+        let loaded = read.load(&mut other);
+        assert_eq!(CompiledModule::from_module(loaded).unwrap(), compiled);
+    }
+}
@@ -0,0 +1,450 @@
+use std::fmt::{Display, Formatter};
+
+/// the items a module declares along with their signatures, it is what code using the module
+/// depends on. Types are written as they are in the source of the module.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModuleApi {
+    pub items: Vec<ApiItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiItem {
+    pub name: String,
+    pub public: bool,
+    pub kind: ApiKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiKind {
+    Function(ApiFunction),
+    Struct {
+        fields: Vec<ApiField>,
+        methods: Vec<ApiItem>,
+    },
+    Enum {
+        variants: Vec<ApiVariant>,
+    },
+    Variable {
+        ty: String,
+        mutable: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiFunction {
+    pub params: Vec<String>,
+    pub ret: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiField {
+    pub name: String,
+    pub ty: String,
+    pub public: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiVariant {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl ApiKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ApiKind::Function(_) => "function",
+            ApiKind::Struct { .. } => "struct",
+            ApiKind::Enum { .. } => "enum",
+            ApiKind::Variable { .. } => "variable",
+        }
+    }
+}
+
+impl Display for ApiFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}) {}", self.params.join(", "), self.ret)
+    }
+}
+
+impl Display for ApiVariant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.fields.is_empty() {
+            write!(f, "{}", self.name)
+        } else {
+            write!(f, "{}({})", self.name, self.fields.join(", "))
+        }
+    }
+}
+
+/// how the api of a module changed from one version to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeKind {
+    Removed,
+    Added,
+    /// a public item that is now private.
+    Narrowed,
+    KindChanged { old: String, new: String },
+    SignatureChanged { old: String, new: String },
+    VariantRemoved(String),
+    VariantAdded(String),
+    VariantChanged { old: String, new: String },
+    /// a public field that was removed or made private.
+    FieldRemoved(String),
+    FieldAdded(String),
+    FieldTypeChanged { field: String, old: String, new: String },
+    FieldMoved { field: String, old: usize, new: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiChange {
+    /// `Point.len` for a method of a struct.
+    pub item: String,
+    pub kind: ChangeKind,
+}
+
+impl ApiChange {
+    /// whether code written against the old module can fail against the new one.
+    pub fn is_breaking(&self) -> bool {
+        !matches!(
+            self.kind,
+            ChangeKind::Added | ChangeKind::VariantAdded(_) | ChangeKind::FieldAdded(_)
+        )
+    }
+}
+
+impl Display for ApiChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let item = self.item.as_str();
+        match &self.kind {
+            ChangeKind::Removed => write!(f, "'{}' was removed", item),
+            ChangeKind::Added => write!(f, "'{}' was added", item),
+            ChangeKind::Narrowed => write!(f, "'{}' is no longer public", item),
+            ChangeKind::KindChanged { old, new } => {
+                write!(f, "'{}' changed from a {} to a {}", item, old, new)
+            }
+            ChangeKind::SignatureChanged { old, new } => {
+                write!(f, "'{}' changed from '{}' to '{}'", item, old, new)
+            }
+            ChangeKind::VariantRemoved(variant) => {
+                write!(f, "variant '{}' of '{}' was removed", variant, item)
+            }
+            ChangeKind::VariantAdded(variant) => {
+                write!(f, "variant '{}' of '{}' was added", variant, item)
+            }
+            ChangeKind::VariantChanged { old, new } => {
+                write!(f, "variant '{}' of '{}' is now '{}'", old, item, new)
+            }
+            ChangeKind::FieldRemoved(field) => {
+                write!(f, "public field '{}' of '{}' was removed", field, item)
+            }
+            ChangeKind::FieldAdded(field) => {
+                write!(f, "public field '{}' of '{}' was added", field, item)
+            }
+            ChangeKind::FieldTypeChanged { field, old, new } => write!(
+                f,
+                "field '{}' of '{}' changed from '{}' to '{}'",
+                field, item, old, new
+            ),
+            ChangeKind::FieldMoved { field, old, new } => write!(
+                f,
+                "field '{}' of '{}' moved from position {} to {}",
+                field, item, old, new
+            ),
+        }
+    }
+}
+
+/// the changes between two versions of the api of a module.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ApiDiff {
+    pub changes: Vec<ApiChange>,
+}
+
+impl ApiDiff {
+    pub fn breaking(&self) -> impl Iterator<Item = &ApiChange> {
+        self.changes.iter().filter(|change| change.is_breaking())
+    }
+
+    pub fn additive(&self) -> impl Iterator<Item = &ApiChange> {
+        self.changes.iter().filter(|change| !change.is_breaking())
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.breaking().next().is_some()
+    }
+
+    fn change(&mut self, item: &str, kind: ChangeKind) {
+        self.changes.push(ApiChange {
+            item: item.to_owned(),
+            kind,
+        });
+    }
+
+    /// compares the public items, anything that is private in both versions is not part of
+    /// the api.
+    fn items(&mut self, prefix: &str, old: &[ApiItem], new: &[ApiItem]) {
+        let path = |item: &ApiItem| format!("{}{}", prefix, item.name);
+        for old in old.iter().filter(|item| item.public) {
+            match new.iter().find(|item| item.name == old.name) {
+                None => self.change(&path(old), ChangeKind::Removed),
+                Some(new) if !new.public => self.change(&path(old), ChangeKind::Narrowed),
+                Some(new) => self.item(&path(old), old, new),
+            }
+        }
+        for new in new.iter().filter(|item| item.public) {
+            if !old.iter().any(|item| item.public && item.name == new.name) {
+                self.change(&path(new), ChangeKind::Added);
+            }
+        }
+    }
+
+    fn item(&mut self, path: &str, old: &ApiItem, new: &ApiItem) {
+        match (&old.kind, &new.kind) {
+            (ApiKind::Function(old), ApiKind::Function(new)) => {
+                if old != new {
+                    let kind = ChangeKind::SignatureChanged {
+                        old: old.to_string(),
+                        new: new.to_string(),
+                    };
+                    self.change(path, kind);
+                }
+            }
+            (
+                ApiKind::Variable {
+                    ty: old_ty,
+                    mutable: old_mutable,
+                },
+                ApiKind::Variable {
+                    ty: new_ty,
+                    mutable: new_mutable,
+                },
+            ) => {
+                if old_ty != new_ty || old_mutable != new_mutable {
+                    let describe = |ty: &str, mutable: bool| {
+                        format!("{} {}", if mutable { "mut" } else { "let" }, ty)
+                    };
+                    let kind = ChangeKind::SignatureChanged {
+                        old: describe(old_ty, *old_mutable),
+                        new: describe(new_ty, *new_mutable),
+                    };
+                    self.change(path, kind);
+                }
+            }
+            (ApiKind::Enum { variants: old }, ApiKind::Enum { variants: new }) => {
+                for variant in old {
+                    match new.iter().find(|other| other.name == variant.name) {
+                        None => self.change(path, ChangeKind::VariantRemoved(variant.name.clone())),
+                        Some(other) if other != variant => {
+                            let kind = ChangeKind::VariantChanged {
+                                old: variant.to_string(),
+                                new: other.to_string(),
+                            };
+                            self.change(path, kind);
+                        }
+                        Some(_) => {}
+                    }
+                }
+                for variant in new {
+                    if !old.iter().any(|other| other.name == variant.name) {
+                        self.change(path, ChangeKind::VariantAdded(variant.name.clone()));
+                    }
+                }
+            }
+            (
+                ApiKind::Struct {
+                    fields: old_fields,
+                    methods: old_methods,
+                },
+                ApiKind::Struct {
+                    fields: new_fields,
+                    methods: new_methods,
+                },
+            ) => {
+                self.fields(path, old_fields, new_fields);
+                self.items(format!("{}.", path).as_str(), old_methods, new_methods);
+            }
+            (old, new) => {
+                let kind = ChangeKind::KindChanged {
+                    old: old.name().to_owned(),
+                    new: new.name().to_owned(),
+                };
+                self.change(path, kind);
+            }
+        }
+    }
+
+    /// fields are stored by their position, moving a public field breaks compiled code.
+    fn fields(&mut self, path: &str, old: &[ApiField], new: &[ApiField]) {
+        for (old_idx, field) in old.iter().enumerate().filter(|(_, field)| field.public) {
+            let found = new
+                .iter()
+                .enumerate()
+                .find(|(_, other)| other.public && other.name == field.name);
+            match found {
+                None => self.change(path, ChangeKind::FieldRemoved(field.name.clone())),
+                Some((_, other)) if other.ty != field.ty => {
+                    let kind = ChangeKind::FieldTypeChanged {
+                        field: field.name.clone(),
+                        old: field.ty.clone(),
+                        new: other.ty.clone(),
+                    };
+                    self.change(path, kind);
+                }
+                Some((new_idx, _)) if new_idx != old_idx => {
+                    let kind = ChangeKind::FieldMoved {
+                        field: field.name.clone(),
+                        old: old_idx,
+                        new: new_idx,
+                    };
+                    self.change(path, kind);
+                }
+                Some(_) => {}
+            }
+        }
+        for field in new.iter().filter(|field| field.public) {
+            if !old
+                .iter()
+                .any(|other| other.public && other.name == field.name)
+            {
+                self.change(path, ChangeKind::FieldAdded(field.name.clone()));
+            }
+        }
+    }
+}
+
+impl Display for ApiDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "no changes to the api");
+        }
+        if self.is_breaking() {
+            writeln!(f, "breaking changes:")?;
+            for change in self.breaking() {
+                writeln!(f, "  {}", change)?;
+            }
+        }
+        if self.additive().next().is_some() {
+            writeln!(f, "non-breaking changes:")?;
+            for change in self.additive() {
+                writeln!(f, "  {}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ModuleApi {
+    /// the changes made by new to the public items of this api.
+    pub fn diff(&self, new: &ModuleApi) -> ApiDiff {
+        let mut diff = ApiDiff::default();
+        diff.items("", &self.items, &new.items);
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiField, ApiFunction, ApiItem, ApiKind, ApiVariant, ChangeKind, ModuleApi};
+
+    fn function(name: &str, params: &[&str], ret: &str) -> ApiItem {
+        ApiItem {
+            name: name.to_string(),
+            public: true,
+            kind: ApiKind::Function(ApiFunction {
+                params: params.iter().map(ToString::to_string).collect(),
+                ret: ret.to_string(),
+            }),
+        }
+    }
+
+    fn field(name: &str, ty: &str, public: bool) -> ApiField {
+        ApiField {
+            name: name.to_string(),
+            ty: ty.to_string(),
+            public,
+        }
+    }
+
+    fn variant(name: &str, fields: &[&str]) -> ApiVariant {
+        ApiVariant {
+            name: name.to_string(),
+            fields: fields.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn api(items: Vec<ApiItem>) -> ModuleApi {
+        ModuleApi { items }
+    }
+
+    #[test]
+    fn test_private_items_are_not_part_of_the_api() {
+        let mut helper = function("helper", &["i64"], "i64");
+        helper.public = false;
+        let old = api(vec![function("area", &["i64"], "i64"), helper.clone()]);
+        let mut changed = helper;
+        changed.kind = ApiKind::Variable {
+            ty: "i64".to_string(),
+            mutable: true,
+        };
+        let new = api(vec![function("area", &["i64"], "i64"), changed]);
+        let diff = old.diff(&new);
+        assert!(diff.changes.is_empty());
+        assert_eq!(diff.to_string(), "no changes to the api\n");
+    }
+
+    #[test]
+    fn test_fields_and_variants() {
+        let shape = |fields, variants| {
+            api(vec![
+                ApiItem {
+                    name: "Point".to_string(),
+                    public: true,
+                    kind: ApiKind::Struct {
+                        fields,
+                        methods: vec![],
+                    },
+                },
+                ApiItem {
+                    name: "Shape".to_string(),
+                    public: true,
+                    kind: ApiKind::Enum { variants },
+                },
+            ])
+        };
+        let old = shape(
+            vec![
+                field("x", "i64", true),
+                field("y", "i64", true),
+                field("cache", "i64", false),
+            ],
+            vec![variant("Circle", &["i64"]), variant("Square", &["i64"])],
+        );
+        let new = shape(
+            vec![
+                field("cache", "i64", false),
+                field("y", "i64", true),
+                field("z", "i64", true),
+            ],
+            vec![variant("Circle", &["f64"]), variant("Line", &[])],
+        );
+        let kinds = old
+            .diff(&new)
+            .changes
+            .into_iter()
+            .map(|change| change.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                ChangeKind::FieldRemoved("x".to_string()),
+                ChangeKind::FieldAdded("z".to_string()),
+                ChangeKind::VariantChanged {
+                    old: "Circle(i64)".to_string(),
+                    new: "Circle(f64)".to_string()
+                },
+                ChangeKind::VariantRemoved("Square".to_string()),
+                ChangeKind::VariantAdded("Line".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1084 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    compiled::{
+        legacy,
+        pool::{read_error, Data, DataSource, Entry, Pool},
+        ApiField, ApiFunction, ApiItem, ApiKind, ApiVariant, CompiledFunction, CompiledModule,
+        CompiledObject, CompiledValue, Constant, LazyConstant, LoadWarning, ModuleApi,
+        ModuleMetadata,
+    },
+    runtime,
+    source_map::{LineTable, LocalTable},
+};
+
+/// first bytes of every module file.
+pub const MODULE_FILE_MAGIC: &[u8; 4] = b"OXB\0";
+
+/// current version of the module file format.
+pub const MODULE_FILE_VERSION: u16 = 18;
+
+/// the magic, the version and the offset of the data section.
+const HEADER_LEN: usize = 10;
+
+const NO_ENTRY: u32 = u32::MAX;
+
+/// constants are written once to a pool shared by every function of the module, functions
+/// refer to them by their index in the pool. Scalars are written inline, strings are written
+/// to the data section at the end of the file so they can be read when they are first used.
+#[derive(Default)]
+struct ConstantPool {
+    entries: Vec<Vec<u8>>,
+    indices: HashMap<Vec<u8>, u32>,
+    data: Vec<u8>,
+}
+
+impl ConstantPool {
+    fn intern(&mut self, constant: &Constant) -> u32 {
+        let constant = match constant {
+            Constant::Lazy(lazy) => lazy
+                .load()
+                .expect("the data section of the module file can not be read"),
+            constant => constant,
+        };
+        let mut key = Writer::default();
+        key.constant(constant);
+        if let Some(idx) = self.indices.get(&key.bytes) {
+            return *idx;
+        }
+
+        let mut entry = Writer::default();
+        match constant {
+            Constant::String(val) => {
+                entry.u8(12);
+                entry.len(self.data.len());
+                entry.len(val.len());
+                self.data.extend_from_slice(val.as_bytes());
+            }
+            Constant::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.intern(element))
+                    .collect::<Vec<_>>();
+                entry.u8(13);
+                entry.len(elements.len());
+                for element in elements {
+                    entry.u32(element);
+                }
+            }
+            scalar => entry.constant(scalar),
+        }
+
+        let idx = self.entries.len() as u32;
+        self.indices.insert(key.bytes, idx);
+        self.entries.push(entry.bytes);
+        idx
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.u32(len as u32);
+    }
+
+    fn data(&mut self, data: &[u8]) {
+        self.len(data.len());
+        self.bytes.extend_from_slice(data);
+    }
+
+    fn str(&mut self, value: &str) {
+        self.data(value.as_bytes());
+    }
+
+    /// the encoding of a constant on its own, it is the key of the constant in the pool.
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::I8(val) => {
+                self.u8(0);
+                self.u8(*val as u8);
+            }
+            Constant::I16(val) => {
+                self.u8(1);
+                self.u16(*val as u16);
+            }
+            Constant::I32(val) => {
+                self.u8(2);
+                self.u32(*val as u32);
+            }
+            Constant::I64(val) => {
+                self.u8(3);
+                self.u64(*val as u64);
+            }
+            Constant::U8(val) => {
+                self.u8(4);
+                self.u8(*val);
+            }
+            Constant::U16(val) => {
+                self.u8(5);
+                self.u16(*val);
+            }
+            Constant::U32(val) => {
+                self.u8(6);
+                self.u32(*val);
+            }
+            Constant::U64(val) => {
+                self.u8(7);
+                self.u64(*val);
+            }
+            Constant::F32(val) => {
+                self.u8(8);
+                self.u32(val.to_bits());
+            }
+            Constant::F64(val) => {
+                self.u8(9);
+                self.u64(val.to_bits());
+            }
+            Constant::Bool(val) => {
+                self.u8(10);
+                self.u8(*val as u8);
+            }
+            Constant::Char(val) => {
+                self.u8(11);
+                self.u32(*val as u32);
+            }
+            Constant::String(val) => {
+                self.u8(12);
+                self.str(val);
+            }
+            Constant::Tuple(elements) => {
+                self.u8(13);
+                self.len(elements.len());
+                for element in elements {
+                    self.constant(element);
+                }
+            }
+            Constant::Unit => self.u8(14),
+            Constant::Lazy(_) => unreachable!("lazy constants are loaded before they are written"),
+        }
+    }
+
+    fn function(&mut self, function: &CompiledFunction, pool: &mut ConstantPool) {
+        self.str(&function.name);
+        self.u8(function.arity);
+        self.data(&function.code);
+
+        self.len(function.constants.len());
+        for constant in &function.constants {
+            self.u32(pool.intern(constant));
+        }
+
+        self.len(function.globals.len());
+        for global in &function.globals {
+            match global {
+                CompiledValue::Constant(constant) => {
+                    self.u8(0);
+                    self.u32(pool.intern(constant));
+                }
+                CompiledValue::Object(idx) => {
+                    self.u8(1);
+                    self.len(*idx);
+                }
+                CompiledValue::Method(idx, method) => {
+                    self.u8(2);
+                    self.len(*idx);
+                    self.len(*method);
+                }
+                CompiledValue::Module => self.u8(3),
+            }
+        }
+
+        self.len(function.lines.runs().len());
+        for run in function.lines.runs() {
+            self.len(run.offset);
+            self.len(run.line);
+            self.u8(run.is_statement_start as u8);
+        }
+
+        self.len(function.locals.entries().len());
+        for entry in function.locals.entries() {
+            self.len(entry.offset);
+            self.len(entry.slot);
+            self.str(&entry.name);
+        }
+    }
+
+    fn strs(&mut self, values: &[String]) {
+        self.len(values.len());
+        for value in values {
+            self.str(value);
+        }
+    }
+
+    fn api_function(&mut self, function: &ApiFunction) {
+        self.strs(&function.params);
+        self.str(&function.ret);
+    }
+
+    fn api_item(&mut self, item: &ApiItem) {
+        self.str(&item.name);
+        self.u8(item.public as u8);
+        match &item.kind {
+            ApiKind::Function(function) => {
+                self.u8(0);
+                self.api_function(function);
+            }
+            ApiKind::Struct { fields, methods } => {
+                self.u8(1);
+                self.len(fields.len());
+                for field in fields {
+                    self.str(&field.name);
+                    self.str(&field.ty);
+                    self.u8(field.public as u8);
+                }
+                self.len(methods.len());
+                for method in methods {
+                    self.api_item(method);
+                }
+            }
+            ApiKind::Enum { variants } => {
+                self.u8(2);
+                self.len(variants.len());
+                for variant in variants {
+                    self.str(&variant.name);
+                    self.strs(&variant.fields);
+                }
+            }
+            ApiKind::Variable { ty, mutable } => {
+                self.u8(3);
+                self.str(ty);
+                self.u8(*mutable as u8);
+            }
+        }
+    }
+
+    /// the api section follows the objects, a module built without one has a single zero.
+    fn api(&mut self, api: Option<&ModuleApi>) {
+        match api {
+            Some(api) => {
+                self.u8(1);
+                self.len(api.items.len());
+                for item in &api.items {
+                    self.api_item(item);
+                }
+            }
+            None => self.u8(0),
+        }
+    }
+
+    /// the metadata section follows the api section, a module built without it has a single
+    /// zero.
+    fn metadata(&mut self, metadata: Option<&ModuleMetadata>) {
+        match metadata {
+            Some(metadata) => {
+                self.u8(1);
+                self.str(&metadata.compiler_version);
+                self.len(metadata.options.len());
+                for (name, value) in &metadata.options {
+                    self.str(name);
+                    self.str(value);
+                }
+            }
+            None => self.u8(0),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, reason: &str) -> runtime::Error {
+        runtime::Error::invalid_module_file(self.offset, reason.to_string())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], runtime::Error> {
+        match self.bytes.get(self.offset..self.offset.saturating_add(len)) {
+            Some(bytes) => {
+                self.offset += len;
+                Ok(bytes)
+            }
+            None => Err(self.error("unexpected end of file")),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8, runtime::Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, runtime::Error> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, runtime::Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, runtime::Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, runtime::Error> {
+        self.u32().map(|len| len as usize)
+    }
+
+    fn data(&mut self) -> Result<&'a [u8], runtime::Error> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, runtime::Error> {
+        let data = self.data()?;
+        String::from_utf8(data.to_vec()).map_err(|_| self.error("a string is not utf-8"))
+    }
+
+    /// the entry at idx of a pool whose data section is data_len bytes.
+    fn entry(&mut self, idx: usize, data_len: usize) -> Result<Entry, runtime::Error> {
+        let constant = match self.u8()? {
+            0 => Constant::I8(self.u8()? as i8),
+            1 => Constant::I16(self.u16()? as i16),
+            2 => Constant::I32(self.u32()? as i32),
+            3 => Constant::I64(self.u64()? as i64),
+            4 => Constant::U8(self.u8()?),
+            5 => Constant::U16(self.u16()?),
+            6 => Constant::U32(self.u32()?),
+            7 => Constant::U64(self.u64()?),
+            8 => Constant::F32(f32::from_bits(self.u32()?)),
+            9 => Constant::F64(f64::from_bits(self.u64()?)),
+            10 => Constant::Bool(self.u8()? != 0),
+            11 => match std::char::from_u32(self.u32()?) {
+                Some(val) => Constant::Char(val),
+                None => return Err(self.error("invalid char constant")),
+            },
+            12 => {
+                let offset = self.len()?;
+                let len = self.len()?;
+                if offset.saturating_add(len) > data_len {
+                    return Err(self.error("a string is outside of the data section"));
+                }
+                return Ok(Entry::String { offset, len });
+            }
+            13 => {
+                let mut elements = vec![];
+                for _ in 0..self.len()? {
+                    let element = self.len()?;
+                    if element >= idx {
+                        return Err(self.error("a tuple refers to a constant written after it"));
+                    }
+                    elements.push(element);
+                }
+                return Ok(Entry::Tuple(elements));
+            }
+            14 => Constant::Unit,
+            _ => return Err(self.error("unknown constant kind")),
+        };
+        Ok(Entry::Scalar(constant))
+    }
+
+    fn pool<'d>(
+        &mut self,
+        data: Data<'d>,
+        data_offset: usize,
+        data_len: usize,
+    ) -> Result<Pool<'d>, runtime::Error> {
+        let len = self.len()?;
+        let entries = (0..len)
+            .map(|idx| self.entry(idx, data_len))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Pool::new(entries, data, data_offset))
+    }
+
+    fn pool_index(&mut self, pool: &Pool) -> Result<usize, runtime::Error> {
+        let idx = self.len()?;
+        if idx >= pool.len() {
+            return Err(self.error("constant index out of bounds of the pool"));
+        }
+        Ok(idx)
+    }
+
+    fn pool_constant(&mut self, pool: &Pool) -> Result<Constant, runtime::Error> {
+        let idx = self.pool_index(pool)?;
+        pool.constant(idx).cloned()
+    }
+
+    /// the constants of functions are left in the data section of a lazily read pool.
+    /// a function of a file of version, the line runs of a version without statement flags
+    /// each start a statement.
+    fn function(
+        &mut self,
+        pool: &Pool,
+        lazy: Option<&Arc<Pool<'static>>>,
+        version: u16,
+    ) -> Result<CompiledFunction, runtime::Error> {
+        let name = self.string()?;
+        let arity = self.u8()?;
+        let code = self.data()?.to_vec();
+
+        let mut constants = vec![];
+        for _ in 0..self.len()? {
+            let idx = self.pool_index(pool)?;
+            let constant = match lazy {
+                Some(lazy) if !pool.is_loaded(idx) => {
+                    Constant::Lazy(LazyConstant::new(lazy.clone(), idx))
+                }
+                _ => pool.constant(idx)?.clone(),
+            };
+            constants.push(constant);
+        }
+
+        let len = self.len()?;
+        let mut globals = vec![];
+        for _ in 0..len {
+            let global = match self.u8()? {
+                0 => CompiledValue::Constant(self.pool_constant(pool)?),
+                1 => CompiledValue::Object(self.len()?),
+                2 => CompiledValue::Method(self.len()?, self.len()?),
+                3 => CompiledValue::Module,
+                _ => return Err(self.error("unknown global kind")),
+            };
+            globals.push(global);
+        }
+
+        let mut lines = LineTable::new();
+        for _ in 0..self.len()? {
+            let offset = self.len()?;
+            let line = self.len()?;
+            if !legacy::has_statement_flags(version) || self.bool()? {
+                lines.push(offset, line);
+            } else {
+                lines.push_expression(offset, line);
+            }
+        }
+
+        let mut locals = LocalTable::new();
+        for _ in 0..self.len()? {
+            let offset = self.len()?;
+            let slot = self.len()?;
+            locals.push(offset, slot, self.string()?);
+        }
+
+        Ok(CompiledFunction {
+            name,
+            arity,
+            code,
+            constants,
+            globals,
+            lines,
+            locals,
+        })
+    }
+
+    fn bool(&mut self) -> Result<bool, runtime::Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(self.error("invalid bool")),
+        }
+    }
+
+    fn strings(&mut self) -> Result<Vec<String>, runtime::Error> {
+        (0..self.len()?).map(|_| self.string()).collect()
+    }
+
+    fn api_function(&mut self) -> Result<ApiFunction, runtime::Error> {
+        Ok(ApiFunction {
+            params: self.strings()?,
+            ret: self.string()?,
+        })
+    }
+
+    fn api_item(&mut self) -> Result<ApiItem, runtime::Error> {
+        let name = self.string()?;
+        let public = self.bool()?;
+        let kind = match self.u8()? {
+            0 => ApiKind::Function(self.api_function()?),
+            1 => {
+                let mut fields = vec![];
+                for _ in 0..self.len()? {
+                    fields.push(ApiField {
+                        name: self.string()?,
+                        ty: self.string()?,
+                        public: self.bool()?,
+                    });
+                }
+                let methods = (0..self.len()?)
+                    .map(|_| self.api_item())
+                    .collect::<Result<Vec<_>, _>>()?;
+                ApiKind::Struct { fields, methods }
+            }
+            2 => {
+                let mut variants = vec![];
+                for _ in 0..self.len()? {
+                    variants.push(ApiVariant {
+                        name: self.string()?,
+                        fields: self.strings()?,
+                    });
+                }
+                ApiKind::Enum { variants }
+            }
+            3 => ApiKind::Variable {
+                ty: self.string()?,
+                mutable: self.bool()?,
+            },
+            _ => return Err(self.error("unknown api item kind")),
+        };
+        Ok(ApiItem { name, public, kind })
+    }
+
+    fn api(&mut self) -> Result<Option<ModuleApi>, runtime::Error> {
+        if !self.bool()? {
+            return Ok(None);
+        }
+        let items = (0..self.len()?)
+            .map(|_| self.api_item())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(ModuleApi { items }))
+    }
+
+    fn metadata(&mut self) -> Result<Option<ModuleMetadata>, runtime::Error> {
+        if !self.bool()? {
+            return Ok(None);
+        }
+        let compiler_version = self.string()?;
+        let options = (0..self.len()?)
+            .map(|_| Ok((self.string()?, self.string()?)))
+            .collect::<Result<Vec<_>, runtime::Error>>()?;
+        Ok(Some(ModuleMetadata {
+            compiler_version,
+            options,
+        }))
+    }
+
+    /// checks the magic and version of the file, they are the offset of the data section and
+    /// the version.
+    fn header(&mut self) -> Result<(usize, u16), runtime::Error> {
+        if self.take(MODULE_FILE_MAGIC.len()).ok() != Some(&MODULE_FILE_MAGIC[..]) {
+            return Err(runtime::Error::invalid_module_file(
+                0,
+                "not a module file".to_string(),
+            ));
+        }
+        let version = self.u16()?;
+        if version != MODULE_FILE_VERSION && !legacy::is_supported(version) {
+            return Err(self.error(&format!(
+                "unsupported version {}, expected {}",
+                version, MODULE_FILE_VERSION
+            )));
+        }
+        let data_offset = self.len()?;
+        if data_offset < HEADER_LEN {
+            return Err(self.error("the data section overlaps the header"));
+        }
+        Ok((data_offset, version))
+    }
+
+    /// the objects of the module, they are read up to the data section. The file is of
+    /// version, the structures of a version without field tables have no public fields.
+    fn module(
+        &mut self,
+        pool: &Pool,
+        lazy: Option<&Arc<Pool<'static>>>,
+        version: u16,
+    ) -> Result<CompiledModule, runtime::Error> {
+        let name = self.string()?;
+        let entry = match self.u32()? {
+            NO_ENTRY => None,
+            entry => Some(entry as usize),
+        };
+        let mut objects = vec![];
+        for _ in 0..self.len()? {
+            let object = match self.u8()? {
+                0 => CompiledObject::Function(self.function(pool, lazy, version)?),
+                1 => {
+                    let name = self.string()?;
+                    let fields = if legacy::has_field_tables(version) {
+                        (0..self.len()?)
+                            .map(|_| Ok((self.string()?, self.len()?)))
+                            .collect::<Result<Vec<_>, runtime::Error>>()?
+                    } else {
+                        vec![]
+                    };
+                    let methods = (0..self.len()?)
+                        .map(|_| self.function(pool, lazy, version))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    CompiledObject::Struct {
+                        name,
+                        fields,
+                        methods,
+                    }
+                }
+                2 => CompiledObject::Constant(self.pool_constant(pool)?),
+                _ => return Err(self.error("unknown object kind")),
+            };
+            objects.push(object);
+        }
+        let api = self.api()?;
+        let metadata = if legacy::has_metadata(version) {
+            self.metadata()?
+        } else {
+            None
+        };
+
+        if self.offset != self.bytes.len() {
+            return Err(self.error("the objects do not end at the data section"));
+        }
+        if entry.is_some_and(|entry| entry >= objects.len()) {
+            return Err(self.error("the entry is not an object of the module"));
+        }
+        Ok(CompiledModule {
+            name,
+            entry,
+            objects,
+            api,
+            metadata,
+        })
+    }
+}
+
+impl CompiledModule {
+    /// the module file of the module, see `from_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut pool = ConstantPool::default();
+        let mut body = Writer::default();
+        body.str(&self.name);
+        body.u32(self.entry.map_or(NO_ENTRY, |entry| entry as u32));
+        body.len(self.objects.len());
+        for object in &self.objects {
+            match object {
+                CompiledObject::Function(function) => {
+                    body.u8(0);
+                    body.function(function, &mut pool);
+                }
+                CompiledObject::Struct {
+                    name,
+                    fields,
+                    methods,
+                } => {
+                    body.u8(1);
+                    body.str(name);
+                    body.len(fields.len());
+                    for (field, slot) in fields {
+                        body.str(field);
+                        body.len(*slot);
+                    }
+                    body.len(methods.len());
+                    for method in methods {
+                        body.function(method, &mut pool);
+                    }
+                }
+                CompiledObject::Constant(constant) => {
+                    body.u8(2);
+                    body.u32(pool.intern(constant));
+                }
+            }
+        }
+        body.api(self.api.as_ref());
+        body.metadata(self.metadata.as_ref());
+
+        // the pool is complete once the objects are written, it is placed before them and the
+        // strings it refers to after them.
+        let mut file = Writer::default();
+        file.bytes.extend_from_slice(MODULE_FILE_MAGIC);
+        file.u16(MODULE_FILE_VERSION);
+        let pool_len: usize = pool.entries.iter().map(Vec::len).sum();
+        file.len(HEADER_LEN + 4 + pool_len + body.bytes.len());
+        file.len(pool.entries.len());
+        for entry in &pool.entries {
+            file.bytes.extend_from_slice(entry);
+        }
+        file.bytes.extend_from_slice(&body.bytes);
+        file.data(&pool.data);
+        file.bytes
+    }
+
+    /// writes the module file of the module to path, it is run with `Vm::load_module`.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    /// reads a module written by `to_bytes`, a file that is truncated, corrupt or of a version
+    /// of the format that is no longer read is an error.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, runtime::Error> {
+        Self::from_bytes_with(bytes, &mut |_| {})
+    }
+
+    /// reads a module like `from_bytes`, a file of a previous version of the format is
+    /// upgraded and reported to warn.
+    pub fn from_bytes_with(
+        bytes: &[u8],
+        warn: &mut dyn FnMut(LoadWarning),
+    ) -> Result<Self, runtime::Error> {
+        let mut reader = Reader { bytes, offset: 0 };
+        let (data_offset, version) = reader.header()?;
+        let mut data = Reader {
+            bytes,
+            offset: data_offset,
+        };
+        let section = data.data()?;
+        if data.offset != bytes.len() {
+            return Err(data.error("trailing bytes after the module"));
+        }
+
+        let mut reader = Reader {
+            bytes: &bytes[..data_offset],
+            offset: reader.offset,
+        };
+        let pool = reader.pool(Data::Bytes(section), data_offset + 4, section.len())?;
+        // every constant is read now so a corrupt string is found with the rest of the file.
+        for idx in 0..pool.len() {
+            pool.constant(idx)?;
+        }
+        Self::upgraded(reader.module(&pool, None, version)?, version, warn)
+    }
+
+    /// module as the current version of the format, it was read from a file of version.
+    fn upgraded(
+        mut module: Self,
+        version: u16,
+        warn: &mut dyn FnMut(LoadWarning),
+    ) -> Result<Self, runtime::Error> {
+        if version != MODULE_FILE_VERSION {
+            legacy::upgrade(&mut module, version)?;
+            warn(LoadWarning::DeprecatedVersion(version));
+        }
+        Ok(module)
+    }
+
+    /// reads a module file from source, the strings of the constant pool are only read from it
+    /// once a function that is loaded uses them. A source that can not seek is read whole as
+    /// by `from_bytes`.
+    pub fn from_reader<R: Read + Seek + Send + 'static>(source: R) -> Result<Self, runtime::Error> {
+        Self::from_reader_with(source, &mut |_| {})
+    }
+
+    /// reads a module like `from_reader`, a file of a previous version of the format is
+    /// upgraded and reported to warn.
+    pub fn from_reader_with<R: Read + Seek + Send + 'static>(
+        mut source: R,
+        warn: &mut dyn FnMut(LoadWarning),
+    ) -> Result<Self, runtime::Error> {
+        let start = match source.stream_position() {
+            Ok(start) => start,
+            Err(_) => {
+                let mut bytes = vec![];
+                source
+                    .read_to_end(&mut bytes)
+                    .map_err(|err| read_error(0, err))?;
+                return Self::from_bytes_with(&bytes, warn);
+            }
+        };
+
+        let mut bytes = vec![0; HEADER_LEN];
+        source
+            .read_exact(&mut bytes)
+            .map_err(|err| read_error(0, err))?;
+        let (data_offset, version) = Reader {
+            bytes: &bytes,
+            offset: 0,
+        }
+        .header()?;
+        // everything before the data section is read, along with the length of the section.
+        bytes.resize(data_offset + 4, 0);
+        source
+            .read_exact(&mut bytes[HEADER_LEN..])
+            .map_err(|err| read_error(HEADER_LEN, err))?;
+        let data_len = u32::from_le_bytes(bytes[data_offset..].try_into().unwrap()) as usize;
+        let len = source
+            .seek(SeekFrom::End(0))
+            .map_err(|err| read_error(data_offset, err))?
+            .saturating_sub(start);
+        let end = (data_offset + 4 + data_len) as u64;
+        if len != end {
+            let reason = if len < end {
+                "unexpected end of file"
+            } else {
+                "trailing bytes after the module"
+            };
+            return Err(runtime::Error::invalid_module_file(
+                len.min(end) as usize,
+                reason.to_string(),
+            ));
+        }
+
+        let mut reader = Reader {
+            bytes: &bytes[..data_offset],
+            offset: HEADER_LEN,
+        };
+        let data = Data::Source {
+            source: Mutex::new(Box::new(source) as Box<dyn DataSource>),
+            start: start + data_offset as u64 + 4,
+        };
+        let pool = Arc::new(reader.pool(data, data_offset + 4, data_len)?);
+        Self::upgraded(reader.module(&pool, Some(&pool), version)?, version, warn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compiled::{
+            ApiField, ApiFunction, ApiItem, ApiKind, ApiVariant, CompiledFunction, CompiledModule,
+            CompiledObject, CompiledValue, Constant, ModuleApi, ModuleMetadata,
+        },
+        runtime::Error,
+        source_map::{LineTable, LocalTable},
+    };
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    fn function(name: &str, constants: Vec<Constant>) -> CompiledFunction {
+        let mut lines = LineTable::new();
+        lines.push(0, 3);
+        let mut locals = LocalTable::new();
+        locals.push(2, 0, "x".to_string());
+        CompiledFunction {
+            name: name.to_string(),
+            arity: 1,
+            code: vec![1, 2, 3],
+            constants,
+            globals: vec![
+                CompiledValue::Object(0),
+                CompiledValue::Method(1, 0),
+                CompiledValue::Module,
+                CompiledValue::Constant(Constant::String("shared".to_string())),
+            ],
+            lines,
+            locals,
+        }
+    }
+
+    fn module() -> CompiledModule {
+        let shared = Constant::String("shared".to_string());
+        CompiledModule {
+            name: "app".to_string(),
+            entry: Some(0),
+            objects: vec![
+                CompiledObject::Function(function(
+                    "main",
+                    vec![shared.clone(), Constant::F64(-0.5), Constant::Char('λ')],
+                )),
+                CompiledObject::Struct {
+                    name: "geometry::Point".to_string(),
+                    fields: vec![("x".to_string(), 0), ("y".to_string(), 2)],
+                    methods: vec![function("len", vec![shared, Constant::I8(-1)])],
+                },
+                CompiledObject::Constant(Constant::Tuple(vec![Constant::I64(7), Constant::Unit])),
+            ],
+            api: None,
+            metadata: None,
+        }
+    }
+
+    fn api() -> ModuleApi {
+        let len = ApiItem {
+            name: "len".to_string(),
+            public: true,
+            kind: ApiKind::Function(ApiFunction {
+                params: vec!["geometry.Point".to_string()],
+                ret: "f64".to_string(),
+            }),
+        };
+        ModuleApi {
+            items: vec![
+                ApiItem {
+                    name: "Point".to_string(),
+                    public: true,
+                    kind: ApiKind::Struct {
+                        fields: vec![ApiField {
+                            name: "x".to_string(),
+                            ty: "f64".to_string(),
+                            public: false,
+                        }],
+                        methods: vec![len],
+                    },
+                },
+                ApiItem {
+                    name: "Shape".to_string(),
+                    public: true,
+                    kind: ApiKind::Enum {
+                        variants: vec![ApiVariant {
+                            name: "Circle".to_string(),
+                            fields: vec!["f64".to_string(), "Option[i64]".to_string()],
+                        }],
+                    },
+                },
+                ApiItem {
+                    name: "origin".to_string(),
+                    public: false,
+                    kind: ApiKind::Variable {
+                        ty: "(i64, i64)".to_string(),
+                        mutable: true,
+                    },
+                },
+            ],
+        }
+    }
+
+    fn reason(result: Result<CompiledModule, Error>) -> (usize, String) {
+        match result {
+            Err(Error::InvalidModuleFile { offset, reason }) => (offset, reason),
+            _ => panic!("expected the module file to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_module_file_round_trip() {
+        let module = module();
+        let bytes = module.to_bytes();
+        assert_eq!(CompiledModule::from_bytes(&bytes).unwrap(), module);
+
+        // "shared" is written once for all of its uses.
+        assert_eq!(bytes.windows(6).filter(|bytes| bytes == b"shared").count(), 1);
+
+        let with_api = module.with_api(api());
+        let bytes = with_api.to_bytes();
+        let read = CompiledModule::from_bytes(&bytes).unwrap();
+        assert_eq!(read.api(), Some(&api()));
+        for len in 0..bytes.len() {
+            assert!(CompiledModule::from_bytes(&bytes[..len]).is_err());
+        }
+
+        let metadata = ModuleMetadata {
+            compiler_version: "0.3.0".to_string(),
+            options: vec![
+                ("mode".to_string(), "debug".to_string()),
+                ("opt_level".to_string(), "2".to_string()),
+            ],
+        };
+        let read = CompiledModule::from_bytes(&with_api.with_metadata(metadata.clone()).to_bytes())
+            .unwrap();
+        assert_eq!(read.metadata(), Some(&metadata));
+        assert_eq!(read.api(), Some(&api()));
+        assert_eq!(metadata.option("opt_level"), Some("2"));
+        assert_eq!(metadata.to_string(), "compiler 0.3.0\nmode: debug\nopt_level: 2");
+    }
+
+    #[test]
+    fn test_module_file_rejects_corrupt_files() {
+        let bytes = module().to_bytes();
+        assert_eq!(
+            reason(CompiledModule::from_bytes(b"#!/bin/sh")),
+            (0, "not a module file".to_string())
+        );
+
+        let mut version = bytes.clone();
+        version[4] = 19;
+        assert_eq!(
+            reason(CompiledModule::from_bytes(&version)),
+            (6, "unsupported version 19, expected 18".to_string())
+        );
+
+        // every truncation is an error, never a panic.
+        for len in 0..bytes.len() {
+            assert!(CompiledModule::from_bytes(&bytes[..len]).is_err());
+        }
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert_eq!(
+            reason(CompiledModule::from_bytes(&trailing)).1,
+            "trailing bytes after the module"
+        );
+    }
+
+    // a source that can only be read front to back.
+    struct Pipe(Cursor<Vec<u8>>);
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for Pipe {
+        fn seek(&mut self, _: SeekFrom) -> std::io::Result<u64> {
+            Err(std::io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn test_module_file_strings_are_read_on_first_use() {
+        let module = module();
+        let bytes = module.to_bytes();
+        let read = CompiledModule::from_reader(Cursor::new(bytes.clone())).unwrap();
+
+        let constants = match &read.objects[0] {
+            CompiledObject::Function(function) => function.constants.clone(),
+            _ => panic!("expected the entry to be a function"),
+        };
+        let lazy = match &constants[0] {
+            Constant::Lazy(lazy) => lazy.clone(),
+            constant => panic!("expected a lazy constant, found {:?}", constant),
+        };
+        assert_eq!(constants[1], Constant::F64(-0.5));
+        assert_eq!(lazy.preview(), "String(\"shared\")");
+        assert_eq!(
+            lazy.load().unwrap(),
+            &Constant::String("shared".to_string())
+        );
+
+        // writing the module again reads every entry.
+        assert_eq!(read.to_bytes(), bytes);
+
+        let eager = CompiledModule::from_reader(Pipe(Cursor::new(bytes.clone()))).unwrap();
+        assert_eq!(eager, module);
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert_eq!(
+            reason(CompiledModule::from_reader(Cursor::new(trailing))).1,
+            "trailing bytes after the module"
+        );
+    }
+
+    #[test]
+    fn test_module_file_previews_long_strings() {
+        let long = "a".repeat(1 << 20);
+        let mut module = module();
+        module.objects.push(CompiledObject::Function(function(
+            "blob",
+            vec![Constant::String(long.clone())],
+        )));
+        let bytes = module.to_bytes();
+        let read = CompiledModule::from_reader(Cursor::new(bytes)).unwrap();
+        let lazy = match &read.objects[3] {
+            CompiledObject::Function(function) => match &function.constants[0] {
+                Constant::Lazy(lazy) => lazy.clone(),
+                constant => panic!("expected a lazy constant, found {:?}", constant),
+            },
+            _ => panic!("expected a function"),
+        };
+        assert!(!lazy.is_loaded());
+        assert_eq!(
+            lazy.preview(),
+            format!("\"{}...\" (1048576 bytes, not loaded)", &long[..24])
+        );
+        assert_eq!(lazy.load().unwrap(), &Constant::String(long));
+        assert!(lazy.is_loaded());
+    }
+}
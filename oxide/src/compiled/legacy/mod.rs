@@ -0,0 +1,234 @@
+//! the previous versions of the module file format that are still read. They are laid out like
+//! the current version and only number their op codes differently, each version maps its op
+//! codes to those of the next one. The structures of versions before 15 have no field tables,
+//! they are read without public fields. The line runs of versions before 16 have no statement
+//! flags, each is read as the start of a statement. Versions before 17 have no metadata section,
+//! they are read without metadata. Reading a version is dropped by removing its module, its
+//! entry in `VERSIONS` and its fixture.
+
+use crate::{
+    compiled::{CompiledModule, CompiledObject},
+    runtime,
+    verifier::Verifier,
+    vm::OpCode,
+};
+
+mod v13;
+mod v14;
+mod v15;
+mod v16;
+mod v17;
+
+/// the op code of the next version for an op code of a version.
+type Renumber = fn(u8) -> u8;
+
+/// the versions read besides the current one, oldest first.
+const VERSIONS: &[(u16, Renumber)] = &[
+    (13, v13::next_op_code),
+    (14, v14::next_op_code),
+    (15, v15::next_op_code),
+    (16, v16::next_op_code),
+    (17, v17::next_op_code),
+];
+
+/// the first version whose structures list their public fields.
+const FIELD_TABLES: u16 = 15;
+
+pub(crate) fn is_supported(version: u16) -> bool {
+    VERSIONS.iter().any(|(supported, _)| *supported == version)
+}
+
+/// the first version whose line runs say whether they start a statement.
+const STATEMENT_FLAGS: u16 = 16;
+
+pub(crate) fn has_field_tables(version: u16) -> bool {
+    version >= FIELD_TABLES
+}
+
+pub(crate) fn has_statement_flags(version: u16) -> bool {
+    version >= STATEMENT_FLAGS
+}
+
+/// the first version with a metadata section after the api.
+const METADATA: u16 = 17;
+
+pub(crate) fn has_metadata(version: u16) -> bool {
+    version >= METADATA
+}
+
+/// rewrites the code of every function of module, read from a file of version, as the code of
+/// the current version.
+pub(crate) fn upgrade(module: &mut CompiledModule, version: u16) -> Result<(), runtime::Error> {
+    let steps = VERSIONS
+        .iter()
+        .filter(|(from, _)| *from >= version)
+        .map(|(_, step)| *step)
+        .collect::<Vec<_>>();
+    for object in &mut module.objects {
+        match object {
+            CompiledObject::Function(function) => upgrade_code(&mut function.code, &steps)?,
+            CompiledObject::Struct { methods, .. } => {
+                for method in methods {
+                    upgrade_code(&mut method.code, &steps)?;
+                }
+            }
+            CompiledObject::Constant(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// the op codes are renumbered in place, the operands are read as the current version reads
+/// them to find the next instruction.
+fn upgrade_code(code: &mut [u8], steps: &[Renumber]) -> Result<(), runtime::Error> {
+    let renumber = |op_code: u8| steps.iter().fold(op_code, |op_code, step| step(op_code));
+    let mut ip = 0;
+    while ip < code.len() {
+        code[ip] = renumber(code[ip]);
+        if code[ip] == OpCode::Wide as u8 && ip + 1 < code.len() {
+            code[ip + 1] = renumber(code[ip + 1]);
+        }
+        let (_, next) = Verifier::instruction(code, ip)?;
+        ip = next;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{v13, v14, v15, v16, v17};
+    use crate::{
+        compiled::{CompiledModule, CompiledObject, LoadWarning, MODULE_FILE_VERSION},
+        vm::OpCode,
+        Value, Vm,
+    };
+
+    /// written once by the compilers of version 13 to 17 from, versions 14 and later declare
+    /// `advance` public.
+    ///
+    /// ```text
+    /// struct Counter {
+    ///     step i64
+    ///
+    ///     fn advance(self, n i64) i64 = n + self.step
+    /// }
+    ///
+    /// fn label(n i64) string = if n > 10 { "large" } else { "small" }
+    ///
+    /// fn main() i64 {
+    ///     let counter = Counter { step: 3 }
+    ///     mut total i64 = 0
+    ///     mut i i64 = 0
+    ///     while i < 5 {
+    ///         total = counter.advance(total)
+    ///         i += 1
+    ///     }
+    ///     let kind = label(total)
+    ///     if kind == "large" { total } else { -total }
+    /// }
+    /// ```
+    const V13: &[u8] = include_bytes!("v13.oxb");
+    const V14: &[u8] = include_bytes!("v14.oxb");
+    const V15: &[u8] = include_bytes!("v15.oxb");
+    const V16: &[u8] = include_bytes!("v16.oxb");
+    const V17: &[u8] = include_bytes!("v17.oxb");
+
+    fn run(compiled: &CompiledModule) -> Value {
+        let mut vm = Vm::new();
+        let module = compiled.load(&mut vm);
+        for object in module.objects().iter() {
+            if let Value::Function(function) = object {
+                function.section().verify().unwrap();
+            }
+        }
+        vm.run_module(module).unwrap();
+        *vm.top()
+    }
+
+    #[test]
+    fn test_previous_versions_load_and_run() {
+        for (version, bytes) in [(13, V13), (14, V14), (15, V15), (16, V16), (17, V17)] {
+            let mut warnings = vec![];
+            let compiled =
+                CompiledModule::from_bytes_with(bytes, &mut |warning| warnings.push(warning))
+                    .unwrap();
+            assert_eq!(warnings, vec![LoadWarning::DeprecatedVersion(version)]);
+            assert_eq!(
+                warnings[0].to_string(),
+                format!(
+                    "module file version {} is deprecated, recompile it to version {}",
+                    version, MODULE_FILE_VERSION
+                )
+            );
+            assert!(matches!(run(&compiled), Value::I64(15)));
+
+            let mut warnings = vec![];
+            let read = CompiledModule::from_reader_with(Cursor::new(bytes), &mut |warning| {
+                warnings.push(warning)
+            })
+            .unwrap();
+            assert_eq!(warnings, vec![LoadWarning::DeprecatedVersion(version)]);
+            assert!(matches!(run(&read), Value::I64(15)));
+
+            // written again it is a module file of the current version.
+            let bytes = compiled.to_bytes();
+            assert_eq!(bytes[4..6], MODULE_FILE_VERSION.to_le_bytes());
+            let current = CompiledModule::from_bytes_with(&bytes, &mut |warning| {
+                panic!("unexpected warning: {}", warning)
+            })
+            .unwrap();
+            assert_eq!(current, compiled);
+        }
+    }
+
+    #[test]
+    fn test_version_13_op_codes() {
+        assert_eq!(v13::next_op_code(0), 0);
+        assert_eq!(v13::next_op_code(221), 221);
+        assert_eq!(v13::next_op_code(222), 223);
+        assert_eq!(v13::next_op_code(u8::MAX), u8::MAX);
+    }
+
+    #[test]
+    fn test_version_14_op_codes() {
+        assert_eq!(v14::next_op_code(202), 202);
+        assert_eq!(v14::next_op_code(203), 204);
+        // new_closure moved once for each version.
+        assert_eq!(v14::next_op_code(v13::next_op_code(222)), 224);
+        assert_eq!(v14::next_op_code(u8::MAX), u8::MAX);
+    }
+
+    #[test]
+    fn test_version_15_line_runs_start_statements() {
+        let compiled = CompiledModule::from_bytes(V15).unwrap();
+        assert_eq!(v15::next_op_code(224), 224);
+        for object in &compiled.objects {
+            if let CompiledObject::Function(function) = object {
+                assert!(!function.lines.is_empty());
+                assert!(function.lines.runs().iter().all(|run| run.is_statement_start));
+            }
+        }
+    }
+    #[test]
+    fn test_version_16_op_codes() {
+        assert_eq!(v16::next_op_code(232), 232);
+        assert_eq!(v16::next_op_code(233), 234);
+        assert_eq!(OpCode::LoadHost as u8, 233);
+        assert_eq!(v16::next_op_code(u8::MAX), u8::MAX);
+        let compiled = CompiledModule::from_bytes(V16).unwrap();
+        assert_eq!(compiled.metadata(), None);
+    }
+
+    #[test]
+    fn test_version_17_op_codes() {
+        assert_eq!(v17::next_op_code(233), 233);
+        assert_eq!(v17::next_op_code(234), 235);
+        assert_eq!(OpCode::Eval as u8, 234);
+        assert_eq!(v17::next_op_code(u8::MAX), u8::MAX);
+        // the metadata of version 17 is read as it is.
+        let compiled = CompiledModule::from_bytes(V17).unwrap();
+        assert!(compiled.metadata().is_some());
+    }
+}
@@ -0,0 +1,14 @@
+//! version 13, before `new_closure` was added after `call`.
+
+/// the op code of `new_closure` in version 14, the op codes of version 13 from it on are one
+/// less.
+const NEW_CLOSURE: u8 = 222;
+
+pub(super) fn next_op_code(op_code: u8) -> u8 {
+    if op_code >= NEW_CLOSURE {
+        // an op code out of range stays out of range.
+        op_code.saturating_add(1)
+    } else {
+        op_code
+    }
+}
@@ -0,0 +1,14 @@
+//! version 14, before `get_field` was added after `set_attr` and structures had field tables.
+
+/// the op code of `get_field` in version 15, the op codes of version 14 from it on are one
+/// less.
+const GET_FIELD: u8 = 203;
+
+pub(super) fn next_op_code(op_code: u8) -> u8 {
+    if op_code >= GET_FIELD {
+        // an op code out of range stays out of range.
+        op_code.saturating_add(1)
+    } else {
+        op_code
+    }
+}
@@ -0,0 +1,6 @@
+//! version 15, before the line runs of functions said whether they start a statement.
+
+/// the op codes of version 15 are those of version 16.
+pub(super) fn next_op_code(op_code: u8) -> u8 {
+    op_code
+}
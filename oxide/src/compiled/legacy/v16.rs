@@ -0,0 +1,14 @@
+//! version 16, before `load_host` was added after `trap_format` and module files had metadata.
+
+/// the op code of `load_host` in version 17, the op codes of version 16 from it on are one
+/// less.
+const LOAD_HOST: u8 = 233;
+
+pub(super) fn next_op_code(op_code: u8) -> u8 {
+    if op_code >= LOAD_HOST {
+        // an op code out of range stays out of range.
+        op_code.saturating_add(1)
+    } else {
+        op_code
+    }
+}
@@ -0,0 +1,13 @@
+//! version 17, before `eval` was added after `load_host`.
+
+/// the op code of `eval` in version 18, the op codes of version 17 from it on are one less.
+const EVAL: u8 = 234;
+
+pub(super) fn next_op_code(op_code: u8) -> u8 {
+    if op_code >= EVAL {
+        // an op code out of range stays out of range.
+        op_code.saturating_add(1)
+    } else {
+        op_code
+    }
+}
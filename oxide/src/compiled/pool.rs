@@ -0,0 +1,191 @@
+use std::{
+    fmt::{Debug, Formatter},
+    io::{self, Read, Seek, SeekFrom},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use crate::{compiled::Constant, runtime};
+
+/// the longest prefix of an entry that is not loaded shown by a preview.
+const PREVIEW_LEN: usize = 24;
+
+/// a source of a module file that can be read from at any offset.
+pub(super) trait DataSource: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> DataSource for T {}
+
+/// where the data section of a module file is read from.
+pub(super) enum Data<'a> {
+    /// the whole file is in memory.
+    Bytes(&'a [u8]),
+    /// the section is read from the source when an entry is first used, start is the offset of
+    /// the section in the source.
+    Source {
+        source: Mutex<Box<dyn DataSource>>,
+        start: u64,
+    },
+}
+
+/// an entry of the pool as it is written, scalars are written inline and strings refer to the
+/// data section.
+#[derive(Debug)]
+pub(super) enum Entry {
+    Scalar(Constant),
+    String {
+        offset: usize,
+        len: usize,
+    },
+    /// the pool indices of the elements, they are written before the tuple.
+    Tuple(Vec<usize>),
+}
+
+/// the constant pool of a module file, each entry is materialized once on first use.
+pub(super) struct Pool<'a> {
+    entries: Vec<Entry>,
+    values: Vec<OnceLock<Constant>>,
+    data: Data<'a>,
+    /// offset of the data section in the file.
+    data_offset: usize,
+}
+
+impl<'a> Pool<'a> {
+    pub(super) fn new(entries: Vec<Entry>, data: Data<'a>, data_offset: usize) -> Self {
+        let values = entries
+            .iter()
+            .map(|entry| match entry {
+                Entry::Scalar(constant) => OnceLock::from(constant.clone()),
+                _ => OnceLock::new(),
+            })
+            .collect();
+        Self {
+            entries,
+            values,
+            data,
+            data_offset,
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(super) fn is_loaded(&self, idx: usize) -> bool {
+        self.values[idx].get().is_some()
+    }
+
+    /// the constant of the entry at idx, it is read from the data section on first use.
+    pub(super) fn constant(&self, idx: usize) -> Result<&Constant, runtime::Error> {
+        if let Some(constant) = self.values[idx].get() {
+            return Ok(constant);
+        }
+
+        let constant = match &self.entries[idx] {
+            Entry::Scalar(constant) => constant.clone(),
+            Entry::String { offset, len } => {
+                let bytes = self.read(*offset, *len)?;
+                let string = String::from_utf8(bytes).map_err(|_| {
+                    runtime::Error::invalid_module_file(
+                        self.data_offset + offset,
+                        "a string is not utf-8".to_string(),
+                    )
+                })?;
+                Constant::String(string)
+            }
+            Entry::Tuple(elements) => Constant::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.constant(*element).cloned())
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+        };
+        // another thread may have read it first, both read the same constant.
+        Ok(self.values[idx].get_or_init(|| constant))
+    }
+
+    /// a short description of the entry at idx, only a short string is loaded for it.
+    pub(super) fn preview(&self, idx: usize) -> String {
+        let state = if self.is_loaded(idx) {
+            ""
+        } else {
+            ", not loaded"
+        };
+        match &self.entries[idx] {
+            Entry::Scalar(constant) => format!("{:?}", constant),
+            Entry::String { len, .. } if *len <= PREVIEW_LEN => match self.constant(idx) {
+                Ok(constant) => format!("{:?}", constant),
+                Err(err) => format!("<{}>", err),
+            },
+            Entry::String { offset, len } => match self.read(*offset, PREVIEW_LEN) {
+                Ok(prefix) => format!(
+                    "\"{}...\" ({} bytes{})",
+                    String::from_utf8_lossy(&prefix),
+                    len,
+                    state
+                ),
+                Err(err) => format!("<{}>", err),
+            },
+            Entry::Tuple(elements) => format!("a tuple of {} constants{}", elements.len(), state),
+        }
+    }
+
+    fn read(&self, offset: usize, len: usize) -> Result<Vec<u8>, runtime::Error> {
+        match &self.data {
+            Data::Bytes(bytes) => Ok(bytes[offset..offset + len].to_vec()),
+            Data::Source { source, start } => {
+                let mut source = source.lock().unwrap_or_else(|poison| poison.into_inner());
+                let mut bytes = vec![0; len];
+                source
+                    .seek(SeekFrom::Start(start + offset as u64))
+                    .and_then(|_| source.read_exact(&mut bytes))
+                    .map_err(|err| read_error(self.data_offset + offset, err))?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+pub(super) fn read_error(offset: usize, err: io::Error) -> runtime::Error {
+    let reason = match err.kind() {
+        io::ErrorKind::UnexpectedEof => "unexpected end of file".to_string(),
+        _ => format!("failed to read the module file: {}", err),
+    };
+    runtime::Error::invalid_module_file(offset, reason)
+}
+
+/// an entry of the pool of a module file that is only read when a function first loads it.
+#[derive(Clone)]
+pub struct LazyConstant {
+    pool: Arc<Pool<'static>>,
+    idx: usize,
+}
+
+impl LazyConstant {
+    pub(super) fn new(pool: Arc<Pool<'static>>, idx: usize) -> Self {
+        Self { pool, idx }
+    }
+
+    /// reads the constant, every loader of the entry shares the constant read first.
+    pub fn load(&self) -> Result<&Constant, runtime::Error> {
+        self.pool.constant(self.idx)
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.pool.is_loaded(self.idx)
+    }
+
+    pub fn preview(&self) -> String {
+        self.pool.preview(self.idx)
+    }
+}
+
+impl Debug for LazyConstant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.preview())
+    }
+}
+
+impl PartialEq for LazyConstant {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.pool, &other.pool) && self.idx == other.idx
+    }
+}
@@ -0,0 +1,462 @@
+use std::convert::TryFrom;
+
+use crate::{Value, Vm};
+
+/// why a value could not be converted to a host type.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ConvertError {
+    /// values of the kind are never converted to the type.
+    #[error("expected {expected}, found {found}")]
+    Kind {
+        found: &'static str,
+        expected: &'static str,
+    },
+    /// the kind converts to the type but the value does not fit in it, or would lose its
+    /// fraction.
+    #[error("{found} {value} does not fit in {expected}")]
+    Range {
+        found: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    #[error("expected {expected} values, found {found}")]
+    Count { found: usize, expected: usize },
+}
+
+impl ConvertError {
+    fn kind(value: &Value, expected: &'static str) -> Self {
+        Self::Kind {
+            found: value.ty(),
+            expected,
+        }
+    }
+
+    fn range(value: &Value, expected: &'static str) -> Self {
+        Self::Range {
+            found: value.ty(),
+            value: value.to_string(),
+            expected,
+        }
+    }
+}
+
+/// a host type a value converts to when its kind and value allow it. Integers and floats
+/// convert to any numeric type that holds the value exactly.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, ConvertError>;
+}
+
+/// a host value that is converted to a value of the vm, strings are allocated on its heap.
+pub trait IntoValue {
+    fn into_value(self, vm: &mut Vm) -> Value;
+}
+
+/// the values of a tuple of host types, see `Extract`.
+pub trait FromValueList: Sized {
+    fn from_value_list(values: &[Value]) -> Result<Self, ConvertError>;
+}
+
+/// converts the actual parameters of a native function at once.
+///
+/// `let (a, b): (i64, String) = actuals.extract()?;`
+pub trait Extract {
+    fn extract<T: FromValueList>(&self) -> Result<T, ConvertError>;
+}
+
+impl Extract for [Value] {
+    fn extract<T: FromValueList>(&self) -> Result<T, ConvertError> {
+        T::from_value_list(self)
+    }
+}
+
+/// the integer held by a numeric value, none for a float with a fraction or out of range.
+fn integer(value: &Value, expected: &'static str) -> Result<Option<i128>, ConvertError> {
+    let integral = |val: f64| {
+        let int = val as i128;
+        if int as f64 == val {
+            Some(int)
+        } else {
+            None
+        }
+    };
+    let int = match *value {
+        Value::I8(val) => Some(val as i128),
+        Value::I16(val) => Some(val as i128),
+        Value::I32(val) => Some(val as i128),
+        Value::I64(val) => Some(val as i128),
+        Value::U8(val) => Some(val as i128),
+        Value::U16(val) => Some(val as i128),
+        Value::U32(val) => Some(val as i128),
+        Value::U64(val) => Some(val as i128),
+        Value::F32(val) => integral(val as f64),
+        Value::F64(val) => integral(val),
+        _ => return Err(ConvertError::kind(value, expected)),
+    };
+    Ok(int)
+}
+
+macro_rules! integer_from_value {
+    ($T:ty, $name:literal) => {
+        impl FromValue for $T {
+            fn from_value(value: &Value) -> Result<Self, ConvertError> {
+                integer(value, $name)?
+                    .and_then(|int| <$T>::try_from(int).ok())
+                    .ok_or_else(|| ConvertError::range(value, $name))
+            }
+        }
+    };
+}
+
+integer_from_value!(i8, "i8");
+integer_from_value!(i16, "i16");
+integer_from_value!(i32, "i32");
+integer_from_value!(i64, "i64");
+integer_from_value!(u8, "u8");
+integer_from_value!(u16, "u16");
+integer_from_value!(u32, "u32");
+integer_from_value!(u64, "u64");
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, ConvertError> {
+        match *value {
+            Value::F32(val) => Ok(val as f64),
+            Value::F64(val) => Ok(val),
+            _ => {
+                let int = integer(value, "f64")?.unwrap();
+                let float = int as f64;
+                if float as i128 == int {
+                    Ok(float)
+                } else {
+                    Err(ConvertError::range(value, "f64"))
+                }
+            }
+        }
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: &Value) -> Result<Self, ConvertError> {
+        match *value {
+            Value::F32(val) => Ok(val),
+            // nan is not equal to itself, it is still a float.
+            Value::F64(val) if val as f32 as f64 == val || val.is_nan() => Ok(val as f32),
+            Value::F64(_) => Err(ConvertError::range(value, "f32")),
+            _ => {
+                let int = integer(value, "f32")?.unwrap();
+                let float = int as f32;
+                if float as i128 == int {
+                    Ok(float)
+                } else {
+                    Err(ConvertError::range(value, "f32"))
+                }
+            }
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::Bool(val) => Ok(*val),
+            _ => Err(ConvertError::kind(value, "bool")),
+        }
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::Char(val) => Ok(*val),
+            _ => Err(ConvertError::kind(value, "char")),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, ConvertError> {
+        match value {
+            Value::String(val) => Ok(val.as_str().to_string()),
+            _ => Err(ConvertError::kind(value, "string")),
+        }
+    }
+}
+
+/// any value, for parameters a native inspects itself.
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, ConvertError> {
+        Ok(*value)
+    }
+}
+
+macro_rules! try_from_value {
+    ($($T:ty),*) => {
+        $(
+            impl TryFrom<Value> for $T {
+                type Error = ConvertError;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    <$T>::from_value(&value)
+                }
+            }
+        )*
+    };
+}
+
+try_from_value!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bool, char, String);
+
+impl Value {
+    pub fn try_into_i64(&self) -> Result<i64, ConvertError> {
+        i64::from_value(self)
+    }
+
+    pub fn try_into_u32(&self) -> Result<u32, ConvertError> {
+        u32::from_value(self)
+    }
+
+    pub fn try_into_f64(&self) -> Result<f64, ConvertError> {
+        f64::from_value(self)
+    }
+
+    pub fn try_into_bool(&self) -> Result<bool, ConvertError> {
+        bool::from_value(self)
+    }
+
+    pub fn try_into_string(&self) -> Result<String, ConvertError> {
+        String::from_value(self)
+    }
+}
+
+macro_rules! into_value {
+    ($($T:ty),*) => {
+        $(
+            impl IntoValue for $T {
+                fn into_value(self, _: &mut Vm) -> Value {
+                    Value::from(self)
+                }
+            }
+        )*
+    };
+}
+
+into_value!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64, bool, char);
+
+impl IntoValue for &str {
+    fn into_value(self, vm: &mut Vm) -> Value {
+        Value::from(vm.new_gc_string_from_str(self))
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self, vm: &mut Vm) -> Value {
+        self.as_str().into_value(vm)
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self, _: &mut Vm) -> Value {
+        self
+    }
+}
+
+macro_rules! value_list {
+    ($len:literal; $($T:ident $idx:tt),*) => {
+        impl<$($T: FromValue),*> FromValueList for ($($T,)*) {
+            fn from_value_list(values: &[Value]) -> Result<Self, ConvertError> {
+                if values.len() != $len {
+                    return Err(ConvertError::Count {
+                        found: values.len(),
+                        expected: $len,
+                    });
+                }
+                Ok(($($T::from_value(&values[$idx])?,)*))
+            }
+        }
+    };
+}
+
+value_list!(0;);
+value_list!(1; A 0);
+value_list!(2; A 0, B 1);
+value_list!(3; A 0, B 1, C 2);
+value_list!(4; A 0, B 1, C 2, D 3);
+value_list!(5; A 0, B 1, C 2, D 3, E 4);
+value_list!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+
+#[cfg(test)]
+mod tests {
+    use super::{ConvertError, Extract, FromValue};
+    use crate::{Value, Vm};
+    use std::convert::TryFrom;
+
+    // whether every sample converts to T, a failure must be a range error.
+    fn converts<T: FromValue>(samples: &[Value]) -> Vec<bool> {
+        samples
+            .iter()
+            .map(|sample| match T::from_value(sample) {
+                Ok(_) => true,
+                Err(ConvertError::Range { .. }) => false,
+                Err(err) => panic!("{} should be a range error: {}", sample, err),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_numeric_conversions() {
+        let samples = [
+            Value::I8(-1),
+            Value::I16(300),
+            Value::I32(70_000),
+            Value::I64(1 << 40),
+            Value::U8(255),
+            Value::U16(40_000),
+            Value::U32(u32::MAX),
+            Value::U64(u64::MAX),
+            Value::F32(2.0),
+            Value::F64(3.5),
+            Value::F64(-1e300),
+            Value::I64((1 << 53) + 1),
+        ];
+        let t = true;
+        let f = false;
+        assert_eq!(
+            converts::<i8>(&samples),
+            [t, f, f, f, f, f, f, f, t, f, f, f]
+        );
+        assert_eq!(
+            converts::<i16>(&samples),
+            [t, t, f, f, t, f, f, f, t, f, f, f]
+        );
+        assert_eq!(
+            converts::<i32>(&samples),
+            [t, t, t, f, t, t, f, f, t, f, f, f]
+        );
+        assert_eq!(
+            converts::<i64>(&samples),
+            [t, t, t, t, t, t, t, f, t, f, f, t]
+        );
+        assert_eq!(
+            converts::<u8>(&samples),
+            [f, f, f, f, t, f, f, f, t, f, f, f]
+        );
+        assert_eq!(
+            converts::<u16>(&samples),
+            [f, t, f, f, t, t, f, f, t, f, f, f]
+        );
+        assert_eq!(
+            converts::<u32>(&samples),
+            [f, t, t, f, t, t, t, f, t, f, f, f]
+        );
+        assert_eq!(
+            converts::<u64>(&samples),
+            [f, t, t, t, t, t, t, t, t, f, f, t]
+        );
+        assert_eq!(
+            converts::<f32>(&samples),
+            [t, t, t, t, t, t, f, f, t, t, f, f]
+        );
+        assert_eq!(
+            converts::<f64>(&samples),
+            [t, t, t, t, t, t, t, f, t, t, t, f]
+        );
+
+        assert_eq!(Value::F64(3.0).try_into_i64(), Ok(3));
+        assert_eq!(Value::I8(-1).try_into_f64(), Ok(-1.0));
+        assert_eq!(Value::U8(7).try_into_u32(), Ok(7));
+        assert_eq!(
+            u16::try_from(Value::I64(-2)).map_err(|err| err.to_string()),
+            Err("i64 -2 does not fit in u16".to_string())
+        );
+        assert_eq!(
+            Value::F64(3.5).try_into_i64().unwrap_err().to_string(),
+            "f64 3.5 does not fit in i64"
+        );
+        assert!(f32::from_value(&Value::F64(f64::NAN)).unwrap().is_nan());
+        assert!(i64::from_value(&Value::F64(f64::NAN)).is_err());
+        assert!(i64::from_value(&Value::F64(f64::INFINITY)).is_err());
+    }
+
+    #[test]
+    fn test_kind_conversions() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let string = Value::from(vm.new_gc_string_from_str("text"));
+        let samples = [
+            Value::I32(1),
+            Value::F64(1.0),
+            Value::Bool(true),
+            Value::Char('x'),
+            string,
+            Value::Unit,
+        ];
+
+        let kinds = |result: Vec<Result<(), ConvertError>>| {
+            result.iter().map(Result::is_ok).collect::<Vec<_>>()
+        };
+        let ok = |samples: &[Value], convert: fn(&Value) -> Result<(), ConvertError>| {
+            kinds(samples.iter().map(convert).collect())
+        };
+        let (t, f) = (true, false);
+        assert_eq!(
+            ok(&samples, |v| i64::from_value(v).map(drop)),
+            [t, t, f, f, f, f]
+        );
+        assert_eq!(
+            ok(&samples, |v| f64::from_value(v).map(drop)),
+            [t, t, f, f, f, f]
+        );
+        assert_eq!(
+            ok(&samples, |v| bool::from_value(v).map(drop)),
+            [f, f, t, f, f, f]
+        );
+        assert_eq!(
+            ok(&samples, |v| char::from_value(v).map(drop)),
+            [f, f, f, t, f, f]
+        );
+        assert_eq!(
+            ok(&samples, |v| String::from_value(v).map(drop)),
+            [f, f, f, f, t, f]
+        );
+
+        assert_eq!(string.try_into_string(), Ok("text".to_string()));
+        assert_eq!(Value::Bool(false).try_into_bool(), Ok(false));
+        assert_eq!(
+            Value::Unit.try_into_i64(),
+            Err(ConvertError::Kind {
+                found: "unit",
+                expected: "i64"
+            })
+        );
+        assert_eq!(
+            Value::I32(1).try_into_bool().unwrap_err().to_string(),
+            "expected bool, found i32"
+        );
+    }
+
+    #[test]
+    fn test_extract_arguments() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let actuals = vm.values_from_iter(vec!["name", "other"]);
+        let (a, b): (String, String) = actuals.extract().unwrap();
+        assert_eq!((a.as_str(), b.as_str()), ("name", "other"));
+
+        let actuals = [Value::I32(2), Value::F64(0.5), Value::Unit];
+        let (a, b, c): (i64, f32, Value) = actuals.extract().unwrap();
+        assert_eq!((a, b, c.ty()), (2, 0.5, "unit"));
+        assert_eq!(
+            actuals.extract::<(i64, i64)>(),
+            Err(ConvertError::Count {
+                found: 3,
+                expected: 2
+            })
+        );
+        assert_eq!(
+            actuals
+                .extract::<(i64, i64, i64)>()
+                .unwrap_err()
+                .to_string(),
+            "f64 0.5 does not fit in i64"
+        );
+        assert_eq!(actuals[..0].extract::<()>(), Ok(()));
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use crate::{runtime::SectionId, source_map::SourceMap, BitMap, OxFunction};
+
+/// line table runs executed per function, keyed by the function name as it is known by the vm.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    functions: HashMap<String, (usize, BitMap)>,
+    /// section and offset range of the run that was executed last.
+    current: Option<(SectionId, usize, usize)>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// records the instruction at ip, the bitmap is only touched when execution leaves the
+    /// current line table run.
+    pub(crate) fn visit(&mut self, function: &OxFunction, ip: usize) {
+        let section = function.section();
+        if let Some((id, start, end)) = self.current {
+            if id == section.id() && start <= ip && ip < end {
+                return;
+            }
+        }
+
+        let runs = section.lines().runs();
+        let idx = runs.partition_point(|run| run.offset <= ip);
+        if idx == 0 {
+            // no debug info for this instruction.
+            let end = runs.first().map_or(usize::MAX, |run| run.offset);
+            self.current = Some((section.id(), 0, end));
+            return;
+        }
+
+        let run = idx - 1;
+        let (_, hits) = self
+            .functions
+            .entry(function.name().as_str().to_string())
+            .or_insert_with(|| (runs.len(), BitMap::new(runs.len())));
+        // the runs without instructions before it, a statement whose expression starts on the
+        // next line, are executed with it.
+        let first = runs[..run].iter().rposition(|earlier| earlier.offset != runs[run].offset);
+        for run in first.map_or(0, |first| first + 1)..=run {
+            hits.set(run, true);
+        }
+
+        let end = runs.get(idx).map_or(usize::MAX, |run| run.offset);
+        self.current = Some((section.id(), runs[run].offset, end));
+    }
+
+    pub fn is_covered(&self, function: &str, run: usize) -> bool {
+        self.functions
+            .get(function)
+            .is_some_and(|(len, hits)| run < *len && hits.get(run))
+    }
+
+    /// accumulates the coverage of another run, e.g. the next test.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (name, (len, hits)) in &other.functions {
+            let (_, merged) = self
+                .functions
+                .entry(name.clone())
+                .or_insert_with(|| (*len, BitMap::new(*len)));
+            for run in (0..*len).filter(|run| hits.get(*run)) {
+                merged.set(run, true);
+            }
+        }
+    }
+}
+
+/// executed state of every line of a file that generated code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCoverage {
+    pub path: String,
+    pub lines: BTreeMap<usize, bool>,
+}
+
+impl FileCoverage {
+    pub fn covered(&self) -> usize {
+        self.lines.values().filter(|covered| **covered).count()
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.lines.is_empty() {
+            100.0
+        } else {
+            self.covered() as f64 * 100.0 / self.lines.len() as f64
+        }
+    }
+
+    /// consecutive uncovered lines as inclusive ranges, lines without code do not break a range.
+    pub fn uncovered_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = vec![];
+        let mut open = false;
+        for (line, covered) in &self.lines {
+            match (covered, open) {
+                (false, true) => ranges.last_mut().unwrap().1 = *line,
+                (false, false) => {
+                    ranges.push((*line, *line));
+                    open = true;
+                }
+                (true, _) => open = false,
+            }
+        }
+        ranges
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub files: Vec<FileCoverage>,
+}
+
+impl CoverageReport {
+    /// lines are taken from the line tables, code that was never generated is not counted.
+    pub fn new(coverage: &Coverage, source_map: &SourceMap) -> Self {
+        let mut files = source_map
+            .files
+            .iter()
+            .map(|file| FileCoverage {
+                path: file.path.clone(),
+                lines: BTreeMap::new(),
+            })
+            .collect::<Vec<_>>();
+
+        for function in &source_map.functions {
+            let lines = &mut files[function.file].lines;
+            for (run, line_run) in function.lines.runs().iter().enumerate() {
+                let covered = coverage.is_covered(function.short_name(), run);
+                *lines.entry(line_run.line).or_insert(false) |= covered;
+            }
+        }
+
+        Self { files }
+    }
+
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            write!(
+                out,
+                "{}: {:.1}% ({}/{} lines)",
+                file.path,
+                file.percent(),
+                file.covered(),
+                file.lines.len()
+            )
+            .unwrap();
+
+            let uncovered = file
+                .uncovered_ranges()
+                .iter()
+                .map(|(start, end)| {
+                    if start == end {
+                        start.to_string()
+                    } else {
+                        format!("{}-{}", start, end)
+                    }
+                })
+                .collect::<Vec<_>>();
+            if !uncovered.is_empty() {
+                write!(out, ", uncovered: {}", uncovered.join(", ")).unwrap();
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// the report in the lcov tracefile format.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for file in &self.files {
+            writeln!(out, "SF:{}", file.path).unwrap();
+            for (line, covered) in &file.lines {
+                writeln!(out, "DA:{},{}", line, *covered as u8).unwrap();
+            }
+            writeln!(out, "LF:{}", file.lines.len()).unwrap();
+            writeln!(out, "LH:{}", file.covered()).unwrap();
+            writeln!(out, "end_of_record").unwrap();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoverageReport;
+    use crate::{
+        source_map::{FunctionMap, SourceFile, SourceMap},
+        vm::OpCode,
+        Value, Vm,
+    };
+
+    #[test]
+    fn test_untaken_branch_is_uncovered() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+
+        // 1: if false {
+        // 2:     1
+        // 3: } else {
+        // 4:     2
+        // 5: }
+        // 6: let x =
+        // 7:     3
+        let mut section = vm.new_section();
+        let one = section.add_constant(Value::I32(1));
+        let two = section.add_constant(Value::I32(2));
+        let three = section.add_constant(Value::I32(3));
+        section.mark_line(1);
+        section.write_op(OpCode::LoadFalse);
+        let else_jmp = section.write_jmp(OpCode::JmpFalse);
+        section.mark_line(2);
+        section.write_index(OpCode::LoadI32, one);
+        section.write_op(OpCode::Pop);
+        let end_jmp = section.write_jmp(OpCode::Jmp);
+        section.patch_jmp(else_jmp);
+        section.mark_line(4);
+        section.write_index(OpCode::LoadI32, two);
+        section.write_op(OpCode::Pop);
+        section.patch_jmp(end_jmp);
+        section.mark_line(6);
+        section.mark_expression_line(7);
+        section.write_index(OpCode::LoadI32, three);
+        section.write_op(OpCode::Return);
+
+        let mut source_map = SourceMap::new();
+        let file = source_map.add_file(SourceFile::new("branch.au".to_string(), ""));
+        source_map.add_function(FunctionMap {
+            name: "main".to_string(),
+            file,
+            span: (0, 0),
+            lines: section.lines().clone(),
+        });
+
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("branch");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        vm.force_no_collection(false);
+
+        vm.set_coverage(true);
+        vm.run_module(module).unwrap();
+        let coverage = vm.take_coverage().unwrap();
+        let report = CoverageReport::new(&coverage, &source_map);
+
+        let file = &report.files[0];
+        assert_eq!(file.uncovered_ranges(), vec![(2, 2)]);
+        // the statement on line 6 has no instructions of its own, it runs with line 7.
+        assert_eq!(file.covered(), 4);
+        assert_eq!(
+            report.summary(),
+            "branch.au: 80.0% (4/5 lines), uncovered: 2\n"
+        );
+        assert_eq!(
+            report.to_lcov(),
+            "SF:branch.au\nDA:1,1\nDA:2,0\nDA:4,1\nDA:6,1\nDA:7,1\nLF:5\nLH:4\nend_of_record\n"
+        );
+    }
+}
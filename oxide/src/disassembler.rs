@@ -1,9 +1,47 @@
+use std::fmt::Write;
+
 use crate::{
     mem::read_to,
     vm::{Instruction, OpCode},
-    Section,
+    OxFunction, OxModule, Section, Value,
 };
 
+/// the code of every function of module as text, an instruction a line with its offset, its
+/// operand and the constant or global it loads. A jump is followed by the offset it continues
+/// at, the methods of a structure are listed under it.
+pub fn disassemble(module: &OxModule) -> String {
+    let mut text = format!("module {}\n", module.name());
+    for object in module.objects().iter() {
+        match object {
+            Value::Function(function) => {
+                let entry = matches!(
+                    module.entry(),
+                    Some(Value::Function(entry)) if entry.ptr() == function.ptr()
+                );
+                disassemble_function(&mut text, function, 1, entry);
+            }
+            Value::Struct(structure) => {
+                writeln!(text, "    struct {}", structure.name()).unwrap();
+                for method in structure.methods().iter() {
+                    disassemble_function(&mut text, method, 2, false);
+                }
+            }
+            other => writeln!(text, "    {} {}", other.ty(), other).unwrap(),
+        }
+    }
+    text
+}
+
+fn disassemble_function(text: &mut String, function: &OxFunction, indent: usize, entry: bool) {
+    let pad = "    ".repeat(indent);
+    let entry = if entry { ", entry" } else { "" };
+    let arity = function.arity();
+    writeln!(text, "{}fn {} (arity {}{}):", pad, function.name(), arity, entry).unwrap();
+    for instruction in function.section().disassemble() {
+        writeln!(text, "{}    {}", pad, instruction).unwrap();
+    }
+}
+
 pub struct Disassembler;
 
 impl Disassembler {
@@ -11,162 +49,355 @@ impl Disassembler {
         let mut ip = 0;
         let mut res = vec![];
         while ip < section.len() {
-            let op_code_raw = section.read(ip);
+            let (instruction, next) = Self::decode(section, ip);
+            res.extend(instruction);
+            ip = next;
+        }
+        res
+    }
+
+    /// the instruction at ip of section along with the offset of the one after it, none for a
+    /// label.
+    pub(crate) fn decode(section: &Section, ip: usize) -> (Option<Instruction>, usize) {
+        let start = ip;
+        let mut ip = ip;
+        let mut op_code = OpCode::from_u8(section.read(ip)).unwrap();
+        ip += 1;
+        let wide = op_code == OpCode::Wide;
+        if wide {
+            op_code = OpCode::from_u8(section.read(ip)).unwrap();
             ip += 1;
-            let op_code = OpCode::from_u8(op_code_raw).unwrap();
-            // println!("OpCode {}", op_code);
-            match op_code {
-                OpCode::LoadI8
-                | OpCode::LoadI16
-                | OpCode::LoadI32
-                | OpCode::LoadI64
-                | OpCode::LoadU8
-                | OpCode::LoadU16
-                | OpCode::LoadU32
-                | OpCode::LoadU64
-                | OpCode::LoadF32
-                | OpCode::LoadF64
-                | OpCode::LoadStr
-                | OpCode::LoadChar => {
-                    let value = section.read(ip);
-                    let con = section.get_constant(value as usize);
-                    res.push(Instruction::with_arg_and_const(
-                        ip - 1,
-                        op_code,
-                        value as u16,
-                        con,
-                    ));
-                    ip += 1;
-                }
-                OpCode::LoadGlobal => {
-                    let value = section.read(ip);
-                    let con = section.get_global(value as usize);
-                    res.push(Instruction::with_arg_and_const(
-                        ip - 1,
-                        op_code,
-                        value as u16,
-                        con,
-                    ));
-                    ip += 1;
-                }
-                OpCode::SetGlobal
-                | OpCode::LoadLocal
-                | OpCode::LoadAssoc
-                | OpCode::SetLocal
-                | OpCode::SetRegister
-                | OpCode::SetAttr
-                | OpCode::LoadRegister
-                | OpCode::Call => {
-                    let value = section.read(ip);
-                    res.push(Instruction::with_arg(ip - 1, op_code, value as u16));
-                    ip += 1;
-                }
-                OpCode::Loop
-                | OpCode::JmpTrue
-                | OpCode::JmpFalse
-                | OpCode::Jmp
-                | OpCode::NewInstance
-                | OpCode::NewTuple
-                | OpCode::InstanceAttr
-                | OpCode::TupleAttr => {
-                    let offset = ip - 1;
-                    let value = read_to::<u16>(section.data(), &mut ip);
-                    res.push(Instruction::with_arg(offset, op_code, value as u16));
-                }
-                OpCode::Label => {}
-                OpCode::Return
-                | OpCode::Exit
-                | OpCode::LoadTrue
-                | OpCode::LoadFalse
-                | OpCode::AddI8
-                | OpCode::AddI16
-                | OpCode::AddI32
-                | OpCode::AddI64
-                | OpCode::AddU8
-                | OpCode::AddU16
-                | OpCode::AddU32
-                | OpCode::AddU64
-                | OpCode::AddF32
-                | OpCode::AddF64
-                | OpCode::SubI8
-                | OpCode::SubI16
-                | OpCode::SubI32
-                | OpCode::SubI64
-                | OpCode::SubU8
-                | OpCode::SubU16
-                | OpCode::SubU32
-                | OpCode::SubU64
-                | OpCode::SubF32
-                | OpCode::SubF64
-                | OpCode::MultI8
-                | OpCode::MultI16
-                | OpCode::MultI32
-                | OpCode::MultI64
-                | OpCode::MultU8
-                | OpCode::MultU16
-                | OpCode::MultU32
-                | OpCode::MultU64
-                | OpCode::MultF32
-                | OpCode::MultF64
-                | OpCode::DivI8
-                | OpCode::DivI16
-                | OpCode::DivI32
-                | OpCode::DivI64
-                | OpCode::DivU8
-                | OpCode::DivU16
-                | OpCode::DivU32
-                | OpCode::DivU64
-                | OpCode::DivF32
-                | OpCode::DivF64
-                | OpCode::LessI8
-                | OpCode::LessI16
-                | OpCode::LessI32
-                | OpCode::LessI64
-                | OpCode::LessU8
-                | OpCode::LessU16
-                | OpCode::LessU32
-                | OpCode::LessU64
-                | OpCode::LessF32
-                | OpCode::LessF64
-                | OpCode::GreaterI8
-                | OpCode::GreaterI16
-                | OpCode::GreaterI32
-                | OpCode::GreaterI64
-                | OpCode::GreaterU8
-                | OpCode::GreaterU16
-                | OpCode::GreaterU32
-                | OpCode::GreaterU64
-                | OpCode::GreaterF32
-                | OpCode::GreaterF64
-                | OpCode::LessEqI8
-                | OpCode::LessEqI16
-                | OpCode::LessEqI32
-                | OpCode::LessEqI64
-                | OpCode::LessEqU8
-                | OpCode::LessEqU16
-                | OpCode::LessEqU32
-                | OpCode::LessEqU64
-                | OpCode::LessEqF32
-                | OpCode::LessEqF64
-                | OpCode::GreaterEqI8
-                | OpCode::GreaterEqI16
-                | OpCode::GreaterEqI32
-                | OpCode::GreaterEqI64
-                | OpCode::GreaterEqU8
-                | OpCode::GreaterEqU16
-                | OpCode::GreaterEqU32
-                | OpCode::GreaterEqU64
-                | OpCode::GreaterEqF32
-                | OpCode::GreaterEqF64
-                | OpCode::Pop
-                | OpCode::FrameStack
-                | OpCode::PushLocal
-                | OpCode::Echo => {
-                    res.push(Instruction::simple(ip - 1, op_code));
-                }
-                OpCode::NumOps => {}
+        }
+        let instruction = match op_code {
+            OpCode::LoadI8
+            | OpCode::LoadI16
+            | OpCode::LoadI32
+            | OpCode::LoadI64
+            | OpCode::LoadU8
+            | OpCode::LoadU16
+            | OpCode::LoadU32
+            | OpCode::LoadU64
+            | OpCode::LoadF32
+            | OpCode::LoadF64
+            | OpCode::LoadStr
+            | OpCode::LoadChar
+            | OpCode::LoadConst
+            | OpCode::NewInstanceConst
+            | OpCode::NewArrayConst => {
+                let value = Self::read_index(section, &mut ip, wide);
+                let con = section.get_constant(value as usize);
+                Some(Instruction::with_arg_and_const(start, op_code, value, con))
+            }
+            OpCode::LoadGlobal => {
+                let value = Self::read_index(section, &mut ip, wide);
+                let con = section.get_global(value as usize);
+                Some(Instruction::with_arg_and_const(start, op_code, value, con))
+            }
+            OpCode::SetGlobal | OpCode::LoadLocal | OpCode::SetLocal => {
+                let value = Self::read_index(section, &mut ip, wide);
+                Some(Instruction::with_arg(start, op_code, value))
+            }
+            OpCode::LoadAssoc
+            | OpCode::SetRegister
+            | OpCode::SetAttr
+            | OpCode::LoadRegister
+            | OpCode::LogEnabled
+            | OpCode::CastInt
+            | OpCode::Log
+            | OpCode::Spawn
+            | OpCode::TrapFormat
+            | OpCode::NewClosure
+            | OpCode::Call => {
+                let value = section.read(ip);
+                ip += 1;
+                Some(Instruction::with_arg(start, op_code, value as u32))
+            }
+            OpCode::Loop | OpCode::JmpTrue | OpCode::JmpFalse | OpCode::JmpUnit | OpCode::Jmp => {
+                let value = read_to::<u16>(section.data(), &mut ip);
+                // a jump is relative to the instruction after it, a loop jumps back.
+                let target = if op_code == OpCode::Loop {
+                    ip.wrapping_sub(value as usize)
+                } else {
+                    ip + value as usize
+                };
+                Some(Instruction::jump(start, op_code, value as u32, target))
+            }
+            OpCode::NewInstance
+            | OpCode::NewTuple
+            | OpCode::NewArray
+            | OpCode::InstanceAttr
+            | OpCode::TupleAttr => {
+                let value = read_to::<u16>(section.data(), &mut ip);
+                Some(Instruction::with_arg(start, op_code, value as u32))
             }
+            OpCode::Label => None,
+            OpCode::Return
+            | OpCode::Exit
+            | OpCode::LoadTrue
+            | OpCode::LoadFalse
+            | OpCode::LoadUnit
+            | OpCode::AddI8
+            | OpCode::AddI16
+            | OpCode::AddI32
+            | OpCode::AddI64
+            | OpCode::AddU8
+            | OpCode::AddU16
+            | OpCode::AddU32
+            | OpCode::AddU64
+            | OpCode::AddF32
+            | OpCode::AddF64
+            | OpCode::SubI8
+            | OpCode::SubI16
+            | OpCode::SubI32
+            | OpCode::SubI64
+            | OpCode::SubU8
+            | OpCode::SubU16
+            | OpCode::SubU32
+            | OpCode::SubU64
+            | OpCode::SubF32
+            | OpCode::SubF64
+            | OpCode::MultI8
+            | OpCode::MultI16
+            | OpCode::MultI32
+            | OpCode::MultI64
+            | OpCode::MultU8
+            | OpCode::MultU16
+            | OpCode::MultU32
+            | OpCode::MultU64
+            | OpCode::MultF32
+            | OpCode::MultF64
+            | OpCode::DivI8
+            | OpCode::DivI16
+            | OpCode::DivI32
+            | OpCode::DivI64
+            | OpCode::DivU8
+            | OpCode::DivU16
+            | OpCode::DivU32
+            | OpCode::DivU64
+            | OpCode::DivF32
+            | OpCode::DivF64
+            | OpCode::RemI8
+            | OpCode::RemI16
+            | OpCode::RemI32
+            | OpCode::RemI64
+            | OpCode::RemU8
+            | OpCode::RemU16
+            | OpCode::RemU32
+            | OpCode::RemU64
+            | OpCode::RemF32
+            | OpCode::RemF64
+            | OpCode::DivFloorI8
+            | OpCode::DivFloorI16
+            | OpCode::DivFloorI32
+            | OpCode::DivFloorI64
+            | OpCode::DivFloorU8
+            | OpCode::DivFloorU16
+            | OpCode::DivFloorU32
+            | OpCode::DivFloorU64
+            | OpCode::ModFloorI8
+            | OpCode::ModFloorI16
+            | OpCode::ModFloorI32
+            | OpCode::ModFloorI64
+            | OpCode::ModFloorU8
+            | OpCode::ModFloorU16
+            | OpCode::ModFloorU32
+            | OpCode::ModFloorU64
+            | OpCode::LessI8
+            | OpCode::LessI16
+            | OpCode::LessI32
+            | OpCode::LessI64
+            | OpCode::LessU8
+            | OpCode::LessU16
+            | OpCode::LessU32
+            | OpCode::LessU64
+            | OpCode::LessF32
+            | OpCode::LessF64
+            | OpCode::GreaterI8
+            | OpCode::GreaterI16
+            | OpCode::GreaterI32
+            | OpCode::GreaterI64
+            | OpCode::GreaterU8
+            | OpCode::GreaterU16
+            | OpCode::GreaterU32
+            | OpCode::GreaterU64
+            | OpCode::GreaterF32
+            | OpCode::GreaterF64
+            | OpCode::LessEqI8
+            | OpCode::LessEqI16
+            | OpCode::LessEqI32
+            | OpCode::LessEqI64
+            | OpCode::LessEqU8
+            | OpCode::LessEqU16
+            | OpCode::LessEqU32
+            | OpCode::LessEqU64
+            | OpCode::LessEqF32
+            | OpCode::LessEqF64
+            | OpCode::GreaterEqI8
+            | OpCode::GreaterEqI16
+            | OpCode::GreaterEqI32
+            | OpCode::GreaterEqI64
+            | OpCode::GreaterEqU8
+            | OpCode::GreaterEqU16
+            | OpCode::GreaterEqU32
+            | OpCode::GreaterEqU64
+            | OpCode::GreaterEqF32
+            | OpCode::GreaterEqF64
+            | OpCode::EqEqI8
+            | OpCode::EqEqI16
+            | OpCode::EqEqI32
+            | OpCode::EqEqI64
+            | OpCode::EqEqU8
+            | OpCode::EqEqU16
+            | OpCode::EqEqU32
+            | OpCode::EqEqU64
+            | OpCode::EqEqF32
+            | OpCode::EqEqF64
+            | OpCode::NotEqI8
+            | OpCode::NotEqI16
+            | OpCode::NotEqI32
+            | OpCode::NotEqI64
+            | OpCode::NotEqU8
+            | OpCode::NotEqU16
+            | OpCode::NotEqU32
+            | OpCode::NotEqU64
+            | OpCode::NotEqF32
+            | OpCode::NotEqF64
+            | OpCode::EqEqFn
+            | OpCode::NotEqFn
+            | OpCode::EqEqStr
+            | OpCode::NotEqStr
+            | OpCode::ConcatStr
+            | OpCode::BinaryAndI8
+            | OpCode::BinaryAndI16
+            | OpCode::BinaryAndI32
+            | OpCode::BinaryAndI64
+            | OpCode::BinaryAndU8
+            | OpCode::BinaryAndU16
+            | OpCode::BinaryAndU32
+            | OpCode::BinaryAndU64
+            | OpCode::BinaryOrI8
+            | OpCode::BinaryOrI16
+            | OpCode::BinaryOrI32
+            | OpCode::BinaryOrI64
+            | OpCode::BinaryOrU8
+            | OpCode::BinaryOrU16
+            | OpCode::BinaryOrU32
+            | OpCode::BinaryOrU64
+            | OpCode::BinaryXorI8
+            | OpCode::BinaryXorI16
+            | OpCode::BinaryXorI32
+            | OpCode::BinaryXorI64
+            | OpCode::BinaryXorU8
+            | OpCode::BinaryXorU16
+            | OpCode::BinaryXorU32
+            | OpCode::BinaryXorU64
+            | OpCode::LoadIndex
+            | OpCode::StoreIndex
+            | OpCode::ListPush
+            | OpCode::ListPop
+            | OpCode::ListLen
+            | OpCode::ListWithCapacity
+            | OpCode::ListExtend
+            | OpCode::ListCopy
+            | OpCode::ListFill
+            | OpCode::ListFillRange
+            | OpCode::ListReserve
+            | OpCode::ListCapacity
+            | OpCode::ListShrinkToFit
+            | OpCode::MapReserve
+            | OpCode::NewMap
+            | OpCode::MapInsert
+            | OpCode::MapGet
+            | OpCode::MapRemove
+            | OpCode::MapContains
+            | OpCode::MapLen
+            | OpCode::GetField
+            | OpCode::ShiftLeftI8
+            | OpCode::ShiftLeftI16
+            | OpCode::ShiftLeftI32
+            | OpCode::ShiftLeftI64
+            | OpCode::ShiftLeftU8
+            | OpCode::ShiftLeftU16
+            | OpCode::ShiftLeftU32
+            | OpCode::ShiftLeftU64
+            | OpCode::ShiftRightI8
+            | OpCode::ShiftRightI16
+            | OpCode::ShiftRightI32
+            | OpCode::ShiftRightI64
+            | OpCode::ShiftRightU8
+            | OpCode::ShiftRightU16
+            | OpCode::ShiftRightU32
+            | OpCode::ShiftRightU64
+            | OpCode::Pop
+            | OpCode::FrameStack
+            | OpCode::PushLocal
+            | OpCode::Join
+            | OpCode::Trap
+            | OpCode::LoadHost
+            | OpCode::Eval
+            | OpCode::NewWeak
+            | OpCode::WeakGet
+            | OpCode::GcCollect
+            | OpCode::RegisterFinalizer
+            | OpCode::Echo => Some(Instruction::simple(start, op_code)),
+            OpCode::Wide | OpCode::NumOps => None,
+        };
+        (instruction, ip)
+    }
+
+    fn read_index(section: &Section, ip: &mut usize, wide: bool) -> u32 {
+        if wide {
+            read_to::<u32>(section.data(), ip)
+        } else {
+            read_to::<u8>(section.data(), ip) as u32
         }
-        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+    use crate::{vm::OpCode, Value, Vm};
+
+    #[test]
+    fn test_jumps_are_annotated_with_their_target() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        // `while true { if false { 1 } }`, then return unit.
+        let mut section = vm.new_section();
+        let one = section.intern_constant(Value::I64(1));
+        section.write_op(OpCode::LoadTrue);
+        let exit = section.write_jmp(OpCode::JmpFalse);
+        section.write_op(OpCode::LoadFalse);
+        let skip = section.write_jmp(OpCode::JmpFalse);
+        section.write_index(OpCode::LoadI64, one);
+        section.write_op(OpCode::Pop);
+        section.patch_jmp(skip);
+        section.write_loop(0);
+        section.patch_jmp(exit);
+        section.write_op(OpCode::LoadUnit);
+        section.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("jumps");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        module.as_ref_mut().add_object(Value::I64(7));
+
+        assert_eq!(
+            disassemble(module.as_ref()),
+            "module jumps\n    fn main (arity 0, entry):\n\
+            \x20       0000000000 load_true\n\
+            \x20       0000000001 jmp_if_false 10 -> 000000000e\n\
+            \x20       0000000004 load_false\n\
+            \x20       0000000005 jmp_if_false 3 -> 000000000b\n\
+            \x20       0000000008 load_i64 0 (1)\n\
+            \x20       000000000a pop\n\
+            \x20       000000000b loop 14 -> 0000000000\n\
+            \x20       000000000e load_unit\n\
+            \x20       000000000f return\n\
+            \x20   i64 7\n"
+        );
     }
 }
@@ -0,0 +1,200 @@
+//! the spelling of floats. A finite float is written with the fewest digits that parse back to
+//! the same bits, the digits come from the shortest exponential form of the standard library.
+
+use std::fmt::LowerExp;
+use std::str::FromStr;
+
+/// the spellings of the floats that are not finite, no other spelling of them is parsed.
+pub const NAN: &str = "nan";
+pub const INFINITY: &str = "inf";
+pub const NEG_INFINITY: &str = "-inf";
+
+/// floats with a decimal exponent in this range are written without one, `0.0001` and
+/// `1234.5`, the others with the shortest exponent, `1.5e-5` and `1e16`.
+const FIXED_EXPONENTS: std::ops::Range<i32> = -4..16;
+
+pub fn format_f64(value: f64) -> String {
+    if value.is_nan() {
+        NAN.to_owned()
+    } else if value.is_infinite() {
+        infinity(value.is_sign_negative())
+    } else {
+        format_finite(value)
+    }
+}
+
+pub fn format_f32(value: f32) -> String {
+    if value.is_nan() {
+        NAN.to_owned()
+    } else if value.is_infinite() {
+        infinity(value.is_sign_negative())
+    } else {
+        format_finite(value)
+    }
+}
+
+/// the float written as text, the inverse of `format_f64`.
+pub fn parse_f64(text: &str) -> Option<f64> {
+    parse(text, f64::NAN, f64::INFINITY)
+}
+
+/// the float written as text, the inverse of `format_f32`.
+pub fn parse_f32(text: &str) -> Option<f32> {
+    parse(text, f32::NAN, f32::INFINITY)
+}
+
+fn infinity(negative: bool) -> String {
+    if negative { NEG_INFINITY } else { INFINITY }.to_owned()
+}
+
+fn parse<T>(text: &str, nan: T, infinity: T) -> Option<T>
+where
+    T: FromStr + std::ops::Neg<Output = T>,
+{
+    match text {
+        NAN => Some(nan),
+        INFINITY => Some(infinity),
+        NEG_INFINITY => Some(-infinity),
+        // the standard library also reads `NaN`, `infinity` and the like.
+        _ if text.chars().any(|c| c.is_alphabetic() && c != 'e' && c != 'E') => None,
+        _ => text.parse().ok(),
+    }
+}
+
+/// lays out the shortest digits, `-0.0`, `1.0`, `0.001`, `1.5e20` or `2.5e-7`. There is always
+/// a fraction or an exponent so the text reads back as a float.
+fn format_finite<T: LowerExp>(value: T) -> String {
+    let exponential = format!("{:e}", value);
+    let (mantissa, exponent) = exponential
+        .split_once('e')
+        .expect("the exponential form has an exponent");
+    let exponent = exponent.parse::<i32>().expect("the exponent is an integer");
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits = mantissa.replace('.', "");
+
+    if !FIXED_EXPONENTS.contains(&exponent) {
+        let (first, rest) = digits.split_at(1);
+        return match rest {
+            "" => format!("{}{}e{}", sign, first, exponent),
+            rest => format!("{}{}.{}e{}", sign, first, rest, exponent),
+        };
+    }
+
+    if exponent < 0 {
+        let zeros = "0".repeat((-exponent - 1) as usize);
+        return format!("{}0.{}{}", sign, zeros, digits);
+    }
+    let point = exponent as usize + 1;
+    if digits.len() <= point {
+        let zeros = "0".repeat(point - digits.len());
+        format!("{}{}{}.0", sign, digits, zeros)
+    } else {
+        let (whole, fraction) = digits.split_at(point);
+        format!("{}{}.{}", sign, whole, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_f32, format_f64, parse_f32, parse_f64};
+    use crate::Value;
+
+    const SAMPLES: usize = 100_000;
+
+    /// bit patterns spread over every exponent, the generator is fixed so a failure repeats.
+    struct Bits(u64);
+
+    impl Iterator for Bits {
+        type Item = u64;
+
+        fn next(&mut self) -> Option<u64> {
+            // xorshift64*
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            Some(self.0.wrapping_mul(0x2545_f491_4f6c_dd1d))
+        }
+    }
+
+    #[test]
+    fn test_spellings() {
+        let cases: [(f64, &str); 14] = [
+            (0.0, "0.0"),
+            (-0.0, "-0.0"),
+            (1.0, "1.0"),
+            (-2.5, "-2.5"),
+            (0.1, "0.1"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1e-4, "0.0001"),
+            (1.5e-5, "1.5e-5"),
+            (123456.789, "123456.789"),
+            (1e15, "1000000000000000.0"),
+            (1e16, "1e16"),
+            (1e300, "1e300"),
+            (f64::MAX, "1.7976931348623157e308"),
+            (5e-324, "5e-324"),
+        ];
+        for (value, text) in cases {
+            assert_eq!(format_f64(value), text);
+            assert_eq!(parse_f64(text).map(f64::to_bits), Some(value.to_bits()), "{}", text);
+        }
+        assert_eq!(format_f32(0.1), "0.1");
+        assert_eq!(format_f32(16777216.0), "16777216.0");
+        assert_eq!(format_f32(f32::MIN_POSITIVE), "1.1754944e-38");
+        assert_eq!(Value::F64(1e300).to_string(), "1e300");
+        assert_eq!(Value::F32(-0.0).to_string(), "-0.0");
+    }
+
+    #[test]
+    fn test_special_values() {
+        assert_eq!(format_f64(f64::NAN), "nan");
+        assert_eq!(format_f64(-f64::NAN), "nan");
+        assert_eq!(format_f64(f64::INFINITY), "inf");
+        assert_eq!(format_f32(f32::NEG_INFINITY), "-inf");
+        assert!(parse_f64("nan").unwrap().is_nan());
+        assert_eq!(parse_f64("inf"), Some(f64::INFINITY));
+        assert_eq!(parse_f32("-inf"), Some(f32::NEG_INFINITY));
+        for text in ["NaN", "infinity", "+inf", "Inf", "1.0x", ""] {
+            assert_eq!(parse_f64(text), None, "{}", text);
+        }
+
+        // negative zero is written apart from zero but is equal to it.
+        let (zero, negative) = (parse_f64("0.0").unwrap(), parse_f64("-0.0").unwrap());
+        assert!(negative.is_sign_negative());
+        assert_eq!(zero, negative);
+        assert_ne!(zero.to_bits(), negative.to_bits());
+    }
+
+    #[test]
+    fn test_f64_round_trips() {
+        for bits in Bits(0x9e37_79b9_7f4a_7c15).take(SAMPLES) {
+            let value = f64::from_bits(bits);
+            if !value.is_finite() {
+                continue;
+            }
+            let text = format_f64(value);
+            let parsed = parse_f64(&text).unwrap_or_else(|| panic!("{} does not parse", text));
+            assert_eq!(parsed.to_bits(), bits, "{}", text);
+            // the text read back is written the same way.
+            assert_eq!(format_f64(parsed), text);
+        }
+    }
+
+    #[test]
+    fn test_f32_round_trips() {
+        for bits in Bits(0x2545_f491_4f6c_dd1d).take(SAMPLES) {
+            let bits = (bits >> 32) as u32;
+            let value = f32::from_bits(bits);
+            if !value.is_finite() {
+                continue;
+            }
+            let text = format_f32(value);
+            let parsed = parse_f32(&text).unwrap_or_else(|| panic!("{} does not parse", text));
+            assert_eq!(parsed.to_bits(), bits, "{}", text);
+            assert_eq!(format_f32(parsed), text);
+        }
+    }
+}
@@ -6,12 +6,25 @@ extern crate itertools;
 
 mod disassembler;
 mod mem;
+mod verifier;
 mod runtime;
 mod value;
 mod bit_map;
+pub mod channel;
+pub mod compiled;
+pub mod convert;
+pub mod coverage;
+pub mod float;
+pub mod source_map;
 pub mod vm;
 
 pub use runtime::*;
 pub use value::Value;
 pub use vm::Vm;
 pub use bit_map::BitMap;
+pub use channel::Channel;
+pub use compiled::CompiledModule;
+pub use disassembler::disassemble;
+pub use convert::{ConvertError, Extract, FromValue, FromValueList, IntoValue};
+
+pub use coverage::{Coverage, CoverageReport};
@@ -1,4 +1,6 @@
-use crate::OxString;
+use std::collections::TryReserveError;
+
+use crate::{vm::Capability, ConvertError, OxString};
 
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum Error {
@@ -10,10 +12,278 @@ pub enum Error {
 
     #[error("attempting to call a value that cannot be called: {0}")]
     CallingInvalidValue(String),
+
+    #[error("unable to create a weak reference to a value of type {0}, it is not a heap object")]
+    WeakToNonHeapValue(String),
+
+    #[error("attempting to get a weak referent from a value of type {0}")]
+    NotAWeakHandle(String),
+
+    #[error("a value of type {0} cannot be part of a compiled module")]
+    UncompilableValue(String),
+
+    #[error("a value of type {0} cannot be sent over a channel")]
+    UnsendableValue(String),
+
+    #[error("sending on a closed channel")]
+    SendOnClosedChannel,
+
+    #[error("a value of type {0} cannot be passed to or returned from a task")]
+    UnsendableTaskValue(String),
+
+    #[error(
+        "a value of type {0} cannot be run as a task, it is not a function of the running module"
+    )]
+    InvalidTaskFunction(String),
+
+    #[error("range {start}..{end} is out of bounds for a list of length {len}")]
+    ListRangeOutOfBounds { start: usize, end: usize, len: usize },
+
+    #[error("index {index} is out of bounds for an array of length {len}")]
+    IndexOutOfBounds { index: i128, len: usize },
+
+    #[error("cannot pop from an empty vector")]
+    EmptyListPop,
+
+    #[error("cannot reserve a negative capacity of {0}")]
+    NegativeCapacity(i64),
+
+    /// an allocation failed even after a collection. The error holds nothing of the heap, so
+    /// raising it allocates nothing while memory is exhausted.
+    #[error("out of memory")]
+    OutOfMemory,
+
+    #[error("invalid bytecode at {offset}: {reason}")]
+    InvalidBytecode { offset: usize, reason: String },
+
+    #[error("invalid module file at {offset}: {reason}")]
+    InvalidModuleFile { offset: usize, reason: String },
+
+    #[error("unable to read module file '{path}': {reason}")]
+    ReadModuleFile { path: String, reason: String },
+
+    #[error("integer overflow of a value of type {0}")]
+    IntegerOverflow(String),
+
+    #[error("integer division by zero")]
+    DivisionByZero,
+
+    /// a call would have run more frames than the vm allows, the frames are the functions
+    /// running when it was made, inner most first.
+    #[error("stack overflow, calls are nested more than {depth} deep: {}", .frames.join(", "))]
+    StackOverflow { depth: usize, frames: Vec<String> },
+
+    #[error("global {index} of '{function}' is read before it is initialized")]
+    GlobalReadBeforeInit { index: usize, function: String },
+
+    /// the stack of a suspended run holds the values of the instruction it stopped in, a
+    /// module entered on top of it would run over them.
+    #[error("module '{0}' is entered while a run is suspended between slices")]
+    RunSuspended(String),
+
+    /// raised by a script, the message is given by the code raising it.
+    #[error("{0}")]
+    Trap(String),
+
+    #[error("no module is running to replace '{0}' in")]
+    NoRunningModule(String),
+
+    #[error("'{function}' can not be replaced without a restart: {}", .mismatches.join(", "))]
+    IncompatibleReplacement {
+        function: String,
+        mismatches: Vec<String>,
+    },
+
+    #[error("'{0}' is not a function of the running module")]
+    UnknownExport(String),
+
+    /// the handle was looked up before a function was replaced or another module entered, it
+    /// is looked up again.
+    #[error("the handle of '{0}' is stale, it is looked up again in the running module")]
+    StaleExport(String),
+
+    #[error("'{0}' can not be called by the host while a function is running")]
+    CallWhileRunning(String),
+
+    #[error("'{function}' takes {expected} arguments but {found} were given")]
+    ExportArity {
+        function: String,
+        expected: u8,
+        found: usize,
+    },
+
+    #[error("argument {index} of '{function}' is {found}, expected {expected}")]
+    ExportArgument {
+        function: String,
+        index: usize,
+        expected: String,
+        found: String,
+    },
+
+    #[error("{0}")]
+    Conversion(ConvertError),
+
+    /// a native was stopped before reaching outside of the vm, the capability names what it
+    /// was about to do.
+    #[error("capability not granted: {0}")]
+    CapabilityNotGranted(String),
+
+    #[error("writing the output failed: {0}")]
+    Io(String),
+
+    /// the output failed with `IoErrorPolicy::Exit`, the script did not fail.
+    #[error("execution stopped, the output failed: {0}")]
+    ExitedDueToIo(String),
+}
+
+/// a native function converting its parameters with `?` raises the error.
+impl From<ConvertError> for Error {
+    fn from(err: ConvertError) -> Self {
+        Self::Conversion(err)
+    }
 }
 
+/// a buffer that cannot grow, its allocation failed or its size overflowed.
+impl From<TryReserveError> for Error {
+    fn from(_: TryReserveError) -> Self {
+        Self::OutOfMemory
+    }
+}
+
+
 impl Error {
     pub fn missing_module_entry(module_name: OxString) -> Self {
         Self::MissingModuleEntry { module_name }
     }
+
+    pub fn weak_to_non_heap_value(ty: &str) -> Self {
+        Self::WeakToNonHeapValue(ty.to_string())
+    }
+
+    pub fn not_a_weak_handle(ty: &str) -> Self {
+        Self::NotAWeakHandle(ty.to_string())
+    }
+
+    pub fn uncompilable_value(ty: &str) -> Self {
+        Self::UncompilableValue(ty.to_string())
+    }
+
+    pub fn unsendable_value(ty: &str) -> Self {
+        Self::UnsendableValue(ty.to_string())
+    }
+
+    pub fn unsendable_task_value(ty: &str) -> Self {
+        Self::UnsendableTaskValue(ty.to_string())
+    }
+
+    pub fn invalid_task_function(ty: &str) -> Self {
+        Self::InvalidTaskFunction(ty.to_string())
+    }
+
+    pub fn list_range_out_of_bounds(start: usize, end: usize, len: usize) -> Self {
+        Self::ListRangeOutOfBounds { start, end, len }
+    }
+
+    pub fn index_out_of_bounds(index: i128, len: usize) -> Self {
+        Self::IndexOutOfBounds { index, len }
+    }
+
+    pub fn empty_list_pop() -> Self {
+        Self::EmptyListPop
+    }
+
+    pub fn negative_capacity(additional: i64) -> Self {
+        Self::NegativeCapacity(additional)
+    }
+
+    pub fn out_of_memory() -> Self {
+        Self::OutOfMemory
+    }
+
+    pub fn global_read_before_init(index: usize, function: String) -> Self {
+        Self::GlobalReadBeforeInit { index, function }
+    }
+
+    pub fn invalid_bytecode(offset: usize, reason: String) -> Self {
+        Self::InvalidBytecode { offset, reason }
+    }
+
+    pub fn invalid_module_file(offset: usize, reason: String) -> Self {
+        Self::InvalidModuleFile { offset, reason }
+    }
+
+    pub fn read_module_file(path: &str, err: &std::io::Error) -> Self {
+        Self::ReadModuleFile {
+            path: path.to_string(),
+            reason: err.to_string(),
+        }
+    }
+
+    pub fn integer_overflow(ty: &str) -> Self {
+        Self::IntegerOverflow(ty.to_string())
+    }
+
+    pub fn stack_overflow(depth: usize, frames: Vec<String>) -> Self {
+        Self::StackOverflow { depth, frames }
+    }
+
+    pub fn run_suspended(module_name: &str) -> Self {
+        Self::RunSuspended(module_name.to_string())
+    }
+
+    pub fn trap(message: &str) -> Self {
+        Self::Trap(message.to_string())
+    }
+
+    pub fn capability_not_granted(capability: &Capability) -> Self {
+        Self::CapabilityNotGranted(capability.to_string())
+    }
+
+    pub fn io(err: &std::io::Error) -> Self {
+        Self::Io(err.to_string())
+    }
+
+    pub fn exited_due_to_io(err: &std::io::Error) -> Self {
+        Self::ExitedDueToIo(err.to_string())
+    }
+
+    pub fn no_running_module(function: &str) -> Self {
+        Self::NoRunningModule(function.to_string())
+    }
+
+    pub fn incompatible_replacement(function: &str, mismatches: Vec<String>) -> Self {
+        Self::IncompatibleReplacement {
+            function: function.to_string(),
+            mismatches,
+        }
+    }
+
+    pub fn unknown_export(function: &str) -> Self {
+        Self::UnknownExport(function.to_string())
+    }
+
+    pub fn stale_export(function: &str) -> Self {
+        Self::StaleExport(function.to_string())
+    }
+
+    pub fn call_while_running(function: &str) -> Self {
+        Self::CallWhileRunning(function.to_string())
+    }
+
+    pub fn export_arity(function: &str, expected: u8, found: usize) -> Self {
+        Self::ExportArity {
+            function: function.to_string(),
+            expected,
+            found,
+        }
+    }
+
+    pub fn export_argument(function: &str, index: usize, expected: &str, found: &str) -> Self {
+        Self::ExportArgument {
+            function: function.to_string(),
+            index,
+            expected: expected.to_string(),
+            found: found.to_string(),
+        }
+    }
 }
@@ -1,6 +1,9 @@
 use std::ops::{Add, Sub};
 
-use crate::{OxFunction, OxInstance, OxModule, OxString, OxStruct, OxTuple, Value};
+use crate::{
+    OxClosure, OxFunction, OxInstance, OxList, OxMap, OxModule, OxString, OxStruct, OxTuple,
+    OxUserData, Value,
+};
 
 use super::{Cell, Gc, Header, Object, ObjectKind};
 
@@ -45,6 +48,13 @@ impl Address {
     pub fn mark(&mut self, val: bool) {
         self.cell_mut().mark(val)
     }
+
+    /// the bytes the object takes on the heap, its header included.
+    pub fn allocation_size(&self) -> usize {
+        let header =
+            unsafe { &*(self.as_ptr().sub(std::mem::size_of::<Header>()) as *const Header) };
+        header.size()
+    }
 }
 
 impl Into<Value> for Address {
@@ -57,6 +67,10 @@ impl Into<Value> for Address {
             ObjectKind::Struct => Value::from(Gc::<OxStruct>::new(self)),
             ObjectKind::Tuple => Value::from(Gc::<OxTuple>::new(self)),
             ObjectKind::Function => Value::from(Gc::<OxFunction>::new(self)),
+            ObjectKind::UserData => Value::from(Gc::<OxUserData>::new(self)),
+            ObjectKind::List => Value::from(Gc::<OxList>::new(self)),
+            ObjectKind::Map => Value::from(Gc::<OxMap>::new(self)),
+            ObjectKind::Closure => Value::from(Gc::<OxClosure>::new(self)),
             _ => {
                 panic!(
                     "Attempting to get a value from object of kind: {:?}",
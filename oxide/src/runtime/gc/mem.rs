@@ -1,6 +1,6 @@
 use std::{
     alloc::{AllocError, Layout},
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     fmt::Display,
     ptr::NonNull,
     sync::atomic::{AtomicUsize, Ordering},
@@ -17,6 +17,8 @@ use super::{Cell, ObjectKind};
 static COLLECT_INITIAL: usize = 256; //1024 * 1024;
 static COLLECT_FACTOR: f64 = 1.5;
 static DEFAULT_POOL_SIZE: usize = 1024;
+// alignment of every pool buffer, objects start right after their header so this keeps them aligned.
+static POOL_ALIGN: usize = std::mem::align_of::<Header>();
 
 struct FreeNode {
     size: usize,
@@ -39,43 +41,66 @@ impl FreeNode {
     }
 }
 
+/// the slots of objects of one size. It starts with a buffer of slots and adds another of the
+/// same size every time they are all allocated, a buffer is only released with the pool.
 #[derive(Debug, Clone)]
 pub struct Pool {
-    buffer: *mut u8,
+    buffers: Vec<*mut u8>,
+    /// the buffers by their address, to find the buffer a slot belongs to.
+    starts: BTreeMap<usize, usize>,
     element_size: usize,
-    len: usize,
+    /// the slots of each buffer.
+    elements: usize,
     bitmap: BitMap,
+    /// the slots allocated, the memory in use is checked on every allocation.
+    slots: usize,
+    /// no slot before it is free.
+    first_free: usize,
 }
 
 impl Drop for Pool {
     fn drop(&mut self) {
-        unsafe {
-            std::alloc::dealloc(
-                self.buffer,
-                Layout::from_size_align_unchecked(self.len(), 1),
-            );
+        for buffer in self.buffers.iter() {
+            unsafe {
+                std::alloc::dealloc(*buffer, self.buffer_layout());
+            }
         }
     }
 }
 
 impl Pool {
     pub fn new(element_size: usize, elements: usize) -> Self {
-        let buffer = unsafe {
-            std::alloc::alloc(Layout::from_size_align_unchecked(
-                element_size * elements,
-                1,
-            ))
-        };
-        Self {
-            buffer,
+        let mut pool = Self {
+            buffers: vec![],
+            starts: BTreeMap::new(),
             element_size,
-            len: element_size * elements,
-            bitmap: BitMap::new(elements),
+            elements,
+            bitmap: BitMap::new(0),
+            slots: 0,
+            first_free: 0,
+        };
+        pool.grow().expect("failed to allocate a pool");
+        pool
+    }
+
+    fn buffer_layout(&self) -> Layout {
+        unsafe { Layout::from_size_align_unchecked(self.element_size * self.elements, POOL_ALIGN) }
+    }
+
+    /// adds a buffer of slots.
+    fn grow(&mut self) -> Result<(), AllocError> {
+        let buffer = unsafe { std::alloc::alloc(self.buffer_layout()) };
+        if buffer.is_null() {
+            return Err(AllocError);
         }
+        self.starts.insert(buffer as usize, self.buffers.len());
+        self.buffers.push(buffer);
+        self.bitmap.grow(self.buffers.len() * self.elements);
+        Ok(())
     }
 
     pub fn len(&self) -> usize {
-        self.len
+        self.buffers.len() * self.element_size * self.elements
     }
 
     pub fn free(&self) -> usize {
@@ -83,36 +108,60 @@ impl Pool {
     }
 
     pub fn allocated(&self) -> usize {
-        self.bitmap.count_ones() * self.element_size
+        self.slots * self.element_size
     }
 
     pub fn alloc(&mut self) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
-        if let Some(index) = self.bitmap.first_zero() {
-            self.bitmap.set(index, true);
-            let ptr = self.ptr(index);
-            let ptr = unsafe { NonNull::new_unchecked(ptr) };
-            Ok(NonNull::slice_from_raw_parts(ptr, self.element_size))
-        } else {
-            Err(std::alloc::AllocError)
-        }
+        let index = match self.bitmap.first_zero_from(self.first_free) {
+            Some(index) => index,
+            None => {
+                self.grow()?;
+                self.len() / self.element_size - self.elements
+            }
+        };
+        self.bitmap.set(index, true);
+        self.slots += 1;
+        self.first_free = index + 1;
+        let ptr = self.ptr(index);
+        let ptr = unsafe { NonNull::new_unchecked(ptr) };
+        Ok(NonNull::slice_from_raw_parts(ptr, self.element_size))
     }
 
     pub fn dealloc(&mut self, ptr: *mut u8) {
-        if self.buffer <= ptr && ptr < unsafe { self.buffer.add(self.len) } {
-            let diff = ptr as usize - self.buffer as usize;
-            let rem = diff % self.element_size;
-            assert_eq!(rem, 0);
+        let address = ptr as usize;
+        let buffer = match self.starts.range(..=address).next_back() {
+            Some((start, buffer)) if address < start + self.element_size * self.elements => *buffer,
+            _ => return,
+        };
+        let diff = address - self.buffers[buffer] as usize;
+        let rem = diff % self.element_size;
+        assert_eq!(rem, 0);
 
-            let slot = diff / self.element_size;
-            self.bitmap.set(slot, false);
+        let slot = buffer * self.elements + diff / self.element_size;
+        if self.bitmap.get(slot) {
+            self.slots -= 1;
         }
+        self.bitmap.set(slot, false);
+        self.first_free = self.first_free.min(slot);
     }
 
     pub fn ptr(&self, idx: usize) -> *mut u8 {
-        unsafe { self.buffer.add(idx * self.element_size) }
+        let buffer = self.buffers[idx / self.elements];
+        unsafe { buffer.add(idx % self.elements * self.element_size) }
+    }
+
+    /// the headers of the allocated slots.
+    fn allocated_slots(&self) -> impl Iterator<Item = *mut u8> + '_ {
+        self.bitmap.iter().enumerate().flat_map(move |(idx, byte)| {
+            (0..8)
+                .filter(move |i| (*byte >> i) & 0x1 == 1)
+                .map(move |i| self.ptr(idx * 8 + i))
+        })
     }
 
-    pub fn dealloc_unmarked(&mut self) {
+    /// clears the mark of every live object and collects the objects that were not marked.
+    /// Vec buffers are skipped, they are released when the object owning them is dropped.
+    pub fn collect_unmarked(&mut self, garbage: &mut Vec<(*mut u8, ObjectKind)>) {
         // for each allocated slot check if it has been marked.
         for (idx, byte) in self.bitmap.clone().iter().enumerate() {
             for i in 0..8 {
@@ -120,17 +169,11 @@ impl Pool {
                     let index = idx * 8 + i;
                     let ptr = self.ptr(index);
                     let header = unsafe { &mut *(ptr as *mut Header) };
-                    // let kind = cell.kind.clone();
                     if header.cell.marked {
                         header.cell.mark(false);
-                    } else {
-                        println!(
-                            "Deallocating: {:p} {:p} {:?}",
-                            ptr,
-                            unsafe { ptr.add(std::mem::size_of::<Header>()) },
-                            header.cell.kind
-                        );
-                        self.dealloc(ptr);
+                    } else if header.cell.kind != ObjectKind::Vec {
+                        let object = unsafe { ptr.add(std::mem::size_of::<Header>()) };
+                        garbage.push((object, header.cell.kind));
                     }
                 }
             }
@@ -152,8 +195,11 @@ pub struct Arena {
 
 impl Drop for Arena {
     fn drop(&mut self) {
-        unsafe {
-            std::alloc::dealloc(self.buffer, Layout::from_size_align_unchecked(self.len, 1));
+        // the arena is lazily initialized and might not own a buffer.
+        if self.allocated() {
+            unsafe {
+                std::alloc::dealloc(self.buffer, Layout::from_size_align_unchecked(self.len, 1));
+            }
         }
     }
 }
@@ -339,12 +385,12 @@ impl Arena {
         }
     }
 
-    fn dealloc_unmarked(&mut self) {
+    fn collect_unmarked(&mut self, _garbage: &mut Vec<(*mut u8, ObjectKind)>) {
         // todo!()
     }
 }
 
-#[repr(packed)]
+#[repr(C, align(8))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Header {
     layout: Layout,
@@ -374,6 +420,11 @@ impl Header {
     pub fn cell_mut(&mut self) -> &mut Cell {
         &mut self.cell
     }
+
+    /// the size of the allocation, this header included.
+    pub fn size(&self) -> usize {
+        self.layout.size()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -428,6 +479,15 @@ pub struct Memory {
     pools: Vec<Pool>,
     arena: Arena,
     start_power: usize,
+    /// allocations too large for the pools come from the system allocator, by address.
+    large: BTreeMap<usize, Layout>,
+    large_allocated: usize,
+    /// the times a vec buffer was moved to a buffer of another size, growing or shrinking.
+    reallocations: usize,
+    /// the allocations left before every allocation fails, none when they are not limited.
+    allocation_budget: Option<usize>,
+    /// the bytes allocated since the memory was created, objects and buffers alike.
+    allocated: u64,
 
     #[cfg(debug_assertions)]
     allocation_records: Vec<AllocationRecord>,
@@ -441,6 +501,11 @@ impl Memory {
             pools: vec![],
             arena: Arena::new(4048),
             start_power: 0,
+            large: BTreeMap::new(),
+            large_allocated: 0,
+            reallocations: 0,
+            allocation_budget: None,
+            allocated: 0,
 
             #[cfg(debug_assertions)]
             allocation_records: vec![],
@@ -452,7 +517,11 @@ impl Memory {
 
     #[inline(always)]
     pub fn memory_usage(&self) -> usize {
-        self.pools.iter().map(|pool| pool.allocated()).sum()
+        self.pools
+            .iter()
+            .map(|pool| pool.allocated())
+            .sum::<usize>()
+            + self.large_allocated
     }
 
     #[inline(always)]
@@ -460,8 +529,31 @@ impl Memory {
         self.memory_usage() >= self.next_collect
     }
 
+    pub fn reallocations(&self) -> usize {
+        self.reallocations
+    }
+
+    /// fails every allocation after the next budget of them, objects and buffers alike. Memory
+    /// running out is simulated with it, none lifts the limit.
+    pub fn set_allocation_budget(&mut self, budget: Option<usize>) {
+        self.allocation_budget = budget;
+    }
+
+    pub fn allocated(&self) -> u64 {
+        self.allocated
+    }
+
+    /// the memory in use the next collection starts at.
+    pub fn next_collection(&self) -> usize {
+        self.next_collect
+    }
+
+    /// called after a collection, the next one starts once the memory in use grows by
+    /// COLLECT_FACTOR over what this one left live. A heap that stays large is collected
+    /// less often, one that shrank goes back to collecting sooner.
     pub fn update_next_collection(&mut self) {
-        self.next_collect = (self.next_collect as f64 * COLLECT_FACTOR) as usize;
+        let live = (self.memory_usage() as f64 * COLLECT_FACTOR) as usize;
+        self.next_collect = live.max(COLLECT_INITIAL);
     }
 
     pub unsafe fn get_header(&self, ptr: *const u8) -> &Header {
@@ -492,6 +584,7 @@ impl Memory {
         // println!("allocating size: {}, with header: {}", old_size, size);
 
         let ptr = self.alloc_base(size)?;
+        self.allocated += size as u64;
 
         let header = ptr.as_ptr() as *mut u8 as *mut Header;
         println!("Allocating: {:p} {:?}", header, kind);
@@ -511,12 +604,46 @@ impl Memory {
     }
 
     fn alloc_base(&mut self, size: usize) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        if let Some(budget) = self.allocation_budget.as_mut() {
+            *budget = budget.checked_sub(1).ok_or(AllocError)?;
+        }
         // this is really expensive for each allocation
         let index = (size as f64).log2().floor() as usize - self.start_power;
         if index < self.pools.len() {
             self.pools[index].alloc()
         } else {
-            panic!("Arena allocator not implemented yet")
+            self.alloc_large(size)
+        }
+    }
+
+    fn alloc_large(&mut self, size: usize) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let layout = Layout::from_size_align(size, POOL_ALIGN).map_err(|_| AllocError)?;
+        let ptr = NonNull::new(unsafe { std::alloc::alloc(layout) }).ok_or(AllocError)?;
+        self.large.insert(ptr.as_ptr() as usize, layout);
+        self.large_allocated += size;
+        Ok(NonNull::slice_from_raw_parts(ptr, size))
+    }
+
+    fn dealloc_large(&mut self, ptr: *mut u8) {
+        let layout = self
+            .large
+            .remove(&(ptr as usize))
+            .expect("deallocating a large allocation that is not allocated");
+        self.large_allocated -= layout.size();
+        unsafe { std::alloc::dealloc(ptr, layout) };
+    }
+
+    /// the large allocation counterpart of `Pool::collect_unmarked`.
+    fn collect_unmarked_large(&mut self, garbage: &mut Vec<(*mut u8, ObjectKind)>) {
+        for ptr in self.large.keys() {
+            let ptr = *ptr as *mut u8;
+            let header = unsafe { &mut *(ptr as *mut Header) };
+            if header.cell.marked {
+                header.cell.mark(false);
+            } else if header.cell.kind != ObjectKind::Vec {
+                let object = unsafe { ptr.add(std::mem::size_of::<Header>()) };
+                garbage.push((object, header.cell.kind));
+            }
         }
     }
 
@@ -541,7 +668,7 @@ impl Memory {
         if index < self.pools.len() {
             self.pools[index].dealloc(ptr);
         } else {
-            panic!("Arean allocator not implemented")
+            self.dealloc_large(ptr);
         }
     }
 
@@ -590,14 +717,43 @@ impl Memory {
             );
         }
 
-        println!("Arena: not implemented");
+        println!(
+            "Large: Allocated: {} in {} allocations",
+            self.large_allocated,
+            self.large.len()
+        );
+    }
+
+    /// every allocated object of a kind, reachable or not.
+    pub fn objects(&self, kind: ObjectKind) -> Vec<*mut u8> {
+        let large = self.large.keys().map(|ptr| *ptr as *mut u8);
+        self.pools
+            .iter()
+            .flat_map(|pool| pool.allocated_slots())
+            .chain(large)
+            .filter(|ptr| unsafe { (*(*ptr as *const Header)).cell.kind == kind })
+            .map(|ptr| unsafe { ptr.add(std::mem::size_of::<Header>()) })
+            .collect()
+    }
+
+    /// unmarks the live objects and returns the unreachable ones. The objects are not released
+    /// here, dropping them frees their buffers through the memory lock this is called under.
+    pub fn sweep(&mut self) -> Vec<(*mut u8, ObjectKind)> {
+        let mut garbage = vec![];
+        for pool in self.pools.iter_mut() {
+            pool.collect_unmarked(&mut garbage);
+        }
+        self.collect_unmarked_large(&mut garbage);
+        self.arena.collect_unmarked(&mut garbage);
+        garbage
     }
+}
 
-    pub fn sweep(&mut self) {
-        for pool in self.pools.iter_mut() {
-            pool.dealloc_unmarked();
+impl Drop for Memory {
+    fn drop(&mut self) {
+        for (ptr, layout) in std::mem::take(&mut self.large) {
+            unsafe { std::alloc::dealloc(ptr as *mut u8, layout) };
         }
-        self.arena.dealloc_unmarked();
     }
 }
 
@@ -610,6 +766,25 @@ impl VecAllocator {
     pub fn new(memory: Arc<Mutex<Memory>>) -> Self {
         Self { memory }
     }
+
+    /// moves the buffer to one of the new size and counts it, the buffer is released.
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        use std::alloc::Allocator;
+        let new = self.allocate(new_layout)?;
+        let len = old_layout.size().min(new_layout.size());
+        std::ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr() as *mut u8, len);
+        self.deallocate(ptr, old_layout);
+        self.memory
+            .lock()
+            .expect("failed to retrieve memory lock")
+            .reallocations += 1;
+        Ok(new)
+    }
 }
 
 unsafe impl std::alloc::Allocator for VecAllocator {
@@ -626,4 +801,23 @@ unsafe impl std::alloc::Allocator for VecAllocator {
             .expect("failed to retrieve memory lock")
             .dealloc(ptr.as_ptr())
     }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.reallocate(ptr, old_layout, new_layout)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        self.reallocate(ptr, old_layout, new_layout)
+    }
 }
+
@@ -119,6 +119,41 @@ impl GcAlloc {
             .should_collect()
     }
 
+    pub fn allocated(&self) -> u64 {
+        self.memory
+            .lock()
+            .expect("failed to retreive memory lock")
+            .allocated()
+    }
+
+    pub fn next_collection(&self) -> usize {
+        self.memory
+            .lock()
+            .expect("failed to retreive memory lock")
+            .next_collection()
+    }
+
+    pub fn update_next_collection(&self) {
+        self.memory
+            .lock()
+            .expect("failed to retreive memory lock")
+            .update_next_collection()
+    }
+
+    pub fn reallocations(&self) -> usize {
+        self.memory
+            .lock()
+            .expect("failed to retreive memory lock")
+            .reallocations()
+    }
+
+    pub fn set_allocation_budget(&self, budget: Option<usize>) {
+        self.memory
+            .lock()
+            .expect("failed to retreive memory lock")
+            .set_allocation_budget(budget)
+    }
+
     #[inline(always)]
     pub fn allocator_vec(&self) -> VecAllocator {
         VecAllocator::new(self.memory.clone())
@@ -185,11 +220,26 @@ impl GcAlloc {
         println!("Sum: {}", self.memory_usage());
     }
 
-    pub fn sweep(&self) {
+    /// every allocated object of a kind, reachable or not.
+    pub fn objects(&self, kind: ObjectKind) -> Vec<Address> {
+        self.memory
+            .lock()
+            .expect("failed to retreive memory lock")
+            .objects(kind)
+            .into_iter()
+            .map(|ptr| Address::from_ptr(ptr))
+            .collect()
+    }
+
+    /// clears the marks of live objects and returns the addresses of the unreachable ones.
+    pub fn sweep(&self) -> Vec<(Address, ObjectKind)> {
         self.memory
             .lock()
             .expect("failed to retreive memory lock")
-            .sweep();
+            .sweep()
+            .into_iter()
+            .map(|(ptr, kind)| (Address::from_ptr(ptr), kind))
+            .collect()
     }
 }
 
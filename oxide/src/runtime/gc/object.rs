@@ -8,6 +8,10 @@ pub enum ObjectKind {
     String,
     Struct,
     Tuple,
+    UserData,
+    List,
+    Map,
+    Closure,
 }
 
 pub trait Object {
@@ -0,0 +1,78 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    gc::{Gc, Object, ObjectKind},
+    OxFunction, OxVec, Value,
+};
+
+/// a function along with the values it captured when it was created. Calling it calls the
+/// function with the captured values after the actual parameters, they are its last parameters.
+#[derive(Debug, Clone)]
+pub struct OxClosure {
+    function: Gc<OxFunction>,
+    captured: OxVec<Value>,
+}
+
+impl OxClosure {
+    pub fn new(function: Gc<OxFunction>, captured: OxVec<Value>) -> Self {
+        Self { function, captured }
+    }
+
+    pub fn function(&self) -> Gc<OxFunction> {
+        self.function
+    }
+
+    pub fn captured(&self) -> &OxVec<Value> {
+        &self.captured
+    }
+
+    pub(crate) fn captured_mut(&mut self) -> &mut OxVec<Value> {
+        &mut self.captured
+    }
+}
+
+impl Display for OxClosure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.function.is_literal() {
+            return write!(f, "{}", *self.function);
+        }
+        write!(f, "<closure {}>", self.function.name())
+    }
+}
+
+impl Object for OxClosure {
+    fn object_kind() -> ObjectKind {
+        ObjectKind::Closure
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Value, Vm};
+
+    #[test]
+    fn test_captured_values_survive_collections() {
+        let mut vm = Vm::new();
+        let name = vm.new_string_from_str("add");
+        let section = vm.new_section();
+        let function = vm.new_function(name, 2, section);
+        vm.push_stack(Value::from(function));
+        let captured = Value::from(vm.new_gc_string_from_str("captured by the closure"));
+        vm.push_stack(captured);
+        let closure = vm.new_closure(function, &[captured, Value::I64(2)]).unwrap();
+        vm.pop();
+        vm.pop();
+        vm.push_stack(Value::from(closure));
+
+        vm.gc_collect();
+        let strings = vm.heap_stats().strings;
+        assert_eq!(closure.captured()[0].to_string(), "captured by the closure");
+        assert_eq!(closure.function().arity(), 2);
+        assert_eq!(Value::from(closure).to_string(), "<closure add>");
+
+        // the captured string goes with the closure.
+        vm.pop();
+        vm.gc_collect();
+        assert_eq!(vm.heap_stats().strings, strings - 1);
+    }
+}
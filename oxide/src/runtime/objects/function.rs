@@ -5,6 +5,10 @@ use crate::{
     OxString, Section,
 };
 
+/// the start of the name of a function built from a function literal, `closure at app.au:12`.
+/// It renders as a closure whether or not it captures anything.
+pub const CLOSURE_NAME_PREFIX: &str = "closure at ";
+
 #[derive(Debug, Clone)]
 pub struct OxFunction {
     name: OxString,
@@ -37,6 +41,11 @@ impl OxFunction {
         self.arity
     }
 
+    /// whether the function was built from a function literal.
+    pub fn is_literal(&self) -> bool {
+        self.name.as_str().starts_with(CLOSURE_NAME_PREFIX)
+    }
+
     pub fn disassemble(&self, indent: usize) {
         println!(
             "{}disassembly {}:",
@@ -55,7 +64,12 @@ impl OxFunction {
 
 impl Display for OxFunction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<fn {}>", self.name)
+        if self.is_literal() {
+            return write!(f, "<{}>", self.name);
+        }
+        // a linked program joins the module of an item to its name with `::`, the path is
+        // written with periods like it is in the source.
+        write!(f, "<fn {}>", self.name.as_str().replace("::", "."))
     }
 }
 
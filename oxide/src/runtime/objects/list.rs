@@ -0,0 +1,319 @@
+use std::fmt::Display;
+
+use itertools::Itertools;
+
+use crate::{
+    gc::{Object, ObjectKind},
+    runtime, AttributeAccess, OxVec, Value,
+};
+
+/// growable list of values.
+#[derive(Debug, Clone)]
+pub struct OxList {
+    elements: OxVec<Value>,
+}
+
+/// start..end if it is inside a list of len elements.
+fn checked_range(start: usize, end: usize, len: usize) -> Result<(), runtime::Error> {
+    if start <= end && end <= len {
+        Ok(())
+    } else {
+        Err(runtime::Error::list_range_out_of_bounds(start, end, len))
+    }
+}
+
+impl OxList {
+    pub fn new(elements: OxVec<Value>) -> Self {
+        Self { elements }
+    }
+
+    pub fn elements(&self) -> &OxVec<Value> {
+        &self.elements
+    }
+
+    pub(crate) fn elements_mut(&mut self) -> &mut OxVec<Value> {
+        &mut self.elements
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.elements.capacity()
+    }
+
+    /// appends value, an error when the buffer cannot grow.
+    pub fn push(&mut self, value: Value) -> Result<(), runtime::Error> {
+        self.elements.try_reserve(1)?;
+        self.elements.push(value);
+        Ok(())
+    }
+
+    /// removes the last element, the buffer is kept.
+    pub fn pop(&mut self) -> Option<Value> {
+        self.elements.pop()
+    }
+
+    /// makes room for at least additional more elements, the buffer grows at most once.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), runtime::Error> {
+        self.elements.try_reserve(additional)?;
+        Ok(())
+    }
+
+    /// moves the elements to a buffer that fits them, an empty list releases its buffer.
+    pub fn shrink_to_fit(&mut self) {
+        self.elements.shrink_to_fit();
+    }
+
+    /// removes the elements, the buffer is kept.
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    /// appends values, the buffer grows at most once.
+    pub fn extend(&mut self, values: &[Value]) -> Result<(), runtime::Error> {
+        self.elements.try_reserve(values.len())?;
+        self.elements.extend_from_slice(values);
+        Ok(())
+    }
+
+    /// appends the list to itself.
+    pub fn extend_self(&mut self) -> Result<(), runtime::Error> {
+        let len = self.elements.len();
+        self.elements.try_reserve(len)?;
+        self.elements.extend_from_within(..len);
+        Ok(())
+    }
+
+    /// copies src[src_start..src_start + len] over self[dst_start..dst_start + len].
+    pub fn copy_from(
+        &mut self,
+        src: &[Value],
+        src_start: usize,
+        dst_start: usize,
+        len: usize,
+    ) -> Result<(), runtime::Error> {
+        let (src_end, dst_end) =
+            Self::copy_ranges(src.len(), self.len(), src_start, dst_start, len)?;
+        self.elements[dst_start..dst_end].copy_from_slice(&src[src_start..src_end]);
+        Ok(())
+    }
+
+    /// copy_from with the list as the source, the ranges may overlap.
+    pub fn copy_within(
+        &mut self,
+        src_start: usize,
+        dst_start: usize,
+        len: usize,
+    ) -> Result<(), runtime::Error> {
+        let (src_end, _) = Self::copy_ranges(self.len(), self.len(), src_start, dst_start, len)?;
+        self.elements.copy_within(src_start..src_end, dst_start);
+        Ok(())
+    }
+
+    fn copy_ranges(
+        src_len: usize,
+        dst_len: usize,
+        src_start: usize,
+        dst_start: usize,
+        len: usize,
+    ) -> Result<(usize, usize), runtime::Error> {
+        let src_end = src_start.saturating_add(len);
+        let dst_end = dst_start.saturating_add(len);
+        checked_range(src_start, src_end, src_len)?;
+        checked_range(dst_start, dst_end, dst_len)?;
+        Ok((src_end, dst_end))
+    }
+
+    pub fn fill(&mut self, value: Value) {
+        self.elements.fill(value);
+    }
+
+    pub fn fill_range(
+        &mut self,
+        start: usize,
+        end: usize,
+        value: Value,
+    ) -> Result<(), runtime::Error> {
+        checked_range(start, end, self.len())?;
+        self.elements[start..end].fill(value);
+        Ok(())
+    }
+}
+
+impl Display for OxList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}]",
+            self.elements.iter().map(Value::to_string).join(", ")
+        )
+    }
+}
+
+impl Object for OxList {
+    fn object_kind() -> ObjectKind {
+        ObjectKind::List
+    }
+}
+
+impl AttributeAccess for OxList {
+    type Output = Value;
+
+    fn get_attr(&self, idx: usize) -> &Self::Output {
+        &self.elements[idx]
+    }
+
+    fn get_attr_mut(&mut self, idx: usize) -> &mut Self::Output {
+        &mut self.elements[idx]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OxList;
+    use crate::{gc::Gc, runtime::Error, Value, Vm};
+
+    fn values(list: Gc<OxList>) -> Vec<i32> {
+        list.elements()
+            .iter()
+            .map(|value| match value {
+                Value::I32(val) => *val,
+                _ => panic!("unexpected element {}", value),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bulk_operations() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+
+        let mut list = vm.new_list_with_capacity(8).unwrap();
+        assert!(list.is_empty());
+        assert_eq!(list.capacity(), 8);
+        list.as_ref_mut()
+            .extend(&[Value::I32(1), Value::I32(2), Value::I32(3)])
+            .unwrap();
+        vm.list_extend(list, list).unwrap();
+        assert_eq!(values(list), vec![1, 2, 3, 1, 2, 3]);
+
+        // overlapping copies behave like a copy through a temporary in both directions.
+        vm.list_copy(list, list, 0, 1, 4).unwrap();
+        assert_eq!(values(list), vec![1, 1, 2, 3, 1, 3]);
+        vm.list_copy(list, list, 2, 0, 4).unwrap();
+        assert_eq!(values(list), vec![2, 3, 1, 3, 1, 3]);
+
+        let mut other = vm.new_list_with_capacity(4).unwrap();
+        other.as_ref_mut().extend(&[Value::I32(7), Value::I32(8)]).unwrap();
+        vm.list_copy(list, other, 0, 4, 2).unwrap();
+        assert_eq!(values(list), vec![2, 3, 1, 3, 7, 8]);
+
+        list.as_ref_mut().fill_range(1, 3, Value::I32(0)).unwrap();
+        assert_eq!(values(list), vec![2, 0, 0, 3, 7, 8]);
+        list.as_ref_mut().fill(Value::I32(5));
+        assert_eq!(values(list), vec![5; 6]);
+    }
+
+    #[test]
+    fn test_bulk_operations_are_bounds_checked() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+
+        let mut list = vm.new_list_with_capacity(4).unwrap();
+        list.as_ref_mut().extend(&[Value::I32(1); 4]).unwrap();
+        let mut other = vm.new_list_with_capacity(2).unwrap();
+        other.as_ref_mut().extend(&[Value::I32(2); 2]).unwrap();
+
+        let out_of_bounds = |result: Result<(), Error>| match result {
+            Err(Error::ListRangeOutOfBounds { start, end, len }) => (start, end, len),
+            _ => panic!("expected an out of bounds error"),
+        };
+        assert_eq!(out_of_bounds(vm.list_copy(list, other, 1, 0, 2)), (1, 3, 2));
+        assert_eq!(out_of_bounds(vm.list_copy(list, other, 0, 3, 2)), (3, 5, 4));
+        assert_eq!(
+            out_of_bounds(vm.list_copy(list, list, 1, 0, usize::MAX)),
+            (1, usize::MAX, 4)
+        );
+        assert_eq!(
+            out_of_bounds(list.as_ref_mut().fill_range(3, 1, Value::I32(0))),
+            (3, 1, 4)
+        );
+        assert_eq!(
+            out_of_bounds(list.as_ref_mut().fill_range(2, 5, Value::I32(0))),
+            (2, 5, 4)
+        );
+        // nothing was written by the failed operations.
+        assert_eq!(values(list), vec![1; 4]);
+    }
+
+    const PUSHES: usize = 100_000;
+
+    /// the buffer reallocations of pushing PUSHES elements, reserving room for them first.
+    fn push_reallocations(reserve: bool) -> usize {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut list = vm.new_list_with_capacity(0).unwrap();
+        vm.push_stack(Value::from(list));
+        if reserve {
+            vm.list_reserve(list, PUSHES as i64).unwrap();
+        }
+        let before = vm.heap_stats().buffer_reallocations;
+        for i in 0..PUSHES {
+            list.as_ref_mut().push(Value::I64(i as i64)).unwrap();
+        }
+        assert_eq!(list.len(), PUSHES);
+        vm.heap_stats().buffer_reallocations - before
+    }
+
+    #[test]
+    fn test_reserve_avoids_reallocations() {
+        assert!(push_reallocations(false) > 10);
+        assert_eq!(push_reallocations(true), 0);
+
+        let mut vm = Vm::new();
+        let list = vm.new_list_with_capacity(4).unwrap();
+        match vm.list_reserve(list, -1) {
+            Err(err @ Error::NegativeCapacity(-1)) => {
+                assert_eq!(err.to_string(), "cannot reserve a negative capacity of -1")
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(list.capacity(), 4);
+    }
+
+    #[test]
+    fn test_shrink_to_fit_returns_memory() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut list = vm.new_list_with_capacity(0).unwrap();
+        vm.push_stack(Value::from(list));
+        list.as_ref_mut().extend(&[Value::I32(1); 1000]).unwrap();
+
+        list.as_ref_mut().clear();
+        let before = vm.heap_stats();
+        list.as_ref_mut().shrink_to_fit();
+        let after = vm.heap_stats();
+        assert_eq!(list.capacity(), 0);
+        assert!(after.memory_usage < before.memory_usage);
+
+        // the list grows again from nothing.
+        list.as_ref_mut().extend(&[Value::I32(2); 3]).unwrap();
+        list.as_ref_mut().push(Value::I32(3)).unwrap();
+        assert_eq!(values(list), vec![2, 2, 2, 3]);
+
+        // shrinking a list that is not empty moves it to a smaller buffer.
+        list.as_ref_mut().reserve(1000).unwrap();
+        let before = vm.heap_stats();
+        list.as_ref_mut().shrink_to_fit();
+        let after = vm.heap_stats();
+        assert_eq!(after.buffer_reallocations, before.buffer_reallocations + 1);
+        assert!(after.memory_usage < before.memory_usage);
+        assert_eq!(values(list), vec![2, 2, 2, 3]);
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Display;
+use std::hash::Hasher;
+
+use itertools::Itertools;
+
+use crate::{
+    gc::{Object, ObjectKind, VecAllocator},
+    runtime, OxVec, Value,
+};
+
+/// the first buffer of a map holds this many slots, buffers always hold a power of two.
+const MIN_SLOTS: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Slot {
+    Empty,
+    /// a removed entry, lookups probe past it and inserts reuse it.
+    Deleted,
+    Full(Value, Value),
+}
+
+/// hash table of values keyed by values, keys are equal when they are the same constant. The
+/// slots are probed linearly and the buffer is replaced by one twice as large once three
+/// quarters of it are used, entries are in no particular order.
+#[derive(Debug, Clone)]
+pub struct OxMap {
+    slots: OxVec<Slot>,
+    len: usize,
+    deleted: usize,
+}
+
+fn hash(key: &Value) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash_constant(&mut hasher);
+    hasher.finish() as usize
+}
+
+impl OxMap {
+    pub fn new(allocator: VecAllocator) -> Self {
+        Self {
+            slots: OxVec::new(allocator),
+            len: 0,
+            deleted: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// the slots of the buffer, used or not.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub(crate) fn slots(&self) -> &OxVec<Slot> {
+        &self.slots
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.slots.iter().filter_map(|slot| match slot {
+            Slot::Full(key, value) => Some((key, value)),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn entries_mut(&mut self) -> impl Iterator<Item = (&mut Value, &mut Value)> {
+        self.slots.iter_mut().filter_map(|slot| match slot {
+            Slot::Full(key, value) => Some((key, value)),
+            _ => None,
+        })
+    }
+
+    pub fn get(&self, key: &Value) -> Option<Value> {
+        self.find(key).map(|index| match self.slots[index] {
+            Slot::Full(_, value) => value,
+            _ => unreachable!("a key was found in a slot that is not full"),
+        })
+    }
+
+    pub fn contains(&self, key: &Value) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// sets the value of key, the value it replaces is returned. The map is left as it was
+    /// when its buffer cannot be replaced by a larger one.
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<Option<Value>, runtime::Error> {
+        if let Some(index) = self.find(&key) {
+            return match std::mem::replace(&mut self.slots[index], Slot::Full(key, value)) {
+                Slot::Full(_, old) => Ok(Some(old)),
+                _ => unreachable!("a key was found in a slot that is not full"),
+            };
+        }
+
+        if (self.len + self.deleted + 1) * 4 > self.slots.len() * 3 {
+            self.rehash()?;
+        }
+        let index = self.free_slot(&key);
+        if let Slot::Deleted = self.slots[index] {
+            self.deleted -= 1;
+        }
+        self.slots[index] = Slot::Full(key, value);
+        self.len += 1;
+        Ok(None)
+    }
+
+    /// makes room for additional more keys, inserting them does not replace the buffer. The
+    /// buffer is replaced at most once.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), runtime::Error> {
+        let used = self.len + self.deleted + additional;
+        if used * 4 <= self.slots.len() * 3 {
+            return Ok(());
+        }
+        let slots = (self.len + additional) * 4 / 3 + 1;
+        self.resize(slots.next_power_of_two().max(MIN_SLOTS))
+    }
+
+    /// removes key, its value is returned.
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        let index = self.find(key)?;
+        match std::mem::replace(&mut self.slots[index], Slot::Deleted) {
+            Slot::Full(_, value) => {
+                self.len -= 1;
+                self.deleted += 1;
+                Some(value)
+            }
+            _ => unreachable!("a key was found in a slot that is not full"),
+        }
+    }
+
+    /// the slots key is looked up in, starting with the slot of its hash.
+    fn probe(&self, key: &Value) -> impl Iterator<Item = usize> {
+        let mask = self.slots.len().wrapping_sub(1);
+        let start = hash(key);
+        (0..self.slots.len()).map(move |offset| start.wrapping_add(offset) & mask)
+    }
+
+    fn find(&self, key: &Value) -> Option<usize> {
+        for index in self.probe(key) {
+            match &self.slots[index] {
+                Slot::Empty => return None,
+                Slot::Full(other, _) if other.is_same_constant(key) => return Some(index),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// the first slot key can be stored in, the key must not be in the map.
+    fn free_slot(&self, key: &Value) -> usize {
+        self.probe(key)
+            .find(|index| !matches!(self.slots[*index], Slot::Full(..)))
+            .expect("a map always has a free slot")
+    }
+
+    /// moves the entries to a new buffer from the allocator of the current one, it is twice as
+    /// large unless most of the used slots were removed entries.
+    fn rehash(&mut self) -> Result<(), runtime::Error> {
+        let slots = if self.len * 2 >= self.slots.len() {
+            (self.slots.len() * 2).max(MIN_SLOTS)
+        } else {
+            self.slots.len()
+        };
+        self.resize(slots)
+    }
+
+    /// moves the entries to a new buffer of slots from the allocator of the current one, the
+    /// removed entries are dropped.
+    fn resize(&mut self, slots: usize) -> Result<(), runtime::Error> {
+        let allocator = self.slots.allocator().clone();
+        let old = std::mem::replace(
+            &mut self.slots,
+            OxVec::try_fill_with_capacity(allocator, slots, Slot::Empty)?,
+        );
+        self.deleted = 0;
+        for slot in old.iter() {
+            if let Slot::Full(key, value) = slot {
+                let index = self.free_slot(key);
+                self.slots[index] = Slot::Full(*key, *value);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for OxMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{{{}}}",
+            self.entries()
+                .map(|(key, value)| format!("{}: {}", key, value))
+                .join(", ")
+        )
+    }
+}
+
+impl Object for OxMap {
+    fn object_kind() -> ObjectKind {
+        ObjectKind::Map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Value, Vm};
+
+    fn int(value: Option<Value>) -> Option<i64> {
+        value.map(|value| value.as_i64())
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut map = vm.new_map().unwrap();
+        let one = Value::from(vm.new_gc_string_from_str("one"));
+        // another string with the same contents is the same key.
+        let other_one = Value::from(vm.new_gc_string_from_str("one"));
+
+        assert!(map.is_empty());
+        assert!(map.get(&one).is_none());
+        assert_eq!(int(map.as_ref_mut().insert(one, Value::I64(1)).unwrap()), None);
+        assert_eq!(int(map.as_ref_mut().insert(Value::I32(1), Value::I64(2)).unwrap()), None);
+        assert_eq!(map.len(), 2);
+        assert_eq!(int(map.get(&other_one)), Some(1));
+        // keys of another type are other keys.
+        assert!(map.get(&Value::I64(1)).is_none());
+
+        let replaced = map.as_ref_mut().insert(other_one, Value::I64(3)).unwrap();
+        assert_eq!(int(replaced), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(int(map.as_ref_mut().remove(&one)), Some(3));
+        assert!(map.as_ref_mut().remove(&one).is_none());
+        assert!(!map.contains(&one));
+        assert!(map.contains(&Value::I32(1)));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.to_string(), "{1: 2}");
+    }
+
+    #[test]
+    fn test_growth_survives_collections() {
+        const KEYS: i64 = 500;
+        let mut vm = Vm::new();
+        let mut map = vm.new_map().unwrap();
+        vm.push_stack(Value::from(map));
+
+        let before = vm.heap_stats().memory_usage;
+        for i in 0..KEYS {
+            // allocating the key may collect, the map and its entries are kept alive.
+            let key = Value::from(vm.new_gc_string_from_str(&format!("key {}", i)));
+            vm.push_stack(key);
+            map.as_ref_mut().insert(key, Value::I64(i)).unwrap();
+            vm.pop();
+        }
+        assert_eq!(map.len(), KEYS as usize);
+        assert!(map.capacity().is_power_of_two());
+        assert!(map.capacity() * 3 >= map.len() * 4);
+        assert!(vm.heap_stats().memory_usage > before);
+
+        vm.gc_collect();
+        for i in (0..KEYS).step_by(7) {
+            let key = Value::from(vm.new_gc_string_from_str(&format!("key {}", i)));
+            assert_eq!(int(map.get(&key)), Some(i));
+        }
+    }
+
+    #[test]
+    fn test_removed_slots_are_reused() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut map = vm.new_map().unwrap();
+        for i in 0..4 {
+            map.as_ref_mut().insert(Value::I64(i), Value::Bool(true)).unwrap();
+        }
+        let capacity = map.capacity();
+
+        // churning through keys rehashes in place rather than growing.
+        for i in 4..10_000 {
+            map.as_ref_mut().remove(&Value::I64(i - 4));
+            map.as_ref_mut().insert(Value::I64(i), Value::Bool(true)).unwrap();
+        }
+        assert_eq!(map.capacity(), capacity);
+        assert_eq!(map.len(), 4);
+        assert!((9_996..10_000).all(|i| map.contains(&Value::I64(i))));
+        assert!(!map.contains(&Value::I64(9_995)));
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut map = vm.new_map().unwrap();
+        map.as_ref_mut().reserve(100).unwrap();
+        let capacity = map.capacity();
+        assert!(capacity.is_power_of_two());
+
+        // the reserved keys are inserted without replacing the buffer.
+        for i in 0..100 {
+            map.as_ref_mut().insert(Value::I64(i), Value::I64(i)).unwrap();
+        }
+        assert_eq!(map.capacity(), capacity);
+        map.as_ref_mut().reserve(0).unwrap();
+        assert_eq!(map.capacity(), capacity);
+        assert!((0..100).all(|i| map.contains(&Value::I64(i))));
+    }
+}
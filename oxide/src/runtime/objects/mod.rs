@@ -1,19 +1,27 @@
+mod closure;
 mod function;
 mod instance;
+mod list;
+mod map;
 mod module;
 mod section;
 mod string;
 mod structure;
 mod tuple;
+mod userdata;
 mod vec;
 
-pub use function::OxFunction;
+pub use closure::OxClosure;
+pub use function::{OxFunction, CLOSURE_NAME_PREFIX};
 pub use instance::OxInstance;
+pub use list::OxList;
+pub use map::OxMap;
 pub use module::OxModule;
-pub use section::Section;
+pub use section::{Section, SectionId, MAX_JUMP};
 pub use string::OxString;
 pub use structure::OxStruct;
 pub use tuple::OxTuple;
+pub use userdata::OxUserData;
 pub use vec::OxVec;
 
 pub trait AttributeAccess {
@@ -1,8 +1,9 @@
 use std::fmt::Display;
 
 use crate::{
-    gc::{Object, ObjectKind, VecAllocator},
-    OxString, OxVec, Value,
+    compiled::ModuleLayout,
+    gc::{Gc, Object, ObjectKind, VecAllocator},
+    OxString, OxStruct, OxVec, Value,
 };
 
 #[derive(Debug, Clone)]
@@ -10,6 +11,8 @@ pub struct OxModule {
     name: OxString,
     entry: Option<usize>,
     objects: OxVec<Value>,
+    /// only known for a module loaded from a compiled module.
+    layout: Option<Box<ModuleLayout>>,
 }
 
 impl OxModule {
@@ -18,6 +21,7 @@ impl OxModule {
             name,
             entry,
             objects,
+            layout: None,
         }
     }
 
@@ -57,6 +61,18 @@ impl OxModule {
         &self.objects
     }
 
+    /// the structure of the module named name, the instances of a structure are named after it.
+    pub fn structure(&self, name: &str) -> Option<Gc<OxStruct>> {
+        self.objects.iter().find_map(|object| match object {
+            Value::Struct(structure) if structure.name().as_str() == name => Some(*structure),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn objects_mut(&mut self) -> &mut OxVec<Value> {
+        &mut self.objects
+    }
+
     pub fn entry(&self) -> Option<&Value> {
         self.entry.map(|idx| self.objects.get(idx)).flatten()
     }
@@ -64,6 +80,14 @@ impl OxModule {
     pub fn set_entry(&mut self, entry: usize) {
         self.entry = Some(entry);
     }
+
+    pub fn layout(&self) -> Option<&ModuleLayout> {
+        self.layout.as_deref()
+    }
+
+    pub fn set_layout(&mut self, layout: ModuleLayout) {
+        self.layout = Some(Box::new(layout));
+    }
 }
 
 impl Display for OxModule {
@@ -74,6 +98,6 @@ impl Display for OxModule {
 
 impl Object for OxModule {
     fn object_kind() -> ObjectKind {
-        ObjectKind::String
+        ObjectKind::Module
     }
 }
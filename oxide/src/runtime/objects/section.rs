@@ -1,15 +1,24 @@
 use crate::{
     disassembler::Disassembler,
     gc::{Object, ObjectKind},
+    runtime,
+    source_map::{LineTable, LocalTable},
+    verifier::Verifier,
     vm::{Instruction, OpCode},
     OxVec, Value, Vm,
 };
 
 use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    convert::TryFrom,
     fmt::{Display, Formatter},
+    hash::Hasher,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
+/// the furthest a jump can move the instruction pointer, its operand is a u16.
+pub const MAX_JUMP: usize = u16::MAX as usize;
+
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct SectionId(usize);
 
@@ -33,10 +42,16 @@ pub struct Section {
     id: SectionId,
     /// constant values known at compile time
     constants: OxVec<Value>,
+    /// indices of the constants by `Value::hash_constant`, used to intern constants.
+    constant_index: HashMap<u64, Vec<u32>>,
     /// global values populated at compiler or just before runtime.
     globals: OxVec<Value>,
     /// raw opcode data
     data: OxVec<u8>,
+    /// embedded debug info, maps instruction offsets to source lines.
+    lines: LineTable,
+    /// embedded debug info, names of the local slots.
+    locals: LocalTable,
 }
 
 impl Section {
@@ -64,8 +79,11 @@ impl Section {
         Self {
             id: SectionId::next(),
             constants: vm.new_vec(),
+            constant_index: HashMap::new(),
             globals: vm.new_vec(),
             data: vm.new_vec(),
+            lines: LineTable::new(),
+            locals: LocalTable::new(),
         }
     }
 
@@ -101,6 +119,12 @@ impl Section {
         Disassembler::disassemble_section(self)
     }
 
+    /// checks that every instruction is well formed and only refers to constants, globals and
+    /// offsets that exist in the section.
+    pub fn verify(&self) -> Result<(), runtime::Error> {
+        Verifier::verify_section(self)
+    }
+
     pub fn data(&self) -> &[u8] {
         self.data.as_slice()
     }
@@ -124,21 +148,118 @@ impl Section {
     pub fn constants_ref(&self) -> &OxVec<Value> {
         &self.constants
     }
+
+    pub(crate) fn constants_mut(&mut self) -> &mut OxVec<Value> {
+        &mut self.constants
+    }
+
+    pub(crate) fn globals_mut(&mut self) -> &mut OxVec<Value> {
+        &mut self.globals
+    }
+
+    pub fn lines(&self) -> &LineTable {
+        &self.lines
+    }
+
+    /// the source line of the instruction at offset, if debug info is present.
+    pub fn line_for(&self, offset: usize) -> Option<usize> {
+        self.lines.line_for(offset)
+    }
+
+    /// marks the code written after this point as generated from the statement on line.
+    pub fn mark_line(&mut self, line: usize) {
+        let offset = self.len();
+        self.lines.push(offset, line);
+    }
+
+    /// marks the code written after this point as generated from a part of an expression on
+    /// line.
+    pub fn mark_expression_line(&mut self, line: usize) {
+        let offset = self.len();
+        self.lines.push_expression(offset, line);
+    }
+
+    pub fn set_lines(&mut self, lines: LineTable) {
+        self.lines = lines;
+    }
+
+    pub fn locals(&self) -> &LocalTable {
+        &self.locals
+    }
+
+    /// names the local slot for the instructions written from here on.
+    pub fn name_local(&mut self, slot: usize, name: &str) {
+        let offset = self.len();
+        self.locals.push(offset, slot, name.to_string());
+    }
+
+    pub fn set_locals(&mut self, locals: LocalTable) {
+        self.locals = locals;
+    }
+
+    pub fn strip_debug_info(&mut self) {
+        self.lines.clear();
+        self.locals.clear();
+    }
 }
 
 impl Section {
     /// adds constant value to constants block
-    pub fn add_constant(&mut self, value: Value) -> u8 {
-        let index = self.constants.len();
+    pub fn add_constant(&mut self, value: Value) -> u32 {
+        let index = Self::index(self.constants.len(), "constants");
+        self.constant_index
+            .entry(Self::constant_hash(&value))
+            .or_default()
+            .push(index);
         self.constants.push(value);
-        index as _
+        index
+    }
+
+    /// adds constant value to constants block unless an equal constant already exists.
+    pub fn intern_constant(&mut self, value: Value) -> u32 {
+        let existing = self
+            .constant_index
+            .get(&Self::constant_hash(&value))
+            .and_then(|indices| {
+                indices
+                    .iter()
+                    .find(|index| self.constants[**index as usize].is_same_constant(&value))
+            });
+        match existing {
+            Some(index) => *index,
+            None => self.add_constant(value),
+        }
+    }
+
+    fn constant_hash(value: &Value) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash_constant(&mut hasher);
+        hasher.finish()
     }
 
-    /// allocates a new global, sets it to unit
-    pub fn add_global(&mut self) -> u8 {
-        let index = self.globals.len();
-        self.globals.push(Value::Unit);
-        index as _
+    /// allocates a new global, it is undefined until it is set.
+    pub fn add_global(&mut self) -> u32 {
+        let index = Self::index(self.globals.len(), "globals");
+        self.globals.push(Value::Undefined);
+        index
+    }
+
+    fn index(len: usize, table: &str) -> u32 {
+        u32::try_from(len)
+            .unwrap_or_else(|_| panic!("a section can not have more than {} {}", u32::MAX, table))
+    }
+
+    /// writes an instruction whose operand is the index of a constant, global or local. An index
+    /// that does not fit in a byte is written as a u32 after the wide prefix.
+    pub fn write_index(&mut self, op: OpCode, index: u32) {
+        match u8::try_from(index) {
+            Ok(index) => self.write_arg(op, index),
+            Err(_) => {
+                self.write_op(OpCode::Wide);
+                self.write_op(op);
+                self.write_bytes(&index.to_be_bytes());
+            }
+        }
     }
 
     pub fn write_byte(&mut self, byte: u8) {
@@ -165,15 +286,27 @@ impl Section {
         self.len() - 2
     }
 
+    /// distance from the jump operand at offset to the end of the section.
+    pub fn jump_distance(&self, offset: usize) -> usize {
+        self.len() - offset - 2
+    }
+
     pub fn patch_jmp(&mut self, offset: usize) {
-        let jump: u16 = (self.len() - offset - 2) as _;
+        let jump = u16::try_from(self.jump_distance(offset))
+            .unwrap_or_else(|_| panic!("jump at {} is further than {} bytes", offset, MAX_JUMP));
         self.data[offset] = ((jump >> 8) & 0xff) as u8;
         self.data[offset + 1] = (jump & 0xff) as u8;
     }
 
+    /// distance a loop written next jumps back to reach start.
+    pub fn loop_distance(&self, start: usize) -> usize {
+        self.len() + 3 - start
+    }
+
     pub fn write_loop(&mut self, start: usize) {
+        let offset = u16::try_from(self.loop_distance(start))
+            .unwrap_or_else(|_| panic!("loop to {} is further than {} bytes", start, MAX_JUMP));
         self.write_op(OpCode::Loop);
-        let offset = (self.len() - start + 2) as u16;
         self.write_bytes(&offset.to_be_bytes());
     }
 }
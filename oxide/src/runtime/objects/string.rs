@@ -2,49 +2,122 @@ use std::fmt::Display;
 
 use crate::{
     gc::{Object, ObjectKind, VecAllocator},
-    OxVec,
+    runtime, OxVec,
 };
 
+/// strings of at most this many bytes are stored in the string object itself.
+pub const INLINE_CAPACITY: usize = 16;
+
+#[derive(Debug, Clone)]
+enum Repr {
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_CAPACITY],
+    },
+    Heap(OxVec<u8>),
+}
+
+/// a short string keeps its bytes inline, it is moved to a buffer of the heap once it grows past
+/// `INLINE_CAPACITY`.
 #[derive(Debug, Clone)]
 pub struct OxString {
-    buffer: OxVec<u8>,
+    repr: Repr,
+    allocator: VecAllocator,
 }
 
 impl OxString {
     pub fn new(allocator: VecAllocator) -> Self {
         Self {
-            buffer: OxVec::new(allocator),
+            repr: Repr::Inline {
+                len: 0,
+                bytes: [0; INLINE_CAPACITY],
+            },
+            allocator,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.repr {
+            Repr::Inline { len, bytes } => &bytes[..*len as usize],
+            Repr::Heap(buffer) => buffer.as_slice(),
         }
     }
 
     pub fn as_str(&self) -> &str {
-        unsafe { std::str::from_utf8_unchecked(self.buffer.as_slice()) }
+        unsafe { std::str::from_utf8_unchecked(self.as_bytes()) }
     }
 
     pub fn len(&self) -> usize {
-        self.buffer.len()
+        self.as_bytes().len()
     }
 
-    pub fn buffer(&self) -> &OxVec<u8> {
-        &self.buffer
+    /// the buffer of the heap holding the string, none when it is inline.
+    pub fn buffer(&self) -> Option<&OxVec<u8>> {
+        match &self.repr {
+            Repr::Inline { .. } => None,
+            Repr::Heap(buffer) => Some(buffer),
+        }
+    }
+
+    pub fn is_inline(&self) -> bool {
+        matches!(self.repr, Repr::Inline { .. })
     }
 
     pub fn capacity(&self) -> usize {
-        self.buffer.capacity()
+        match &self.repr {
+            Repr::Inline { .. } => INLINE_CAPACITY,
+            Repr::Heap(buffer) => buffer.capacity(),
+        }
     }
 
+    /// a string of the host, running out of memory panics.
     pub fn with_value(allocator: VecAllocator, value: &str) -> Self {
-        let mut val = Self {
-            buffer: OxVec::new(allocator),
-        };
+        Self::try_with_value(allocator, value).expect("out of memory allocating a string")
+    }
 
-        val.push_bytes(value.as_bytes());
+    pub fn try_with_value(allocator: VecAllocator, value: &str) -> Result<Self, runtime::Error> {
+        let mut val = Self::new(allocator);
+        val.push_bytes(value.as_bytes())?;
+        Ok(val)
+    }
 
-        val
+    /// makes room for at least additional more bytes, a string that would not fit inline
+    /// anymore is moved to a buffer of the heap.
+    pub fn reserve(&mut self, additional: usize) -> Result<(), runtime::Error> {
+        match &mut self.repr {
+            Repr::Inline { len, .. } if *len as usize + additional <= INLINE_CAPACITY => {}
+            Repr::Inline { len, bytes } => {
+                let capacity = *len as usize + additional;
+                let mut buffer = OxVec::try_with_capacity(self.allocator.clone(), capacity)?;
+                buffer.extend_from_slice(&bytes[..*len as usize]);
+                self.repr = Repr::Heap(buffer);
+            }
+            Repr::Heap(buffer) => buffer.try_reserve(additional)?,
+        }
+        Ok(())
     }
 
-    pub fn push_bytes(&mut self, value: &[u8]) {
-        self.buffer.extend_from_slice(value)
+    /// appends the bytes, the string is left as it was when its buffer cannot grow.
+    pub fn push_bytes(&mut self, value: &[u8]) -> Result<(), runtime::Error> {
+        match &mut self.repr {
+            Repr::Inline { len, bytes } if *len as usize + value.len() <= INLINE_CAPACITY => {
+                let start = *len as usize;
+                bytes[start..start + value.len()].copy_from_slice(value);
+                *len += value.len() as u8;
+            }
+            Repr::Inline { len, bytes } => {
+                let capacity = *len as usize + value.len();
+                let mut buffer = OxVec::try_with_capacity(self.allocator.clone(), capacity)?;
+                buffer.extend_from_slice(&bytes[..*len as usize]);
+                buffer.extend_from_slice(value);
+                self.repr = Repr::Heap(buffer);
+            }
+            Repr::Heap(buffer) => {
+                buffer.try_reserve(value.len())?;
+                buffer.extend_from_slice(value);
+            }
+        }
+        Ok(())
     }
 
     pub fn disassemble(&self, indent: usize) {
@@ -67,3 +140,39 @@ impl Object for OxString {
         ObjectKind::String
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::INLINE_CAPACITY;
+    use crate::Vm;
+
+    #[test]
+    fn test_short_strings_are_inline() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+
+        let mut used = vec![];
+        for len in [15, 16, 17] {
+            let text = "x".repeat(len);
+            let start = vm.memory_usage();
+            let string = vm.new_gc_string_from_str(text.as_str());
+            assert_eq!(string.as_str(), text);
+            assert_eq!(string.len(), len);
+            assert_eq!(string.is_inline(), len <= INLINE_CAPACITY, "{}", len);
+            assert_eq!(string.buffer().is_none(), len <= INLINE_CAPACITY);
+            used.push(vm.memory_usage() - start);
+        }
+        // an inline string is only the object, a longer one allocates a buffer as well.
+        assert_eq!(used[0], used[1]);
+        assert!(used[2] > used[1]);
+
+        // growing past the inline capacity moves the bytes to the heap.
+        let mut string = vm.new_string_from_str("0123456789abcde");
+        string.push_bytes(b"f").unwrap();
+        assert!(string.is_inline());
+        string.push_bytes(b"g").unwrap();
+        assert!(!string.is_inline());
+        assert_eq!(string.as_str(), "0123456789abcdefg");
+        assert_eq!(string.as_bytes().len(), 17);
+    }
+}
@@ -9,17 +9,40 @@ use crate::{
 pub struct OxStruct {
     name: OxString,
     methods: OxVec<Gc<OxFunction>>,
+    /// the public fields of its instances and the slot of each, `get_field` looks them up by
+    /// name.
+    fields: Vec<(String, usize)>,
 }
 
 impl OxStruct {
     pub fn new(name: OxString, methods: OxVec<Gc<OxFunction>>) -> Self {
-        Self { name, methods }
+        Self {
+            name,
+            methods,
+            fields: vec![],
+        }
     }
 
     pub fn name(&self) -> &OxString {
         &self.name
     }
 
+    pub fn fields(&self) -> &[(String, usize)] {
+        self.fields.as_slice()
+    }
+
+    pub fn set_fields(&mut self, fields: Vec<(String, usize)>) {
+        self.fields = fields;
+    }
+
+    /// the slot of the public field named name.
+    pub fn field_slot(&self, name: &str) -> Option<usize> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, slot)| *slot)
+    }
+
     pub fn methods(&self) -> &OxVec<Gc<OxFunction>> {
         &self.methods
     }
@@ -49,7 +72,7 @@ impl Display for OxStruct {
 
 impl Object for OxStruct {
     fn object_kind() -> ObjectKind {
-        ObjectKind::String
+        ObjectKind::Struct
     }
 }
 
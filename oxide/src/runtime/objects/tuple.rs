@@ -21,6 +21,10 @@ impl OxTuple {
         &self.elements
     }
 
+    pub(crate) fn elements_mut(&mut self) -> &mut OxVec<Value> {
+        &mut self.elements
+    }
+
     pub fn disassemble(&self, indent: usize) {
         println!(
             "{}<tuple {}>",
@@ -42,7 +46,7 @@ impl Display for OxTuple {
 
 impl Object for OxTuple {
     fn object_kind() -> ObjectKind {
-        ObjectKind::String
+        ObjectKind::Tuple
     }
 }
 
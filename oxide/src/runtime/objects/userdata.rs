@@ -0,0 +1,37 @@
+use std::fmt::Display;
+
+use crate::gc::{Object, ObjectKind};
+
+/// an opaque value owned by the embedder, e.g. a file descriptor or socket id.
+/// the vm is only responsible for its lifetime, the handle is interpreted by the host.
+#[derive(Debug, Clone)]
+pub struct OxUserData {
+    type_id: usize,
+    handle: u64,
+}
+
+impl OxUserData {
+    pub fn new(type_id: usize, handle: u64) -> Self {
+        Self { type_id, handle }
+    }
+
+    pub fn type_id(&self) -> usize {
+        self.type_id
+    }
+
+    pub fn handle(&self) -> u64 {
+        self.handle
+    }
+}
+
+impl Display for OxUserData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<userdata {}:{}>", self.type_id, self.handle)
+    }
+}
+
+impl Object for OxUserData {
+    fn object_kind() -> ObjectKind {
+        ObjectKind::UserData
+    }
+}
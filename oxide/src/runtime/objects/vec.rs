@@ -5,6 +5,7 @@ use std::{
 type VecInner<Ty> = VecInnerImpl<Ty, VecAllocator>;
 
 use crate::gc::{Address, Object, ObjectKind, VecAllocator};
+use crate::runtime;
 
 #[derive(Debug, Clone)]
 pub struct OxVec<Ty> {
@@ -24,6 +25,13 @@ impl<Ty> OxVec<Ty> {
         }
     }
 
+    /// with_capacity, failing instead of aborting when the buffer cannot be allocated.
+    pub fn try_with_capacity(allocator: VecAllocator, len: usize) -> Result<Self, runtime::Error> {
+        let mut inner = VecInner::new_in(allocator);
+        inner.try_reserve_exact(len)?;
+        Ok(Self { inner })
+    }
+
     pub fn ptr(&self) -> Address {
         Address::from_ptr(self.inner.as_ptr() as *const u8)
     }
@@ -35,6 +43,16 @@ impl<Ty: Clone> OxVec<Ty> {
         vec.inner.resize(len, value);
         vec
     }
+
+    pub fn try_fill_with_capacity(
+        allocator: VecAllocator,
+        len: usize,
+        value: Ty,
+    ) -> Result<Self, runtime::Error> {
+        let mut vec = Self::try_with_capacity(allocator, len)?;
+        vec.inner.resize(len, value);
+        Ok(vec)
+    }
 }
 
 impl<Ty> Deref for OxVec<Ty> {
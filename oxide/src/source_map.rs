@@ -0,0 +1,799 @@
+use std::fmt::Write;
+
+/// current version of the source map format.
+pub const SOURCE_MAP_VERSION: u64 = 2;
+
+/// the line runs of a version before it have no statement flag, each is a statement start.
+const STATEMENT_FLAGS_VERSION: u64 = 2;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum SourceMapError {
+    #[error("invalid source map at offset {0}: {1}")]
+    InvalidFormat(usize, String),
+
+    #[error("missing field '{0}' in source map")]
+    MissingField(&'static str),
+
+    #[error("unsupported source map version {0}, expected {}", SOURCE_MAP_VERSION)]
+    UnsupportedVersion(u64),
+}
+
+/// a run of instructions starting at `offset` generated from `line`.
+/// the run ends at the offset of the next entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRun {
+    pub offset: usize,
+    pub line: usize,
+    /// the run starts a statement, stepping over stops only at these. A run that does not is
+    /// a part of an expression on a line of its own, stepping into stops at every run.
+    pub is_statement_start: bool,
+}
+
+/// how far a debugger steps, to the next statement or to the next part of an expression on a
+/// line of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Over,
+    Into,
+}
+
+/// run length encoded instruction offset to source line table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineTable {
+    runs: Vec<LineRun>,
+}
+
+impl LineTable {
+    pub fn new() -> Self {
+        Self { runs: vec![] }
+    }
+
+    /// marks that the instructions starting at offset were generated from the statement
+    /// starting on line.
+    pub fn push(&mut self, offset: usize, line: usize) {
+        self.push_run(offset, line, true)
+    }
+
+    /// marks that the instructions starting at offset were generated from a part of an
+    /// expression on line.
+    pub fn push_expression(&mut self, offset: usize, line: usize) {
+        self.push_run(offset, line, false)
+    }
+
+    /// adjacent runs of the same line are one run. A run without instructions is replaced by
+    /// the next one, unless it starts a statement that the next one does not, the line of the
+    /// statement stays mapped to where its expression starts.
+    fn push_run(&mut self, offset: usize, line: usize, is_statement_start: bool) {
+        match self.runs.last_mut() {
+            Some(last) if last.line == line => {
+                if last.offset == offset {
+                    last.is_statement_start |= is_statement_start;
+                }
+            }
+            Some(last)
+                if last.offset == offset && (is_statement_start || !last.is_statement_start) =>
+            {
+                last.line = line;
+                last.is_statement_start = is_statement_start;
+            }
+            _ => self.runs.push(LineRun {
+                offset,
+                line,
+                is_statement_start,
+            }),
+        }
+    }
+
+    /// the line of the last run, the line the next instructions are generated from.
+    pub fn current_line(&self) -> Option<usize> {
+        self.runs.last().map(|run| run.line)
+    }
+
+    pub fn line_for(&self, offset: usize) -> Option<usize> {
+        self.runs
+            .iter()
+            .take_while(|run| run.offset <= offset)
+            .last()
+            .map(|run| run.line)
+    }
+
+    pub fn runs(&self) -> &[LineRun] {
+        self.runs.as_slice()
+    }
+
+    /// the lines that generated instructions, in order.
+    pub fn mapped_lines(&self) -> Vec<usize> {
+        let mut lines = self.runs.iter().map(|run| run.line).collect::<Vec<_>>();
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+    }
+
+    /// the first instruction of line, that of the statement starting on it when there is one.
+    pub fn offset_of(&self, line: usize) -> Option<usize> {
+        let mut runs = self.runs.iter().filter(|run| run.line == line);
+        let first = runs.clone().next()?;
+        Some(runs.find(|run| run.is_statement_start).unwrap_or(first).offset)
+    }
+
+    /// whether a step stops at the instruction at offset, it starts a statement for a step
+    /// over and any run for a step into.
+    pub fn stops_at(&self, offset: usize, step: Step) -> bool {
+        let mut runs = self.runs.iter().filter(|run| run.offset == offset);
+        match step {
+            Step::Over => runs.any(|run| run.is_statement_start),
+            Step::Into => runs.next().is_some(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.runs.clear()
+    }
+}
+
+/// a named local slot of a function, the name applies from `offset` until the slot is reused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalEntry {
+    pub offset: usize,
+    pub slot: usize,
+    pub name: String,
+}
+
+/// names of the local slots of a function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalTable {
+    entries: Vec<LocalEntry>,
+}
+
+impl LocalTable {
+    pub fn new() -> Self {
+        Self { entries: vec![] }
+    }
+
+    pub fn push(&mut self, offset: usize, slot: usize, name: String) {
+        self.entries.push(LocalEntry { offset, slot, name });
+    }
+
+    /// the name of slot at the instruction offset.
+    pub fn name_for(&self, slot: usize, offset: usize) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.slot == slot && entry.offset <= offset)
+            .map(|entry| entry.name.as_str())
+    }
+
+    pub fn entries(&self) -> &[LocalEntry] {
+        self.entries.as_slice()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    pub path: String,
+    /// fnv-1a hash of the file content, used to detect a stale map.
+    pub hash: u64,
+}
+
+impl SourceFile {
+    pub fn new(path: String, content: &str) -> Self {
+        Self {
+            path,
+            hash: content_hash(content),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionMap {
+    /// mangled name of the function, associated functions are prefixed with their struct.
+    pub name: String,
+    /// index of the file in the source map.
+    pub file: usize,
+    /// byte span of the declaration
+    pub span: (usize, usize),
+    pub lines: LineTable,
+}
+
+impl FunctionMap {
+    /// the unmangled name, as it is known by the vm.
+    pub fn short_name(&self) -> &str {
+        self.name.rsplit('.').next().unwrap_or(self.name.as_str())
+    }
+}
+
+/// a breakpoint bound to an instruction of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub function: String,
+    /// the line the breakpoint was set on.
+    pub requested: usize,
+    /// the line it stops on, the first line with code from the line requested.
+    pub line: usize,
+    pub offset: usize,
+}
+
+impl Breakpoint {
+    pub fn is_moved(&self) -> bool {
+        self.requested != self.line
+    }
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_moved() {
+            write!(
+                f,
+                "breakpoint on line {} moved to line {} of {}, line {} has no code",
+                self.requested, self.line, self.function, self.requested
+            )
+        } else {
+            write!(f, "breakpoint on line {} of {}", self.line, self.function)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    pub version: u64,
+    pub files: Vec<SourceFile>,
+    pub functions: Vec<FunctionMap>,
+}
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self {
+            version: SOURCE_MAP_VERSION,
+            files: vec![],
+            functions: vec![],
+        }
+    }
+
+    pub fn add_file(&mut self, file: SourceFile) -> usize {
+        if let Some(idx) = self.files.iter().position(|f| f.path == file.path) {
+            idx
+        } else {
+            self.files.push(file);
+            self.files.len() - 1
+        }
+    }
+
+    pub fn add_function(&mut self, function: FunctionMap) {
+        self.functions.push(function);
+    }
+
+    /// finds a function by its mangled name, falling back to the unmangled name.
+    pub fn function(&self, name: &str) -> Option<&FunctionMap> {
+        self.functions
+            .iter()
+            .find(|f| f.name == name)
+            .or_else(|| self.functions.iter().find(|f| f.short_name() == name))
+    }
+
+    pub fn file(&self, idx: usize) -> Option<&SourceFile> {
+        self.files.get(idx)
+    }
+
+    /// binds a breakpoint on line of file to the first instruction of the line, a line without
+    /// code snaps forward to the next line with code of the same function. The function is the
+    /// innermost one whose lines surround line, a line outside every function is not bound.
+    pub fn bind_breakpoint(&self, file: usize, line: usize) -> Option<Breakpoint> {
+        let function = self
+            .functions
+            .iter()
+            .filter(|function| function.file == file)
+            .filter_map(|function| {
+                let lines = function.lines.mapped_lines();
+                let (first, last) = (*lines.first()?, *lines.last()?);
+                (first <= line && line <= last).then_some((last - first, function, lines))
+            })
+            .min_by_key(|(extent, _, _)| *extent);
+        let (_, function, lines) = function?;
+        let bound = lines.into_iter().find(|mapped| *mapped >= line)?;
+        Some(Breakpoint {
+            function: function.name.clone(),
+            requested: line,
+            line: bound,
+            offset: function.lines.offset_of(bound)?,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "{{").unwrap();
+        writeln!(out, "  \"version\": {},", self.version).unwrap();
+
+        writeln!(out, "  \"files\": [").unwrap();
+        for (idx, file) in self.files.iter().enumerate() {
+            write!(
+                out,
+                "    {{\"path\": {}, \"hash\": \"{:016x}\"}}",
+                escape(file.path.as_str()),
+                file.hash
+            )
+            .unwrap();
+            writeln!(out, "{}", if idx + 1 < self.files.len() { "," } else { "" }).unwrap();
+        }
+        writeln!(out, "  ],").unwrap();
+
+        writeln!(out, "  \"functions\": [").unwrap();
+        for (idx, function) in self.functions.iter().enumerate() {
+            let lines = function
+                .lines
+                .runs()
+                .iter()
+                .map(|run| {
+                    let flag = run.is_statement_start as u8;
+                    format!("[{}, {}, {}]", run.offset, run.line, flag)
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(
+                out,
+                "    {{\"name\": {}, \"file\": {}, \"span\": [{}, {}], \"lines\": [{}]}}",
+                escape(function.name.as_str()),
+                function.file,
+                function.span.0,
+                function.span.1,
+                lines
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "{}",
+                if idx + 1 < self.functions.len() {
+                    ","
+                } else {
+                    ""
+                }
+            )
+            .unwrap();
+        }
+        writeln!(out, "  ]").unwrap();
+        write!(out, "}}").unwrap();
+        out
+    }
+
+    pub fn from_json(input: &str) -> Result<Self, SourceMapError> {
+        let mut reader = JsonReader::new(input);
+        let value = reader.parse_value()?;
+        reader.skip_whitespace();
+        if !reader.at_end() {
+            return Err(reader.error("trailing characters"));
+        }
+
+        let version = value.field("version")?.as_u64("version")?;
+        if version == 0 || version > SOURCE_MAP_VERSION {
+            return Err(SourceMapError::UnsupportedVersion(version));
+        }
+
+        let mut map = Self::new();
+        for file in value.field("files")?.as_array("files")? {
+            let path = file.field("path")?.as_str("path")?.to_owned();
+            let hash = file.field("hash")?.as_str("hash")?;
+            let hash = u64::from_str_radix(hash, 16).map_err(|_| {
+                SourceMapError::InvalidFormat(0, format!("invalid hash '{}'", hash))
+            })?;
+            map.files.push(SourceFile { path, hash });
+        }
+
+        for function in value.field("functions")?.as_array("functions")? {
+            let name = function.field("name")?.as_str("name")?.to_owned();
+            let file = function.field("file")?.as_u64("file")? as usize;
+            let span = function.field("span")?.as_pair("span")?;
+            let mut lines = LineTable::new();
+            for run in function.field("lines")?.as_array("lines")? {
+                let run = if version < STATEMENT_FLAGS_VERSION {
+                    let (offset, line) = run.as_pair("lines")?;
+                    LineRun {
+                        offset,
+                        line,
+                        is_statement_start: true,
+                    }
+                } else {
+                    match run.as_array("lines")? {
+                        [offset, line, flag] => LineRun {
+                            offset: offset.as_u64("lines")? as usize,
+                            line: line.as_u64("lines")? as usize,
+                            is_statement_start: flag.as_u64("lines")? != 0,
+                        },
+                        _ => {
+                            return Err(SourceMapError::InvalidFormat(
+                                0,
+                                "'lines' must be triples".to_string(),
+                            ))
+                        }
+                    }
+                };
+                lines.runs.push(run);
+            }
+
+            map.functions.push(FunctionMap {
+                name,
+                file,
+                span,
+                lines,
+            });
+        }
+
+        Ok(map)
+    }
+}
+
+/// fnv-1a, stable across builds unlike the std hasher.
+pub fn content_hash(content: &str) -> u64 {
+    content.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            ch if (ch as u32) < 0x20 => write!(out, "\\u{:04x}", ch as u32).unwrap(),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// subset of json used by the source map, numbers are unsigned integers only.
+#[derive(Debug, Clone)]
+enum Json {
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn field(&self, name: &'static str) -> Result<&Json, SourceMapError> {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value)
+                .ok_or(SourceMapError::MissingField(name)),
+            _ => Err(SourceMapError::MissingField(name)),
+        }
+    }
+
+    fn as_u64(&self, name: &'static str) -> Result<u64, SourceMapError> {
+        match self {
+            Self::Number(val) => Ok(*val),
+            _ => Err(SourceMapError::InvalidFormat(
+                0,
+                format!("'{}' must be a number", name),
+            )),
+        }
+    }
+
+    fn as_str(&self, name: &'static str) -> Result<&str, SourceMapError> {
+        match self {
+            Self::String(val) => Ok(val.as_str()),
+            _ => Err(SourceMapError::InvalidFormat(
+                0,
+                format!("'{}' must be a string", name),
+            )),
+        }
+    }
+
+    fn as_array(&self, name: &'static str) -> Result<&[Json], SourceMapError> {
+        match self {
+            Self::Array(val) => Ok(val.as_slice()),
+            _ => Err(SourceMapError::InvalidFormat(
+                0,
+                format!("'{}' must be an array", name),
+            )),
+        }
+    }
+
+    fn as_pair(&self, name: &'static str) -> Result<(usize, usize), SourceMapError> {
+        match self.as_array(name)? {
+            [first, second] => Ok((first.as_u64(name)? as usize, second.as_u64(name)? as usize)),
+            _ => Err(SourceMapError::InvalidFormat(
+                0,
+                format!("'{}' must be a pair", name),
+            )),
+        }
+    }
+}
+
+struct JsonReader<'a> {
+    input: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> JsonReader<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input: input.as_bytes(),
+            offset: 0,
+        }
+    }
+
+    fn error(&self, msg: &str) -> SourceMapError {
+        SourceMapError::InvalidFormat(self.offset, msg.to_owned())
+    }
+
+    fn at_end(&self) -> bool {
+        self.offset >= self.input.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\n') | Some(b'\t') | Some(b'\r') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), SourceMapError> {
+        self.skip_whitespace();
+        if self.peek() == Some(byte) {
+            self.offset += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", byte as char).as_str()))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, SourceMapError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => self.parse_string().map(Json::String),
+            Some(b'0'..=b'9') => self.parse_number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, SourceMapError> {
+        self.expect(b'{')?;
+        let mut fields = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+            return Ok(Json::Object(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.expect(b':')?;
+            let value = self.parse_value()?;
+            fields.push((key, value));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b'}') => {
+                    self.offset += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, SourceMapError> {
+        self.expect(b'[')?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+            return Ok(Json::Array(elements));
+        }
+
+        loop {
+            elements.push(self.parse_value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b']') => {
+                    self.offset += 1;
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, SourceMapError> {
+        let start = self.offset;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.offset += 1;
+        }
+
+        std::str::from_utf8(&self.input[start..self.offset])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn parse_string(&mut self) -> Result<String, SourceMapError> {
+        self.expect(b'"')?;
+        let mut bytes = vec![];
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.offset += 1;
+                    break;
+                }
+                Some(b'\\') => {
+                    self.offset += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => b'"',
+                        Some(b'\\') => b'\\',
+                        Some(b'/') => b'/',
+                        Some(b'n') => b'\n',
+                        Some(b't') => b'\t',
+                        Some(b'r') => b'\r',
+                        Some(b'u') => {
+                            let digits = self
+                                .input
+                                .get(self.offset + 1..self.offset + 5)
+                                .and_then(|d| std::str::from_utf8(d).ok())
+                                .and_then(|d| u32::from_str_radix(d, 16).ok())
+                                .and_then(std::char::from_u32)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            let mut buf = [0; 4];
+                            bytes.extend_from_slice(digits.encode_utf8(&mut buf).as_bytes());
+                            self.offset += 5;
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    bytes.push(escaped);
+                    self.offset += 1;
+                }
+                Some(byte) => {
+                    bytes.push(byte);
+                    self.offset += 1;
+                }
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("invalid utf8 in string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SourceMap {
+        let mut map = SourceMap::new();
+        let file = map.add_file(SourceFile::new(
+            "test/vm/\"quoted\".au".to_owned(),
+            "fn main() {}",
+        ));
+
+        let mut lines = LineTable::new();
+        lines.push(0, 1);
+        lines.push(3, 1);
+        lines.push(5, 2);
+        lines.push(9, 4);
+
+        map.add_function(FunctionMap {
+            name: "Point.length".to_owned(),
+            file,
+            span: (10, 42),
+            lines,
+        });
+        map.add_function(FunctionMap {
+            name: "main".to_owned(),
+            file,
+            span: (0, 12),
+            lines: LineTable::new(),
+        });
+        map
+    }
+
+    #[test]
+    fn test_line_table_run_length() {
+        let map = sample();
+        let lines = &map.functions[0].lines;
+        assert_eq!(lines.runs().len(), 3);
+        assert_eq!(lines.line_for(0), Some(1));
+        assert_eq!(lines.line_for(4), Some(1));
+        assert_eq!(lines.line_for(5), Some(2));
+        assert_eq!(lines.line_for(100), Some(4));
+    }
+
+    #[test]
+    fn test_expression_runs() {
+        let mut lines = LineTable::new();
+        lines.push(0, 1);
+        // a statement whose expression starts on the next line keeps its run.
+        lines.push_expression(0, 2);
+        lines.push_expression(4, 1);
+        // a run without instructions is replaced, a statement replaces an expression.
+        lines.push_expression(6, 3);
+        lines.push(6, 4);
+        lines.push_expression(8, 4);
+        let runs = lines
+            .runs()
+            .iter()
+            .map(|run| (run.offset, run.line, run.is_statement_start))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            vec![(0, 1, true), (0, 2, false), (4, 1, false), (6, 4, true)]
+        );
+        assert_eq!(lines.mapped_lines(), vec![1, 2, 4]);
+        assert_eq!(lines.offset_of(1), Some(0));
+        assert_eq!(lines.line_for(0), Some(2));
+        assert!(lines.stops_at(0, Step::Over));
+        assert!(lines.stops_at(4, Step::Into));
+        assert!(!lines.stops_at(4, Step::Over));
+    }
+
+    #[test]
+    fn test_version_1_runs_start_statements() {
+        let json = sample()
+            .to_json()
+            .replace("\"version\": 2", "\"version\": 1")
+            .replace(", 1]", "]");
+        let map = SourceMap::from_json(json.as_str()).unwrap();
+        assert_eq!(map, sample());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let map = sample();
+        let json = map.to_json();
+        assert_eq!(SourceMap::from_json(json.as_str()), Ok(map));
+    }
+
+    #[test]
+    fn test_lookup_by_short_name() {
+        let map = sample();
+        assert_eq!(map.function("length").unwrap().name, "Point.length");
+        assert_eq!(map.function("main").unwrap().span, (0, 12));
+        assert!(map.function("missing").is_none());
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let json = sample()
+            .to_json()
+            .replace("\"version\": 2", "\"version\": 99");
+        assert_eq!(
+            SourceMap::from_json(json.as_str()),
+            Err(SourceMapError::UnsupportedVersion(99))
+        );
+    }
+}
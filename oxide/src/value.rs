@@ -1,9 +1,12 @@
 use crate::{
+    float,
     gc::{Address, Gc},
-    runtime::{OxFunction, OxString, OxStruct, OxTuple},
+    runtime::{OxClosure, OxFunction, OxList, OxMap, OxString, OxStruct, OxTuple, OxUserData},
+    vm::WeakHandle,
     OxInstance, OxModule,
 };
 use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Value {
@@ -25,7 +28,18 @@ pub enum Value {
     Instance(Gc<OxInstance>),
     Module(Gc<OxModule>),
     Tuple(Gc<OxTuple>),
+    UserData(Gc<OxUserData>),
+    List(Gc<OxList>),
+    Map(Gc<OxMap>),
+    Closure(Gc<OxClosure>),
+    /// handle into the vm's weak reference table, does not keep the referent alive.
+    Weak(WeakHandle),
+    /// index of a host function registered with the vm.
+    Native(usize),
     Unit,
+    /// a slot of the stack or a global that has not been written yet. Scripts can not create
+    /// it, reading it is a bug of the code rather than a value.
+    Undefined,
 }
 
 impl Value {
@@ -37,6 +51,10 @@ impl Value {
             Value::Instance(addr) => addr.ptr(),
             Value::Module(addr) => addr.ptr(),
             Value::Tuple(addr) => addr.ptr(),
+            Value::UserData(addr) => addr.ptr(),
+            Value::List(addr) => addr.ptr(),
+            Value::Map(addr) => addr.ptr(),
+            Value::Closure(addr) => addr.ptr(),
             _ => return None,
         };
 
@@ -63,7 +81,14 @@ impl Value {
             Self::Module(..) => "module",
             Self::Tuple(..) => "tuple",
             Self::Char(..) => "char",
+            Self::UserData(..) => "userdata",
+            Self::List(..) => "list",
+            Self::Map(..) => "map",
+            Self::Closure(..) => "closure",
+            Self::Weak(..) => "weak",
+            Self::Native(..) => "native",
             Self::Unit => "unit",
+            Self::Undefined => "undefined",
         }
     }
 
@@ -262,6 +287,39 @@ impl Value {
         }
     }
 
+    pub fn as_userdata(&self) -> &Gc<OxUserData> {
+        if let Self::UserData(val) = self {
+            val
+        } else {
+            panic!(
+                "Attempting to get a userdata from a value of type {}",
+                self.ty()
+            );
+        }
+    }
+
+    pub fn as_list(&self) -> &Gc<OxList> {
+        if let Self::List(val) = self {
+            val
+        } else {
+            panic!(
+                "Attempting to get a list from a value of type {}",
+                self.ty()
+            );
+        }
+    }
+
+    pub fn as_map(&self) -> &Gc<OxMap> {
+        if let Self::Map(val) = self {
+            val
+        } else {
+            panic!(
+                "Attempting to get a map from a value of type {}",
+                self.ty()
+            );
+        }
+    }
+
     pub fn as_instance(&self) -> &Gc<OxInstance> {
         if let Self::Instance(val) = self {
             val
@@ -396,6 +454,10 @@ impl Value {
         }
     }
 
+    pub fn is_weak(&self) -> bool {
+        matches!(self, Self::Weak(..))
+    }
+
     pub fn is_instance(&self) -> bool {
         match self {
             Self::Instance(..) => true,
@@ -403,6 +465,61 @@ impl Value {
         }
     }
 
+    /// structural equality of constant pool entries, other heap objects are only equal to
+    /// themselves.
+    pub fn is_same_constant(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Self::I8(a), Self::I8(b)) => a == b,
+            (Self::I16(a), Self::I16(b)) => a == b,
+            (Self::I32(a), Self::I32(b)) => a == b,
+            (Self::I64(a), Self::I64(b)) => a == b,
+            (Self::U8(a), Self::U8(b)) => a == b,
+            (Self::U16(a), Self::U16(b)) => a == b,
+            (Self::U32(a), Self::U32(b)) => a == b,
+            (Self::U64(a), Self::U64(b)) => a == b,
+            (Self::F32(a), Self::F32(b)) => a.to_bits() == b.to_bits(),
+            (Self::F64(a), Self::F64(b)) => a.to_bits() == b.to_bits(),
+            (Self::Bool(a), Self::Bool(b)) => a == b,
+            (Self::Char(a), Self::Char(b)) => a == b,
+            (Self::String(a), Self::String(b)) => a.ptr() == b.ptr() || a.as_str() == b.as_str(),
+            (Self::Tuple(a), Self::Tuple(b)) => {
+                let (a, b) = (a.elements(), b.elements());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.is_same_constant(b))
+            }
+            (Self::Unit, Self::Unit) => true,
+            _ => match (self.addr(), other.addr()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+        }
+    }
+
+    /// hashes the parts of the value that are compared by `is_same_constant`.
+    pub fn hash_constant<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Self::I8(val) => val.hash(state),
+            Self::I16(val) => val.hash(state),
+            Self::I32(val) => val.hash(state),
+            Self::I64(val) => val.hash(state),
+            Self::U8(val) => val.hash(state),
+            Self::U16(val) => val.hash(state),
+            Self::U32(val) => val.hash(state),
+            Self::U64(val) => val.hash(state),
+            Self::F32(val) => val.to_bits().hash(state),
+            Self::F64(val) => val.to_bits().hash(state),
+            Self::Bool(val) => val.hash(state),
+            Self::Char(val) => val.hash(state),
+            Self::String(val) => val.as_str().hash(state),
+            Self::Tuple(tuple) => tuple
+                .elements()
+                .iter()
+                .for_each(|element| element.hash_constant(state)),
+            Self::Unit => {}
+            _ => self.addr().hash(state),
+        }
+    }
+
     pub fn disassemble(&self, indent: usize) {
         match self {
             Self::String(s) => println!(
@@ -466,6 +583,10 @@ value_from!(Module, Gc<OxModule>);
 value_from!(String, Gc<OxString>);
 value_from!(Tuple, Gc<OxTuple>);
 value_from!(Instance, Gc<OxInstance>);
+value_from!(UserData, Gc<OxUserData>);
+value_from!(List, Gc<OxList>);
+value_from!(Map, Gc<OxMap>);
+value_from!(Closure, Gc<OxClosure>);
 
 impl Display for Value {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -478,8 +599,8 @@ impl Display for Value {
             Self::U16(val) => write!(f, "{}", val),
             Self::U32(val) => write!(f, "{}", val),
             Self::U64(val) => write!(f, "{}", val),
-            Self::F32(val) => write!(f, "{}", val),
-            Self::F64(val) => write!(f, "{}", val),
+            Self::F32(val) => write!(f, "{}", float::format_f32(*val)),
+            Self::F64(val) => write!(f, "{}", float::format_f64(*val)),
             Self::Bool(val) => write!(f, "{}", val),
             Self::String(val) => write!(f, "{}", val),
             Self::Function(val) => write!(f, "{}", val),
@@ -488,7 +609,14 @@ impl Display for Value {
             Self::Instance(val) => write!(f, "{}", val),
             Self::Char(val) => write!(f, "{}", val),
             Self::Tuple(val) => write!(f, "{}", val),
-            Self::Unit => write!(f, "<>"),
+            Self::UserData(val) => write!(f, "{}", val),
+            Self::List(val) => write!(f, "{}", val),
+            Self::Map(val) => write!(f, "{}", val),
+            Self::Closure(val) => write!(f, "{}", val),
+            Self::Weak(handle) => write!(f, "<weak {}>", handle),
+            Self::Native(idx) => write!(f, "<native {}>", idx),
+            Self::Unit => write!(f, "()"),
+            Self::Undefined => write!(f, "<undefined>"),
         }
     }
 }
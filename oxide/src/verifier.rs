@@ -0,0 +1,282 @@
+use std::collections::BTreeSet;
+
+use crate::{mem::read_to, runtime, vm::OpCode, Section};
+
+/// what the operand of an instruction is, decides how it is read and checked.
+enum Operand {
+    None,
+    Constant,
+    Global,
+    Local,
+    Byte,
+    Short,
+    Forward,
+    Backward,
+    Label,
+}
+
+pub struct Verifier;
+
+impl Verifier {
+    pub fn verify_section(section: &Section) -> Result<(), runtime::Error> {
+        let data = section.data();
+        let mut starts = BTreeSet::new();
+        let mut targets = vec![];
+        let mut ip = 0;
+        while ip < data.len() {
+            let start = ip;
+            starts.insert(start);
+            let mut op_code = Self::read_op(data, &mut ip)?;
+            let wide = op_code == OpCode::Wide;
+            if wide {
+                op_code = Self::read_op(data, &mut ip)?;
+            }
+
+            let operand = Self::operand(op_code);
+            if wide
+                && !matches!(
+                    operand,
+                    Operand::Constant | Operand::Global | Operand::Local
+                )
+            {
+                let reason = format!("'{}' does not take a wide operand", op_code);
+                return Err(runtime::Error::invalid_bytecode(start, reason));
+            }
+
+            let size = Self::operand_len(&operand, wide, data, ip);
+            if ip + size > data.len() {
+                let reason = format!("the operand of '{}' is truncated", op_code);
+                return Err(runtime::Error::invalid_bytecode(start, reason));
+            }
+
+            let next = ip + size;
+            match operand {
+                Operand::Constant | Operand::Global => {
+                    let index = if wide {
+                        read_to::<u32>(data, &mut ip) as usize
+                    } else {
+                        data[ip] as usize
+                    };
+                    let (table, len) = match operand {
+                        Operand::Constant => ("constants", section.constants().len()),
+                        _ => ("globals", section.globals().len()),
+                    };
+                    if index >= len {
+                        let reason = format!(
+                            "'{}' refers to index {} of {} {}",
+                            op_code, index, len, table
+                        );
+                        return Err(runtime::Error::invalid_bytecode(start, reason));
+                    }
+                }
+                Operand::Forward => {
+                    let distance = read_to::<u16>(data, &mut ip) as usize;
+                    targets.push((start, next + distance));
+                }
+                Operand::Backward => {
+                    let distance = read_to::<u16>(data, &mut ip) as usize;
+                    match next.checked_sub(distance) {
+                        Some(target) => targets.push((start, target)),
+                        None => {
+                            let reason = format!("'{}' jumps before the section", op_code);
+                            return Err(runtime::Error::invalid_bytecode(start, reason));
+                        }
+                    }
+                }
+                Operand::None
+                | Operand::Local
+                | Operand::Byte
+                | Operand::Short
+                | Operand::Label => {}
+            }
+            ip = next;
+        }
+
+        // a jump lands on an instruction or returns by running off the end of the section.
+        for (start, target) in targets {
+            if target != data.len() && !starts.contains(&target) {
+                let reason = format!("jump to {} does not land on an instruction", target);
+                return Err(runtime::Error::invalid_bytecode(start, reason));
+            }
+        }
+        Ok(())
+    }
+
+    /// the length of the operand at ip, it may run past the end of data.
+    fn operand_len(operand: &Operand, wide: bool, data: &[u8], ip: usize) -> usize {
+        match operand {
+            Operand::None => 0,
+            Operand::Constant | Operand::Global | Operand::Local if wide => 4,
+            Operand::Constant | Operand::Global | Operand::Local | Operand::Byte => 1,
+            Operand::Short | Operand::Forward | Operand::Backward => 2,
+            // matches the interpreter, a label is skipped along with the byte after it.
+            Operand::Label => match data.get(ip) {
+                Some(len) => 2 + *len as usize,
+                None => 1,
+            },
+        }
+    }
+
+    /// the op code of the instruction at start and the offset of the next one, a wide prefix
+    /// is part of the instruction it widens.
+    pub(crate) fn instruction(
+        data: &[u8],
+        start: usize,
+    ) -> Result<(OpCode, usize), runtime::Error> {
+        let mut ip = start;
+        let mut op_code = Self::read_op(data, &mut ip)?;
+        let wide = op_code == OpCode::Wide;
+        if wide {
+            op_code = Self::read_op(data, &mut ip)?;
+        }
+        let next = ip + Self::operand_len(&Self::operand(op_code), wide, data, ip);
+        if next > data.len() {
+            let reason = format!("the operand of '{}' is truncated", op_code);
+            return Err(runtime::Error::invalid_bytecode(start, reason));
+        }
+        Ok((op_code, next))
+    }
+
+    fn read_op(data: &[u8], ip: &mut usize) -> Result<OpCode, runtime::Error> {
+        let byte = data.get(*ip).copied();
+        let op_code = byte
+            .and_then(OpCode::from_u8)
+            .filter(|op| *op != OpCode::NumOps);
+        match op_code {
+            Some(op_code) => {
+                *ip += 1;
+                Ok(op_code)
+            }
+            None => {
+                let reason = match byte {
+                    Some(byte) => format!("unknown op code {}", byte),
+                    None => "a wide prefix ends the section".to_string(),
+                };
+                Err(runtime::Error::invalid_bytecode(*ip, reason))
+            }
+        }
+    }
+
+    fn operand(op_code: OpCode) -> Operand {
+        match op_code {
+            OpCode::LoadI8
+            | OpCode::LoadI16
+            | OpCode::LoadI32
+            | OpCode::LoadI64
+            | OpCode::LoadU8
+            | OpCode::LoadU16
+            | OpCode::LoadU32
+            | OpCode::LoadU64
+            | OpCode::LoadF32
+            | OpCode::LoadF64
+            | OpCode::LoadStr
+            | OpCode::LoadChar
+            | OpCode::LoadConst
+            | OpCode::NewInstanceConst
+            | OpCode::NewArrayConst => Operand::Constant,
+            OpCode::LoadGlobal | OpCode::SetGlobal => Operand::Global,
+            OpCode::LoadLocal | OpCode::SetLocal => Operand::Local,
+            OpCode::LoadAssoc
+            | OpCode::SetRegister
+            | OpCode::SetAttr
+            | OpCode::LoadRegister
+            | OpCode::LogEnabled
+            | OpCode::CastInt
+            | OpCode::Log
+            | OpCode::Spawn
+            | OpCode::TrapFormat
+            | OpCode::NewClosure
+            | OpCode::Call => Operand::Byte,
+            OpCode::NewInstance
+            | OpCode::NewTuple
+            | OpCode::NewArray
+            | OpCode::InstanceAttr
+            | OpCode::TupleAttr => Operand::Short,
+            OpCode::JmpTrue | OpCode::JmpFalse | OpCode::JmpUnit | OpCode::Jmp => Operand::Forward,
+            OpCode::Loop => Operand::Backward,
+            OpCode::Label => Operand::Label,
+            _ => Operand::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{runtime::Error, vm::OpCode, Value, Vm};
+
+    fn reason(result: Result<(), Error>) -> (usize, String) {
+        match result {
+            Err(Error::InvalidBytecode { offset, reason }) => (offset, reason),
+            _ => panic!("expected the section to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_verify_wide_operands() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut section = vm.new_section();
+        for value in 0..300 {
+            section.add_constant(Value::I32(value));
+        }
+        section.write_index(OpCode::LoadI32, 3);
+        section.write_index(OpCode::LoadI32, 299);
+        let exit = section.write_jmp(OpCode::Jmp);
+        section.write_op(OpCode::AddI32);
+        section.patch_jmp(exit);
+        section.write_op(OpCode::Return);
+        assert_eq!(
+            section.data()[2..4],
+            [OpCode::Wide as u8, OpCode::LoadI32 as u8]
+        );
+        assert!(section.verify().is_ok());
+
+        let disassembled = section.disassemble();
+        assert_eq!(
+            disassembled[1].to_string().trim_end(),
+            "0000000002 load_i32 299 (299)"
+        );
+        assert_eq!(disassembled.len(), 5);
+
+        section.write_index(OpCode::LoadI32, 300);
+        assert_eq!(
+            reason(section.verify()),
+            (
+                13,
+                "'load_i32' refers to index 300 of 300 constants".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_verify_rejects_malformed_code() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+
+        let mut section = vm.new_section();
+        section.write_op(OpCode::Wide);
+        section.write_arg(OpCode::Call, 0);
+        assert_eq!(
+            reason(section.verify()),
+            (0, "'call' does not take a wide operand".to_string())
+        );
+
+        let mut section = vm.new_section();
+        section.write_op(OpCode::LoadTrue);
+        section.write_bytes(&[OpCode::Jmp as u8, 0, 1]);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_op(OpCode::Return);
+        assert_eq!(
+            reason(section.verify()),
+            (1, "jump to 5 does not land on an instruction".to_string())
+        );
+
+        let mut section = vm.new_section();
+        section.write_op(OpCode::LoadTrue);
+        section.write_byte(0xff);
+        assert_eq!(
+            reason(section.verify()),
+            (1, "unknown op code 255".to_string())
+        );
+    }
+}
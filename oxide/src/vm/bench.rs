@@ -0,0 +1,254 @@
+use std::time::{Duration, Instant};
+
+use crate::{gc::Gc, runtime, source_map::escape, OxModule, Value, Vm};
+
+/// how long a phase of a benchmark runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    Iterations(u64),
+    /// iterations are run until the time has passed, there is always at least one.
+    Time(Duration),
+}
+
+impl Budget {
+    fn is_spent(self, iterations: u64, elapsed: Duration) -> bool {
+        match self {
+            Budget::Iterations(count) => iterations >= count,
+            Budget::Time(time) => iterations > 0 && elapsed >= time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchConfig {
+    /// iterations run before any is measured, so the heap has grown to what the body uses.
+    pub warmup: Budget,
+    pub measure: Budget,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            warmup: Budget::Time(Duration::from_millis(200)),
+            measure: Budget::Time(Duration::from_secs(1)),
+        }
+    }
+}
+
+/// a measure taken once per iteration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Samples {
+    values: Vec<f64>,
+}
+
+impl Samples {
+    pub fn push(&mut self, value: f64) {
+        self.values.push(value);
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.values.iter().sum()
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.values.is_empty() {
+            0.0
+        } else {
+            self.sum() / self.values.len() as f64
+        }
+    }
+
+    /// the standard deviation of the samples as a sample of every iteration, zero for fewer
+    /// than two.
+    pub fn std_dev(&self) -> f64 {
+        if self.values.len() < 2 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        let squares = self.values.iter().map(|value| (value - mean).powi(2)).sum::<f64>();
+        (squares / (self.values.len() - 1) as f64).sqrt()
+    }
+}
+
+/// the measured iterations of a benchmark.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchReport {
+    /// instructions executed by each iteration, the same for every run of the program.
+    pub instructions: Samples,
+    /// wall time of each iteration in nanoseconds.
+    pub nanos: Samples,
+}
+
+impl BenchReport {
+    pub fn iterations(&self) -> usize {
+        self.nanos.len()
+    }
+
+    pub fn iterations_per_sec(&self) -> f64 {
+        let seconds = self.nanos.sum() / 1e9;
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.iterations() as f64 / seconds
+        }
+    }
+
+    /// the report of the benchmark named name as a json object.
+    pub fn to_json(&self, name: &str) -> String {
+        format!(
+            "{{\"name\":{},\"iterations\":{},\"iterations_per_sec\":{:.3},\"mean_ns\":{:.3},\
+             \"std_dev_ns\":{:.3},\"mean_instructions\":{:.3},\"std_dev_instructions\":{:.3}}}",
+            escape(name),
+            self.iterations(),
+            self.iterations_per_sec(),
+            self.nanos.mean(),
+            self.nanos.std_dev(),
+            self.instructions.mean(),
+            self.instructions.std_dev()
+        )
+    }
+}
+
+impl Vm {
+    /// calls function, a function of module without parameters, for the warmup and then
+    /// measures each call until the budget of the config is spent. The value it returns is
+    /// dropped.
+    pub fn bench(
+        &mut self,
+        module: Gc<OxModule>,
+        function: Value,
+        config: &BenchConfig,
+    ) -> Result<BenchReport, runtime::Error> {
+        match function {
+            Value::Function(inner) if inner.arity() == 0 => {}
+            _ => return Err(runtime::Error::CallingInvalidValue(function.ty().to_string())),
+        }
+        self.running = Some(module);
+        self.push_stack(Value::from(module));
+
+        let start = Instant::now();
+        let mut iterations = 0;
+        while !config.warmup.is_spent(iterations, start.elapsed()) {
+            self.bench_iteration(function)?;
+            iterations += 1;
+        }
+
+        let mut report = BenchReport {
+            instructions: Samples::default(),
+            nanos: Samples::default(),
+        };
+        let start = Instant::now();
+        let mut iterations = 0;
+        while !config.measure.is_spent(iterations, start.elapsed()) {
+            let executed = self.executed;
+            let iteration = Instant::now();
+            self.bench_iteration(function)?;
+            report.nanos.push(iteration.elapsed().as_nanos() as f64);
+            report.instructions.push((self.executed - executed) as f64);
+            iterations += 1;
+        }
+
+        self.pop();
+        Ok(report)
+    }
+
+    fn bench_iteration(&mut self, function: Value) -> Result<(), runtime::Error> {
+        self.push_stack(function);
+        self.call_value(&function, 0)?;
+        self.run()?;
+        self.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BenchConfig, Budget, Samples};
+    use crate::{gc::Gc, vm::OpCode, OxModule, Value, Vm};
+
+    /// a module whose first object is `fn() = 1 + 2`, four instructions.
+    fn add(vm: &mut Vm) -> Gc<OxModule> {
+        vm.force_no_collection(true);
+        let mut section = vm.new_section();
+        let one = section.intern_constant(Value::I64(1));
+        let two = section.intern_constant(Value::I64(2));
+        section.write_index(OpCode::LoadI64, one);
+        section.write_index(OpCode::LoadI64, two);
+        section.write_op(OpCode::AddI64);
+        section.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("add");
+        let function = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("bench");
+        let mut module = vm.new_empty_module(name);
+        module.as_ref_mut().add_object(Value::from(function));
+        vm.force_no_collection(false);
+        module
+    }
+
+    #[test]
+    fn test_iterations_are_counted_in_instructions() {
+        let mut vm = Vm::new();
+        let module = add(&mut vm);
+        let function = module.objects()[0];
+        let config = BenchConfig {
+            warmup: Budget::Iterations(5),
+            measure: Budget::Iterations(20),
+        };
+
+        let report = vm.bench(module, function, &config).unwrap();
+        assert_eq!(report.iterations(), 20);
+        assert_eq!(report.instructions.mean(), 4.0);
+        assert_eq!(report.instructions.std_dev(), 0.0);
+        // the warmup runs but is not measured.
+        assert_eq!(vm.instructions_executed(), 25 * 4);
+        assert_eq!(report.nanos.len(), 20);
+        assert!(report.iterations_per_sec() > 0.0);
+
+        // every run of the benchmark executes the same instructions.
+        let again = vm.bench(module, function, &config).unwrap();
+        assert_eq!(again.instructions, report.instructions);
+        assert_eq!(vm.instructions_executed(), 50 * 4);
+
+        let json = report.to_json("add \"one\"");
+        assert!(json.starts_with("{\"name\":\"add \\\"one\\\"\",\"iterations\":20,"));
+        assert!(json.ends_with("\"mean_instructions\":4.000,\"std_dev_instructions\":0.000}"));
+    }
+
+    #[test]
+    fn test_a_timed_budget_runs_at_least_once() {
+        let mut vm = Vm::new();
+        let module = add(&mut vm);
+        let function = module.objects()[0];
+        let config = BenchConfig {
+            warmup: Budget::Time(std::time::Duration::ZERO),
+            measure: Budget::Time(std::time::Duration::ZERO),
+        };
+        let report = vm.bench(module, function, &config).unwrap();
+        assert_eq!(report.iterations(), 1);
+        assert_eq!(vm.instructions_executed(), 2 * 4);
+
+        // only functions without parameters are measured.
+        let err = vm.bench(module, Value::I64(1), &config).unwrap_err();
+        assert_eq!(err.to_string(), "attempting to call a value that cannot be called: i64");
+    }
+
+    #[test]
+    fn test_sample_statistics() {
+        let mut samples = Samples::default();
+        assert_eq!((samples.mean(), samples.std_dev()), (0.0, 0.0));
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            samples.push(value);
+        }
+        assert_eq!(samples.mean(), 5.0);
+        // the sum of the squared differences is 32, over seven degrees of freedom.
+        assert!((samples.std_dev() - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{runtime, Vm};
+
+/// something a native does on behalf of a script that reaches outside of the vm. A native
+/// requires the capability before doing it, a file operation names the path it touches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Capability {
+    /// reading the files under the path.
+    FsRead(PathBuf),
+    /// creating, writing and removing the files under the path.
+    FsWrite(PathBuf),
+    Env,
+    Clock,
+    Rng,
+    Ffi,
+    /// running functions as tasks.
+    Spawn,
+    /// reserved, nothing requires it yet.
+    Net,
+    /// compiling and running source given by the script with `eval`.
+    Eval,
+}
+
+/// a capability without the path of a file operation, the command line denies by kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapabilityKind {
+    FsRead,
+    FsWrite,
+    Env,
+    Clock,
+    Rng,
+    Ffi,
+    Spawn,
+    Net,
+    Eval,
+}
+
+impl CapabilityKind {
+    pub const ALL: [CapabilityKind; 9] = [
+        CapabilityKind::FsRead,
+        CapabilityKind::FsWrite,
+        CapabilityKind::Env,
+        CapabilityKind::Clock,
+        CapabilityKind::Rng,
+        CapabilityKind::Ffi,
+        CapabilityKind::Spawn,
+        CapabilityKind::Net,
+        CapabilityKind::Eval,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CapabilityKind::FsRead => "fs.read",
+            CapabilityKind::FsWrite => "fs.write",
+            CapabilityKind::Env => "env",
+            CapabilityKind::Clock => "clock",
+            CapabilityKind::Rng => "rng",
+            CapabilityKind::Ffi => "ffi",
+            CapabilityKind::Spawn => "spawn",
+            CapabilityKind::Net => "net",
+            CapabilityKind::Eval => "eval",
+        }
+    }
+}
+
+impl FromStr for CapabilityKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        CapabilityKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                let names = CapabilityKind::ALL.iter().map(|kind| kind.name()).collect::<Vec<_>>();
+                format!("unknown capability '{}', expected one of {}", s, names.join(", "))
+            })
+    }
+}
+
+impl Display for CapabilityKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Capability {
+    pub fn kind(&self) -> CapabilityKind {
+        match self {
+            Capability::FsRead(_) => CapabilityKind::FsRead,
+            Capability::FsWrite(_) => CapabilityKind::FsWrite,
+            Capability::Env => CapabilityKind::Env,
+            Capability::Clock => CapabilityKind::Clock,
+            Capability::Rng => CapabilityKind::Rng,
+            Capability::Ffi => CapabilityKind::Ffi,
+            Capability::Spawn => CapabilityKind::Spawn,
+            Capability::Net => CapabilityKind::Net,
+            Capability::Eval => CapabilityKind::Eval,
+        }
+    }
+
+    /// whether the grant self covers the request, a grant of a path covers every path under it.
+    fn covers(&self, request: &Capability) -> bool {
+        match (self, request) {
+            (Capability::FsRead(granted), Capability::FsRead(path))
+            | (Capability::FsWrite(granted), Capability::FsWrite(path)) => {
+                normalize(path).starts_with(normalize(granted))
+            }
+            _ => self == request,
+        }
+    }
+}
+
+/// `fs.write(/etc/passwd)`, the capability as it is named in errors.
+impl Display for Capability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capability::FsRead(path) | Capability::FsWrite(path) => {
+                write!(f, "{}({})", self.kind(), path.display())
+            }
+            _ => write!(f, "{}", self.kind()),
+        }
+    }
+}
+
+/// removes the `.` and `..` of path so `/tmp/../etc` is not under `/tmp`. Links are not
+/// followed, a native opening a file resolves them before requiring the capability.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// the capabilities granted to the scripts of a vm, a vm starts with none.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    granted: Vec<Capability>,
+}
+
+impl Capabilities {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn granted(&self) -> &[Capability] {
+        self.granted.as_slice()
+    }
+
+    pub fn grant(&mut self, capability: Capability) {
+        if !self.granted.contains(&capability) {
+            self.granted.push(capability);
+        }
+    }
+
+    /// takes back every grant of kind, whatever path it names.
+    pub fn deny(&mut self, kind: CapabilityKind) {
+        self.granted.retain(|granted| granted.kind() != kind);
+    }
+
+    pub fn allows(&self, capability: &Capability) -> bool {
+        self.granted.iter().any(|granted| granted.covers(capability))
+    }
+}
+
+impl Vm {
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    pub fn grant(&mut self, capability: Capability) {
+        self.capabilities.grant(capability);
+    }
+
+    /// fails with a runtime error naming the operation unless capability is granted, every
+    /// native reaching outside of the vm calls it first.
+    pub fn require(&self, capability: Capability) -> Result<(), runtime::Error> {
+        if self.capabilities.allows(&capability) {
+            Ok(())
+        } else {
+            Err(runtime::Error::capability_not_granted(&capability))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Capabilities, Capability, CapabilityKind};
+    use crate::{runtime::Error, Vm};
+
+    fn every_capability() -> Vec<Capability> {
+        vec![
+            Capability::FsRead(PathBuf::from("/data/input.txt")),
+            Capability::FsWrite(PathBuf::from("/data/output.txt")),
+            Capability::Env,
+            Capability::Clock,
+            Capability::Rng,
+            Capability::Ffi,
+            Capability::Spawn,
+            Capability::Net,
+            Capability::Eval,
+        ]
+    }
+
+    #[test]
+    fn test_grant_and_deny_every_capability() {
+        let mut vm = Vm::new();
+        for capability in every_capability() {
+            // an embedded vm is granted nothing.
+            let err = vm.require(capability.clone()).unwrap_err();
+            assert_eq!(err.to_string(), format!("capability not granted: {}", capability));
+            assert!(matches!(err, Error::CapabilityNotGranted(_)));
+
+            vm.grant(capability.clone());
+            assert!(vm.require(capability.clone()).is_ok(), "{}", capability);
+        }
+        for capability in every_capability() {
+            let mut capabilities = vm.capabilities().clone();
+            capabilities.deny(capability.kind());
+            assert!(!capabilities.allows(&capability), "{}", capability);
+            // the other grants are kept.
+            assert_eq!(capabilities.granted().len(), CapabilityKind::ALL.len() - 1);
+        }
+    }
+
+    #[test]
+    fn test_paths_are_granted_by_directory() {
+        let mut capabilities = Capabilities::none();
+        capabilities.grant(Capability::FsWrite(PathBuf::from("/tmp/out")));
+        capabilities.grant(Capability::FsRead(PathBuf::from("/")));
+
+        let write = |path: &str| Capability::FsWrite(PathBuf::from(path));
+        assert!(capabilities.allows(&write("/tmp/out/a.txt")));
+        assert!(capabilities.allows(&write("/tmp/out/./logs/../b.txt")));
+        assert!(!capabilities.allows(&write("/tmp/out/../../etc/passwd")));
+        assert!(!capabilities.allows(&write("/tmp/outside")));
+        assert!(capabilities.allows(&Capability::FsRead(PathBuf::from("/etc/passwd"))));
+        assert_eq!(write("/etc/passwd").to_string(), "fs.write(/etc/passwd)");
+
+        capabilities.deny(CapabilityKind::FsWrite);
+        assert!(!capabilities.allows(&write("/tmp/out/a.txt")));
+        assert!(capabilities.allows(&Capability::FsRead(PathBuf::from("/etc/passwd"))));
+    }
+
+    #[test]
+    fn test_capability_names() {
+        for kind in CapabilityKind::ALL.iter().copied() {
+            assert_eq!(kind.name().parse::<CapabilityKind>(), Ok(kind));
+        }
+        assert_eq!(
+            "fs".parse::<CapabilityKind>(),
+            Err("unknown capability 'fs', expected one of fs.read, fs.write, env, clock, rng, \
+                 ffi, spawn, net, eval"
+                .to_string())
+        );
+    }
+}
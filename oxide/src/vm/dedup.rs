@@ -0,0 +1,356 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    gc::{Address, Gc, ObjectKind},
+    OxClosure, OxFunction, OxInstance, OxList, OxMap, OxModule, OxString, OxStruct, OxTuple,
+    Value, Vm,
+};
+
+/// a summary of the heap of a vm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// the bytes allocated on the heap, objects that are unreachable but not swept yet included.
+    pub memory_usage: usize,
+    /// the string objects on the heap.
+    pub strings: usize,
+    /// the bytes taken by the string objects and their buffers.
+    pub string_bytes: usize,
+    /// the strings folded into a string with the same contents by the collections so far.
+    pub deduplicated_strings: usize,
+    /// the bytes released by folding those strings.
+    pub deduplicated_bytes: usize,
+    /// the times the buffer of a list or string was moved to one of another size, growing or
+    /// shrinking. The bytes of both buffers are part of `memory_usage` only while they are held.
+    pub buffer_reallocations: usize,
+    /// the bytes allocated since the vm was created, the ones released since included.
+    pub bytes_allocated: u64,
+    /// the collections that ran, the ones the host forced included.
+    pub collections: u64,
+    /// the memory usage the next collection starts at.
+    pub next_collection: usize,
+}
+
+/// the totals of every deduplication the vm ran.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Deduplicated {
+    strings: usize,
+    bytes: usize,
+}
+
+impl Vm {
+    /// folds the live strings with the same contents into one of them during every collection.
+    /// The references held by the vm are moved to the string that is kept, a `Gc<OxString>` the
+    /// host keeps outside of the vm is not and must not be used after a collection.
+    pub fn set_string_dedup(&mut self, dedup: bool) {
+        self.string_dedup = dedup;
+    }
+
+    pub fn heap_stats(&self) -> HeapStats {
+        let strings = self.allocator.objects(ObjectKind::String);
+        let string_bytes = strings
+            .iter()
+            .map(|address| {
+                let buffer = address
+                    .into_ref::<OxString>()
+                    .buffer()
+                    .map_or(0, |buffer| buffer.ptr().allocation_size());
+                address.allocation_size() + buffer
+            })
+            .sum();
+        HeapStats {
+            memory_usage: self.memory_usage(),
+            strings: strings.len(),
+            string_bytes,
+            deduplicated_strings: self.deduplicated.strings,
+            deduplicated_bytes: self.deduplicated.bytes,
+            buffer_reallocations: self.allocator.reallocations(),
+            bytes_allocated: self.allocator.allocated(),
+            collections: self.collections,
+            next_collection: self.allocator.next_collection(),
+        }
+    }
+
+    /// walks the live objects like marking does and redirects every reference to a string to the
+    /// first string reached with the same contents, the others are released. This runs between
+    /// marking and sweeping, the strings it releases are marked and the sweep would keep them.
+    pub(super) fn dedup_strings(&mut self) {
+        let mut forwarding = Forwarding::default();
+        // interned strings are looked up by their contents, they are the strings that are kept.
+        for string in self.interned() {
+            forwarding.pin(*string);
+        }
+        // the compiler refers to its objects by address, the strings it holds cannot move.
+        for address in &self.compiler_address {
+            match address.cell().kind {
+                ObjectKind::String => forwarding.pin(Gc::new(*address)),
+                _ => forwarding.visit(*address),
+            }
+        }
+        forwarding.values(&mut self.stack[0..self.top_stack]);
+        for frame in &self.call_stack[0..self.top_frame] {
+            forwarding.visit(frame.function.ptr());
+        }
+        if let Some(module) = self.running {
+            forwarding.visit(module.ptr());
+        }
+        for scratch in self.scratch.iter_mut().flatten() {
+            forwarding.values(scratch.values_mut());
+        }
+        for binding in self.eval.bindings_mut() {
+            forwarding.values(std::slice::from_mut(&mut binding.value));
+        }
+        // the instances are weak, but their finalizers and the queued instances are roots.
+        for (_, finalizer) in self.finalizable_instances.iter_mut() {
+            forwarding.value(finalizer);
+        }
+        for (instance, finalizer) in self.pending_finalizers.iter_mut() {
+            forwarding.value(instance);
+            forwarding.value(finalizer);
+        }
+        forwarding.follow();
+
+        // weak handles and registers do not keep their values alive, they are only redirected.
+        for value in self.weak_refs.referents_mut() {
+            forwarding.redirect(value);
+        }
+        for value in self.registers.iter_mut() {
+            forwarding.redirect(value);
+        }
+        for (instance, _) in self.finalizable_instances.iter_mut() {
+            forwarding.redirect(instance);
+        }
+
+        if forwarding.duplicates.is_empty() {
+            return;
+        }
+        let before = self.memory_usage();
+        for address in &forwarding.duplicates {
+            Self::release_object(*address, ObjectKind::String);
+            self.allocator.dealloc(*address);
+        }
+        self.deduplicated.strings += forwarding.duplicates.len();
+        self.deduplicated.bytes += before - self.memory_usage();
+    }
+}
+
+#[derive(Default)]
+struct Forwarding {
+    /// the string each string reached so far is folded into, by address.
+    forwarded: HashMap<Address, Gc<OxString>>,
+    /// the string that is kept for some contents.
+    canonical: HashMap<Vec<u8>, Gc<OxString>>,
+    /// the strings that were folded into another.
+    duplicates: Vec<Address>,
+    /// the objects reached that are not strings.
+    visited: HashSet<Address>,
+    gray_list: Vec<Address>,
+}
+
+impl Forwarding {
+    fn pin(&mut self, string: Gc<OxString>) {
+        self.forwarded.insert(string.ptr(), string);
+        self.canonical
+            .entry(string.as_bytes().to_vec())
+            .or_insert(string);
+    }
+
+    fn visit(&mut self, address: Address) {
+        if self.visited.insert(address) {
+            self.gray_list.push(address);
+        }
+    }
+
+    fn fold(&mut self, string: Gc<OxString>) -> Gc<OxString> {
+        if let Some(kept) = self.forwarded.get(&string.ptr()) {
+            return *kept;
+        }
+        let kept = *self
+            .canonical
+            .entry(string.as_bytes().to_vec())
+            .or_insert(string);
+        if kept.ptr() != string.ptr() {
+            self.duplicates.push(string.ptr());
+        }
+        self.forwarded.insert(string.ptr(), kept);
+        kept
+    }
+
+    fn values(&mut self, values: &mut [Value]) {
+        for value in values {
+            self.value(value);
+        }
+    }
+
+    fn value(&mut self, value: &mut Value) {
+        match value {
+            Value::String(string) => *string = self.fold(*string),
+            value => {
+                if let Some(address) = value.addr() {
+                    self.visit(address);
+                }
+            }
+        }
+    }
+
+    /// points a value that is not traced to the string its string was folded into.
+    fn redirect(&self, value: &mut Value) {
+        if let Value::String(string) = value {
+            if let Some(kept) = self.forwarded.get(&string.ptr()) {
+                *string = *kept;
+            }
+        }
+    }
+
+    fn follow(&mut self) {
+        while let Some(mut address) = self.gray_list.pop() {
+            match address.cell().kind {
+                ObjectKind::Function => {
+                    let section = address.into_ref_mut::<OxFunction>().section_mut();
+                    self.values(section.constants_mut());
+                    self.values(section.globals_mut());
+                }
+                ObjectKind::Instance => {
+                    self.values(address.into_ref_mut::<OxInstance>().fields_mut())
+                }
+                ObjectKind::Module => self.values(address.into_ref_mut::<OxModule>().objects_mut()),
+                ObjectKind::Struct => {
+                    for method in address.into_ref::<OxStruct>().methods().iter() {
+                        self.visit(method.ptr());
+                    }
+                }
+                ObjectKind::Tuple => self.values(address.into_ref_mut::<OxTuple>().elements_mut()),
+                ObjectKind::List => self.values(address.into_ref_mut::<OxList>().elements_mut()),
+                ObjectKind::Map => {
+                    // a folded key has the same contents, it hashes to the same slot.
+                    for (key, value) in address.into_ref_mut::<OxMap>().entries_mut() {
+                        self.value(key);
+                        self.value(value);
+                    }
+                }
+                ObjectKind::Closure => {
+                    let closure = address.into_ref_mut::<OxClosure>();
+                    self.visit(closure.function().ptr());
+                    self.values(closure.captured_mut());
+                }
+                ObjectKind::String | ObjectKind::UserData => {}
+                ObjectKind::Section | ObjectKind::Vec => unreachable!(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        vm::{render, RenderOptions},
+        Value, Vm,
+    };
+
+    const KEYS: usize = 10_000;
+
+    #[test]
+    fn test_parsed_keys_are_folded_into_one_string() {
+        let mut vm = Vm::new();
+        // collections only run when a pool is full, far more keys are parsed than fit at once.
+        vm.force_no_collection(true);
+        vm.set_string_dedup(true);
+        let input = (0..KEYS)
+            .map(|i| format!("session_id={}\n", i))
+            .collect::<String>();
+
+        let mut keys = vm.new_list_with_capacity(KEYS).unwrap();
+        vm.push_stack(Value::from(keys));
+        for line in input.lines() {
+            let (key, _) = line.split_once('=').unwrap();
+            let key = vm.new_gc_string_from_str(key);
+            keys.as_ref_mut().push(Value::from(key)).unwrap();
+        }
+
+        let before = vm.heap_stats();
+        vm.gc_collect();
+        let after = vm.heap_stats();
+        assert!(before.strings > 1);
+        assert_eq!(after.strings, 1);
+        assert!(after.string_bytes < before.string_bytes);
+        // every duplicate took as much of the heap as the copy that is left.
+        assert_eq!(after.deduplicated_strings, KEYS - 1);
+        assert_eq!(after.deduplicated_bytes, (KEYS - 1) * after.string_bytes);
+
+        let first = keys.elements()[0];
+        assert_eq!(keys.elements().len(), KEYS);
+        for key in keys.elements().iter() {
+            assert_eq!(key.addr(), first.addr());
+            assert!(key.is_same_constant(&first));
+            assert_eq!(key.try_into_string().unwrap(), "session_id");
+            assert_eq!(render(key, &RenderOptions::print()), "\"session_id\"");
+        }
+    }
+
+    #[test]
+    fn test_strings_are_folded_wherever_they_are_referenced() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        vm.set_string_dedup(true);
+        let strings = ["a rather long string", "a rather long string", "another one"]
+            .map(|string| Value::from(vm.new_gc_string_from_str(string)));
+        let mut elements = vm.vec_with_capacity(2);
+        elements.extend_from_slice(&strings[1..]);
+        let tuple = vm.new_tuple(elements).unwrap();
+        vm.push_stack(strings[0]);
+        vm.push_stack(Value::from(tuple));
+        let weak = vm.new_weak(strings[1]).unwrap();
+
+        vm.gc_collect();
+        let stats = vm.heap_stats();
+        assert_eq!(stats.strings, 2);
+        assert_eq!(stats.deduplicated_strings, 1);
+        assert_eq!(tuple.elements()[0].addr(), strings[0].addr());
+        assert_eq!(tuple.elements()[1].addr(), strings[2].addr());
+        let referent = vm.weak_get(&weak).unwrap();
+        assert_eq!(referent.and_then(|value| value.addr()), strings[0].addr());
+    }
+
+    #[test]
+    fn test_eval_bindings_are_redirected() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        vm.set_string_dedup(true);
+        let kept = Value::from(vm.new_gc_string_from_str("a rather long string"));
+        let exposed = Value::from(vm.new_gc_string_from_str("a rather long string"));
+        vm.push_stack(kept);
+        vm.push_stack(exposed);
+        vm.expose_to_eval("name", exposed);
+
+        // the first string on the stack is kept, the one the binding shares is released.
+        vm.gc_collect();
+        assert_eq!(vm.heap_stats().deduplicated_strings, 1);
+        let binding = vm.eval.bindings()[0].value;
+        assert_eq!(binding.addr(), kept.addr());
+        assert_eq!(binding.try_into_string().unwrap(), "a rather long string");
+    }
+
+    #[test]
+    fn test_finalizers_are_redirected() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        vm.set_string_dedup(true);
+        let kept = Value::from(vm.new_gc_string_from_str("a rather long string"));
+        let shared = Value::from(vm.new_gc_string_from_str("a rather long string"));
+        vm.push_stack(kept);
+        vm.push_stack(shared);
+        // any value stands in for the finalizer and the instance, they are only traced.
+        let mut elements = vm.vec_with_capacity(1);
+        elements.push(shared);
+        let finalizer = Value::from(vm.new_tuple(elements).unwrap());
+        let mut elements = vm.vec_with_capacity(1);
+        elements.push(shared);
+        let queued = Value::from(vm.new_tuple(elements).unwrap());
+        vm.finalizable_instances.push((Value::Unit, finalizer));
+        vm.pending_finalizers.push((queued, Value::Unit));
+
+        vm.gc_collect();
+        assert_eq!(vm.heap_stats().deduplicated_strings, 1);
+        assert_eq!(finalizer.as_tuple().elements()[0].addr(), kept.addr());
+        assert_eq!(queued.as_tuple().elements()[0].addr(), kept.addr());
+    }
+}
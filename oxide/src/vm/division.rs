@@ -0,0 +1,142 @@
+/// the integer divisions of the language. `/` and `%` truncate toward zero, the remainder has
+/// the sign of the dividend: `-7 / 2` is `-3` and `-7 % 2` is `-1`. `div_floor` and `mod_floor`
+/// round toward negative infinity, the remainder has the sign of the divisor: `(-7).div_floor(2)`
+/// is `-4` and `(-7).mod_floor(2)` is `1`. The compiler folds constants with the same functions
+/// so a folded division is the division the vm would have made.
+///
+/// The checked functions are `None` when the divisor is zero or the quotient does not fit, only
+/// `MIN / -1` does not. A remainder always fits, `MIN % -1` is zero. The wrapping functions
+/// wrap `MIN / -1` to `MIN`, their divisor must not be zero.
+pub trait Division: Sized {
+    fn checked_modulo(self, rhs: Self) -> Option<Self>;
+
+    fn wrapping_modulo(self, rhs: Self) -> Self;
+
+    fn checked_div_floor(self, rhs: Self) -> Option<Self>;
+
+    fn wrapping_div_floor(self, rhs: Self) -> Self;
+
+    fn checked_mod_floor(self, rhs: Self) -> Option<Self>;
+
+    fn wrapping_mod_floor(self, rhs: Self) -> Self;
+}
+
+macro_rules! division {
+    ($($ty:ty), *; $floor:ident) => {
+        $(
+            impl Division for $ty {
+                fn checked_modulo(self, rhs: Self) -> Option<Self> {
+                    if rhs == 0 {
+                        None
+                    } else {
+                        Some(self.wrapping_rem(rhs))
+                    }
+                }
+
+                fn wrapping_modulo(self, rhs: Self) -> Self {
+                    self.wrapping_rem(rhs)
+                }
+
+                fn checked_div_floor(self, rhs: Self) -> Option<Self> {
+                    self.checked_div(rhs)?;
+                    Some(self.wrapping_div_floor(rhs))
+                }
+
+                fn wrapping_div_floor(self, rhs: Self) -> Self {
+                    $floor!(quotient, self, rhs)
+                }
+
+                fn checked_mod_floor(self, rhs: Self) -> Option<Self> {
+                    if rhs == 0 {
+                        None
+                    } else {
+                        Some(self.wrapping_mod_floor(rhs))
+                    }
+                }
+
+                fn wrapping_mod_floor(self, rhs: Self) -> Self {
+                    $floor!(remainder, self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+/// a truncated division that is off by one when the operands have different signs.
+macro_rules! signed_floor {
+    (quotient, $lhs:expr, $rhs:expr) => {{
+        let quotient = $lhs.wrapping_div($rhs);
+        let remainder = $lhs.wrapping_rem($rhs);
+        if remainder != 0 && (remainder < 0) != ($rhs < 0) {
+            quotient - 1
+        } else {
+            quotient
+        }
+    }};
+    (remainder, $lhs:expr, $rhs:expr) => {{
+        let remainder = $lhs.wrapping_rem($rhs);
+        if remainder != 0 && (remainder < 0) != ($rhs < 0) {
+            remainder + $rhs
+        } else {
+            remainder
+        }
+    }};
+}
+
+/// without a sign rounding toward zero is rounding down.
+macro_rules! unsigned_floor {
+    (quotient, $lhs:expr, $rhs:expr) => {
+        $lhs.wrapping_div($rhs)
+    };
+    (remainder, $lhs:expr, $rhs:expr) => {
+        $lhs.wrapping_rem($rhs)
+    };
+}
+
+division!(i8, i16, i32, i64; signed_floor);
+division!(u8, u16, u32, u64; unsigned_floor);
+
+#[cfg(test)]
+mod tests {
+    use super::Division;
+
+    #[test]
+    fn test_every_sign_combination() {
+        // (dividend, divisor, truncated quotient, remainder, floored quotient, floored remainder)
+        let cases: [(i64, i64, i64, i64, i64, i64); 8] = [
+            (7, 2, 3, 1, 3, 1),
+            (-7, 2, -3, -1, -4, 1),
+            (7, -2, -3, 1, -4, -1),
+            (-7, -2, 3, -1, 3, -1),
+            (6, 2, 3, 0, 3, 0),
+            (-6, 2, -3, 0, -3, 0),
+            (6, -2, -3, 0, -3, 0),
+            (-6, -2, 3, 0, 3, 0),
+        ];
+        for (lhs, rhs, div, rem, div_floor, mod_floor) in cases {
+            assert_eq!(lhs.checked_div(rhs), Some(div), "{} / {}", lhs, rhs);
+            assert_eq!(lhs.checked_modulo(rhs), Some(rem), "{} % {}", lhs, rhs);
+            assert_eq!(lhs.checked_div_floor(rhs), Some(div_floor), "{} div_floor {}", lhs, rhs);
+            assert_eq!(lhs.checked_mod_floor(rhs), Some(mod_floor), "{} mod_floor {}", lhs, rhs);
+            // a quotient and its remainder give back the dividend.
+            assert_eq!(div * rhs + rem, lhs);
+            assert_eq!(div_floor * rhs + mod_floor, lhs);
+        }
+        assert_eq!(7u8.checked_div_floor(2), Some(3));
+        assert_eq!(7u8.checked_mod_floor(2), Some(1));
+    }
+
+    #[test]
+    fn test_overflow_and_zero() {
+        assert_eq!(i64::MIN.checked_div_floor(-1), None);
+        assert_eq!(i64::MIN.wrapping_div_floor(-1), i64::MIN);
+        assert_eq!(i64::MIN.checked_modulo(-1), Some(0));
+        assert_eq!(i64::MIN.checked_mod_floor(-1), Some(0));
+        assert_eq!(i8::MIN.checked_div_floor(3), Some(-43));
+        assert_eq!(i8::MIN.checked_mod_floor(3), Some(1));
+        assert_eq!(i8::MAX.checked_mod_floor(i8::MIN), Some(-1));
+        assert_eq!(1i32.checked_div_floor(0), None);
+        assert_eq!(1i32.checked_mod_floor(0), None);
+        assert_eq!(1u32.checked_modulo(0), None);
+    }
+}
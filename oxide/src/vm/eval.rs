@@ -0,0 +1,232 @@
+use crate::{gc::Gc, OxModule, Value, Vm};
+
+use super::Execution;
+
+/// how many evaluated scripts can be running at once, an evaluated script may call `eval`
+/// itself.
+pub const MAX_EVAL_DEPTH: usize = 8;
+
+/// the instructions an evaluated script runs before it is stopped, unless the host gives it
+/// another budget.
+pub const DEFAULT_EVAL_FUEL: u64 = 1_000_000;
+
+/// compiles the source scripts give to `eval`. The vm does not know the language, the host
+/// embedding it wires up its compiler with `Vm::set_eval_compiler`.
+pub trait EvalCompiler {
+    /// compiles source as a module whose entry function takes the values of bindings as its
+    /// parameters, in order, and returns the value of the script. The error is the message the
+    /// script is given.
+    fn compile(
+        &mut self,
+        vm: &mut Vm,
+        source: &str,
+        bindings: &[EvalBinding],
+    ) -> Result<Gc<OxModule>, String>;
+}
+
+/// a value of the host evaluated scripts read by name, they can not assign it.
+#[derive(Debug, Clone)]
+pub struct EvalBinding {
+    pub name: String,
+    pub value: Value,
+}
+
+pub(crate) struct Eval {
+    compiler: Option<Box<dyn EvalCompiler>>,
+    bindings: Vec<EvalBinding>,
+    fuel: u64,
+    /// the evaluated scripts that are running.
+    depth: usize,
+}
+
+impl Default for Eval {
+    fn default() -> Self {
+        Self {
+            compiler: None,
+            bindings: vec![],
+            fuel: DEFAULT_EVAL_FUEL,
+            depth: 0,
+        }
+    }
+}
+
+impl Eval {
+    /// the exposed values, they are roots of the heap.
+    pub(super) fn bindings(&self) -> &[EvalBinding] {
+        self.bindings.as_slice()
+    }
+
+    pub(super) fn bindings_mut(&mut self) -> &mut [EvalBinding] {
+        self.bindings.as_mut_slice()
+    }
+}
+
+impl Vm {
+    /// the compiler of the source given to `eval`, a script calling it is given an error until
+    /// one is set. Scripts only evaluate source when `Capability::Eval` is granted.
+    pub fn set_eval_compiler(&mut self, compiler: Box<dyn EvalCompiler>) {
+        self.eval.compiler = Some(compiler);
+    }
+
+    /// exposes value to evaluated scripts as name, a name exposed again has the new value.
+    /// Nothing else of the running module can be reached from an evaluated script.
+    pub fn expose_to_eval(&mut self, name: &str, value: Value) {
+        match self
+            .eval
+            .bindings
+            .iter_mut()
+            .find(|binding| binding.name == name)
+        {
+            Some(binding) => binding.value = value,
+            None => self.eval.bindings.push(EvalBinding {
+                name: name.to_owned(),
+                value,
+            }),
+        }
+    }
+
+    /// the instructions each evaluated script may run, `DEFAULT_EVAL_FUEL` unless it is set.
+    pub fn set_eval_fuel(&mut self, fuel: u64) {
+        self.eval.fuel = fuel;
+    }
+
+    /// compiles source and runs it on top of the running frames with the exposed values as
+    /// its parameters. The value of the script, or the message of the error that stopped it
+    /// from compiling or completing, a script that fails leaves the vm as it was.
+    pub(super) fn eval(&mut self, source: &str) -> Result<Value, String> {
+        if self.eval.depth >= MAX_EVAL_DEPTH {
+            return Err(format!("eval is nested more than {} deep", MAX_EVAL_DEPTH));
+        }
+        // taken while compiling, the script it compiles can call eval again once it runs.
+        let mut compiler = self
+            .eval
+            .compiler
+            .take()
+            .ok_or_else(|| "no compiler is set for eval".to_string())?;
+        let bindings = self.eval.bindings.clone();
+        let compiled = compiler.compile(self, source, bindings.as_slice());
+        self.eval.compiler = Some(compiler);
+        let module = compiled?;
+        let entry = *module
+            .entry()
+            .ok_or_else(|| "the evaluated script has no entry".to_string())?;
+
+        let base_stack = self.top_stack;
+        let base_frame = std::mem::replace(&mut self.base_frame, self.top_frame);
+        self.eval.depth += 1;
+        // the module is kept on the stack so it is not collected while it runs.
+        self.push_stack(Value::from(module));
+        self.push_stack(entry);
+        for binding in bindings.iter() {
+            self.push_stack(binding.value);
+        }
+        let fuel = self.eval.fuel;
+        let result = self
+            .call_value(&entry, bindings.len() as u8)
+            .and_then(|_| self.execute(Some(fuel)));
+        let result = match result {
+            Ok(Execution::Completed) => Ok(self.pop()),
+            Ok(Execution::Yielded) => Err(format!(
+                "the evaluated script ran out of fuel after {} instructions",
+                fuel
+            )),
+            Ok(Execution::Blocked) => Err("a native blocked the evaluated script".to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        self.top_frame = self.base_frame;
+        self.top_stack = base_stack;
+        self.base_frame = base_frame;
+        self.eval.depth -= 1;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EvalBinding, EvalCompiler, MAX_EVAL_DEPTH};
+    use crate::{
+        gc::Gc,
+        runtime,
+        vm::{Capability, OpCode},
+        OxModule, Value, Vm,
+    };
+
+    /// a module whose entry evaluates the source "again" and returns what it is given.
+    fn evaluating_module(vm: &mut Vm) -> Gc<OxModule> {
+        vm.force_no_collection(true);
+        let mut section = vm.new_section();
+        let source = Value::from(vm.new_gc_string_from_str("again"));
+        let source = section.add_constant(source);
+        section.write_index(OpCode::LoadStr, source);
+        section.write_op(OpCode::Eval);
+        section.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("eval");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        vm.force_no_collection(false);
+        module
+    }
+
+    /// compiles every source to a script that evaluates again.
+    struct Recursive;
+
+    impl EvalCompiler for Recursive {
+        fn compile(
+            &mut self,
+            vm: &mut Vm,
+            _source: &str,
+            _bindings: &[EvalBinding],
+        ) -> Result<Gc<OxModule>, String> {
+            Ok(evaluating_module(vm))
+        }
+    }
+
+    #[test]
+    fn test_eval_depth_is_limited() {
+        let mut vm = Vm::new();
+        vm.grant(Capability::Eval);
+        vm.set_eval_compiler(Box::new(Recursive));
+        let module = evaluating_module(&mut vm);
+        vm.run_module(module).unwrap();
+
+        // each script returns the result of the one it evaluated, until one is refused.
+        let mut result = *vm.top();
+        let mut depth = 0;
+        let error = loop {
+            let elements = result.as_tuple().elements();
+            match elements[1] {
+                Value::Unit => result = elements[0],
+                error => break error,
+            }
+            depth += 1;
+        };
+        assert_eq!(depth, MAX_EVAL_DEPTH);
+        assert_eq!(
+            error.as_string().as_str(),
+            "eval is nested more than 8 deep"
+        );
+    }
+
+    #[test]
+    fn test_eval_without_a_compiler_or_capability() {
+        let mut vm = Vm::new();
+        let module = evaluating_module(&mut vm);
+        let err = vm.run_module(module).unwrap_err();
+        assert!(matches!(err, runtime::Error::CapabilityNotGranted(_)));
+        assert_eq!(err.to_string(), "capability not granted: eval");
+
+        let mut vm = Vm::new();
+        vm.grant(Capability::Eval);
+        let module = evaluating_module(&mut vm);
+        vm.run_module(module).unwrap();
+        let elements = vm.top().as_tuple().elements();
+        assert!(matches!(elements[0], Value::Unit));
+        assert_eq!(
+            elements[1].as_string().as_str(),
+            "no compiler is set for eval"
+        );
+    }
+}
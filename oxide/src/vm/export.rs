@@ -0,0 +1,151 @@
+use crate::{
+    compiled::{ApiFunction, ApiItem, ApiKind},
+    gc::Gc,
+    runtime, OxFunction, OxModule, Value,
+};
+
+use super::{reload::find_function, Vm};
+
+/// the types of parameters an argument is checked against, those a value names itself. Other
+/// parameters, structures and lists among them, take any value.
+const CHECKED_TYPES: [&str; 13] = [
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64", "bool", "string", "char",
+];
+
+/// a function of the running module resolved once so the host can call it without looking it
+/// up by name each time. A handle belongs to the module it was looked up in and is refused
+/// once another module is entered or any function of the module is replaced, it is looked up
+/// again then.
+#[derive(Debug, Clone)]
+pub struct ExportedFn {
+    name: String,
+    module: Gc<OxModule>,
+    function: Gc<OxFunction>,
+    signature: Option<ApiFunction>,
+    /// the type each argument must have, none for a parameter taking any value.
+    checks: Vec<Option<&'static str>>,
+    /// the replacements made in the vm when the handle was looked up.
+    generation: u64,
+}
+
+impl ExportedFn {
+    pub fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    pub fn arity(&self) -> u8 {
+        self.function.arity()
+    }
+
+    /// the declared signature, none when the module was not loaded from a compiled module with
+    /// an api.
+    pub fn signature(&self) -> Option<&ApiFunction> {
+        self.signature.as_ref()
+    }
+
+    /// calls the function with args and returns the value it returns. The handle, the number
+    /// of arguments and the types of those with a primitive parameter are checked first.
+    pub fn call(&self, vm: &mut Vm, args: &[Value]) -> Result<Value, runtime::Error> {
+        let running = vm.running.map(|module| module.ptr());
+        if running != Some(self.module.ptr()) || vm.replacements != self.generation {
+            return Err(runtime::Error::stale_export(self.name.as_str()));
+        }
+        if vm.top_frame != 0 {
+            return Err(runtime::Error::call_while_running(self.name.as_str()));
+        }
+        if args.len() != self.arity() as usize {
+            let err = runtime::Error::export_arity(self.name.as_str(), self.arity(), args.len());
+            return Err(err);
+        }
+        for (index, (arg, check)) in args.iter().zip(self.checks.iter()).enumerate() {
+            match check {
+                Some(expected) if arg.ty() != *expected => {
+                    let name = self.name.as_str();
+                    return Err(runtime::Error::export_argument(name, index, expected, arg.ty()));
+                }
+                _ => {}
+            }
+        }
+        self.call_unchecked(vm, args)
+    }
+
+    /// calls the function without checking the handle or the arguments.
+    ///
+    /// The caller upholds what the verifier does for code calling the function: the handle is
+    /// not stale, no function of the vm is running, there is one argument for each parameter
+    /// and each has the type of its parameter. A stale handle runs the body of the function it
+    /// was looked up for and the wrong arguments are read as the values they are not, neither
+    /// is detected.
+    pub fn call_unchecked(&self, vm: &mut Vm, args: &[Value]) -> Result<Value, runtime::Error> {
+        let base = vm.top_stack;
+        let function = Value::from(self.function);
+        vm.push_stack(function);
+        for arg in args {
+            vm.push_stack(*arg);
+        }
+        let result = vm
+            .call_value(&function, args.len() as u8)
+            .and_then(|_| vm.run());
+        match result {
+            Ok(()) => Ok(vm.pop()),
+            // the frames of the failed call are dropped so the handle can be called again.
+            Err(err) => {
+                vm.top_frame = 0;
+                vm.top_stack = base;
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Vm {
+    /// the function of the running module named like it is in a source map, `Point.len` for a
+    /// method, as a handle calling it without the name being looked up again.
+    pub fn lookup_export(&self, name: &str) -> Option<ExportedFn> {
+        let module = self.running?;
+        let function = find_function(module, name)?;
+        let signature = module
+            .layout()
+            .and_then(|layout| layout.api.as_ref())
+            .and_then(|api| find_signature(&api.items, name));
+        let checks = match &signature {
+            Some(signature) if signature.params.len() == function.arity() as usize => signature
+                .params
+                .iter()
+                .map(|param| CHECKED_TYPES.iter().copied().find(|ty| ty == param))
+                .collect(),
+            _ => vec![None; function.arity() as usize],
+        };
+        Some(ExportedFn {
+            name: name.to_owned(),
+            module,
+            function,
+            signature,
+            checks,
+            generation: self.replacements,
+        })
+    }
+
+    /// calls the function of the running module named name, it is looked up for every call.
+    pub fn call_by_name(&mut self, name: &str, args: &[Value]) -> Result<Value, runtime::Error> {
+        self.lookup_export(name)
+            .ok_or_else(|| runtime::Error::unknown_export(name))?
+            .call(self, args)
+    }
+}
+
+fn find_signature(items: &[ApiItem], name: &str) -> Option<ApiFunction> {
+    let (structure, name) = match name.rsplit_once('.') {
+        Some((structure, method)) => (Some(structure), method),
+        None => (None, name),
+    };
+    items
+        .iter()
+        .find_map(|item| match (&item.kind, structure) {
+            (ApiKind::Function(function), None) if item.name == name => Some(function.clone()),
+            (ApiKind::Struct { methods, .. }, Some(structure)) if item.name == structure => {
+                find_signature(methods, name)
+            }
+            _ => None,
+        })
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use crate::{gc::Gc, OxString, Vm};
+
+/// the strings of the literals compiled or loaded into the vm, by contents. The table does not
+/// keep its strings alive, a string is dropped from it once a collection finds it unreachable.
+#[derive(Default)]
+pub(crate) struct Interned {
+    strings: HashMap<String, Gc<OxString>>,
+}
+
+impl Vm {
+    /// the string with the contents of value, every literal with the same contents is the same
+    /// object so comparing them is a pointer compare.
+    pub fn intern_string(&mut self, value: &str) -> Gc<OxString> {
+        if let Some(string) = self.interned.strings.get(value) {
+            return *string;
+        }
+        let string = self.new_gc_string_from_str(value);
+        self.interned.strings.insert(value.to_owned(), string);
+        string
+    }
+
+    /// the number of strings in the table.
+    pub fn interned_strings(&self) -> usize {
+        self.interned.strings.len()
+    }
+
+    /// the strings in the table, deduplication folds other strings into them.
+    pub(super) fn interned(&self) -> impl Iterator<Item = &Gc<OxString>> {
+        self.interned.strings.values()
+    }
+
+    /// drops the strings that were not reached while marking, before they are swept.
+    pub(super) fn clear_unreachable_interned(&mut self) {
+        self.interned
+            .strings
+            .retain(|_, string| string.ptr().cell().marked);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Value, Vm};
+
+    #[test]
+    fn test_literals_share_one_string() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let first = vm.intern_string("hello");
+        let second = vm.intern_string("hello");
+        let other = vm.intern_string("world");
+        assert_eq!(first.ptr(), second.ptr());
+        assert_ne!(first.ptr(), other.ptr());
+        assert_eq!(vm.interned_strings(), 2);
+    }
+
+    #[test]
+    fn test_unreachable_strings_leave_the_table() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let kept = vm.intern_string("kept");
+        vm.intern_string("dropped");
+        vm.push_stack(Value::from(kept));
+
+        vm.gc_collect();
+        assert_eq!(vm.interned_strings(), 1);
+        assert_eq!(vm.heap_stats().strings, 1);
+        assert_eq!(vm.intern_string("kept").ptr(), kept.ptr());
+        // a literal interned again after it was collected is a new string.
+        vm.intern_string("dropped");
+        assert_eq!(vm.interned_strings(), 2);
+    }
+}
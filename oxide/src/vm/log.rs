@@ -0,0 +1,170 @@
+use std::fmt::{Display, Formatter};
+
+/// severity of a message logged by a script, from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn from_u8(level: u8) -> Option<Self> {
+        match level {
+            0 => Some(Self::Debug),
+            1 => Some(Self::Info),
+            2 => Some(Self::Warn),
+            3 => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// the level of the `log` function with the given name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" => Some(Self::Warn),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// a message logged by a script and where it was logged from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogRecord<'a> {
+    pub level: LogLevel,
+    pub message: &'a str,
+    pub function: &'a str,
+    /// only known when a source map has been loaded.
+    pub file: Option<&'a str>,
+    pub line: Option<usize>,
+}
+
+/// receives the messages logged by scripts, installed with `Vm::set_log_handler`.
+pub trait LogHandler {
+    fn log(&mut self, record: &LogRecord);
+}
+
+/// writes every message to stdout, prefixed with its level and location.
+pub struct StdoutLogHandler;
+
+impl LogHandler for StdoutLogHandler {
+    fn log(&mut self, record: &LogRecord) {
+        let file = record.file.unwrap_or(record.function);
+        match record.line {
+            Some(line) => println!("[{}] {}:{}: {}", record.level, file, line, record.message),
+            None => println!("[{}] {}: {}", record.level, file, record.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogHandler, LogLevel, LogRecord};
+    use crate::{
+        vm::{NativeResult, OpCode},
+        Value, Vm,
+    };
+    use std::{cell::RefCell, rc::Rc};
+
+    type Records = Rc<RefCell<Vec<(LogLevel, String, String, Option<usize>)>>>;
+
+    struct Recorder(Records);
+
+    impl LogHandler for Recorder {
+        fn log(&mut self, record: &LogRecord) {
+            self.0.borrow_mut().push((
+                record.level,
+                record.message.to_string(),
+                record.function.to_string(),
+                record.line,
+            ));
+        }
+    }
+
+    #[test]
+    fn test_log_levels_and_locations() {
+        let records = Records::default();
+        let evaluated = Rc::new(RefCell::new(0));
+        let mut vm = Vm::new();
+        vm.set_log_handler(Box::new(Recorder(records.clone())));
+        vm.set_log_level(LogLevel::Info);
+        vm.force_no_collection(true);
+
+        // the message of every log is produced by a native so skipped messages can be counted.
+        let counter = evaluated.clone();
+        let message = vm.register_native(
+            "message",
+            0,
+            Box::new(move |vm, _| {
+                *counter.borrow_mut() += 1;
+                let message = vm.new_gc_string_from_str("message");
+                Ok(NativeResult::Return(Value::from(message)))
+            }),
+        );
+
+        let mut section = vm.new_section();
+        let global = section.add_global();
+        section.set_global(global as usize, message);
+        for (line, level) in [LogLevel::Debug, LogLevel::Info, LogLevel::Error]
+            .iter()
+            .enumerate()
+        {
+            section.mark_line(line + 1);
+            section.write_arg(OpCode::LogEnabled, *level as u8);
+            let skip = section.write_jmp(OpCode::JmpFalse);
+            section.write_index(OpCode::LoadGlobal, global);
+            section.write_arg(OpCode::Call, 0);
+            section.write_arg(OpCode::Log, *level as u8);
+            section.patch_jmp(skip);
+        }
+        section.write_op(OpCode::LoadTrue);
+        section.write_op(OpCode::Return);
+
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("logging");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        vm.force_no_collection(false);
+
+        vm.run_module(module).unwrap();
+        assert_eq!(*evaluated.borrow(), 2);
+        assert_eq!(
+            *records.borrow(),
+            vec![
+                (
+                    LogLevel::Info,
+                    "message".to_string(),
+                    "main".to_string(),
+                    Some(2)
+                ),
+                (
+                    LogLevel::Error,
+                    "message".to_string(),
+                    "main".to_string(),
+                    Some(3)
+                ),
+            ]
+        );
+    }
+}
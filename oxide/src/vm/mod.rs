@@ -1,64 +1,161 @@
+mod bench;
 mod call_frame;
+mod capability;
+mod dedup;
+mod division;
+mod eval;
+mod export;
+mod intern;
+mod log;
+mod native;
 mod op_codes;
+mod output;
+mod reload;
+mod render;
+mod scratch;
+mod snapshot;
+mod task;
+mod trace;
+mod weak;
 
-use std::alloc::Layout;
+use std::{alloc::Layout, convert::TryFrom, path::Path, sync::Arc};
 
 use crate::{
+    compiled::CompiledModule,
+    coverage::Coverage,
     gc::{Address, Gc, GcAlloc, Object, ObjectKind, VecAllocator},
+    convert::IntoValue,
+
     mem::read_to,
-    runtime, OxInstance, OxModule, OxString, OxTuple, OxVec, Section, Value,
+    runtime,
+    source_map::SourceMap,
+    OxClosure, OxInstance, OxList, OxMap, OxModule, OxString, OxTuple, OxUserData, OxVec, Section,
+    Value,
 };
+pub use bench::{BenchConfig, BenchReport, Budget, Samples};
 use call_frame::CallFrame;
+pub use capability::{Capabilities, Capability, CapabilityKind};
+use dedup::Deduplicated;
+pub use dedup::HeapStats;
+pub use division::Division;
+use eval::Eval;
+pub use eval::{EvalBinding, EvalCompiler, DEFAULT_EVAL_FUEL, MAX_EVAL_DEPTH};
+pub use export::ExportedFn;
+use intern::Interned;
+pub use log::{LogHandler, LogLevel, LogRecord, StdoutLogHandler};
+use native::Native;
+use scratch::Scratch;
+pub use native::{NativeFunction, NativeResult};
 pub use op_codes::{Instruction, OpCode};
+use output::Output;
+pub use output::IoErrorPolicy;
 use ordered_float::OrderedFloat;
 use runtime::{AttributeAccess, OxFunction, OxStruct};
+pub use render::{render, RenderOptions};
+pub use scratch::{ListBuilder, StringBuilder};
+pub use snapshot::{ErrorCaptureLevel, ErrorSnapshot, FrameSnapshot};
+pub use task::{Task, TaskEntry};
+use trace::Trace;
+use weak::WeakRefs;
+pub use weak::WeakHandle;
 
 static DEFAULT_STACK_SIZE: usize = 2056;
 
+/// the frames a vm runs at once until it is given another limit, see `Vm::set_max_frames`.
+pub const DEFAULT_MAX_FRAMES: usize = 1024;
+
+/// the functions named by a stack overflow error, the frames past them are elided.
+const OVERFLOW_FRAMES: usize = 8;
+
+/// the host a vm identifies itself by until it is given one, see `Vm::set_host`.
+pub const DEFAULT_HOST: &str = "cli";
+
+/// releases the host resource behind a userdata object once the script can no longer reach it.
+pub type Finalizer = Box<dyn FnMut(&mut Vm, Gc<OxUserData>) -> Result<(), String>>;
+
+/// receives the errors returned by finalizers, a failing finalizer does not stop the collection.
+pub type FinalizerErrorHandler = Box<dyn FnMut(String)>;
+
+/// outcome of running the vm for a bounded number of instructions.
+#[derive(Debug, Clone)]
+pub enum SliceResult {
+    /// the entry function returned with the value.
+    Completed(Value),
+    /// the fuel ran out, calling `run_slice` again continues where this slice stopped.
+    Yielded,
+    /// a native could not make progress, the call is retried by the next `run_slice`.
+    Blocked,
+    Errored(runtime::Error),
+}
+
+/// why the interpreter loop stopped.
+enum Execution {
+    Completed,
+    Yielded,
+    Blocked,
+}
+
+struct UserDataType {
+    name: String,
+    finalizer: Option<Finalizer>,
+}
+
+/// an integer operation that fails on division by zero, an overflow wraps unless the vm traps.
+macro_rules! checked_integer_op {
+    ($vm:expr, $lhs:expr, $rhs:expr, $checked:ident, $wrapping:ident, $ty:literal) => {
+        match $lhs.$checked($rhs) {
+            Some(value) => Value::from(value),
+            None if $rhs == 0 => return Err(runtime::Error::DivisionByZero),
+            None if $vm.overflow_traps => return Err(runtime::Error::integer_overflow($ty)),
+            None => Value::from($lhs.$wrapping($rhs)),
+        }
+    };
+}
+
 macro_rules! binary_op {
-    ($name:ident, $start_op:ident, $op:tt) => {
-        fn $name(&mut self, op: OpCode) -> Value {
+    ($name:ident, $start_op:ident, $op:tt, $checked:ident, $wrapping:ident) => {
+        fn $name(&mut self, op: OpCode) -> Result<Value, runtime::Error> {
             let type_index = op as u8 - OpCode::$start_op as u8;
-            match type_index {
+            let value = match type_index {
                 0 => {
                     let rhs = self.pop().as_i8();
                     let lhs = self.pop().as_i8();
-                    Value::from(lhs $op rhs)
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "i8")
                 }
                 1 => {
                     let rhs = self.pop().as_i16();
                     let lhs = self.pop().as_i16();
-                    Value::from(lhs $op rhs)
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "i16")
                 }
                 2 => {
                     let rhs = self.pop().as_i32();
                     let lhs = self.pop().as_i32();
-                    Value::from(lhs $op rhs)
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "i32")
                 }
                 3 => {
                     let rhs = self.pop().as_i64();
                     let lhs = self.pop().as_i64();
-                    Value::from(lhs $op rhs)
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "i64")
                 }
                 4 => {
                     let rhs = self.pop().as_u8();
                     let lhs = self.pop().as_u8();
-                    Value::from(lhs $op rhs)
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "u8")
                 }
                 5 => {
                     let rhs = self.pop().as_u16();
                     let lhs = self.pop().as_u16();
-                    Value::from(lhs $op rhs)
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "u16")
                 }
                 6 => {
                     let rhs = self.pop().as_u32();
                     let lhs = self.pop().as_u32();
-                    Value::from(lhs $op rhs)
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "u32")
                 }
                 7 => {
                     let rhs = self.pop().as_u64();
                     let lhs = self.pop().as_u64();
-                    Value::from(lhs $op rhs)
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "u64")
                 }
                 8 => {
                     let rhs = self.pop().as_f32();
@@ -71,7 +168,8 @@ macro_rules! binary_op {
                     Value::from(lhs $op rhs)
                 }
                 _ => panic!("Invalid Opcode {} for {}", op, stringify!($name)),
-            }
+            };
+            Ok(value)
         }
     };
 }
@@ -138,16 +236,167 @@ macro_rules! conditional_binary_op {
     };
 }
 
-macro_rules! load_constant {
-    ($cond:ident, $name:literal, $self:expr) => {
-        let frame = $self.frame_mut();
-        let idx = {
-            let section = frame.section();
-            section.read(frame.ip)
-        };
+/// binary_op for the operators that are only defined on integers.
+macro_rules! integer_binary_op {
+    ($name:ident, $start_op:ident, $op:tt) => {
+        fn $name(&mut self, op: OpCode) -> Value {
+            let type_index = op as u8 - OpCode::$start_op as u8;
+            match type_index {
+                0 => {
+                    let rhs = self.pop().as_i8();
+                    let lhs = self.pop().as_i8();
+                    Value::from(lhs $op rhs)
+                }
+                1 => {
+                    let rhs = self.pop().as_i16();
+                    let lhs = self.pop().as_i16();
+                    Value::from(lhs $op rhs)
+                }
+                2 => {
+                    let rhs = self.pop().as_i32();
+                    let lhs = self.pop().as_i32();
+                    Value::from(lhs $op rhs)
+                }
+                3 => {
+                    let rhs = self.pop().as_i64();
+                    let lhs = self.pop().as_i64();
+                    Value::from(lhs $op rhs)
+                }
+                4 => {
+                    let rhs = self.pop().as_u8();
+                    let lhs = self.pop().as_u8();
+                    Value::from(lhs $op rhs)
+                }
+                5 => {
+                    let rhs = self.pop().as_u16();
+                    let lhs = self.pop().as_u16();
+                    Value::from(lhs $op rhs)
+                }
+                6 => {
+                    let rhs = self.pop().as_u32();
+                    let lhs = self.pop().as_u32();
+                    Value::from(lhs $op rhs)
+                }
+                7 => {
+                    let rhs = self.pop().as_u64();
+                    let lhs = self.pop().as_u64();
+                    Value::from(lhs $op rhs)
+                }
+                _ => panic!("Invalid Opcode {} for {}", op, stringify!($name)),
+            }
+        }
+    };
+}
 
-        frame.ip += 1;
-        let value = frame.section().get_constant(idx as usize);
+/// binary_op for the shifts, the amount is an integer of the same type as the value.
+macro_rules! shift_binary_op {
+    ($name:ident, $start_op:ident, $shift:ident) => {
+        fn $name(&mut self, op: OpCode) -> Value {
+            let type_index = op as u8 - OpCode::$start_op as u8;
+            match type_index {
+                0 => {
+                    let rhs = self.pop().as_i8();
+                    let lhs = self.pop().as_i8();
+                    Value::from(lhs.$shift(rhs as u32))
+                }
+                1 => {
+                    let rhs = self.pop().as_i16();
+                    let lhs = self.pop().as_i16();
+                    Value::from(lhs.$shift(rhs as u32))
+                }
+                2 => {
+                    let rhs = self.pop().as_i32();
+                    let lhs = self.pop().as_i32();
+                    Value::from(lhs.$shift(rhs as u32))
+                }
+                3 => {
+                    let rhs = self.pop().as_i64();
+                    let lhs = self.pop().as_i64();
+                    Value::from(lhs.$shift(rhs as u32))
+                }
+                4 => {
+                    let rhs = self.pop().as_u8();
+                    let lhs = self.pop().as_u8();
+                    Value::from(lhs.$shift(rhs as u32))
+                }
+                5 => {
+                    let rhs = self.pop().as_u16();
+                    let lhs = self.pop().as_u16();
+                    Value::from(lhs.$shift(rhs as u32))
+                }
+                6 => {
+                    let rhs = self.pop().as_u32();
+                    let lhs = self.pop().as_u32();
+                    Value::from(lhs.$shift(rhs))
+                }
+                7 => {
+                    let rhs = self.pop().as_u64();
+                    let lhs = self.pop().as_u64();
+                    Value::from(lhs.$shift(rhs as u32))
+                }
+                _ => panic!("Invalid Opcode {} for {}", op, stringify!($name)),
+            }
+        }
+    };
+}
+
+/// binary_op for the divisions that are only defined on integers.
+macro_rules! checked_integer_binary_op {
+    ($name:ident, $start_op:ident, $checked:ident, $wrapping:ident) => {
+        fn $name(&mut self, op: OpCode) -> Result<Value, runtime::Error> {
+            let type_index = op as u8 - OpCode::$start_op as u8;
+            let value = match type_index {
+                0 => {
+                    let rhs = self.pop().as_i8();
+                    let lhs = self.pop().as_i8();
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "i8")
+                }
+                1 => {
+                    let rhs = self.pop().as_i16();
+                    let lhs = self.pop().as_i16();
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "i16")
+                }
+                2 => {
+                    let rhs = self.pop().as_i32();
+                    let lhs = self.pop().as_i32();
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "i32")
+                }
+                3 => {
+                    let rhs = self.pop().as_i64();
+                    let lhs = self.pop().as_i64();
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "i64")
+                }
+                4 => {
+                    let rhs = self.pop().as_u8();
+                    let lhs = self.pop().as_u8();
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "u8")
+                }
+                5 => {
+                    let rhs = self.pop().as_u16();
+                    let lhs = self.pop().as_u16();
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "u16")
+                }
+                6 => {
+                    let rhs = self.pop().as_u32();
+                    let lhs = self.pop().as_u32();
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "u32")
+                }
+                7 => {
+                    let rhs = self.pop().as_u64();
+                    let lhs = self.pop().as_u64();
+                    checked_integer_op!(self, lhs, rhs, $checked, $wrapping, "u64")
+                }
+                _ => panic!("Invalid Opcode {} for {}", op, stringify!($name)),
+            };
+            Ok(value)
+        }
+    };
+}
+
+macro_rules! load_constant {
+    ($cond:ident, $name:literal, $self:expr, $wide:expr) => {
+        let idx = $self.read_index($wide);
+        let value = $self.frame().section().get_constant(idx);
         if value.$cond() {
             $self.push_stack(value);
         } else {
@@ -156,6 +405,9 @@ macro_rules! load_constant {
     };
 }
 
+/// a vm and the objects on its heap belong to the thread that created them, the handles are not
+/// synchronized. To run the same script on several threads share a `CompiledModule` and give
+/// each thread its own vm.
 pub struct Vm {
     allocator: GcAlloc,
     stack: Vec<Value>,
@@ -164,26 +416,134 @@ pub struct Vm {
     registers: [Value; 8],
     top_stack: usize,
     top_frame: usize,
+    /// the frames that can run at once, a call past it is a stack overflow.
+    max_frames: usize,
+    /// the stack when the running module was entered, a failed run is dropped back to it.
+    run_base: usize,
+    /// whether the last run failed, its frames are kept for the host until a module is entered.
+    run_failed: bool,
+    /// the frames below it belong to the run that started the current one, `eval` runs the
+    /// script it compiled on top of them.
+    base_frame: usize,
     no_collection: bool,
+    /// whether every allocation of an object collects first.
+    gc_stress: bool,
+    /// the collections run since the vm was created.
+    collections: u64,
     code_gen: bool, // the vm is in code gen mode
     locals: bool,   // these values are locals and not currently on the stack
     pub compiler_address: Vec<Address>,
+    /// external debug info for modules compiled without it.
+    source_map: Option<SourceMap>,
+    /// referents of weak handles, an entry is cleared once its object is collected.
+    weak_refs: WeakRefs,
+    userdata_types: Vec<UserDataType>,
+    /// userdata objects whose finalizer has not run yet.
+    finalizable: Vec<Address>,
+    /// instances of structures finalized by the script and the function finalizing each.
+    finalizable_instances: Vec<(Value, Value)>,
+    /// unreachable instances whose finalizer has not run yet, they are kept alive until it has.
+    pending_finalizers: Vec<(Value, Value)>,
+    /// set while the queued finalizers run, the instances they drop are finalized by the loop
+    /// already running them.
+    running_finalizers: bool,
+    finalizer_error: Option<FinalizerErrorHandler>,
+    natives: Vec<Native>,
+    error_capture: ErrorCaptureLevel,
+    /// state of the vm when the last runtime error occurred.
+    error_snapshot: Option<ErrorSnapshot>,
+    /// lines executed while coverage is enabled.
+    coverage: Option<Coverage>,
+    log_handler: Box<dyn LogHandler>,
+    /// the destination of `echo`.
+    output: Output,
+    /// where executed instructions are written while tracing is enabled.
+    trace: Option<Trace>,
+    /// compiles and runs the source given to `eval`.
+    eval: Eval,
+    /// messages below this level are skipped before their message is evaluated.
+    log_level: LogLevel,
+    /// how values printed by `echo` and `log` are rendered.
+    print_options: RenderOptions,
+    /// whether integer overflow is a runtime error rather than wrapping.
+    overflow_traps: bool,
+    /// the module entered last, functions are replaced in it.
+    running: Option<Gc<OxModule>>,
+    /// the functions replaced in the running modules, the handles of exported functions record
+    /// it to be refused once a function they may call is replaced.
+    replacements: u64,
+    /// the running module shared with the tasks it spawns, compiled by the first spawn.
+    task_module: Option<Arc<CompiledModule>>,
+    /// the tasks spawned by scripts, a task is taken once it is joined.
+    tasks: Vec<Option<Task>>,
+    /// userdata type of task handles, registered by the first spawn.
+    task_type: Option<usize>,
+    /// buffers of the objects natives are building, a slot is cleared once its builder is finished.
+    scratch: Vec<Option<Scratch>>,
+    /// whether collections fold the live strings with the same contents.
+    string_dedup: bool,
+    deduplicated: Deduplicated,
+    /// the strings of literals, a literal is the same string wherever it is used.
+    interned: Interned,
+    /// what the natives of scripts may do outside of the vm, nothing unless the host grants it.
+    capabilities: Capabilities,
+    /// the instructions executed since the vm was created.
+    executed: u64,
+    /// what `sys.host()` returns, the embedder names itself with it.
+    host: String,
 }
 
 impl Vm {
     pub fn new() -> Self {
         Self {
             allocator: GcAlloc::new(),
-            stack: vec![Value::Unit; DEFAULT_STACK_SIZE],
+            stack: vec![Value::Undefined; DEFAULT_STACK_SIZE],
             registers: [Value::Unit; 8],
             gray_list: vec![],
             call_stack: vec![CallFrame::default(); 512],
             top_stack: 0,
             top_frame: 0,
+            max_frames: DEFAULT_MAX_FRAMES,
+            run_base: 0,
+            run_failed: false,
+            base_frame: 0,
             no_collection: false,
+            gc_stress: false,
+            collections: 0,
             code_gen: false,
             locals: false,
             compiler_address: vec![],
+            source_map: None,
+            weak_refs: WeakRefs::default(),
+            userdata_types: vec![],
+            finalizable: vec![],
+            finalizable_instances: vec![],
+            pending_finalizers: vec![],
+            running_finalizers: false,
+            finalizer_error: None,
+            natives: vec![],
+            error_capture: ErrorCaptureLevel::FramesOnly,
+            error_snapshot: None,
+            coverage: None,
+            log_handler: Box::new(StdoutLogHandler),
+            output: Output::default(),
+            trace: None,
+            eval: Eval::default(),
+            log_level: LogLevel::Info,
+            print_options: RenderOptions::print(),
+            overflow_traps: false,
+            running: None,
+            replacements: 0,
+            task_module: None,
+            tasks: vec![],
+            task_type: None,
+            scratch: vec![],
+            string_dedup: false,
+            deduplicated: Deduplicated::default(),
+            interned: Interned::default(),
+            capabilities: Capabilities::none(),
+            executed: 0,
+            host: DEFAULT_HOST.to_string(),
         }
     }
 
@@ -203,10 +563,27 @@ impl Vm {
         }
     }
 
+    /// makes integer overflow a runtime error, it wraps by default.
+    pub fn set_overflow_traps(&mut self, traps: bool) {
+        self.overflow_traps = traps;
+    }
+
     pub fn force_no_collection(&mut self, val: bool) {
         self.no_collection = val;
     }
 
+    pub(crate) fn collection_disabled(&self) -> bool {
+        self.no_collection
+    }
+
+    /// collects before every allocation of an object instead of once the heap grows past its
+    /// threshold. An object the host or a native holds without rooting it is freed by the next
+    /// allocation, so a missing root shows up right where it is. Collections disabled with
+    /// `force_no_collection` stay disabled.
+    pub fn set_gc_stress(&mut self, stress: bool) {
+        self.gc_stress = stress;
+    }
+
     pub fn memory_usage(&self) -> usize {
         self.allocator.memory_usage()
     }
@@ -216,6 +593,10 @@ impl Vm {
     }
 
     pub fn push_stack(&mut self, value: Value) {
+        // frames with many locals grow the stack instead of running off the end of it.
+        if self.top_stack == self.stack.len() {
+            self.stack.resize(self.stack.len() * 2, Value::Undefined);
+        }
         self.stack[self.top_stack] = value;
         self.top_stack += 1;
     }
@@ -243,13 +624,18 @@ impl Vm {
     }
 
     pub fn push_frame(&mut self, frame: CallFrame) {
+        if self.top_frame == self.call_stack.len() {
+            let len = self.call_stack.len() * 2;
+            self.call_stack.resize(len, CallFrame::default());
+        }
         self.call_stack[self.top_frame] = frame;
         self.top_frame += 1;
     }
 
     pub fn print_stack(&self) {
+        let options = RenderOptions::repl();
         for i in 0..self.top_stack {
-            println!("{}| {}", i, self.stack[i]);
+            println!("{}| {}", i, render(&self.stack[i], &options));
         }
     }
 
@@ -263,7 +649,28 @@ impl Vm {
         &mut self.call_stack[self.top_frame.saturating_sub(1)]
     }
 
-    fn call_value(&mut self, value: &Value, arity: u8) -> Result<(), runtime::Error> {
+    fn read_op(&mut self) -> OpCode {
+        let frame = self.frame_mut();
+        let code = frame.section().read(frame.ip);
+        frame.ip += 1;
+        OpCode::from_u8(code).unwrap()
+    }
+
+    /// the index operand of the current instruction, a u32 after a wide prefix.
+    fn read_index(&mut self, wide: bool) -> usize {
+        let frame = self.frame_mut();
+        let mut ip = frame.ip;
+        let index = if wide {
+            read_to::<u32>(frame.section().data(), &mut ip) as usize
+        } else {
+            read_to::<u8>(frame.section().data(), &mut ip) as usize
+        };
+        frame.ip = ip;
+        index
+    }
+
+    /// returns false when a native blocked, nothing has been consumed from the stack then.
+    fn call_value(&mut self, value: &Value, arity: u8) -> Result<bool, runtime::Error> {
         match value {
             Value::Function(funct) => {
                 if arity != funct.arity() {
@@ -273,17 +680,105 @@ impl Vm {
                         funct.arity()
                     );
                 }
+                if self.top_frame >= self.max_frames {
+                    return Err(self.stack_overflow());
+                }
                 let stack_start = self.top_stack - arity as usize;
                 // self.print_stack();
                 let call_frame = CallFrame::new(*funct, stack_start);
                 self.push_frame(call_frame);
-                Ok(())
+                Ok(true)
             }
+            // the captured values follow the actuals, they are the last parameters.
+            Value::Closure(closure) => {
+                for value in closure.captured().iter() {
+                    self.push_stack(*value);
+                }
+                let captured = closure.captured().len() as u8;
+                self.call_value(&Value::from(closure.function()), arity + captured)
+            }
+            Value::Native(idx) => self.call_native(*idx, arity),
             _ => Err(runtime::Error::CallingInvalidValue(value.ty().to_string())),
         }
     }
 
-    fn call(&mut self) -> Result<(), runtime::Error> {
+    fn call_native(&mut self, idx: usize, arity: u8) -> Result<bool, runtime::Error> {
+        let native = &mut self.natives[idx];
+        if arity != native.arity {
+            panic!(
+                "{} is not given the correct number of parameters, expected {}",
+                native.name, native.arity
+            );
+        }
+
+        let mut function = native
+            .function
+            .take()
+            .expect("native function called while it is running");
+        let stack_start = self.top_stack - arity as usize;
+        let actuals = self.stack[stack_start..self.top_stack].to_vec();
+        let scratch = self.scratch.len();
+        let result = function(self, actuals.as_slice());
+        self.natives[idx].function = Some(function);
+        // the builders the native did not finish are abandoned.
+        self.release_scratch(scratch);
+
+        match result? {
+            NativeResult::Return(value) => {
+                self.top_stack = stack_start - 1;
+                self.push_stack(value);
+                Ok(true)
+            }
+            NativeResult::Blocked => Ok(false),
+        }
+    }
+
+    /// makes a host function callable, the returned value is what scripts call.
+    pub fn register_native(&mut self, name: &str, arity: u8, function: NativeFunction) -> Value {
+        self.natives.push(Native {
+            name: name.to_string(),
+            arity,
+            function: Some(function),
+        });
+        Value::Native(self.natives.len() - 1)
+    }
+
+    /// the error of a call past the frame limit. The frames are left for the error snapshot and
+    /// the stack trace, the next module entered drops them.
+    fn stack_overflow(&self) -> runtime::Error {
+        let mut frames = self.call_stack[0..self.top_frame]
+            .iter()
+            .rev()
+            .take(OVERFLOW_FRAMES)
+            .map(|frame| frame.function.name().to_string())
+            .collect::<Vec<_>>();
+        if self.top_frame > OVERFLOW_FRAMES {
+            frames.push("..".to_string());
+        }
+        runtime::Error::stack_overflow(self.max_frames, frames)
+    }
+
+    /// pushes the tuple (value, error) a result is in a script, the missing one is unit.
+    fn push_result(&mut self, result: Result<Value, String>) -> Result<(), runtime::Error> {
+        let no_collection = self.collection_disabled();
+        self.force_no_collection(true);
+        let (value, error) = match result {
+            Ok(value) => (value, Value::Unit),
+            Err(err) => (Value::Unit, Value::from(self.new_gc_string_from_str(&err))),
+        };
+        let tuple = self
+            .try_vec_fill_with_capacity(2, Value::Unit)
+            .and_then(|mut elements| {
+                elements[0] = value;
+                elements[1] = error;
+                self.new_tuple(elements)
+            });
+        self.force_no_collection(no_collection);
+        self.push_stack(Value::from(tuple?));
+        Ok(())
+    }
+
+    fn call(&mut self) -> Result<bool, runtime::Error> {
         let arity = {
             let frame = self.frame_mut();
             let arity = frame.section().read(frame.ip);
@@ -292,16 +787,34 @@ impl Vm {
         };
 
         let function = self.peek(arity as usize).clone();
-        self.call_value(&function, arity)
+        let called = self.call_value(&function, arity)?;
+        if !called {
+            // step back over the call and its arity so it is executed again on resume.
+            self.frame_mut().ip -= 2;
+        }
+        Ok(called)
     }
 
     pub fn run_module(&mut self, module: Gc<OxModule>) -> Result<(), runtime::Error> {
+        self.enter_module(module)?;
+        self.run()
+    }
+
+    /// calls the entry function of the module without executing it, it is then driven by
+    /// `run` or `run_slice`. A run suspended between slices is finished or abandoned first.
+    pub fn enter_module(&mut self, module: Gc<OxModule>) -> Result<(), runtime::Error> {
+        // a run that failed leaves its frames for the host to inspect, they are dropped now.
+        if self.run_failed {
+            self.abandon_run();
+        } else if self.top_frame > 0 {
+            return Err(runtime::Error::run_suspended(module.name().as_str()));
+        }
+        self.run_base = self.top_stack;
+        self.running = Some(module);
         self.push_stack(Value::from(module.clone()));
         if let Some(entry_function) = module.entry() {
             self.push_stack(entry_function.clone());
-            self.call_value(&entry_function, 0)?;
-
-            self.run()
+            self.call_value(&entry_function, 0).map(|_| ())
         } else {
             Err(runtime::Error::missing_module_entry(
                 module.as_ref().name().clone(),
@@ -309,57 +822,350 @@ impl Vm {
         }
     }
 
+    /// drops the frames of the entered module, whether its run failed or is suspended between
+    /// slices.
+    pub fn abandon_run(&mut self) {
+        self.top_frame = 0;
+        self.top_stack = self.run_base;
+        self.run_failed = false;
+    }
+
+    /// loads the module file at path written by `CompiledModule::save`. The header and version
+    /// are checked and the whole file is read before anything is loaded, a file that can not
+    /// be run is an error.
+    pub fn load_module(&mut self, path: &Path) -> Result<Gc<OxModule>, runtime::Error> {
+        let bytes = std::fs::read(path)
+            .map_err(|err| runtime::Error::read_module_file(&path.display().to_string(), &err))?;
+        Ok(CompiledModule::from_bytes(&bytes)?.load(self))
+    }
+
+    /// runs a module using an external source map for debug info.
+    pub fn load_with_sourcemap(
+        &mut self,
+        module: Gc<OxModule>,
+        source_map: SourceMap,
+    ) -> Result<(), runtime::Error> {
+        self.source_map = Some(source_map);
+        self.run_module(module)
+    }
+
+    /// controls how much state is captured when a runtime error occurs.
+    pub fn set_error_capture(&mut self, level: ErrorCaptureLevel) {
+        self.error_capture = level;
+    }
+
+    /// the state captured by the last runtime error.
+    pub fn on_error_snapshot(&self) -> Option<&ErrorSnapshot> {
+        self.error_snapshot.as_ref()
+    }
+
+    /// records the executed lines of every function until coverage is taken or disabled.
+    pub fn set_coverage(&mut self, enabled: bool) {
+        if !enabled {
+            self.coverage = None;
+        } else if self.coverage.is_none() {
+            self.coverage = Some(Coverage::new());
+        }
+    }
+
+    /// the lines executed since coverage was enabled, collection continues with a fresh record.
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.as_mut().map(std::mem::take)
+    }
+
+    /// routes the messages logged by scripts to handler.
+    pub fn set_log_handler(&mut self, handler: Box<dyn LogHandler>) {
+        self.log_handler = handler;
+    }
+
+    /// messages below level are not logged.
+    pub fn set_log_level(&mut self, level: LogLevel) {
+        self.log_level = level;
+    }
+
+    pub fn log_level(&self) -> LogLevel {
+        self.log_level
+    }
+
+    /// the frames that can run at once, `DEFAULT_MAX_FRAMES` until it is set. A call past it
+    /// fails with a stack overflow instead of growing the call stack, the tasks of the vm share
+    /// the limit.
+    pub fn set_max_frames(&mut self, frames: usize) {
+        assert!(frames > 0, "a vm runs at least one frame");
+        self.max_frames = frames;
+    }
+
+    pub fn max_frames(&self) -> usize {
+        self.max_frames
+    }
+
+    /// names the program embedding the vm to the scripts it runs, they read it with
+    /// `sys.host()`. It is `DEFAULT_HOST` until it is set, the tasks of the vm share it.
+    pub fn set_host(&mut self, host: &str) {
+        self.host = host.to_string();
+    }
+
+    pub fn host(&self) -> &str {
+        self.host.as_str()
+    }
+
+    pub fn set_print_options(&mut self, options: RenderOptions) {
+        self.print_options = options;
+    }
+
+    /// the instructions executed since the vm was created, unlike time it is the same every run
+    /// of the same program.
+    pub fn instructions_executed(&self) -> u64 {
+        self.executed
+    }
+
+    pub fn print_options(&self) -> &RenderOptions {
+        &self.print_options
+    }
+
+    /// renders a value printed by the script, a string is printed without quotes.
+    fn render_printed(&self, value: &Value) -> String {
+        match value {
+            Value::String(val) => val.as_str().to_string(),
+            _ => render(value, &self.print_options),
+        }
+    }
+
+    /// the message of a trap with values, every `{}` of the message is replaced by the next
+    /// value rendered and `{{` and `}}` are braces.
+    fn render_trap(&self, message: &Value, values: &[Value]) -> String {
+        let message = self.render_printed(message);
+        let mut values = values.iter();
+        let mut rendered = String::with_capacity(message.len());
+        let mut chars = message.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('{', Some('{')) | ('}', Some('}')) => {
+                    chars.next();
+                    rendered.push(c);
+                }
+                ('{', Some('}')) => match values.next() {
+                    Some(value) => {
+                        chars.next();
+                        rendered.push_str(&render(value, &self.print_options));
+                    }
+                    None => rendered.push(c),
+                },
+                _ => rendered.push(c),
+            }
+        }
+        rendered
+    }
+
+    fn log(&mut self, level: LogLevel, message: &Value) {
+        let message = self.render_printed(message);
+
+        // borrow the fields directly, the handler is borrowed mutably below.
+        let frame = &self.call_stack[self.top_frame - 1];
+        let function = frame.function.name().as_str();
+        let ip = frame.ip.saturating_sub(1);
+        let mapped = self
+            .source_map
+            .as_ref()
+            .and_then(|map| map.function(function).map(|f| (map, f)));
+        let file = mapped
+            .and_then(|(map, f)| map.file(f.file))
+            .map(|f| f.path.as_str());
+        let line = frame
+            .section()
+            .line_for(ip)
+            .or_else(|| mapped.and_then(|(_, f)| f.lines.line_for(ip)));
+
+        let record = LogRecord {
+            level,
+            message: message.as_str(),
+            function,
+            file,
+            line,
+        };
+        self.log_handler.log(&record);
+    }
+
+    fn capture_error(&mut self, err: &runtime::Error) {
+        if self.error_capture == ErrorCaptureLevel::None {
+            return;
+        }
+
+        let capture_options = RenderOptions::error_capture();
+        let frames = (0..self.top_frame)
+            .rev()
+            .map(|idx| {
+                let frame = &self.call_stack[idx];
+                let section = frame.section();
+                let ip = frame.ip.saturating_sub(1);
+                let locals = if self.error_capture == ErrorCaptureLevel::FramesAndLocals {
+                    // the locals of a frame end where the callee of the next frame starts.
+                    let end = if idx + 1 < self.top_frame {
+                        self.call_stack[idx + 1].local_start.saturating_sub(1)
+                    } else {
+                        self.top_stack
+                    };
+                    (frame.local_start..end)
+                        .filter_map(|slot| {
+                            section
+                                .locals()
+                                .name_for(slot - frame.local_start, ip)
+                                .map(|name| {
+                                    let value = render(&self.stack[slot], &capture_options);
+                                    (name.to_string(), value)
+                                })
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                };
+
+                FrameSnapshot {
+                    function: frame.function.name().to_string(),
+                    line: section.line_for(ip),
+                    locals,
+                }
+            })
+            .collect();
+
+        self.error_snapshot = Some(ErrorSnapshot {
+            error: err.to_string(),
+            frames,
+        });
+    }
+
+    /// renders the active call frames, inner most first.
+    /// debug info embedded in the function is preferred over the source map.
+    pub fn stack_trace(&self) -> Vec<String> {
+        self.call_stack[0..self.top_frame]
+            .iter()
+            .rev()
+            .map(|frame| {
+                let name = frame.function.name().as_str();
+                let ip = frame.ip.saturating_sub(1);
+                let mapped = self.source_map.as_ref().and_then(|map| map.function(name));
+                let file = mapped
+                    .and_then(|f| self.source_map.as_ref().unwrap().file(f.file))
+                    .map(|f| f.path.as_str())
+                    .unwrap_or("<unknown>");
+                let line = frame
+                    .section()
+                    .line_for(ip)
+                    .or_else(|| mapped.and_then(|f| f.lines.line_for(ip)));
+
+                match line {
+                    Some(line) => format!("at {} ({}:{})", name, file, line),
+                    None => format!("at {} ({})", name, file),
+                }
+            })
+            .collect()
+    }
+
     pub fn run(&mut self) -> Result<(), runtime::Error> {
+        match self.execute(None) {
+            Ok(Execution::Blocked) => panic!("a native blocked outside of run_slice"),
+            Ok(_) => Ok(()),
+            Err(err) => {
+                self.run_failed = true;
+                self.capture_error(&err);
+                Err(err)
+            }
+        }
+    }
+
+    /// executes at most `fuel` instructions of the entered module. All of the interpreter
+    /// state lives in the frames and on the stack so the host can do other work between slices.
+    pub fn run_slice(&mut self, fuel: u64) -> SliceResult {
+        match self.execute(Some(fuel)) {
+            Ok(Execution::Completed) if self.top_stack == 0 => SliceResult::Completed(Value::Unit),
+            Ok(Execution::Completed) => SliceResult::Completed(*self.top()),
+            Ok(Execution::Yielded) => SliceResult::Yielded,
+            Ok(Execution::Blocked) => SliceResult::Blocked,
+            Err(err) => {
+                self.run_failed = true;
+                self.capture_error(&err);
+                SliceResult::Errored(err)
+            }
+        }
+    }
+
+    fn execute(&mut self, mut fuel: Option<u64>) -> Result<Execution, runtime::Error> {
         //self.push_frame(call_frame);
 
         loop {
-            // read the next op code and advance the instruction pointer.
+            // a collection queues the finalizers of unreachable instances, they run between
+            // instructions where the stack is consistent.
+            if !self.pending_finalizers.is_empty() && !self.running_finalizers {
+                self.run_script_finalizers();
+            }
 
-            let op_code_raw = {
-                let frame = self.frame_mut();
-                let code = frame.section().read(frame.ip);
-                frame.ip += 1;
-                code
-            };
+            // only stop between instructions, an instruction is never partially executed.
+            if let Some(fuel) = fuel.as_mut() {
+                if *fuel == 0 {
+                    return Ok(Execution::Yielded);
+                }
+                *fuel -= 1;
+            }
+            self.executed += 1;
+
+            if let Some(coverage) = self.coverage.as_mut() {
+                let frame = &self.call_stack[self.top_frame.saturating_sub(1)];
+                coverage.visit(&frame.function, frame.ip);
+            }
+            // formatting the trace is kept out of the loop, it only checks whether it is set.
+            if self.trace.is_some() {
+                self.trace_instruction();
+            }
 
-            let op_code = OpCode::from_u8(op_code_raw).unwrap();
+            // read the next op code and advance the instruction pointer.
+
+            let mut op_code = self.read_op();
+            // the operand of the instruction after a wide prefix is a u32.
+            let wide = op_code == OpCode::Wide;
+            if wide {
+                op_code = self.read_op();
+            }
             // println!("OpCode {:014x} {}", self.frame().ip - 1, op_code);
             match op_code {
                 OpCode::LoadI8 => {
-                    load_constant!(is_i8, "i8", self);
+                    load_constant!(is_i8, "i8", self, wide);
                 }
                 OpCode::LoadI16 => {
-                    load_constant!(is_i16, "i16", self);
+                    load_constant!(is_i16, "i16", self, wide);
                 }
                 OpCode::LoadI32 => {
-                    load_constant!(is_i32, "i32", self);
+                    load_constant!(is_i32, "i32", self, wide);
                 }
                 OpCode::LoadI64 => {
-                    load_constant!(is_i64, "i64", self);
+                    load_constant!(is_i64, "i64", self, wide);
                 }
                 OpCode::LoadU8 => {
-                    load_constant!(is_u8, "u8", self);
+                    load_constant!(is_u8, "u8", self, wide);
                 }
                 OpCode::LoadU16 => {
-                    load_constant!(is_u16, "u16", self);
+                    load_constant!(is_u16, "u16", self, wide);
                 }
                 OpCode::LoadU32 => {
-                    load_constant!(is_u32, "u32", self);
+                    load_constant!(is_u32, "u32", self, wide);
                 }
                 OpCode::LoadU64 => {
-                    load_constant!(is_u64, "u64", self);
+                    load_constant!(is_u64, "u64", self, wide);
                 }
                 OpCode::LoadF32 => {
-                    load_constant!(is_f32, "f8", self);
+                    load_constant!(is_f32, "f8", self, wide);
                 }
                 OpCode::LoadF64 => {
-                    load_constant!(is_f64, "f64", self);
+                    load_constant!(is_f64, "f64", self, wide);
                 }
                 OpCode::LoadStr => {
-                    load_constant!(is_string, "string", self);
+                    load_constant!(is_string, "string", self, wide);
                 }
                 OpCode::LoadChar => {
-                    load_constant!(is_char, "char", self);
+                    load_constant!(is_char, "char", self, wide);
+                }
+                OpCode::LoadConst => {
+                    // pooled tuples are immutable, every load shares the same object.
+                    load_constant!(is_tuple, "tuple", self, wide);
                 }
                 OpCode::LoadTrue => {
                     self.push_stack(Value::from(true));
@@ -367,39 +1173,55 @@ impl Vm {
                 OpCode::LoadFalse => {
                     self.push_stack(Value::from(false));
                 }
-                OpCode::LoadGlobal => {
-                    let global = {
-                        let frame = self.frame_mut();
-                        let mut ip = frame.ip;
-                        let value = read_to::<u8>(frame.section().data(), &mut ip);
-                        frame.ip = ip;
-                        frame.section().get_global(value as usize)
+                OpCode::LoadUnit => {
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::CastInt => {
+                    let frame = self.frame_mut();
+                    let kind = frame.section().read(frame.ip);
+                    frame.ip += 1;
+                    // enums are stored as i64, a bool is 0 or 1.
+                    let value = match self.pop() {
+                        Value::Bool(val) => val as i64,
+                        value => value.as_i64(),
                     };
-
+                    self.push_stack(Self::cast_int(value, kind));
+                }
+                OpCode::LoadGlobal => {
+                    let idx = self.read_index(wide);
+                    let global = self.frame().section().get_global(idx);
+                    // globals are written when the module is built, a section built by hand may
+                    // leave one out.
+                    if let Value::Undefined = global {
+                        let function = self.frame().funct().name().to_string();
+                        return Err(runtime::Error::global_read_before_init(idx, function));
+                    }
                     self.push_stack(global);
                 }
                 OpCode::SetGlobal => {
                     let top = self.pop();
-                    let frame = self.frame_mut();
-                    let mut ip = frame.ip;
-                    let idx = read_to::<u8>(frame.section().data(), &mut ip);
-                    frame.funct().section_mut().set_global(idx as usize, top);
-                    frame.ip = ip;
+                    let idx = self.read_index(wide);
+                    self.frame_mut().funct().section_mut().set_global(idx, top);
                 }
                 OpCode::LoadLocal => {
-                    let frame = self.frame_mut();
-                    let local = frame.local_start;
-                    let idx = frame.section().read(frame.ip);
-                    frame.ip += 1;
-                    self.push_stack(self.stack[local + idx as usize].clone());
+                    let idx = self.read_index(wide);
+                    let local = self.frame().local_start;
+                    // the compiler declares a local before using it, only a section built by
+                    // hand reads one that was never written.
+                    debug_assert!(
+                        local + idx < self.top_stack
+                            && !matches!(self.stack[local + idx], Value::Undefined),
+                        "local {} of '{}' is read before it is initialized",
+                        idx,
+                        self.frame().funct().name()
+                    );
+                    self.push_stack(self.stack[local + idx].clone());
                 }
                 OpCode::SetLocal => {
-                    let frame = self.frame_mut();
-                    let local = frame.local_start;
-                    let idx = frame.section().read(frame.ip);
-                    frame.ip += 1;
+                    let idx = self.read_index(wide);
+                    let local = self.frame().local_start;
                     let value = self.pop();
-                    self.stack[local + idx as usize] = value;
+                    self.stack[local + idx] = value;
                 }
                 OpCode::Label => {
                     // skip the label I am not sure how else to reprsent this.
@@ -430,6 +1252,17 @@ impl Vm {
                         frame.ip = ip;
                     }
                 }
+                OpCode::JmpUnit => {
+                    let is_unit = matches!(self.top(), Value::Unit);
+                    let frame = self.frame_mut();
+                    let mut ip = frame.ip;
+                    let value = read_to::<u16>(frame.section().data(), &mut ip);
+                    if is_unit {
+                        frame.ip = ip + value as usize;
+                    } else {
+                        frame.ip = ip;
+                    }
+                }
                 OpCode::Jmp => {
                     let frame = self.frame_mut();
                     let mut ip = frame.ip;
@@ -448,8 +1281,8 @@ impl Vm {
                     self.top_stack = last_frame.local_start.saturating_sub(1);
                     self.push_stack(top);
 
-                    // if we are returning from the top function then exit.
-                    if self.top_frame == 0 {
+                    // if we are returning from the top function of the run then exit.
+                    if self.top_frame == self.base_frame {
                         break;
                     }
                 }
@@ -470,7 +1303,7 @@ impl Vm {
                     let count = read_to::<u16>(frame.section().data(), &mut ip);
                     frame.ip = ip;
 
-                    let mut fields = self.vec_fill_with_capacity(count as usize, Value::Unit);
+                    let mut fields = self.try_vec_fill_with_capacity(count as usize, Value::Unit)?;
 
                     (0..count)
                         .rev()
@@ -484,7 +1317,7 @@ impl Vm {
                     let instance = self.new_instance(name, fields);
                     self.force_no_collection(false);
 
-                    self.push_stack(Value::from(instance));
+                    self.push_stack(Value::from(instance?));
                 }
                 OpCode::NewTuple => {
                     let frame = self.frame_mut();
@@ -492,15 +1325,33 @@ impl Vm {
                     let count = read_to::<u16>(frame.section().data(), &mut ip);
                     // println!("elements: {}", count);
                     frame.ip = ip;
-                    let mut elements = self.vec_fill_with_capacity(count as usize, Value::Unit);
-                    (0..count)
-                        .rev()
-                        .for_each(|idx| elements[idx as usize] = self.pop());
+                    let count = count as usize;
+                    let mut elements = self.try_vec_fill_with_capacity(count, Value::Unit)?;
+                    (0..count).for_each(|idx| elements[idx] = *self.peek(count - idx - 1));
 
-                    let tuple = self.new_tuple(elements);
+                    // the elements stay on the stack until the tuple is allocated, allocating can
+                    // collect and they are only reachable from the stack.
+                    let tuple = self.new_tuple(elements)?;
+                    self.top_stack -= count;
 
                     self.push_stack(Value::from(tuple));
                 }
+                OpCode::NewInstanceConst => {
+                    // instances can be mutated, the pooled fields are copied into a new one.
+                    let idx = self.read_index(wide);
+                    let template = self.frame().section().get_constant(idx);
+                    let template = template.as_tuple().elements();
+
+                    let mut fields = self.try_vec_fill_with_capacity(template.len(), Value::Unit)?;
+                    fields.copy_from_slice(template.as_slice());
+                    let name = *self.pop().as_string();
+
+                    self.force_no_collection(true);
+                    let instance = self.new_instance(name, fields);
+                    self.force_no_collection(false);
+
+                    self.push_stack(Value::from(instance?));
+                }
                 OpCode::InstanceAttr => {
                     let frame = self.frame_mut();
                     let mut ip = frame.ip;
@@ -512,6 +1363,184 @@ impl Vm {
                     let value = s.get_attr(count as usize).clone();
                     self.push_stack(value)
                 }
+                OpCode::NewArray => {
+                    let frame = self.frame_mut();
+                    let mut ip = frame.ip;
+                    let count = read_to::<u16>(frame.section().data(), &mut ip) as usize;
+                    frame.ip = ip;
+
+                    // like a tuple, the elements are on the stack while the array is allocated.
+                    let mut array = self.new_list_with_capacity(count)?;
+                    array.extend(&self.stack[self.top_stack - count..self.top_stack])?;
+                    self.top_stack -= count;
+                    self.push_stack(Value::from(array));
+                }
+                OpCode::NewArrayConst => {
+                    // the pooled elements are shared by every evaluation of the literal.
+                    let idx = self.read_index(wide);
+                    let template = self.frame().section().get_constant(idx);
+                    let template = template.as_tuple().elements();
+                    let mut array = self.new_list_with_capacity(template.len())?;
+                    array.extend(template.as_slice())?;
+                    self.push_stack(Value::from(array));
+                }
+                OpCode::NewClosure => {
+                    let frame = self.frame_mut();
+                    let count = frame.section().read(frame.ip) as usize;
+                    frame.ip += 1;
+
+                    // the function and captured values are on the stack while it is allocated.
+                    let function = *self.peek(count).as_function();
+                    let captured = self.stack[self.top_stack - count..self.top_stack].to_vec();
+                    let closure = self.new_closure(function, captured.as_slice())?;
+                    self.top_stack -= count + 1;
+                    self.push_stack(Value::from(closure));
+                }
+                OpCode::LoadIndex => {
+                    let index = Self::integer_index(&self.pop());
+                    let array = *self.pop().as_list();
+                    let element = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| array.elements().get(index).copied());
+                    match element {
+                        Some(element) => self.push_stack(element),
+                        None => {
+                            return Err(runtime::Error::index_out_of_bounds(index, array.len()));
+                        }
+                    }
+                }
+                OpCode::StoreIndex => {
+                    let value = self.pop();
+                    let index = Self::integer_index(&self.pop());
+                    let mut array = *self.pop().as_list();
+                    let len = array.len();
+                    let element = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| array.as_ref_mut().elements_mut().get_mut(index));
+                    match element {
+                        Some(element) => *element = value,
+                        None => return Err(runtime::Error::index_out_of_bounds(index, len)),
+                    }
+                }
+                OpCode::ListPush => {
+                    // the list and value stay rooted while the buffer grows, it can collect.
+                    let value = *self.peek(0);
+                    let mut list = *self.peek(1).as_list();
+                    self.with_emergency_collection(|_| list.as_ref_mut().push(value))?;
+                    self.top_stack -= 2;
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::ListPop => {
+                    let mut list = *self.pop().as_list();
+                    match list.as_ref_mut().pop() {
+                        Some(value) => self.push_stack(value),
+                        None => return Err(runtime::Error::empty_list_pop()),
+                    }
+                }
+                OpCode::ListLen => {
+                    let list = *self.pop().as_list();
+                    self.push_stack(Value::I64(list.len() as i64));
+                }
+                OpCode::ListWithCapacity => {
+                    let capacity = self.pop().as_i64();
+                    if capacity < 0 {
+                        return Err(runtime::Error::negative_capacity(capacity));
+                    }
+                    let list = self.new_list_with_capacity(capacity as usize)?;
+                    self.push_stack(Value::from(list));
+                }
+                OpCode::ListExtend => {
+                    // both lists stay rooted while the destination grows, it can collect.
+                    let src = *self.peek(0).as_list();
+                    let dst = *self.peek(1).as_list();
+                    self.list_extend(dst, src)?;
+                    self.top_stack -= 2;
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::ListCopy => {
+                    let len = self.pop();
+                    let dst_start = self.pop();
+                    let src_start = self.pop();
+                    let src = *self.pop().as_list();
+                    let dst = *self.pop().as_list();
+                    let len = Self::list_offset(&len, src.len())?;
+                    let src_start = Self::list_offset(&src_start, src.len())?;
+                    let dst_start = Self::list_offset(&dst_start, dst.len())?;
+                    self.list_copy(dst, src, src_start, dst_start, len)?;
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::ListFill => {
+                    let value = self.pop();
+                    let mut list = *self.pop().as_list();
+                    list.as_ref_mut().fill(value);
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::ListFillRange => {
+                    let value = self.pop();
+                    let end = self.pop();
+                    let start = self.pop();
+                    let mut list = *self.pop().as_list();
+                    let start = Self::list_offset(&start, list.len())?;
+                    let end = Self::list_offset(&end, list.len())?;
+                    list.as_ref_mut().fill_range(start, end, value)?;
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::ListReserve => {
+                    // the list stays rooted while it grows, it can collect.
+                    let additional = self.peek(0).as_i64();
+                    let list = *self.peek(1).as_list();
+                    self.list_reserve(list, additional)?;
+                    self.top_stack -= 2;
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::ListCapacity => {
+                    let list = *self.pop().as_list();
+                    self.push_stack(Value::I64(list.capacity() as i64));
+                }
+                OpCode::ListShrinkToFit => {
+                    let mut list = *self.pop().as_list();
+                    list.as_ref_mut().shrink_to_fit();
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::MapReserve => {
+                    let additional = self.peek(0).as_i64();
+                    let map = *self.peek(1).as_map();
+                    self.map_reserve(map, additional)?;
+                    self.top_stack -= 2;
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::NewMap => {
+                    let map = self.new_map()?;
+                    self.push_stack(Value::from(map));
+                }
+                OpCode::MapInsert => {
+                    let value = *self.peek(0);
+                    let key = *self.peek(1);
+                    let mut map = *self.peek(2).as_map();
+                    self.with_emergency_collection(|_| map.as_ref_mut().insert(key, value))?;
+                    self.top_stack -= 3;
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::MapGet => {
+                    let key = self.pop();
+                    let map = *self.pop().as_map();
+                    self.push_stack(map.get(&key).unwrap_or(Value::Unit));
+                }
+                OpCode::MapRemove => {
+                    let key = self.pop();
+                    let mut map = *self.pop().as_map();
+                    let value = map.as_ref_mut().remove(&key);
+                    self.push_stack(value.unwrap_or(Value::Unit));
+                }
+                OpCode::MapContains => {
+                    let key = self.pop();
+                    let map = *self.pop().as_map();
+                    self.push_stack(Value::Bool(map.contains(&key)));
+                }
+                OpCode::MapLen => {
+                    let map = *self.pop().as_map();
+                    self.push_stack(Value::I64(map.len() as i64));
+                }
                 OpCode::TupleAttr => {
                     let frame = self.frame_mut();
                     let mut ip = frame.ip;
@@ -530,6 +1559,19 @@ impl Vm {
                     let mut obj = self.pop();
                     *obj.as_instance_mut().get_attr_mut(idx as usize) = value;
                 }
+                OpCode::GetField => {
+                    let name = self.pop();
+                    let value = self.pop();
+                    let field = match value {
+                        Value::Instance(instance) => self
+                            .running
+                            .and_then(|module| module.structure(instance.name().as_str()))
+                            .and_then(|structure| structure.field_slot(name.as_string().as_str()))
+                            .and_then(|slot| instance.fields().get(slot).copied()),
+                        _ => None,
+                    };
+                    self.push_stack(field.unwrap_or(Value::Unit));
+                }
                 OpCode::LoadAssoc => {
                     let frame = self.frame_mut();
                     let idx = frame.section().read(frame.ip);
@@ -552,8 +1594,8 @@ impl Vm {
                 | OpCode::AddU64
                 | OpCode::AddF32
                 | OpCode::AddF64 => {
-                    self.print_stack();
-                    let value = self.perform_add(op_code);
+                    // self.print_stack();
+                    let value = self.perform_add(op_code)?;
                     self.push_stack(value);
                 }
                 OpCode::SubI8
@@ -566,7 +1608,7 @@ impl Vm {
                 | OpCode::SubU64
                 | OpCode::SubF32
                 | OpCode::SubF64 => {
-                    let value = self.perform_sub(op_code);
+                    let value = self.perform_sub(op_code)?;
                     self.push_stack(value);
                 }
                 OpCode::MultI8
@@ -579,7 +1621,7 @@ impl Vm {
                 | OpCode::MultU64
                 | OpCode::MultF32
                 | OpCode::MultF64 => {
-                    let value = self.perform_mult(op_code);
+                    let value = self.perform_mult(op_code)?;
                     self.push_stack(value);
                 }
                 OpCode::DivI8
@@ -592,7 +1634,42 @@ impl Vm {
                 | OpCode::DivU64
                 | OpCode::DivF32
                 | OpCode::DivF64 => {
-                    let value = self.perform_div(op_code);
+                    let value = self.perform_div(op_code)?;
+                    self.push_stack(value);
+                }
+                OpCode::RemI8
+                | OpCode::RemI16
+                | OpCode::RemI32
+                | OpCode::RemI64
+                | OpCode::RemU8
+                | OpCode::RemU16
+                | OpCode::RemU32
+                | OpCode::RemU64
+                | OpCode::RemF32
+                | OpCode::RemF64 => {
+                    let value = self.perform_rem(op_code)?;
+                    self.push_stack(value);
+                }
+                OpCode::DivFloorI8
+                | OpCode::DivFloorI16
+                | OpCode::DivFloorI32
+                | OpCode::DivFloorI64
+                | OpCode::DivFloorU8
+                | OpCode::DivFloorU16
+                | OpCode::DivFloorU32
+                | OpCode::DivFloorU64 => {
+                    let value = self.perform_div_floor(op_code)?;
+                    self.push_stack(value);
+                }
+                OpCode::ModFloorI8
+                | OpCode::ModFloorI16
+                | OpCode::ModFloorI32
+                | OpCode::ModFloorI64
+                | OpCode::ModFloorU8
+                | OpCode::ModFloorU16
+                | OpCode::ModFloorU32
+                | OpCode::ModFloorU64 => {
+                    let value = self.perform_mod_floor(op_code)?;
                     self.push_stack(value);
                 }
                 OpCode::LessI8
@@ -647,13 +1724,207 @@ impl Vm {
                     let value = self.perform_greatereq(op_code);
                     self.push_stack(value);
                 }
+                OpCode::EqEqI8
+                | OpCode::EqEqI16
+                | OpCode::EqEqI32
+                | OpCode::EqEqI64
+                | OpCode::EqEqU8
+                | OpCode::EqEqU16
+                | OpCode::EqEqU32
+                | OpCode::EqEqU64
+                | OpCode::EqEqF32
+                | OpCode::EqEqF64 => {
+                    let value = self.perform_eqeq(op_code);
+                    self.push_stack(value);
+                }
+                OpCode::NotEqI8
+                | OpCode::NotEqI16
+                | OpCode::NotEqI32
+                | OpCode::NotEqI64
+                | OpCode::NotEqU8
+                | OpCode::NotEqU16
+                | OpCode::NotEqU32
+                | OpCode::NotEqU64
+                | OpCode::NotEqF32
+                | OpCode::NotEqF64 => {
+                    let value = self.perform_noteq(op_code);
+                    self.push_stack(value);
+                }
+                OpCode::ConcatStr => {
+                    // the result is allocated while the operands are still on the stack.
+                    let left = *self.peek(1).as_string();
+                    let right = *self.peek(0).as_string();
+                    let string = self.with_emergency_collection(|vm| {
+                        let allocator = vm.allocator_vec();
+                        let mut string = OxString::try_with_value(allocator, left.as_str())?;
+                        string.push_bytes(right.as_bytes())?;
+                        Ok(string)
+                    })?;
+                    let address = self.allocate_from::<OxString>()?;
+                    self.pop();
+                    self.pop();
+                    self.push_stack(Value::from(Gc::with_value(address, string)));
+                }
+                OpCode::EqEqStr | OpCode::NotEqStr => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    // literals are interned, those with the same contents are the same string.
+                    let equal = left.is_same_constant(&right);
+                    self.push_stack(Value::from(equal == (op_code == OpCode::EqEqStr)));
+                }
+                OpCode::EqEqFn | OpCode::NotEqFn => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    // the identity `hash_constant` hashes, a function is only equal to itself.
+                    let equal = left.is_same_constant(&right);
+                    self.push_stack(Value::from(equal == (op_code == OpCode::EqEqFn)));
+                }
+                OpCode::BinaryAndI8
+                | OpCode::BinaryAndI16
+                | OpCode::BinaryAndI32
+                | OpCode::BinaryAndI64
+                | OpCode::BinaryAndU8
+                | OpCode::BinaryAndU16
+                | OpCode::BinaryAndU32
+                | OpCode::BinaryAndU64 => {
+                    let value = self.perform_binary_and(op_code);
+                    self.push_stack(value);
+                }
+                OpCode::BinaryOrI8
+                | OpCode::BinaryOrI16
+                | OpCode::BinaryOrI32
+                | OpCode::BinaryOrI64
+                | OpCode::BinaryOrU8
+                | OpCode::BinaryOrU16
+                | OpCode::BinaryOrU32
+                | OpCode::BinaryOrU64 => {
+                    let value = self.perform_binary_or(op_code);
+                    self.push_stack(value);
+                }
+                OpCode::BinaryXorI8
+                | OpCode::BinaryXorI16
+                | OpCode::BinaryXorI32
+                | OpCode::BinaryXorI64
+                | OpCode::BinaryXorU8
+                | OpCode::BinaryXorU16
+                | OpCode::BinaryXorU32
+                | OpCode::BinaryXorU64 => {
+                    let value = self.perform_binary_xor(op_code);
+                    self.push_stack(value);
+                }
+                OpCode::ShiftLeftI8
+                | OpCode::ShiftLeftI16
+                | OpCode::ShiftLeftI32
+                | OpCode::ShiftLeftI64
+                | OpCode::ShiftLeftU8
+                | OpCode::ShiftLeftU16
+                | OpCode::ShiftLeftU32
+                | OpCode::ShiftLeftU64 => {
+                    let value = self.perform_shift_left(op_code);
+                    self.push_stack(value);
+                }
+                OpCode::ShiftRightI8
+                | OpCode::ShiftRightI16
+                | OpCode::ShiftRightI32
+                | OpCode::ShiftRightI64
+                | OpCode::ShiftRightU8
+                | OpCode::ShiftRightU16
+                | OpCode::ShiftRightU32
+                | OpCode::ShiftRightU64 => {
+                    let value = self.perform_shift_right(op_code);
+                    self.push_stack(value);
+                }
                 OpCode::Pop => {
                     self.pop();
                 }
-                OpCode::Call => self.call()?,
+                OpCode::Call => {
+                    if !self.call()? {
+                        return Ok(Execution::Blocked);
+                    }
+                }
                 OpCode::Echo => {
                     let value = self.pop();
-                    println!("{}", value)
+                    let printed = self.render_printed(&value);
+                    self.write_output(&[printed.as_str(), "\n"])?;
+                }
+                OpCode::LogEnabled => {
+                    let frame = self.frame_mut();
+                    let level = frame.section().read(frame.ip);
+                    frame.ip += 1;
+                    let level = LogLevel::from_u8(level).expect("invalid log level");
+                    self.push_stack(Value::from(level >= self.log_level));
+                }
+                OpCode::Log => {
+                    let frame = self.frame_mut();
+                    let level = frame.section().read(frame.ip);
+                    frame.ip += 1;
+                    let level = LogLevel::from_u8(level).expect("invalid log level");
+                    let message = self.pop();
+                    self.log(level, &message);
+                }
+                OpCode::Spawn => {
+                    let frame = self.frame_mut();
+                    let arity = frame.section().read(frame.ip) as usize;
+                    frame.ip += 1;
+                    // the function and actuals stay rooted until the handle is allocated.
+                    let function = *self.peek(arity);
+                    let actuals = self.stack[self.top_stack - arity..self.top_stack].to_vec();
+                    let handle = self.spawn_task(&function, actuals.as_slice())?;
+                    self.top_stack -= arity + 1;
+                    self.push_stack(handle);
+                }
+                OpCode::Join => {
+                    let handle = self.pop();
+                    let result = self.join_task(&handle);
+                    self.push_result(result)?;
+                }
+                OpCode::Trap => {
+                    let message = self.pop();
+                    return Err(runtime::Error::trap(&self.render_printed(&message)));
+                }
+                OpCode::TrapFormat => {
+                    let frame = self.frame_mut();
+                    let count = frame.section().read(frame.ip) as usize;
+                    frame.ip += 1;
+                    let values = &self.stack[self.top_stack - count..self.top_stack];
+                    let message = self.peek(count);
+                    return Err(runtime::Error::trap(&self.render_trap(message, values)));
+                }
+                OpCode::LoadHost => {
+                    let host = self.with_emergency_collection(|vm| {
+                        OxString::try_with_value(vm.allocator_vec(), vm.host.as_str())
+                    })?;
+                    let address = self.allocate_from::<OxString>()?;
+                    self.push_stack(Value::from(Gc::with_value(address, host)));
+                }
+                OpCode::Eval => {
+                    // the source is copied out, compiling it may collect the string.
+                    let source = self.pop().as_string().as_str().to_owned();
+                    self.require(Capability::Eval)?;
+                    let result = self.eval(source.as_str());
+                    self.push_result(result)?;
+                }
+                OpCode::NewWeak => {
+                    let referent = self.pop();
+                    let handle = self.new_weak(referent)?;
+                    self.push_stack(handle);
+                }
+                OpCode::WeakGet => {
+                    let handle = self.pop();
+                    let referent = self.weak_get(&handle)?;
+                    self.push_stack(referent.unwrap_or(Value::Unit));
+                }
+                OpCode::GcCollect => {
+                    // the marks are in use while finalizers run, a collection is not nested.
+                    if !self.no_collection {
+                        self.collect();
+                    }
+                    self.push_stack(Value::Unit);
+                }
+                OpCode::RegisterFinalizer => {
+                    let finalizer = self.pop();
+                    let instance = *self.top();
+                    self.finalizable_instances.push((instance, finalizer));
                 }
                 OpCode::FrameStack => {
                     let frame = self.frame();
@@ -668,39 +1939,98 @@ impl Vm {
                     self.print_stack();
                     break;
                 }
+                OpCode::Wide => unreachable!("a wide prefix is read with its instruction"),
                 OpCode::NumOps => {}
             }
         }
 
         println!("Section Complete");
         println!("Memory Usage at end: {}", self.allocator.memory_usage());
-        Ok(())
+        Ok(Execution::Completed)
     }
 
-    binary_op!(perform_add, AddI8, +);
-    binary_op!(perform_sub, SubI8, -);
-    binary_op!(perform_mult, MultI8, *);
-    binary_op!(perform_div, DivI8, /);
+    binary_op!(perform_add, AddI8, +, checked_add, wrapping_add);
+    binary_op!(perform_sub, SubI8, -, checked_sub, wrapping_sub);
+    binary_op!(perform_mult, MultI8, *, checked_mul, wrapping_mul);
+    binary_op!(perform_div, DivI8, /, checked_div, wrapping_div);
+    binary_op!(perform_rem, RemI8, %, checked_modulo, wrapping_modulo);
+    checked_integer_binary_op!(
+        perform_div_floor,
+        DivFloorI8,
+        checked_div_floor,
+        wrapping_div_floor
+    );
+    checked_integer_binary_op!(
+        perform_mod_floor,
+        ModFloorI8,
+        checked_mod_floor,
+        wrapping_mod_floor
+    );
     conditional_binary_op!(perform_less, LessI8, <);
     conditional_binary_op!(perform_greater, GreaterI8, >);
     conditional_binary_op!(perform_lesseq, LessEqI8, <);
     conditional_binary_op!(perform_greatereq, GreaterEqI8, >);
+    conditional_binary_op!(perform_eqeq, EqEqI8, ==);
+    conditional_binary_op!(perform_noteq, NotEqI8, !=);
+    integer_binary_op!(perform_binary_and, BinaryAndI8, &);
+    integer_binary_op!(perform_binary_or, BinaryOrI8, |);
+    integer_binary_op!(perform_binary_xor, BinaryXorI8, ^);
+    shift_binary_op!(perform_shift_left, ShiftLeftI8, wrapping_shl);
+    shift_binary_op!(perform_shift_right, ShiftRightI8, wrapping_shr);
+
+    /// the value of an integer used as an index, it is wide enough for any of them.
+    fn integer_index(value: &Value) -> i128 {
+        match value {
+            Value::I8(val) => i128::from(*val),
+            Value::I16(val) => i128::from(*val),
+            Value::I32(val) => i128::from(*val),
+            Value::I64(val) => i128::from(*val),
+            Value::U8(val) => i128::from(*val),
+            Value::U16(val) => i128::from(*val),
+            Value::U32(val) => i128::from(*val),
+            Value::U64(val) => i128::from(*val),
+            _ => panic!("Attempting to index with a value of type {}", value.ty()),
+        }
+    }
+
+    /// a position or count of a bulk list operation, a negative one is out of bounds of the list.
+    fn list_offset(value: &Value, len: usize) -> Result<usize, runtime::Error> {
+        let offset = Self::integer_index(value);
+        usize::try_from(offset).map_err(|_| runtime::Error::index_out_of_bounds(offset, len))
+    }
+
+    /// converts an i64 to the integer type at kind, in the order i8, i16, i32, i64, u8, u16, u32, u64.
+    fn cast_int(value: i64, kind: u8) -> Value {
+        match kind {
+            0 => Value::from(value as i8),
+            1 => Value::from(value as i16),
+            2 => Value::from(value as i32),
+            3 => Value::from(value),
+            4 => Value::from(value as u8),
+            5 => Value::from(value as u16),
+            6 => Value::from(value as u32),
+            7 => Value::from(value as u64),
+            _ => panic!("Invalid integer kind {} for cast_int", kind),
+        }
+    }
 
-    fn allocate(&mut self, kind: ObjectKind, layout: Layout) -> Address {
+    /// an object of the heap, a failed allocation is retried once after an emergency collection
+    /// even when collections are disabled. Memory that is still exhausted is an error of the
+    /// script, nothing is allocated to raise it.
+    fn allocate(&mut self, kind: ObjectKind, layout: Layout) -> Result<Address, runtime::Error> {
         // self.allocator.dump_mem_stats();
         // println!("Should collect: {}", self.allocator.should_collect());
-        if !self.no_collection && self.allocator.should_collect() {
+        if !self.no_collection && (self.gc_stress || self.allocator.should_collect()) {
             self.collect();
         }
 
         let address = match self.allocator.alloc(kind, layout) {
             Ok(address) => address,
             Err(_) => {
-                // collect and retry
                 self.collect();
                 self.allocator
                     .alloc(kind, layout)
-                    .expect("alloc failed: out of memory")
+                    .map_err(|_| runtime::Error::out_of_memory())?
             }
         };
 
@@ -709,14 +2039,42 @@ impl Vm {
             self.compiler_address.push(address);
         }
 
-        address
+        Ok(address)
     }
 
-    fn allocate_from<Ty: Object>(&mut self) -> Address {
+    fn allocate_from<Ty: Object>(&mut self) -> Result<Address, runtime::Error> {
         let layout = Layout::new::<Ty>();
         self.allocate(Ty::object_kind(), layout)
     }
 
+    /// the objects the host creates building or loading a program, there is no script to raise
+    /// an error in so running out of memory panics.
+    fn allocate_for_host<Ty: Object>(&mut self) -> Address {
+        self.allocate_from::<Ty>()
+            .expect("out of memory allocating an object for the host")
+    }
+
+    /// fails every allocation of the heap after the next budget of them, none lifts the limit.
+    /// Running out of memory is simulated with it.
+    pub fn fail_allocations_after(&mut self, budget: Option<usize>) {
+        self.allocator.set_allocation_budget(budget);
+    }
+
+    /// runs an operation growing a buffer of the heap, it is run again after an emergency
+    /// collection when memory runs out.
+    fn with_emergency_collection<T>(
+        &mut self,
+        mut operation: impl FnMut(&mut Self) -> Result<T, runtime::Error>,
+    ) -> Result<T, runtime::Error> {
+        match operation(self) {
+            Err(runtime::Error::OutOfMemory) => {
+                self.collect();
+                operation(self)
+            }
+            result => result,
+        }
+    }
+
     pub fn deallocate(&mut self, address: Address) {
         self.allocator.dealloc(address)
     }
@@ -730,7 +2088,7 @@ impl Vm {
     }
 
     pub fn new_gc_string_from_str(&mut self, val: &str) -> Gc<OxString> {
-        let address = self.allocate_from::<OxStruct>();
+        let address = self.allocate_for_host::<OxString>();
         let x = Gc::with_value(address, self.new_string_from_str(val));
         if cfg!(debug_assertions) {
             println!("new_string_gc {}", self.allocator.last_record().unwrap());
@@ -749,6 +2107,15 @@ impl Vm {
         x
     }
 
+    /// vec_fill_with_capacity for the values a script builds, running out of memory is an error.
+    pub fn try_vec_fill_with_capacity<Ty: Clone>(
+        &mut self,
+        len: usize,
+        fill: Ty,
+    ) -> Result<OxVec<Ty>, runtime::Error> {
+        OxVec::try_fill_with_capacity(self.allocator_vec(), len, fill)
+    }
+
     pub fn vec_fill_with_capacity<Ty: Clone>(&mut self, len: usize, fill: Ty) -> OxVec<Ty> {
         let x = OxVec::fill_with_capacity(self.allocator_vec(), len, fill);
         if cfg!(debug_assertions) {
@@ -764,8 +2131,27 @@ impl Vm {
         OxVec::new(self.allocator_vec())
     }
 
+    /// converts host values to values of the vm, they are not rooted until they are stored.
+    pub fn values_from_iter<I>(&mut self, values: I) -> OxVec<Value>
+    where
+        I: IntoIterator,
+        I::Item: IntoValue,
+    {
+        // the strings allocated first are not rooted while the rest are converted.
+        let no_collection = self.collection_disabled();
+        self.force_no_collection(true);
+        let mut converted = self.new_vec();
+        for value in values {
+            let value = value.into_value(self);
+            converted.push(value);
+        }
+        self.force_no_collection(no_collection);
+        converted
+    }
+
+
     pub fn new_module(&mut self, name: OxString, objects: OxVec<Value>) -> Gc<OxModule> {
-        let address = self.allocate_from::<OxModule>();
+        let address = self.allocate_for_host::<OxModule>();
         if cfg!(debug_assertions) {
             println!("new_module {}", self.allocator.last_record().unwrap());
         }
@@ -773,7 +2159,7 @@ impl Vm {
     }
 
     pub fn new_empty_module(&mut self, name: OxString) -> Gc<OxModule> {
-        let address = self.allocate_from::<OxModule>();
+        let address = self.allocate_for_host::<OxModule>();
         let objects = self.new_vec();
         if cfg!(debug_assertions) {
             println!("new_module {}", self.allocator.last_record().unwrap());
@@ -787,7 +2173,7 @@ impl Vm {
         entry: usize,
         objects: OxVec<Value>,
     ) -> Gc<OxModule> {
-        let address = self.allocate_from::<OxModule>();
+        let address = self.allocate_for_host::<OxModule>();
         if cfg!(debug_assertions) {
             println!("new_entry_module {}", self.allocator.last_record().unwrap());
         }
@@ -795,7 +2181,7 @@ impl Vm {
     }
 
     pub fn new_function(&mut self, name: OxString, arity: u8, section: Section) -> Gc<OxFunction> {
-        let address = self.allocate_from::<OxFunction>();
+        let address = self.allocate_for_host::<OxFunction>();
         Gc::with_value(address, OxFunction::new(name, section, arity))
     }
 
@@ -808,21 +2194,186 @@ impl Vm {
     }
 
     pub fn new_struct(&mut self, name: OxString, methods: OxVec<Gc<OxFunction>>) -> Gc<OxStruct> {
-        let address = self.allocate_from::<OxStruct>();
+        let address = self.allocate_for_host::<OxStruct>();
         if cfg!(debug_assertions) {
             println!("new_struct {}", self.allocator.last_record().unwrap());
         }
         Gc::with_value(address, OxStruct::new(name, methods))
     }
 
-    pub fn new_instance(&mut self, object: Gc<OxString>, fields: OxVec<Value>) -> Gc<OxInstance> {
-        let address = self.allocate_from::<OxInstance>();
-        Gc::with_value(address, OxInstance::new(object, fields))
+    pub fn new_instance(
+        &mut self,
+        object: Gc<OxString>,
+        fields: OxVec<Value>,
+    ) -> Result<Gc<OxInstance>, runtime::Error> {
+        let address = self.allocate_from::<OxInstance>()?;
+        Ok(Gc::with_value(address, OxInstance::new(object, fields)))
+    }
+
+    pub fn new_tuple(&mut self, elements: OxVec<Value>) -> Result<Gc<OxTuple>, runtime::Error> {
+        let address = self.allocate_from::<OxTuple>()?;
+        Ok(Gc::with_value(address, OxTuple::new(elements)))
+    }
+
+    pub fn new_list_with_capacity(
+        &mut self,
+        capacity: usize,
+    ) -> Result<Gc<OxList>, runtime::Error> {
+        let elements = OxVec::try_with_capacity(self.allocator_vec(), capacity)?;
+        let address = self.allocate_from::<OxList>()?;
+        Ok(Gc::with_value(address, OxList::new(elements)))
+    }
+
+    /// a closure of function capturing the values, they are copied into it.
+    pub fn new_closure(
+        &mut self,
+        function: Gc<OxFunction>,
+        captured: &[Value],
+    ) -> Result<Gc<OxClosure>, runtime::Error> {
+        let mut values = OxVec::try_with_capacity(self.allocator_vec(), captured.len())?;
+        values.extend_from_slice(captured);
+        let address = self.allocate_from::<OxClosure>()?;
+        Ok(Gc::with_value(address, OxClosure::new(function, values)))
+    }
+
+    pub fn new_map(&mut self) -> Result<Gc<OxMap>, runtime::Error> {
+        let map = OxMap::new(self.allocator_vec());
+        let address = self.allocate_from::<OxMap>()?;
+        Ok(Gc::with_value(address, map))
+    }
+
+    /// appends the elements of src to dst, src may be dst itself.
+    pub fn list_extend(
+        &mut self,
+        mut dst: Gc<OxList>,
+        src: Gc<OxList>,
+    ) -> Result<(), runtime::Error> {
+        self.with_emergency_collection(|_| {
+            if dst.ptr() == src.ptr() {
+                dst.as_ref_mut().extend_self()
+            } else {
+                dst.as_ref_mut().extend(src.elements())
+            }
+        })
+    }
+
+    /// makes room for additional more elements, a script passing a negative count is an error.
+    pub fn list_reserve(
+        &mut self,
+        mut list: Gc<OxList>,
+        additional: i64,
+    ) -> Result<(), runtime::Error> {
+        if additional < 0 {
+            return Err(runtime::Error::negative_capacity(additional));
+        }
+        self.with_emergency_collection(|_| list.as_ref_mut().reserve(additional as usize))
+    }
+
+    /// makes room for additional more keys, a script passing a negative count is an error.
+    pub fn map_reserve(
+        &mut self,
+        mut map: Gc<OxMap>,
+        additional: i64,
+    ) -> Result<(), runtime::Error> {
+        if additional < 0 {
+            return Err(runtime::Error::negative_capacity(additional));
+        }
+        self.with_emergency_collection(|_| map.as_ref_mut().reserve(additional as usize))
+    }
+
+    /// copies len elements of src starting at src_start over dst starting at dst_start.
+    /// src may be dst itself, overlapping ranges are copied as if through a temporary.
+    pub fn list_copy(
+        &mut self,
+        mut dst: Gc<OxList>,
+        src: Gc<OxList>,
+        src_start: usize,
+        dst_start: usize,
+        len: usize,
+    ) -> Result<(), runtime::Error> {
+        if dst.ptr() == src.ptr() {
+            dst.as_ref_mut().copy_within(src_start, dst_start, len)
+        } else {
+            dst.as_ref_mut()
+                .copy_from(src.elements(), src_start, dst_start, len)
+        }
+    }
+
+    /// creates a weak handle to a heap object, the handle does not keep the object alive.
+    pub fn new_weak(&mut self, value: Value) -> Result<Value, runtime::Error> {
+        if value.addr().is_none() {
+            return Err(runtime::Error::weak_to_non_heap_value(value.ty()));
+        }
+
+        Ok(Value::Weak(self.weak_refs.insert(value)))
+    }
+
+    /// the referent of a weak handle, none once the object has been collected.
+    pub fn weak_get(&self, handle: &Value) -> Result<Option<Value>, runtime::Error> {
+        match handle {
+            Value::Weak(handle) => Ok(self.weak_refs.get(*handle)),
+            _ => Err(runtime::Error::not_a_weak_handle(handle.ty())),
+        }
+    }
+
+    /// the slots of the weak reference table, live or free.
+    pub fn weak_capacity(&self) -> usize {
+        self.weak_refs.capacity()
+    }
+
+    pub fn should_collect(&self) -> bool {
+        self.allocator.should_collect()
     }
 
-    pub fn new_tuple(&mut self, elements: OxVec<Value>) -> Gc<OxTuple> {
-        let address = self.allocate_from::<OxTuple>();
-        Gc::with_value(address, OxTuple::new(elements))
+    /// runs a full collection.
+    pub fn gc_collect(&mut self) {
+        self.collect();
+    }
+
+    /// registers a kind of host object, the returned id is used to create values of it.
+    pub fn register_userdata_type(&mut self, name: &str) -> usize {
+        self.userdata_types.push(UserDataType {
+            name: name.to_string(),
+            finalizer: None,
+        });
+        self.userdata_types.len() - 1
+    }
+
+    /// registers a kind of host object that is finalized once it becomes unreachable.
+    /// finalizers run on the vm after marking, the order they run in is unspecified.
+    pub fn register_userdata_type_with_finalizer(
+        &mut self,
+        name: &str,
+        finalizer: Finalizer,
+    ) -> usize {
+        let type_id = self.register_userdata_type(name);
+        self.userdata_types[type_id].finalizer = Some(finalizer);
+        type_id
+    }
+
+    pub fn userdata_type_name(&self, type_id: usize) -> &str {
+        self.userdata_types[type_id].name.as_str()
+    }
+
+    pub fn set_finalizer_error_handler(&mut self, handler: FinalizerErrorHandler) {
+        self.finalizer_error = Some(handler);
+    }
+
+    pub fn new_userdata(
+        &mut self,
+        type_id: usize,
+        handle: u64,
+    ) -> Result<Gc<OxUserData>, runtime::Error> {
+        assert!(
+            type_id < self.userdata_types.len(),
+            "unregistered userdata type {}",
+            type_id
+        );
+        let address = self.allocate_from::<OxUserData>()?;
+        if self.userdata_types[type_id].finalizer.is_some() {
+            self.finalizable.push(address);
+        }
+        Ok(Gc::with_value(address, OxUserData::new(type_id, handle)))
     }
 
     pub fn dump_mem_stats(&self) {
@@ -830,6 +2381,29 @@ impl Vm {
     }
 }
 
+impl Drop for Vm {
+    fn drop(&mut self) {
+        // drop all of the roots and let the collector release the heap, this walks the objects
+        // iteratively instead of relying on a recursive drop of the object graph.
+        self.top_stack = 0;
+        self.top_frame = 0;
+        self.compiler_address.clear();
+        self.gray_list.clear();
+        self.weak_refs.clear();
+        // the finalizers of scripts need the interpreter, they do not run once the vm is dropped.
+        self.finalizable_instances.clear();
+        self.pending_finalizers.clear();
+
+        // give the host a chance to release its resources. whatever the finalizers leave
+        // behind is dropped as well, the first sweep only clears the marks they needed.
+        self.run_finalizers();
+        self.top_stack = 0;
+        self.gray_list.clear();
+        self.sweep();
+        self.sweep();
+    }
+}
+
 impl Vm {
     fn collect(&mut self) {
         println!("--Beginning collect: {}", self.memory_usage());
@@ -837,7 +2411,19 @@ impl Vm {
         // println!("{}", backtrace);
 
         self.mark();
+        self.queue_script_finalizers();
+        if self.run_finalizers() {
+            // finalizers can store objects somewhere reachable, mark again so those survive.
+            self.mark();
+        }
+        self.weak_refs.clear_unreachable();
+        self.clear_unreachable_interned();
+        if self.string_dedup {
+            self.dedup_strings();
+        }
         self.sweep();
+        self.allocator.update_next_collection();
+        self.collections += 1;
 
         // clean up after collection
         self.gray_list.clear();
@@ -852,8 +2438,130 @@ impl Vm {
         self.follow_references();
     }
 
+    /// runs the finalizers of the userdata objects that were not reached while marking. Each
+    /// object is finalized once, it is kept alive for this cycle and released by the next
+    /// collection unless a finalizer resurrected it. Returns if any finalizer ran.
+    fn run_finalizers(&mut self) -> bool {
+        let (pending, live): (Vec<Address>, Vec<Address>) = self
+            .finalizable
+            .iter()
+            .partition(|address| !address.cell().marked);
+        self.finalizable = live;
+
+        if pending.is_empty() {
+            return false;
+        }
+
+        for address in &pending {
+            Self::mark_object(&mut self.gray_list, *address);
+        }
+        self.follow_references();
+
+        // the marks are in use, a finalizer allocating must not start another collection.
+        let no_collection = self.no_collection;
+        self.no_collection = true;
+        for address in pending {
+            let userdata = Gc::<OxUserData>::new(address);
+            let type_id = userdata.type_id();
+            let mut finalizer = self.userdata_types[type_id].finalizer.take();
+            if let Some(finalizer) = finalizer.as_mut() {
+                if let Err(err) = finalizer(self, userdata) {
+                    let name = self.userdata_types[type_id].name.clone();
+                    self.report_finalizer_error(&name, err);
+                }
+            }
+            self.userdata_types[type_id].finalizer = finalizer;
+        }
+        self.no_collection = no_collection;
+
+        true
+    }
+
+    /// queues the instances finalized by the script that were not reached while marking. Like
+    /// userdata they are finalized once and kept alive until their finalizer has run, the
+    /// interpreter runs it before its next instruction.
+    fn queue_script_finalizers(&mut self) {
+        let (unreachable, live): (Vec<_>, Vec<_>) = self
+            .finalizable_instances
+            .iter()
+            .partition(|(instance, _)| instance.addr().is_some_and(|addr| !addr.cell().marked));
+        self.finalizable_instances = live;
+
+        if unreachable.is_empty() {
+            return;
+        }
+        for (instance, finalizer) in unreachable.iter() {
+            Self::mark_value(&mut self.gray_list, instance);
+            Self::mark_value(&mut self.gray_list, finalizer);
+        }
+        self.follow_references();
+        self.pending_finalizers.extend(unreachable);
+    }
+
+    /// calls the queued finalizers on top of the running frames, the same way `eval` runs the
+    /// script it compiled. An error is reported like the errors of userdata finalizers.
+    fn run_script_finalizers(&mut self) {
+        self.running_finalizers = true;
+        while let Some((instance, finalizer)) = self.pending_finalizers.pop() {
+            let base_stack = self.top_stack;
+            let base_frame = std::mem::replace(&mut self.base_frame, self.top_frame);
+            self.push_stack(finalizer);
+            self.push_stack(instance);
+            let result = self
+                .call_value(&finalizer, 1)
+                .and_then(|_| self.execute(None));
+            self.top_frame = self.base_frame;
+            self.top_stack = base_stack;
+            self.base_frame = base_frame;
+
+            let err = match result {
+                Ok(Execution::Completed) => continue,
+                Ok(Execution::Yielded) | Ok(Execution::Blocked) => {
+                    "a native blocked the finalizer".to_string()
+                }
+                Err(err) => err.to_string(),
+            };
+            let name = instance.as_instance().name().to_string();
+            self.report_finalizer_error(&name, err);
+        }
+        self.running_finalizers = false;
+    }
+
+    fn report_finalizer_error(&mut self, name: &str, err: String) {
+        let message = format!("finalizer for {} failed: {}", name, err);
+        match self.finalizer_error.as_mut() {
+            Some(handler) => handler(message),
+            None => eprintln!("{}", message),
+        }
+    }
+
+    /// releases every unreachable object. Objects only own their buffers, references to other
+    /// objects are plain handles, so dropping one never recurses into the objects it points to.
+    /// Those are found unreachable on their own in the same pass.
     fn sweep(&mut self) {
-        self.allocator.sweep();
+        for (address, kind) in self.allocator.sweep() {
+            Self::release_object(address, kind);
+            self.allocator.dealloc(address);
+        }
+    }
+
+    fn release_object(address: Address, kind: ObjectKind) {
+        let ptr = address.as_ptr_mut();
+        unsafe {
+            match kind {
+                ObjectKind::Function => std::ptr::drop_in_place(ptr as *mut OxFunction),
+                ObjectKind::Instance => std::ptr::drop_in_place(ptr as *mut OxInstance),
+                ObjectKind::Module => std::ptr::drop_in_place(ptr as *mut OxModule),
+                ObjectKind::String => std::ptr::drop_in_place(ptr as *mut OxString),
+                ObjectKind::Struct => std::ptr::drop_in_place(ptr as *mut OxStruct),
+                ObjectKind::Tuple => std::ptr::drop_in_place(ptr as *mut OxTuple),
+                ObjectKind::UserData => std::ptr::drop_in_place(ptr as *mut OxUserData),
+                ObjectKind::List => std::ptr::drop_in_place(ptr as *mut OxList),
+                ObjectKind::Map => std::ptr::drop_in_place(ptr as *mut OxMap),
+                ObjectKind::Closure => std::ptr::drop_in_place(ptr as *mut OxClosure),
+                ObjectKind::Section | ObjectKind::Vec => {}
+            }
+        }
     }
 
     fn mark_roots(&mut self) {
@@ -862,6 +2570,29 @@ impl Vm {
         for value in &self.stack[0..self.top_stack] {
             Self::mark_value(&mut self.gray_list, value);
         }
+        // a frame running a replaced function holds the only reference to the old body.
+        for frame in &self.call_stack[0..self.top_frame] {
+            Self::mark_value(&mut self.gray_list, &Value::from(frame.function));
+        }
+        if let Some(module) = self.running {
+            Self::mark_value(&mut self.gray_list, &Value::from(module));
+        }
+        for binding in self.eval.bindings() {
+            Self::mark_value(&mut self.gray_list, &binding.value);
+        }
+        // the instances are weak, but their finalizers are kept for when they are collected.
+        for (_, finalizer) in &self.finalizable_instances {
+            Self::mark_value(&mut self.gray_list, finalizer);
+        }
+        for (instance, finalizer) in &self.pending_finalizers {
+            Self::mark_value(&mut self.gray_list, instance);
+            Self::mark_value(&mut self.gray_list, finalizer);
+        }
+        for scratch in self.scratch.iter().flatten() {
+            for value in scratch.values() {
+                Self::mark_value(&mut self.gray_list, value);
+            }
+        }
         // println!("gray list size: {}", self.gray_list.len());
     }
     fn mark_compiler(&mut self) {
@@ -901,6 +2632,10 @@ impl Vm {
             Value::Instance(val) => val.ptr(),
             Value::Module(val) => val.ptr(),
             Value::Tuple(val) => val.ptr(),
+            Value::UserData(val) => val.ptr(),
+            Value::List(val) => val.ptr(),
+            Value::Map(val) => val.ptr(),
+            Value::Closure(val) => val.ptr(),
             _ => {
                 // println!();
                 return;
@@ -950,6 +2685,22 @@ impl Vm {
                 let t = address.into_ref::<OxTuple>();
                 Self::trace_tuple(gray_list, t);
             }
+            ObjectKind::UserData => {
+                // the handle is opaque to the vm, there is nothing to follow.
+            }
+            ObjectKind::List => {
+                let l = address.into_ref::<OxList>();
+                Self::trace_vec_value(gray_list, l.elements());
+            }
+            ObjectKind::Map => {
+                let m = address.into_ref::<OxMap>();
+                Self::trace_map(gray_list, m);
+            }
+            ObjectKind::Closure => {
+                let c = address.into_ref::<OxClosure>();
+                Self::mark_value(gray_list, &Value::from(c.function()));
+                Self::trace_vec_value(gray_list, c.captured());
+            }
             ObjectKind::Section => {
                 unreachable!()
             }
@@ -971,6 +2722,15 @@ impl Vm {
         Self::trace_vec_gc(gray_list, structure.methods());
     }
 
+    fn trace_map(gray_list: &mut Vec<Address>, map: &OxMap) {
+        if Self::trace_vec(map.slots()) {
+            for (key, value) in map.entries() {
+                Self::mark_value(gray_list, key);
+                Self::mark_value(gray_list, value);
+            }
+        }
+    }
+
     fn trace_tuple(gray_list: &mut Vec<Address>, tuple: &OxTuple) {
         // println!("trace tuple");
         Self::trace_vec_value(gray_list, tuple.elements());
@@ -993,10 +2753,11 @@ impl Vm {
         Self::trace_section(gray_list, section);
     }
 
-    fn trace_string(_gray_list: &mut Vec<Address>, vec: &OxString) {
-        // println!("trace string");
-        // do anything need to happen here?
-        Self::trace_vec(vec.buffer());
+    fn trace_string(_gray_list: &mut Vec<Address>, string: &OxString) {
+        // an inline string has no buffer of its own.
+        if let Some(buffer) = string.buffer() {
+            Self::trace_vec(buffer);
+        }
     }
 
     fn trace_section(gray_list: &mut Vec<Address>, section: &Section) {
@@ -1039,3 +2800,764 @@ impl Vm {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorCaptureLevel, OpCode, SliceResult, Vm, DEFAULT_MAX_FRAMES};
+    use crate::{gc::Gc, runtime, AttributeAccess, OxModule, Value};
+    use std::{cell::RefCell, rc::Rc};
+
+    const NODES: usize = 256;
+    // the pools grow with the heap, a list this long is torn down without recursing.
+    const LONG_LIST: usize = 1_000_000;
+
+    // builds a singly linked list of instances, the head is kept on the stack.
+    fn build_list(vm: &mut Vm, len: usize) {
+        let name = vm.new_gc_string_from_str("Node");
+        vm.push_stack(Value::from(name));
+        vm.push_stack(Value::Unit);
+        for _ in 0..len {
+            let next = *vm.top();
+            let fields = vm.vec_fill_with_capacity(1, next);
+            let name = *vm.peek(1).as_string();
+            let node = vm.new_instance(name, fields).unwrap();
+            vm.pop();
+            vm.push_stack(Value::from(node));
+        }
+    }
+
+    fn list_len(head: &Value) -> usize {
+        let mut len = 0;
+        let mut current = *head;
+        while current.is_instance() {
+            len += 1;
+            current = *current.as_instance().get_attr(0);
+        }
+        len
+    }
+
+    #[test]
+    fn test_integer_overflow_wraps_unless_trapped() {
+        let mut vm = Vm::new();
+        vm.push_stack(Value::from(i8::MAX));
+        vm.push_stack(Value::from(1i8));
+        assert!(matches!(
+            vm.perform_add(OpCode::AddI8),
+            Ok(Value::I8(i8::MIN))
+        ));
+
+        vm.set_overflow_traps(true);
+        vm.push_stack(Value::from(u32::MIN));
+        vm.push_stack(Value::from(1u32));
+        let err = vm.perform_sub(OpCode::SubU32).err().unwrap();
+        assert_eq!(err.to_string(), "integer overflow of a value of type u32");
+        vm.push_stack(Value::from(i64::MAX / 2));
+        vm.push_stack(Value::from(2i64));
+        assert!(matches!(
+            vm.perform_mult(OpCode::MultI64),
+            Ok(Value::I64(_))
+        ));
+
+        // a division by zero fails whether or not overflow traps.
+        vm.set_overflow_traps(false);
+        vm.push_stack(Value::from(1i32));
+        vm.push_stack(Value::from(0i32));
+        let err = vm.perform_div(OpCode::DivI32).err().unwrap();
+        assert_eq!(err.to_string(), "integer division by zero");
+    }
+
+    #[test]
+    fn test_dividing_min_by_minus_one_wraps_unless_trapped() {
+        let mut vm = Vm::new();
+        let divide = |vm: &mut Vm, op: OpCode| {
+            vm.push_stack(Value::from(i64::MIN));
+            vm.push_stack(Value::from(-1i64));
+            match op {
+                OpCode::DivI64 => vm.perform_div(op),
+                OpCode::RemI64 => vm.perform_rem(op),
+                OpCode::DivFloorI64 => vm.perform_div_floor(op),
+                _ => vm.perform_mod_floor(op),
+            }
+        };
+        for op in [OpCode::DivI64, OpCode::DivFloorI64] {
+            vm.set_overflow_traps(false);
+            assert!(matches!(divide(&mut vm, op), Ok(Value::I64(i64::MIN))));
+            vm.set_overflow_traps(true);
+            let err = divide(&mut vm, op).err().unwrap();
+            assert_eq!(err.to_string(), "integer overflow of a value of type i64");
+        }
+        // the remainder is zero, it does not overflow.
+        for op in [OpCode::RemI64, OpCode::ModFloorI64] {
+            assert!(matches!(divide(&mut vm, op), Ok(Value::I64(0))));
+        }
+
+        vm.push_stack(Value::from(-7i8));
+        vm.push_stack(Value::from(0i8));
+        let err = vm.perform_mod_floor(OpCode::ModFloorI8).err().unwrap();
+        assert_eq!(err.to_string(), "integer division by zero");
+    }
+
+    #[test]
+    fn test_shifts_take_the_amount_modulo_the_bits() {
+        let mut vm = Vm::new();
+        let mut shift = |op: OpCode, lhs: Value, rhs: Value| {
+            vm.push_stack(lhs);
+            vm.push_stack(rhs);
+            match op {
+                OpCode::ShiftLeftI8 | OpCode::ShiftLeftU32 => vm.perform_shift_left(op),
+                _ => vm.perform_shift_right(op),
+            }
+        };
+        let value = shift(OpCode::ShiftLeftU32, Value::from(1u32), Value::from(4u32));
+        assert!(matches!(value, Value::U32(16)));
+        let value = shift(OpCode::ShiftLeftI8, Value::from(1i8), Value::from(7i8));
+        assert!(matches!(value, Value::I8(i8::MIN)));
+        // a signed right shift keeps the sign, an unsigned one fills with zeros.
+        let value = shift(OpCode::ShiftRightI64, Value::from(-16i64), Value::from(2i64));
+        assert!(matches!(value, Value::I64(-4)));
+        let value = shift(OpCode::ShiftRightU8, Value::from(0x80u8), Value::from(7u8));
+        assert!(matches!(value, Value::U8(1)));
+        let value = shift(OpCode::ShiftRightU16, Value::from(4u16), Value::from(17u16));
+        assert!(matches!(value, Value::U16(2)));
+    }
+
+    #[test]
+    fn test_drop_vm_with_long_list() {
+        let mut vm = Vm::new();
+        build_list(&mut vm, LONG_LIST);
+        assert_eq!(list_len(vm.top()), LONG_LIST);
+        drop(vm);
+    }
+
+    #[test]
+    fn test_collect_long_list() {
+        let mut vm = Vm::new();
+        let start = vm.memory_usage();
+        build_list(&mut vm, LONG_LIST);
+
+        let used = vm.memory_usage();
+        vm.collect();
+        assert_eq!(vm.memory_usage(), used);
+        assert_eq!(list_len(vm.top()), LONG_LIST);
+
+        // drop the head of the list, every node becomes garbage.
+        vm.pop();
+        vm.pop();
+        vm.collect();
+        assert_eq!(vm.memory_usage(), start);
+    }
+
+    #[test]
+    fn test_gc_stress_keeps_live_objects() {
+        let mut vm = Vm::new();
+        let start = vm.memory_usage();
+        vm.set_gc_stress(true);
+        build_list(&mut vm, NODES);
+        let stats = vm.heap_stats();
+        assert!(stats.collections >= NODES as u64);
+        assert_eq!(list_len(vm.top()), NODES);
+
+        vm.pop();
+        vm.pop();
+        vm.gc_collect();
+        assert_eq!(vm.heap_stats().collections, stats.collections + 1);
+        assert_eq!(vm.memory_usage(), start);
+    }
+
+    #[test]
+    fn test_collection_threshold_adapts_to_live_memory() {
+        let mut vm = Vm::new();
+        build_list(&mut vm, NODES);
+        vm.gc_collect();
+        let stats = vm.heap_stats();
+        assert!(stats.bytes_allocated >= stats.memory_usage as u64);
+        assert!(stats.next_collection > stats.memory_usage);
+        // growing the list a little more does not collect, the threshold is past it.
+        let collections = stats.collections;
+        let name = *vm.peek(1).as_string();
+        let fields = vm.vec_fill_with_capacity(1, *vm.top());
+        let node = vm.new_instance(name, fields).unwrap();
+        vm.pop();
+        vm.push_stack(Value::from(node));
+        assert_eq!(vm.heap_stats().collections, collections);
+
+        vm.pop();
+        vm.pop();
+        vm.gc_collect();
+        let reclaimed = vm.heap_stats();
+        assert_eq!(reclaimed.memory_usage, 0);
+        assert!(reclaimed.bytes_allocated > stats.bytes_allocated);
+        assert!(reclaimed.next_collection < stats.next_collection);
+    }
+
+    #[test]
+    fn test_weak_to_non_heap_value() {
+        let mut vm = Vm::new();
+        assert!(vm.new_weak(Value::I32(1)).is_err());
+        assert!(vm.new_weak(Value::Bool(true)).is_err());
+        assert!(vm.weak_get(&Value::I32(1)).is_err());
+    }
+
+    #[test]
+    fn test_failed_allocations_leave_objects_unchanged() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut list = vm.new_list_with_capacity(1).unwrap();
+        let mut map = vm.new_map().unwrap();
+        let mut string = vm.new_string_from_str("0123456789abcdef");
+        list.as_ref_mut().push(Value::I32(1)).unwrap();
+
+        vm.fail_allocations_after(Some(0));
+        let out_of_memory = |result: Result<(), runtime::Error>| match result {
+            Err(err @ runtime::Error::OutOfMemory) => err.to_string(),
+            result => panic!("unexpected result {:?}", result),
+        };
+        let elements = vm.new_vec();
+        assert_eq!(out_of_memory(vm.new_tuple(elements).map(|_| ())), "out of memory");
+        out_of_memory(list.as_ref_mut().push(Value::I32(2)));
+        out_of_memory(map.as_ref_mut().insert(Value::I32(1), Value::Unit).map(|_| ()));
+        out_of_memory(string.push_bytes(b"g"));
+        assert_eq!(list.len(), 1);
+        assert!(map.is_empty());
+        assert_eq!(string.as_str(), "0123456789abcdef");
+
+        vm.fail_allocations_after(None);
+        list.as_ref_mut().push(Value::I32(2)).unwrap();
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_weak_memoization_cache() {
+        let mut vm = Vm::new();
+
+        // cache of id -> big object, only the weak handles are held by the cache.
+        let mut cache = vec![];
+        for id in 0..4 {
+            build_list(&mut vm, 16);
+            let handle = vm.new_weak(*vm.top()).unwrap();
+            cache.push((id, handle));
+            if id != 3 {
+                vm.pop();
+                vm.pop();
+            }
+        }
+
+        // only the last list is still rooted by the stack.
+        vm.gc_collect();
+        for (id, handle) in &cache {
+            assert_eq!(vm.weak_get(handle).unwrap().is_some(), *id == 3);
+        }
+        assert_eq!(list_len(&vm.weak_get(&cache[3].1).unwrap().unwrap()), 16);
+
+        vm.pop();
+        vm.gc_collect();
+        for (_, handle) in &cache {
+            assert!(vm.weak_get(handle).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_weak_slots_are_reused() {
+        let mut vm = Vm::new();
+        let mut stale = vec![];
+        for _ in 0..64 {
+            build_list(&mut vm, 4);
+            stale.push(vm.new_weak(*vm.top()).unwrap());
+            vm.pop();
+            vm.pop();
+            vm.gc_collect();
+        }
+        // the slot of a collected referent is given to the next handle.
+        assert_eq!(vm.weak_capacity(), 1);
+
+        // a handle to a collected referent stays none once its slot holds another object.
+        build_list(&mut vm, 4);
+        let live = vm.new_weak(*vm.top()).unwrap();
+        assert!(vm.weak_get(&live).unwrap().is_some());
+        for handle in &stale {
+            assert!(vm.weak_get(handle).unwrap().is_none());
+        }
+    }
+
+    // registers a finalized userdata type that records the handles it releases.
+    fn register_handle_type(vm: &mut Vm, resurrect: bool) -> (usize, Rc<RefCell<Vec<u64>>>) {
+        let finalized = Rc::new(RefCell::new(vec![]));
+        let record = finalized.clone();
+        let type_id = vm.register_userdata_type_with_finalizer(
+            "Handle",
+            Box::new(move |vm, userdata| {
+                record.borrow_mut().push(userdata.handle());
+                if resurrect {
+                    vm.push_stack(Value::from(userdata));
+                }
+                Ok(())
+            }),
+        );
+        (type_id, finalized)
+    }
+
+    #[test]
+    fn test_finalize_dropped_userdata() {
+        let mut vm = Vm::new();
+        let (type_id, finalized) = register_handle_type(&mut vm, false);
+        let start = vm.memory_usage();
+
+        for handle in 0..5 {
+            let userdata = vm.new_userdata(type_id, handle).unwrap();
+            vm.push_stack(Value::from(userdata));
+        }
+
+        // drop the last three references.
+        vm.pop();
+        vm.pop();
+        vm.pop();
+        vm.gc_collect();
+        let mut dropped = finalized.borrow().clone();
+        dropped.sort_unstable();
+        assert_eq!(dropped, vec![2, 3, 4]);
+
+        vm.pop();
+        vm.pop();
+        vm.gc_collect();
+        assert_eq!(finalized.borrow().len(), 5);
+
+        // finalized objects are released by the following collection.
+        vm.gc_collect();
+        assert_eq!(vm.memory_usage(), start);
+    }
+
+    #[test]
+    fn test_finalizer_resurrection() {
+        let mut vm = Vm::new();
+        let (type_id, finalized) = register_handle_type(&mut vm, true);
+        let start = vm.memory_usage();
+
+        vm.new_userdata(type_id, 7).unwrap();
+        vm.gc_collect();
+        assert_eq!(*finalized.borrow(), vec![7]);
+        assert_eq!(vm.top().as_userdata().handle(), 7);
+
+        // a resurrected object survives, but it is never finalized twice.
+        vm.gc_collect();
+        assert_eq!(vm.top().as_userdata().handle(), 7);
+        vm.pop();
+        vm.gc_collect();
+        assert_eq!(finalized.borrow().len(), 1);
+        assert_eq!(vm.memory_usage(), start);
+    }
+
+    #[test]
+    fn test_finalizer_errors_are_reported() {
+        let mut vm = Vm::new();
+        let errors = Rc::new(RefCell::new(vec![]));
+        let record = errors.clone();
+        vm.set_finalizer_error_handler(Box::new(move |err| record.borrow_mut().push(err)));
+        let failing = vm.register_userdata_type_with_finalizer(
+            "Socket",
+            Box::new(|_, _| Err("already closed".to_string())),
+        );
+        let (type_id, finalized) = register_handle_type(&mut vm, false);
+
+        vm.new_userdata(failing, 0).unwrap();
+        vm.new_userdata(type_id, 1).unwrap();
+        vm.gc_collect();
+
+        assert_eq!(
+            *errors.borrow(),
+            vec!["finalizer for Socket failed: already closed".to_string()]
+        );
+        assert_eq!(*finalized.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn test_finalize_on_drop() {
+        let mut vm = Vm::new();
+        let (type_id, finalized) = register_handle_type(&mut vm, true);
+        let userdata = vm.new_userdata(type_id, 3).unwrap();
+        vm.push_stack(Value::from(userdata));
+        drop(vm);
+        assert_eq!(*finalized.borrow(), vec![3]);
+    }
+
+    // sums 0..len in a loop, the locals are the counter and the sum.
+    fn build_sum_module(vm: &mut Vm, len: i32) -> Gc<OxModule> {
+        // nothing is rooted while the module is built.
+        vm.force_no_collection(true);
+        let mut section = vm.new_section();
+        let zero = section.add_constant(Value::I32(0));
+        let one = section.add_constant(Value::I32(1));
+        let len = section.add_constant(Value::I32(len));
+        section.write_index(OpCode::LoadI32, zero);
+        section.write_index(OpCode::LoadI32, zero);
+
+        let start = section.len();
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, len);
+        section.write_op(OpCode::LessI32);
+        let exit = section.write_jmp(OpCode::JmpFalse);
+        section.write_arg(OpCode::LoadLocal, 1);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_op(OpCode::AddI32);
+        section.write_arg(OpCode::SetLocal, 1);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, one);
+        section.write_op(OpCode::AddI32);
+        section.write_arg(OpCode::SetLocal, 0);
+        section.write_loop(start);
+        section.patch_jmp(exit);
+        section.write_arg(OpCode::LoadLocal, 1);
+        section.write_op(OpCode::Return);
+
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("sum");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        vm.force_no_collection(false);
+        module
+    }
+
+    #[test]
+    fn test_run_in_slices() {
+        let mut vm = Vm::new();
+        let module = build_sum_module(&mut vm, 10_000);
+        vm.run_module(module).unwrap();
+        let expected = vm.top().as_i32();
+
+        let mut vm = Vm::new();
+        let module = build_sum_module(&mut vm, 10_000);
+        vm.enter_module(module).unwrap();
+        let mut slices = 0;
+        let result = loop {
+            slices += 1;
+            match vm.run_slice(1000) {
+                SliceResult::Completed(value) => break value.as_i32(),
+                SliceResult::Yielded | SliceResult::Blocked => {}
+                SliceResult::Errored(err) => panic!("{}", err),
+            }
+        };
+
+        assert_eq!(result, expected);
+        assert_eq!(expected, (0..10_000).sum::<i32>());
+        assert!(slices > 100);
+    }
+
+    #[test]
+    fn test_error_snapshot_locals() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+
+        // helper(x) calls its parameter, which is an integer.
+        let mut helper = vm.new_section();
+        helper.name_local(0, "x");
+        helper.mark_line(2);
+        helper.write_arg(OpCode::LoadLocal, 0);
+        helper.write_arg(OpCode::Call, 0);
+        helper.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("helper");
+        let helper = vm.new_function(name, 1, helper);
+
+        let mut main = vm.new_section();
+        let global = main.add_global();
+        main.set_global(global as usize, Value::from(helper));
+        let count = main.add_constant(Value::I32(7));
+        let label = vm.new_gc_string_from_str("label");
+        let label = main.add_constant(Value::from(label));
+        main.mark_line(5);
+        main.name_local(0, "count");
+        main.write_index(OpCode::LoadI32, count);
+        main.name_local(1, "label");
+        main.write_index(OpCode::LoadStr, label);
+        main.mark_line(6);
+        main.write_index(OpCode::LoadGlobal, global);
+        main.write_index(OpCode::LoadI32, count);
+        main.write_arg(OpCode::Call, 1);
+        main.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, main);
+
+        let name = vm.new_string_from_str("errors");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        vm.force_no_collection(false);
+
+        vm.set_error_capture(ErrorCaptureLevel::FramesAndLocals);
+        assert!(vm.run_module(module).is_err());
+        let snapshot = vm.on_error_snapshot().unwrap();
+        assert_eq!(snapshot.frames.len(), 2);
+        assert_eq!(snapshot.frames[0].function, "helper");
+        assert_eq!(snapshot.frames[0].line, Some(2));
+        assert_eq!(snapshot.frames[0].local("x"), Some("7"));
+        assert_eq!(snapshot.frames[1].function, "main");
+        assert_eq!(snapshot.frames[1].line, Some(6));
+        assert_eq!(snapshot.frames[1].local("count"), Some("7"));
+        assert_eq!(snapshot.frames[1].local("label"), Some("\"label\""));
+        assert_eq!(snapshot.frames[1].local("x"), None);
+    }
+
+    /// a module whose entry is main, built from the section.
+    fn module_with_main(vm: &mut Vm, main: crate::Section) -> Gc<OxModule> {
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, main);
+        let name = vm.new_string_from_str("undefined");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        module
+    }
+
+    #[test]
+    fn test_global_read_before_init() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut main = vm.new_section();
+        let global = main.add_global();
+        main.write_index(OpCode::LoadGlobal, global);
+        main.write_op(OpCode::Return);
+        assert!(matches!(main.globals()[0], Value::Undefined));
+        let module = module_with_main(&mut vm, main);
+        vm.force_no_collection(false);
+
+        // checked whether or not debug assertions are enabled.
+        let err = vm.run_module(module).unwrap_err();
+        assert_eq!(err.to_string(), "global 0 of 'main' is read before it is initialized");
+    }
+
+    #[test]
+    fn test_unbounded_recursion_is_a_stack_overflow() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        // down calls itself through its global until the frames run out.
+        let mut down = vm.new_section();
+        let global = down.add_global();
+        down.write_index(OpCode::LoadGlobal, global);
+        down.write_arg(OpCode::Call, 0);
+        down.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("down");
+        let mut down = vm.new_function(name, 0, down);
+        let value = Value::from(down);
+        down.as_ref_mut().section_mut().set_global(global as usize, value);
+
+        let mut main = vm.new_section();
+        let global = main.add_global();
+        main.set_global(global as usize, value);
+        main.write_index(OpCode::LoadGlobal, global);
+        main.write_arg(OpCode::Call, 0);
+        main.write_op(OpCode::Return);
+        let recursive = module_with_main(&mut vm, main);
+        vm.force_no_collection(false);
+
+        let err = vm.run_module(recursive).unwrap_err();
+        assert!(matches!(
+            err,
+            runtime::Error::StackOverflow {
+                depth: DEFAULT_MAX_FRAMES,
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "stack overflow, calls are nested more than 1024 deep: \
+            down, down, down, down, down, down, down, down, .."
+        );
+        assert_eq!(vm.stack_trace().len(), DEFAULT_MAX_FRAMES);
+
+        // the next run drops the frames of the failed one.
+        vm.set_max_frames(2);
+        vm.force_no_collection(true);
+        let mut main = vm.new_section();
+        let seven = main.add_constant(Value::I64(7));
+        main.write_index(OpCode::LoadI64, seven);
+        main.write_op(OpCode::Return);
+        let module = module_with_main(&mut vm, main);
+        vm.force_no_collection(false);
+        vm.run_module(module).unwrap();
+        assert!(matches!(vm.top(), Value::I64(7)));
+        assert_eq!(vm.stack_trace().len(), 0);
+        let err = vm.run_module(recursive).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "stack overflow, calls are nested more than 2 deep: down, main"
+        );
+    }
+
+    #[test]
+    fn test_enter_module_while_a_slice_is_suspended() {
+        let finish = |vm: &mut Vm| loop {
+            match vm.run_slice(1000) {
+                SliceResult::Completed(value) => break value,
+                SliceResult::Yielded | SliceResult::Blocked => {}
+                SliceResult::Errored(err) => panic!("{}", err),
+            }
+        };
+        let mut vm = Vm::new();
+        let sum = build_sum_module(&mut vm, 10_000);
+        vm.force_no_collection(true);
+        let mut main = vm.new_section();
+        let seven = main.add_constant(Value::I64(7));
+        main.write_index(OpCode::LoadI64, seven);
+        main.write_op(OpCode::Return);
+        let module = module_with_main(&mut vm, main);
+        vm.force_no_collection(false);
+        vm.push_stack(Value::from(module));
+
+        // the suspended run is kept and finishes, the module is entered after it.
+        vm.enter_module(sum).unwrap();
+        assert!(matches!(vm.run_slice(1000), SliceResult::Yielded));
+        let err = vm.enter_module(module).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "module 'undefined' is entered while a run is suspended between slices"
+        );
+        assert_eq!(finish(&mut vm).as_i32(), (0..10_000).sum::<i32>());
+        vm.enter_module(module).unwrap();
+        assert!(matches!(finish(&mut vm), Value::I64(7)));
+
+        // a suspended run the host abandons is dropped.
+        vm.enter_module(sum).unwrap();
+        assert!(matches!(vm.run_slice(1000), SliceResult::Yielded));
+        vm.abandon_run();
+        assert_eq!(vm.stack_trace().len(), 0);
+        vm.enter_module(module).unwrap();
+        assert!(matches!(finish(&mut vm), Value::I64(7)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "local 1 of 'main' is read before it is initialized")]
+    fn test_local_read_before_init() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut main = vm.new_section();
+        let one = main.add_constant(Value::I64(1));
+        // the constant is local 0, nothing is local 1.
+        main.write_index(OpCode::LoadI64, one);
+        main.write_arg(OpCode::LoadLocal, 1);
+        main.write_op(OpCode::Return);
+        let module = module_with_main(&mut vm, main);
+        vm.force_no_collection(false);
+        let _ = vm.run_module(module);
+    }
+
+    const POOLED: i32 = 1000;
+
+    fn build_pooled_module(vm: &mut Vm, main: crate::Section) -> Gc<OxModule> {
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, main);
+        let name = vm.new_string_from_str("pool");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        vm.force_no_collection(false);
+        module
+    }
+
+    fn pooled_tuple(vm: &mut Vm) -> Value {
+        let mut elements = vm.new_vec();
+        (0..POOLED).for_each(|idx| elements.push(Value::I32(idx)));
+        Value::from(vm.new_tuple(elements).unwrap())
+    }
+
+    #[test]
+    fn test_pooled_tuple_is_not_allocated() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut section = vm.new_section();
+        let tuple = pooled_tuple(&mut vm);
+        let list = section.intern_constant(tuple);
+        let other = pooled_tuple(&mut vm);
+        assert_eq!(section.intern_constant(other), list);
+        let zero = section.intern_constant(Value::I32(0));
+        let one = section.intern_constant(Value::I32(1));
+        let count = section.intern_constant(Value::I32(1000));
+
+        // for i in 0..1000 { let list = (0, 1, ..) }
+        section.write_index(OpCode::LoadI32, zero);
+        let start = section.len();
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, count);
+        section.write_op(OpCode::LessI32);
+        let exit = section.write_jmp(OpCode::JmpFalse);
+        section.write_index(OpCode::LoadConst, list);
+        section.write_op(OpCode::Pop);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, one);
+        section.write_op(OpCode::AddI32);
+        section.write_arg(OpCode::SetLocal, 0);
+        section.write_loop(start);
+        section.patch_jmp(exit);
+        section.write_index(OpCode::LoadConst, list);
+        section.write_op(OpCode::Return);
+        let module = build_pooled_module(&mut vm, section);
+
+        let used = vm.memory_usage();
+        vm.run_module(module).unwrap();
+        assert_eq!(vm.memory_usage(), used);
+        assert_eq!(vm.top().addr(), tuple.addr());
+    }
+
+    #[test]
+    fn test_pooled_instance_is_copied() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut section = vm.new_section();
+        let name = vm.new_gc_string_from_str("List");
+        let name = section.intern_constant(Value::from(name));
+        let fields = pooled_tuple(&mut vm);
+        let fields = section.intern_constant(fields);
+        let value = section.intern_constant(Value::I32(-1));
+
+        // let a = List { .. }; a.0 = -1; let b = List { .. }; b
+        section.write_index(OpCode::LoadStr, name);
+        section.write_index(OpCode::NewInstanceConst, fields);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, value);
+        section.write_arg(OpCode::SetAttr, 0);
+        section.write_index(OpCode::LoadStr, name);
+        section.write_index(OpCode::NewInstanceConst, fields);
+        section.write_op(OpCode::Return);
+        let module = build_pooled_module(&mut vm, section);
+
+        vm.run_module(module).unwrap();
+        let copy = vm.top().as_instance();
+        assert_eq!(copy.get_attr(0).as_i32(), 0);
+        assert_eq!(copy.fields().len(), POOLED as usize);
+    }
+
+    #[test]
+    fn test_pooled_array_is_copied() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut section = vm.new_section();
+        let template = pooled_tuple(&mut vm);
+        let elements = section.intern_constant(template);
+        let zero = section.intern_constant(Value::I32(0));
+        let value = section.intern_constant(Value::I32(-1));
+
+        // let a = [0, 1, ..]; a[0] = -1; let b = [0, 1, ..]; a[0] + b[0]
+        section.write_index(OpCode::NewArrayConst, elements);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, zero);
+        section.write_index(OpCode::LoadI32, value);
+        section.write_op(OpCode::StoreIndex);
+        section.write_index(OpCode::NewArrayConst, elements);
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_index(OpCode::LoadI32, zero);
+        section.write_op(OpCode::LoadIndex);
+        section.write_arg(OpCode::LoadLocal, 1);
+        section.write_index(OpCode::LoadI32, zero);
+        section.write_op(OpCode::LoadIndex);
+        section.write_op(OpCode::AddI32);
+        section.write_op(OpCode::Return);
+        assert!(section.verify().is_ok());
+        let module = build_pooled_module(&mut vm, section);
+
+        vm.run_module(module).unwrap();
+        assert_eq!(vm.top().as_i32(), -1);
+        let template = template.as_tuple().elements();
+        assert_eq!(template.len(), POOLED as usize);
+        assert_eq!(template[0].as_i32(), 0);
+    }
+}
@@ -0,0 +1,19 @@
+use crate::{runtime, Value, Vm};
+
+/// what a native function did when it was called.
+#[derive(Debug, Clone, Copy)]
+pub enum NativeResult {
+    Return(Value),
+    /// the native cannot make progress yet, the call is retried when the vm is resumed.
+    Blocked,
+}
+
+/// host function callable from scripts, it is given the actual parameters of the call.
+pub type NativeFunction = Box<dyn FnMut(&mut Vm, &[Value]) -> Result<NativeResult, runtime::Error>>;
+
+pub(crate) struct Native {
+    pub name: String,
+    pub arity: u8,
+    /// taken while the native is running.
+    pub function: Option<NativeFunction>,
+}
@@ -36,6 +36,7 @@ define_opcodes!(
     "load_f64" => LoadF64,
     "load_str" => LoadStr,
     "load_char" => LoadChar,
+    "load_const" => LoadConst,
     "load_local" => LoadLocal,
     "set_local" => SetLocal,
     "push_local" => PushLocal,
@@ -84,6 +85,35 @@ define_opcodes!(
     "div_f32" => DivF32,
     "div_f64" => DivF64,
 
+    "rem_i8"  => RemI8,
+    "rem_i16" => RemI16,
+    "rem_i32" => RemI32,
+    "rem_i64" => RemI64,
+    "rem_u8"  => RemU8,
+    "rem_u16" => RemU16,
+    "rem_u32" => RemU32,
+    "rem_u64" => RemU64,
+    "rem_f32" => RemF32,
+    "rem_f64" => RemF64,
+
+    "div_floor_i8"  => DivFloorI8,
+    "div_floor_i16" => DivFloorI16,
+    "div_floor_i32" => DivFloorI32,
+    "div_floor_i64" => DivFloorI64,
+    "div_floor_u8"  => DivFloorU8,
+    "div_floor_u16" => DivFloorU16,
+    "div_floor_u32" => DivFloorU32,
+    "div_floor_u64" => DivFloorU64,
+
+    "mod_floor_i8"  => ModFloorI8,
+    "mod_floor_i16" => ModFloorI16,
+    "mod_floor_i32" => ModFloorI32,
+    "mod_floor_i64" => ModFloorI64,
+    "mod_floor_u8"  => ModFloorU8,
+    "mod_floor_u16" => ModFloorU16,
+    "mod_floor_u32" => ModFloorU32,
+    "mod_floor_u64" => ModFloorU64,
+
     "less_i8"  => LessI8,
     "less_i16" => LessI16,
     "less_i32" => LessI32,
@@ -128,32 +158,194 @@ define_opcodes!(
     "greatereq_f32" => GreaterEqF32,
     "greatereq_f64" => GreaterEqF64,
 
+    "eqeq_i8"  => EqEqI8,
+    "eqeq_i16" => EqEqI16,
+    "eqeq_i32" => EqEqI32,
+    "eqeq_i64" => EqEqI64,
+    "eqeq_u8"  => EqEqU8,
+    "eqeq_u16" => EqEqU16,
+    "eqeq_u32" => EqEqU32,
+    "eqeq_u64" => EqEqU64,
+    "eqeq_f32" => EqEqF32,
+    "eqeq_f64" => EqEqF64,
+
+    "noteq_i8"  => NotEqI8,
+    "noteq_i16" => NotEqI16,
+    "noteq_i32" => NotEqI32,
+    "noteq_i64" => NotEqI64,
+    "noteq_u8"  => NotEqU8,
+    "noteq_u16" => NotEqU16,
+    "noteq_u32" => NotEqU32,
+    "noteq_u64" => NotEqU64,
+    "noteq_f32" => NotEqF32,
+    "noteq_f64" => NotEqF64,
+    // functions are equal when they are the same function.
+    "eqeq_fn" => EqEqFn,
+    "noteq_fn" => NotEqFn,
+    // strings are equal when they have the same contents.
+    "eqeq_str" => EqEqStr,
+    "noteq_str" => NotEqStr,
+    // a new string of the contents of the two strings on the stack.
+    "concat_str" => ConcatStr,
+
+    "binary_and_i8" => BinaryAndI8,
+    "binary_and_i16" => BinaryAndI16,
+    "binary_and_i32" => BinaryAndI32,
+    "binary_and_i64" => BinaryAndI64,
+    "binary_and_u8" => BinaryAndU8,
+    "binary_and_u16" => BinaryAndU16,
+    "binary_and_u32" => BinaryAndU32,
+    "binary_and_u64" => BinaryAndU64,
+
+    "binary_or_i8" => BinaryOrI8,
+    "binary_or_i16" => BinaryOrI16,
+    "binary_or_i32" => BinaryOrI32,
+    "binary_or_i64" => BinaryOrI64,
+    "binary_or_u8" => BinaryOrU8,
+    "binary_or_u16" => BinaryOrU16,
+    "binary_or_u32" => BinaryOrU32,
+    "binary_or_u64" => BinaryOrU64,
+
+    "binary_xor_i8" => BinaryXorI8,
+    "binary_xor_i16" => BinaryXorI16,
+    "binary_xor_i32" => BinaryXorI32,
+    "binary_xor_i64" => BinaryXorI64,
+    "binary_xor_u8" => BinaryXorU8,
+    "binary_xor_u16" => BinaryXorU16,
+    "binary_xor_u32" => BinaryXorU32,
+    "binary_xor_u64" => BinaryXorU64,
+
+    // the amount of a shift is taken modulo the bits of the type, a right shift of a signed
+    // integer keeps its sign.
+    "shift_left_i8" => ShiftLeftI8,
+    "shift_left_i16" => ShiftLeftI16,
+    "shift_left_i32" => ShiftLeftI32,
+    "shift_left_i64" => ShiftLeftI64,
+    "shift_left_u8" => ShiftLeftU8,
+    "shift_left_u16" => ShiftLeftU16,
+    "shift_left_u32" => ShiftLeftU32,
+    "shift_left_u64" => ShiftLeftU64,
+
+    "shift_right_i8" => ShiftRightI8,
+    "shift_right_i16" => ShiftRightI16,
+    "shift_right_i32" => ShiftRightI32,
+    "shift_right_i64" => ShiftRightI64,
+    "shift_right_u8" => ShiftRightU8,
+    "shift_right_u16" => ShiftRightU16,
+    "shift_right_u32" => ShiftRightU32,
+    "shift_right_u64" => ShiftRightU64,
+
     "return" => Return,
 
     "load_global" => LoadGlobal,
     "set_global" => SetGlobal,
     "load_true" => LoadTrue,
     "load_false" => LoadFalse,
+    "load_unit" => LoadUnit,
+    "cast_int" => CastInt,
     "set_register" => SetRegister,
     "load_register" => LoadRegister,
     "load_assoc" => LoadAssoc,
 
     "new_instance" => NewInstance,
     "new_tuple" => NewTuple,
+    "new_instance_const" => NewInstanceConst,
     "object_attr" => InstanceAttr,
     "tuple_attr" => TupleAttr,
     "set_attr" => SetAttr,
+    // the public field named by the string on the stack of the instance below it, unit when the
+    // value is not an instance or its structure has no such field.
+    "get_field" => GetField,
+    // an array of the elements on the stack, the operand is their count.
+    "new_array" => NewArray,
+    // the element of the array below the index on the stack.
+    "load_index" => LoadIndex,
+    // stores the top of the stack in the array below the index below it.
+    "store_index" => StoreIndex,
+    // appends the top of the stack to the list below it, it leaves unit.
+    "list_push" => ListPush,
+    // removes the last element of the list on the stack and leaves it.
+    "list_pop" => ListPop,
+    // the number of elements of the list on the stack, an i64.
+    "list_len" => ListLen,
+    "new_map" => NewMap,
+    // sets the top of the stack as the value of the key below it in the map below both, it
+    // leaves unit.
+    "map_insert" => MapInsert,
+    // the value of the key on the stack in the map below it, unit when it has none.
+    "map_get" => MapGet,
+    // removes the key on the stack from the map below it and leaves its value or unit.
+    "map_remove" => MapRemove,
+    // whether the map below the key on the stack has it, a bool.
+    "map_contains" => MapContains,
+    // the number of entries of the map on the stack, an i64.
+    "map_len" => MapLen,
 
     "jmp_if_true" => JmpTrue,
     "jmp_if_false" => JmpFalse,
+    // jumps when the top of the stack is unit without popping it.
+    "jmp_if_unit" => JmpUnit,
     "jmp" => Jmp,
     "loop" => Loop,
     "exit" => Exit,
     "call" => Call,
+    // a closure of the function below the operand count values on the stack, it captures them.
+    "new_closure" => NewClosure,
     "label" => Label,
     "pop" => Pop,
     "echo" => Echo,
+    "log_enabled" => LogEnabled,
+    "log" => Log,
+    // runs the function below the actuals on a thread of its own, the handle replaces them.
+    "spawn" => Spawn,
+    // waits for the task handle on the stack and replaces it with (result, error).
+    "join" => Join,
+    // raises a runtime error with the message on the stack.
+    "trap" => Trap,
+    // raises a runtime error with the message below the operand count values filling its `{}`.
+    "trap_format" => TrapFormat,
+    // pushes the string the host running the vm identifies itself by, see `Vm::set_host`.
+    "load_host" => LoadHost,
+    // compiles the source on the stack as a script and runs it, the source is replaced with
+    // (value, error), see `Vm::set_eval_compiler`.
+    "eval" => Eval,
+    // a weak handle to the object on the stack, see `Vm::new_weak`.
+    "new_weak" => NewWeak,
+    // the referent of the weak handle on the stack, unit once it has been collected.
+    "weak_get" => WeakGet,
+    // runs a full collection, it leaves unit.
+    "gc_collect" => GcCollect,
+    // registers the function on the stack to finalize the instance below it once it becomes
+    // unreachable, it pops the function and leaves the instance.
+    "register_finalizer" => RegisterFinalizer,
+    // an array of the elements of the pooled tuple named by the operand, they are copied
+    // because arrays can be assigned.
+    "new_array_const" => NewArrayConst,
+    // a list with room for the i64 count on the stack, a negative count is an error.
+    "list_with_capacity" => ListWithCapacity,
+    // appends the elements of the list on the stack to the list below it, it leaves unit.
+    "list_extend" => ListExtend,
+    // copies the count on the stack of elements of the source list below the source start and
+    // destination start into the destination list below the source, it leaves unit.
+    "list_copy" => ListCopy,
+    // sets every element of the list below the value on the stack to it, it leaves unit.
+    "list_fill" => ListFill,
+    // sets the elements from the start to the end below the value on the stack of the list
+    // below them to the value, it leaves unit.
+    "list_fill_range" => ListFillRange,
+    // makes room in the list below the i64 count on the stack for that many more elements, a
+    // negative count is an error. It leaves unit.
+    "list_reserve" => ListReserve,
+    // the number of elements the list on the stack holds without growing, as an i64.
+    "list_capacity" => ListCapacity,
+    // moves the elements of the list on the stack to a buffer that fits them, it leaves unit.
+    "list_shrink_to_fit" => ListShrinkToFit,
+    // makes room in the map below the i64 count on the stack for that many more keys, a
+    // negative count is an error. It leaves unit.
+    "map_reserve" => MapReserve,
     "frame_stack" => FrameStack,
+    // the operand of the next instruction is a u32 index instead of a u8.
+    "wide" => Wide,
     "__NUMOPS__" => NumOps,
     OpCode
 );
@@ -175,8 +367,10 @@ impl OpCode {
 pub struct Instruction {
     offset: usize,
     op_code: OpCode,
-    args: Option<u16>,
+    args: Option<u32>,
     con: Option<Value>,
+    /// the offset a jump continues at when it is taken.
+    target: Option<usize>,
 }
 
 impl PartialEq for Instruction {
@@ -195,36 +389,57 @@ impl Instruction {
         Self::new(offset, op_code, None, None)
     }
 
-    pub fn with_arg(offset: usize, op_code: OpCode, args: u16) -> Self {
+    pub fn with_arg(offset: usize, op_code: OpCode, args: u32) -> Self {
         Self::new(offset, op_code, Some(args), None)
     }
 
-    pub fn with_arg_and_const(offset: usize, op_code: OpCode, args: u16, con: Value) -> Self {
+    pub fn with_arg_and_const(offset: usize, op_code: OpCode, args: u32, con: Value) -> Self {
         Self::new(offset, op_code, Some(args), Some(con))
     }
 
-    pub fn new(offset: usize, op_code: OpCode, args: Option<u16>, con: Option<Value>) -> Self {
+    /// a jump by args bytes that continues at target.
+    pub fn jump(offset: usize, op_code: OpCode, args: u32, target: usize) -> Self {
+        Self {
+            target: Some(target),
+            ..Self::with_arg(offset, op_code, args)
+        }
+    }
+
+    pub fn new(offset: usize, op_code: OpCode, args: Option<u32>, con: Option<Value>) -> Self {
         Self {
             offset,
             op_code,
             args,
             con,
+            target: None,
         }
     }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn op_code(&self) -> OpCode {
+        self.op_code
+    }
+
+    pub fn target(&self) -> Option<usize> {
+        self.target
+    }
 }
 
 impl Display for Instruction {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{:010x} {} {}",
-            self.offset,
-            self.op_code,
-            self.args.map_or("".to_string(), |arg| arg.to_string())
-        )?;
+        write!(f, "{:010x} {}", self.offset, self.op_code)?;
+        if let Some(args) = self.args {
+            write!(f, " {}", args)?;
+        }
         if let Some(con) = self.con.as_ref() {
             write!(f, " ({})", con)?;
         }
+        if let Some(target) = self.target {
+            write!(f, " -> {:010x}", target)?;
+        }
         Ok(())
     }
 }
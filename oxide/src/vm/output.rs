@@ -0,0 +1,173 @@
+use std::io::Write;
+
+use crate::{runtime, Vm};
+
+/// what the vm does when writing the output of `echo` fails, a pipe closed by its reader for
+/// example.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoErrorPolicy {
+    /// the failure is a runtime error of the script, a task raising it fails its join.
+    Raise,
+    /// the output is dropped from then on and the script keeps running, like a unix program
+    /// writing to `head` after it has read enough.
+    Ignore,
+    /// execution stops with `runtime::Error::ExitedDueToIo`, the driver exits with the code it
+    /// chooses for it.
+    Exit,
+}
+
+/// where the output of the vm goes and what happens when it fails.
+pub(crate) struct Output {
+    sink: Box<dyn Write>,
+    policy: IoErrorPolicy,
+    /// set once a write failed under `IoErrorPolicy::Ignore`.
+    closed: bool,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            sink: Box::new(std::io::stdout()),
+            policy: IoErrorPolicy::Raise,
+            closed: false,
+        }
+    }
+}
+
+impl Vm {
+    /// replaces stdout as the destination of `echo`.
+    pub fn set_output(&mut self, sink: Box<dyn Write>) {
+        self.output.sink = sink;
+        self.output.closed = false;
+    }
+
+    pub fn set_io_error_policy(&mut self, policy: IoErrorPolicy) {
+        self.output.policy = policy;
+    }
+
+    /// writes the parts one after the other, a failure part way leaves what was written so far.
+    pub(super) fn write_output(&mut self, parts: &[&str]) -> Result<(), runtime::Error> {
+        if self.output.closed {
+            return Ok(());
+        }
+        let written = parts
+            .iter()
+            .try_for_each(|part| self.output.sink.write_all(part.as_bytes()))
+            .and_then(|_| self.output.sink.flush());
+        match (written, self.output.policy) {
+            (Ok(()), _) => Ok(()),
+            (Err(err), IoErrorPolicy::Raise) => Err(runtime::Error::io(&err)),
+            (Err(_), IoErrorPolicy::Ignore) => {
+                self.output.closed = true;
+                Ok(())
+            }
+            (Err(err), IoErrorPolicy::Exit) => Err(runtime::Error::exited_due_to_io(&err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io, rc::Rc};
+
+    use super::IoErrorPolicy;
+    use crate::{runtime, vm::OpCode, Value, Vm};
+
+    /// accepts limit bytes and fails every write after them.
+    struct Failing {
+        written: Rc<RefCell<Vec<u8>>>,
+        limit: usize,
+    }
+
+    impl io::Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut written = self.written.borrow_mut();
+            let room = self.limit - written.len();
+            if room == 0 {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            let len = room.min(buf.len());
+            written.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// echoes every line and returns the number of lines, with the output limited to limit bytes.
+    fn echo_lines(
+        lines: &[&str],
+        policy: IoErrorPolicy,
+        limit: usize,
+    ) -> (Result<Value, runtime::Error>, String) {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let written = Rc::new(RefCell::new(vec![]));
+        vm.set_output(Box::new(Failing {
+            written: written.clone(),
+            limit,
+        }));
+        vm.set_io_error_policy(policy);
+
+        let mut section = vm.new_section();
+        for line in lines {
+            let constant = Value::from(vm.new_gc_string_from_str(line));
+            let index = section.add_constant(constant);
+            section.write_index(OpCode::LoadStr, index);
+            section.write_op(OpCode::Echo);
+        }
+        let count = section.add_constant(Value::I64(lines.len() as i64));
+        section.write_index(OpCode::LoadI64, count);
+        section.write_op(OpCode::Return);
+
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("output");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        let result = vm.run_module(module).map(|_| *vm.top());
+
+        let written = String::from_utf8(written.borrow().clone()).unwrap();
+        (result, written)
+    }
+
+    const LINES: [&str; 3] = ["first", "second", "third"];
+
+    #[test]
+    fn test_output_within_the_limit() {
+        for policy in [IoErrorPolicy::Raise, IoErrorPolicy::Ignore, IoErrorPolicy::Exit] {
+            let (result, written) = echo_lines(&LINES, policy, 1024);
+            assert!(matches!(result, Ok(Value::I64(3))));
+            assert_eq!(written, "first\nsecond\nthird\n");
+        }
+    }
+
+    #[test]
+    fn test_io_error_policies() {
+        // the output fails in the middle of the second line.
+        let (result, written) = echo_lines(&LINES, IoErrorPolicy::Raise, 9);
+        assert_eq!(written, "first\nsec");
+        match result {
+            Err(runtime::Error::Io(message)) => assert_eq!(message, "broken pipe"),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        // the script keeps running without output.
+        let (result, written) = echo_lines(&LINES, IoErrorPolicy::Ignore, 9);
+        assert_eq!(written, "first\nsec");
+        assert!(matches!(result, Ok(Value::I64(3))));
+
+        // the line is complete but its newline is not written.
+        let (result, written) = echo_lines(&LINES, IoErrorPolicy::Exit, 12);
+        assert_eq!(written, "first\nsecond");
+        match result {
+            Err(err @ runtime::Error::ExitedDueToIo(_)) => {
+                assert_eq!(err.to_string(), "execution stopped, the output failed: broken pipe")
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{
+    compiled::{ApiItem, ApiKind, CompiledModule, ModuleApi, ModuleLayout},
+    gc::Gc,
+    runtime, OxFunction, OxModule, Value,
+};
+
+use super::Vm;
+
+impl Vm {
+    /// swaps the body of a function of the running module for the one in `replacement`, a
+    /// newer build of the same module. The next call of the function runs the new body while
+    /// calls already in progress finish on the old one. Only bodies can change, a replacement
+    /// declaring anything differently is refused along with every difference found. The
+    /// handles of exported functions looked up before the replacement are refused after it.
+    pub fn replace_function(
+        &mut self,
+        name: &str,
+        replacement: &CompiledModule,
+    ) -> Result<(), runtime::Error> {
+        let module = self
+            .running
+            .ok_or_else(|| runtime::Error::no_running_module(name))?;
+
+        let mut mismatches = match module.layout() {
+            Some(layout) => layout_mismatches(layout, &replacement.layout()),
+            None => vec!["the running module was not loaded from a compiled module".to_owned()],
+        };
+        let target = find_function(module, name);
+        let compiled = replacement.function(name);
+        if target.is_none() {
+            mismatches.push(format!(
+                "'{}' is not a function of the running module",
+                name
+            ));
+        } else if compiled.is_none() {
+            mismatches.push(format!("'{}' is not a function of the replacement", name));
+        }
+        let (mut target, compiled) = match (target, compiled) {
+            (Some(target), Some(compiled)) if mismatches.is_empty() => (target, compiled),
+            _ => return Err(runtime::Error::incompatible_replacement(name, mismatches)),
+        };
+
+        let no_collection = self.collection_disabled();
+        self.force_no_collection(true);
+        let mut copy = compiled.load(self);
+        compiled.link(copy, module, self);
+        // the copy is given the old body and the frames running it, so every other reference
+        // to the function sees the new body.
+        std::mem::swap(
+            target.as_ref_mut().section_mut(),
+            copy.as_ref_mut().section_mut(),
+        );
+        for frame in &mut self.call_stack[0..self.top_frame] {
+            if frame.function.ptr() == target.ptr() {
+                frame.function = copy;
+            }
+        }
+        self.force_no_collection(no_collection);
+        self.replacements += 1;
+        Ok(())
+    }
+}
+
+/// the function named like it is in a source map, `Point.len` for a method.
+pub(super) fn find_function(module: Gc<OxModule>, name: &str) -> Option<Gc<OxFunction>> {
+    let (structure, name) = match name.rsplit_once('.') {
+        Some((structure, method)) => (Some(structure), method),
+        None => (None, name),
+    };
+    module
+        .objects()
+        .iter()
+        .find_map(|object| match (object, structure) {
+            (Value::Function(function), None) if function.name().as_str() == name => {
+                Some(*function)
+            }
+            (Value::Struct(found), Some(structure)) if found.name().as_str() == structure => found
+                .methods()
+                .iter()
+                .find(|method| method.name().as_str() == name)
+                .copied(),
+            _ => None,
+        })
+}
+
+/// why the functions of a module with the `new` layout can not run against the objects of one
+/// with the `old` layout.
+fn layout_mismatches(old: &ModuleLayout, new: &ModuleLayout) -> Vec<String> {
+    let mut mismatches = match (&old.api, &new.api) {
+        (Some(old), Some(new)) => api_mismatches(old, new),
+        (None, _) => vec!["the running module has no api".to_owned()],
+        (_, None) => vec!["the replacement has no api".to_owned()],
+    };
+    // a difference in the api already accounts for the objects that differ.
+    if !mismatches.is_empty() {
+        return mismatches;
+    }
+
+    if old.objects.len() != new.objects.len() {
+        mismatches.push(format!(
+            "the module has {} objects instead of {}",
+            new.objects.len(),
+            old.objects.len()
+        ));
+    }
+    for (old, new) in old.objects.iter().zip(&new.objects) {
+        if old != new {
+            mismatches.push(format!("{} is now {}", old, new));
+        }
+    }
+    mismatches
+}
+
+/// every difference between the items, private ones included, as the code of a function
+/// depends on all of them.
+fn api_mismatches(old: &ModuleApi, new: &ModuleApi) -> Vec<String> {
+    let mut mismatches = vec![];
+    items(&mut mismatches, "", &old.items, &new.items);
+    mismatches
+}
+
+fn items(mismatches: &mut Vec<String>, prefix: &str, old: &[ApiItem], new: &[ApiItem]) {
+    for old in old {
+        let path = format!("{}{}", prefix, old.name);
+        match new.iter().find(|item| item.name == old.name) {
+            None => mismatches.push(format!("'{}' was removed", path)),
+            Some(new) => item(mismatches, &path, &old.kind, &new.kind),
+        }
+    }
+    for new in new {
+        if !old.iter().any(|item| item.name == new.name) {
+            mismatches.push(format!("'{}{}' was added", prefix, new.name));
+        }
+    }
+}
+
+fn item(mismatches: &mut Vec<String>, path: &str, old: &ApiKind, new: &ApiKind) {
+    match (old, new) {
+        (ApiKind::Function(old), ApiKind::Function(new)) if old != new => {
+            mismatches.push(format!("'{}' changed from '{}' to '{}'", path, old, new))
+        }
+        (
+            ApiKind::Struct {
+                fields: old_fields,
+                methods: old_methods,
+            },
+            ApiKind::Struct {
+                fields: new_fields,
+                methods: new_methods,
+            },
+        ) => {
+            if old_fields != new_fields {
+                mismatches.push(format!("the layout of struct '{}' changed", path));
+            }
+            items(
+                mismatches,
+                format!("{}.", path).as_str(),
+                old_methods,
+                new_methods,
+            );
+        }
+        (ApiKind::Enum { .. }, ApiKind::Enum { .. }) if old != new => {
+            mismatches.push(format!("the variants of enum '{}' changed", path))
+        }
+        (ApiKind::Variable { .. }, ApiKind::Variable { .. }) if old != new => {
+            mismatches.push(format!("the global '{}' changed", path))
+        }
+        (old, new) if std::mem::discriminant(old) != std::mem::discriminant(new) => mismatches
+            .push(format!(
+                "'{}' changed from a {} to a {}",
+                path,
+                old.name(),
+                new.name()
+            )),
+        _ => {}
+    }
+}
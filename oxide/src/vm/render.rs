@@ -0,0 +1,277 @@
+use itertools::Itertools;
+
+use crate::{gc::Address, Value};
+
+/// limits on how much of a value is rendered, a marker takes the place of whatever is elided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// objects nested deeper than this are rendered as `...`.
+    pub max_depth: usize,
+    /// elements of a list or tuple, fields of an instance or entries of a map, rendered before
+    /// the rest is elided.
+    pub max_elements: usize,
+    /// characters of a string rendered before the rest is elided.
+    pub max_string_len: usize,
+    /// follow every primitive value with its type, `1: i32`.
+    pub show_types: bool,
+}
+
+impl RenderOptions {
+    /// values printed by a script, only very large values are elided.
+    pub fn print() -> Self {
+        Self {
+            max_depth: 16,
+            max_elements: 1000,
+            max_string_len: usize::MAX,
+            show_types: false,
+        }
+    }
+
+    /// values shown back to the user interactively.
+    pub fn repl() -> Self {
+        Self {
+            max_depth: 8,
+            max_elements: 100,
+            max_string_len: 1024,
+            show_types: true,
+        }
+    }
+
+    /// locals captured when a runtime error occurs, a snapshot should stay a few lines long.
+    pub fn error_capture() -> Self {
+        Self {
+            max_depth: 3,
+            max_elements: 8,
+            max_string_len: 64,
+            show_types: false,
+        }
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self::print()
+    }
+}
+
+/// renders a value within the limits of the options. An object that contains itself is
+/// rendered as `<cycle>` where it is reached again.
+pub fn render(value: &Value, options: &RenderOptions) -> String {
+    Renderer {
+        options,
+        parents: vec![],
+    }
+    .render(value)
+}
+
+struct Renderer<'a> {
+    options: &'a RenderOptions,
+    /// objects currently being rendered, outer most first.
+    parents: Vec<Address>,
+}
+
+impl<'a> Renderer<'a> {
+    fn render(&mut self, value: &Value) -> String {
+        match value {
+            Value::Instance(instance) => {
+                let fields = instance.fields();
+                self.render_object(
+                    instance.ptr(),
+                    &format!("{} {{ ", instance.name()),
+                    fields,
+                    " }",
+                )
+            }
+            Value::Tuple(tuple) => self.render_object(tuple.ptr(), "(", tuple.elements(), ")"),
+            Value::List(list) => self.render_object(list.ptr(), "[", list.elements(), "]"),
+            Value::Map(map) => {
+                let entries = map.entries().map(|(key, value)| (Some(key), value));
+                self.render_entries(map.ptr(), "{", map.len(), entries, "}")
+            }
+            Value::String(val) => self.typed(self.render_string(val.as_str()), value),
+            Value::Function(_)
+            | Value::Closure(_)
+            | Value::Struct(_)
+            | Value::Module(_)
+            | Value::UserData(_)
+            | Value::Weak(_)
+            | Value::Native(_) => value.to_string(),
+            _ => self.typed(value.to_string(), value),
+        }
+    }
+
+    fn render_object(
+        &mut self,
+        address: Address,
+        open: &str,
+        elements: &[Value],
+        close: &str,
+    ) -> String {
+        let entries = elements.iter().map(|element| (None, element));
+        self.render_entries(address, open, elements.len(), entries, close)
+    }
+
+    /// renders len entries, those with a key as `key: value`.
+    fn render_entries<'v>(
+        &mut self,
+        address: Address,
+        open: &str,
+        len: usize,
+        entries: impl Iterator<Item = (Option<&'v Value>, &'v Value)>,
+        close: &str,
+    ) -> String {
+        if self.parents.contains(&address) {
+            return format!("{}<cycle>{}", open, close);
+        }
+        if self.parents.len() >= self.options.max_depth {
+            return format!("{}...{}", open, close);
+        }
+
+        self.parents.push(address);
+        let mut rendered = entries
+            .take(self.options.max_elements)
+            .map(|(key, value)| match key {
+                Some(key) => format!("{}: {}", self.render(key), self.render(value)),
+                None => self.render(value),
+            })
+            .collect_vec();
+        self.parents.pop();
+
+        if len > self.options.max_elements {
+            rendered.push(format!("... {} more", len - self.options.max_elements));
+        }
+        format!("{}{}{}", open, rendered.join(", "), close)
+    }
+
+    fn render_string(&self, val: &str) -> String {
+        let len = val.chars().count();
+        if len <= self.options.max_string_len {
+            return format!("{:?}", val);
+        }
+
+        let kept = val
+            .chars()
+            .take(self.options.max_string_len)
+            .collect::<String>();
+        format!(
+            "{:?}... {} more chars",
+            kept,
+            len - self.options.max_string_len
+        )
+    }
+
+    fn typed(&self, rendered: String, value: &Value) -> String {
+        if self.options.show_types {
+            format!("{}: {}", rendered, value.ty())
+        } else {
+            rendered
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, RenderOptions, Renderer};
+    use crate::{gc::Address, AttributeAccess, Value, Vm};
+
+    #[test]
+    fn test_render_cyclic_instance() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let name = vm.new_gc_string_from_str("Node");
+        let fields = vm.vec_fill_with_capacity(2, Value::I32(1));
+        let mut node = vm.new_instance(name, fields).unwrap();
+        let elements = vm.vec_fill_with_capacity(2, Value::Unit);
+        let mut pair = vm.new_tuple(elements).unwrap();
+        *pair.as_ref_mut().get_attr_mut(0) = Value::from(node);
+        *node.as_ref_mut().get_attr_mut(0) = Value::from(pair);
+
+        let options = RenderOptions::error_capture();
+        assert_eq!(
+            render(&Value::from(node), &options),
+            "Node { (Node { <cycle> }, ()), 1 }"
+        );
+
+        // the same object twice in one parent is not a cycle.
+        let elements = vm.vec_fill_with_capacity(2, Value::I32(1));
+        let twice = vm.new_tuple(elements).unwrap();
+        let elements = vm.vec_fill_with_capacity(2, Value::from(twice));
+        let outer = vm.new_tuple(elements).unwrap();
+        assert_eq!(
+            render(&Value::from(outer), &RenderOptions::repl()),
+            "((1: i32, 1: i32), (1: i32, 1: i32))"
+        );
+    }
+
+    #[test]
+    fn test_render_truncates_large_values() {
+        // the elements of a list this large do not fit the pools of the allocator.
+        let elements = vec![Value::I64(7); 10_000];
+        let render_list = |options: &RenderOptions| {
+            Renderer {
+                options,
+                parents: vec![],
+            }
+            .render_object(Address::null(), "[", &elements, "]")
+        };
+        assert_eq!(
+            render_list(&RenderOptions::error_capture()),
+            "[7, 7, 7, 7, 7, 7, 7, 7, ... 9992 more]"
+        );
+        assert!(render_list(&RenderOptions::print()).ends_with(", 7, ... 9000 more]"));
+
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let string = vm.new_gc_string_from_str(&"a".repeat(100));
+        let options = RenderOptions {
+            max_string_len: 4,
+            ..RenderOptions::print()
+        };
+        assert_eq!(
+            render(&Value::from(string), &options),
+            "\"aaaa\"... 96 more chars"
+        );
+    }
+
+    #[test]
+    fn test_render_deeply_nested_instance() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut value = Value::I32(0);
+        for _ in 0..100 {
+            let name = vm.new_gc_string_from_str("Wrap");
+            let fields = vm.vec_fill_with_capacity(1, value);
+            value = Value::from(vm.new_instance(name, fields).unwrap());
+        }
+
+        assert_eq!(
+            render(&value, &RenderOptions::error_capture()),
+            "Wrap { Wrap { Wrap { Wrap { ... } } } }"
+        );
+        let options = RenderOptions {
+            max_depth: 100,
+            ..RenderOptions::repl()
+        };
+        let rendered = render(&value, &options);
+        assert_eq!(rendered.matches("Wrap").count(), 100);
+        assert!(rendered.contains("Wrap { 0: i32 }"));
+    }
+
+    #[test]
+    fn test_render_map() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let mut map = vm.new_map().unwrap();
+        let key = vm.new_gc_string_from_str("self");
+        let value = Value::from(map);
+        map.as_ref_mut().insert(Value::from(key), value).unwrap();
+        assert_eq!(render(&value, &RenderOptions::print()), "{\"self\": {<cycle>}}");
+
+        for i in 0..20 {
+            map.as_ref_mut().insert(Value::I32(i), Value::Bool(true)).unwrap();
+        }
+        let rendered = render(&Value::from(map), &RenderOptions::error_capture());
+        assert!(rendered.ends_with(", ... 13 more}"), "{}", rendered);
+        assert_eq!(rendered.matches(": true").count(), 8 - rendered.matches("self").count());
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{gc::Gc, runtime, OxList, OxString, OxVec, Value, Vm};
+
+/// buffer of an object being built, owned by the vm until it is finished.
+pub(crate) enum Scratch {
+    List(OxVec<Value>),
+    String(OxString),
+}
+
+impl Scratch {
+    /// the values the collector has to keep alive while the object is built.
+    pub(crate) fn values(&self) -> &[Value] {
+        match self {
+            Scratch::List(elements) => elements.as_slice(),
+            Scratch::String(_) => &[],
+        }
+    }
+
+    pub(crate) fn values_mut(&mut self) -> &mut [Value] {
+        match self {
+            Scratch::List(elements) => elements.as_mut_slice(),
+            Scratch::String(_) => &mut [],
+        }
+    }
+}
+
+/// a list being built by a native. The elements are written to a buffer of the vm that is kept
+/// alive by the collector, finishing the builder moves the buffer into the list without copying.
+/// A builder that is not finished is released when the native that created it returns, those
+/// created outside of a native are released with the vm.
+pub struct ListBuilder {
+    slot: usize,
+}
+
+impl ListBuilder {
+    pub fn push(&self, vm: &mut Vm, value: Value) -> Result<(), runtime::Error> {
+        self.reserve(vm, 1)?;
+        match vm.scratch_mut(self.slot) {
+            Scratch::List(elements) => elements.push(value),
+            Scratch::String(_) => unreachable!("list builder refers to a string"),
+        }
+        Ok(())
+    }
+
+    pub fn reserve(&self, vm: &mut Vm, additional: usize) -> Result<(), runtime::Error> {
+        vm.with_emergency_collection(|vm| match vm.scratch_mut(self.slot) {
+            Scratch::List(elements) => Ok(elements.try_reserve(additional)?),
+            Scratch::String(_) => unreachable!("list builder refers to a string"),
+        })
+    }
+
+    pub fn len(&self, vm: &Vm) -> usize {
+        vm.scratch(self.slot).values().len()
+    }
+
+    pub fn is_empty(&self, vm: &Vm) -> bool {
+        self.len(vm) == 0
+    }
+
+    pub fn finish(self, vm: &mut Vm) -> Result<Value, runtime::Error> {
+        // the list is allocated while the buffer is still rooted, allocating can collect.
+        let address = vm.allocate_from::<OxList>()?;
+        match vm.take_scratch(self.slot) {
+            Scratch::List(elements) => Ok(Value::from(Gc::with_value(
+                address,
+                OxList::new(elements),
+            ))),
+            Scratch::String(_) => unreachable!("list builder refers to a string"),
+        }
+    }
+}
+
+/// the string counterpart of `ListBuilder`.
+pub struct StringBuilder {
+    slot: usize,
+}
+
+impl StringBuilder {
+    pub fn push_str(&self, vm: &mut Vm, value: &str) -> Result<(), runtime::Error> {
+        vm.with_emergency_collection(|vm| match vm.scratch_mut(self.slot) {
+            Scratch::String(string) => string.push_bytes(value.as_bytes()),
+            Scratch::List(_) => unreachable!("string builder refers to a list"),
+        })
+    }
+
+    pub fn reserve(&self, vm: &mut Vm, additional: usize) -> Result<(), runtime::Error> {
+        vm.with_emergency_collection(|vm| match vm.scratch_mut(self.slot) {
+            Scratch::String(string) => string.reserve(additional),
+            Scratch::List(_) => unreachable!("string builder refers to a list"),
+        })
+    }
+
+    pub fn len(&self, vm: &Vm) -> usize {
+        match vm.scratch(self.slot) {
+            Scratch::String(string) => string.len(),
+            Scratch::List(_) => unreachable!("string builder refers to a list"),
+        }
+    }
+
+    pub fn is_empty(&self, vm: &Vm) -> bool {
+        self.len(vm) == 0
+    }
+
+    pub fn finish(self, vm: &mut Vm) -> Result<Value, runtime::Error> {
+        let address = vm.allocate_from::<OxString>()?;
+        match vm.take_scratch(self.slot) {
+            Scratch::String(string) => Ok(Value::from(Gc::with_value(address, string))),
+            Scratch::List(_) => unreachable!("string builder refers to a list"),
+        }
+    }
+}
+
+impl Vm {
+    /// starts a list with room for capacity elements.
+    pub fn new_list_builder(&mut self, capacity: usize) -> Result<ListBuilder, runtime::Error> {
+        let elements = self.with_emergency_collection(|vm| {
+            OxVec::try_with_capacity(vm.allocator_vec(), capacity)
+        })?;
+        Ok(ListBuilder {
+            slot: self.push_scratch(Scratch::List(elements)),
+        })
+    }
+
+    pub fn new_string_builder(&mut self) -> StringBuilder {
+        let string = OxString::new(self.allocator_vec());
+        StringBuilder {
+            slot: self.push_scratch(Scratch::String(string)),
+        }
+    }
+
+    /// the number of builders that are neither finished nor released.
+    pub fn live_builders(&self) -> usize {
+        self.scratch.iter().filter(|scratch| scratch.is_some()).count()
+    }
+
+    fn push_scratch(&mut self, scratch: Scratch) -> usize {
+        self.scratch.push(Some(scratch));
+        self.scratch.len() - 1
+    }
+
+    fn scratch(&self, slot: usize) -> &Scratch {
+        self.scratch
+            .get(slot)
+            .and_then(Option::as_ref)
+            .expect("builder used after it was finished or released")
+    }
+
+    fn scratch_mut(&mut self, slot: usize) -> &mut Scratch {
+        self.scratch
+            .get_mut(slot)
+            .and_then(Option::as_mut)
+            .expect("builder used after it was finished or released")
+    }
+
+    fn take_scratch(&mut self, slot: usize) -> Scratch {
+        let scratch = self
+            .scratch
+            .get_mut(slot)
+            .and_then(Option::take)
+            .expect("builder used after it was finished or released");
+        // the slots of finished builders at the end are not needed anymore.
+        while matches!(self.scratch.last(), Some(None)) {
+            self.scratch.pop();
+        }
+        scratch
+    }
+
+    /// releases the builders created since the table had mark slots.
+    pub(crate) fn release_scratch(&mut self, mark: usize) {
+        self.scratch.truncate(mark);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        runtime,
+        vm::{NativeResult, OpCode},
+        Value, Vm,
+    };
+
+    fn run_native(vm: &mut Vm, native: Value) -> Result<(), runtime::Error> {
+        let mut section = vm.new_section();
+        let global = section.add_global();
+        section.set_global(global as usize, native);
+        section.write_index(OpCode::LoadGlobal, global);
+        section.write_arg(OpCode::Call, 0);
+        section.write_op(OpCode::Return);
+
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("builders");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        vm.force_no_collection(false);
+        vm.run_module(module)
+    }
+
+    #[test]
+    fn test_collection_while_building_keeps_the_elements() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let split = vm.register_native(
+            "split",
+            0,
+            Box::new(|vm, _| {
+                let words = vm.new_list_builder(2)?;
+                for word in "the quick brown fox jumps over the lazy dog".split(' ') {
+                    let word = vm.new_gc_string_from_str(word);
+                    words.push(vm, Value::from(word))?;
+                    // the words are only reachable through the builder.
+                    vm.gc_collect();
+                }
+                let line = vm.new_string_builder();
+                for _ in 0..3 {
+                    line.push_str(vm, "ab")?;
+                    vm.gc_collect();
+                }
+                let line = line.finish(vm)?;
+                words.push(vm, line)?;
+                Ok(NativeResult::Return(words.finish(vm)?))
+            }),
+        );
+
+        run_native(&mut vm, split).unwrap();
+        let words = match vm.top() {
+            Value::List(list) => list
+                .elements()
+                .iter()
+                .map(|word| word.try_into_string().unwrap())
+                .collect::<Vec<_>>(),
+            value => panic!("unexpected result {:?}", value),
+        };
+        assert_eq!(
+            words,
+            vec!["the", "quick", "brown", "fox", "jumps", "over", "the", "lazy", "dog", "ababab"]
+        );
+        assert_eq!(vm.live_builders(), 0);
+    }
+
+    #[test]
+    fn test_reserved_builders_do_not_reallocate() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let list = vm.new_list_builder(1).unwrap();
+        list.reserve(&mut vm, 64).unwrap();
+        let line = vm.new_string_builder();
+        line.reserve(&mut vm, 128).unwrap();
+        let before = vm.heap_stats().buffer_reallocations;
+        for _ in 0..64 {
+            list.push(&mut vm, Value::I32(1)).unwrap();
+            line.push_str(&mut vm, "ab").unwrap();
+        }
+        assert_eq!(vm.heap_stats().buffer_reallocations, before);
+        assert_eq!(list.len(&vm), 64);
+        assert_eq!(line.finish(&mut vm).unwrap().try_into_string().unwrap(), "ab".repeat(64));
+    }
+
+    #[test]
+    fn test_abandoned_builders_are_released() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let failing = vm.register_native(
+            "failing",
+            0,
+            Box::new(|vm, _| {
+                let list = vm.new_list_builder(4)?;
+                list.push(vm, Value::I32(1))?;
+                let _string = vm.new_string_builder();
+                Err(runtime::Error::CallingInvalidValue("builder".to_string()))
+            }),
+        );
+
+        assert!(run_native(&mut vm, failing).is_err());
+        assert_eq!(vm.live_builders(), 0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_finishing_a_list_does_not_copy_the_elements() {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let list = vm.new_list_builder(16).unwrap();
+        for i in 0..16 {
+            list.push(&mut vm, Value::I32(i)).unwrap();
+        }
+        let before = vm.allocator().records().len();
+        let list = list.finish(&mut vm).unwrap();
+        // only the list object itself is allocated.
+        assert_eq!(vm.allocator().records().len(), before + 1);
+        match list {
+            Value::List(list) => assert_eq!(list.capacity(), 16),
+            value => panic!("unexpected result {:?}", value),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+/// how much of the vm state is captured when a runtime error occurs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCaptureLevel {
+    None,
+    FramesOnly,
+    FramesAndLocals,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameSnapshot {
+    pub function: String,
+    pub line: Option<usize>,
+    /// name and rendered value of the locals that are live in the frame.
+    pub locals: Vec<(String, String)>,
+}
+
+/// state of the call stack at the moment a runtime error occurred, inner most frame first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorSnapshot {
+    pub error: String,
+    pub frames: Vec<FrameSnapshot>,
+}
+
+impl FrameSnapshot {
+    pub fn local(&self, name: &str) -> Option<&str> {
+        self.locals
+            .iter()
+            .find(|(local, _)| local == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    any::Any,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    compiled::{CompiledModule, Constant},
+    gc::Gc,
+    runtime, OxModule, OxUserData, Value,
+};
+
+use super::{Capabilities, Capability, LogLevel, Vm, DEFAULT_HOST, DEFAULT_MAX_FRAMES};
+
+/// the function a task runs, found by its place in the module so every vm loading the module
+/// finds the same one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskEntry {
+    Function(usize),
+    /// method of the structure at the given object index.
+    Method(usize, usize),
+}
+
+impl TaskEntry {
+    /// where function is found in module.
+    pub fn of(module: Gc<OxModule>, function: &Value) -> Option<Self> {
+        let function = match function {
+            Value::Function(function) => function,
+            _ => return None,
+        };
+        module
+            .objects()
+            .iter()
+            .enumerate()
+            .find_map(|(idx, object)| match object {
+                Value::Function(found) if found.ptr() == function.ptr() => {
+                    Some(Self::Function(idx))
+                }
+                Value::Struct(structure) => structure
+                    .methods()
+                    .iter()
+                    .position(|method| method.ptr() == function.ptr())
+                    .map(|method| Self::Method(idx, method)),
+                _ => None,
+            })
+    }
+
+    fn function(&self, module: Gc<OxModule>) -> Value {
+        match (self, module.objects()[self.object()]) {
+            (Self::Function(_), function) => function,
+            (Self::Method(_, method), Value::Struct(structure)) => {
+                Value::from(structure.methods()[*method])
+            }
+            (_, object) => panic!("a task entry refers to a {}", object.ty()),
+        }
+    }
+
+    fn object(&self) -> usize {
+        match self {
+            Self::Function(idx) | Self::Method(idx, _) => *idx,
+        }
+    }
+}
+
+/// what a task inherits from the vm that spawned it.
+#[derive(Debug, Clone)]
+struct TaskSettings {
+    overflow_traps: bool,
+    max_frames: usize,
+    log_level: LogLevel,
+    capabilities: Capabilities,
+    host: String,
+}
+
+impl Default for TaskSettings {
+    fn default() -> Self {
+        Self {
+            overflow_traps: false,
+            max_frames: DEFAULT_MAX_FRAMES,
+            log_level: LogLevel::Info,
+            capabilities: Capabilities::none(),
+            host: DEFAULT_HOST.to_string(),
+        }
+    }
+}
+
+/// a function running on a thread of its own in a fresh vm. The vms only share the immutable
+/// compiled module, the actual parameters and the result are copied between their heaps so
+/// only what can be sent over a channel can cross.
+#[derive(Debug)]
+pub struct Task {
+    thread: JoinHandle<Result<Constant, String>>,
+}
+
+impl Task {
+    /// starts running the entry of module with the actual parameters, the task is granted no
+    /// capabilities.
+    pub fn spawn(
+        module: Arc<CompiledModule>,
+        entry: TaskEntry,
+        actuals: &[Value],
+    ) -> Result<Self, runtime::Error> {
+        Self::spawn_with(module, entry, actuals, TaskSettings::default())
+    }
+
+    fn spawn_with(
+        module: Arc<CompiledModule>,
+        entry: TaskEntry,
+        actuals: &[Value],
+        settings: TaskSettings,
+    ) -> Result<Self, runtime::Error> {
+        let actuals = actuals
+            .iter()
+            .map(copy_out)
+            .collect::<Result<Vec<_>, _>>()?;
+        let thread = thread::Builder::new()
+            .name(format!("task of {}", module.name()))
+            .spawn(move || {
+                let mut vm = Vm::new();
+                vm.set_overflow_traps(settings.overflow_traps);
+                vm.set_max_frames(settings.max_frames);
+                vm.set_log_level(settings.log_level);
+                vm.set_capabilities(settings.capabilities);
+                vm.set_host(settings.host.as_str());
+                vm.run_task(module, entry, &actuals)
+            })
+            .expect("failed to start the thread of a task");
+        Ok(Self { thread })
+    }
+
+    /// waits for the task to finish. The error a task failed with, or the message it panicked
+    /// with, is returned instead of its result.
+    pub fn join(self) -> Result<Constant, String> {
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| Err(panic_message(panic)))
+    }
+}
+
+fn copy_out(value: &Value) -> Result<Constant, runtime::Error> {
+    Constant::from_value(value).map_err(|_| runtime::Error::unsendable_task_value(value.ty()))
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    let message = match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown cause".to_string(),
+        },
+    };
+    format!("the task panicked: {}", message)
+}
+
+impl Vm {
+    /// shares module with the tasks spawned by scripts instead of compiling the running module
+    /// again on the first spawn.
+    pub fn set_task_module(&mut self, module: Arc<CompiledModule>) {
+        self.task_module = Some(module);
+    }
+
+    /// runs function, a function of the running module, as a task. The returned handle is a
+    /// userdata object, a task that is no longer referenced keeps running detached. The task is
+    /// granted the capabilities of the vm.
+    pub fn spawn_task(
+        &mut self,
+        function: &Value,
+        actuals: &[Value],
+    ) -> Result<Value, runtime::Error> {
+        self.require(Capability::Spawn)?;
+        let entry = self
+            .running
+            .and_then(|module| TaskEntry::of(module, function))
+            .ok_or_else(|| runtime::Error::invalid_task_function(function.ty()))?;
+        let module = match self.task_module.as_ref() {
+            Some(module) => module.clone(),
+            None => {
+                let module = Arc::new(CompiledModule::from_module(self.running.unwrap())?);
+                self.task_module = Some(module.clone());
+                module
+            }
+        };
+
+        let settings = TaskSettings {
+            overflow_traps: self.overflow_traps,
+            max_frames: self.max_frames,
+            log_level: self.log_level,
+            capabilities: self.capabilities.clone(),
+            host: self.host.clone(),
+        };
+        let task = Task::spawn_with(module, entry, actuals, settings)?;
+        self.tasks.push(Some(task));
+        let type_id = self.task_type();
+        let handle = self.new_userdata(type_id, (self.tasks.len() - 1) as u64)?;
+        Ok(Value::from(handle))
+    }
+
+    /// waits for the task behind handle and copies its result onto the heap.
+    pub fn join_task(&mut self, handle: &Value) -> Result<Value, String> {
+        let task = match handle {
+            Value::UserData(data) if Some(OxUserData::type_id(data)) == self.task_type => {
+                self.tasks[data.handle() as usize].take()
+            }
+            _ => return Err(format!("a {} is not a task", handle.ty())),
+        };
+        let result = task
+            .ok_or_else(|| "the task was already joined".to_string())?
+            .join()?;
+        Ok(result.to_value(self))
+    }
+
+    fn task_type(&mut self) -> usize {
+        match self.task_type {
+            Some(type_id) => type_id,
+            None => {
+                // dropping the task detaches its thread.
+                let finalizer = Box::new(|vm: &mut Vm, handle: Gc<OxUserData>| {
+                    vm.tasks[handle.handle() as usize] = None;
+                    Ok(())
+                });
+                let type_id = self.register_userdata_type_with_finalizer("task", finalizer);
+                self.task_type = Some(type_id);
+                type_id
+            }
+        }
+    }
+
+    /// the body of the thread of a task.
+    fn run_task(
+        &mut self,
+        module: Arc<CompiledModule>,
+        entry: TaskEntry,
+        actuals: &[Constant],
+    ) -> Result<Constant, String> {
+        let loaded = module.load(self);
+        self.task_module = Some(module);
+        self.running = Some(loaded);
+
+        let function = entry.function(loaded);
+        let arity = match function {
+            Value::Function(function) => function.arity(),
+            _ => return Err(runtime::Error::invalid_task_function(function.ty()).to_string()),
+        };
+        if arity as usize != actuals.len() {
+            return Err(format!(
+                "the task is given {} parameters, its function takes {}",
+                actuals.len(),
+                arity
+            ));
+        }
+
+        // the module is on the stack before anything else is allocated.
+        self.push_stack(Value::from(loaded));
+        self.push_stack(function);
+        for actual in actuals {
+            let value = actual.to_value(self);
+            self.push_stack(value);
+        }
+        self.call_value(&function, arity)
+            .and_then(|_| self.run())
+            .map_err(|err| err.to_string())?;
+        copy_out(self.top()).map_err(|err| err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Task, TaskEntry};
+    use crate::{compiled::Constant, gc::Gc, vm::OpCode, CompiledModule, OxModule, Value, Vm};
+    use std::sync::Arc;
+
+    // a module whose first object is `fn(a, b) = a / b`.
+    fn divide(vm: &mut Vm) -> Gc<OxModule> {
+        vm.force_no_collection(true);
+        let mut section = vm.new_section();
+        section.write_arg(OpCode::LoadLocal, 0);
+        section.write_arg(OpCode::LoadLocal, 1);
+        section.write_op(OpCode::DivI32);
+        section.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("divide");
+        let function = vm.new_function(name, 2, section);
+        let name = vm.new_string_from_str("script");
+        let mut module = vm.new_empty_module(name);
+        module.as_ref_mut().add_object(Value::from(function));
+        vm.force_no_collection(false);
+        module
+    }
+
+    #[test]
+    fn test_tasks_run_in_parallel_vms() {
+        let mut vm = Vm::new();
+        let module = divide(&mut vm);
+        let function = module.objects()[0];
+        let entry = TaskEntry::of(module, &function).unwrap();
+        assert_eq!(entry, TaskEntry::Function(0));
+        let compiled = Arc::new(CompiledModule::from_module(module).unwrap());
+
+        let tasks = (0..4)
+            .map(|i| {
+                let actuals = [Value::I32(100 * i), Value::I32(i - 1)];
+                Task::spawn(compiled.clone(), entry, &actuals).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let results = tasks.into_iter().map(Task::join).collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            vec![
+                Ok(Constant::I32(0)),
+                Err("integer division by zero".to_string()),
+                Ok(Constant::I32(200)),
+                Ok(Constant::I32(150)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spawn_rejects_heap_objects() {
+        let mut vm = Vm::new();
+        let module = divide(&mut vm);
+        let compiled = Arc::new(CompiledModule::from_module(module).unwrap());
+        let name = vm.new_gc_string_from_str("Point");
+        let fields = vm.new_vec();
+        let instance = vm.new_instance(name, fields).unwrap();
+
+        let actuals = [Value::from(instance), Value::I32(1)];
+        let err = Task::spawn(compiled.clone(), TaskEntry::Function(0), &actuals).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a value of type instance cannot be passed to or returned from a task"
+        );
+        let err = Task::spawn(compiled, TaskEntry::Function(0), &[Value::I32(1)])
+            .unwrap()
+            .join()
+            .unwrap_err();
+        assert_eq!(err, "the task is given 1 parameters, its function takes 2");
+    }
+}
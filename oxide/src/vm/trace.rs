@@ -0,0 +1,130 @@
+use std::io::Write;
+
+use crate::{
+    disassembler::Disassembler,
+    vm::{Instruction, OpCode},
+    Vm,
+};
+
+/// how many slots from the top of the stack a traced instruction shows.
+const TRACED_SLOTS: usize = 3;
+
+/// where each instruction is written before it executes while tracing is enabled.
+pub(crate) struct Trace {
+    sink: Box<dyn Write>,
+}
+
+impl Vm {
+    /// writes every instruction to sink before it executes, with the function running it and
+    /// the top of the stack. None stops tracing, the loop only checks whether it is set.
+    pub fn set_trace(&mut self, sink: Option<Box<dyn Write>>) {
+        self.trace = sink.map(|sink| Trace { sink });
+    }
+
+    /// a line for the instruction at the instruction pointer of the top frame, the depth of the
+    /// frame, the name of its function, the instruction and the top slots of the stack with the
+    /// top last. A failed write is ignored, the trace is not the output of the script.
+    #[cold]
+    #[inline(never)]
+    pub(super) fn trace_instruction(&mut self) {
+        let depth = self.top_frame;
+        let frame = &self.call_stack[depth.saturating_sub(1)];
+        let section = frame.function.section();
+        let instruction = match Disassembler::decode(section, frame.ip) {
+            (Some(instruction), _) => instruction,
+            // a label is not listed by the disassembler, it is still executed.
+            (None, _) => Instruction::simple(frame.ip, OpCode::Label),
+        };
+        let first = self.top_stack.saturating_sub(TRACED_SLOTS);
+        let mut slots = self.stack[first..self.top_stack]
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        if first != 0 {
+            slots.insert(0, "..".to_owned());
+        }
+        let line = format!(
+            "{:>3} {:<12} {:<32} [{}]\n",
+            depth,
+            frame.function.name().as_str(),
+            instruction.to_string(),
+            slots.join(", ")
+        );
+        if let Some(trace) = self.trace.as_mut() {
+            let _ = trace.sink.write_all(line.as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io, rc::Rc};
+
+    use crate::{vm::OpCode, Value, Vm};
+
+    /// keeps what is written so the test can read it after the vm is done with it.
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// runs `1 + 2 + 3` with the trace written to a buffer when trace is set.
+    fn run(trace: bool) -> (Value, String) {
+        let mut vm = Vm::new();
+        vm.force_no_collection(true);
+        let written = Rc::new(RefCell::new(vec![]));
+        if trace {
+            vm.set_trace(Some(Box::new(Shared(written.clone()))));
+        }
+
+        let mut section = vm.new_section();
+        for value in 1..=3 {
+            let index = section.intern_constant(Value::I64(value));
+            section.write_index(OpCode::LoadI64, index);
+        }
+        section.write_op(OpCode::AddI64);
+        section.write_op(OpCode::AddI64);
+        section.write_op(OpCode::Return);
+        let name = vm.new_string_from_str("main");
+        let main = vm.new_function(name, 0, section);
+        let name = vm.new_string_from_str("trace");
+        let mut module = vm.new_empty_module(name);
+        let entry = module.as_ref_mut().add_object(Value::from(main));
+        module.as_ref_mut().set_entry(entry);
+        vm.run_module(module).unwrap();
+
+        let written = String::from_utf8(written.borrow().clone()).unwrap();
+        (*vm.top(), written)
+    }
+
+    #[test]
+    fn test_trace_instructions() {
+        let (result, written) = run(true);
+        assert!(matches!(result, Value::I64(6)));
+        // the function being run and the module are below the values of main.
+        assert_eq!(
+            written,
+            "  1 main         0000000000 load_i64 0 (1)        [<module trace>, <fn main>]\n\
+            \x20 1 main         0000000002 load_i64 1 (2)        [<module trace>, <fn main>, 1]\n\
+            \x20 1 main         0000000004 load_i64 2 (3)        [.., <fn main>, 1, 2]\n\
+            \x20 1 main         0000000006 add_i64               [.., 1, 2, 3]\n\
+            \x20 1 main         0000000007 add_i64               [.., <fn main>, 1, 5]\n\
+            \x20 1 main         0000000008 return                [<module trace>, <fn main>, 6]\n"
+        );
+    }
+
+    #[test]
+    fn test_trace_disabled_writes_nothing() {
+        let (result, written) = run(false);
+        assert!(matches!(result, Value::I64(6)));
+        assert!(written.is_empty());
+    }
+}
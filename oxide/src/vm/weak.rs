@@ -0,0 +1,92 @@
+use crate::Value;
+use std::fmt::{Display, Formatter};
+
+/// a weak reference to a heap object, it names a slot of the vm's weak reference table.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct WeakHandle {
+    index: u32,
+    /// the generation of the slot when the handle was created, the slot is reused once its
+    /// referent is collected and the handles to the old referent must keep reading none.
+    generation: u32,
+}
+
+impl Display for WeakHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.index)
+    }
+}
+
+struct WeakSlot {
+    referent: Option<Value>,
+    generation: u32,
+}
+
+/// the referents of the weak handles of a vm. The slot of a referent that was collected is
+/// put on a free list, the table only grows with the number of referents alive at once.
+#[derive(Default)]
+pub(crate) struct WeakRefs {
+    slots: Vec<WeakSlot>,
+    free: Vec<usize>,
+}
+
+impl WeakRefs {
+    pub(crate) fn insert(&mut self, referent: Value) -> WeakHandle {
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index].referent = Some(referent);
+                index
+            }
+            None => {
+                self.slots.push(WeakSlot {
+                    referent: Some(referent),
+                    generation: 0,
+                });
+                self.slots.len() - 1
+            }
+        };
+        WeakHandle {
+            index: index as u32,
+            generation: self.slots[index].generation,
+        }
+    }
+
+    pub(crate) fn get(&self, handle: WeakHandle) -> Option<Value> {
+        self.slots
+            .get(handle.index as usize)
+            .filter(|slot| slot.generation == handle.generation)
+            .and_then(|slot| slot.referent)
+    }
+
+    /// clears the slots whose referent was not reached while marking and frees them. This has
+    /// to run before the sweep, it resets the marks on the live objects.
+    pub(crate) fn clear_unreachable(&mut self) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let unreachable = slot
+                .referent
+                .and_then(|value| value.addr())
+                .is_some_and(|address| !address.cell().marked);
+            if unreachable {
+                slot.referent = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index);
+            }
+        }
+    }
+
+    /// the referents still alive, they are redirected when the object they refer to moves.
+    pub(crate) fn referents_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.slots
+            .iter_mut()
+            .filter_map(|slot| slot.referent.as_mut())
+    }
+
+    /// the number of slots, live or free.
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.clear();
+        self.free.clear();
+    }
+}